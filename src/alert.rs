@@ -0,0 +1,537 @@
+//! Alerts raised by the monitors, and where they are sent.
+//!
+//! Monitors such as [`MissingPetMonitor`](../missing/struct.MissingPetMonitor.html)
+//! watch the state for things worth telling someone about and raise an
+//! [`Alert`](enum.Alert.html) for each. An [`AlertSink`](trait.AlertSink.html)
+//! is anything alerts can be sent to, such as a push service, a chat
+//! channel or a log. [`LogSink`](struct.LogSink.html) writes them out as
+//! lines of text, and with the `timeline` and `mqtt` features a
+//! [`WebhookSink`](../webhook/struct.WebhookSink.html) posts them and a
+//! [`Bridge`](../mqtt/struct.Bridge.html) publishes them as JSON.
+//!
+//! Alerts serialize as an object with the variant name in `kind`, such as
+//! `{"kind":"low_battery","device_id":10,"name":"Back door","percent":17.0}`.
+//!
+//! [`QuietHours`](struct.QuietHours.html) sits in front of a sink and holds
+//! alerts that can wait, such as a low battery, until the morning, while
+//! [critical](enum.Alert.html#method.is_critical) ones go straight
+//! through.
+use crate::clock::{Clock, SystemClock};
+use crate::error::{Error, Result};
+use crate::id::{DeviceId, PetId};
+use crate::time::{TimeOfDay, Timestamp};
+use crate::zone::Zone;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Something a person should know about.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Alert {
+    /// A pet has been outside for longer than its threshold.
+    PetMissing {
+        pet_id: PetId,
+        name: String,
+        out_since: Timestamp,
+        /// Seconds outside so far.
+        outside: i64,
+    },
+    /// A device's battery is running low.
+    LowBattery {
+        device_id: DeviceId,
+        name: String,
+        /// The estimated charge.
+        percent: f64,
+    },
+    /// A feeder bowl is running out of food.
+    LowFood {
+        device_id: DeviceId,
+        name: String,
+        /// The bowl index, `0` being the left hand bowl.
+        bowl: u8,
+        /// Grams left.
+        remaining: f64,
+        /// When it will be empty at the rate it has been eaten, if known.
+        empty_at: Option<Timestamp>,
+    },
+    /// A device has been offline for longer than the grace period.
+    DeviceOffline {
+        device_id: DeviceId,
+        name: String,
+        /// When the cloud last heard from it, if known.
+        last_seen: Option<Timestamp>,
+    },
+    /// A device alerted about as offline is back.
+    DeviceOnline {
+        device_id: DeviceId,
+        name: String,
+        /// Seconds it was down for.
+        offline: i64,
+    },
+    /// A water dispenser is running dry.
+    LowWater {
+        device_id: DeviceId,
+        name: String,
+        /// Millilitres left.
+        remaining: f64,
+    },
+    /// A water dispenser has not been refilled for too long.
+    StaleWater {
+        device_id: DeviceId,
+        name: String,
+        refilled_at: Timestamp,
+        /// Seconds since the refill.
+        age: i64,
+    },
+    /// An animal without a registered tag tried a flap.
+    Intruder {
+        device_id: DeviceId,
+        name: String,
+        at: Timestamp,
+    },
+    /// Animals without a registered tag tried a flap more often than the
+    /// threshold, see [`IntrusionMonitor`](../intruder/struct.IntrusionMonitor.html).
+    Intrusions {
+        device_id: DeviceId,
+        name: String,
+        /// Attempts within the window.
+        count: usize,
+        /// The window, in seconds.
+        window: i64,
+    },
+}
+
+impl Alert {
+    /// The pet the alert is about, if any.
+    pub fn pet_id(&self) -> Option<PetId> {
+        match self {
+            Alert::PetMissing { pet_id, .. } => Some(*pet_id),
+            _ => None,
+        }
+    }
+
+    /// The device the alert is about, if any.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        match self {
+            Alert::PetMissing { .. } => None,
+            Alert::LowBattery { device_id, .. }
+            | Alert::LowFood { device_id, .. }
+            | Alert::DeviceOffline { device_id, .. }
+            | Alert::DeviceOnline { device_id, .. }
+            | Alert::LowWater { device_id, .. }
+            | Alert::StaleWater { device_id, .. }
+            | Alert::Intruder { device_id, .. }
+            | Alert::Intrusions { device_id, .. } => Some(*device_id),
+        }
+    }
+
+    /// Whether the alert needs attention straight away: a missing pet, an
+    /// intruder or a device gone offline. The rest can wait for the
+    /// morning.
+    pub fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            Alert::PetMissing { .. }
+                | Alert::Intruder { .. }
+                | Alert::Intrusions { .. }
+                | Alert::DeviceOffline { .. }
+        )
+    }
+}
+
+/// The JSON sinks send: the serialized alert with its text in `message`.
+#[cfg(any(feature = "timeline", feature = "mqtt"))]
+pub(crate) fn payload(alert: &Alert) -> Result<Vec<u8>> {
+    let mut value = serde_json::to_value(alert)?;
+    if let serde_json::Value::Object(fields) = &mut value {
+        fields.insert("message".into(), alert.to_string().into());
+    }
+    Ok(serde_json::to_vec(&value)?)
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::PetMissing { name, outside, .. } => {
+                write!(
+                    f,
+                    "{} has been outside for {}",
+                    name,
+                    HoursMinutes(*outside)
+                )
+            }
+            Alert::LowBattery { name, percent, .. } => {
+                write!(f, "{} battery is at {:.0}%, replace it soon", name, percent)
+            }
+            Alert::LowFood {
+                name,
+                bowl,
+                remaining,
+                ..
+            } => write!(
+                f,
+                "{} bowl {} is down to {:.0}g, refill soon",
+                name,
+                bowl + 1,
+                remaining
+            ),
+            Alert::DeviceOffline { name, .. } => write!(f, "{} is offline", name),
+            Alert::DeviceOnline { name, offline, .. } => {
+                write!(
+                    f,
+                    "{} is back online after {}",
+                    name,
+                    HoursMinutes(*offline)
+                )
+            }
+            Alert::LowWater {
+                name, remaining, ..
+            } => write!(f, "{} is down to {:.0}ml, top it up", name, remaining),
+            Alert::StaleWater { name, age, .. } => {
+                let days = age / 86_400;
+                let unit = if days == 1 { "day" } else { "days" };
+                write!(f, "{} has not been changed for {} {}", name, days, unit)
+            }
+            Alert::Intruder { name, .. } => write!(f, "An unknown animal tried {}", name),
+            Alert::Intrusions {
+                name,
+                count,
+                window,
+                ..
+            } => write!(
+                f,
+                "Unknown animals tried {} {} times in {}",
+                name,
+                count,
+                HoursMinutes(*window)
+            ),
+        }
+    }
+}
+
+/// Formats seconds as `7h 05m`.
+struct HoursMinutes(i64);
+
+impl fmt::Display for HoursMinutes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}h {:02}m", self.0 / 3600, self.0 % 3600 / 60)
+    }
+}
+
+/// Delivers alerts.
+pub trait AlertSink {
+    /// Sends an alert. An error means it was not delivered, and the
+    /// monitor offers it again on its next check.
+    fn raise(&self, alert: &Alert) -> Result<()>;
+
+    /// Sends any alerts the sink has been holding back that are now due.
+    /// A [`Watcher`](../watcher/struct.Watcher.html) calls it on every
+    /// poll. Sinks that send everything straight away do nothing.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: AlertSink + ?Sized> AlertSink for &S {
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        (**self).raise(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<S: AlertSink + ?Sized> AlertSink for Box<S> {
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        (**self).raise(alert)
+    }
+
+    fn flush(&self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Holds alerts that are not critical back during quiet hours, see the
+/// module documentation. Held alerts are sent, oldest first, by the first
+/// [`raise`](trait.AlertSink.html#tymethod.raise) or
+/// [`flush`](trait.AlertSink.html#method.flush) after the quiet hours end.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::alert::{Alert, AlertSink, LogSink, QuietHours};
+/// use sure_petcare::clock::ManualClock;
+/// use sure_petcare::id::DeviceId;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// let clock = Arc::new(ManualClock::new("2019-06-01T23:30:00Z".parse().unwrap()));
+/// let log = LogSink::new(Vec::new());
+/// let quiet = QuietHours::new(&log, "22:00".parse().unwrap(), "07:00".parse().unwrap())
+///     .with_clock(Arc::clone(&clock));
+/// quiet.raise(&Alert::LowWater { device_id: DeviceId::new(12), name: "Fountain".into(), remaining: 150.0 })?;
+/// assert_eq!(1, quiet.deferred().len());
+/// clock.advance(Duration::from_secs(8 * 3600));
+/// quiet.flush()?;
+/// assert!(quiet.deferred().is_empty());
+/// # drop(quiet);
+/// assert_eq!(b"Fountain is down to 150ml, top it up\n", &log.into_inner()[..]);
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+pub struct QuietHours<S> {
+    sink: S,
+    from: TimeOfDay,
+    to: TimeOfDay,
+    zone: Zone,
+    clock: Arc<dyn Clock>,
+    deferred: Mutex<Vec<Alert>>,
+}
+
+impl<S: AlertSink> QuietHours<S> {
+    /// Holds alerts for `sink` back from `from` until `to` every day, UTC
+    /// by default. A period ending before it starts runs over midnight.
+    pub fn new(sink: S, from: TimeOfDay, to: TimeOfDay) -> Self {
+        QuietHours {
+            sink,
+            from,
+            to,
+            zone: Zone::utc(),
+            clock: Arc::new(SystemClock),
+            deferred: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sets the zone the quiet hours are in.
+    pub fn with_zone(mut self, zone: Zone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// Sets the clock deciding whether it is quiet.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Whether alerts that can wait are held back at an instant.
+    pub fn is_quiet_at(&self, instant: Timestamp) -> bool {
+        let (_, time) = self.zone.local(instant);
+        time.is_within(self.from, self.to)
+    }
+
+    /// The alerts being held back.
+    pub fn deferred(&self) -> Vec<Alert> {
+        self.held().clone()
+    }
+
+    fn held(&self) -> MutexGuard<'_, Vec<Alert>> {
+        self.deferred
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S: AlertSink> AlertSink for QuietHours<S> {
+    /// Sends the alert, or holds it back if it can wait. Outside quiet hours
+    /// the held alerts are sent first; any the sink fails to deliver stay
+    /// held, and the next flush reports the failure.
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        if alert.is_critical() {
+            return self.sink.raise(alert);
+        }
+        if self.is_quiet_at(self.clock.now()) {
+            self.held().push(alert.clone());
+            return Ok(());
+        }
+        let _ = self.flush();
+        self.sink.raise(alert)
+    }
+
+    /// Sends the held alerts once the quiet hours are over. Alerts the sink
+    /// fails to deliver are held for the next flush, and the first failure
+    /// is returned once the rest have been sent.
+    fn flush(&self) -> Result<()> {
+        if self.is_quiet_at(self.clock.now()) {
+            return self.sink.flush();
+        }
+        let held = mem::take(&mut *self.held());
+        let mut failure = None;
+        let mut undelivered = Vec::new();
+        for alert in held {
+            if let Err(error) = self.sink.raise(&alert) {
+                failure = failure.or(Some(error));
+                undelivered.push(alert);
+            }
+        }
+        let mut deferred = self.held();
+        undelivered.append(&mut deferred);
+        *deferred = undelivered;
+        drop(deferred);
+        failure.map_or_else(|| self.sink.flush(), Err)
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for QuietHours<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuietHours")
+            .field("sink", &self.sink)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("zone", &self.zone)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes each alert as a line of text.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::alert::{Alert, AlertSink, LogSink};
+/// use sure_petcare::id::DeviceId;
+/// let sink = LogSink::new(Vec::new());
+/// sink.raise(&Alert::DeviceOffline {
+///     device_id: DeviceId::new(10),
+///     name: "Back door".into(),
+///     last_seen: None,
+/// })?;
+/// assert_eq!(b"Back door is offline\n", &sink.into_inner()[..]);
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct LogSink<W> {
+    writer: Mutex<W>,
+}
+
+impl LogSink<io::Stdout> {
+    /// A sink printing to standard output.
+    pub fn stdout() -> Self {
+        LogSink::new(io::stdout())
+    }
+}
+
+impl LogSink<io::Stderr> {
+    /// A sink printing to standard error.
+    pub fn stderr() -> Self {
+        LogSink::new(io::stderr())
+    }
+}
+
+impl<W: Write> LogSink<W> {
+    /// A sink writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        LogSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// The writer, once the sink is no longer needed.
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<W: Write> AlertSink for LogSink<W> {
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        writeln!(writer, "{}", alert)
+            .and_then(|()| writer.flush())
+            .map_err(Error::transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[cfg(any(feature = "timeline", feature = "mqtt"))]
+    #[test]
+    fn should_serialize_alerts_with_their_kind_and_message() {
+        let alert = Alert::LowBattery {
+            device_id: DeviceId::new(10),
+            name: "Back door".into(),
+            percent: 17.0,
+        };
+
+        let payload: serde_json::Value = serde_json::from_slice(&payload(&alert).unwrap()).unwrap();
+
+        assert_eq!(
+            serde_json::json!({
+                "kind": "low_battery",
+                "device_id": 10,
+                "name": "Back door",
+                "percent": 17.0,
+                "message": "Back door battery is at 17%, replace it soon",
+            }),
+            payload
+        );
+        assert_eq!(Some(DeviceId::new(10)), alert.device_id());
+        assert_eq!(None, alert.pet_id());
+    }
+
+    struct Outbox {
+        sent: Mutex<Vec<String>>,
+        down: Mutex<bool>,
+    }
+
+    impl AlertSink for Outbox {
+        fn raise(&self, alert: &Alert) -> Result<()> {
+            if *self.down.lock().unwrap() {
+                return Err(Error::transport("push service down"));
+            }
+            self.sent.lock().unwrap().push(alert.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_hold_alerts_that_can_wait_until_quiet_hours_end() {
+        use crate::clock::ManualClock;
+        use std::time::Duration;
+
+        let clock = Arc::new(ManualClock::new("2019-06-01T21:30:00Z".parse().unwrap()));
+        let outbox = Outbox {
+            sent: Mutex::new(Vec::new()),
+            down: Mutex::new(false),
+        };
+        let quiet = QuietHours::new(&outbox, "22:00".parse().unwrap(), "07:00".parse().unwrap())
+            .with_zone("CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap())
+            .with_clock(Arc::clone(&clock));
+        let battery = Alert::LowBattery {
+            device_id: DeviceId::new(10),
+            name: "Back door".into(),
+            percent: 17.0,
+        };
+        let hub = Alert::DeviceOffline {
+            device_id: DeviceId::new(1),
+            name: "Hub".into(),
+            last_seen: None,
+        };
+
+        // 23:30 in Paris.
+        quiet.raise(&battery).unwrap();
+        quiet.raise(&hub).unwrap();
+        quiet.flush().unwrap();
+        assert_eq!(
+            vec!["Hub is offline".to_string()],
+            *outbox.sent.lock().unwrap()
+        );
+        assert_eq!(vec![battery.clone()], quiet.deferred());
+
+        clock.advance(Duration::from_secs(8 * 3600));
+        *outbox.down.lock().unwrap() = true;
+        assert!(quiet.flush().is_err());
+        assert_eq!(1, quiet.deferred().len());
+        *outbox.down.lock().unwrap() = false;
+        quiet.flush().unwrap();
+        assert!(quiet.deferred().is_empty());
+        assert_eq!(2, outbox.sent.lock().unwrap().len());
+    }
+}
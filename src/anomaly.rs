@@ -0,0 +1,291 @@
+//! Detection of missed meals, long absences and days without drinking.
+//!
+//! The rules are deliberately simple: each compares recent events against a
+//! [`Baseline`](struct.Baseline.html) of what is normal for the pet.
+use crate::id::PetId;
+use crate::report::DailyOutside;
+use crate::stats::Stats;
+use crate::time::{Date, DateRange, Timestamp};
+use crate::timeline::{Direction, Event, EventKind, PET_VISIT};
+use serde::Serialize;
+
+/// Something unusual about a pet.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Anomaly {
+    /// The pet has not eaten for longer than the configured number of hours.
+    MissedMeal {
+        pet_id: PetId,
+        /// The last meal seen, if any.
+        last_meal: Option<Timestamp>,
+    },
+    /// The pet has been outside for longer than usual.
+    ExtendedAbsence {
+        pet_id: PetId,
+        out_since: Timestamp,
+        /// Seconds outside so far.
+        outside: i64,
+        /// The usual longest trip in seconds.
+        usual: i64,
+    },
+    /// A pet that normally drinks every day has not drunk today.
+    NoDrinking { pet_id: PetId, date: Date },
+}
+
+/// What is normal for a pet.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Baseline {
+    usual_trip: Option<i64>,
+    drinks_per_day: f64,
+}
+
+impl Baseline {
+    /// Creates a baseline from the usual longest trip outside in seconds and
+    /// the usual number of drinks per day.
+    pub fn new(usual_trip: Option<i64>, drinks_per_day: f64) -> Self {
+        Baseline {
+            usual_trip,
+            drinks_per_day,
+        }
+    }
+
+    /// Works the baseline out from a history of events for the pet: the
+    /// usual trip is the average of each day's longest trip in `range`.
+    pub fn from_history(events: &[Event<'_>], pet_id: PetId, range: DateRange) -> Self {
+        let outside = DailyOutside::from_events(events, pet_id, range, range.end());
+        let longest: Vec<i64> = outside
+            .iter()
+            .map(DailyOutside::longest)
+            .filter(|seconds| *seconds > 0)
+            .collect();
+        let usual_trip = if longest.is_empty() {
+            None
+        } else {
+            Some(longest.iter().sum::<i64>() / longest.len() as i64)
+        };
+
+        let own: Vec<Event<'_>> = events
+            .iter()
+            .filter(|event| event.involves_pet(pet_id))
+            .cloned()
+            .collect();
+        Baseline {
+            usual_trip,
+            drinks_per_day: Stats::from_events(&own).drinks_per_day(),
+        }
+    }
+
+    /// The usual longest trip outside, in seconds.
+    pub fn usual_trip(&self) -> Option<i64> {
+        self.usual_trip
+    }
+
+    /// The usual number of drinks per day.
+    pub fn drinks_per_day(&self) -> f64 {
+        self.drinks_per_day
+    }
+}
+
+/// Flags anomalies in a pet's recent events.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::anomaly::{AnomalyDetectorBuilder, Baseline};
+/// use sure_petcare::id::PetId;
+/// let detector = AnomalyDetectorBuilder::new()
+///     .with_missed_meal_hours(10)
+///     .with_absence_factor(2.0)
+///     .build();
+/// let now = "2019-06-01T12:00:00Z".parse().unwrap();
+/// let anomalies = detector.detect(PetId::new(1), &[], &Baseline::default(), now);
+/// assert_eq!(1, anomalies.len());
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnomalyDetector {
+    missed_meal_hours: i64,
+    absence_factor: f64,
+}
+
+/// A builder to help with the creation of an `AnomalyDetector`.
+#[derive(Clone, Debug)]
+pub struct AnomalyDetectorBuilder {
+    missed_meal_hours: i64,
+    absence_factor: f64,
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        AnomalyDetectorBuilder::default().build()
+    }
+}
+
+impl Default for AnomalyDetectorBuilder {
+    fn default() -> Self {
+        AnomalyDetectorBuilder {
+            missed_meal_hours: 12,
+            absence_factor: 1.5,
+        }
+    }
+}
+
+impl AnomalyDetectorBuilder {
+    /// Create a new builder with a twelve hour meal gap and a 1.5 times
+    /// absence factor.
+    pub fn new() -> Self {
+        AnomalyDetectorBuilder::default()
+    }
+
+    /// Sets how many hours without a meal count as a missed meal.
+    pub fn with_missed_meal_hours(&mut self, hours: i64) -> &mut Self {
+        self.missed_meal_hours = hours;
+        self
+    }
+
+    /// Sets how many times the usual longest trip a pet must be out before
+    /// it counts as an extended absence.
+    pub fn with_absence_factor(&mut self, factor: f64) -> &mut Self {
+        self.absence_factor = factor;
+        self
+    }
+
+    /// Builds the detector.
+    pub fn build(&self) -> AnomalyDetector {
+        AnomalyDetector {
+            missed_meal_hours: self.missed_meal_hours,
+            absence_factor: self.absence_factor,
+        }
+    }
+}
+
+impl AnomalyDetector {
+    /// Checks the recent `events` of a pet against its baseline as of `now`.
+    ///
+    /// The events should reach back at least as far as the missed meal
+    /// window and the start of the current day.
+    pub fn detect(
+        &self,
+        pet_id: PetId,
+        events: &[Event<'_>],
+        baseline: &Baseline,
+        now: Timestamp,
+    ) -> Vec<Anomaly> {
+        let own: Vec<&Event<'_>> = events.iter().filter(|e| e.involves_pet(pet_id)).collect();
+        let mut anomalies = Vec::new();
+
+        let last_meal = last_visit(&own, EventKind::Feeding);
+        if last_meal.is_none_or(|at| now.seconds_since(at) > self.missed_meal_hours * 3600) {
+            anomalies.push(Anomaly::MissedMeal { pet_id, last_meal });
+        }
+
+        let last_movement = own
+            .iter()
+            .filter(|e| e.kind() == EventKind::Movement)
+            .flat_map(|e| {
+                e.movements()
+                    .iter()
+                    .map(move |m| (e.created_at(), m.direction()))
+            })
+            .filter(|(_, direction)| matches!(direction, Direction::In | Direction::Out))
+            .max_by_key(|(at, _)| *at);
+        if let (Some((out_since, Direction::Out)), Some(usual)) =
+            (last_movement, baseline.usual_trip)
+        {
+            let outside = now.seconds_since(out_since);
+            if outside as f64 > usual as f64 * self.absence_factor {
+                anomalies.push(Anomaly::ExtendedAbsence {
+                    pet_id,
+                    out_since,
+                    outside,
+                    usual,
+                });
+            }
+        }
+
+        let today = now.date();
+        let drank_today = own
+            .iter()
+            .filter(|e| e.kind() == EventKind::Drinking && e.created_at().date() == today)
+            .any(|e| e.weights().iter().any(|w| w.context() == PET_VISIT));
+        if baseline.drinks_per_day >= 1.0 && !drank_today {
+            anomalies.push(Anomaly::NoDrinking {
+                pet_id,
+                date: today,
+            });
+        }
+
+        anomalies
+    }
+}
+
+fn last_visit(events: &[&Event<'_>], kind: EventKind) -> Option<Timestamp> {
+    events
+        .iter()
+        .filter(|e| e.kind() == kind && e.weights().iter().any(|w| w.context() == PET_VISIT))
+        .map(|e| e.created_at())
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn events() -> Vec<Event<'static>> {
+        serde_json::from_str(
+            r#"[
+            {"id":1,"type":22,"created_at":"2019-06-01T06:00:00Z","pets":[{"id":2}],
+             "weights":[{"duration":60,"context":1,"frames":[{"current_weight":30,"change":-10}]}]},
+            {"id":2,"type":29,"created_at":"2019-05-31T20:00:00Z","pets":[{"id":2}],
+             "weights":[{"duration":30,"context":1,"frames":[{"current_weight":300,"change":-15}]}]},
+            {"id":3,"type":0,"created_at":"2019-06-01T07:00:00Z","pets":[{"id":2}],"movements":[{"direction":2}]}
+        ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_flag_everything_unusual() {
+        let detector = AnomalyDetectorBuilder::new()
+            .with_missed_meal_hours(4)
+            .build();
+        let now = "2019-06-01T12:00:00Z".parse().unwrap();
+        let anomalies = detector.detect(
+            PetId::new(2),
+            &events(),
+            &Baseline::new(Some(3 * 3600), 2.0),
+            now,
+        );
+
+        assert_eq!(
+            vec![
+                Anomaly::MissedMeal {
+                    pet_id: PetId::new(2),
+                    last_meal: Some("2019-06-01T06:00:00Z".parse().unwrap()),
+                },
+                Anomaly::ExtendedAbsence {
+                    pet_id: PetId::new(2),
+                    out_since: "2019-06-01T07:00:00Z".parse().unwrap(),
+                    outside: 5 * 3600,
+                    usual: 3 * 3600,
+                },
+                Anomaly::NoDrinking {
+                    pet_id: PetId::new(2),
+                    date: Date::new(2019, 6, 1).unwrap(),
+                },
+            ],
+            anomalies
+        );
+    }
+
+    #[test]
+    fn should_stay_quiet_when_normal() {
+        let detector = AnomalyDetector::default();
+        let now = "2019-06-01T08:00:00Z".parse().unwrap();
+        let anomalies = detector.detect(
+            PetId::new(2),
+            &events(),
+            &Baseline::new(Some(4 * 3600), 0.5),
+            now,
+        );
+        assert!(anomalies.is_empty());
+    }
+}
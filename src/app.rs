@@ -0,0 +1,129 @@
+//! Identifying the client the way the official mobile app does.
+//!
+//! The app sends a few extra headers with every request, naming its
+//! version, platform and locale and the installation it runs on. Sending
+//! the same set keeps requests from this crate looking like the app's,
+//! should the API ever start treating other clients differently. Attach an
+//! [`AppIdentity`](struct.AppIdentity.html) with
+//! [`SurePet::with_app_identity`](../client/struct.SurePet.html#method.with_app_identity).
+use std::borrow::Cow;
+
+/// The app version sent unless another is set.
+pub const APP_VERSION: &str = "2.38.0";
+
+/// The platform the app runs on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Platform {
+    /// The Android app.
+    Android,
+    /// The iOS app.
+    Ios,
+}
+
+impl Platform {
+    /// The platform name as the app sends it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Platform::Android => "android",
+            Platform::Ios => "ios",
+        }
+    }
+}
+
+/// The headers identifying an app installation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AppIdentity {
+    headers: Vec<(String, String)>,
+}
+
+impl AppIdentity {
+    /// The headers to send, in order.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+}
+
+/// A builder for an [`AppIdentity`](struct.AppIdentity.html).
+/// # Examples
+///
+/// ```
+/// use sure_petcare::app::{AppIdentityBuilder, Platform};
+/// let identity = AppIdentityBuilder::new()
+///     .with_platform(Platform::Ios)
+///     .with_locale("de-DE")
+///     .with_device_id("xxx-xxx-xxx-xxx")
+///     .build();
+/// assert!(identity.headers().contains(&("Accept-Language".to_string(), "de-DE".to_string())));
+/// ```
+#[derive(Clone, Debug)]
+pub struct AppIdentityBuilder<'a> {
+    version: Cow<'a, str>,
+    platform: Platform,
+    locale: Cow<'a, str>,
+    device_id: Option<Cow<'a, str>>,
+}
+
+impl<'a> Default for AppIdentityBuilder<'a> {
+    fn default() -> Self {
+        AppIdentityBuilder {
+            version: APP_VERSION.into(),
+            platform: Platform::Android,
+            locale: "en-GB".into(),
+            device_id: None,
+        }
+    }
+}
+
+impl<'a> AppIdentityBuilder<'a> {
+    /// Create a new builder for the current Android app in British English.
+    pub fn new() -> Self {
+        AppIdentityBuilder::default()
+    }
+
+    /// Sets the app version.
+    pub fn with_version<T: Into<Cow<'a, str>>>(&mut self, version: T) -> &mut Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the platform.
+    pub fn with_platform(&mut self, platform: Platform) -> &mut Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Sets the locale, such as `en-GB`.
+    pub fn with_locale<T: Into<Cow<'a, str>>>(&mut self, locale: T) -> &mut Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Sets the installation id, which should match the device id the
+    /// client logged in with.
+    pub fn with_device_id<T: Into<Cow<'a, str>>>(&mut self, device_id: T) -> &mut Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Builds the identity.
+    pub fn build(&self) -> AppIdentity {
+        let platform = self.platform.as_str();
+        let mut headers = vec![
+            (
+                "User-Agent".to_string(),
+                format!("SurePetcare/{} ({})", self.version, platform),
+            ),
+            ("X-App-Version".to_string(), self.version.to_string()),
+            ("X-Platform".to_string(), platform.to_string()),
+            ("Accept-Language".to_string(), self.locale.to_string()),
+            (
+                "X-Requested-With".to_string(),
+                "com.sureflap.surepetcare".to_string(),
+            ),
+        ];
+        if let Some(device_id) = &self.device_id {
+            headers.push(("X-Device-Id".to_string(), device_id.to_string()));
+        }
+        AppIdentity { headers }
+    }
+}
@@ -0,0 +1,584 @@
+//! Saving a household's settings and putting them back, enabled with the
+//! `backup` feature.
+//!
+//! Resetting a hub, or replacing one, wipes what the household had set up.
+//! [`export`](fn.export.html) captures it as a [`Backup`](struct.Backup.html):
+//! each flap's lock mode and curfews, which pets each flap keeps in, each
+//! feeder's lid delay, the hub's lights, and each pet's name, comments and
+//! medical conditions. It is plain JSON with a version number, to be kept
+//! somewhere safe.
+//!
+//! [`apply`](fn.apply.html) puts a backup onto a household, the one it came
+//! from or another, as when setting up a second home the same way. Devices
+//! are matched by id, then by product and name, then by product alone;
+//! pets by tag, then by name. Bowl targets are not reported by the API, so
+//! they cannot be backed up.
+//!
+//! ```no_run
+//! # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+//! use sure_petcare::backup::{self, Backup};
+//! use sure_petcare::id::HouseholdId;
+//! let json = backup::export(&client, HouseholdId::new(1001))?.to_json()?;
+//! // after the reset
+//! let restored = backup::apply(&client, &Backup::from_json(&json)?, HouseholdId::new(1001))?;
+//! assert!(restored.is_complete());
+//! # Ok(())
+//! # }
+//! ```
+use crate::batch::BatchResult;
+use crate::client::SurePet;
+use crate::device::{
+    CloseDelay, ControlUpdate, ControlUpdateBuilder, Curfew, Device, LedMode, LockMode, Product,
+};
+use crate::error::{Error, Result, ValidationError};
+use crate::id::{DeviceId, HouseholdId, PetId, TagId};
+use crate::pet::{Condition, Pet, TagProfile};
+use crate::time::Timestamp;
+use crate::transport::{Method, Transport};
+use serde::{Deserialize, Serialize};
+
+/// The version of the document [`export`](fn.export.html) writes. Backups
+/// from a newer version of the crate are refused rather than half applied.
+pub const VERSION: u32 = 1;
+
+/// A household's settings, see the module documentation.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Backup {
+    version: u32,
+    household_id: HouseholdId,
+    created_at: Timestamp,
+    devices: Vec<DeviceSettings>,
+    pets: Vec<PetSettings>,
+}
+
+impl Backup {
+    /// The household the backup was taken from.
+    pub fn household_id(&self) -> HouseholdId {
+        self.household_id
+    }
+
+    /// When the backup was taken.
+    pub fn created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
+    /// The devices' settings.
+    pub fn devices(&self) -> &[DeviceSettings] {
+        &self.devices
+    }
+
+    /// The pets' details.
+    pub fn pets(&self) -> &[PetSettings] {
+        &self.pets
+    }
+
+    /// The backup as pretty printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Reads a backup, failing with `Error::Invalid` for one written by a
+    /// newer version of the crate.
+    pub fn from_json(json: &str) -> Result<Backup> {
+        let backup: Backup = serde_json::from_str(json)?;
+        if backup.version > VERSION {
+            return Err(Error::from(ValidationError::new(
+                "version",
+                format!("is {}, newer than {}", backup.version, VERSION),
+            )));
+        }
+        Ok(backup)
+    }
+}
+
+/// The settings of one device.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct DeviceSettings {
+    id: DeviceId,
+    name: String,
+    product: Product,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lock_mode: Option<LockMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    curfews: Vec<Curfew>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    close_delay: Option<CloseDelay>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    led_mode: Option<LedMode>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    access: Vec<Access>,
+}
+
+impl DeviceSettings {
+    /// The device id in the household backed up.
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    /// The device's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The kind of device.
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// The lock mode of a flap.
+    pub fn lock_mode(&self) -> Option<LockMode> {
+        self.lock_mode
+    }
+
+    /// The curfews of a flap.
+    pub fn curfews(&self) -> &[Curfew] {
+        &self.curfews
+    }
+
+    /// How long a feeder keeps its lid open.
+    pub fn close_delay(&self) -> Option<CloseDelay> {
+        self.close_delay
+    }
+
+    /// The brightness of a hub's lights.
+    pub fn led_mode(&self) -> Option<LedMode> {
+        self.led_mode
+    }
+
+    /// What the device lets each tag it knows do.
+    pub fn access(&self) -> &[Access] {
+        &self.access
+    }
+}
+
+/// What a device lets a tag do.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Access {
+    #[serde(rename = "id")]
+    tag_id: TagId,
+    #[serde(default = "outdoor")]
+    profile: TagProfile,
+}
+
+impl Access {
+    /// The tag.
+    pub fn tag_id(&self) -> TagId {
+        self.tag_id
+    }
+
+    /// What the tag may do.
+    pub fn profile(&self) -> TagProfile {
+        self.profile
+    }
+}
+
+fn outdoor() -> TagProfile {
+    TagProfile::Outdoor
+}
+
+/// The details of one pet.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PetSettings {
+    id: PetId,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag_id: Option<TagId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comments: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    conditions: Vec<Condition>,
+}
+
+impl PetSettings {
+    /// The pet id in the household backed up.
+    pub fn id(&self) -> PetId {
+        self.id
+    }
+
+    /// The pet's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The pet's tag.
+    pub fn tag_id(&self) -> Option<TagId> {
+        self.tag_id
+    }
+
+    /// The pet's comments.
+    pub fn comments(&self) -> Option<&str> {
+        self.comments.as_deref()
+    }
+
+    /// The pet's medical conditions.
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+}
+
+/// A part of a backup [`apply`](fn.apply.html) restored or failed to,
+/// by the ids in the backup.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Item {
+    /// A device's settings.
+    Device(DeviceId),
+    /// A pet's details.
+    Pet(PetId),
+    /// What a device lets a pet's tag do.
+    Access(DeviceId, TagId),
+}
+
+/// A device as listed with its tags, which the device model leaves out.
+#[derive(Deserialize)]
+struct Listed {
+    #[serde(flatten)]
+    device: Device<'static>,
+    #[serde(default)]
+    tags: Vec<Access>,
+}
+
+#[derive(Serialize)]
+struct PetUpdate<'a> {
+    name: &'a str,
+    comments: &'a str,
+    conditions: &'a [Condition],
+}
+
+#[derive(Serialize)]
+struct ProfileUpdate {
+    profile: TagProfile,
+}
+
+/// Captures the settings of a household's devices and pets.
+pub fn export<T: Transport>(client: &SurePet<T>, household_id: HouseholdId) -> Result<Backup> {
+    let devices = list_devices(client, household_id)?
+        .into_iter()
+        .map(|Listed { device, tags }| DeviceSettings {
+            id: device.id,
+            name: device.name.into_owned(),
+            product: device.product_id,
+            lock_mode: device.status.lock_mode(),
+            curfews: device.control.curfew,
+            close_delay: device.control.lid.map(|lid| lid.close_delay),
+            led_mode: device.control.led_mode,
+            access: if device.product_id.is_flap() {
+                tags
+            } else {
+                Vec::new()
+            },
+        })
+        .collect();
+    let pets = list_pets(client, household_id)?
+        .into_iter()
+        .map(|pet| PetSettings {
+            id: pet.id,
+            name: pet.name.into_owned(),
+            tag_id: pet.tag_id,
+            comments: pet.comments.map(|comments| comments.into_owned()),
+            conditions: pet.conditions,
+        })
+        .collect();
+    Ok(Backup {
+        version: VERSION,
+        household_id,
+        created_at: client.clock().now(),
+        devices,
+        pets,
+    })
+}
+
+/// Puts a backup onto a household, see the module documentation. Parts that
+/// fail, or have nothing to go onto, do not stop the rest from being
+/// restored.
+pub fn apply<T: Transport>(
+    client: &SurePet<T>,
+    backup: &Backup,
+    household_id: HouseholdId,
+) -> Result<BatchResult<Item>> {
+    let devices: Vec<Device<'static>> = list_devices(client, household_id)?
+        .into_iter()
+        .map(|listed| listed.device)
+        .collect();
+    let pets = list_pets(client, household_id)?;
+    let mut outcomes = Vec::new();
+
+    let mut matched: Vec<(&DeviceSettings, Option<&Device<'static>>)> = Vec::new();
+    for settings in &backup.devices {
+        let taken: Vec<DeviceId> = matched
+            .iter()
+            .filter_map(|(_, device)| device.map(|device| device.id))
+            .collect();
+        let device = match_device(settings, &devices, &taken);
+        let outcome = match device {
+            Some(device) => restore_device(client, settings, device),
+            None => Err(unmatched("device", settings.product.to_string())),
+        };
+        matched.push((settings, device));
+        outcomes.push((Item::Device(settings.id), outcome));
+    }
+
+    let mut tags = Vec::new();
+    let mut taken = Vec::new();
+    for settings in &backup.pets {
+        let outcome = match match_pet(settings, &pets, &taken) {
+            Some(pet) => {
+                taken.push(pet.id);
+                if let (Some(from), Some(to)) = (settings.tag_id, pet.tag_id) {
+                    tags.push((from, to));
+                }
+                restore_pet(client, settings, pet)
+            }
+            None => Err(unmatched("pet", settings.name.clone())),
+        };
+        outcomes.push((Item::Pet(settings.id), outcome));
+    }
+
+    for (settings, device) in matched {
+        for access in &settings.access {
+            let tag = tags.iter().find(|(from, _)| *from == access.tag_id);
+            let outcome = match (device, tag) {
+                (Some(device), Some((_, tag_id))) => {
+                    let path = format!("/api/device/{}/tag/{}", device.id, tag_id);
+                    client.send_unit(
+                        Method::Put,
+                        &path,
+                        Some(&ProfileUpdate {
+                            profile: access.profile,
+                        }),
+                    )
+                }
+                (None, _) => Err(unmatched("device", settings.product.to_string())),
+                (_, None) => Err(unmatched("tag", access.tag_id.to_string())),
+            };
+            outcomes.push((Item::Access(settings.id, access.tag_id), outcome));
+        }
+    }
+    Ok(outcomes.into_iter().collect())
+}
+
+fn list_devices<T: Transport>(
+    client: &SurePet<T>,
+    household_id: HouseholdId,
+) -> Result<Vec<Listed>> {
+    let devices: Vec<Listed> = client.get(
+        "/api/device",
+        &[
+            ("with[]", "status".to_string()),
+            ("with[]", "control".to_string()),
+            ("with[]", "tags".to_string()),
+        ],
+    )?;
+    Ok(devices
+        .into_iter()
+        .filter(|listed| {
+            listed
+                .device
+                .household_id
+                .is_none_or(|id| id == household_id)
+        })
+        .collect())
+}
+
+fn list_pets<T: Transport>(
+    client: &SurePet<T>,
+    household_id: HouseholdId,
+) -> Result<Vec<Pet<'static>>> {
+    let mut pets = client.pets().list()?;
+    pets.retain(|pet| pet.household_id.is_none_or(|id| id == household_id));
+    Ok(pets)
+}
+
+/// The device to restore settings onto: the same one, or one of the same
+/// product, preferring the same name, that has not been used already.
+fn match_device<'d>(
+    settings: &DeviceSettings,
+    devices: &'d [Device<'static>],
+    taken: &[DeviceId],
+) -> Option<&'d Device<'static>> {
+    let free: Vec<&Device<'static>> = devices
+        .iter()
+        .filter(|device| device.product_id == settings.product && !taken.contains(&device.id))
+        .collect();
+    free.iter()
+        .find(|device| device.id == settings.id)
+        .or_else(|| {
+            free.iter()
+                .find(|device| device.name.eq_ignore_ascii_case(&settings.name))
+        })
+        .or_else(|| free.first())
+        .copied()
+}
+
+/// The pet to restore details onto: the one wearing the same tag, or one of
+/// the same name, that has not been used already.
+fn match_pet<'p>(
+    settings: &PetSettings,
+    pets: &'p [Pet<'static>],
+    taken: &[PetId],
+) -> Option<&'p Pet<'static>> {
+    let free = || pets.iter().filter(move |pet| !taken.contains(&pet.id));
+    free()
+        .find(|pet| settings.tag_id.is_some() && pet.tag_id == settings.tag_id)
+        .or_else(|| free().find(|pet| pet.name.eq_ignore_ascii_case(&settings.name)))
+}
+
+fn restore_device<T: Transport>(
+    client: &SurePet<T>,
+    settings: &DeviceSettings,
+    device: &Device<'static>,
+) -> Result<()> {
+    let product = device.product_id;
+    let mut update = ControlUpdateBuilder::new();
+    if let (Some(mode), true) = (settings.lock_mode, product.is_flap()) {
+        update.with_lock_mode(mode);
+    }
+    if product.curfew_slots() > 0 {
+        let current = &device.control.curfew;
+        match settings.curfews.as_slice() {
+            [] if current.is_empty() => {}
+            [] if product == Product::PetDoor => {
+                update.with_curfew(current[0].disabled());
+            }
+            [] => {
+                update.clear_curfews();
+            }
+            [curfew] if product == Product::PetDoor => {
+                update.with_curfew(*curfew);
+            }
+            curfews => {
+                update.with_curfews(curfews);
+            }
+        }
+    }
+    if let (Some(delay), true) = (settings.close_delay, product.is_feeder()) {
+        update.with_close_delay(delay);
+    }
+    if let (Some(mode), Product::Hub) = (settings.led_mode, product) {
+        update.with_led_mode(mode);
+    }
+    let update = update.build();
+    if update == ControlUpdate::default() {
+        return Ok(());
+    }
+    update.validate(product)?;
+    client.devices().update(device.id, &update)
+}
+
+fn restore_pet<T: Transport>(
+    client: &SurePet<T>,
+    settings: &PetSettings,
+    pet: &Pet<'static>,
+) -> Result<()> {
+    let path = format!("/api/pet/{}", pet.id);
+    client.send_unit(
+        Method::Put,
+        &path,
+        Some(&PetUpdate {
+            name: &settings.name,
+            comments: settings.comments.as_deref().unwrap_or_default(),
+            conditions: &settings.conditions,
+        }),
+    )?;
+    if let Some(cache) = client.state_cache() {
+        cache.invalidate_pet(pet.id);
+    }
+    Ok(())
+}
+
+fn unmatched(what: &'static str, which: String) -> Error {
+    Error::from(ValidationError::new(
+        what,
+        format!("has nothing to restore {} onto in the household", which),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_restore_a_backup_onto_another_household() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[
+                    {"id":10,"name":"Back door","product_id":6,"household_id":1,
+                     "status":{"locking":{"mode":1}},
+                     "control":{"curfew":[{"enabled":true,"lock_time":"21:00","unlock_time":"07:00"}]},
+                     "tags":[{"id":5,"profile":3}]},
+                    {"id":11,"name":"Feeder","product_id":4,"household_id":1,
+                     "control":{"lid":{"close_delay":20}}},
+                    {"id":12,"name":"Other home","product_id":6,"household_id":2}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":2,"name":"Milo","household_id":1,"tag_id":5,
+                    "comments":"on medication","conditions":[7]}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":20,"name":"Cat flap","product_id":6,"household_id":2}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":30,"name":"milo","household_id":2,"tag_id":50}]}"#,
+            )
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token")
+            .with_clock(ManualClock::new(Timestamp::from_unix(1_559_372_400)));
+
+        let json = export(&client, HouseholdId::new(1))
+            .unwrap()
+            .to_json()
+            .unwrap();
+        let backup = Backup::from_json(&json).unwrap();
+        assert_eq!(2, backup.devices().len());
+        assert_eq!(Some(LockMode::KeepIn), backup.devices()[0].lock_mode());
+        assert_eq!(Some(CloseDelay::Slow), backup.devices()[1].close_delay());
+        assert_eq!(Some("on medication"), backup.pets()[0].comments());
+
+        let restored = apply(&client, &backup, HouseholdId::new(2)).unwrap();
+        assert_eq!(
+            vec![
+                Item::Device(DeviceId::new(10)),
+                Item::Pet(PetId::new(2)),
+                Item::Access(DeviceId::new(10), TagId::new(5)),
+            ],
+            restored.succeeded_keys()
+        );
+        assert_eq!(
+            vec![Item::Device(DeviceId::new(11))],
+            restored.failed_keys()
+        );
+
+        let requests = transport.requests();
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/20/control",
+            requests[4].url
+        );
+        assert_eq!(
+            r#"{"locking":1,"curfew":[{"enabled":true,"lock_time":"21:00","unlock_time":"07:00"}]}"#,
+            String::from_utf8_lossy(requests[4].body.as_ref().unwrap())
+        );
+        assert_eq!(
+            r#"{"name":"Milo","comments":"on medication","conditions":[7]}"#,
+            String::from_utf8_lossy(requests[5].body.as_ref().unwrap())
+        );
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/20/tag/50",
+            requests[6].url
+        );
+        assert_eq!(
+            r#"{"profile":3}"#,
+            String::from_utf8_lossy(requests[6].body.as_ref().unwrap())
+        );
+
+        let newer = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(matches!(Backup::from_json(&newer), Err(Error::Invalid(_))));
+    }
+}
@@ -0,0 +1,114 @@
+//! The outcome of a request sent to several pets or devices.
+//!
+//! Bulk helpers such as
+//! [`Pets::set_all_locations`](../pet/struct.Pets.html#method.set_all_locations)
+//! carry on past a failure instead of stopping at it, so one flap that is
+//! offline does not leave the rest unchanged. The
+//! [`BatchResult`](struct.BatchResult.html) they return says which items
+//! succeeded and why the others failed, so just those can be tried again.
+use crate::error::{Error, Result};
+use std::iter::FromIterator;
+
+/// The successes and failures of a batch, each under the item's key and in
+/// the order the items were given.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::batch::BatchResult;
+/// use sure_petcare::error::Error;
+/// let batch: BatchResult<u32> = vec![
+///     (1, Ok(())),
+///     (2, Err(Error::transport("timed out"))),
+///     (3, Ok(())),
+/// ]
+/// .into_iter()
+/// .collect();
+/// assert!(!batch.is_complete());
+/// assert_eq!(vec![1, 3], batch.succeeded_keys());
+/// assert_eq!(vec![2], batch.failed_keys());
+/// ```
+#[derive(Debug)]
+pub struct BatchResult<K, V = ()> {
+    succeeded: Vec<(K, V)>,
+    failed: Vec<(K, Error)>,
+}
+
+impl<K, V> BatchResult<K, V> {
+    /// The items that succeeded, with what each returned.
+    pub fn succeeded(&self) -> &[(K, V)] {
+        &self.succeeded
+    }
+
+    /// The items that failed, with the error each failed with.
+    pub fn failed(&self) -> &[(K, Error)] {
+        &self.failed
+    }
+
+    /// Whether every item succeeded.
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// The number of items in the batch.
+    pub fn len(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    /// Whether the batch had no items.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The keys of the items that succeeded.
+    pub fn succeeded_keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.succeeded.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    /// The keys of the items that failed, to retry them.
+    pub fn failed_keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.failed.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    /// Treats the batch as all or nothing: the successes when there were no
+    /// failures, otherwise the first failure.
+    pub fn into_result(self) -> Result<Vec<(K, V)>> {
+        match self.failed.into_iter().next() {
+            Some((_, error)) => Err(error),
+            None => Ok(self.succeeded),
+        }
+    }
+}
+
+impl<K, V> Default for BatchResult<K, V> {
+    fn default() -> Self {
+        BatchResult {
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> Extend<(K, Result<V>)> for BatchResult<K, V> {
+    fn extend<I: IntoIterator<Item = (K, Result<V>)>>(&mut self, outcomes: I) {
+        for (key, outcome) in outcomes {
+            match outcome {
+                Ok(value) => self.succeeded.push((key, value)),
+                Err(error) => self.failed.push((key, error)),
+            }
+        }
+    }
+}
+
+impl<K, V> FromIterator<(K, Result<V>)> for BatchResult<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, Result<V>)>>(outcomes: I) -> Self {
+        let mut batch = BatchResult::default();
+        batch.extend(outcomes);
+        batch
+    }
+}
@@ -0,0 +1,309 @@
+//! Battery level estimates from the voltage devices report.
+//!
+//! Devices run on four AA cells and only report the pack voltage. How that
+//! maps to a charge level depends on the cells: alkaline cells fade steadily
+//! while rechargeable NiMH cells hold a flat plateau and then drop sharply.
+//! Pack voltage also wobbles with temperature and load, so a
+//! [`BatteryMonitor`](struct.BatteryMonitor.html) alerts below one level
+//! and only clears above a higher one.
+use crate::alert::Alert;
+use crate::device::Device;
+use crate::error::ParseNameError;
+use crate::id::DeviceId;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// The kind of cells fitted to a device.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Chemistry {
+    /// Disposable alkaline cells, reading 6.0V new and 4.8V when the
+    /// device stops working.
+    #[default]
+    Alkaline,
+    /// Rechargeable NiMH cells, reading about 5.5V freshly charged and
+    /// 4.4V when flat.
+    NiMh,
+}
+
+impl Chemistry {
+    /// The default calibration curve for the chemistry.
+    pub fn calibration(self) -> Calibration {
+        match self {
+            Chemistry::Alkaline => Calibration::linear(4.8, 6.0),
+            Chemistry::NiMh => Calibration::from_points(&[
+                (4.4, 0.0),
+                (4.8, 10.0),
+                (5.0, 50.0),
+                (5.2, 90.0),
+                (5.5, 100.0),
+            ]),
+        }
+    }
+}
+
+impl fmt::Display for Chemistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Chemistry::Alkaline => "alkaline",
+            Chemistry::NiMh => "nimh",
+        })
+    }
+}
+
+impl FromStr for Chemistry {
+    type Err = ParseNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace(['-', ' '], "").as_str() {
+            "alkaline" => Ok(Chemistry::Alkaline),
+            "nimh" => Ok(Chemistry::NiMh),
+            _ => Err(ParseNameError::new("battery chemistry", s)),
+        }
+    }
+}
+
+/// A discharge curve turning a pack voltage into a percentage, given as
+/// `(volts, percent)` points joined by straight lines. Voltages outside the
+/// curve clamp to its first and last percentages.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::battery::{Calibration, Chemistry};
+/// let curve = Calibration::from_points(&[(4.5, 0.0), (5.0, 80.0), (5.6, 100.0)]);
+/// assert_eq!(40.0, curve.percent(4.75));
+/// assert_eq!(50.0, Chemistry::Alkaline.calibration().percent(5.4).round());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    points: Vec<(f64, f64)>,
+}
+
+impl Calibration {
+    /// A straight line from 0% at `empty` volts to 100% at `full` volts.
+    pub fn linear(empty: f64, full: f64) -> Self {
+        Calibration::from_points(&[(empty, 0.0), (full, 100.0)])
+    }
+
+    /// A curve through the given points, in any order.
+    pub fn from_points(points: &[(f64, f64)]) -> Self {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Calibration { points }
+    }
+
+    /// The points of the curve, in voltage order.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// The estimated charge in percent for a pack voltage, `0` for a curve
+    /// without points.
+    pub fn percent(&self, volts: f64) -> f64 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 0.0,
+        };
+        if volts <= first.0 {
+            return first.1.clamp(0.0, 100.0);
+        }
+        let percent = self
+            .points
+            .windows(2)
+            .find(|pair| volts <= pair[1].0)
+            .map_or(last.1, |pair| {
+                let ((v0, p0), (v1, p1)) = (pair[0], pair[1]);
+                p0 + (volts - v0) / (v1 - v0) * (p1 - p0)
+            });
+        percent.clamp(0.0, 100.0)
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Chemistry::default().calibration()
+    }
+}
+
+/// Raises an alert when a device's battery runs low, once until it has
+/// been charged or replaced.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::battery::BatteryMonitor;
+/// use sure_petcare::device::Device;
+/// let at = |volts: f64| -> Vec<Device<'static>> {
+///     let json = format!(r#"[{{"id":10,"name":"Back door","product_id":6,"status":{{"battery":{}}}}}]"#, volts);
+///     serde_json::from_str(&json).unwrap()
+/// };
+/// let mut monitor = BatteryMonitor::new();
+/// assert_eq!(1, monitor.check(&at(5.05)).len()); // 21%
+/// assert!(monitor.check(&at(5.12)).is_empty()); // 27%, still low
+/// assert!(monitor.check(&at(5.05)).is_empty());
+/// assert!(monitor.check(&at(5.6)).is_empty()); // 67%, cleared
+/// assert_eq!(1, monitor.check(&at(5.05)).len());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatteryMonitor {
+    threshold: (f64, f64),
+    thresholds: HashMap<DeviceId, (f64, f64)>,
+    calibration: Calibration,
+    calibrations: HashMap<DeviceId, Calibration>,
+    low: HashSet<DeviceId>,
+}
+
+impl Default for BatteryMonitor {
+    fn default() -> Self {
+        BatteryMonitor {
+            threshold: (25.0, 35.0),
+            thresholds: HashMap::new(),
+            calibration: Calibration::default(),
+            calibrations: HashMap::new(),
+            low: HashSet::new(),
+        }
+    }
+}
+
+impl BatteryMonitor {
+    /// A monitor alerting below 25% and clearing above 35%.
+    pub fn new() -> Self {
+        BatteryMonitor::default()
+    }
+
+    /// Alerts when a battery drops below `alert_below` percent, and clears
+    /// once it is back above `clear_above`, which is raised to
+    /// `alert_below` if lower.
+    pub fn with_threshold(mut self, alert_below: f64, clear_above: f64) -> Self {
+        self.threshold = (alert_below, clear_above.max(alert_below));
+        self
+    }
+
+    /// Sets different thresholds for one device.
+    pub fn with_threshold_for(
+        mut self,
+        device_id: DeviceId,
+        alert_below: f64,
+        clear_above: f64,
+    ) -> Self {
+        self.thresholds
+            .insert(device_id, (alert_below, clear_above.max(alert_below)));
+        self
+    }
+
+    /// Sets the discharge curve percentages are worked out on.
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = calibration;
+        self
+    }
+
+    /// Sets the discharge curve for one device, such as one fitted with
+    /// rechargeable cells.
+    pub fn with_calibration_for(mut self, device_id: DeviceId, calibration: Calibration) -> Self {
+        self.calibrations.insert(device_id, calibration);
+        self
+    }
+
+    /// The `(alert_below, clear_above)` percentages for a device.
+    pub fn threshold_for(&self, device_id: DeviceId) -> (f64, f64) {
+        self.thresholds
+            .get(&device_id)
+            .copied()
+            .unwrap_or(self.threshold)
+    }
+
+    /// The discharge curve for a device.
+    pub fn calibration_for(&self, device_id: DeviceId) -> &Calibration {
+        self.calibrations
+            .get(&device_id)
+            .unwrap_or(&self.calibration)
+    }
+
+    /// Whether a device has been alerted about and not cleared since.
+    pub fn is_low(&self, device_id: DeviceId) -> bool {
+        self.low.contains(&device_id)
+    }
+
+    /// Looks at the battery of each device, returning an alert for each
+    /// that has just dropped below its threshold. Devices that report no
+    /// battery reading keep their state.
+    pub fn check(&mut self, devices: &[Device<'_>]) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for device in devices {
+            let id = device.id();
+            let percent = match device
+                .status()
+                .battery_percent_with(self.calibration_for(id))
+            {
+                Some(percent) => percent,
+                None => continue,
+            };
+            let (alert_below, clear_above) = self.threshold_for(id);
+            if percent < alert_below {
+                if self.low.insert(id) {
+                    alerts.push(Alert::LowBattery {
+                        device_id: id,
+                        name: device.name().to_string(),
+                        percent,
+                    });
+                }
+            } else if percent > clear_above {
+                self.low.remove(&id);
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_interpolate_between_points() {
+        let nimh = Chemistry::NiMh.calibration();
+        assert_eq!(0.0, nimh.percent(4.0));
+        assert_eq!(50.0, nimh.percent(5.0));
+        assert_eq!(70.0, nimh.percent(5.1).round());
+        assert_eq!(100.0, nimh.percent(5.9));
+        assert_eq!(25.0, Calibration::default().percent(5.1).round());
+        assert_eq!(0.0, Calibration::from_points(&[]).percent(5.0));
+        assert_eq!(Ok(Chemistry::NiMh), "NiMH".parse());
+        assert!("lithium".parse::<Chemistry>().is_err());
+    }
+
+    #[test]
+    fn should_alert_once_until_the_battery_recovers() {
+        let devices = |door: f64, feeder: f64| -> Vec<Device<'static>> {
+            let json = format!(
+                r#"[{{"id":10,"name":"Back door","product_id":6,"status":{{"battery":{}}}}},
+                    {{"id":11,"name":"Feeder","product_id":4,"status":{{"battery":{}}}}},
+                    {{"id":12,"name":"Hub","product_id":1,"status":{{}}}}]"#,
+                door, feeder
+            );
+            serde_json::from_str(&json).unwrap()
+        };
+        let feeder = DeviceId::new(11);
+        let mut monitor = BatteryMonitor::new()
+            .with_threshold_for(feeder, 10.0, 20.0)
+            .with_calibration_for(feeder, Chemistry::NiMh.calibration());
+
+        let alerts: Vec<String> = monitor
+            .check(&devices(5.0, 5.0))
+            .iter()
+            .map(Alert::to_string)
+            .collect();
+        assert_eq!(
+            vec!["Back door battery is at 17%, replace it soon".to_string()],
+            alerts
+        );
+        assert!(monitor.check(&devices(5.2, 4.85)).is_empty());
+        assert!(monitor.check(&devices(5.0, 4.85)).is_empty());
+        assert!(monitor.is_low(DeviceId::new(10)));
+        assert!(!monitor.is_low(feeder));
+        assert_eq!(1, monitor.check(&devices(5.3, 4.7)).len());
+        assert!(!monitor.is_low(DeviceId::new(10)));
+        assert!(monitor.is_low(feeder));
+        assert_eq!(1, monitor.check(&devices(5.0, 4.6)).len());
+    }
+}
@@ -0,0 +1,120 @@
+//! Command line parsing.
+use std::collections::BTreeMap;
+
+/// Options that never take a value.
+const SWITCHES: &[&str] = &["follow", "help", "json", "password-stdin", "wait"];
+
+/// Options that take a value.
+const OPTIONS: &[&str] = &[
+    "battery",
+    "battery-threshold",
+    "email",
+    "format",
+    "from",
+    "interval",
+    "kind",
+    "lock",
+    "output",
+    "password",
+    "pet",
+    "profile",
+    "slot",
+    "timeout",
+    "to",
+    "unlock",
+    "units",
+];
+
+/// The parsed command line: the words naming the command and its
+/// arguments, and the `--name value` options.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    words: Vec<String>,
+    options: BTreeMap<String, String>,
+}
+
+impl Args {
+    /// Parses the arguments following the program name. Options may be
+    /// written `--name value` or `--name=value`, anywhere on the line; one
+    /// spc does not know fails, so a typo is not silently ignored.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    parsed.words.push(arg);
+                    continue;
+                }
+            };
+            let known = name.split_once('=').map_or(name, |(name, _)| name);
+            if !SWITCHES.contains(&known) && !OPTIONS.contains(&known) {
+                return Err(format!("unknown option --{}, see spc --help", known));
+            }
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None if SWITCHES.contains(&name) => (name.to_string(), String::new()),
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{} needs a value", name))?;
+                    (name.to_string(), value)
+                }
+            };
+            parsed.options.insert(name, value);
+        }
+        Ok(parsed)
+    }
+
+    /// The words of the command line, e.g. `["pets", "list"]`.
+    pub fn words(&self) -> Vec<&str> {
+        self.words.iter().map(String::as_str).collect()
+    }
+
+    /// The value of an option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    /// Whether a switch was given.
+    pub fn switch(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(line: &str) -> Result<Args, String> {
+        Args::parse(line.split(' ').map(String::from))
+    }
+
+    #[test]
+    fn should_parse_words_and_options() {
+        let args = parse("login --email me@example.com --password=a=b --help").unwrap();
+
+        assert_eq!(vec!["login"], args.words());
+        assert_eq!(Some("me@example.com"), args.option("email"));
+        assert_eq!(Some("a=b"), args.option("password"));
+        assert!(args.switch("help"));
+        assert_eq!(
+            Err("--email needs a value".to_string()),
+            parse("login --email")
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_options() {
+        assert_eq!(
+            Err("unknown option --hosuehold, see spc --help".to_string()),
+            parse("pets list --hosuehold 5")
+        );
+        assert_eq!(
+            Err("unknown option --jsno, see spc --help".to_string()),
+            parse("status --jsno=1")
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! The configuration file, holding the credentials `spc login` obtained for
+//! each profile.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The profile used when none is named.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// What `spc` remembers between runs.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Config {
+    /// The accounts logged in to, by profile name.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Files written before profiles existed kept one account at the top
+    /// level; it is read as the default profile.
+    #[serde(default, flatten, skip_serializing)]
+    legacy: Profile,
+}
+
+/// The credentials of one account, with the email address and token left
+/// out of `Debug` output unless built with `debug-secrets`.
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+pub struct Profile {
+    /// The email address last logged in with.
+    #[serde(default)]
+    pub email: Option<String>,
+    /// The device id sent when logging in, generated once.
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// The bearer token.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secret = |value: &Option<String>| match value {
+            Some(_) if !cfg!(feature = "debug-secrets") => Some("REDACTED".to_string()),
+            value => value.clone(),
+        };
+        f.debug_struct("Profile")
+            .field("email", &secret(&self.email))
+            .field("device_id", &self.device_id)
+            .field("token", &secret(&self.token))
+            .finish()
+    }
+}
+
+impl Config {
+    /// The location of the configuration file: `$SPC_CONFIG` when set,
+    /// otherwise `spc/config.json` in the user's configuration directory.
+    pub fn path() -> Result<PathBuf, Box<dyn Error>> {
+        if let Some(path) = std::env::var_os("SPC_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        let directory = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(directory) => PathBuf::from(directory),
+            None => std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+                .ok_or("cannot find a home directory, set SPC_CONFIG")?,
+        };
+        Ok(directory.join("spc").join("config.json"))
+    }
+
+    /// Reads the configuration, which is empty when the file does not exist.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut config: Config = match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|error| format!("{}: {}", path.display(), error))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(error) => return Err(format!("{}: {}", path.display(), error).into()),
+        };
+        let legacy = std::mem::take(&mut config.legacy);
+        if legacy != Profile::default() {
+            config
+                .profiles
+                .entry(DEFAULT_PROFILE.to_string())
+                .or_insert(legacy);
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration, readable only by the current user as it
+    /// holds tokens.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        // The mode above only applies to a new file, so tighten an existing
+        // one before the tokens go in.
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// A profile, empty when it has never been logged in to.
+    pub fn profile(&self, name: &str) -> Profile {
+        self.profiles.get(name).cloned().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_round_trip_through_the_file() {
+        let path = std::env::temp_dir().join(format!("spc-config-{}.json", std::process::id()));
+        assert_eq!(Config::default(), Config::load(&path).unwrap());
+
+        let mut config = Config::default();
+        config.profiles.insert(
+            "work".into(),
+            Profile {
+                email: Some("me@example.com".into()),
+                device_id: Some("spc-1".into()),
+                token: Some("abc".into()),
+            },
+        );
+        config.save(&path).unwrap();
+        assert_eq!(config, Config::load(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_make_an_existing_file_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("spc-private-{}.json", std::process::id()));
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        Config::default().save(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(0o600, mode & 0o777);
+    }
+
+    #[test]
+    fn should_read_files_without_profiles_as_default() {
+        let legacy = std::env::temp_dir().join(format!("spc-legacy-{}.json", std::process::id()));
+        fs::write(&legacy, r#"{"email":"me@example.com","token":"abc"}"#).unwrap();
+        let config = Config::load(&legacy).unwrap();
+        fs::remove_file(&legacy).unwrap();
+
+        assert_eq!(Some("abc".to_string()), config.profile("default").token);
+        assert_eq!(Profile::default(), config.profile("work"));
+    }
+}
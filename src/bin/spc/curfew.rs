@@ -0,0 +1,192 @@
+//! `spc curfew`: showing and changing flap curfews.
+use crate::args::Args;
+use crate::{client, flap, output, Result};
+use std::process::ExitCode;
+use sure_petcare::device::{Curfew, Device, Product};
+use sure_petcare::time::TimeOfDay;
+
+const USAGE: &str = "usage: spc curfew show <device> | \
+spc curfew set <device> --lock <HH:MM> --unlock <HH:MM> [--slot <n>] | \
+spc curfew clear <device> [--slot <n>]";
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    let (action, name) = match args.words()[1..] {
+        [action, name] => (action, name),
+        _ => return Err(USAGE.into()),
+    };
+    let client = client(args)?;
+    let devices = client.devices().list()?;
+    let device = flap::find(&devices, name)?;
+    let slot = match args.option("slot") {
+        Some(slot) => Some(parse_slot(device, slot)?),
+        None => None,
+    };
+    let current = device.control().curfews();
+
+    let curfews = match action {
+        "show" => {
+            show(args, current)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        "set" => {
+            let lock = time(args, "lock")?;
+            let unlock = time(args, "unlock")?;
+            set(current, slot.unwrap_or(1), Curfew::new(lock, unlock))?
+        }
+        "clear" => clear(device.product(), current, slot)?,
+        _ => return Err(USAGE.into()),
+    };
+    client.devices().set_curfews(device, &curfews)?;
+    show(args, &curfews)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn show(args: &Args, curfews: &[Curfew]) -> Result<()> {
+    if args.switch("json") {
+        let curfews: Vec<_> = curfews
+            .iter()
+            .enumerate()
+            .map(|(index, curfew)| {
+                serde_json::json!({
+                    "slot": index + 1,
+                    "lock_time": curfew.lock_time(),
+                    "unlock_time": curfew.unlock_time(),
+                    "enabled": curfew.enabled(),
+                })
+            })
+            .collect();
+        return output::json(&curfews);
+    }
+    let rows: Vec<Vec<String>> = curfews
+        .iter()
+        .enumerate()
+        .map(|(index, curfew)| {
+            vec![
+                (index + 1).to_string(),
+                curfew.lock_time().to_string(),
+                curfew.unlock_time().to_string(),
+                if curfew.enabled() { "on" } else { "off" }.to_string(),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        output::table(&["Slot", "Lock", "Unlock", "Enabled"], &rows)
+    );
+    Ok(())
+}
+
+fn time(args: &Args, name: &str) -> Result<TimeOfDay> {
+    let value = args
+        .option(name)
+        .ok_or_else(|| format!("--{} is required, e.g. --{} 21:00", name, name))?;
+    Ok(value
+        .parse()
+        .map_err(|_| format!("--{} must be a time like 21:00, not `{}`", name, value))?)
+}
+
+/// Checks a one based slot number against what the device supports.
+fn parse_slot(device: &Device<'_>, slot: &str) -> Result<usize> {
+    let slot: usize = slot
+        .parse()
+        .map_err(|_| format!("--slot must be a number, not `{}`", slot))?;
+    match device.product().curfew_slots() {
+        0 => Err(format!("{} does not support curfews", device.name()).into()),
+        1 if slot != 1 => Err(format!(
+            "{} is a pet door, which has a single curfew slot",
+            device.name()
+        )
+        .into()),
+        slots if slot == 0 || slot > slots => Err(format!(
+            "{} has curfew slots 1 to {}, not {}",
+            device.name(),
+            slots,
+            slot
+        )
+        .into()),
+        _ => Ok(slot),
+    }
+}
+
+/// The curfews after putting `curfew` in a slot.
+fn set(current: &[Curfew], slot: usize, curfew: Curfew) -> Result<Vec<Curfew>> {
+    let mut curfews = current.to_vec();
+    match slot - 1 {
+        index if index < curfews.len() => curfews[index] = curfew,
+        index if index == curfews.len() => curfews.push(curfew),
+        _ => {
+            return Err(format!(
+                "slot {} is not in use, the next free slot is {}",
+                slot,
+                curfews.len() + 1
+            )
+            .into())
+        }
+    }
+    Ok(curfews)
+}
+
+/// The curfews after clearing one slot, or all of them. Pet doors always
+/// keep their curfew, so it is switched off instead.
+fn clear(product: Product, current: &[Curfew], slot: Option<usize>) -> Result<Vec<Curfew>> {
+    if product == Product::PetDoor {
+        return Ok(current.iter().map(|curfew| curfew.disabled()).collect());
+    }
+    match slot {
+        None => Ok(Vec::new()),
+        Some(slot) if slot <= current.len() => {
+            let mut curfews = current.to_vec();
+            curfews.remove(slot - 1);
+            Ok(curfews)
+        }
+        Some(slot) => Err(format!("slot {} is not in use", slot).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_validate_slots() {
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":1,"name":"Back door","product_id":6},
+                {"id":2,"name":"Garage","product_id":3},
+                {"id":3,"name":"Feeder","product_id":4}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(4, parse_slot(&devices[0], "4").unwrap());
+        assert_eq!(
+            "Back door has curfew slots 1 to 4, not 5",
+            parse_slot(&devices[0], "5").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Garage is a pet door, which has a single curfew slot",
+            parse_slot(&devices[1], "2").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Feeder does not support curfews",
+            parse_slot(&devices[2], "1").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn should_fill_and_clear_slots() {
+        let night = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+        let day = Curfew::new("12:00".parse().unwrap(), "13:00".parse().unwrap());
+
+        let curfews = set(&[night], 2, day).unwrap();
+        assert_eq!(vec![night, day], curfews);
+        assert!(set(&curfews, 4, day).is_err());
+        assert_eq!(
+            vec![day],
+            clear(Product::CatFlap, &curfews, Some(1)).unwrap()
+        );
+        assert_eq!(
+            vec![night.disabled()],
+            clear(Product::PetDoor, &[night], None).unwrap()
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! `spc devices`: a device health table for people and cron jobs.
+use crate::args::Args;
+use crate::{client, output, Result};
+use std::process::ExitCode;
+use sure_petcare::battery::{Calibration, Chemistry};
+use sure_petcare::device::Device;
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    if args.words().len() > 1 {
+        return Err(
+            "usage: spc devices [--battery-threshold <percent>] [--battery alkaline|nimh]".into(),
+        );
+    }
+    let threshold = match args.option("battery-threshold") {
+        Some(percent) => percent.parse().map_err(|_| {
+            format!(
+                "--battery-threshold must be a percentage, not `{}`",
+                percent
+            )
+        })?,
+        None => 20.0,
+    };
+    let calibration = match args.option("battery") {
+        Some(chemistry) => chemistry.parse::<Chemistry>()?.calibration(),
+        None => Calibration::default(),
+    };
+
+    let devices = client(args)?.devices().list()?;
+    if args.switch("json") {
+        let devices: Vec<_> = devices
+            .iter()
+            .map(|device| {
+                let status = device.status();
+                serde_json::json!({
+                    "id": device.id(),
+                    "name": device.name(),
+                    "product": output::product(device.product()),
+                    "online": status.online(),
+                    "battery_percent": status.battery_percent_with(&calibration),
+                    "rssi": status.signal().and_then(|signal| signal.device_rssi()),
+                    "firmware": status.firmware(),
+                    "problem": problem(device, threshold, &calibration),
+                })
+            })
+            .collect();
+        output::json(&devices)?;
+    } else {
+        let rows: Vec<Vec<String>> = devices
+            .iter()
+            .map(|device| row(device, &calibration))
+            .collect();
+        print!(
+            "{}",
+            output::table(
+                &["Device", "Product", "Status", "Battery", "RSSI", "Firmware"],
+                &rows
+            )
+        );
+    }
+
+    let problems: Vec<String> = devices
+        .iter()
+        .filter_map(|device| problem(device, threshold, &calibration))
+        .collect();
+    for problem in &problems {
+        eprintln!("spc: {}", problem);
+    }
+    // A distinct code lets scripts tell unhealthy devices from errors.
+    Ok(if problems.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(2)
+    })
+}
+
+fn row(device: &Device<'_>, calibration: &Calibration) -> Vec<String> {
+    let status = device.status();
+    vec![
+        device.name().to_string(),
+        output::product(device.product()),
+        match status.online() {
+            Some(true) => "online".to_string(),
+            Some(false) => "OFFLINE".to_string(),
+            None => String::new(),
+        },
+        status
+            .battery_percent_with(calibration)
+            .map_or_else(String::new, |percent| format!("{:.0}%", percent)),
+        status
+            .signal()
+            .and_then(|signal| signal.device_rssi())
+            .map_or_else(String::new, |rssi| format!("{} dBm", rssi)),
+        status.firmware().unwrap_or_default(),
+    ]
+}
+
+/// Why a device needs attention, if it does.
+pub(crate) fn problem(
+    device: &Device<'_>,
+    threshold: f64,
+    calibration: &Calibration,
+) -> Option<String> {
+    let status = device.status();
+    if status.online() == Some(false) {
+        return Some(format!("{} is offline", device.name()));
+    }
+    match status.battery_percent_with(calibration) {
+        Some(percent) if percent < threshold => {
+            Some(format!("{} battery is at {:.0}%", device.name(), percent))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_report_unhealthy_devices() {
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":1,"name":"Hub","product_id":1,"status":{"online":true,
+                  "version":{"device":{"firmware":"2.43"}}}},
+                {"id":2,"name":"Back door","product_id":6,
+                 "status":{"online":true,"battery":5.0,"signal":{"device_rssi":-70}}},
+                {"id":3,"name":"Feeder","product_id":4,"status":{"online":false,"battery":6.0}}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec!["Hub", "Hub", "online", "", "", "2.43"],
+            row(&devices[0], &Calibration::default())
+        );
+        assert_eq!("-70 dBm", row(&devices[1], &Calibration::default())[4]);
+        assert_eq!(None, problem(&devices[0], 20.0, &Calibration::default()));
+        assert_eq!(
+            Some("Back door battery is at 17%".to_string()),
+            problem(&devices[1], 20.0, &Calibration::default())
+        );
+        assert_eq!(
+            None,
+            problem(&devices[1], 20.0, &Chemistry::NiMh.calibration())
+        );
+        assert_eq!(
+            Some("Feeder is offline".to_string()),
+            problem(&devices[2], 20.0, &Calibration::default())
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! `spc export`: writing timeline history with the library exporters.
+use crate::args::Args;
+use crate::{client, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+use sure_petcare::export::{csv, influx, jsonl::JsonLines};
+use sure_petcare::time::{Date, DateRange, Timestamp};
+use sure_petcare::timeline::Event;
+
+const USAGE: &str =
+    "usage: spc export --format csv|jsonl|influx [--from <date>] [--to <date>] [--output <file>]";
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    if args.words().len() > 1 {
+        return Err(USAGE.into());
+    }
+    let format = args.option("format").unwrap_or("csv");
+    if !["csv", "jsonl", "influx"].contains(&format) {
+        return Err(format!("unknown format `{}`, {}", format, USAGE).into());
+    }
+    let client = client(args)?;
+    let range = range(args.option("from"), args.option("to"), client.clock().now())?;
+    let mut events: Vec<Event<'static>> = Vec::new();
+    for household in client.start()?.households() {
+        events.extend(
+            client
+                .timeline()
+                .since(household.id(), range.start())?
+                .into_iter()
+                .filter(|event| event.created_at() <= range.end()),
+        );
+    }
+    events.sort_by_key(Event::id);
+    events.dedup_by_key(|event| event.id());
+
+    let writer: Box<dyn Write> = match args.option("output") {
+        Some(path) => Box::new(File::create(path).map_err(|error| format!("{}: {}", path, error))?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = BufWriter::new(writer);
+    match format {
+        "csv" => csv::write_events(&events, &mut writer)?,
+        "jsonl" => JsonLines::new().write_events(&events, &mut writer)?,
+        _ => influx::write_events(&events, &mut writer)?,
+    }
+    writer.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The dates to export, both inclusive, defaulting to the last week.
+fn range(from: Option<&str>, to: Option<&str>, now: Timestamp) -> Result<DateRange> {
+    let week = DateRange::last_days(7, now);
+    let parse = |value: Option<&str>, default: Date| match value {
+        Some(value) => value
+            .parse::<Date>()
+            .map_err(|error| format!("`{}` is not a date like 2024-01-31: {}", value, error)),
+        None => Ok(default),
+    };
+    Ok(DateRange::new(
+        parse(from, week.from())?,
+        parse(to, week.to())?,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_ranges() {
+        let now = Timestamp::from_unix(1_706_745_600);
+        let range = range(Some("2024-01-01"), Some("2024-02-01"), now).unwrap();
+        assert_eq!(32, range.len());
+        assert_eq!(7, super::range(None, None, now).unwrap().len());
+        assert!(super::range(Some("2024-02-01"), Some("2024-01-01"), now).is_err());
+        assert!(super::range(Some("January"), None, now).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! `spc flap`: changing the lock mode of a flap.
+use crate::args::Args;
+use crate::{client, output, Result};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant};
+use sure_petcare::device::{Device, LockMode, Product};
+use sure_petcare::fold::NameMatch;
+
+const USAGE: &str =
+    "usage: spc flap lock-in|lock-out|lock|unlock <device> [--wait] [--timeout <seconds>]";
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    let (mode, name) = match args.words()[1..] {
+        ["lock-in", name] => (LockMode::KeepIn, name),
+        ["lock-out", name] => (LockMode::KeepOut, name),
+        ["lock", name] => (LockMode::Locked, name),
+        ["unlock", name] => (LockMode::Unlocked, name),
+        _ => return Err(USAGE.into()),
+    };
+    let timeout = match args.option("timeout") {
+        Some(seconds) => seconds
+            .parse()
+            .map_err(|_| format!("--timeout must be a number of seconds, not `{}`", seconds))?,
+        None => 60,
+    };
+
+    let client = client(args)?;
+    let devices = client.devices().list()?;
+    let flap = find(&devices, name)?;
+    client.devices().set_lock_mode(flap.id(), mode)?;
+    let report = |confirmed: bool| {
+        if !args.switch("json") {
+            return Ok(());
+        }
+        output::json(&serde_json::json!({
+            "id": flap.id(),
+            "name": flap.name(),
+            "lock_mode": mode.code(),
+            "confirmed": confirmed,
+        }))
+    };
+    if !args.switch("wait") {
+        report(false)?;
+        eprintln!("Sent {} to {}", describe(mode), flap.name());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // The API accepts the change straight away, but the hub only applies it
+    // the next time the flap checks in.
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    loop {
+        let devices = client.devices().list()?;
+        let current = devices
+            .iter()
+            .find(|device| device.id() == flap.id())
+            .and_then(|device| device.status().lock_mode());
+        if current == Some(mode) {
+            report(true)?;
+            eprintln!("{} is now {}", flap.name(), describe(mode));
+            return Ok(ExitCode::SUCCESS);
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "{} did not confirm {} within {} seconds",
+                flap.name(),
+                describe(mode),
+                timeout
+            )
+            .into());
+        }
+        thread::sleep(Duration::from_secs(2));
+    }
+}
+
+fn describe(mode: LockMode) -> String {
+    match mode {
+        LockMode::Unlocked => "unlocked".to_string(),
+        LockMode::KeepIn => "keeping pets in".to_string(),
+        LockMode::KeepOut => "keeping pets out".to_string(),
+        LockMode::Locked => "locked".to_string(),
+        LockMode::Curfew => "on curfew".to_string(),
+        LockMode::Unknown(code) => format!("in lock mode {}", code),
+    }
+}
+
+/// Finds a flap by name ignoring case and accents, or by id.
+pub fn find<'d>(devices: &'d [Device<'static>], name: &str) -> Result<&'d Device<'static>> {
+    let flaps: Vec<&Device<'static>> = devices
+        .iter()
+        .filter(|device| matches!(device.product(), Product::CatFlap | Product::PetDoor))
+        .collect();
+    let mut matches = flaps.iter().filter(|device| {
+        NameMatch::IgnoreAccents.matches(device.name(), name) || device.id().to_string() == name
+    });
+    match (matches.next(), matches.next()) {
+        (Some(flap), None) => Ok(flap),
+        (Some(_), Some(_)) => Err(format!("more than one flap is called `{}`", name).into()),
+        (None, _) => {
+            let names: Vec<&str> = flaps.iter().map(|flap| flap.name()).collect();
+            Err(format!(
+                "no flap called `{}`, try one of: {}",
+                name,
+                names.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_find_flaps_by_name_or_id() {
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":1,"name":"Hub","product_id":1},
+                {"id":2,"name":"Back door","product_id":6},
+                {"id":3,"name":"Garage","product_id":3}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(2, find(&devices, "back DOOR").unwrap().id().get());
+        assert_eq!(3, find(&devices, "3").unwrap().id().get());
+        assert_eq!(
+            "no flap called `Hub`, try one of: Back door, Garage",
+            find(&devices, "Hub").unwrap_err().to_string()
+        );
+    }
+}
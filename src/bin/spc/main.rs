@@ -0,0 +1,372 @@
+//! `spc`, a command line client for the Sure Petcare API.
+//!
+//! Run `spc login` once to store a token, then `spc status` to see what
+//! the household's pets and devices are up to. Requests are sent with the
+//! `curl` command, which has to be installed.
+mod args;
+mod config;
+mod curfew;
+mod devices;
+mod export;
+mod flap;
+mod output;
+mod pets;
+mod timeline;
+#[cfg(feature = "tui")]
+mod tui;
+
+use args::Args;
+use config::{Config, DEFAULT_PROFILE};
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::process::{Command, ExitCode, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sure_petcare::client::SurePet;
+use sure_petcare::login;
+use sure_petcare::transport::curl::CurlTransport;
+use sure_petcare::units::Units;
+
+pub(crate) type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "\
+usage: spc [--profile <name>] [--json] [--units metric|imperial] <command> [options]
+
+Each profile logs in to its own account. The profile is named with
+--profile or SPC_PROFILE, and is `default` otherwise. With --json every
+command prints JSON to standard output instead of tables. Amounts eaten
+and drunk are shown in grams and millilitres, or in ounces with --units
+imperial or SPC_UNITS=imperial.
+
+commands:
+    login [--email <address>] [--password <password>] [--password-stdin]
+        log in and store the token, reading the password from SPC_PASSWORD
+        when it is not given, from the first line of standard input with
+        --password-stdin, or else asking for it on the terminal
+    status
+        print households, pets and devices
+    profiles
+        list the profiles that have been logged in to
+    pets list
+        print where each pet is and when it was last seen
+    pets set <name> inside|outside
+        correct where a pet is
+    flap lock-in|lock-out|lock|unlock <device> [--wait] [--timeout <seconds>]
+        change how a flap is locked, with --wait polling until the flap has
+        applied the change
+    devices [--battery-threshold <percent>] [--battery alkaline|nimh]
+        print device health, exiting with status 2 when a device is offline
+        or its battery is below the threshold, 20% by default; --battery
+        picks the discharge curve of the cells fitted, alkaline by default
+    curfew show <device>
+    curfew set <device> --lock <HH:MM> --unlock <HH:MM> [--slot <n>]
+    curfew clear <device> [--slot <n>]
+        show or change the curfews of a flap; cat flaps have four slots and
+        pet doors one
+    timeline [--follow] [--interval <seconds>] [--pet <name>] [--kind <kind>]
+        print recent events, with --follow printing new ones as they happen;
+        --json prints one JSON object per line
+    export [--format csv|jsonl|influx] [--from <date>] [--to <date>] [--output <file>]
+        write the events between two dates, both inclusive and defaulting to
+        the last week, to standard output or a file; --json is ignored in
+        favour of --format
+    tui [--interval <seconds>]
+        a full screen dashboard of pets, devices and recent events, when
+        built with the tui feature
+";
+
+fn main() -> ExitCode {
+    sure_petcare::unknown::set_hook(|unknown| {
+        eprintln!(
+            "spc: warning: unrecognised {} {}",
+            unknown.kind(),
+            unknown.code()
+        )
+    });
+    let result = Args::parse(std::env::args().skip(1))
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|args| run(&args));
+    match result {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("spc: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<ExitCode> {
+    if args.switch("help") {
+        print!("{}", USAGE);
+        return Ok(ExitCode::SUCCESS);
+    }
+    match args.words().as_slice() {
+        ["login"] => login(args),
+        ["status"] => status(args),
+        ["profiles"] => profiles(args),
+        ["pets", ..] => pets::run(args),
+        ["flap", ..] => flap::run(args),
+        ["devices", ..] => devices::run(args),
+        ["curfew", ..] => curfew::run(args),
+        ["timeline", ..] => timeline::run(args),
+        ["export", ..] => export::run(args),
+        #[cfg(feature = "tui")]
+        ["tui"] => tui::run(args),
+        [] | ["help"] => {
+            print!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        words => Err(format!("unknown command `{}`, see `spc help`", words.join(" ")).into()),
+    }
+}
+
+/// The name of the profile to use.
+fn profile_name(args: &Args) -> String {
+    args.option("profile")
+        .map(String::from)
+        .or_else(|| std::env::var("SPC_PROFILE").ok())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// The units to show amounts in.
+pub(crate) fn units(args: &Args) -> Result<Units> {
+    match args.option("units") {
+        Some(units) => Ok(units.parse()?),
+        None => match std::env::var("SPC_UNITS") {
+            Ok(units) => Ok(units.parse()?),
+            Err(_) => Ok(Units::Metric),
+        },
+    }
+}
+
+/// Rides out an outage in a polling loop: while the service is unavailable
+/// a warning is printed and the loop sleeps for as long as the API asked, at
+/// least `interval`. Any other error is returned.
+pub(crate) fn wait_out(error: sure_petcare::error::Error, interval: Duration) -> Result<()> {
+    match error {
+        sure_petcare::error::Error::ServiceUnavailable { retry_after } => {
+            let wait = retry_after.unwrap_or_default().max(interval);
+            eprintln!(
+                "spc: the service is unavailable, trying again in {} seconds",
+                wait.as_secs()
+            );
+            thread::sleep(wait);
+            Ok(())
+        }
+        error => Err(error.into()),
+    }
+}
+
+/// A client using the token stored for the profile.
+pub(crate) fn client(args: &Args) -> Result<SurePet<CurlTransport>> {
+    let name = profile_name(args);
+    let config = Config::load(&Config::path()?)?;
+    let token = config
+        .profile(&name)
+        .token
+        .ok_or_else(|| match name.as_str() {
+            DEFAULT_PROFILE => "not logged in, run `spc login` first".to_string(),
+            name => format!(
+                "profile `{}` is not logged in, run `spc --profile {} login` first",
+                name, name
+            ),
+        })?;
+    Ok(SurePet::new(CurlTransport::new(), token))
+}
+
+fn login(args: &Args) -> Result<ExitCode> {
+    let name = profile_name(args);
+    let path = Config::path()?;
+    let mut config = Config::load(&path)?;
+    let mut profile = config.profile(&name);
+
+    let email = match args.option("email") {
+        Some(email) => email.to_string(),
+        None => profile
+            .email
+            .clone()
+            .map_or_else(|| prompt("Email address: "), Ok)?,
+    };
+    let password = match args.option("password") {
+        Some(password) => password.to_string(),
+        None if args.switch("password-stdin") => read_line("--password-stdin")?,
+        None => std::env::var("SPC_PASSWORD").or_else(|_| prompt_password("Password: "))?,
+    };
+    let device_id = profile.device_id.clone().unwrap_or_else(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos());
+        format!("spc-{:x}-{:x}", nanos, std::process::id())
+    });
+
+    let request = login::RequestBuilder::new()
+        .with_email_address(email.as_str())
+        .with_password(password.as_str())
+        .with_device_id(device_id.as_str())
+        .build();
+    let client = SurePet::login(CurlTransport::new(), &request)?;
+
+    profile.email = Some(email);
+    profile.device_id = Some(device_id);
+    profile.token = Some(client.token());
+    config.profiles.insert(name.clone(), profile);
+    config.save(&path)?;
+    if args.switch("json") {
+        output::json(&serde_json::json!({ "profile": name, "config": path }))?;
+    }
+    eprintln!(
+        "Logged in as profile `{}`, token stored in {}",
+        name,
+        path.display()
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn profiles(args: &Args) -> Result<ExitCode> {
+    let config = Config::load(&Config::path()?)?;
+    if args.switch("json") {
+        let profiles: Vec<_> = config
+            .profiles
+            .iter()
+            .map(|(name, profile)| {
+                serde_json::json!({
+                    "profile": name,
+                    "email": profile.email,
+                    "logged_in": profile.token.is_some(),
+                })
+            })
+            .collect();
+        output::json(&profiles)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let rows: Vec<Vec<String>> = config
+        .profiles
+        .iter()
+        .map(|(name, profile)| {
+            vec![
+                name.clone(),
+                profile.email.clone().unwrap_or_default(),
+                if profile.token.is_some() { "yes" } else { "no" }.to_string(),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        output::table(&["Profile", "Email", "Logged in"], &rows)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn status(args: &Args) -> Result<ExitCode> {
+    let state = client(args)?.start()?;
+    if args.switch("json") {
+        output::json(&state)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    for household in state.households() {
+        println!("Household {} ({})", household.name(), household.id());
+    }
+
+    let pets: Vec<Vec<String>> = state
+        .pets()
+        .iter()
+        .map(|pet| {
+            let position = pet.position();
+            vec![
+                pet.name().to_string(),
+                position.map_or_else(String::new, |position| {
+                    output::location(position.location())
+                }),
+                position.map_or_else(String::new, |position| position.since().to_string()),
+            ]
+        })
+        .collect();
+    println!();
+    print!("{}", output::table(&["Pet", "Location", "Since"], &pets));
+
+    let devices: Vec<Vec<String>> = state
+        .devices()
+        .iter()
+        .map(|device| {
+            let status = device.status();
+            vec![
+                device.name().to_string(),
+                output::product(device.product()),
+                match status.online() {
+                    Some(true) => "online".to_string(),
+                    Some(false) => "offline".to_string(),
+                    None => String::new(),
+                },
+                status
+                    .battery_percent()
+                    .map_or_else(String::new, |percent| format!("{:.0}%", percent)),
+            ]
+        })
+        .collect();
+    println!();
+    print!(
+        "{}",
+        output::table(&["Device", "Product", "Status", "Battery"], &devices)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Asks for a line on standard input.
+fn prompt(question: &str) -> Result<String> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    read_line(question.trim_end())
+}
+
+/// Asks for a password on the terminal without echoing it. Standard input
+/// that is not a terminal is refused, since the password would be read from
+/// wherever it comes from without the user seeing a prompt.
+fn prompt_password(question: &str) -> Result<String> {
+    let _echo = EchoOff::new().ok_or(
+        "standard input is not a terminal, give the password with SPC_PASSWORD \
+         or --password-stdin",
+    )?;
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    let line = read_line(question.trim_end());
+    eprintln!();
+    line
+}
+
+/// Reads a line from standard input, failing on an empty one.
+fn read_line(what: &str) -> Result<String> {
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    let line = line.trim().to_string();
+    if line.is_empty() {
+        return Err(format!("no answer to `{}`", what).into());
+    }
+    Ok(line)
+}
+
+/// Turns off terminal echo until dropped.
+struct EchoOff;
+
+impl EchoOff {
+    /// `None` when standard input is not a terminal `stty` can change.
+    fn new() -> Option<Self> {
+        let status = Command::new("stty")
+            .arg("-echo")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .status()
+            .ok()?;
+        status.success().then_some(EchoOff)
+    }
+}
+
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        let _ = Command::new("stty")
+            .arg("echo")
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
@@ -0,0 +1,79 @@
+//! Human readable output, and the JSON printed instead with `--json`.
+use serde::Serialize;
+use sure_petcare::device::Product;
+use sure_petcare::pet::Location;
+
+/// Lays rows out in columns under a header row.
+pub fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string() + "\n"
+    };
+    let mut output = line(headers.to_vec());
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
+
+/// Prints a value as one line of JSON.
+pub fn json<S: Serialize + ?Sized>(value: &S) -> crate::Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// How a location is shown.
+pub fn location(location: Location) -> String {
+    match location {
+        Location::Inside => "inside".to_string(),
+        Location::Outside => "outside".to_string(),
+        Location::Unknown(code) => format!("unknown ({})", code),
+    }
+}
+
+/// How a product is shown.
+pub fn product(product: Product) -> String {
+    match product {
+        Product::Hub => "Hub".to_string(),
+        Product::Repeater => "Repeater".to_string(),
+        Product::PetDoor => "Pet door".to_string(),
+        Product::Feeder => "Feeder".to_string(),
+        Product::Programmer => "Programmer".to_string(),
+        Product::CatFlap => "Cat flap".to_string(),
+        Product::FeederLite => "Feeder Lite".to_string(),
+        Product::Felaqua => "Felaqua".to_string(),
+        Product::Unknown(code) => format!("Product {}", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_align_columns() {
+        let rows = vec![
+            vec!["Milo".to_string(), "inside".to_string()],
+            vec!["Cleo".to_string(), "".to_string()],
+        ];
+        assert_eq!(
+            "Pet   Location\nMilo  inside\nCleo\n",
+            table(&["Pet", "Location"], &rows)
+        );
+    }
+}
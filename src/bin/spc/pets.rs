@@ -0,0 +1,136 @@
+//! `spc pets`: listing pets and correcting where they are.
+use crate::args::Args;
+use crate::{client, output, Result};
+use std::process::ExitCode;
+use sure_petcare::fold::NameMatch;
+use sure_petcare::humanize::Humanizer;
+use sure_petcare::pet::{Location, Pet};
+use sure_petcare::time::Timestamp;
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    match args.words()[1..] {
+        ["list"] | [] => list(args),
+        ["set", name, location] => set(args, name, location),
+        _ => Err("usage: spc pets list | spc pets set <name> inside|outside".into()),
+    }
+}
+
+fn list(args: &Args) -> Result<ExitCode> {
+    let client = client(args)?;
+    let pets = client.pets().list()?;
+    if args.switch("json") {
+        let pets: Vec<_> = pets
+            .iter()
+            .map(|pet| {
+                let position = pet.position();
+                serde_json::json!({
+                    "id": pet.id(),
+                    "name": pet.name(),
+                    "location": position.map(|position| output::location(position.location())),
+                    "since": position.map(|position| position.since()),
+                    "last_seen": last_seen(pet),
+                })
+            })
+            .collect();
+        output::json(&pets)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let humanizer = Humanizer::new(Timestamp::now()).with_zone_of(&client.start()?);
+    let rows: Vec<Vec<String>> = pets
+        .iter()
+        .map(|pet| {
+            let position = pet.position();
+            vec![
+                pet.name().to_string(),
+                position.map_or_else(String::new, |position| {
+                    output::location(position.location())
+                }),
+                position.map_or_else(String::new, |position| humanizer.local(position.since())),
+                last_seen(pet).map_or_else(String::new, |at| humanizer.relative(at)),
+            ]
+        })
+        .collect();
+    print!(
+        "{}",
+        output::table(&["Pet", "Location", "Since", "Last seen"], &rows)
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn set(args: &Args, name: &str, location: &str) -> Result<ExitCode> {
+    let location = match location.to_ascii_lowercase().as_str() {
+        "in" => Location::Inside,
+        "out" => Location::Outside,
+        other => match other.parse() {
+            Ok(location @ (Location::Inside | Location::Outside)) => location,
+            _ => return Err(format!("unknown location `{}`, use inside or outside", other).into()),
+        },
+    };
+    let client = client(args)?;
+    let pets = client.pets().list()?;
+    let pet = find(&pets, name)?;
+    client
+        .pets()
+        .set_location(pet.id(), location, Timestamp::now())?;
+    if args.switch("json") {
+        output::json(&serde_json::json!({
+            "id": pet.id(),
+            "name": pet.name(),
+            "location": output::location(location),
+        }))?;
+    }
+    eprintln!("{} is now {}", pet.name(), output::location(location));
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The latest time the pet used a flap, feeder or water dispenser.
+fn last_seen(pet: &Pet<'_>) -> Option<Timestamp> {
+    let status = pet.status();
+    let visits = [status.feeding(), status.drinking()];
+    pet.position()
+        .map(|position| position.since())
+        .into_iter()
+        .chain(visits.iter().flatten().map(|visit| visit.at()))
+        .max()
+}
+
+/// Finds a pet by name, ignoring case and accents.
+pub fn find<'p>(pets: &'p [Pet<'static>], name: &str) -> Result<&'p Pet<'static>> {
+    let mut matches = pets
+        .iter()
+        .filter(|pet| NameMatch::IgnoreAccents.matches(pet.name(), name));
+    match (matches.next(), matches.next()) {
+        (Some(pet), None) => Ok(pet),
+        (Some(_), Some(_)) => Err(format!("more than one pet is called `{}`", name).into()),
+        (None, _) => {
+            let names: Vec<&str> = pets.iter().map(Pet::name).collect();
+            Err(format!("no pet called `{}`, try one of: {}", name, names.join(", ")).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_find_pets_ignoring_case() {
+        let pets: Vec<Pet<'static>> = serde_json::from_str(
+            r#"[{"id":1,"name":"Milo","position":{"where":1,"since":"2019-06-01T07:00:00Z"},
+                 "status":{"feeding":{"at":"2019-06-01T08:00:00Z"}}},
+                {"id":2,"name":"Cleo"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(1, find(&pets, "MILO").unwrap().id().get());
+        assert_eq!(
+            "no pet called `Tom`, try one of: Milo, Cleo",
+            find(&pets, "Tom").unwrap_err().to_string()
+        );
+        assert_eq!(
+            Some("2019-06-01T08:00:00Z".parse().unwrap()),
+            last_seen(&pets[0])
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! `spc timeline`: printing and following the household timeline.
+use crate::args::Args;
+use crate::{client, output, units, wait_out, Result};
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+use sure_petcare::client::SurePet;
+use sure_petcare::export::event_summary_in;
+use sure_petcare::export::jsonl::JsonLines;
+use sure_petcare::fold::NameMatch;
+use sure_petcare::humanize::Humanizer;
+use sure_petcare::id::HouseholdId;
+use sure_petcare::time::Timestamp;
+use sure_petcare::timeline::{Direction, Event, EventKind};
+use sure_petcare::transport::curl::CurlTransport;
+use sure_petcare::units::Units;
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    if args.words().len() > 1 {
+        return Err(
+            "usage: spc timeline [--follow] [--interval <seconds>] [--pet <name>] [--kind <kind>]"
+                .into(),
+        );
+    }
+    let interval = match args.option("interval") {
+        Some(seconds) => seconds
+            .parse()
+            .map_err(|_| format!("--interval must be a number of seconds, not `{}`", seconds))?,
+        None => 30,
+    };
+    let filter = Filter {
+        pet: args
+            .option("pet")
+            .map(|name| NameMatch::IgnoreAccents.key(name)),
+        kind: args.option("kind").map(str::parse).transpose()?,
+    };
+    let units = units(args)?;
+    let colour = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    let client = client(args)?;
+    let state = client.start()?;
+    let households: Vec<HouseholdId> = state
+        .households()
+        .iter()
+        .map(|household| household.id())
+        .collect();
+    let mut last_id = None;
+    loop {
+        let mut events = match fetch(&client, &households) {
+            Ok(events) => events,
+            Err(error) if args.switch("follow") => {
+                wait_out(error, Duration::from_secs(interval))?;
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+        events.sort_by_key(Event::id);
+        events.dedup_by_key(|event| event.id());
+        let humanizer = Humanizer::new(Timestamp::now()).with_zone_of(&state);
+        for event in &events {
+            if last_id < Some(event.id()) && filter.matches(event) {
+                if args.switch("json") {
+                    output::json(&JsonLines::new().event(event))?;
+                } else {
+                    println!("{}", line(event, colour, units, &humanizer));
+                }
+            }
+        }
+        last_id = events.last().map(Event::id).max(last_id);
+
+        if !args.switch("follow") {
+            return Ok(ExitCode::SUCCESS);
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// The latest page of events of every household.
+fn fetch(
+    client: &SurePet<CurlTransport>,
+    households: &[HouseholdId],
+) -> sure_petcare::error::Result<Vec<Event<'static>>> {
+    let mut events = Vec::new();
+    for household in households {
+        events.extend(client.timeline().household(*household)?);
+    }
+    Ok(events)
+}
+
+/// The `--pet` filter, keyed to ignore case and accents, and the `--kind`
+/// filter.
+struct Filter {
+    pet: Option<String>,
+    kind: Option<EventKind>,
+}
+
+impl Filter {
+    fn matches(&self, event: &Event<'_>) -> bool {
+        let pet = self.pet.as_ref().is_none_or(|name| {
+            event
+                .pets()
+                .iter()
+                .any(|pet| NameMatch::IgnoreAccents.key(pet.name()) == *name)
+        });
+        let kind = self.kind.is_none_or(|kind| kind == event.kind());
+        pet && kind
+    }
+}
+
+/// A line such as `18:42 Milo came in through Kitchen flap`, with the
+/// time in the humanizer's zone.
+pub(crate) fn line(event: &Event<'_>, colour: bool, units: Units, humanizer: &Humanizer) -> String {
+    let time = humanizer.time(event.created_at());
+    let summary = event_summary_in(event, units);
+    if !colour {
+        return format!("{} {}", time, summary);
+    }
+    let direction = event
+        .movements()
+        .first()
+        .map(|movement| movement.direction());
+    let code = match (event.kind(), direction) {
+        (EventKind::Movement, Some(Direction::In)) => "32",
+        (EventKind::Movement, Some(Direction::Out)) => "33",
+        (EventKind::Feeding, _) => "36",
+        (EventKind::Drinking, _) => "34",
+        (EventKind::IntruderMovement, _) | (EventKind::LowBattery, _) => "31",
+        _ => "0",
+    };
+    format!("\x1b[2m{}\x1b[0m \x1b[{}m{}\x1b[0m", time, code, summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use sure_petcare::zone::Zone;
+
+    #[test]
+    fn should_filter_and_format_events() {
+        let event: Event<'static> = serde_json::from_str(
+            r#"{"id":1,"type":0,"created_at":"2019-06-01T18:42:10Z","pets":[{"id":2,"name":"Milo"}],
+                "devices":[{"id":3,"name":"Kitchen flap"}],"movements":[{"direction":1}]}"#,
+        )
+        .unwrap();
+        let humanizer = Humanizer::new(event.created_at()).with_zone(Zone::fixed(3600));
+        let filter = |pet: Option<&str>, kind: Option<&str>| Filter {
+            pet: pet.map(String::from),
+            kind: kind.map(|kind| kind.parse().unwrap()),
+        };
+
+        assert_eq!(
+            "19:42 Milo came in through Kitchen flap",
+            line(&event, false, Units::Metric, &humanizer)
+        );
+        assert_eq!(
+            "\x1b[2m19:42\x1b[0m \x1b[32mMilo came in through Kitchen flap\x1b[0m",
+            line(&event, true, Units::Metric, &humanizer)
+        );
+        assert!(filter(Some("milo"), Some("movement")).matches(&event));
+        assert!(!filter(Some("cleo"), None).matches(&event));
+        assert!(!filter(None, Some("feeding")).matches(&event));
+    }
+}
@@ -0,0 +1,162 @@
+//! `spc tui`: a full screen dashboard for a wall mounted tablet or spare
+//! terminal, enabled with the `tui` feature.
+//!
+//! The screen is redrawn with ANSI escape codes after every poll; press
+//! Ctrl-C to leave.
+use crate::args::Args;
+use crate::{client, devices, output, timeline, units, wait_out, Result};
+use std::io::{self, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+use sure_petcare::battery::Calibration;
+use sure_petcare::client::SurePet;
+use sure_petcare::humanize::Humanizer;
+use sure_petcare::pet::Location;
+use sure_petcare::state::State;
+use sure_petcare::time::Timestamp;
+use sure_petcare::timeline::Event;
+use sure_petcare::transport::curl::CurlTransport;
+use sure_petcare::units::Units;
+
+/// How many events the dashboard shows.
+const RECENT_EVENTS: usize = 10;
+
+pub fn run(args: &Args) -> Result<ExitCode> {
+    let interval = match args.option("interval") {
+        Some(seconds) => seconds
+            .parse()
+            .map_err(|_| format!("--interval must be a number of seconds, not `{}`", seconds))?,
+        None => 30,
+    };
+    let units = units(args)?;
+    let client = client(args)?;
+    let mut stdout = io::stdout();
+    loop {
+        let (state, mut events) = match fetch(&client) {
+            Ok(fetched) => fetched,
+            Err(error) => {
+                wait_out(error, Duration::from_secs(interval))?;
+                continue;
+            }
+        };
+        events.sort_by_key(|event| std::cmp::Reverse(event.id()));
+        events.truncate(RECENT_EVENTS);
+
+        let frame = render(&state, &events, Timestamp::now(), units);
+        write!(stdout, "\x1b[H\x1b[2J{}", frame)?;
+        stdout.flush()?;
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// The start state and the latest events of every household.
+fn fetch(
+    client: &SurePet<CurlTransport>,
+) -> sure_petcare::error::Result<(State<'static>, Vec<Event<'static>>)> {
+    let state = client.start()?;
+    let mut events = Vec::new();
+    for household in state.households() {
+        events.extend(client.timeline().household(household.id())?);
+    }
+    Ok((state, events))
+}
+
+/// One screen of the dashboard, newest events first.
+fn render(state: &State<'_>, events: &[Event<'_>], now: Timestamp, units: Units) -> String {
+    let mut screen = String::new();
+    let households: Vec<&str> = state
+        .households()
+        .iter()
+        .map(|household| household.name())
+        .collect();
+    let humanizer = Humanizer::new(now).with_zone_of(state);
+    screen.push_str(&format!(
+        "\x1b[1m{}\x1b[0m  \x1b[2mupdated {}\x1b[0m\n\n",
+        households.join(", "),
+        humanizer.time(now)
+    ));
+
+    screen.push_str("\x1b[1mPets\x1b[0m\n");
+    let pets: Vec<Vec<String>> = state
+        .pets()
+        .iter()
+        .map(|pet| {
+            let position = pet.position();
+            let location = position.map_or_else(String::new, |position| {
+                let colour = match position.location() {
+                    Location::Inside => "32",
+                    Location::Outside => "33",
+                    Location::Unknown(_) => "0",
+                };
+                format!(
+                    "\x1b[{}m{}\x1b[0m",
+                    colour,
+                    output::location(position.location())
+                )
+            });
+            let since = position.map_or_else(String::new, |position| {
+                format!("since {}", humanizer.local(position.since()))
+            });
+            vec![pet.name().to_string(), location, since]
+        })
+        .collect();
+    screen.push_str(&indent(&output::table(&["", "", ""], &pets)));
+
+    screen.push_str("\n\x1b[1mDevices\x1b[0m\n");
+    for device in state.devices() {
+        match devices::problem(device, 20.0, &Calibration::default()) {
+            Some(problem) => screen.push_str(&format!("  \x1b[31m{}\x1b[0m\n", problem)),
+            None => screen.push_str(&format!("  {} ok\n", device.name())),
+        }
+    }
+
+    screen.push_str("\n\x1b[1mRecent events\x1b[0m\n");
+    for event in events {
+        screen.push_str(&format!(
+            "  {}\n",
+            timeline::line(event, true, units, &humanizer)
+        ));
+    }
+    screen
+}
+
+/// Indents a table, dropping its empty header row.
+fn indent(table: &str) -> String {
+    table
+        .lines()
+        .skip(1)
+        .map(|line| format!("  {}\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_pets_devices_and_events() {
+        let state: State<'static> = serde_json::from_str(
+            r#"{"households":[{"id":1,"name":"Home"}],
+                "pets":[{"id":2,"name":"Milo","position":{"where":2,"since":"2019-06-01T07:00:00Z"}}],
+                "devices":[{"id":3,"name":"Back door","product_id":6,"status":{"online":false}}]}"#,
+        )
+        .unwrap();
+        let events: Vec<Event<'static>> = serde_json::from_str(
+            r#"[{"id":4,"type":0,"created_at":"2019-06-01T07:00:00Z","pets":[{"id":2,"name":"Milo"}],
+                 "movements":[{"direction":2}]}]"#,
+        )
+        .unwrap();
+        let screen = render(
+            &state,
+            &events,
+            "2019-06-01T08:15:00Z".parse().unwrap(),
+            Units::Metric,
+        );
+
+        assert!(screen.starts_with("\x1b[1mHome\x1b[0m  \x1b[2mupdated 08:15"));
+        assert!(screen.contains("Milo  \x1b[33moutside\x1b[0m  since today 07:00"));
+        assert!(screen.contains("\x1b[31mBack door is offline\x1b[0m"));
+        assert!(screen.contains("07:00\x1b[0m \x1b[33mMilo went out\x1b[0m"));
+    }
+}
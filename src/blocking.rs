@@ -0,0 +1,17 @@
+//! The blocking client, under the name sync code bases tend to look for.
+//!
+//! Every endpoint in this crate is already synchronous, with requests sent
+//! on the calling thread by a [`Transport`](../transport/trait.Transport.html),
+//! so there is no runtime to start and nothing to keep in step with an async
+//! surface. These are the same types as in [`client`](../client/index.html).
+//!
+//! ```no_run
+//! # fn run<T: sure_petcare::transport::Transport>(transport: T) -> sure_petcare::error::Result<()> {
+//! use sure_petcare::blocking::Client;
+//! let client = Client::new(transport, "token");
+//! let state = client.start()?;
+//! # Ok(())
+//! # }
+//! ```
+pub use crate::client::SurePet as Client;
+pub use crate::client::SurePetcare;
@@ -0,0 +1,247 @@
+//! Caching of report data for days that are over, and of pets and devices
+//! for a short while.
+//!
+//! Reports for past days never change, so dashboards that refresh every
+//! minute only need to fetch today. Attach a cache with
+//! [`Reports::with_cache`](../report/struct.Reports.html#method.with_cache).
+//!
+//! Pets and devices do change, but rarely within a few seconds. A
+//! [`StateCache`](struct.StateCache.html) attached with
+//! [`SurePet::with_state_cache`](../client/struct.SurePet.html#method.with_state_cache)
+//! answers repeated lookups of the same pet or device from memory, and drops
+//! an entry as soon as the client changes it.
+use crate::clock::{Clock, SystemClock};
+use crate::device::Device;
+use crate::id::{DeviceId, PetId};
+use crate::pet::Pet;
+#[cfg(feature = "reports")]
+use crate::report::Report;
+#[cfg(feature = "reports")]
+use crate::time::Date;
+use crate::time::Timestamp;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Stores the report of a single pet for a single day.
+///
+/// Only days before the current day are ever stored.
+#[cfg(feature = "reports")]
+pub trait ReportCache {
+    /// Looks up the report for a pet on a day.
+    fn get(&self, pet_id: PetId, date: Date) -> Option<Report>;
+
+    /// Stores the report for a pet on a day.
+    fn put(&self, pet_id: PetId, date: Date, report: Report);
+}
+
+/// An in-memory cache that forgets entries after a time to live.
+#[cfg(feature = "reports")]
+pub struct MemoryCache {
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+    entries: Mutex<HashMap<(PetId, Date), (Timestamp, Report)>>,
+}
+
+#[cfg(feature = "reports")]
+impl MemoryCache {
+    /// Creates an empty cache whose entries live for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        MemoryCache {
+            ttl,
+            clock: Arc::new(SystemClock),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the system clock entries are timed with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The number of entries, including any that have expired but not yet
+    /// been evicted.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every entry.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(feature = "reports")]
+impl fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryCache")
+            .field("ttl", &self.ttl)
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "reports")]
+impl ReportCache for MemoryCache {
+    fn get(&self, pet_id: PetId, date: Date) -> Option<Report> {
+        fresh(&self.entries, (pet_id, date), self.clock.now(), self.ttl)
+    }
+
+    fn put(&self, pet_id: PetId, date: Date, report: Report) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((pet_id, date), (self.clock.now(), report));
+    }
+}
+
+/// Pets and devices fetched by id, kept for a time to live. Clones of a
+/// client share the cache attached to it.
+pub struct StateCache {
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+    pets: Mutex<HashMap<PetId, (Timestamp, Pet<'static>)>>,
+    devices: Mutex<HashMap<DeviceId, (Timestamp, Device<'static>)>>,
+}
+
+impl StateCache {
+    /// Creates an empty cache whose entries live for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        StateCache {
+            ttl,
+            clock: Arc::new(SystemClock),
+            pets: Mutex::new(HashMap::new()),
+            devices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the system clock entries are timed with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Looks up a pet fetched within the time to live.
+    pub fn pet(&self, pet_id: PetId) -> Option<Pet<'static>> {
+        fresh(&self.pets, pet_id, self.clock.now(), self.ttl)
+    }
+
+    /// Stores a pet.
+    pub fn put_pet(&self, pet: Pet<'static>) {
+        let now = self.clock.now();
+        self.pets.lock().unwrap().insert(pet.id(), (now, pet));
+    }
+
+    /// Drops a pet, so the next lookup fetches it again.
+    pub fn invalidate_pet(&self, pet_id: PetId) {
+        self.pets.lock().unwrap().remove(&pet_id);
+    }
+
+    /// Looks up a device fetched within the time to live.
+    pub fn device(&self, device_id: DeviceId) -> Option<Device<'static>> {
+        fresh(&self.devices, device_id, self.clock.now(), self.ttl)
+    }
+
+    /// Stores a device.
+    pub fn put_device(&self, device: Device<'static>) {
+        let now = self.clock.now();
+        self.devices
+            .lock()
+            .unwrap()
+            .insert(device.id(), (now, device));
+    }
+
+    /// Drops a device, so the next lookup fetches it again.
+    pub fn invalidate_device(&self, device_id: DeviceId) {
+        self.devices.lock().unwrap().remove(&device_id);
+    }
+
+    /// Drops every entry.
+    pub fn clear(&self) {
+        self.pets.lock().unwrap().clear();
+        self.devices.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for StateCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateCache")
+            .field("ttl", &self.ttl)
+            .field("pets", &self.pets.lock().unwrap().len())
+            .field("devices", &self.devices.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// The value stored under `key` if it is younger than `ttl`, evicting it
+/// otherwise.
+fn fresh<K: Eq + Hash, V: Clone>(
+    entries: &Mutex<HashMap<K, (Timestamp, V)>>,
+    key: K,
+    now: Timestamp,
+    ttl: Duration,
+) -> Option<V> {
+    let mut entries = entries.lock().unwrap();
+    match entries.get(&key) {
+        Some((stored, value)) if (now.seconds_since(*stored) as u64) < ttl.as_secs() => {
+            Some(value.clone())
+        }
+        Some(_) => {
+            entries.remove(&key);
+            None
+        }
+        None => None,
+    }
+}
+
+#[cfg(feature = "reports")]
+impl<C: ReportCache + ?Sized> ReportCache for &C {
+    fn get(&self, pet_id: PetId, date: Date) -> Option<Report> {
+        (**self).get(pet_id, date)
+    }
+
+    fn put(&self, pet_id: PetId, date: Date, report: Report) {
+        (**self).put(pet_id, date, report)
+    }
+}
+
+#[cfg(all(test, feature = "reports"))]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_expire_entries() {
+        let date = Date::new(2019, 6, 1).unwrap();
+        let cache = MemoryCache::new(Duration::from_secs(60));
+        cache.put(PetId::new(2), date, Report::default());
+        assert!(cache.get(PetId::new(2), date).is_some());
+        assert!(cache.get(PetId::new(3), date).is_none());
+
+        let expired = MemoryCache::new(Duration::from_secs(0));
+        expired.put(PetId::new(2), date, Report::default());
+        assert!(expired.get(PetId::new(2), date).is_none());
+        assert_eq!(0, expired.len());
+    }
+
+    #[test]
+    fn should_expire_entries_by_the_clock() {
+        let date = Date::new(2019, 6, 1).unwrap();
+        let clock = Arc::new(ManualClock::new(date.midnight()));
+        let cache = MemoryCache::new(Duration::from_secs(60)).with_clock(clock.clone());
+        cache.put(PetId::new(2), date, Report::default());
+        clock.advance(Duration::from_secs(59));
+        assert!(cache.get(PetId::new(2), date).is_some());
+        clock.advance(Duration::from_secs(1));
+        assert!(cache.get(PetId::new(2), date).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+//! Serialization of the models with a different field casing.
+//!
+//! The models serialize with the API's own snake case field names. Some
+//! consumers mimic the official app's camelCase payloads instead; rather
+//! than keeping a parallel set of types, [`to_value`](fn.to_value.html)
+//! serializes any model and renames the keys of every object on the way.
+use crate::error::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// A field name casing.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Casing {
+    /// `created_at`, as the API sends it.
+    Snake,
+    /// `createdAt`.
+    Camel,
+}
+
+impl Casing {
+    /// Converts a snake case name to this casing.
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Casing::Snake => name.to_string(),
+            Casing::Camel => {
+                let mut output = String::with_capacity(name.len());
+                let mut upper = false;
+                for c in name.chars() {
+                    if c == '_' && !output.is_empty() {
+                        upper = true;
+                    } else if upper {
+                        output.extend(c.to_uppercase());
+                        upper = false;
+                    } else {
+                        output.push(c);
+                    }
+                }
+                output
+            }
+        }
+    }
+}
+
+/// Serializes `value` to JSON with every object key in the given casing.
+/// Keys of maps, such as unknown fields kept in `other`, are renamed too.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::casing::{self, Casing};
+/// let value = casing::to_value(&serde_json::json!({"tag_id": 5}), Casing::Camel).unwrap();
+/// assert_eq!(serde_json::json!({"tagId": 5}), value);
+/// ```
+pub fn to_value<T: Serialize + ?Sized>(value: &T, casing: Casing) -> Result<Value> {
+    Ok(rename(serde_json::to_value(value)?, casing))
+}
+
+/// Serializes `value` to a JSON string with every object key in the given
+/// casing.
+pub fn to_string<T: Serialize + ?Sized>(value: &T, casing: Casing) -> Result<String> {
+    Ok(to_value(value, casing)?.to_string())
+}
+
+fn rename(value: Value, casing: Casing) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (casing.apply(&key), rename(value, casing)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| rename(value, casing))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::Pet;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn should_convert_names() {
+        assert_eq!("createdAt", Casing::Camel.apply("created_at"));
+        assert_eq!("deviceRssi", Casing::Camel.apply("device_rssi"));
+        assert_eq!("_private", Casing::Camel.apply("_private"));
+        assert_eq!("created_at", Casing::Snake.apply("created_at"));
+    }
+
+    #[test]
+    fn should_rename_nested_models() {
+        let pet: Pet = serde_json::from_str(
+            r#"{"id":2,"name":"Milo","household_id":1,
+                "position":{"device_id":10,"where":1,"since":"2019-06-01T05:00:00+00:00"}}"#,
+        )
+        .unwrap();
+        let value = to_value(&pet, Casing::Camel).unwrap();
+
+        assert_eq!(json!(1), value["householdId"]);
+        assert_eq!(json!(10), value["position"]["deviceId"]);
+        assert_eq!(None, value.get("household_id"));
+    }
+}
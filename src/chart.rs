@@ -0,0 +1,305 @@
+//! Simple SVG charts of report data.
+//!
+//! A [`Chart`](struct.Chart.html) is a titled series of labelled values
+//! drawn as bars or a line, rendered to a standalone SVG document that can
+//! be written to a file, embedded in an HTML digest or attached to an email
+//! without any script to draw it. The `daily_*` constructors chart the
+//! per-day totals of the [`report`](../report/index.html) module: grams
+//! eaten, millilitres drunk and minutes spent outside.
+use crate::report::{DailyDrinking, DailyFeeding, DailyOutside};
+use crate::time::Date;
+use std::fmt;
+
+const MARGIN_LEFT: f64 = 48.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 32.0;
+const MARGIN_BOTTOM: f64 = 32.0;
+
+/// The most x-axis labels drawn; longer series label every few points.
+const MAX_LABELS: usize = 14;
+
+/// How the values are drawn.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Style {
+    /// One bar per value.
+    Bar,
+    /// A line through the values.
+    Line,
+}
+
+/// A chart of labelled values.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::chart::Chart;
+/// let svg = Chart::bar("Eaten")
+///     .with_unit("g")
+///     .point("Mon", 42.0)
+///     .point("Tue", 38.5)
+///     .svg();
+/// assert!(svg.starts_with("<svg"));
+/// assert_eq!(2, svg.matches("<rect class=\"bar\"").count());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chart {
+    style: Style,
+    title: String,
+    unit: String,
+    width: u32,
+    height: u32,
+    points: Vec<(String, f64)>,
+}
+
+impl Chart {
+    /// An empty bar chart.
+    pub fn bar(title: &str) -> Self {
+        Chart::new(Style::Bar, title)
+    }
+
+    /// An empty line chart.
+    pub fn line(title: &str) -> Self {
+        Chart::new(Style::Line, title)
+    }
+
+    fn new(style: Style, title: &str) -> Self {
+        Chart {
+            style,
+            title: title.to_string(),
+            unit: String::new(),
+            width: 480,
+            height: 240,
+            points: Vec::new(),
+        }
+    }
+
+    /// Grams eaten per day, as bars.
+    pub fn daily_consumption(days: &[DailyFeeding]) -> Self {
+        days.iter()
+            .fold(Chart::bar("Eaten per day").with_unit("g"), |chart, day| {
+                chart.point(&label(day.date()), day.consumed())
+            })
+    }
+
+    /// Millilitres drunk per day, as a line.
+    pub fn daily_water(days: &[DailyDrinking]) -> Self {
+        days.iter().fold(
+            Chart::line("Water per day").with_unit("ml"),
+            |chart, day| chart.point(&label(day.date()), day.consumed()),
+        )
+    }
+
+    /// Minutes spent outside per day, as bars.
+    pub fn daily_outside(days: &[DailyOutside]) -> Self {
+        days.iter().fold(
+            Chart::bar("Time outside per day").with_unit("min"),
+            |chart, day| chart.point(&label(day.date()), day.total() as f64 / 60.0),
+        )
+    }
+
+    /// Labels the values with a unit, shown on the y axis.
+    pub fn with_unit(mut self, unit: &str) -> Self {
+        self.unit = unit.to_string();
+        self
+    }
+
+    /// Sets the size in pixels, 480 by 240 unless given.
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Adds a value. Negative and non-finite values are drawn as `0`.
+    pub fn point(mut self, label: &str, value: f64) -> Self {
+        self.points.push((label.to_string(), value));
+        self
+    }
+
+    /// How the values are drawn.
+    pub fn style(&self) -> Style {
+        self.style
+    }
+
+    /// The labelled values, in order.
+    pub fn points(&self) -> &[(String, f64)] {
+        &self.points
+    }
+
+    /// The chart as an SVG document.
+    pub fn svg(&self) -> String {
+        let (width, height) = (f64::from(self.width), f64::from(self.height));
+        let plot_width = (width - MARGIN_LEFT - MARGIN_RIGHT).max(1.0);
+        let plot_height = (height - MARGIN_TOP - MARGIN_BOTTOM).max(1.0);
+        let bottom = MARGIN_TOP + plot_height;
+        let values: Vec<f64> = self
+            .points
+            .iter()
+            .map(|(_, value)| {
+                if value.is_finite() {
+                    value.max(0.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let top = nice_max(values.iter().copied().fold(0.0, f64::max));
+        let slot = plot_width / values.len().max(1) as f64;
+        let x = |i: usize| MARGIN_LEFT + slot * (i as f64 + 0.5);
+        let y = |value: f64| bottom - value / top * plot_height;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+             viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\" font-size=\"11\">\n",
+            w = self.width,
+            h = self.height
+        );
+        svg.push_str(&format!(
+            "<title>{}</title>\n<text x=\"{:.1}\" y=\"20\" font-size=\"14\">{}</text>\n",
+            escape(&self.title),
+            MARGIN_LEFT,
+            escape(&self.title)
+        ));
+        svg.push_str(&format!(
+            "<line x1=\"{l:.1}\" y1=\"{t:.1}\" x2=\"{l:.1}\" y2=\"{b:.1}\" stroke=\"#999\"/>\n\
+             <line x1=\"{l:.1}\" y1=\"{b:.1}\" x2=\"{r:.1}\" y2=\"{b:.1}\" stroke=\"#999\"/>\n",
+            l = MARGIN_LEFT,
+            t = MARGIN_TOP,
+            b = bottom,
+            r = MARGIN_LEFT + plot_width
+        ));
+        for value in &[0.0, top] {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n",
+                MARGIN_LEFT - 4.0,
+                y(*value) + 4.0,
+                escape(&axis_label(*value, &self.unit))
+            ));
+        }
+
+        match self.style {
+            Style::Bar => {
+                for (i, value) in values.iter().enumerate() {
+                    svg.push_str(&format!(
+                        "<rect class=\"bar\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                         height=\"{:.1}\" fill=\"#4e79a7\"/>\n",
+                        x(i) - slot * 0.4,
+                        y(*value),
+                        slot * 0.8,
+                        bottom - y(*value)
+                    ));
+                }
+            }
+            Style::Line => {
+                let points: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| format!("{:.1},{:.1}", x(i), y(*value)))
+                    .collect();
+                svg.push_str(&format!(
+                    "<polyline class=\"line\" points=\"{}\" fill=\"none\" stroke=\"#4e79a7\" \
+                     stroke-width=\"2\"/>\n",
+                    points.join(" ")
+                ));
+            }
+        }
+
+        let every = self.points.len().div_ceil(MAX_LABELS).max(1);
+        for (i, (label, _)) in self.points.iter().enumerate() {
+            if i % every == 0 {
+                svg.push_str(&format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                    x(i),
+                    bottom + 16.0,
+                    escape(label)
+                ));
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+impl fmt::Display for Chart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.svg())
+    }
+}
+
+/// A day as an x-axis label, such as `06-01`.
+fn label(date: Date) -> String {
+    format!("{:02}-{:02}", date.month(), date.day())
+}
+
+/// The top of the y axis: the smallest 1, 2 or 5 times a power of ten at
+/// least `max`, and `1` for an empty or all-zero chart.
+fn nice_max(max: f64) -> f64 {
+    if max <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(max.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|step| step * magnitude)
+        .find(|top| *top >= max)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn axis_label(value: f64, unit: &str) -> String {
+    let number = if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{}", value)
+    };
+    if unit.is_empty() {
+        number
+    } else {
+        format!("{} {}", number, unit)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Feeding;
+    use crate::time::DateRange;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_draw_daily_totals_scaled_to_the_axis() {
+        let range = DateRange::new(
+            Date::new(2019, 6, 1).unwrap(),
+            Date::new(2019, 6, 3).unwrap(),
+        )
+        .unwrap();
+        let feeding = DailyFeeding::from_datapoints(&Vec::<Feeding>::new(), range);
+        let chart = Chart::daily_consumption(&feeding);
+        assert_eq!(
+            vec!["06-01", "06-02", "06-03"],
+            chart
+                .points()
+                .iter()
+                .map(|(l, _)| l.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, chart.svg().matches("<rect class=\"bar\"").count());
+
+        let svg = Chart::line("Water <ml>")
+            .with_size(164, 164)
+            .point("a", 40.0)
+            .point("b", 100.0)
+            .svg();
+        assert!(svg.contains("<title>Water &lt;ml&gt;</title>"));
+        assert!(svg.contains("points=\"73.0,92.0 123.0,32.0\""));
+        assert!(svg.contains(">100</text>"));
+        assert_eq!(200.0, nice_max(120.0));
+        assert_eq!(1.0, nice_max(0.0));
+    }
+}
@@ -1,6 +1,1557 @@
-#[derive(Clone, Debug, Default)]
-pub struct SurePet {
-    token: String,
+//! The client used to talk to the Sure Petcare API.
+use crate::app::{AppIdentity, APP_VERSION};
+use crate::cache::StateCache;
+use crate::clock::{Clock, SystemClock};
+use crate::compat::{Compatibility, CompatibilityReport};
+use crate::device::Curfew;
+#[cfg(feature = "devices")]
+use crate::device::Devices;
+use crate::error::{Error, Result};
+use crate::id::{DeviceId, HouseholdId};
+use crate::login;
+#[cfg(feature = "notifications")]
+use crate::notification::Notifications;
+#[cfg(feature = "pets")]
+use crate::pet::Pets;
+use crate::photo::Photos;
+use crate::product::ProductCatalogue;
+use crate::ratelimit::{self, Pacer, RateLimit};
+#[cfg(feature = "reports")]
+use crate::report::Reports;
+use crate::secret::{self, Secret};
+use crate::state::State;
+use crate::tag::Tags;
+use crate::time::Timestamp;
+#[cfg(feature = "timeline")]
+use crate::timeline::Timeline;
+use crate::token::{self, TokenChange, TokenEvent, TokenHooks};
+use crate::transport::{self, Method, Transport};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+/// The default location of the Sure Petcare API.
+pub const BASE_URL: &str = "https://app.api.surehub.io";
+
+const LOGIN_PATH: &str = "/api/auth/login";
+
+/// Successful responses wrap their payload in a `data` field.
+///
+/// Parsing an envelope from a response kept with
+/// [`Timeline::page_response`](../timeline/struct.Timeline.html#method.page_response)
+/// lets models such as [`Event`](../timeline/struct.Event.html) borrow their
+/// strings from the body instead of allocating a copy of each.
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "timeline")] {
+/// use sure_petcare::client::ResponseEnvelope;
+/// use sure_petcare::timeline::Event;
+/// let body = br#"{"data":[{"id":1,"type":0,"created_at":"2019-06-01T05:00:00Z",
+///                          "pets":[{"id":2,"name":"Milo"}]}]}"#;
+/// let page: ResponseEnvelope<Vec<Event<'_>>> = ResponseEnvelope::parse(body)?;
+/// assert_eq!("Milo", page.data()[0].pets()[0].name());
+/// # }
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ResponseEnvelope<T> {
+    pub(crate) data: T,
+}
+
+impl<'de, T: Deserialize<'de>> ResponseEnvelope<T> {
+    /// Parses a response body, borrowing from it where the payload allows.
+    pub fn parse(body: &'de [u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+impl<T> ResponseEnvelope<T> {
+    /// The payload.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Takes the payload out of the envelope.
+    pub fn into_data(self) -> T {
+        self.data
+    }
+}
+
+/// An authenticated client for the Sure Petcare API.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "reports")]
+/// # fn run<T: sure_petcare::transport::Transport>(transport: T) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::{client::SurePet, id::{HouseholdId, PetId}, login};
+/// let request = login::RequestBuilder::new()
+///     .with_email_address("email@example.com")
+///     .with_password("qwerty123")
+///     .with_device_id("xxx-xxx-xxx-xxx")
+///     .build();
+/// let client = SurePet::login(transport, &request)?;
+/// let report = client.reports().pet(HouseholdId::new(1234), PetId::new(5678))?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Clones are cheap and share the transport, the token and the last
+/// response, so a client can be handed to threads or kept in web framework
+/// state as it is. Its `Debug` output redacts the token.
+pub struct SurePet<T> {
+    transport: Arc<T>,
+    token: Arc<RwLock<String>>,
+    base_url: Arc<str>,
+    clock: Arc<dyn Clock>,
+    identity: Option<Arc<AppIdentity>>,
+    state_cache: Option<Arc<StateCache>>,
+    max_response_size: Option<usize>,
+    token_leeway: Duration,
+    calibrate_skew: bool,
+    skew: Arc<AtomicI64>,
+    relogin: Option<Arc<Relogin>>,
+    last_response: Arc<LastResponse>,
+    compatibility: Arc<Compatibility>,
+    pacer: Option<Arc<Mutex<Pacer>>>,
+    rate_limit: Arc<Mutex<Option<RateLimit>>>,
+    token_hooks: Arc<TokenHooks>,
+    dry_run: bool,
+    would_have_sent: Arc<Mutex<Vec<transport::Request>>>,
+    extra_headers: Vec<(String, String)>,
+    extra_query: Vec<(String, String)>,
+    /// The curfews of flaps as they were before being switched off, so
+    /// switching them on again restores only the slots that were on.
+    curfews_off: Arc<Mutex<HashMap<DeviceId, Vec<Curfew>>>>,
+}
+
+impl<T> Clone for SurePet<T> {
+    fn clone(&self) -> Self {
+        SurePet {
+            transport: Arc::clone(&self.transport),
+            token: Arc::clone(&self.token),
+            base_url: Arc::clone(&self.base_url),
+            clock: Arc::clone(&self.clock),
+            identity: self.identity.clone(),
+            state_cache: self.state_cache.clone(),
+            max_response_size: self.max_response_size,
+            token_leeway: self.token_leeway,
+            calibrate_skew: self.calibrate_skew,
+            skew: Arc::clone(&self.skew),
+            relogin: self.relogin.clone(),
+            last_response: Arc::clone(&self.last_response),
+            compatibility: Arc::clone(&self.compatibility),
+            pacer: self.pacer.clone(),
+            rate_limit: Arc::clone(&self.rate_limit),
+            token_hooks: Arc::clone(&self.token_hooks),
+            dry_run: self.dry_run,
+            would_have_sent: Arc::clone(&self.would_have_sent),
+            extra_headers: self.extra_headers.clone(),
+            extra_query: self.extra_query.clone(),
+            curfews_off: Arc::clone(&self.curfews_off),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SurePet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SurePet")
+            .field("transport", &self.transport)
+            .field("token", &Secret(&self.token))
+            .field("base_url", &self.base_url)
+            .field("clock", &self.clock)
+            .field("identity", &self.identity)
+            .field("state_cache", &self.state_cache)
+            .field("max_response_size", &self.max_response_size)
+            .field("token_leeway", &self.token_leeway)
+            .field("calibrate_skew", &self.calibrate_skew)
+            .field("skew", &self.skew)
+            .field("relogin", &self.relogin)
+            .field("last_response", &self.last_response)
+            .field("compatibility", &self.compatibility)
+            .field("pacer", &self.pacer)
+            .field("rate_limit", &self.rate_limit)
+            .field("token_hooks", &self.token_hooks)
+            .field("dry_run", &self.dry_run)
+            .field("would_have_sent", &self.would_have_sent)
+            .field("extra_headers", &secret::Headers(&self.extra_headers))
+            .field("extra_query", &secret::Query(&self.extra_query))
+            .field("curfews_off", &self.curfews_off)
+            .finish()
+    }
+}
+
+/// The credentials to log in again with when the token is rejected, and the
+/// login in flight, shared by the clones of a client.
+#[derive(Debug)]
+struct Relogin {
+    request: login::Request<'static>,
+    flight: Mutex<Flight>,
+    landed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct Flight {
+    running: bool,
+    /// Counts finished logins, so waiters know theirs is over.
+    generation: u64,
+}
+
+/// The most recent response any clone of a client received.
+#[derive(Debug, Default)]
+struct LastResponse(Mutex<Option<transport::Response>>);
+
+impl LastResponse {
+    fn get(&self) -> Option<transport::Response> {
+        self.0.lock().ok().and_then(|last| last.clone())
+    }
+
+    fn set(&self, response: &transport::Response) {
+        if let Ok(mut last) = self.0.lock() {
+            *last = Some(response.clone());
+        }
+    }
+}
+
+impl<T: Transport> SurePet<T> {
+    /// Creates a client from a previously obtained bearer token.
+    pub fn new<S: Into<String>>(transport: T, token: S) -> Self {
+        SurePet {
+            transport: Arc::new(transport),
+            token: Arc::new(RwLock::new(token.into())),
+            base_url: BASE_URL.into(),
+            clock: Arc::new(SystemClock),
+            identity: None,
+            state_cache: None,
+            max_response_size: None,
+            token_leeway: Duration::from_secs(60),
+            calibrate_skew: false,
+            skew: Arc::default(),
+            relogin: None,
+            last_response: Arc::default(),
+            compatibility: Arc::default(),
+            pacer: None,
+            rate_limit: Arc::default(),
+            token_hooks: Arc::default(),
+            dry_run: false,
+            would_have_sent: Arc::default(),
+            extra_headers: Vec::new(),
+            extra_query: Vec::new(),
+            curfews_off: Arc::default(),
+        }
+    }
+
+    /// Logs in with the given credentials and returns an authenticated client.
+    pub fn login(transport: T, request: &login::Request) -> Result<Self> {
+        SurePet::new(transport, "").log_in(request)
+    }
+
+    /// Logs in with the given credentials, keeping settings such as the base
+    /// url and app identity for the login request itself.
+    pub fn log_in(self, request: &login::Request) -> Result<Self> {
+        self.set_token("");
+        let response: login::Response = self.send(Method::Post, LOGIN_PATH, &[], Some(request))?;
+        let token = response.access_token();
+        self.set_token(token.as_ref());
+        self.token_hooks.notify(TokenChange::Acquired, &token);
+        Ok(self)
+    }
+
+    /// Calls `hook` with the new token whenever
+    /// [`log_in`](#method.log_in) gives the client one. Register it on a
+    /// client made with [`new`](#method.new) before logging in:
+    /// [`login`](#method.login) makes a client of its own.
+    /// # Examples
+    ///
+    /// ```
+    /// # use sure_petcare::transport::Transport;
+    /// use sure_petcare::client::SurePet;
+    /// # fn run<T: Transport>(transport: T, request: &sure_petcare::login::Request) -> sure_petcare::error::Result<()> {
+    /// let client = SurePet::new(transport, "")
+    ///     .on_token_acquired(|event| {
+    ///         println!("logged in until {:?}", event.expires_at());
+    ///     })
+    ///     .log_in(request)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_token_acquired<F>(self, hook: F) -> Self
+    where
+        F: Fn(&TokenEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_token(TokenChange::Acquired, hook)
+    }
+
+    /// Calls `hook` with the new token whenever the client logs in again
+    /// after the API rejected the old one, see
+    /// [`with_relogin`](#method.with_relogin). However many clones saw the
+    /// rejection, it is called once per login.
+    pub fn on_token_refreshed<F>(self, hook: F) -> Self
+    where
+        F: Fn(&TokenEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_token(TokenChange::Refreshed, hook)
+    }
+
+    /// Calls `hook` with the rejected token whenever the API answers `401`
+    /// to a request carrying it, once per token, before any login again.
+    pub fn on_token_invalidated<F>(self, hook: F) -> Self
+    where
+        F: Fn(&TokenEvent<'_>) + Send + Sync + 'static,
+    {
+        self.on_token(TokenChange::Invalidated, hook)
+    }
+
+    fn on_token<F>(mut self, change: TokenChange, hook: F) -> Self
+    where
+        F: Fn(&TokenEvent<'_>) + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.token_hooks).add(change, Arc::new(hook));
+        self
+    }
+
+    /// Logs in again with `request` when the API rejects the token with
+    /// `401`, then retries the request once. However many requests are
+    /// rejected at the same time, by this client or its clones, only one
+    /// login is sent; the others wait for it and retry with the new token.
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "curl")]
+    /// # fn run() -> sure_petcare::error::Result<()> {
+    /// use sure_petcare::{client::SurePet, login, transport::curl::CurlTransport};
+    /// let request = login::RequestBuilder::new()
+    ///     .with_email_address("email@example.com")
+    ///     .with_password("qwerty123")
+    ///     .with_device_id("xxx-xxx-xxx-xxx")
+    ///     .build();
+    /// let client = SurePet::login(CurlTransport::new(), &request)?.with_relogin(&request);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_relogin(mut self, request: &login::Request<'_>) -> Self {
+        self.relogin = Some(Arc::new(Relogin {
+            request: request.clone().into_owned(),
+            flight: Mutex::default(),
+            landed: Condvar::new(),
+        }));
+        self
+    }
+
+    /// Points the client at a different API location, mostly useful for tests.
+    pub fn with_base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into().into();
+        self
+    }
+
+    /// Replaces the system clock, e.g. with a
+    /// [`ManualClock`](../clock/struct.ManualClock.html) in tests.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Sends the headers of the official app with every request.
+    pub fn with_app_identity(mut self, identity: AppIdentity) -> Self {
+        self.identity = Some(Arc::new(identity));
+        self
+    }
+
+    /// Fails requests whose response body is larger than `bytes` with
+    /// `Error::TooLarge`. Transports that support it, such as the curl
+    /// transport, stop the download once the limit is passed rather than
+    /// buffering the rest.
+    pub fn with_max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// Answers [`Pets::get`](../pet/struct.Pets.html#method.get) and
+    /// [`Devices::get`](../device/struct.Devices.html#method.get) from
+    /// `cache` while its entries are fresh. Successful updates through this
+    /// client drop the entries they affect.
+    pub fn with_state_cache(mut self, cache: StateCache) -> Self {
+        self.state_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Spaces out the requests of this client and its clones with `pacer`,
+    /// which adapts to the limits the API reports, see the
+    /// [`ratelimit`](../ratelimit/index.html) module. Requests wait on the
+    /// calling thread until the pacer lets them go.
+    pub fn with_pacing(mut self, pacer: Pacer) -> Self {
+        self.pacer = Some(Arc::new(Mutex::new(pacer)));
+        self
+    }
+
+    /// The rate limit the last response reporting one carried, shared by
+    /// the clones of a client.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self
+            .rate_limit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Stops the client sending anything but reads. Every request that
+    /// would change the account, such as locking a flap, renaming a pet or
+    /// deleting a tag, is still built and checked as usual, then recorded in
+    /// [`would_have_sent`](#method.would_have_sent) and failed with
+    /// [`Error::WouldHaveSent`](../error/enum.Error.html#variant.WouldHaveSent)
+    /// carrying it, without reaching the API. Logging in is still sent.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// A clone of the client in dry-run mode, see
+    /// [`with_dry_run`](#method.with_dry_run), for trying a single call:
+    /// `client.dry_run().devices().set_lock_mode(..)`.
+    pub fn dry_run(&self) -> Self {
+        self.clone().with_dry_run(true)
+    }
+
+    /// Whether the client is in dry-run mode.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The requests dry-run mode held back, oldest first, shared by the
+    /// clones of a client. Their `Authorization` header is left out.
+    pub fn would_have_sent(&self) -> Vec<transport::Request> {
+        self.would_have_sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Sends a header the crate does not model with every request, such as
+    /// one an experimental endpoint needs; it replaces a header of the same
+    /// name the client would send. Clones are cheap, so a header meant for a
+    /// single call goes on a clone:
+    /// `client.clone().with_header("X-Device-Id", "abc").pets().list()`.
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.extra_headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Adds a query parameter the crate does not model to every request,
+    /// after those the endpoint sends, such as a `with[]` expansion the
+    /// models do not know yet:
+    /// `client.clone().with_query("with[]", "photo").pets().list()`. The
+    /// parameter can be given more than once. Fields the models do not
+    /// keep can be read back with [`last_json`](#method.last_json).
+    pub fn with_query<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.extra_query.push((name.into(), value.into()));
+        self
+    }
+
+    /// The curfews of each flap switched off by
+    /// [`Devices::set_curfew_enabled`](../device/struct.Devices.html#method.set_curfew_enabled),
+    /// as they were before.
+    #[cfg(feature = "devices")]
+    pub(crate) fn curfews_off(&self) -> std::sync::MutexGuard<'_, HashMap<DeviceId, Vec<Curfew>>> {
+        self.curfews_off
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The cache attached with [`with_state_cache`](#method.with_state_cache).
+    pub fn state_cache(&self) -> Option<&StateCache> {
+        self.state_cache.as_deref()
+    }
+
+    /// The bearer token the client authenticates with.
+    pub fn token(&self) -> String {
+        self.token.read().map_or_else(
+            |poisoned| poisoned.into_inner().clone(),
+            |token| token.clone(),
+        )
+    }
+
+    /// Replaces the bearer token of this client and every clone of it, for
+    /// instance after logging in again.
+    pub fn set_token<S: Into<String>>(&self, token: S) {
+        let token = token.into();
+        match self.token.write() {
+            Ok(mut current) => *current = token,
+            Err(poisoned) => *poisoned.into_inner() = token,
+        }
+    }
+
+    /// The clock the client works out the current time with.
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Treats the token as expired this long before its `exp` claim, one
+    /// minute by default, so a request is not sent with a token that runs
+    /// out on the way.
+    pub fn with_token_leeway(mut self, leeway: Duration) -> Self {
+        self.token_leeway = leeway;
+        self
+    }
+
+    /// Measures how far the local clock is from the API's using the `Date`
+    /// header of each response, and allows for the difference when deciding
+    /// whether the token has expired. Useful on devices without NTP, whose
+    /// clocks can be off by minutes or more.
+    pub fn with_skew_calibration(mut self) -> Self {
+        self.calibrate_skew = true;
+        self
+    }
+
+    /// The seconds the API's clock was ahead of the local one in the last
+    /// response, negative when behind, and zero without
+    /// [`with_skew_calibration`](#method.with_skew_calibration).
+    pub fn clock_skew(&self) -> i64 {
+        self.skew.load(Ordering::Relaxed)
+    }
+
+    /// When the token expires according to its `exp` claim, `None` when it
+    /// carries none.
+    pub fn token_expires_at(&self) -> Option<Timestamp> {
+        token::expires_at(&self.token())
+    }
+
+    /// Whether the token has expired, or will within the
+    /// [leeway](#method.with_token_leeway), by the API's clock as far as it
+    /// is known. Tokens without an expiry are never considered expired.
+    /// # Examples
+    ///
+    /// ```
+    /// use sure_petcare::client::SurePet;
+    /// use sure_petcare::clock::ManualClock;
+    /// # struct Http;
+    /// # impl sure_petcare::transport::Transport for Http {
+    /// #     fn send(&self, _: &sure_petcare::transport::Request) -> sure_petcare::error::Result<sure_petcare::transport::Response> { unimplemented!() }
+    /// # }
+    /// // expires at 2019-06-01T12:00:00Z
+    /// let token = "eyJhbGciOiJub25lIn0.eyJleHAiOjE1NTkzOTA0MDB9.";
+    /// let client = SurePet::new(Http, token)
+    ///     .with_clock(ManualClock::new("2019-06-01T11:58:00Z".parse().unwrap()));
+    /// assert!(!client.is_token_expired());
+    /// assert!(client.with_token_leeway(std::time::Duration::from_secs(180)).is_token_expired());
+    /// ```
+    pub fn is_token_expired(&self) -> bool {
+        let server_now = self.clock.now().unix() + self.clock_skew();
+        self.token_expires_at().is_some_and(|expires| {
+            server_now + self.token_leeway.as_secs() as i64 >= expires.unix()
+        })
+    }
+
+    /// The last response the API sent, successful or not. Typed models only
+    /// keep the fields this crate knows about, so this is the place to look
+    /// when debugging what the API actually returned.
+    pub fn last_response(&self) -> Option<transport::Response> {
+        self.last_response.get()
+    }
+
+    /// The body of the last response parsed as JSON, `None` before the first
+    /// request or when the body was not JSON.
+    pub fn last_json(&self) -> Option<serde_json::Value> {
+        self.last_response
+            .get()
+            .and_then(|response| serde_json::from_slice(&response.body).ok())
+    }
+
+    /// What the responses so far said about the API moving on, such as
+    /// deprecated endpoints, see the [`compat`](../compat/index.html)
+    /// module. Shared by the clones of a client.
+    pub fn compatibility(&self) -> CompatibilityReport {
+        self.compatibility.get()
+    }
+
+    /// Fetches the start state: every household, pet and device on the
+    /// account with their current status.
+    pub fn start(&self) -> Result<State<'static>> {
+        self.get("/api/me/start", &[])
+    }
+
+    /// Fetches the API's list of products, naming each kind of hardware
+    /// and what it can do, see the [`product`](../product/index.html)
+    /// module.
+    pub fn products(&self) -> Result<ProductCatalogue> {
+        self.get("/api/product", &[])
+    }
+
+    /// Endpoints for the account's pets.
+    #[cfg(feature = "pets")]
+    pub fn pets(&self) -> Pets<'_, T> {
+        Pets::new(self)
+    }
+
+    /// Endpoints for the aggregated pet reports.
+    #[cfg(feature = "reports")]
+    pub fn reports(&self) -> Reports<'_, T> {
+        Reports::new(self)
+    }
+
+    /// Endpoints for photos of pets, households and devices.
+    pub fn photos(&self) -> Photos<'_, T> {
+        Photos::new(self)
+    }
+
+    /// Endpoints for the microchips and collar tags on the account.
+    pub fn tags(&self) -> Tags<'_, T> {
+        Tags::new(self)
+    }
+
+    /// Endpoints for the account's devices.
+    #[cfg(feature = "devices")]
+    pub fn devices(&self) -> Devices<'_, T> {
+        Devices::new(self)
+    }
+
+    /// Endpoints for the account's notifications.
+    #[cfg(feature = "notifications")]
+    pub fn notifications(&self) -> Notifications<'_, T> {
+        Notifications::new(self)
+    }
+
+    /// Endpoints for the household timeline.
+    #[cfg(feature = "timeline")]
+    pub fn timeline(&self) -> Timeline<'_, T> {
+        Timeline::new(self)
+    }
+
+    /// Sends a `GET` request and unwraps the `data` envelope of the response.
+    pub(crate) fn get<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<R> {
+        self.send::<R, ()>(Method::Get, path, query, None)
+    }
+
+    /// Sends a `GET` request and keeps the whole response, for callers that
+    /// parse the body themselves.
+    #[cfg(feature = "timeline")]
+    pub(crate) fn fetch(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<transport::Response> {
+        self.execute::<()>(Method::Get, path, query, None)
+    }
+
+    /// Sends a request whose response body carries nothing of interest,
+    /// which may be empty or a `204`.
+    pub(crate) fn send_unit<B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<()> {
+        self.execute(method, path, &[], body).map(|_| ())
+    }
+
+    pub(crate) fn send<R: DeserializeOwned, B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&B>,
+    ) -> Result<R> {
+        self.send_optional(method, path, query, body)?
+            .ok_or_else(|| {
+                Error::Json(serde::de::Error::custom(format!(
+                    "expected a body in the response to {} {}",
+                    method.as_str(),
+                    path
+                )))
+            })
+    }
+
+    /// Like `send`, for endpoints that answer with an empty body or a `204`
+    /// some of the time, which read as `None`.
+    pub(crate) fn send_optional<R: DeserializeOwned, B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&B>,
+    ) -> Result<Option<R>> {
+        let response = self.execute(method, path, query, body)?;
+        if response.is_empty() {
+            return Ok(None);
+        }
+        match ResponseEnvelope::<R>::parse(&response.body) {
+            Ok(envelope) => Ok(Some(envelope.data)),
+            Err(_) if !response.body.trim_ascii_start().starts_with(b"{") => {
+                Err(unexpected_content(&response))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Sends a request, turning non-success status codes into errors.
+    fn execute<B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&B>,
+    ) -> Result<transport::Response> {
+        let body = body.map(serde_json::to_vec).transpose()?;
+        self.execute_encoded(
+            method,
+            path,
+            query,
+            body.map(|body| ("application/json", body)),
+        )
+    }
+
+    /// Sends a request with a body encoded by the caller, such as a
+    /// multipart form, and unwraps the `data` envelope of the response.
+    pub(crate) fn send_encoded<R: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<R> {
+        let response = self.execute_encoded(method, path, &[], Some((content_type, body)))?;
+        match ResponseEnvelope::<R>::parse(&response.body) {
+            Ok(envelope) => Ok(envelope.data),
+            Err(_) if !response.body.trim_ascii_start().starts_with(b"{") => {
+                Err(unexpected_content(&response))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn execute_encoded(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<(&str, Vec<u8>)>,
+    ) -> Result<transport::Response> {
+        let token = self.token();
+        let request = self.request_encoded(method, path, query, body.clone());
+        if self.dry_run && method != Method::Get && path != LOGIN_PATH {
+            return Err(self.hold_back(request));
+        }
+        let result = self.check(&request, self.paced_send(&request)?);
+        if path == LOGIN_PATH || !matches!(result, Err(Error::Status { status: 401, .. })) {
+            return result;
+        }
+        if !token.is_empty() {
+            self.token_hooks.notify(TokenChange::Invalidated, &token);
+        }
+        if !self.relogin(&token)? {
+            return result;
+        }
+        let request = self.request_encoded(method, path, query, body);
+        self.check(&request, self.paced_send(&request)?)
+    }
+
+    /// Records a request dry-run mode keeps from the API.
+    fn hold_back(&self, mut request: transport::Request) -> Error {
+        request.headers.retain(|(name, _)| name != "Authorization");
+        self.would_have_sent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(request.clone());
+        Error::WouldHaveSent(Box::new(request))
+    }
+
+    /// Replaces a rejected token by logging in again, returning whether
+    /// there is a new token to retry with. Only one login runs at a time;
+    /// callers arriving while it runs wait for its outcome instead of
+    /// sending their own.
+    fn relogin(&self, rejected: &str) -> Result<bool> {
+        let relogin = match &self.relogin {
+            Some(relogin) => relogin,
+            None => return Ok(false),
+        };
+        let mut flight = relogin
+            .flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if flight.running {
+            let generation = flight.generation;
+            while flight.running && flight.generation == generation {
+                flight = relogin
+                    .landed
+                    .wait(flight)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            return Ok(self.token() != rejected);
+        }
+        if self.token() != rejected {
+            return Ok(true);
+        }
+        flight.running = true;
+        drop(flight);
+
+        let result = self.authenticate(&relogin.request);
+        if let Ok(token) = &result {
+            self.set_token(token.as_str());
+            self.token_hooks.notify(TokenChange::Refreshed, token);
+        }
+        let mut flight = relogin
+            .flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        flight.running = false;
+        flight.generation += 1;
+        relogin.landed.notify_all();
+        result.map(|_| true)
+    }
+
+    /// Sends a login request without the current token, returning the new
+    /// one.
+    fn authenticate(&self, login: &login::Request<'_>) -> Result<String> {
+        let mut request = self.request(Method::Post, LOGIN_PATH, &[], Some(login))?;
+        request.headers.retain(|(name, _)| name != "Authorization");
+        let response = self.check(&request, self.paced_send(&request)?)?;
+        let response: login::Response = ResponseEnvelope::parse(&response.body)?.data;
+        Ok(response.access_token().into_owned())
+    }
+
+    /// Sends several `GET` requests through
+    /// [`Transport::send_all`](../transport/trait.Transport.html#method.send_all),
+    /// keeping the whole responses.
+    #[cfg(feature = "timeline")]
+    pub(crate) fn fetch_all(
+        &self,
+        calls: &[(String, Vec<(&str, String)>)],
+    ) -> Vec<Result<transport::Response>> {
+        let requests: Vec<Result<transport::Request>> = calls
+            .iter()
+            .map(|(path, query)| self.request::<()>(Method::Get, path, query, None))
+            .collect();
+        let ready: Vec<transport::Request> = requests
+            .iter()
+            .filter_map(|request| request.as_ref().ok().cloned())
+            .collect();
+        self.pace();
+        let mut responses = self.transport.send_all(&ready).into_iter();
+        requests
+            .into_iter()
+            .map(|request| {
+                let request = request?;
+                let response = responses
+                    .next()
+                    .unwrap_or_else(|| Err(Error::transport("transport dropped a response")));
+                self.check(&request, response?)
+            })
+            .collect()
+    }
+
+    /// Sends a request once the pacer, if any, lets it go.
+    fn paced_send(&self, request: &transport::Request) -> Result<transport::Response> {
+        self.pace();
+        self.transport.send(request)
+    }
+
+    /// Waits until the pacer lets the next request go, and counts it sent.
+    fn pace(&self) {
+        let pacer = match &self.pacer {
+            Some(pacer) => pacer,
+            None => return,
+        };
+        let mut pacer = pacer.lock().unwrap_or_else(PoisonError::into_inner);
+        let delay = pacer.delay(Instant::now());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        pacer.sent(Instant::now());
+    }
+
+    /// Whether the transport multiplexes batches of requests.
+    #[cfg(feature = "timeline")]
+    pub(crate) fn multiplexes(&self) -> bool {
+        self.transport.multiplexes()
+    }
+
+    fn request<B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&B>,
+    ) -> Result<transport::Request> {
+        let body = body.map(serde_json::to_vec).transpose()?;
+        Ok(self.request_encoded(
+            method,
+            path,
+            query,
+            body.map(|body| ("application/json", body)),
+        ))
+    }
+
+    fn request_encoded(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<(&str, Vec<u8>)>,
+    ) -> transport::Request {
+        let mut url = format!("{}{}", self.base_url, path);
+        let mut query = query.to_vec();
+        query.extend(
+            self.extra_query
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone())),
+        );
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(&transport::query_string(&query));
+        }
+
+        let mut headers = vec![("Accept".to_string(), "application/json".to_string())];
+        match &self.identity {
+            Some(identity) => headers.extend(identity.headers().iter().cloned()),
+            None => headers.push(("X-App-Version".into(), APP_VERSION.into())),
+        }
+        let token = self.token();
+        if !token.is_empty() {
+            headers.push(("Authorization".into(), format!("Bearer {}", token)));
+        }
+        let body = body.map(|(content_type, body)| {
+            headers.push(("Content-Type".into(), content_type.into()));
+            body
+        });
+        for (name, value) in &self.extra_headers {
+            headers.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+            headers.push((name.clone(), value.clone()));
+        }
+
+        transport::Request {
+            method,
+            url,
+            headers,
+            body,
+            max_body_size: self.max_response_size,
+        }
+    }
+
+    /// Applies the size limit, notes any signs of the API moving on and
+    /// turns non-success status codes into errors.
+    fn check(
+        &self,
+        request: &transport::Request,
+        response: transport::Response,
+    ) -> Result<transport::Response> {
+        if let Some(limit) = self.max_response_size {
+            if response.body.len() > limit {
+                return Err(Error::TooLarge { limit });
+            }
+        }
+        if self.calibrate_skew {
+            if let Some(date) = response.header("Date").and_then(Timestamp::from_http_date) {
+                let skew = date.seconds_since(self.clock.now());
+                self.skew.store(skew, Ordering::Relaxed);
+            }
+        }
+        self.last_response.set(&response);
+        self.compatibility.observe(request, &response);
+        let rate_limit = RateLimit::from_response(&response, self.server_now());
+        if let Some(rate_limit) = rate_limit {
+            *self
+                .rate_limit
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(rate_limit);
+            if let Some(pacer) = &self.pacer {
+                pacer
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .observe(&rate_limit, Instant::now());
+            }
+        }
+        if response.status == 429 {
+            return Err(Error::RateLimited {
+                rate_limit: rate_limit.unwrap_or_default(),
+            });
+        }
+        if response.is_unavailable() {
+            return Err(Error::ServiceUnavailable {
+                retry_after: self.retry_after(&response),
+            });
+        }
+        if response.content_type().is_some() && !response.is_json() && !response.is_empty() {
+            return Err(unexpected_content(&response));
+        }
+        if !response.is_success() {
+            return Err(Error::Status {
+                status: response.status,
+                body: String::from_utf8_lossy(&response.body).into_owned(),
+            });
+        }
+        Ok(response)
+    }
+
+    /// The wait a `Retry-After` header asks for, given as seconds or as a
+    /// date measured against the response's `Date`.
+    fn retry_after(&self, response: &transport::Response) -> Option<Duration> {
+        ratelimit::retry_after(response, self.server_now())
+    }
+
+    /// The current time by the API's clock, as far as it is known.
+    fn server_now(&self) -> Timestamp {
+        Timestamp::from_unix(self.clock.now().unix() + self.clock_skew())
+    }
+}
+
+/// How much of a body `unexpected_content` keeps, in characters.
+const SNIPPET_CHARS: usize = 120;
+
+/// The error for a response that is not JSON, quoting the start of its body
+/// so an HTML error page from a proxy is easy to recognise.
+fn unexpected_content(response: &transport::Response) -> Error {
+    let body = secret::redact_body(&response.body);
+    let mut snippet = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if let Some((end, _)) = snippet.char_indices().nth(SNIPPET_CHARS) {
+        snippet.truncate(end);
+        snippet.push('…');
+    }
+    Error::UnexpectedContent {
+        status: response.status,
+        content_type: response.content_type().map(str::to_string),
+        snippet,
+    }
+}
+
+/// A logged in client with the start state already fetched, covering what
+/// most scripts need in a few lines.
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "curl")]
+/// # fn run() -> sure_petcare::error::Result<()> {
+/// use sure_petcare::client::SurePetcare;
+/// let surepet = SurePetcare::connect("email@example.com", "qwerty123")?;
+/// for pet in surepet.state().pets() {
+///     println!("{}", pet.name());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SurePetcare<T> {
+    client: SurePet<T>,
+    state: State<'static>,
+}
+
+#[cfg(feature = "curl")]
+impl SurePetcare<transport::curl::CurlTransport> {
+    /// Logs in over the [`curl`](../transport/curl/index.html) transport,
+    /// with a device id made up for this session.
+    pub fn connect(email: &str, password: &str) -> Result<Self> {
+        SurePetcare::connect_with(transport::curl::CurlTransport::new(), email, password)
+    }
+}
+
+impl<T: Transport> SurePetcare<T> {
+    /// Logs in over `transport`, with a device id made up for this session,
+    /// and fetches the start state.
+    pub fn connect_with(transport: T, email: &str, password: &str) -> Result<Self> {
+        SurePetcare::connect_client(SurePet::new(transport, ""), email, password)
+    }
+
+    /// Logs in with a client not yet logged in, keeping its settings such
+    /// as the clock the made up device id is stamped by, and fetches the
+    /// start state.
+    pub fn connect_client(client: SurePet<T>, email: &str, password: &str) -> Result<Self> {
+        let device_id = format!(
+            "{:08x}-{:04x}-sure-petcare",
+            client.clock().now().unix(),
+            std::process::id() & 0xffff
+        );
+        let request = login::RequestBuilder::new()
+            .with_email_address(email)
+            .with_password(password)
+            .with_device_id(device_id)
+            .build();
+        SurePetcare::from_client(client.log_in(&request)?)
+    }
+
+    /// Fetches the start state with an existing client.
+    pub fn from_client(client: SurePet<T>) -> Result<Self> {
+        let state = client.start()?;
+        Ok(SurePetcare { client, state })
+    }
+
+    /// The client, for every endpoint.
+    pub fn client(&self) -> &SurePet<T> {
+        &self.client
+    }
+
+    /// The start state as last fetched.
+    pub fn state(&self) -> &State<'static> {
+        &self.state
+    }
+
+    /// The first household on the account, which for most accounts is the
+    /// only one.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.state
+            .households()
+            .first()
+            .map(|household| household.id())
+    }
+
+    /// Fetches the start state again.
+    pub fn refresh(&mut self) -> Result<&State<'static>> {
+        self.state = self.client.start()?;
+        Ok(&self.state)
+    }
+
+    /// Gives up the preloaded state and returns the client.
+    pub fn into_client(self) -> SurePet<T> {
+        self.client
+    }
 }
 
-impl SurePet {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::id::{PetId, TagId};
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_login_and_send_token() {
+        let transport = MockTransport::new().respond(200, r#"{"data":{"token":"abc.def"}}"#);
+        let request = login::RequestBuilder::new()
+            .with_email_address("email@example.com")
+            .with_password("qwerty123")
+            .with_device_id("xxx-xxx-xxx-xxx")
+            .build();
+        let client = SurePet::login(&transport, &request).unwrap();
+        assert_eq!("abc.def", client.token());
+
+        let sent = transport.requests();
+        assert_eq!(Method::Post, sent[0].method);
+        assert_eq!("https://app.api.surehub.io/api/auth/login", sent[0].url);
+        assert_eq!(None, sent[0].header("authorization"));
+    }
+
+    #[test]
+    fn should_connect_and_preload_state() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{"token":"abc.def"}}"#)
+            .respond(
+                200,
+                r#"{"data":{"households":[{"id":7,"name":"Home"}],"pets":[{"id":2,"name":"Milo"}]}}"#,
+            );
+        let surepet =
+            SurePetcare::connect_with(&transport, "email@example.com", "qwerty123").unwrap();
+
+        assert_eq!(Some(HouseholdId::new(7)), surepet.household_id());
+        assert_eq!("Milo", surepet.state().pets()[0].name());
+        assert_eq!("abc.def", surepet.client().token());
+        let login: serde_json::Value =
+            serde_json::from_slice(transport.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert!(login["device_id"]
+            .as_str()
+            .unwrap()
+            .ends_with("-sure-petcare"));
+    }
+
+    #[test]
+    fn should_stamp_the_device_id_by_the_client_clock() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{"token":"abc.def"}}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "").with_clock(crate::clock::ManualClock::new(
+            Timestamp::from_unix(0x5cf2_2ec0),
+        ));
+        SurePetcare::connect_client(client, "email@example.com", "qwerty123").unwrap();
+
+        let login: serde_json::Value =
+            serde_json::from_slice(transport.requests()[0].body.as_ref().unwrap()).unwrap();
+        assert!(login["device_id"]
+            .as_str()
+            .unwrap()
+            .starts_with("5cf22ec0-"));
+    }
+
+    #[test]
+    fn should_send_app_identity() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{"token":"abc.def"}}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let identity = crate::app::AppIdentityBuilder::new()
+            .with_device_id("xxx-xxx-xxx-xxx")
+            .build();
+        let request = login::RequestBuilder::new()
+            .with_device_id("xxx-xxx-xxx-xxx")
+            .build();
+        let client = SurePet::new(&transport, "")
+            .with_app_identity(identity)
+            .log_in(&request)
+            .unwrap();
+        client.start().unwrap();
+
+        for sent in transport.requests() {
+            assert_eq!(
+                Some("SurePetcare/2.38.0 (android)"),
+                sent.header("user-agent")
+            );
+            assert_eq!(Some("xxx-xxx-xxx-xxx"), sent.header("X-Device-Id"));
+        }
+        assert_eq!(
+            Some("Bearer abc.def"),
+            transport.requests()[1].header("Authorization")
+        );
+    }
+
+    #[test]
+    fn should_surface_status_errors() {
+        let transport = MockTransport::new().respond(401, r#"{"error":"unauthorised"}"#);
+        let client = SurePet::new(&transport, "expired");
+        let result: Result<serde_json::Value> = client.get("/api/me/start", &[]);
+        match result {
+            Err(Error::Status { status, body }) => {
+                assert_eq!(401, status);
+                assert_eq!(r#"{"error":"unauthorised"}"#, body);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(
+            Some("Bearer expired"),
+            transport.requests()[0].header("Authorization")
+        );
+    }
+
+    #[test]
+    fn should_reject_bodies_over_the_limit() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":{"households":[]}}"#);
+        let client = SurePet::new(&transport, "token").with_max_response_size(16);
+
+        client.start().unwrap();
+        assert!(matches!(client.start(), Err(Error::TooLarge { limit: 16 })));
+        assert_eq!(Some(16), transport.requests()[1].max_body_size);
+    }
+
+    #[test]
+    fn should_share_the_token_between_clones() {
+        #[derive(Debug)]
+        struct Offline;
+        impl Transport for Offline {
+            fn send(&self, _: &transport::Request) -> Result<transport::Response> {
+                Err(Error::transport("offline"))
+            }
+        }
+        fn shareable<C: Clone + Send + Sync>(client: &C) -> C {
+            client.clone()
+        }
+        let client = SurePet::new(Offline, "old");
+        let clone = shareable(&client);
+        client.set_token("new");
+        assert_eq!("new", clone.token());
+    }
+
+    /// Accepts only the token `fresh`, handing it out after a slow login.
+    #[derive(Debug, Default)]
+    struct Expiring {
+        logins: std::sync::atomic::AtomicUsize,
+        refuse_login: bool,
+    }
+
+    impl Transport for Expiring {
+        fn send(&self, request: &transport::Request) -> Result<transport::Response> {
+            let respond = |status, body: &str| transport::Response {
+                status,
+                headers: Vec::new(),
+                body: body.as_bytes().to_vec(),
+            };
+            if request.url.ends_with(LOGIN_PATH) {
+                assert_eq!(None, request.header("Authorization"));
+                self.logins.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(50));
+                return Ok(if self.refuse_login {
+                    respond(401, r#"{"error":{"login":["Invalid"]}}"#)
+                } else {
+                    respond(200, r#"{"data":{"token":"fresh"}}"#)
+                });
+            }
+            Ok(match request.header("Authorization") {
+                Some("Bearer fresh") => respond(200, r#"{"data":{"households":[]}}"#),
+                _ => respond(401, r#"{"error":"Unauthenticated."}"#),
+            })
+        }
+    }
+
+    #[test]
+    fn should_log_in_once_for_concurrent_rejections() {
+        let request = login::RequestBuilder::new()
+            .with_email_address("email@example.com")
+            .with_password("qwerty123")
+            .with_device_id("xxx-xxx-xxx-xxx")
+            .build();
+        let transport = Expiring::default();
+        let client = SurePet::new(&transport, "stale").with_relogin(&request);
+        let results: Vec<Result<State<'static>>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..20)
+                .map(|_| {
+                    let client = client.clone();
+                    scope.spawn(move || client.start())
+                })
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(1, transport.logins.load(Ordering::SeqCst));
+        assert_eq!("fresh", client.token());
+
+        let refusing = Expiring {
+            refuse_login: true,
+            ..Expiring::default()
+        };
+        let client = SurePet::new(&refusing, "stale").with_relogin(&request);
+        let results: Vec<Result<State<'static>>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..20).map(|_| scope.spawn(|| client.start())).collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Err(Error::Status { status: 401, .. }))));
+        assert!(refusing.logins.load(Ordering::SeqCst) < 20);
+    }
+
+    #[test]
+    fn should_report_token_changes_to_hooks() {
+        let request = login::RequestBuilder::new()
+            .with_email_address("email@example.com")
+            .with_password("qwerty123")
+            .with_device_id("xxx-xxx-xxx-xxx")
+            .build();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let record = |changes: &Arc<Mutex<Vec<(TokenChange, String)>>>| {
+            let changes = Arc::clone(changes);
+            move |event: &TokenEvent<'_>| {
+                let change = (event.change(), event.token().to_string());
+                changes.lock().unwrap().push(change);
+            }
+        };
+        let transport = Expiring::default();
+        let client = SurePet::new(&transport, "stale")
+            .on_token_acquired(record(&changes))
+            .on_token_refreshed(record(&changes))
+            .on_token_invalidated(record(&changes))
+            .with_relogin(&request);
+        std::thread::scope(|scope| {
+            for _ in 0..5 {
+                let client = client.clone();
+                scope.spawn(move || client.start().unwrap());
+            }
+        });
+        client.log_in(&request).unwrap();
+
+        assert_eq!(
+            vec![
+                (TokenChange::Invalidated, "stale".to_string()),
+                (TokenChange::Refreshed, "fresh".to_string()),
+                (TokenChange::Acquired, "fresh".to_string()),
+            ],
+            *changes.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn should_hold_back_changes_in_dry_run_mode() {
+        let transport = MockTransport::new().respond(200, r#"{"data":[]}"#);
+        let client = SurePet::new(&transport, "token");
+        let dry_run = client.dry_run();
+
+        assert!(dry_run.tags().list().unwrap().is_empty());
+        let held = match dry_run.tags().assign(TagId::new(6), PetId::new(3)) {
+            Err(Error::WouldHaveSent(request)) => request,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert!(matches!(
+            dry_run.tags().delete(TagId::new(5)),
+            Err(Error::WouldHaveSent(_))
+        ));
+
+        assert_eq!(1, transport.requests().len());
+        assert_eq!(Method::Put, held.method);
+        assert_eq!("https://app.api.surehub.io/api/pet/3", held.url);
+        assert_eq!(Some(r#"{"tag_id":6}"#.as_bytes()), held.body.as_deref());
+        assert!(held.header("Authorization").is_none());
+        let sent = client.would_have_sent();
+        assert_eq!(vec![*held, sent[1].clone()], sent);
+        assert_eq!(Method::Delete, sent[1].method);
+        assert!(!client.is_dry_run());
+    }
+
+    #[test]
+    fn should_add_extra_headers_and_query_parameters() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":[]}"#)
+            .respond(200, r#"{"data":[]}"#);
+        let client = SurePet::new(&transport, "token");
+        let extended = client
+            .clone()
+            .with_header("x-app-version", "experimental")
+            .with_query("with[]", "pets")
+            .with_query("with[]", "devices");
+
+        extended.tags().query().descending().fetch().unwrap();
+        client.tags().list().unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            "https://app.api.surehub.io/api/tag?sort=id&order=desc&with%5B%5D=pets&with%5B%5D=devices",
+            requests[0].url
+        );
+        assert_eq!(Some("experimental"), requests[0].header("X-App-Version"));
+        assert_eq!(
+            1,
+            requests[0]
+                .headers
+                .iter()
+                .filter(|(name, _)| name.eq_ignore_ascii_case("x-app-version"))
+                .count()
+        );
+        assert_eq!("https://app.api.surehub.io/api/tag", requests[1].url);
+        assert_eq!(Some(APP_VERSION), requests[1].header("X-App-Version"));
+    }
+
+    #[test]
+    fn should_judge_token_expiry_by_the_api_clock() {
+        // {"exp":1559390400}, 2019-06-01T12:00:00Z
+        let token = "eyJhbGciOiJub25lIn0.eyJleHAiOjE1NTkzOTA0MDB9.";
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .with_header("Date", "Sat, 01 Jun 2019 11:59:30 GMT");
+        let client = SurePet::new(&transport, token)
+            .with_clock(ManualClock::new("2019-06-01T11:50:00Z".parse().unwrap()))
+            .with_token_leeway(Duration::from_secs(45))
+            .with_skew_calibration();
+
+        assert!(!client.is_token_expired());
+        let _: serde_json::Value = client.get("/api/me/start", &[]).unwrap();
+        assert_eq!(570, client.clock_skew());
+        assert!(client.is_token_expired());
+        assert!(!SurePet::new(&transport, "opaque").is_token_expired());
+    }
+
+    #[test]
+    fn should_report_outages_as_unavailable() {
+        let transport = MockTransport::new()
+            .respond(503, "<html><body>Back soon</body></html>")
+            .with_header("Retry-After", "120")
+            .respond(200, r#"{"message":"Down for maintenance"}"#)
+            .with_header("Date", "Sat, 01 Jun 2019 12:00:00 GMT")
+            .with_header("Retry-After", "Sat, 01 Jun 2019 12:30:00 GMT")
+            .respond(502, "<!DOCTYPE html>");
+        let client = SurePet::new(&transport, "token");
+
+        let retry_after = |result: Result<State>| match result {
+            Err(Error::ServiceUnavailable { retry_after }) => retry_after,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(Some(Duration::from_secs(120)), retry_after(client.start()));
+        assert_eq!(Some(Duration::from_secs(1800)), retry_after(client.start()));
+        assert_eq!(None, retry_after(client.start()));
+    }
+
+    #[test]
+    fn should_keep_rate_limits_and_fail_when_limited() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .with_header("X-RateLimit-Limit", "100")
+            .with_header("X-RateLimit-Remaining", "99")
+            .with_header("X-RateLimit-Reset", "0")
+            .respond(429, r#"{"error":"Too Many Attempts."}"#)
+            .with_header("Retry-After", "0")
+            .with_header("X-RateLimit-Remaining", "0");
+        let client = SurePet::new(&transport, "token").with_pacing(Pacer::default());
+
+        client.start().unwrap();
+        assert_eq!(Some(99), client.rate_limit().and_then(|r| r.remaining()));
+        match client.start() {
+            Err(Error::RateLimited { rate_limit }) => {
+                assert_eq!(Some(Duration::ZERO), rate_limit.retry_after());
+                assert!(rate_limit.is_exhausted());
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(None, client.rate_limit().and_then(|r| r.limit()));
+    }
+
+    #[test]
+    fn should_explain_responses_that_are_not_json() {
+        let page = format!("<html>\n  <h1>403 Forbidden</h1>{}</html>", "x".repeat(200));
+        let transport = MockTransport::new()
+            .respond(403, &page)
+            .with_header("Content-Type", "text/html; charset=utf-8")
+            .respond(200, "Bad gateway from me@example.com")
+            .respond(200, r#"{"data":{}}"#)
+            .with_header("Content-Type", "application/json; charset=utf-8");
+        let client = SurePet::new(&transport, "token");
+
+        match client.start() {
+            Err(Error::UnexpectedContent {
+                status,
+                content_type,
+                snippet,
+            }) => {
+                assert_eq!(403, status);
+                assert_eq!(Some("text/html"), content_type.as_deref());
+                assert!(snippet.starts_with("<html> <h1>403 Forbidden</h1>xxx"));
+                assert_eq!(121, snippet.chars().count());
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(
+            "status 200 response is not json (application/json): Bad gateway from REDACTED",
+            client.start().unwrap_err().to_string()
+        );
+        client.start().unwrap();
+    }
+
+    #[test]
+    fn should_keep_the_last_response() {
+        let transport =
+            MockTransport::new().respond(200, r#"{"data":{"households":[],"extra":true}}"#);
+        let client = SurePet::new(&transport, "token");
+        assert_eq!(None, client.last_json());
+
+        let state = client.start().unwrap();
+        assert!(state.households().is_empty());
+        assert_eq!(
+            Some(serde_json::json!({"data": {"households": [], "extra": true}})),
+            client.last_json()
+        );
+        assert_eq!(200, client.last_response().unwrap().status);
+    }
+
+    #[test]
+    fn should_send_the_app_version_and_note_deprecations() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .with_header("Deprecation", "true")
+            .respond(426, r#"{"error":"upgrade"}"#);
+        let client = SurePet::new(&transport, "token");
+
+        client.start().unwrap();
+        assert!(client.compatibility().is_compatible());
+        assert!(client.clone().start().is_err());
+
+        assert_eq!(
+            Some(APP_VERSION),
+            transport.requests()[0].header("x-app-version")
+        );
+        assert_eq!(
+            "GET /api/me/start is deprecated\nGET /api/me/start is no longer served (426)",
+            client.compatibility().to_string()
+        );
+        assert!(!client.compatibility().is_compatible());
+    }
+}
@@ -0,0 +1,85 @@
+//! Where the crate gets the current time from.
+//!
+//! Anything that depends on "now", such as which report days are over or
+//! when cache entries expire, asks a [`Clock`](trait.Clock.html). The
+//! default is the system clock; tests can use a
+//! [`ManualClock`](struct.ManualClock.html) and move it forward by hand.
+use crate::time::Timestamp;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A source of the current time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> Timestamp;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+/// The system clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// A clock that only moves when told to.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+/// let clock = ManualClock::new("2019-06-01T12:00:00Z".parse().unwrap());
+/// clock.advance(Duration::from_secs(90));
+/// assert_eq!("2019-06-01T12:01:30+00:00", clock.now().to_string());
+/// ```
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicI64,
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at `now`.
+    pub fn new(now: Timestamp) -> Self {
+        ManualClock {
+            now: AtomicI64::new(now.unix()),
+        }
+    }
+
+    /// Moves the clock to `now`, which may be in the past.
+    pub fn set(&self, now: Timestamp) {
+        self.now.store(now.unix(), Ordering::SeqCst);
+    }
+
+    /// Moves the clock forward, to the whole second.
+    pub fn advance(&self, by: Duration) {
+        self.now.fetch_add(by.as_secs() as i64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::from_unix(self.now.load(Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,363 @@
+//! Noticing when the API moves on.
+//!
+//! The API is undocumented and changes without announcement, so the first
+//! sign of a breaking change is usually a baffling `4xx`. Every request
+//! names the app version it speaks for in `X-App-Version`, [`APP_VERSION`](../app/constant.APP_VERSION.html)
+//! unless an [`AppIdentity`](../app/struct.AppIdentity.html) says
+//! otherwise, and the client watches responses for the standard signs that
+//! this is about to stop working:
+//!
+//! - a `Deprecation` header, with any `Sunset` date and deprecation link,
+//! - a `Warning` header with code `299`, a persistent warning,
+//! - `426 Upgrade Required` or `410 Gone`,
+//! - an `X-Api-Version` header that changes between responses.
+//!
+//! What it has seen is kept in a [`CompatibilityReport`](struct.CompatibilityReport.html),
+//! read with [`SurePet::compatibility`](../client/struct.SurePet.html#method.compatibility).
+use crate::time::Timestamp;
+use crate::transport::{Request, Response};
+use std::fmt;
+use std::sync::Mutex;
+
+/// The header naming the API version a response was served by.
+pub const API_VERSION_HEADER: &str = "X-Api-Version";
+
+/// One sign of an incompatibility, see the module documentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Notice {
+    /// The endpoint is deprecated.
+    Deprecated {
+        /// The endpoint, such as `GET /api/pet/{id}`.
+        endpoint: String,
+        /// When it was deprecated, if the server said.
+        since: Option<Timestamp>,
+        /// When it will stop working, if the server said.
+        sunset: Option<Timestamp>,
+        /// Where the server says to read about it.
+        link: Option<String>,
+    },
+    /// The server sent a persistent warning about the endpoint.
+    Warning {
+        /// The endpoint.
+        endpoint: String,
+        /// The warning text.
+        text: String,
+    },
+    /// The server no longer serves the endpoint to this client.
+    Unsupported {
+        /// The endpoint.
+        endpoint: String,
+        /// `426` or `410`.
+        status: u16,
+    },
+    /// The API version changed while the client was running.
+    VersionChanged {
+        /// The version served before.
+        from: String,
+        /// The version served now.
+        to: String,
+    },
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notice::Deprecated {
+                endpoint,
+                sunset,
+                link,
+                ..
+            } => {
+                write!(f, "{} is deprecated", endpoint)?;
+                if let Some(sunset) = sunset {
+                    write!(f, " and stops working at {}", sunset)?;
+                }
+                if let Some(link) = link {
+                    write!(f, ", see {}", link)?;
+                }
+                Ok(())
+            }
+            Notice::Warning { endpoint, text } => write!(f, "{}: {}", endpoint, text),
+            Notice::Unsupported { endpoint, status } => {
+                write!(f, "{} is no longer served ({})", endpoint, status)
+            }
+            Notice::VersionChanged { from, to } => {
+                write!(f, "the API changed from version {} to {}", from, to)
+            }
+        }
+    }
+}
+
+/// What the client has learnt about its compatibility with the API.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::compat::CompatibilityReport;
+/// let report = CompatibilityReport::default();
+/// assert!(report.is_compatible());
+/// assert_eq!("no compatibility problems seen", report.to_string());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompatibilityReport {
+    server_version: Option<String>,
+    notices: Vec<Notice>,
+}
+
+impl CompatibilityReport {
+    /// The API version the last response named, if any did.
+    pub fn server_version(&self) -> Option<&str> {
+        self.server_version.as_deref()
+    }
+
+    /// Each sign seen, oldest first, once per endpoint.
+    pub fn notices(&self) -> &[Notice] {
+        &self.notices
+    }
+
+    /// Whether nothing has stopped working yet: no endpoint was refused and
+    /// no sunset has passed by `now`. Deprecations and warnings still count
+    /// as compatible.
+    pub fn is_compatible_at(&self, now: Timestamp) -> bool {
+        self.notices.iter().all(|notice| match notice {
+            Notice::Unsupported { .. } => false,
+            Notice::Deprecated {
+                sunset: Some(sunset),
+                ..
+            } => now < *sunset,
+            _ => true,
+        })
+    }
+
+    /// Like [`is_compatible_at`](#method.is_compatible_at), now.
+    pub fn is_compatible(&self) -> bool {
+        self.is_compatible_at(Timestamp::now())
+    }
+
+    /// Whether anything at all was seen.
+    pub fn is_empty(&self) -> bool {
+        self.notices.is_empty()
+    }
+
+    /// Records the signs in one response.
+    pub fn observe(&mut self, request: &Request, response: &Response) {
+        let endpoint = endpoint(request);
+        if let Some(version) = response.header(API_VERSION_HEADER) {
+            let version = version.trim().to_string();
+            match self.server_version.replace(version.clone()) {
+                Some(from) if from != version => {
+                    self.notify(Notice::VersionChanged { from, to: version })
+                }
+                _ => {}
+            }
+        }
+        if let Some(deprecation) = response.header("Deprecation") {
+            self.notify(Notice::Deprecated {
+                endpoint: endpoint.clone(),
+                since: parse_date(deprecation),
+                sunset: response.header("Sunset").and_then(parse_date),
+                link: response.header("Link").and_then(deprecation_link),
+            });
+        }
+        if let Some(text) = response.header("Warning").and_then(persistent_warning) {
+            self.notify(Notice::Warning {
+                endpoint: endpoint.clone(),
+                text,
+            });
+        }
+        if response.status == 426 || response.status == 410 {
+            self.notify(Notice::Unsupported {
+                endpoint,
+                status: response.status,
+            });
+        }
+    }
+
+    fn notify(&mut self, notice: Notice) {
+        let seen = self.notices.iter().any(|seen| {
+            std::mem::discriminant(seen) == std::mem::discriminant(&notice)
+                && match (seen, &notice) {
+                    (Notice::VersionChanged { to, .. }, Notice::VersionChanged { to: now, .. }) => {
+                        to == now
+                    }
+                    _ => endpoint_of(seen) == endpoint_of(&notice),
+                }
+        });
+        if !seen {
+            self.notices.push(notice);
+        }
+    }
+}
+
+impl fmt::Display for CompatibilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.notices.is_empty() {
+            return f.write_str("no compatibility problems seen");
+        }
+        for (index, notice) in self.notices.iter().enumerate() {
+            if index > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", notice)?;
+        }
+        Ok(())
+    }
+}
+
+/// The report shared by the clones of a client.
+#[derive(Debug, Default)]
+pub(crate) struct Compatibility(Mutex<CompatibilityReport>);
+
+impl Compatibility {
+    pub(crate) fn get(&self) -> CompatibilityReport {
+        self.0
+            .lock()
+            .map(|report| report.clone())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn observe(&self, request: &Request, response: &Response) {
+        if let Ok(mut report) = self.0.lock() {
+            report.observe(request, response);
+        }
+    }
+}
+
+fn endpoint_of(notice: &Notice) -> Option<&str> {
+    match notice {
+        Notice::Deprecated { endpoint, .. }
+        | Notice::Warning { endpoint, .. }
+        | Notice::Unsupported { endpoint, .. } => Some(endpoint),
+        Notice::VersionChanged { .. } => None,
+    }
+}
+
+/// The method and path of a request, without the host or query and with
+/// numeric ids as `{id}`, so notices group by endpoint.
+fn endpoint(request: &Request) -> String {
+    let url = request.url.split(['?', '#']).next().unwrap_or_default();
+    let path = match url.find("://") {
+        Some(scheme) => url[scheme + 3..]
+            .find('/')
+            .map_or("/", |start| &url[scheme + 3 + start..]),
+        None => url,
+    };
+    let path: Vec<&str> = path
+        .split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect();
+    format!("{} {}", request.method.as_str(), path.join("/"))
+}
+
+/// A `Deprecation` or `Sunset` date: an HTTP date, or `@` and seconds since
+/// the epoch as RFC 9745 has it. `true` and other values mean no date.
+fn parse_date(value: &str) -> Option<Timestamp> {
+    let value = value.trim();
+    match value.strip_prefix('@') {
+        Some(seconds) => seconds.parse().ok().map(Timestamp::from_unix),
+        None => Timestamp::from_http_date(value),
+    }
+}
+
+/// The target of a `Link` with `rel="deprecation"` or `rel="sunset"`.
+fn deprecation_link(value: &str) -> Option<String> {
+    value.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let rel = params.split(';').any(|param| {
+            let param = param.trim().replace('"', "");
+            param == "rel=deprecation" || param == "rel=sunset"
+        });
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        Some(target.to_string()).filter(|_| rel)
+    })
+}
+
+/// The text of a `299` warning, such as `299 - "Deprecated API"`.
+fn persistent_warning(value: &str) -> Option<String> {
+    let rest = value.trim().strip_prefix("299")?;
+    let start = rest.find('"')?;
+    let end = rest[start + 1..].find('"')?;
+    Some(rest[start + 1..start + 1 + end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Method;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_report_deprecations_warnings_and_version_changes() {
+        let request = |method, url: &str| Request {
+            method,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+            max_body_size: None,
+        };
+        let response = |status, headers: &[(&str, &str)]| Response {
+            status,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: Vec::new(),
+        };
+        let pet = request(
+            Method::Get,
+            "https://app.api.surehub.io/api/pet/12?with[]=photo",
+        );
+        let mut report = CompatibilityReport::default();
+
+        report.observe(&pet, &response(200, &[("X-Api-Version", "1.4")]));
+        assert!(report.is_empty());
+        let deprecated = response(
+            200,
+            &[
+                ("X-Api-Version", "1.5"),
+                ("Deprecation", "@1559347200"),
+                ("Sunset", "Sat, 01 Jun 2019 12:00:00 GMT"),
+                ("Link", r#"<https://example.com/next>; rel="deprecation""#),
+                ("Warning", r#"299 - "Use /api/v2/pet""#),
+            ],
+        );
+        report.observe(&pet, &deprecated);
+        report.observe(
+            &request(Method::Get, "https://app.api.surehub.io/api/pet/13"),
+            &deprecated,
+        );
+        report.observe(
+            &request(
+                Method::Post,
+                "https://app.api.surehub.io/api/pet/12/position",
+            ),
+            &response(426, &[]),
+        );
+
+        assert_eq!(Some("1.5"), report.server_version());
+        assert_eq!(
+            "the API changed from version 1.4 to 1.5\n\
+             GET /api/pet/{id} is deprecated and stops working at 2019-06-01T12:00:00+00:00, \
+             see https://example.com/next\n\
+             GET /api/pet/{id}: Use /api/v2/pet\n\
+             POST /api/pet/{id}/position is no longer served (426)",
+            report.to_string()
+        );
+        assert_eq!(
+            Some(Timestamp::from_unix(1_559_347_200)),
+            match &report.notices()[1] {
+                Notice::Deprecated { since, .. } => *since,
+                _ => None,
+            }
+        );
+        assert!(!report.is_compatible_at("2019-05-01T00:00:00Z".parse().unwrap()));
+        report.notices.pop();
+        assert!(report.is_compatible_at("2019-05-01T00:00:00Z".parse().unwrap()));
+        assert!(!report.is_compatible_at("2019-06-01T12:00:00Z".parse().unwrap()));
+    }
+}
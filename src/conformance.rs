@@ -0,0 +1,143 @@
+//! Spotting differences between API payloads and the models.
+//!
+//! The API changes without notice, and since models ignore fields they do
+//! not know about, new data goes unnoticed. [`check`](fn.check.html)
+//! parses a payload into a model, serializes it again and compares the
+//! field paths of both, so a payload captured from the live API tells what
+//! the models are missing. Fields an unmodelled catch-all keeps verbatim,
+//! such as an event's [`other`](../timeline/struct.Event.html#method.other)
+//! fields, count as modelled.
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A field found on only one side.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum SchemaDiff {
+    /// The payload has a field the model drops.
+    Unmodelled(String),
+    /// The model has a field the payload does not send, so it was filled
+    /// with a default.
+    Missing(String),
+}
+
+impl SchemaDiff {
+    /// The dotted path of the field, such as `status.version.device`. Array
+    /// elements share the path of their array.
+    pub fn path(&self) -> &str {
+        match self {
+            SchemaDiff::Unmodelled(path) | SchemaDiff::Missing(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaDiff::Unmodelled(path) => write!(f, "{} is not modelled", path),
+            SchemaDiff::Missing(path) => write!(f, "{} is missing from the payload", path),
+        }
+    }
+}
+
+/// Compares a payload with the model `T`. The `data` envelope of API
+/// responses is looked through, so whole response bodies can be passed.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::conformance::{check, SchemaDiff};
+/// use sure_petcare::state::Household;
+/// let diffs = check::<Household>(r#"{"data":{"id":1,"name":"Home","created_at":"2019"}}"#).unwrap();
+/// assert_eq!(vec![SchemaDiff::Unmodelled("created_at".to_string())], diffs);
+/// ```
+pub fn check<T: Deserialize<'static> + Serialize>(raw: &str) -> Result<Vec<SchemaDiff>> {
+    let mut payload: Value = serde_json::from_str(raw)?;
+    if let Value::Object(map) = &mut payload {
+        if map.len() == 1 {
+            if let Some(data) = map.remove("data") {
+                payload = data;
+            }
+        }
+    }
+    // The model is parsed from an owned copy, so borrowing models work too.
+    let model = T::deserialize(payload.clone())?;
+    let model = serde_json::to_value(&model)?;
+
+    let mut sent = BTreeSet::new();
+    paths(&payload, "", &mut sent);
+    let mut kept = BTreeSet::new();
+    paths(&model, "", &mut kept);
+
+    let unmodelled = sent
+        .difference(&kept)
+        .map(|path| SchemaDiff::Unmodelled(path.clone()));
+    let missing = kept
+        .difference(&sent)
+        .map(|path| SchemaDiff::Missing(path.clone()));
+    Ok(unmodelled.chain(missing).collect())
+}
+
+/// Collects the paths of every field. Nulls, and objects holding nothing
+/// but nulls, are what defaulted models serialize to, so they are left out
+/// on both sides.
+fn paths(value: &Value, prefix: &str, found: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                if is_blank(value) {
+                    continue;
+                }
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                paths(value, &path, found);
+                found.insert(path);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                paths(value, prefix, found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_blank(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(map) => map.values().all(is_blank),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Device;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_report_both_directions() {
+        let diffs = check::<Vec<Device>>(
+            r#"{"data":[{"id":1,"name":"Flap","product_id":6,"serial_number":"H0",
+                "status":{"online":true,"led_mode":2,"signal":{"device_rssi":-60}},
+                "control":{"curfew":{"enabled":true,"lock_time":"21:00","unlock_time":"07:00"}}}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                SchemaDiff::Unmodelled("serial_number".to_string()),
+                SchemaDiff::Unmodelled("status.led_mode".to_string()),
+                SchemaDiff::Missing("status.bowl_status".to_string()),
+            ],
+            diffs
+        );
+        assert_eq!("status.led_mode is not modelled", diffs[1].to_string());
+    }
+}
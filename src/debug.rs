@@ -0,0 +1,185 @@
+//! Data for bug reports, with the personal parts taken out.
+//!
+//! Reproducing a problem often needs the account's data, which holds email
+//! addresses, microchip numbers, share codes and the household's location.
+//! [`dump_state`](fn.dump_state.html) writes a state as pretty JSON with
+//! each of those replaced by a pseudonym such as `email-1@example.invalid`
+//! or `chip-2`, and credentials replaced by `REDACTED`. The same value gets
+//! the same pseudonym throughout a dump, so the data still lines up, but
+//! nothing in it leads back to the original. [`dump_json`](fn.dump_json.html)
+//! does the same for any JSON, such as
+//! [`SurePet::last_json`](../client/struct.SurePet.html#method.last_json),
+//! which keeps fields the models drop. The `debug-secrets` feature does not
+//! change dumps.
+use crate::error::Result;
+use crate::secret::{is_email, REDACTED, SECRET_KEYS};
+use crate::state::State;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Keys holding microchip or collar tag numbers.
+const CHIP_KEYS: &[&str] = &["tag", "microchip", "microchip_number", "chip"];
+
+/// Keys holding coordinates.
+const COORDINATE_KEYS: &[&str] = &["latitude", "longitude", "lat", "lon", "lng"];
+
+/// Writes a state as anonymised, pretty printed JSON, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::debug::dump_state;
+/// use sure_petcare::state::State;
+/// let state: State = serde_json::from_str(r#"{"pets":[{"id":1,"name":"Milo"}]}"#)?;
+/// assert!(dump_state(&state)?.contains("Milo"));
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+pub fn dump_state(state: &State<'_>) -> Result<String> {
+    dump_json(&serde_json::to_value(state)?)
+}
+
+/// Writes any JSON as anonymised, pretty printed JSON, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::debug::dump_json;
+/// let user = serde_json::json!({
+///     "email_address": "me@example.com",
+///     "invited_by": "me@example.com",
+///     "share_code": "XYZ123",
+///     "token": "abc.def",
+/// });
+/// let dump: serde_json::Value = serde_json::from_str(&dump_json(&user)?)?;
+/// assert_eq!(
+///     serde_json::json!({
+///         "email_address": "email-1@example.invalid",
+///         "invited_by": "email-1@example.invalid",
+///         "share_code": "share-1",
+///         "token": "REDACTED",
+///     }),
+///     dump
+/// );
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+pub fn dump_json(value: &Value) -> Result<String> {
+    let mut value = value.clone();
+    Pseudonyms::default().anonymise(None, &mut value);
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// The pseudonyms handed out so far, numbered per kind in the order the
+/// values are met.
+#[derive(Debug, Default)]
+struct Pseudonyms {
+    given: HashMap<(&'static str, String), usize>,
+    counts: HashMap<&'static str, usize>,
+}
+
+impl Pseudonyms {
+    fn number(&mut self, kind: &'static str, original: String) -> usize {
+        let counts = &mut self.counts;
+        *self.given.entry((kind, original)).or_insert_with(|| {
+            let count = counts.entry(kind).or_insert(0);
+            *count += 1;
+            *count
+        })
+    }
+
+    fn anonymise(&mut self, key: Option<&str>, value: &mut Value) {
+        let key = key.map(str::to_ascii_lowercase);
+        match (key.as_deref(), &mut *value) {
+            (_, Value::Null) => {}
+            (Some("share_code"), _) => {
+                let number = self.number("share", value.to_string());
+                *value = Value::String(format!("share-{}", number));
+            }
+            (Some(key), _) if SECRET_KEYS.contains(&key) && !key.starts_with("email") => {
+                *value = Value::String(REDACTED.into());
+            }
+            (Some(key), Value::String(_) | Value::Number(_)) if CHIP_KEYS.contains(&key) => {
+                let number = self.number("chip", value.to_string());
+                *value = Value::String(format!("chip-{}", number));
+            }
+            (Some(key), Value::Number(_)) if COORDINATE_KEYS.contains(&key) => {
+                // Keep it a number so the dump still parses into the models.
+                let number = self.number(
+                    if key.starts_with("lat") { "lat" } else { "lon" },
+                    value.to_string(),
+                );
+                *value = serde_json::json!(number as f64 / 1000.0);
+            }
+            (_, Value::String(text)) if is_email(text) => {
+                let number = self.number("email", text.to_ascii_lowercase());
+                *text = format!("email-{}@example.invalid", number);
+            }
+            (_, Value::Object(map)) => {
+                for (key, value) in map.iter_mut() {
+                    self.anonymise(Some(key), value);
+                }
+            }
+            (_, Value::Array(values)) => {
+                for value in values {
+                    self.anonymise(key.as_deref(), value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn should_replace_personal_data_with_stable_pseudonyms() {
+        let start = json!({
+            "data": {
+                "user": {"email_address": "Me@Example.com", "password": "qwerty"},
+                "households": [{
+                    "id": 1,
+                    "name": "Home",
+                    "share_code": "XYZ123",
+                    "latitude": 51.5,
+                    "longitude": -0.12,
+                    "users": [{"email": "me@example.com"}, {"email": "you@example.com"}],
+                }],
+                "tags": [{"id": 5, "tag": "900.000123456789"}, {"id": 6, "tag": "900.000987654321"}],
+                "pets": [{"id": 2, "name": "Milo", "tag": {"id": 5, "tag": "900.000123456789"}}],
+                "note": null,
+            }
+        });
+
+        let dump: Value = serde_json::from_str(&dump_json(&start).unwrap()).unwrap();
+
+        assert_eq!(
+            json!({
+                "data": {
+                    "user": {"email_address": "email-1@example.invalid", "password": "REDACTED"},
+                    "households": [{
+                        "id": 1,
+                        "name": "Home",
+                        "share_code": "share-1",
+                        "latitude": 0.001,
+                        "longitude": 0.001,
+                        "users": [
+                            {"email": "email-1@example.invalid"},
+                            {"email": "email-2@example.invalid"},
+                        ],
+                    }],
+                    "tags": [{"id": 5, "tag": "chip-1"}, {"id": 6, "tag": "chip-2"}],
+                    "pets": [{"id": 2, "name": "Milo", "tag": {"id": 5, "tag": "chip-1"}}],
+                    "note": null,
+                }
+            }),
+            dump
+        );
+        let state: State<'static> =
+            serde_json::from_str(r#"{"households":[{"id":1,"name":"Home"}]}"#).unwrap();
+        let dump: State<'_> = serde_json::from_str(&dump_state(&state).unwrap()).unwrap();
+        assert_eq!(state, dump);
+    }
+}
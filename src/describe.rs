@@ -0,0 +1,246 @@
+//! Describing timeline events in words, in the user's language.
+//!
+//! [`Event::describe`](../timeline/struct.Event.html#method.describe) turns
+//! an event into a sentence such as "Milo came in through Back door", for
+//! chat bots and digests. The phrasing comes from a
+//! [`Catalogue`](struct.Catalogue.html) of messages written in a subset of
+//! the Fluent syntax, one `key = text` per line with `{ $pet }` and
+//! `{ $device }` standing for the names:
+//!
+//! ```text
+//! # Movements through a flap.
+//! movement-in = { $pet } came in through { $device }
+//! ```
+//!
+//! English, German and French are built in. Other languages, or other
+//! phrasing, can be parsed from text; messages a catalogue lacks fall back
+//! to English.
+use crate::timeline::{Direction, Event, EventKind, PET_VISIT};
+use std::collections::HashMap;
+use std::fmt;
+
+const EN: &str = include_str!("describe/en.ftl");
+const DE: &str = include_str!("describe/de.ftl");
+const FR: &str = include_str!("describe/fr.ftl");
+
+/// The error returned when a catalogue fails to parse.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CatalogueError {
+    line: usize,
+    reason: &'static str,
+}
+
+impl CatalogueError {
+    /// The line the error is on, counting from one.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for CatalogueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid catalogue line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for CatalogueError {}
+
+/// Messages describing events in one language, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::describe::Catalogue;
+/// use sure_petcare::timeline::Event;
+/// let event: Event = serde_json::from_str(
+///     r#"{"id":1,"type":0,"created_at":"2019-06-01T07:00:00Z",
+///         "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":10,"name":"die Küchenklappe"}],
+///         "movements":[{"device_id":10,"direction":2}]}"#,
+/// )?;
+/// let catalogue = Catalogue::for_locale("de-AT");
+/// assert_eq!("de", catalogue.locale());
+/// assert_eq!("Milo ging durch die Küchenklappe hinaus", catalogue.describe(&event));
+/// let pirate = Catalogue::parse("en-x-pirate", "movement-out = { $pet } set sail")?;
+/// assert_eq!("Milo set sail", pirate.describe(&event));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Catalogue {
+    locale: String,
+    messages: HashMap<String, String>,
+}
+
+impl Catalogue {
+    /// The built in catalogue closest to a locale such as `de-DE`, matching
+    /// on the language and falling back to English.
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let (locale, source) = match language.as_str() {
+            "de" => ("de", DE),
+            "fr" => ("fr", FR),
+            _ => ("en", EN),
+        };
+        Catalogue::parse(locale, source).expect("built in catalogues parse")
+    }
+
+    /// Parses a catalogue for `locale`. Blank lines and lines starting with
+    /// `#` are skipped.
+    pub fn parse(locale: &str, source: &str) -> Result<Self, CatalogueError> {
+        let mut messages = HashMap::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason| CatalogueError {
+                line: index + 1,
+                reason,
+            };
+            let (key, text) = line.split_once('=').ok_or_else(|| error("missing `=`"))?;
+            let key = key.trim();
+            if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '-') {
+                return Err(error("bad message key"));
+            }
+            if text.matches('{').count() != text.matches('}').count() {
+                return Err(error("unbalanced braces"));
+            }
+            messages.insert(key.to_string(), text.trim().to_string());
+        }
+        Ok(Catalogue {
+            locale: locale.to_string(),
+            messages,
+        })
+    }
+
+    /// The locale the catalogue is for.
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Formats a message, replacing each `{ $name }` with its argument.
+    /// Messages the catalogue lacks come from the English one, and unknown
+    /// keys format as the key.
+    pub fn format(&self, key: &str, arguments: &[(&str, &str)]) -> String {
+        let english;
+        let text = match self.messages.get(key) {
+            Some(text) => text,
+            None => {
+                english = Catalogue::for_locale("en");
+                match english.messages.get(key) {
+                    Some(text) => text,
+                    None => return key.to_string(),
+                }
+            }
+        };
+        let mut formatted = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find('{') {
+            formatted.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .map_or(rest.len(), |end| start + end);
+            let name = rest[start + 1..end].trim().trim_start_matches('$');
+            match arguments.iter().find(|(argument, _)| *argument == name) {
+                Some((_, value)) => formatted.push_str(value),
+                None => formatted.push_str(&rest[start..(end + 1).min(rest.len())]),
+            }
+            rest = rest.get(end + 1..).unwrap_or_default();
+        }
+        formatted.push_str(rest);
+        formatted
+    }
+
+    /// Describes an event, naming its first pet and device.
+    pub fn describe(&self, event: &Event<'_>) -> String {
+        let pet = match event.pets().first() {
+            Some(pet) => pet.name().to_string(),
+            None => self.format("unknown-pet", &[]),
+        };
+        let device = match event.devices().first() {
+            Some(device) => device.name().to_string(),
+            None => self.format("unknown-device", &[]),
+        };
+        let visit = event
+            .weights()
+            .first()
+            .is_none_or(|weight| weight.context() == PET_VISIT);
+        let key = match event.kind() {
+            EventKind::Movement => match event.movements().first().map(|m| m.direction()) {
+                Some(Direction::In) => "movement-in",
+                Some(Direction::Out) => "movement-out",
+                _ => "movement-looked",
+            },
+            EventKind::LowBattery => "low-battery",
+            EventKind::LockStatus => "lock-status",
+            EventKind::IntruderMovement => "intruder",
+            EventKind::Curfew => "curfew",
+            EventKind::Feeding if visit => "feeding",
+            EventKind::Feeding => "feeding-filled",
+            EventKind::Drinking if visit => "drinking",
+            EventKind::Drinking => "drinking-refilled",
+            EventKind::Unknown(_) => "unknown-event",
+        };
+        let code = event.kind().code().to_string();
+        self.format(key, &[("pet", &pet), ("device", &device), ("code", &code)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_describe_events_in_each_language() {
+        let event = |kind: i64, extra: &str| -> Event<'static> {
+            serde_json::from_str::<Event<'_>>(&format!(
+                r#"{{"id":1,"type":{},"created_at":"2019-06-01T07:00:00Z",
+                    "pets":[{{"id":2,"name":"Milo"}}],"devices":[{{"id":10,"name":"Kitchen"}}]{}}}"#,
+                kind, extra
+            ))
+            .unwrap()
+            .into_owned()
+        };
+        let came_in = event(0, r#","movements":[{"direction":1}]"#);
+        let filled = event(22, r#","weights":[{"context":2,"frames":[]}]"#);
+        let unknown: Event<'static> =
+            serde_json::from_str(r#"{"id":1,"type":99,"created_at":"2019-06-01T07:00:00Z"}"#)
+                .unwrap();
+
+        let english = Catalogue::for_locale("en-GB");
+        assert_eq!("Milo came in through Kitchen", came_in.describe("en-GB"));
+        assert_eq!("Kitchen was filled", english.describe(&filled));
+        assert_eq!("Unknown event 99 at a device", english.describe(&unknown));
+        assert_eq!(
+            "Milo ist durch Kitchen hereingekommen",
+            came_in.describe("de")
+        );
+        assert_eq!("Milo est entré par Kitchen", came_in.describe("fr_FR"));
+        assert_eq!("Milo came in through Kitchen", came_in.describe("pt-BR"));
+
+        let partial = Catalogue::parse(
+            "nl",
+            "# Dutch\n\nmovement-in = { $pet } kwam binnen via {$device}",
+        )
+        .unwrap();
+        assert_eq!("Milo kwam binnen via Kitchen", partial.describe(&came_in));
+        assert_eq!("Kitchen was filled", partial.describe(&filled));
+        assert_eq!(
+            Err(2),
+            Catalogue::parse("nl", "a = b\nmovement-in").map_err(|e| e.line())
+        );
+        for (locale, source) in [("en", EN), ("de", DE), ("fr", FR)] {
+            let catalogue = Catalogue::parse(locale, source).unwrap();
+            let keys = |catalogue: &Catalogue| {
+                let mut keys: Vec<String> = catalogue.messages.keys().cloned().collect();
+                keys.sort();
+                keys
+            };
+            assert_eq!(keys(&english), keys(&catalogue), "{}", locale);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+# Namen, wenn ein Ereignis keine nennt.
+unknown-pet = Ein Haustier
+unknown-device = einem Gerät
+
+# Bewegungen durch eine Klappe.
+movement-in = { $pet } ist durch { $device } hereingekommen
+movement-out = { $pet } ging durch { $device } hinaus
+movement-looked = { $pet } hat durch { $device } geschaut
+intruder = Ein fremdes Tier hat es an { $device } versucht
+
+# Gerätemeldungen.
+low-battery = { $device } braucht neue Batterien
+lock-status = { $device } hat den Sperrmodus geändert
+curfew = Eine Sperrzeit hat { $device } umgestellt
+unknown-event = Unbekanntes Ereignis { $code } an { $device }
+
+# Futterautomaten und Trinkbrunnen.
+feeding = { $pet } hat aus { $device } gefressen
+feeding-filled = { $device } wurde befüllt
+drinking = { $pet } hat aus { $device } getrunken
+drinking-refilled = { $device } wurde nachgefüllt
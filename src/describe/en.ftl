@@ -0,0 +1,21 @@
+# Names used when an event does not say.
+unknown-pet = A pet
+unknown-device = a device
+
+# Movements through a flap.
+movement-in = { $pet } came in through { $device }
+movement-out = { $pet } went out through { $device }
+movement-looked = { $pet } looked through { $device }
+intruder = An unknown animal tried { $device }
+
+# Device events.
+low-battery = { $device } needs new batteries
+lock-status = { $device } changed lock mode
+curfew = A curfew changed the lock on { $device }
+unknown-event = Unknown event { $code } at { $device }
+
+# Feeders and water dispensers.
+feeding = { $pet } ate from { $device }
+feeding-filled = { $device } was filled
+drinking = { $pet } drank from { $device }
+drinking-refilled = { $device } was refilled
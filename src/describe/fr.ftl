@@ -0,0 +1,21 @@
+# Noms utilisés quand un événement n'en donne pas.
+unknown-pet = Un animal
+unknown-device = un appareil
+
+# Passages par une chatière.
+movement-in = { $pet } est entré par { $device }
+movement-out = { $pet } est sorti par { $device }
+movement-looked = { $pet } a regardé par { $device }
+intruder = Un animal inconnu a essayé { $device }
+
+# Événements des appareils.
+low-battery = { $device } a besoin de nouvelles piles
+lock-status = { $device } a changé de mode de verrouillage
+curfew = Un couvre-feu a changé le verrouillage de { $device }
+unknown-event = Événement inconnu { $code } sur { $device }
+
+# Distributeurs de nourriture et fontaines.
+feeding = { $pet } a mangé dans { $device }
+feeding-filled = { $device } a été rempli
+drinking = { $pet } a bu dans { $device }
+drinking-refilled = { $device } a été rempli
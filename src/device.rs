@@ -0,0 +1,2097 @@
+//! Hubs, flaps, feeders and water dispensers registered to the account.
+#[cfg(feature = "devices")]
+use crate::batch::BatchResult;
+use crate::battery::Calibration;
+#[cfg(feature = "devices")]
+use crate::client::SurePet;
+use crate::error::{Error, Result, ValidationError};
+use crate::float::HashFloat;
+#[cfg(feature = "devices")]
+use crate::id::TagId;
+use crate::id::{DeviceId, HouseholdId};
+use crate::names::names;
+use crate::patch::Patch;
+#[cfg(feature = "devices")]
+use crate::portion::Portioning;
+#[cfg(feature = "devices")]
+use crate::sort::Listing;
+use crate::sort::{Field, Key, Sortable};
+use crate::time::{self, TimeOfDay, Timestamp};
+#[cfg(feature = "devices")]
+use crate::transport::{Method, Transport};
+use crate::zone::Zone;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// The kind of hardware, as sent in a device's `product_id` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Product {
+    /// The hub that connects the other devices to the internet.
+    Hub,
+    /// A signal repeater.
+    Repeater,
+    /// The large microchip pet door.
+    PetDoor,
+    /// The microchip feeder.
+    Feeder,
+    /// The programmer used to pair tags.
+    Programmer,
+    /// The dual scan microchip cat flap.
+    CatFlap,
+    /// The single bowl feeder.
+    FeederLite,
+    /// The Felaqua water dispenser.
+    Felaqua,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl Product {
+    /// The code the API uses for this product.
+    pub fn code(self) -> i64 {
+        match self {
+            Product::Hub => 1,
+            Product::Repeater => 2,
+            Product::PetDoor => 3,
+            Product::Feeder => 4,
+            Product::Programmer => 5,
+            Product::CatFlap => 6,
+            Product::FeederLite => 7,
+            Product::Felaqua => 8,
+            Product::Unknown(code) => code,
+        }
+    }
+
+    /// How many curfews the product can hold: four for cat flaps, one for
+    /// pet doors and none for anything else.
+    pub fn curfew_slots(self) -> usize {
+        match self {
+            Product::CatFlap => 4,
+            Product::PetDoor => 1,
+            _ => 0,
+        }
+    }
+
+    /// Whether the product is a cat flap or pet door.
+    pub fn is_flap(self) -> bool {
+        matches!(self, Product::CatFlap | Product::PetDoor)
+    }
+
+    /// Whether the product is one of the feeders.
+    pub fn is_feeder(self) -> bool {
+        matches!(self, Product::Feeder | Product::FeederLite)
+    }
+
+    /// A name to show people: `cat flap` for a known product, and for an
+    /// unknown one the name [registered](../product/struct.ProductCatalogue.html#method.register)
+    /// from the API's product list, or `product 42` before one is.
+    pub fn display_name(self) -> String {
+        match self {
+            Product::Unknown(code) => {
+                crate::product::registered_name(code).unwrap_or_else(|| format!("product {}", code))
+            }
+            product => product.to_string().replace('_', " "),
+        }
+    }
+}
+
+impl From<i64> for Product {
+    fn from(code: i64) -> Self {
+        match code {
+            1 => Product::Hub,
+            2 => Product::Repeater,
+            3 => Product::PetDoor,
+            4 => Product::Feeder,
+            5 => Product::Programmer,
+            6 => Product::CatFlap,
+            7 => Product::FeederLite,
+            8 => Product::Felaqua,
+            code => Product::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for Product {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Product {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("product", Product {
+    Hub => "hub",
+    Repeater => "repeater",
+    PetDoor => "pet_door",
+    Feeder => "feeder",
+    Programmer => "programmer",
+    CatFlap => "cat_flap",
+    FeederLite => "feeder_lite",
+    Felaqua => "felaqua",
+});
+
+/// How a flap lets pets through, as sent in a status' `locking.mode` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LockMode {
+    /// Pets can go in and out.
+    Unlocked,
+    /// Pets can come in but not go out.
+    KeepIn,
+    /// Pets can go out but not come in.
+    KeepOut,
+    /// The flap is locked both ways.
+    Locked,
+    /// The flap follows its curfew.
+    Curfew,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl LockMode {
+    /// The code the API uses for this mode.
+    pub fn code(self) -> i64 {
+        match self {
+            LockMode::Unlocked => 0,
+            LockMode::KeepIn => 1,
+            LockMode::KeepOut => 2,
+            LockMode::Locked => 3,
+            LockMode::Curfew => 4,
+            LockMode::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for LockMode {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => LockMode::Unlocked,
+            1 => LockMode::KeepIn,
+            2 => LockMode::KeepOut,
+            3 => LockMode::Locked,
+            4 => LockMode::Curfew,
+            code => LockMode::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for LockMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for LockMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("lock mode", LockMode {
+    Unlocked => "unlocked",
+    KeepIn => "keep_in",
+    KeepOut => "keep_out",
+    Locked => "locked",
+    Curfew => "curfew",
+});
+
+/// How long a feeder waits after a pet leaves before closing its lid, as
+/// sent in a control's `lid.close_delay` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CloseDelay {
+    /// Close straight away.
+    Fast,
+    /// Close after four seconds.
+    Normal,
+    /// Close after twenty seconds, for nervous eaters.
+    Slow,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl CloseDelay {
+    /// The code the API uses for this delay, which is also the delay in
+    /// seconds.
+    pub fn code(self) -> i64 {
+        match self {
+            CloseDelay::Fast => 0,
+            CloseDelay::Normal => 4,
+            CloseDelay::Slow => 20,
+            CloseDelay::Unknown(code) => code,
+        }
+    }
+
+    /// The delay before the lid closes.
+    pub fn duration(self) -> Duration {
+        time::seconds(self.code())
+    }
+}
+
+impl From<i64> for CloseDelay {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => CloseDelay::Fast,
+            4 => CloseDelay::Normal,
+            20 => CloseDelay::Slow,
+            code => CloseDelay::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for CloseDelay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for CloseDelay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("close delay", CloseDelay {
+    Fast => "fast",
+    Normal => "normal",
+    Slow => "slow",
+});
+
+/// How brightly the hub's ears glow, as sent in a control's `led_mode`
+/// field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LedMode {
+    /// The lights are off.
+    Off,
+    /// The lights are at full brightness.
+    Bright,
+    /// The lights are dimmed.
+    Dim,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl LedMode {
+    /// The code the API uses for this mode.
+    pub fn code(self) -> i64 {
+        match self {
+            LedMode::Off => 0,
+            LedMode::Bright => 1,
+            LedMode::Dim => 4,
+            LedMode::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for LedMode {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => LedMode::Off,
+            1 => LedMode::Bright,
+            4 => LedMode::Dim,
+            code => LedMode::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for LedMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for LedMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("led mode", LedMode {
+    Off => "off",
+    Bright => "bright",
+    Dim => "dim",
+});
+
+/// A device and its last reported status.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Device<'a> {
+    pub(crate) id: DeviceId,
+    pub(crate) name: Cow<'a, str>,
+    pub(crate) product_id: Product,
+    #[serde(default)]
+    pub(crate) household_id: Option<HouseholdId>,
+    #[serde(default)]
+    pub(crate) parent_device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) status: Status,
+    #[serde(default)]
+    pub(crate) control: Control,
+    #[serde(default)]
+    pub(crate) last_activity_at: Option<Timestamp>,
+}
+
+impl<'a> Sortable for Device<'a> {
+    fn sort_key(&self, field: Field) -> Key<'_> {
+        match field {
+            Field::Id => Key::Number(self.id.get()),
+            Field::Name => Key::Text(&self.name),
+            Field::UpdatedAt => self.last_activity_at.into(),
+            Field::CreatedAt => Key::None,
+        }
+    }
+}
+
+impl<'a> Device<'a> {
+    /// The device id.
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    /// The name given to the device in the app.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What kind of device it is.
+    pub fn product(&self) -> Product {
+        self.product_id
+    }
+
+    /// The household the device belongs to.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.household_id
+    }
+
+    /// The hub the device talks through, `None` for hubs themselves.
+    pub fn parent_device_id(&self) -> Option<DeviceId> {
+        self.parent_device_id
+    }
+
+    /// The last reported status.
+    pub fn status(&self) -> &Status {
+        &self.status
+    }
+
+    /// The settings the device was last told to use.
+    pub fn control(&self) -> &Control {
+        &self.control
+    }
+
+    /// When the cloud last heard from the device.
+    pub fn last_activity_at(&self) -> Option<Timestamp> {
+        self.last_activity_at
+    }
+}
+
+/// The settings of a device.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Control {
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub(crate) curfew: Vec<Curfew>,
+    #[serde(default)]
+    pub(crate) lid: Option<Lid>,
+    #[serde(default)]
+    pub(crate) led_mode: Option<LedMode>,
+}
+
+/// The lid settings of a feeder.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Lid {
+    pub(crate) close_delay: CloseDelay,
+}
+
+impl Control {
+    /// How long a feeder keeps its lid open, `None` for other devices.
+    pub fn close_delay(&self) -> Option<CloseDelay> {
+        self.lid.map(|lid| lid.close_delay)
+    }
+
+    /// The brightness of a hub's lights, `None` for other devices.
+    pub fn led_mode(&self) -> Option<LedMode> {
+        self.led_mode
+    }
+
+    /// The curfews of a flap. Pet doors have a single curfew, cat flaps up
+    /// to four.
+    pub fn curfews(&self) -> &[Curfew] {
+        &self.curfew
+    }
+
+    /// Whether any curfew keeps the flap locked at `instant`.
+    pub fn is_locked_at(&self, instant: Timestamp, zone: &Zone) -> bool {
+        self.curfew.iter().any(|c| c.is_locked_at(instant, zone))
+    }
+
+    /// The first time after `instant` any curfew locks the flap.
+    pub fn next_lock_after(&self, instant: Timestamp, zone: &Zone) -> Option<Timestamp> {
+        self.curfew
+            .iter()
+            .filter_map(|c| c.next_lock_after(instant, zone))
+            .min()
+    }
+}
+
+/// A daily period during which a flap keeps pets in.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Curfew {
+    #[serde(default)]
+    pub(crate) enabled: bool,
+    pub(crate) lock_time: TimeOfDay,
+    pub(crate) unlock_time: TimeOfDay,
+}
+
+impl Curfew {
+    /// Creates an enabled curfew locking at `lock_time` and unlocking at
+    /// `unlock_time`.
+    pub fn new(lock_time: TimeOfDay, unlock_time: TimeOfDay) -> Self {
+        Curfew {
+            enabled: true,
+            lock_time,
+            unlock_time,
+        }
+    }
+
+    /// The same curfew switched off, keeping its times.
+    pub fn disabled(self) -> Self {
+        Curfew {
+            enabled: false,
+            ..self
+        }
+    }
+
+    /// The same curfew switched on or off, keeping its times.
+    pub fn with_enabled(self, enabled: bool) -> Self {
+        Curfew { enabled, ..self }
+    }
+
+    /// Whether the curfew is switched on.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// When the flap locks, in the household's local time.
+    pub fn lock_time(&self) -> TimeOfDay {
+        self.lock_time
+    }
+
+    /// When the flap unlocks again, the next day if before `lock_time`.
+    pub fn unlock_time(&self) -> TimeOfDay {
+        self.unlock_time
+    }
+
+    /// The period the curfew covers, or the reason it covers none.
+    pub fn window(&self) -> std::result::Result<CurfewWindow, ValidationError> {
+        CurfewWindow::new(self.lock_time, self.unlock_time)
+    }
+
+    /// Whether the curfew keeps the flap locked at `instant`, judged by the
+    /// wall clock of the household's `zone`. Overnight curfews, locking at
+    /// 21:00 and unlocking at 06:30, cover both sides of midnight.
+    /// # Examples
+    ///
+    /// ```
+    /// use sure_petcare::device::Curfew;
+    /// use sure_petcare::zone::Zone;
+    /// let london: Zone = "GMT0BST,M3.5.0/1,M10.5.0".parse()?;
+    /// let curfew = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+    /// // 05:45 in London, an hour ahead of UTC in summer
+    /// assert!(curfew.is_locked_at("2019-07-01T04:45:00Z".parse().unwrap(), &london));
+    /// assert!(!curfew.is_locked_at("2019-07-01T05:45:00Z".parse().unwrap(), &london));
+    /// # Ok::<(), sure_petcare::zone::ZoneError>(())
+    /// ```
+    pub fn is_locked_at(&self, instant: Timestamp, zone: &Zone) -> bool {
+        let (_, now) = zone.local(instant);
+        let (lock, unlock) = (self.lock_time, self.unlock_time);
+        self.enabled
+            && if lock <= unlock {
+                lock <= now && now < unlock
+            } else {
+                now >= lock || now < unlock
+            }
+    }
+
+    /// The first time after `instant` the curfew locks the flap, `None`
+    /// when it is switched off. A lock time the clocks skip over when they
+    /// go forward happens as they change.
+    pub fn next_lock_after(&self, instant: Timestamp, zone: &Zone) -> Option<Timestamp> {
+        self.next_after(self.lock_time, instant, zone)
+    }
+
+    /// The first time after `instant` the curfew unlocks the flap, `None`
+    /// when it is switched off.
+    pub fn next_unlock_after(&self, instant: Timestamp, zone: &Zone) -> Option<Timestamp> {
+        self.next_after(self.unlock_time, instant, zone)
+    }
+
+    fn next_after(&self, time: TimeOfDay, instant: Timestamp, zone: &Zone) -> Option<Timestamp> {
+        if !self.enabled {
+            return None;
+        }
+        let (today, _) = zone.local(instant);
+        (-1..=2)
+            .map(|days| zone.resolve(today.add_days(days), time))
+            .find(|at| *at > instant)
+    }
+}
+
+/// The daily period of a curfew, from locking to unlocking, checked to be
+/// usable. A window whose unlock time is earlier than its lock time runs
+/// overnight, as most curfews do.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::CurfewWindow;
+/// let night = CurfewWindow::new("21:00".parse().unwrap(), "06:30".parse().unwrap())?;
+/// let morning = CurfewWindow::new("06:00".parse().unwrap(), "08:00".parse().unwrap())?;
+/// assert!(night.is_overnight());
+/// assert_eq!(570, night.minutes());
+/// assert!(night.overlaps(&morning));
+/// assert!(CurfewWindow::new("21:00".parse().unwrap(), "21:00".parse().unwrap()).is_err());
+/// # Ok::<(), sure_petcare::error::ValidationError>(())
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CurfewWindow {
+    lock_time: TimeOfDay,
+    unlock_time: TimeOfDay,
+}
+
+impl CurfewWindow {
+    /// A window locking at `lock_time` and unlocking at `unlock_time`, the
+    /// next day if that is earlier. Windows that unlock as they lock are
+    /// rejected, as a flap cannot tell them from no curfew.
+    pub fn new(
+        lock_time: TimeOfDay,
+        unlock_time: TimeOfDay,
+    ) -> std::result::Result<Self, ValidationError> {
+        if lock_time == unlock_time {
+            return Err(ValidationError::new(
+                "curfew",
+                format!("locks and unlocks at the same time, {}", lock_time),
+            ));
+        }
+        Ok(CurfewWindow {
+            lock_time,
+            unlock_time,
+        })
+    }
+
+    /// When the flap locks.
+    pub fn lock_time(&self) -> TimeOfDay {
+        self.lock_time
+    }
+
+    /// When the flap unlocks.
+    pub fn unlock_time(&self) -> TimeOfDay {
+        self.unlock_time
+    }
+
+    /// Whether the window runs past midnight.
+    pub fn is_overnight(&self) -> bool {
+        self.unlock_time < self.lock_time
+    }
+
+    /// How many minutes the flap stays locked.
+    pub fn minutes(&self) -> u16 {
+        (self.unlock_time.minutes() + 1440 - self.lock_time.minutes()) % 1440
+    }
+
+    /// Whether a time of day falls inside the window; the unlock minute
+    /// itself does not.
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        if self.is_overnight() {
+            time >= self.lock_time || time < self.unlock_time
+        } else {
+            self.lock_time <= time && time < self.unlock_time
+        }
+    }
+
+    /// Whether two windows share any minute of the day.
+    pub fn overlaps(&self, other: &CurfewWindow) -> bool {
+        self.spans().iter().any(|(start, end)| {
+            other
+                .spans()
+                .iter()
+                .any(|(other_start, other_end)| start < other_end && other_start < end)
+        })
+    }
+
+    /// Checks a set of windows for a flap: none may overlap another.
+    pub fn check_all(windows: &[CurfewWindow]) -> std::result::Result<(), ValidationError> {
+        for (index, window) in windows.iter().enumerate() {
+            if let Some(other) = windows[index + 1..].iter().find(|w| window.overlaps(w)) {
+                return Err(ValidationError::new(
+                    "curfew",
+                    format!("{} overlaps {}", window, other),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The window as minute ranges within a day, split at midnight.
+    fn spans(&self) -> Vec<(u16, u16)> {
+        let (lock, unlock) = (self.lock_time.minutes(), self.unlock_time.minutes());
+        if self.is_overnight() {
+            vec![(lock, 1440), (0, unlock)]
+        } else {
+            vec![(lock, unlock)]
+        }
+    }
+}
+
+impl fmt::Display for CurfewWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.lock_time, self.unlock_time)
+    }
+}
+
+impl From<CurfewWindow> for Curfew {
+    fn from(window: CurfewWindow) -> Self {
+        Curfew::new(window.lock_time, window.unlock_time)
+    }
+}
+
+/// Pet doors send a single curfew object where cat flaps send a list.
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<Curfew>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Curfew),
+        Many(Vec<Curfew>),
+    }
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        Some(OneOrMany::One(curfew)) => vec![curfew],
+        Some(OneOrMany::Many(curfews)) => curfews,
+        None => Vec::new(),
+    })
+}
+
+/// The status a device last reported to the hub.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Status {
+    #[serde(default)]
+    pub(crate) battery: Option<f64>,
+    #[serde(default)]
+    pub(crate) online: Option<bool>,
+    #[serde(default)]
+    pub(crate) signal: Option<Signal>,
+    #[serde(default)]
+    pub(crate) bowl_status: Vec<BowlStatus>,
+    #[serde(default)]
+    pub(crate) locking: Option<Locking>,
+    #[serde(default)]
+    pub(crate) version: Option<Version>,
+}
+
+impl Eq for Status {}
+
+impl Hash for Status {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.battery.hash_float(state);
+        self.online.hash(state);
+        self.signal.hash(state);
+        self.bowl_status.hash(state);
+        self.locking.hash(state);
+        self.version.hash(state);
+    }
+}
+
+impl Status {
+    /// The battery voltage, `None` for mains powered devices.
+    pub fn battery(&self) -> Option<f64> {
+        self.battery
+    }
+
+    /// The battery level as a percentage, assuming four alkaline cells that
+    /// read 6.0V when new and 4.8V when the device stops working.
+    pub fn battery_percent(&self) -> Option<f64> {
+        self.battery_percent_with(&Calibration::default())
+    }
+
+    /// The battery level as a percentage on the given discharge curve, such
+    /// as `Chemistry::NiMh.calibration()` for rechargeable cells.
+    pub fn battery_percent_with(&self, calibration: &Calibration) -> Option<f64> {
+        self.battery.map(|volts| calibration.percent(volts))
+    }
+
+    /// Whether the device is currently reachable.
+    pub fn online(&self) -> Option<bool> {
+        self.online
+    }
+
+    /// The radio signal strengths between the device and the hub.
+    pub fn signal(&self) -> Option<&Signal> {
+        self.signal.as_ref()
+    }
+
+    /// The current bowl weights of feeders and water dispensers.
+    pub fn bowls(&self) -> &[BowlStatus] {
+        &self.bowl_status
+    }
+
+    /// The lock mode of a flap, `None` for other devices.
+    pub fn lock_mode(&self) -> Option<LockMode> {
+        self.locking.map(|locking| locking.mode)
+    }
+
+    /// The firmware version the device runs, such as `1.177`.
+    pub fn firmware(&self) -> Option<String> {
+        version_string(self.version.as_ref()?.device.as_ref()?.firmware.as_ref())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Locking {
+    pub(crate) mode: LockMode,
+}
+
+/// Versions are numbers for some products and strings for others, so they
+/// are kept as received.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Version {
+    #[serde(default)]
+    pub(crate) device: Option<DeviceVersion>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct DeviceVersion {
+    #[serde(default)]
+    pub(crate) hardware: Option<serde_json::Value>,
+    #[serde(default)]
+    pub(crate) firmware: Option<serde_json::Value>,
+}
+
+/// What the API knows about firmware for a device, from
+/// [`Devices::firmware`](struct.Devices.html#method.firmware).
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::FirmwareUpdate;
+/// let update: FirmwareUpdate = serde_json::from_str(
+///     r#"{"current_version":1.177,"available_version":"1.200","progress":40}"#,
+/// )?;
+/// assert_eq!(Some("1.200".to_string()), update.available_version());
+/// assert!(update.is_available());
+/// assert!(update.is_in_progress());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct FirmwareUpdate {
+    #[serde(default)]
+    pub(crate) current_version: Option<serde_json::Value>,
+    #[serde(default)]
+    pub(crate) available_version: Option<serde_json::Value>,
+    #[serde(default)]
+    pub(crate) progress: Option<u8>,
+    #[serde(default)]
+    pub(crate) checked_at: Option<Timestamp>,
+}
+
+impl FirmwareUpdate {
+    /// The version the device runs, such as `1.177`.
+    pub fn current_version(&self) -> Option<String> {
+        version_string(self.current_version.as_ref())
+    }
+
+    /// The version the device can be updated to, if there is one.
+    pub fn available_version(&self) -> Option<String> {
+        version_string(self.available_version.as_ref())
+    }
+
+    /// How far an update has got as a percentage, `None` when none is
+    /// running.
+    pub fn progress(&self) -> Option<u8> {
+        self.progress
+    }
+
+    /// When the API last checked for new firmware.
+    pub fn checked_at(&self) -> Option<Timestamp> {
+        self.checked_at
+    }
+
+    /// Whether a version other than the current one is available.
+    pub fn is_available(&self) -> bool {
+        match (self.available_version(), self.current_version()) {
+            (Some(available), current) => Some(available) != current,
+            (None, _) => false,
+        }
+    }
+
+    /// Whether an update is running: started, and not yet at 100%.
+    pub fn is_in_progress(&self) -> bool {
+        self.progress.is_some_and(|progress| progress < 100)
+    }
+}
+
+/// A version as a string, whether it was sent as one or as a number.
+fn version_string(version: Option<&serde_json::Value>) -> Option<String> {
+    match version? {
+        serde_json::Value::String(version) => Some(version.clone()),
+        serde_json::Value::Null => None,
+        version => Some(version.to_string()),
+    }
+}
+
+/// Received signal strengths in dBm.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Signal {
+    #[serde(default)]
+    pub(crate) device_rssi: Option<f64>,
+    #[serde(default)]
+    pub(crate) hub_rssi: Option<f64>,
+}
+
+impl Eq for Signal {}
+
+impl Hash for Signal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.device_rssi.hash_float(state);
+        self.hub_rssi.hash_float(state);
+    }
+}
+
+impl Signal {
+    /// The strength of the hub as heard by the device.
+    pub fn device_rssi(&self) -> Option<f64> {
+        self.device_rssi
+    }
+
+    /// The strength of the device as heard by the hub.
+    pub fn hub_rssi(&self) -> Option<f64> {
+        self.hub_rssi
+    }
+}
+
+/// The weight currently in one bowl.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BowlStatus {
+    #[serde(default)]
+    pub(crate) index: u8,
+    pub(crate) current_weight: f64,
+}
+
+impl Eq for BowlStatus {}
+
+impl Hash for BowlStatus {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.current_weight.hash_float(state);
+    }
+}
+
+impl BowlStatus {
+    /// The bowl index, `0` being the left hand bowl.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// The weight in the bowl, grams of food or millilitres of water.
+    pub fn current_weight(&self) -> f64 {
+        self.current_weight
+    }
+}
+
+/// The device endpoints, obtained from [`SurePet::devices`](../client/struct.SurePet.html#method.devices).
+#[cfg(feature = "devices")]
+#[derive(Debug)]
+pub struct Devices<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+#[cfg(feature = "devices")]
+impl<'c, T: Transport> Devices<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Devices { client }
+    }
+
+    /// The control methods for one device, which check the product
+    /// supports an operation before sending it.
+    pub fn handle(&self, device: &Device<'_>) -> DeviceHandle<'c, T> {
+        DeviceHandle {
+            devices: Devices {
+                client: self.client,
+            },
+            id: device.id(),
+            product: device.product(),
+        }
+    }
+
+    /// Fetches every device on the account along with its status.
+    pub fn list(&self) -> Result<Vec<Device<'static>>> {
+        self.client
+            .get("/api/device", &[("with[]", "status".to_string())])
+    }
+
+    /// Like [`list`](#method.list), sorted, see the
+    /// [`sort`](../sort/index.html) module.
+    pub fn query(&self) -> Listing<'c, T, Device<'static>> {
+        Listing::new(
+            self.client,
+            "/api/device",
+            vec![("with[]", "status".to_string())],
+        )
+    }
+
+    /// Fetches a single device with its status, or takes it from the
+    /// client's [state cache](../cache/struct.StateCache.html).
+    pub fn get(&self, device_id: DeviceId) -> Result<Device<'static>> {
+        let cache = self.client.state_cache();
+        if let Some(device) = cache.and_then(|cache| cache.device(device_id)) {
+            return Ok(device);
+        }
+        let device = self.fetch(device_id)?;
+        if let Some(cache) = cache {
+            cache.put_device(device.clone());
+        }
+        Ok(device)
+    }
+
+    fn fetch(&self, device_id: DeviceId) -> Result<Device<'static>> {
+        self.client.get(
+            &format!("/api/device/{}", device_id),
+            &[("with[]", "status".to_string())],
+        )
+    }
+
+    /// Fetches the firmware a device runs and any update available to it,
+    /// with the progress of an update under way.
+    pub fn firmware(&self, device_id: DeviceId) -> Result<FirmwareUpdate> {
+        self.client
+            .get(&format!("/api/device/{}/firmware", device_id), &[])
+    }
+
+    /// Asks the API to look for new firmware for a device, returning what
+    /// it found. Devices install updates themselves once one is available,
+    /// so polling [`firmware`](#method.firmware) afterwards shows the
+    /// progress.
+    pub fn check_firmware(&self, device_id: DeviceId) -> Result<FirmwareUpdate> {
+        let path = format!("/api/device/{}/firmware/check", device_id);
+        let update = self.client.send(Method::Post, &path, &[], None::<&()>)?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_device(device_id);
+        }
+        Ok(update)
+    }
+
+    /// Teaches a flap or feeder a chip, as pressing its learn button and
+    /// presenting the pet would. The pet is let through with the outdoor
+    /// [profile](../pet/enum.TagProfile.html) until told otherwise.
+    pub fn add_tag(&self, device_id: DeviceId, tag_id: TagId) -> Result<()> {
+        let path = format!("/api/device/{}/tag/{}", device_id, tag_id);
+        self.client.send_unit::<()>(Method::Put, &path, None)?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_device(device_id);
+        }
+        Ok(())
+    }
+
+    /// Makes a flap or feeder forget a chip, so the pet wearing it is
+    /// treated as an intruder there.
+    pub fn remove_tag(&self, device_id: DeviceId, tag_id: TagId) -> Result<()> {
+        let path = format!("/api/device/{}/tag/{}", device_id, tag_id);
+        self.client.send_unit::<()>(Method::Delete, &path, None)?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_device(device_id);
+        }
+        Ok(())
+    }
+
+    /// Changes the lock mode of a flap.
+    pub fn set_lock_mode(&self, device_id: DeviceId, mode: LockMode) -> Result<()> {
+        self.update(
+            device_id,
+            &ControlUpdateBuilder::new().with_lock_mode(mode).build(),
+        )
+    }
+
+    /// Replaces the curfews of a flap. Pet doors hold a single curfew, which
+    /// is sent as an object the way they report it, so switch it off with
+    /// [`Curfew::disabled`](struct.Curfew.html#method.disabled) rather than
+    /// sending none.
+    pub fn set_curfews(&self, device: &Device<'_>, curfews: &[Curfew]) -> Result<()> {
+        let mut update = ControlUpdateBuilder::new();
+        match curfews {
+            [curfew] if device.product() == Product::PetDoor => update.with_curfew(*curfew),
+            curfews => update.with_curfews(curfews),
+        };
+        let update = update.build();
+        update.validate(device.product())?;
+        self.update(device.id(), &update)
+    }
+
+    /// Switches a flap's curfews on or off, keeping their lock and unlock
+    /// times. The flap is fetched afresh rather than from the state cache,
+    /// since sending stale curfews back would overwrite newer ones, and a
+    /// flap without curfews fails with `Error::Invalid`.
+    ///
+    /// The curfew counts as on while any of its slots is. Switching it off
+    /// turns off the slots that are on, and the client remembers which they
+    /// were: switching it on again turns back on just those, as long as the
+    /// times have not changed since. Without that memory, as in another
+    /// process, every slot is turned on. Nothing is sent when the curfew is
+    /// already as asked.
+    pub fn set_curfew_enabled(&self, device_id: DeviceId, enabled: bool) -> Result<()> {
+        let device = self.fetch(device_id)?;
+        let curfews = device.control().curfews();
+        if curfews.is_empty() {
+            return Err(Error::Invalid(ValidationError::new(
+                "curfew",
+                "the device has no curfew to switch",
+            )));
+        }
+        if curfews.iter().any(Curfew::enabled) == enabled {
+            return Ok(());
+        }
+        let switched: Vec<Curfew> = if enabled {
+            let remembered = self.client.curfews_off().get(&device_id).cloned();
+            match remembered {
+                Some(before)
+                    if before.len() == curfews.len()
+                        && before.iter().zip(curfews).all(|(before, now)| {
+                            before.lock_time == now.lock_time
+                                && before.unlock_time == now.unlock_time
+                        }) =>
+                {
+                    before
+                }
+                _ => curfews
+                    .iter()
+                    .map(|curfew| curfew.with_enabled(true))
+                    .collect(),
+            }
+        } else {
+            curfews.iter().map(|curfew| curfew.disabled()).collect()
+        };
+        self.set_curfews(&device, &switched)?;
+        let mut curfews_off = self.client.curfews_off();
+        if enabled {
+            curfews_off.remove(&device_id);
+        } else {
+            curfews_off.insert(device_id, curfews.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Replaces the curfews of several flaps, as when putting every flap in
+    /// the house on the same curfew. Flaps that fail do not stop the rest
+    /// from being updated, and devices that cannot hold the curfews fail
+    /// with `Error::Unsupported` or `Error::Invalid` without a request.
+    pub fn set_all_curfews(
+        &self,
+        devices: &[Device<'_>],
+        curfews: &[Curfew],
+    ) -> BatchResult<DeviceId> {
+        devices
+            .iter()
+            .map(|device| (device.id(), self.set_curfews(device, curfews)))
+            .collect()
+    }
+
+    /// Sends a partial update of a device's settings; fields the update
+    /// leaves unset keep their current values.
+    pub fn update(&self, device_id: DeviceId, update: &ControlUpdate) -> Result<()> {
+        let path = format!("/api/device/{}/control", device_id);
+        self.client.send_unit(Method::Put, &path, Some(update))?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_device(device_id);
+        }
+        Ok(())
+    }
+
+    /// Sends an update and reads the device back until it shows the change,
+    /// returning the device as last read. The update is only sent again
+    /// when the reads show it did not land, so an update whose response was
+    /// lost to a timeout is not applied twice. A confirmation with no
+    /// checks could never confirm anything, so it fails with
+    /// `Error::Invalid` before the update is sent.
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+    /// use sure_petcare::device::{Confirmation, ControlUpdateBuilder, LockMode};
+    /// use sure_petcare::id::DeviceId;
+    /// let update = ControlUpdateBuilder::new().with_lock_mode(LockMode::KeepIn).build();
+    /// let flap = client
+    ///     .devices()
+    ///     .update_confirmed(DeviceId::new(1234), &update, Confirmation::new())?;
+    /// assert_eq!(Some(LockMode::KeepIn), flap.status().lock_mode());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn update_confirmed(
+        &self,
+        device_id: DeviceId,
+        update: &ControlUpdate,
+        confirmation: Confirmation,
+    ) -> Result<Device<'static>> {
+        if confirmation.checks == 0 {
+            return Err(Error::Invalid(ValidationError::new(
+                "checks",
+                "at least one read is needed to confirm an update",
+            )));
+        }
+        for _ in 0..=confirmation.resends {
+            match self.update(device_id, update) {
+                Ok(()) | Err(Error::Transport(_)) => {}
+                Err(error) => return Err(error),
+            }
+            for _ in 0..confirmation.checks {
+                std::thread::sleep(confirmation.interval);
+                let device = self.fetch(device_id)?;
+                if update.is_applied_to(&device) {
+                    return Ok(device);
+                }
+            }
+        }
+        Err(Error::Unconfirmed {
+            device_id,
+            attempts: confirmation.resends + 1,
+        })
+    }
+}
+
+/// How [`Devices::update_confirmed`](struct.Devices.html#method.update_confirmed)
+/// checks that an update landed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Confirmation {
+    checks: u32,
+    interval: Duration,
+    resends: u32,
+}
+
+impl Confirmation {
+    /// Five reads two seconds apart after each send, and one resend.
+    pub fn new() -> Self {
+        Confirmation {
+            checks: 5,
+            interval: Duration::from_secs(2),
+            resends: 1,
+        }
+    }
+
+    /// Sets how many times the device is read after each send, at least
+    /// one.
+    pub fn with_checks(mut self, checks: u32) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// Sets the wait before each read. The hub only applies a change when
+    /// the device next checks in, which takes a few seconds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how many times the update is sent again when the reads do not
+    /// show it.
+    pub fn with_resends(mut self, resends: u32) -> Self {
+        self.resends = resends;
+        self
+    }
+}
+
+impl Default for Confirmation {
+    fn default() -> Self {
+        Confirmation::new()
+    }
+}
+
+/// The control methods of one device, obtained from
+/// [`Devices::handle`](struct.Devices.html#method.handle). Operations the
+/// product lacks fail with `Error::Unsupported` without a request.
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::device::{CloseDelay, Product};
+/// for device in client.devices().list()? {
+///     let handle = client.devices().handle(&device);
+///     match device.product() {
+///         Product::CatFlap => handle.lock_in()?,
+///         Product::Feeder => handle.set_close_delay(CloseDelay::Slow)?,
+///         Product::Hub => handle.leds_off()?,
+///         _ => {}
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "devices")]
+#[derive(Debug)]
+pub struct DeviceHandle<'c, T> {
+    devices: Devices<'c, T>,
+    id: DeviceId,
+    product: Product,
+}
+
+#[cfg(feature = "devices")]
+impl<'c, T: Transport> DeviceHandle<'c, T> {
+    /// The device id.
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    /// The kind of hardware.
+    pub fn product(&self) -> Product {
+        self.product
+    }
+
+    /// Lets pets come in but not go out.
+    pub fn lock_in(&self) -> Result<()> {
+        self.set_lock_mode(LockMode::KeepIn)
+    }
+
+    /// Lets pets go out but not come in.
+    pub fn lock_out(&self) -> Result<()> {
+        self.set_lock_mode(LockMode::KeepOut)
+    }
+
+    /// Locks the flap both ways.
+    pub fn lock(&self) -> Result<()> {
+        self.set_lock_mode(LockMode::Locked)
+    }
+
+    /// Lets pets through both ways.
+    pub fn unlock(&self) -> Result<()> {
+        self.set_lock_mode(LockMode::Unlocked)
+    }
+
+    /// Changes the lock mode of a flap.
+    pub fn set_lock_mode(&self, mode: LockMode) -> Result<()> {
+        self.update(&ControlUpdateBuilder::new().with_lock_mode(mode).build())
+    }
+
+    /// Changes how long a feeder keeps its lid open.
+    pub fn set_close_delay(&self, delay: CloseDelay) -> Result<()> {
+        self.update(&ControlUpdateBuilder::new().with_close_delay(delay).build())
+    }
+
+    /// Sets how much food, in grams, a feeder aims to serve in each bowl.
+    pub fn set_bowl_targets(&self, grams: &[u32]) -> Result<()> {
+        self.update(&ControlUpdateBuilder::new().with_bowl_targets(grams).build())
+    }
+
+    /// Sets a feeder's bowl targets from a daily budget, see
+    /// [`Portioning`](../portion/struct.Portioning.html).
+    pub fn set_portions(&self, portioning: &Portioning) -> Result<()> {
+        self.update(&portioning.update()?)
+    }
+
+    /// Switches the hub's lights off.
+    pub fn leds_off(&self) -> Result<()> {
+        self.set_led_mode(LedMode::Off)
+    }
+
+    /// Dims the hub's lights.
+    pub fn leds_dim(&self) -> Result<()> {
+        self.set_led_mode(LedMode::Dim)
+    }
+
+    /// Turns the hub's lights up to full brightness.
+    pub fn leds_bright(&self) -> Result<()> {
+        self.set_led_mode(LedMode::Bright)
+    }
+
+    /// Changes the brightness of the hub's lights.
+    pub fn set_led_mode(&self, mode: LedMode) -> Result<()> {
+        self.update(&ControlUpdateBuilder::new().with_led_mode(mode).build())
+    }
+
+    /// Sends a partial update after
+    /// [validating](struct.ControlUpdate.html#method.validate) it for the
+    /// product.
+    pub fn update(&self, update: &ControlUpdate) -> Result<()> {
+        update.validate(self.product)?;
+        self.devices.update(self.id, update)
+    }
+}
+
+/// A partial update of a device's settings, for
+/// [`Devices::update`](struct.Devices.html#method.update).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ControlUpdate {
+    #[serde(skip_serializing_if = "Patch::is_unset")]
+    locking: Patch<LockMode>,
+    #[serde(skip_serializing_if = "Patch::is_unset")]
+    curfew: Patch<CurfewUpdate>,
+    #[serde(skip_serializing_if = "Patch::is_unset")]
+    lid: Patch<Lid>,
+    #[serde(skip_serializing_if = "Patch::is_unset")]
+    led_mode: Patch<LedMode>,
+    #[serde(skip_serializing_if = "Patch::is_unset")]
+    bowls: Patch<Bowls>,
+}
+
+/// The most food, in grams, a bowl target may ask for; more than any bowl
+/// holds.
+pub(crate) const MAX_BOWL_TARGET: u32 = 1000;
+
+/// The bowl settings of a feeder: one big bowl or two halves.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+struct Bowls {
+    #[serde(rename = "type")]
+    kind: i64,
+    settings: Vec<BowlSetting>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+struct BowlSetting {
+    target: u32,
+}
+
+impl ControlUpdate {
+    /// Checks the update before it is sent: settings the product does not
+    /// have fail with `Error::Unsupported`, and values it cannot take, such
+    /// as more curfews than it has slots for or enabled curfews that overlap,
+    /// fail with `Error::Invalid`. The API can otherwise reject an update or
+    /// apply only part of it.
+    /// # Examples
+    ///
+    /// ```
+    /// use sure_petcare::device::{ControlUpdateBuilder, Curfew, Product};
+    /// let night = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+    /// let noon = Curfew::new("12:00".parse().unwrap(), "13:00".parse().unwrap());
+    /// let update = ControlUpdateBuilder::new().with_curfews(&[night, noon]).build();
+    /// assert!(update.validate(Product::CatFlap).is_ok());
+    /// assert_eq!(
+    ///     "invalid request: curfew has 2 entries but a pet_door holds at most 1",
+    ///     update.validate(Product::PetDoor).unwrap_err().to_string()
+    /// );
+    /// ```
+    pub fn validate(&self, product: Product) -> Result<()> {
+        let unsupported = |operation| Error::Unsupported { product, operation };
+        let invalid = |field, reason: String| Error::from(ValidationError::new(field, reason));
+        if !self.locking.is_unset() && !product.is_flap() {
+            return Err(unsupported("changing the lock mode"));
+        }
+        match self.locking {
+            Patch::Null => return Err(invalid("locking", "cannot be cleared".into())),
+            Patch::Value(LockMode::Unknown(code)) => {
+                return Err(invalid("locking", format!("has unknown mode {}", code)))
+            }
+            _ => {}
+        }
+        let slots = product.curfew_slots();
+        if !self.curfew.is_unset() && slots == 0 {
+            return Err(unsupported("setting curfews"));
+        }
+        if let Patch::Value(CurfewUpdate::Many(curfews)) = &self.curfew {
+            if curfews.len() > slots {
+                return Err(invalid(
+                    "curfew",
+                    format!(
+                        "has {} entries but a {} holds at most {}",
+                        curfews.len(),
+                        product,
+                        slots
+                    ),
+                ));
+            }
+        }
+        let curfews = match &self.curfew {
+            Patch::Value(CurfewUpdate::One(curfew)) => std::slice::from_ref(curfew),
+            Patch::Value(CurfewUpdate::Many(curfews)) => &curfews[..],
+            _ => &[],
+        };
+        let windows = curfews
+            .iter()
+            .filter(|curfew| curfew.enabled)
+            .map(Curfew::window)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        CurfewWindow::check_all(&windows)?;
+        if !self.lid.is_unset() && !product.is_feeder() {
+            return Err(unsupported("changing the close delay"));
+        }
+        match self.lid {
+            Patch::Null => return Err(invalid("lid", "cannot be cleared".into())),
+            Patch::Value(Lid {
+                close_delay: CloseDelay::Unknown(code),
+            }) => return Err(invalid("lid", format!("has unknown close delay {}", code))),
+            _ => {}
+        }
+        if !self.led_mode.is_unset() && product != Product::Hub {
+            return Err(unsupported("changing the lights"));
+        }
+        match self.led_mode {
+            Patch::Null => return Err(invalid("led_mode", "cannot be cleared".into())),
+            Patch::Value(LedMode::Unknown(code)) => {
+                return Err(invalid("led_mode", format!("has unknown mode {}", code)))
+            }
+            _ => {}
+        }
+        if !self.bowls.is_unset() && !product.is_feeder() {
+            return Err(unsupported("setting bowl targets"));
+        }
+        match &self.bowls {
+            Patch::Null => return Err(invalid("bowls", "cannot be cleared".into())),
+            Patch::Value(bowls) => {
+                let most = if product == Product::Feeder { 2 } else { 1 };
+                if bowls.settings.is_empty() || bowls.settings.len() > most {
+                    return Err(invalid(
+                        "bowls",
+                        format!(
+                            "has {} targets but a {} has {} bowl{}",
+                            bowls.settings.len(),
+                            product,
+                            if most == 1 { "one" } else { "one or two" },
+                            if most == 1 { "" } else { "s" }
+                        ),
+                    ));
+                }
+                if let Some(setting) = bowls.settings.iter().find(|s| s.target > MAX_BOWL_TARGET) {
+                    return Err(invalid(
+                        "bowls",
+                        format!(
+                            "target of {} g is over the {} g limit",
+                            setting.target, MAX_BOWL_TARGET
+                        ),
+                    ));
+                }
+            }
+            Patch::Unset => {}
+        }
+        Ok(())
+    }
+
+    /// Whether a device read after the update shows every field it sets.
+    /// Bowl targets are not reported back, so they are not checked.
+    pub fn is_applied_to(&self, device: &Device<'_>) -> bool {
+        let locking = match self.locking {
+            Patch::Value(mode) => device.status().lock_mode() == Some(mode),
+            _ => true,
+        };
+        let curfew = match &self.curfew {
+            Patch::Value(CurfewUpdate::One(curfew)) => device.control().curfews() == [*curfew],
+            Patch::Value(CurfewUpdate::Many(curfews)) => device.control().curfews() == &curfews[..],
+            Patch::Null => device.control().curfews().is_empty(),
+            Patch::Unset => true,
+        };
+        let lid = match self.lid {
+            Patch::Value(lid) => device.control().close_delay() == Some(lid.close_delay),
+            _ => true,
+        };
+        let led_mode = match self.led_mode {
+            Patch::Value(mode) => device.control().led_mode() == Some(mode),
+            _ => true,
+        };
+        locking && curfew && lid && led_mode
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(untagged)]
+enum CurfewUpdate {
+    One(Curfew),
+    Many(Vec<Curfew>),
+}
+
+/// A builder for a partial update of a device's settings. Only the fields
+/// set on the builder are sent.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::{ControlUpdateBuilder, LockMode};
+/// let update = ControlUpdateBuilder::new()
+///     .with_lock_mode(LockMode::KeepIn)
+///     .clear_curfews()
+///     .build();
+/// assert_eq!(
+///     r#"{"locking":1,"curfew":null}"#,
+///     serde_json::to_string(&update).unwrap()
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ControlUpdateBuilder {
+    update: ControlUpdate,
+}
+
+impl ControlUpdateBuilder {
+    /// Create a new builder that changes nothing.
+    pub fn new() -> Self {
+        ControlUpdateBuilder::default()
+    }
+
+    /// Sets the lock mode of a flap.
+    pub fn with_lock_mode(&mut self, mode: LockMode) -> &mut Self {
+        self.update.locking = Patch::Value(mode);
+        self
+    }
+
+    /// Replaces the curfews of a cat flap.
+    pub fn with_curfews(&mut self, curfews: &[Curfew]) -> &mut Self {
+        self.update.curfew = Patch::Value(CurfewUpdate::Many(curfews.to_vec()));
+        self
+    }
+
+    /// Replaces the single curfew of a pet door, sent as an object.
+    pub fn with_curfew(&mut self, curfew: Curfew) -> &mut Self {
+        self.update.curfew = Patch::Value(CurfewUpdate::One(curfew));
+        self
+    }
+
+    /// Removes every curfew by sending an explicit `null`.
+    pub fn clear_curfews(&mut self) -> &mut Self {
+        self.update.curfew = Patch::Null;
+        self
+    }
+
+    /// Sets how long a feeder keeps its lid open.
+    pub fn with_close_delay(&mut self, delay: CloseDelay) -> &mut Self {
+        self.update.lid = Patch::Value(Lid { close_delay: delay });
+        self
+    }
+
+    /// Sets the brightness of a hub's lights.
+    pub fn with_led_mode(&mut self, mode: LedMode) -> &mut Self {
+        self.update.led_mode = Patch::Value(mode);
+        self
+    }
+
+    /// Sets how much food, in grams, a feeder aims to serve: one target
+    /// for a single bowl, or two for a bowl split in halves.
+    pub fn with_bowl_targets(&mut self, grams: &[u32]) -> &mut Self {
+        self.update.bowls = Patch::Value(Bowls {
+            kind: if grams.len() > 1 { 4 } else { 1 },
+            settings: grams.iter().map(|&target| BowlSetting { target }).collect(),
+        });
+        self
+    }
+
+    /// Builds the update.
+    pub fn build(&self) -> ControlUpdate {
+        self.update.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "devices")]
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_display_and_parse_names() {
+        assert_eq!("keep_in", LockMode::KeepIn.to_string());
+        assert_eq!("7", LockMode::Unknown(7).to_string());
+        assert_eq!(Ok(LockMode::KeepOut), "Keep-Out".parse());
+        assert_eq!(Ok(LockMode::Unknown(7)), "7".parse());
+        assert_eq!(Ok(Product::CatFlap), "cat flap".parse());
+        assert_eq!(
+            "unknown lock mode `ajar`",
+            "ajar".parse::<LockMode>().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_list_devices() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[
+                {"id":10,"name":"Hub","product_id":1,"household_id":1,
+                 "status":{"online":true}},
+                {"id":11,"name":"Water","product_id":8,"household_id":1,"parent_device_id":10,
+                 "status":{"battery":5.8,"online":true,"version":{"device":{"hardware":3,"firmware":1.177}},
+                           "signal":{"device_rssi":-70.5,"hub_rssi":-61},
+                           "bowl_status":[{"index":0,"current_weight":412.5}]}},
+                {"id":13,"name":"Back door","product_id":6,"status":{"locking":{"mode":1}},
+                 "control":{"curfew":[{"enabled":true,"lock_time":"19:00","unlock_time":"07:00"}]}},
+                {"id":14,"name":"Pet door","product_id":3,
+                 "control":{"curfew":{"enabled":false,"lock_time":"22:00","unlock_time":"06:30"}}},
+                {"id":12,"name":"New","product_id":42}
+            ]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let devices = client.devices().list().unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/device?with%5B%5D=status",
+            transport.requests()[0].url
+        );
+        assert_eq!(Product::Felaqua, devices[1].product());
+        assert_eq!(Some(5.8), devices[1].status().battery());
+        assert_eq!(
+            Some(-61.0),
+            devices[1].status().signal().unwrap().hub_rssi()
+        );
+        assert_eq!(412.5, devices[1].status().bowls()[0].current_weight());
+        assert_eq!(Some("1.177".to_string()), devices[1].status().firmware());
+        assert_eq!(None, devices[0].status().firmware());
+        assert_eq!(Some(LockMode::KeepIn), devices[2].status().lock_mode());
+        assert_eq!(
+            "19:00",
+            devices[2].control().curfews()[0].lock_time().to_string()
+        );
+        assert!(!devices[3].control().curfews()[0].enabled());
+        assert_eq!(Product::Unknown(42), devices[4].product());
+        assert_eq!(None, devices[4].status().online());
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_fetch_and_check_firmware() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"current_version":1.177,"available_version":null,
+                    "checked_at":"2019-06-01T07:00:00+00:00"}}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":{"current_version":1.177,"available_version":1.2,"progress":0}}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+
+        let firmware = client.devices().firmware(DeviceId::new(11)).unwrap();
+        assert_eq!(Some("1.177".to_string()), firmware.current_version());
+        assert!(!firmware.is_available());
+        assert!(!firmware.is_in_progress());
+        assert_eq!(
+            Some(Timestamp::from_unix(1_559_372_400)),
+            firmware.checked_at()
+        );
+
+        let checked = client.devices().check_firmware(DeviceId::new(11)).unwrap();
+        assert_eq!(Some("1.2".to_string()), checked.available_version());
+        assert!(checked.is_in_progress());
+        let requests = transport.requests();
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/11/firmware",
+            requests[0].url
+        );
+        assert_eq!(Method::Post, requests[1].method);
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/11/firmware/check",
+            requests[1].url
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_pair_and_unpair_tags() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .respond(204, "");
+        let client = SurePet::new(&transport, "token");
+
+        client
+            .devices()
+            .add_tag(DeviceId::new(10), TagId::new(5))
+            .unwrap();
+        client
+            .devices()
+            .remove_tag(DeviceId::new(10), TagId::new(5))
+            .unwrap();
+        let requests = transport.requests();
+        assert_eq!(
+            vec![Method::Put, Method::Delete],
+            requests.iter().map(|r| r.method).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/10/tag/5",
+            requests[1].url
+        );
+        assert_eq!(None, requests[0].body);
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_switch_curfews_keeping_their_times() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"id":13,"name":"Back door","product_id":6,"control":{"curfew":[
+                    {"enabled":true,"lock_time":"19:00","unlock_time":"07:00"},
+                    {"enabled":false,"lock_time":"12:00","unlock_time":"13:00"}]}}}"#,
+            )
+            .respond(200, r#"{"data":{}}"#)
+            .respond(
+                200,
+                r#"{"data":{"id":14,"name":"Pet door","product_id":3,
+                    "control":{"curfew":{"enabled":false,"lock_time":"22:00","unlock_time":"06:30"}}}}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":{"id":10,"name":"Hub","product_id":1,"control":{}}}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+
+        client
+            .devices()
+            .set_curfew_enabled(DeviceId::new(13), false)
+            .unwrap();
+        client
+            .devices()
+            .set_curfew_enabled(DeviceId::new(14), false)
+            .unwrap();
+        assert!(matches!(
+            client.devices().set_curfew_enabled(DeviceId::new(10), true),
+            Err(Error::Invalid(_))
+        ));
+
+        let requests = transport.requests();
+        assert_eq!(4, requests.len());
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/13/control",
+            requests[1].url
+        );
+        assert_eq!(
+            Some(
+                &br#"{"curfew":[{"enabled":false,"lock_time":"19:00","unlock_time":"07:00"},{"enabled":false,"lock_time":"12:00","unlock_time":"13:00"}]}"#[..]
+            ),
+            requests[1].body.as_deref()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_switch_curfews_back_on_leaving_disabled_slots_off() {
+        let flap = |first: bool, second: bool| {
+            format!(
+                r#"{{"data":{{"id":13,"name":"Back door","product_id":6,"control":{{"curfew":[
+                    {{"enabled":{},"lock_time":"19:00","unlock_time":"07:00"}},
+                    {{"enabled":{},"lock_time":"12:00","unlock_time":"13:00"}}]}}}}}}"#,
+                first, second
+            )
+        };
+        let transport = MockTransport::new()
+            .respond(200, &flap(true, false))
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, &flap(false, false))
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, &flap(true, false))
+            .respond(200, &flap(false, false))
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token");
+        let devices = client.devices();
+
+        devices
+            .set_curfew_enabled(DeviceId::new(13), false)
+            .unwrap();
+        devices.set_curfew_enabled(DeviceId::new(13), true).unwrap();
+        devices.set_curfew_enabled(DeviceId::new(13), true).unwrap();
+        SurePet::new(&transport, "token")
+            .devices()
+            .set_curfew_enabled(DeviceId::new(13), true)
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(7, requests.len());
+        assert_eq!(
+            Some(
+                &br#"{"curfew":[{"enabled":true,"lock_time":"19:00","unlock_time":"07:00"},{"enabled":false,"lock_time":"12:00","unlock_time":"13:00"}]}"#[..]
+            ),
+            requests[3].body.as_deref()
+        );
+        assert_eq!(
+            Some(
+                &br#"{"curfew":[{"enabled":true,"lock_time":"19:00","unlock_time":"07:00"},{"enabled":true,"lock_time":"12:00","unlock_time":"13:00"}]}"#[..]
+            ),
+            requests[6].body.as_deref()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_set_lock_mode() {
+        let transport = MockTransport::new().respond(200, r#"{"data":{"locking":3}}"#);
+        let client = SurePet::new(&transport, "token");
+        client
+            .devices()
+            .set_lock_mode(DeviceId::new(13), LockMode::Locked)
+            .unwrap();
+
+        let request = &transport.requests()[0];
+        assert_eq!(Method::Put, request.method);
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/13/control",
+            request.url
+        );
+        assert_eq!(Some(&br#"{"locking":3}"#[..]), request.body.as_deref());
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_check_the_product_before_controlling() {
+        let transport = MockTransport::new().respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token");
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":10,"name":"Hub","product_id":1,"control":{"led_mode":4}},
+                {"id":11,"name":"Feeder","product_id":4,"control":{"lid":{"close_delay":20}}}]"#,
+        )
+        .unwrap();
+        assert_eq!(Some(LedMode::Dim), devices[0].control().led_mode());
+        assert_eq!(Some(CloseDelay::Slow), devices[1].control().close_delay());
+
+        let feeder = client.devices().handle(&devices[1]);
+        feeder.set_close_delay(CloseDelay::Fast).unwrap();
+        match feeder.lock_in() {
+            Err(Error::Unsupported { product, .. }) => assert_eq!(Product::Feeder, product),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(1, transport.requests().len());
+        assert_eq!(
+            Some(&br#"{"lid":{"close_delay":0}}"#[..]),
+            transport.requests()[0].body.as_deref()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_send_pet_door_curfew_as_object() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token");
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":13,"name":"Back door","product_id":6},{"id":14,"name":"Pet door","product_id":3}]"#,
+        )
+        .unwrap();
+        let curfew = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+        client
+            .devices()
+            .set_curfews(&devices[0], &[curfew])
+            .unwrap();
+        client
+            .devices()
+            .set_curfews(&devices[1], &[curfew.disabled()])
+            .unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(
+            r#"{"curfew":[{"enabled":true,"lock_time":"21:00","unlock_time":"06:30"}]}"#,
+            String::from_utf8_lossy(requests[0].body.as_ref().unwrap())
+        );
+        assert_eq!(
+            r#"{"curfew":{"enabled":false,"lock_time":"21:00","unlock_time":"06:30"}}"#,
+            String::from_utf8_lossy(requests[1].body.as_ref().unwrap())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_carry_on_past_devices_that_fail() {
+        let transport = MockTransport::new()
+            .respond(500, "oops")
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token");
+        let devices: Vec<Device<'static>> = serde_json::from_str(
+            r#"[{"id":13,"name":"Back door","product_id":6},
+                {"id":15,"name":"Bowl","product_id":4},
+                {"id":14,"name":"Pet door","product_id":3}]"#,
+        )
+        .unwrap();
+        let curfew = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+        let batch = client.devices().set_all_curfews(&devices, &[curfew]);
+
+        assert_eq!(vec![DeviceId::new(14)], batch.succeeded_keys());
+        assert_eq!(
+            vec![DeviceId::new(13), DeviceId::new(15)],
+            batch.failed_keys()
+        );
+        assert!(matches!(
+            batch.failed()[1].1,
+            Error::Unsupported {
+                product: Product::Feeder,
+                ..
+            }
+        ));
+        assert_eq!(2, transport.requests().len());
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_resend_only_when_the_update_did_not_land() {
+        let flap = |mode: i64| {
+            format!(
+                r#"{{"data":{{"id":13,"name":"Back door","product_id":6,"status":{{"locking":{{"mode":{}}}}}}}}}"#,
+                mode
+            )
+        };
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, &flap(0))
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, &flap(1));
+        let client = SurePet::new(&transport, "token");
+        let update = ControlUpdateBuilder::new()
+            .with_lock_mode(LockMode::KeepIn)
+            .build();
+        let confirmation = Confirmation::new()
+            .with_checks(1)
+            .with_interval(Duration::from_secs(0));
+        let device = client
+            .devices()
+            .update_confirmed(DeviceId::new(13), &update, confirmation)
+            .unwrap();
+
+        assert_eq!(Some(LockMode::KeepIn), device.status().lock_mode());
+        let methods: Vec<Method> = transport.requests().iter().map(|r| r.method).collect();
+        assert_eq!(
+            vec![Method::Put, Method::Get, Method::Put, Method::Get],
+            methods
+        );
+        match client.devices().update_confirmed(
+            DeviceId::new(13),
+            &update,
+            confirmation.with_resends(0),
+        ) {
+            Err(Error::Transport(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "devices")]
+    fn should_refuse_to_confirm_without_checks() {
+        let transport = MockTransport::new();
+        let client = SurePet::new(&transport, "token");
+        let update = ControlUpdateBuilder::new()
+            .with_lock_mode(LockMode::KeepIn)
+            .build();
+
+        assert!(matches!(
+            client.devices().update_confirmed(
+                DeviceId::new(13),
+                &update,
+                Confirmation::new().with_checks(0)
+            ),
+            Err(Error::Invalid(_))
+        ));
+        assert_eq!(0, transport.requests().len());
+    }
+
+    #[test]
+    fn should_find_curfew_times_across_daylight_saving_changes() {
+        let london: Zone = "GMT0BST,M3.5.0/1,M10.5.0".parse().unwrap();
+        let at = |s: &str| s.parse::<Timestamp>().unwrap();
+        let curfew = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+
+        // The clocks go forward overnight, so the flap unlocks an hour
+        // earlier by UTC than it locked.
+        let evening = at("2019-03-30T21:00:00Z");
+        assert_eq!(
+            Some(at("2019-03-31T05:30:00Z")),
+            curfew.next_unlock_after(evening, &london)
+        );
+        assert_eq!(
+            Some(at("2019-03-31T20:00:00Z")),
+            curfew.next_lock_after(evening, &london)
+        );
+        assert!(curfew.is_locked_at(at("2019-03-31T05:29:00Z"), &london));
+        assert!(!curfew.is_locked_at(at("2019-03-31T05:30:00Z"), &london));
+        assert_eq!(None, curfew.disabled().next_lock_after(evening, &london));
+        let control = Control {
+            curfew: vec![
+                curfew,
+                Curfew::new("12:00".parse().unwrap(), "13:00".parse().unwrap()),
+            ],
+            ..Control::default()
+        };
+        assert_eq!(
+            Some(at("2019-03-31T11:00:00Z")),
+            control.next_lock_after(evening, &london)
+        );
+    }
+
+    #[test]
+    fn should_validate_updates_for_the_product() {
+        let curfew = Curfew::new("21:00".parse().unwrap(), "06:30".parse().unwrap());
+        let curfews = ControlUpdateBuilder::new()
+            .with_curfews(&[curfew; 5])
+            .build();
+        let targets = ControlUpdateBuilder::new()
+            .with_bowl_targets(&[25, 1200])
+            .build();
+        let unknown = ControlUpdateBuilder::new()
+            .with_lock_mode(LockMode::Unknown(9))
+            .build();
+
+        let reason = |result: Result<()>| match result {
+            Err(Error::Invalid(error)) => (error.field(), error.reason().to_string()),
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            (
+                "curfew",
+                "has 5 entries but a cat_flap holds at most 4".to_string()
+            ),
+            reason(curfews.validate(Product::CatFlap))
+        );
+        assert_eq!(
+            (
+                "bowls",
+                "target of 1200 g is over the 1000 g limit".to_string()
+            ),
+            reason(targets.validate(Product::Feeder))
+        );
+        assert_eq!(
+            (
+                "bowls",
+                "has 2 targets but a feeder_lite has one bowl".to_string()
+            ),
+            reason(targets.validate(Product::FeederLite))
+        );
+        assert_eq!(
+            ("locking", "has unknown mode 9".to_string()),
+            reason(unknown.validate(Product::PetDoor))
+        );
+        assert!(matches!(
+            targets.validate(Product::CatFlap),
+            Err(Error::Unsupported { .. })
+        ));
+        assert_eq!(
+            r#"{"bowls":{"type":1,"settings":[{"target":40}]}}"#,
+            serde_json::to_string(&ControlUpdateBuilder::new().with_bowl_targets(&[40]).build())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn should_reject_empty_and_overlapping_curfews() {
+        let window = |lock: &str, unlock: &str| {
+            CurfewWindow::new(lock.parse().unwrap(), unlock.parse().unwrap()).unwrap()
+        };
+        let night = window("21:00", "06:30");
+        let early = window("05:00", "07:00");
+        let midday = window("12:00", "13:00");
+
+        assert!(night.contains("23:59".parse().unwrap()));
+        assert!(!night.contains("06:30".parse().unwrap()));
+        assert!(!night.overlaps(&midday));
+        assert!(!window("06:30", "21:00").overlaps(&night));
+        assert_eq!(
+            "21:00-06:30 overlaps 05:00-07:00",
+            CurfewWindow::check_all(&[night, midday, early])
+                .unwrap_err()
+                .reason()
+        );
+
+        let update = ControlUpdateBuilder::new()
+            .with_curfews(&[
+                night.into(),
+                Curfew::new("09:00".parse().unwrap(), "09:00".parse().unwrap()),
+            ])
+            .build();
+        match update.validate(Product::CatFlap) {
+            Err(Error::Invalid(error)) => assert_eq!(
+                "curfew locks and unlocks at the same time, 09:00",
+                error.to_string()
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let disabled = ControlUpdateBuilder::new()
+            .with_curfews(&[night.into(), Curfew::from(early).disabled()])
+            .build();
+        assert!(disabled.validate(Product::CatFlap).is_ok());
+    }
+}
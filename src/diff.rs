@@ -0,0 +1,222 @@
+//! Comparing two start states.
+//!
+//! Some consumers would rather fetch the whole start state on a schedule
+//! than follow the timeline. [`StateDiff::between`](struct.StateDiff.html#method.between)
+//! compares two snapshots and lists what changed as typed
+//! [`StateChange`](enum.StateChange.html)s, much like the changes a
+//! [`StateSync`](../sync/struct.StateSync.html) reports from events.
+use crate::device::{Device, LockMode};
+use crate::id::{DeviceId, PetId};
+use crate::pet::Position;
+use crate::state::State;
+use serde::Serialize;
+
+/// One difference between two start states.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum StateChange {
+    /// A pet is somewhere else, or went there again.
+    PetMoved {
+        /// The pet.
+        pet_id: PetId,
+        /// Where it was, `None` if that was not known.
+        from: Option<Position>,
+        /// Where it is now.
+        to: Position,
+    },
+    /// A flap has a different lock mode.
+    LockModeChanged {
+        /// The flap.
+        device_id: DeviceId,
+        /// The old mode, `None` if it was not reported.
+        from: Option<LockMode>,
+        /// The new mode, `None` if it is no longer reported.
+        to: Option<LockMode>,
+    },
+    /// A device's battery reads lower than it did.
+    BatteryDropped {
+        /// The device.
+        device_id: DeviceId,
+        /// The old voltage.
+        from: f64,
+        /// The new voltage.
+        to: f64,
+    },
+    /// A device is in the new state but not the old one.
+    DeviceAdded {
+        /// The device.
+        device_id: DeviceId,
+    },
+    /// A device is in the old state but not the new one.
+    DeviceRemoved {
+        /// The device.
+        device_id: DeviceId,
+    },
+}
+
+/// The changes between two start states, pets first and then devices, each
+/// in the order of the new state.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::diff::{StateChange, StateDiff};
+/// use sure_petcare::state::State;
+/// let before: State = serde_json::from_str(
+///     r#"{"devices":[{"id":10,"name":"Back door","product_id":6,"status":{"battery":5.8}}]}"#,
+/// )?;
+/// let after: State = serde_json::from_str(
+///     r#"{"devices":[{"id":10,"name":"Back door","product_id":6,"status":{"battery":5.6}}]}"#,
+/// )?;
+/// let diff = StateDiff::between(&before, &after);
+/// assert!(matches!(diff.changes()[0], StateChange::BatteryDropped { to, .. } if to == 5.6));
+/// assert!(StateDiff::between(&after, &after).is_empty());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StateDiff {
+    changes: Vec<StateChange>,
+}
+
+impl StateDiff {
+    /// Compares two snapshots of the same account.
+    pub fn between(old: &State<'_>, new: &State<'_>) -> Self {
+        let mut changes = Vec::new();
+        for pet in new.pets() {
+            let to = match pet.position() {
+                Some(position) => *position,
+                None => continue,
+            };
+            let from = old.pet(pet.id()).and_then(|old| old.position()).copied();
+            if from != Some(to) {
+                changes.push(StateChange::PetMoved {
+                    pet_id: pet.id(),
+                    from,
+                    to,
+                });
+            }
+        }
+        for device in new.devices() {
+            match old.device(device.id()) {
+                Some(old) => compare(old, device, &mut changes),
+                None => changes.push(StateChange::DeviceAdded {
+                    device_id: device.id(),
+                }),
+            }
+        }
+        for device in old.devices() {
+            if new.device(device.id()).is_none() {
+                changes.push(StateChange::DeviceRemoved {
+                    device_id: device.id(),
+                });
+            }
+        }
+        StateDiff { changes }
+    }
+
+    /// What changed.
+    pub fn changes(&self) -> &[StateChange] {
+        &self.changes
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl IntoIterator for StateDiff {
+    type Item = StateChange;
+    type IntoIter = std::vec::IntoIter<StateChange>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+fn compare(old: &Device<'_>, new: &Device<'_>, changes: &mut Vec<StateChange>) {
+    let device_id = new.id();
+    let (from, to) = (old.status().lock_mode(), new.status().lock_mode());
+    if from != to {
+        changes.push(StateChange::LockModeChanged {
+            device_id,
+            from,
+            to,
+        });
+    }
+    if let (Some(from), Some(to)) = (old.status().battery(), new.status().battery()) {
+        if to < from {
+            changes.push(StateChange::BatteryDropped {
+                device_id,
+                from,
+                to,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pet::Location;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_list_changes_between_snapshots() {
+        let old: State<'static> = serde_json::from_str(
+            r#"{"pets":[{"id":1,"name":"Milo","position":{"where":1,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":2,"name":"Cleo","position":{"where":1,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":3,"name":"Tom"}],
+                "devices":[{"id":10,"name":"Back door","product_id":6,
+                            "status":{"battery":5.8,"locking":{"mode":0}}},
+                           {"id":11,"name":"Feeder","product_id":4,"status":{"battery":5.2}},
+                           {"id":12,"name":"Old hub","product_id":1}]}"#,
+        )
+        .unwrap();
+        let new: State<'static> = serde_json::from_str(
+            r#"{"pets":[{"id":1,"name":"Milo","position":{"where":2,"since":"2019-06-01T07:00:00Z"}},
+                        {"id":2,"name":"Cleo","position":{"where":1,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":3,"name":"Tom","position":{"where":1,"since":"2019-06-01T06:00:00Z"}}],
+                "devices":[{"id":10,"name":"Back door","product_id":6,
+                            "status":{"battery":5.7,"locking":{"mode":1}}},
+                           {"id":11,"name":"Feeder","product_id":4,"status":{"battery":6.0}},
+                           {"id":13,"name":"Hub","product_id":1}]}"#,
+        )
+        .unwrap();
+
+        let diff = StateDiff::between(&old, &new);
+
+        let milo = old.pet(PetId::new(1)).unwrap().position().copied();
+        let moved = |pet: &State<'_>, id| *pet.pet(PetId::new(id)).unwrap().position().unwrap();
+        assert_eq!(
+            vec![
+                StateChange::PetMoved {
+                    pet_id: PetId::new(1),
+                    from: milo,
+                    to: moved(&new, 1),
+                },
+                StateChange::PetMoved {
+                    pet_id: PetId::new(3),
+                    from: None,
+                    to: moved(&new, 3),
+                },
+                StateChange::LockModeChanged {
+                    device_id: DeviceId::new(10),
+                    from: Some(LockMode::Unlocked),
+                    to: Some(LockMode::KeepIn),
+                },
+                StateChange::BatteryDropped {
+                    device_id: DeviceId::new(10),
+                    from: 5.8,
+                    to: 5.7,
+                },
+                StateChange::DeviceAdded {
+                    device_id: DeviceId::new(13),
+                },
+                StateChange::DeviceRemoved {
+                    device_id: DeviceId::new(12),
+                },
+            ],
+            diff.changes()
+        );
+        assert_eq!(Location::Outside, moved(&new, 1).location());
+    }
+}
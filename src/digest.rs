@@ -0,0 +1,266 @@
+//! Daily summaries of a household, ready to be emailed.
+//!
+//! A [`Digest`](struct.Digest.html) totals a day's timeline events per pet
+//! and renders them as plain text and HTML, one line per pet:
+//!
+//! ```text
+//! Milo: 3 meals (42 g), outside 5 h 12 m
+//! ```
+//!
+//! Amounts are in grams and millilitres unless
+//! [`with_units`](struct.Digest.html#method.with_units) says otherwise.
+//! Sending the result is left to the caller.
+use crate::humanize;
+use crate::report::{DailyDrinking, DailyFeeding, DailyOutside, Report};
+use crate::time::{Date, DateRange, Timestamp};
+use crate::timeline::Event;
+use crate::units::Units;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// One pet's totals for the day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PetDigest {
+    name: String,
+    meals: u32,
+    eaten: f64,
+    drinks: u32,
+    drunk: f64,
+    outside: i64,
+}
+
+impl PetDigest {
+    /// The pet's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The number of meals.
+    pub fn meals(&self) -> u32 {
+        self.meals
+    }
+
+    /// Grams eaten.
+    pub fn eaten(&self) -> f64 {
+        self.eaten
+    }
+
+    /// The number of drinks.
+    pub fn drinks(&self) -> u32 {
+        self.drinks
+    }
+
+    /// Millilitres drunk.
+    pub fn drunk(&self) -> f64 {
+        self.drunk
+    }
+
+    /// Seconds spent outside.
+    pub fn outside(&self) -> i64 {
+        self.outside
+    }
+
+    /// The summary line without the name, such as `3 meals (42 g)`.
+    fn summary(&self, units: Units) -> String {
+        let mut parts = vec![format!(
+            "{} {} ({} {})",
+            self.meals,
+            plural(self.meals, "meal", "meals"),
+            amount(units, units.weight(self.eaten)),
+            units.weight_symbol()
+        )];
+        if self.drinks > 0 {
+            parts.push(format!(
+                "{} {} ({} {})",
+                self.drinks,
+                plural(self.drinks, "drink", "drinks"),
+                amount(units, units.volume(self.drunk)),
+                units.volume_symbol()
+            ));
+        }
+        if self.outside > 0 {
+            parts.push(format!(
+                "outside {}",
+                humanize::duration(Duration::from_secs(self.outside as u64))
+            ));
+        }
+        parts.join(", ")
+    }
+}
+
+/// The summary of a household's day.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::digest::Digest;
+/// let digest = Digest::from_events("Home", "2019-06-01".parse().unwrap(), &[]);
+/// assert_eq!("Home, 2019-06-01\n\nNo pet activity.\n", digest.text());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Digest {
+    title: String,
+    date: Date,
+    units: Units,
+    pets: Vec<PetDigest>,
+}
+
+impl Digest {
+    /// Totals the events of every pet that appears in them on `date`. Events
+    /// from the day before help work out where a pet was at midnight.
+    pub fn from_events(title: &str, date: Date, events: &[Event<'_>]) -> Self {
+        Digest::from_events_at(title, date, events, Timestamp::now())
+    }
+
+    /// Like [`from_events`](#method.from_events), as of `now` rather than
+    /// the system time, which only matters while `date` is still in
+    /// progress.
+    pub fn from_events_at(title: &str, date: Date, events: &[Event<'_>], now: Timestamp) -> Self {
+        let mut names = BTreeMap::new();
+        for pet in events.iter().flat_map(Event::pets) {
+            names.insert(pet.name().to_string(), pet.id());
+        }
+
+        let range = DateRange::day(date);
+        let until = now.min(date.add_days(1).midnight());
+        let pets = names
+            .into_iter()
+            .map(|(name, pet_id)| {
+                let report = Report::from_events(events, pet_id);
+                let feeding = DailyFeeding::from_datapoints(report.feeding(), range);
+                let drinking = DailyDrinking::from_datapoints(report.drinking(), range, 1);
+                let outside = DailyOutside::from_events(events, pet_id, range, until);
+                PetDigest {
+                    name,
+                    meals: feeding.iter().map(DailyFeeding::visits).sum(),
+                    eaten: feeding
+                        .iter()
+                        .map(DailyFeeding::consumed)
+                        .fold(0.0, |a, b| a + b),
+                    drinks: drinking.iter().map(DailyDrinking::visits).sum(),
+                    drunk: drinking
+                        .iter()
+                        .map(DailyDrinking::consumed)
+                        .fold(0.0, |a, b| a + b),
+                    outside: outside.iter().map(DailyOutside::total).sum(),
+                }
+            })
+            .collect();
+        Digest {
+            title: title.to_string(),
+            date,
+            units: Units::Metric,
+            pets,
+        }
+    }
+
+    /// Shows amounts in `units` when rendering.
+    pub fn with_units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// The totals per pet, by name.
+    pub fn pets(&self) -> &[PetDigest] {
+        &self.pets
+    }
+
+    /// The digest as plain text.
+    pub fn text(&self) -> String {
+        let mut text = format!("{}, {}\n\n", self.title, self.date);
+        if self.pets.is_empty() {
+            text.push_str("No pet activity.\n");
+        }
+        for pet in &self.pets {
+            text.push_str(&format!("{}: {}\n", pet.name, pet.summary(self.units)));
+        }
+        text
+    }
+
+    /// The digest as an HTML fragment.
+    pub fn html(&self) -> String {
+        let mut html = format!("<h1>{}, {}</h1>\n", escape(&self.title), self.date);
+        if self.pets.is_empty() {
+            html.push_str("<p>No pet activity.</p>\n");
+            return html;
+        }
+        html.push_str("<ul>\n");
+        for pet in &self.pets {
+            html.push_str(&format!(
+                "<li><strong>{}</strong>: {}</li>\n",
+                escape(&pet.name),
+                escape(&pet.summary(self.units))
+            ));
+        }
+        html.push_str("</ul>\n");
+        html
+    }
+}
+
+fn plural<'a>(count: u32, one: &'a str, many: &'a str) -> &'a str {
+    if count == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// Rounds to whole grams or millilitres, or to a tenth of an ounce.
+fn amount(units: Units, value: f64) -> f64 {
+    match units {
+        Units::Metric => value.round(),
+        Units::Imperial => (value * 10.0).round() / 10.0,
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_summarise_each_pet() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":0,"created_at":"2019-06-01T06:00:00+00:00",
+             "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":2}]},
+            {"id":2,"type":22,"created_at":"2019-06-01T07:00:00+00:00","pets":[{"id":2,"name":"Milo"}],
+             "weights":[{"duration":60,"context":1,"frames":[{"current_weight":10,"change":-20}]}]},
+            {"id":3,"type":22,"created_at":"2019-06-01T09:00:00+00:00","pets":[{"id":2,"name":"Milo"}],
+             "weights":[{"duration":60,"context":1,"frames":[{"current_weight":10,"change":-22.4}]}]},
+            {"id":4,"type":0,"created_at":"2019-06-01T11:12:00+00:00",
+             "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":1}]},
+            {"id":5,"type":29,"created_at":"2019-06-01T12:00:00+00:00","pets":[{"id":3,"name":"Tilly & co"}],
+             "weights":[{"duration":30,"context":1,"frames":[{"current_weight":300,"change":-15}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let digest = Digest::from_events("Home", "2019-06-01".parse().unwrap(), &events);
+
+        assert_eq!(
+            "Home, 2019-06-01\n\n\
+             Milo: 2 meals (42 g), outside 5 h 12 m\n\
+             Tilly & co: 0 meals (0 g), 1 drink (15 ml)\n",
+            digest.text()
+        );
+        assert_eq!(
+            "<h1>Home, 2019-06-01</h1>\n<ul>\n\
+             <li><strong>Milo</strong>: 2 meals (42 g), outside 5 h 12 m</li>\n\
+             <li><strong>Tilly &amp; co</strong>: 0 meals (0 g), 1 drink (15 ml)</li>\n\
+             </ul>\n",
+            digest.html()
+        );
+        assert_eq!(
+            "Home, 2019-06-01\n\n\
+             Milo: 2 meals (1.5 oz), outside 5 h 12 m\n\
+             Tilly & co: 0 meals (0 oz), 1 drink (0.5 fl oz)\n",
+            digest.with_units(Units::Imperial).text()
+        );
+    }
+}
@@ -0,0 +1,487 @@
+//! The error type returned by the client.
+use crate::device::Product;
+use crate::id::DeviceId;
+use crate::ratelimit::RateLimit;
+use crate::secret;
+use crate::time::RangeError;
+use crate::transport::Request;
+use std::error::Error as StdError;
+use std::fmt;
+use std::time::Duration;
+
+/// A convenience alias for results returned by this crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while talking to the Sure Petcare API. The
+/// `Debug` output redacts secrets in response bodies, such as the email
+/// address a failed login echoes back.
+pub enum Error {
+    /// The transport failed to deliver the request or read the response.
+    Transport(Box<dyn StdError + Send + Sync>),
+
+    /// The API answered with a non-success status code.
+    Status {
+        /// The HTTP status code.
+        status: u16,
+        /// The response body, lossily decoded as UTF-8.
+        body: String,
+    },
+
+    /// A request or response body could not be (de)serialized.
+    Json(serde_json::Error),
+
+    /// A date range was rejected before sending the request.
+    Range(RangeError),
+
+    /// The device cannot do what was asked, found before sending anything.
+    Unsupported {
+        /// The kind of device.
+        product: Product,
+        /// What was asked, such as `changing the lock mode`.
+        operation: &'static str,
+    },
+
+    /// The response body was larger than the client accepts, see
+    /// [`SurePet::with_max_response_size`](../client/struct.SurePet.html#method.with_max_response_size).
+    TooLarge {
+        /// The limit in bytes.
+        limit: usize,
+    },
+
+    /// A request was found to be invalid before sending it.
+    Invalid(ValidationError),
+
+    /// A device did not show an update after it was sent, see
+    /// [`Devices::update_confirmed`](../device/struct.Devices.html#method.update_confirmed).
+    Unconfirmed {
+        /// The device.
+        device_id: DeviceId,
+        /// How many times the update was sent.
+        attempts: u32,
+    },
+
+    /// The service is down for maintenance or overloaded: the API answered
+    /// `503 Service Unavailable`, or an HTML page or maintenance notice in
+    /// place of its JSON.
+    ServiceUnavailable {
+        /// How long the API asked callers to wait, from `Retry-After`.
+        retry_after: Option<Duration>,
+    },
+
+    /// The API answered `429 Too Many Requests`.
+    RateLimited {
+        /// What the response said about the limits, such as how long to
+        /// wait; empty when it said nothing.
+        rate_limit: RateLimit,
+    },
+
+    /// The response was not JSON, as when a proxy or CDN answers with an
+    /// HTML error page of its own.
+    UnexpectedContent {
+        /// The HTTP status code.
+        status: u16,
+        /// The `Content-Type` the response declared, if any.
+        content_type: Option<String>,
+        /// The start of the body, with secrets redacted and whitespace
+        /// collapsed.
+        snippet: String,
+    },
+    /// The client is in dry-run mode, see
+    /// [`SurePet::with_dry_run`](../client/struct.SurePet.html#method.with_dry_run),
+    /// and held back this request rather than change the account.
+    WouldHaveSent(Box<Request>),
+}
+
+impl Error {
+    /// Wraps any error raised by a transport implementation.
+    pub fn transport<E: Into<Box<dyn StdError + Send + Sync>>>(error: E) -> Self {
+        Error::Transport(error.into())
+    }
+
+    /// The error the API described for a non-success status, or `None` for
+    /// errors that never reached the API.
+    /// # Examples
+    ///
+    /// ```
+    /// use sure_petcare::error::{ApiErrorKind, Error};
+    /// let error = Error::Status {
+    ///     status: 422,
+    ///     body: r#"{"error":{"name":["The name field is required."]}}"#.into(),
+    /// };
+    /// match error.api_error().unwrap().kind() {
+    ///     ApiErrorKind::Validation(fields) => assert_eq!("name", fields[0].field()),
+    ///     kind => panic!("unexpected {:?}", kind),
+    /// }
+    /// ```
+    pub fn api_error(&self) -> Option<ApiError> {
+        match self {
+            Error::Status { status, body } => Some(ApiError::parse(*status, body.as_bytes())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(error) => f.debug_tuple("Transport").field(error).finish(),
+            Error::Status { status, body } => f
+                .debug_struct("Status")
+                .field("status", status)
+                .field("body", &secret::Body(body.as_bytes()))
+                .finish(),
+            Error::Json(error) => f.debug_tuple("Json").field(error).finish(),
+            Error::Range(error) => f.debug_tuple("Range").field(error).finish(),
+            Error::Unsupported { product, operation } => f
+                .debug_struct("Unsupported")
+                .field("product", product)
+                .field("operation", operation)
+                .finish(),
+            Error::TooLarge { limit } => f.debug_struct("TooLarge").field("limit", limit).finish(),
+            Error::Invalid(error) => f.debug_tuple("Invalid").field(error).finish(),
+            Error::Unconfirmed {
+                device_id,
+                attempts,
+            } => f
+                .debug_struct("Unconfirmed")
+                .field("device_id", device_id)
+                .field("attempts", attempts)
+                .finish(),
+            Error::ServiceUnavailable { retry_after } => f
+                .debug_struct("ServiceUnavailable")
+                .field("retry_after", retry_after)
+                .finish(),
+            Error::RateLimited { rate_limit } => f
+                .debug_struct("RateLimited")
+                .field("rate_limit", rate_limit)
+                .finish(),
+            Error::UnexpectedContent {
+                status,
+                content_type,
+                snippet,
+            } => f
+                .debug_struct("UnexpectedContent")
+                .field("status", status)
+                .field("content_type", content_type)
+                .field("snippet", snippet)
+                .finish(),
+            Error::WouldHaveSent(request) => f.debug_tuple("WouldHaveSent").field(request).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(error) => write!(f, "transport error: {}", error),
+            Error::Status { status, body } => write!(f, "api returned status {}: {}", status, body),
+            Error::Json(error) => write!(f, "json error: {}", error),
+            Error::Range(error) => write!(f, "invalid range: {}", error),
+            Error::Unsupported { product, operation } => {
+                write!(f, "{} does not support {}", product, operation)
+            }
+            Error::TooLarge { limit } => {
+                write!(f, "response body is larger than {} bytes", limit)
+            }
+            Error::Invalid(error) => write!(f, "invalid request: {}", error),
+            Error::Unconfirmed {
+                device_id,
+                attempts,
+            } => write!(
+                f,
+                "device {} did not confirm the update after {} attempts",
+                device_id, attempts
+            ),
+            Error::ServiceUnavailable {
+                retry_after: Some(retry_after),
+            } => write!(
+                f,
+                "service unavailable, retry after {} seconds",
+                retry_after.as_secs()
+            ),
+            Error::ServiceUnavailable { retry_after: None } => f.write_str("service unavailable"),
+            Error::RateLimited { rate_limit } => match rate_limit.retry_after() {
+                Some(retry_after) => write!(
+                    f,
+                    "rate limited, retry after {} seconds",
+                    retry_after.as_secs()
+                ),
+                None => f.write_str("rate limited"),
+            },
+            Error::UnexpectedContent {
+                status,
+                content_type,
+                snippet,
+            } => write!(
+                f,
+                "status {} response is not json ({}): {}",
+                status,
+                content_type.as_deref().unwrap_or("no content type"),
+                snippet
+            ),
+            Error::WouldHaveSent(request) => write!(
+                f,
+                "dry run, would have sent {} {}",
+                request.method.as_str(),
+                request.url
+            ),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Transport(error) => Some(error.as_ref()),
+            Error::Status { .. } => None,
+            Error::Json(error) => Some(error),
+            Error::Range(error) => Some(error),
+            Error::Unsupported { .. } => None,
+            Error::TooLarge { .. } => None,
+            Error::Invalid(error) => Some(error),
+            Error::Unconfirmed { .. } => None,
+            Error::ServiceUnavailable { .. } => None,
+            Error::RateLimited { .. } => None,
+            Error::UnexpectedContent { .. } => None,
+            Error::WouldHaveSent(_) => None,
+        }
+    }
+}
+
+/// An error response from the API, parsed from its status and body.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApiError {
+    status: u16,
+    kind: ApiErrorKind,
+    message: Option<String>,
+}
+
+/// What kind of error the API answered with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ApiErrorKind {
+    /// The email address or password was not accepted when logging in.
+    InvalidCredentials,
+    /// The token is missing, has expired or was revoked.
+    Unauthorized,
+    /// The account may not see or change the resource.
+    Forbidden,
+    /// The resource does not exist.
+    NotFound,
+    /// The request was rejected; each field the API complained about is
+    /// listed with its messages.
+    Validation(Vec<FieldError>),
+    /// Too many requests were sent.
+    RateLimited,
+    /// The API failed on its side.
+    Server,
+    /// Any other status.
+    Other,
+}
+
+/// The messages the API gave about one field of a rejected request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FieldError {
+    field: String,
+    messages: Vec<String>,
+}
+
+impl ApiError {
+    /// Parses an error response. The API reports errors as
+    /// `{"error": "message"}` or, per field, as
+    /// `{"error": {"field": ["message", ...]}}`; bodies in neither shape still
+    /// get a kind from the status.
+    pub fn parse(status: u16, body: &[u8]) -> Self {
+        let error = serde_json::from_slice::<serde_json::Value>(body)
+            .ok()
+            .and_then(|mut body| body.get_mut("error").map(serde_json::Value::take));
+        let mut message = None;
+        let mut fields = Vec::new();
+        match error {
+            Some(serde_json::Value::String(text)) => message = Some(text),
+            Some(serde_json::Value::Object(map)) => {
+                for (field, value) in map {
+                    let messages = match value {
+                        serde_json::Value::String(text) => vec![text],
+                        serde_json::Value::Array(items) => items
+                            .into_iter()
+                            .filter_map(|item| item.as_str().map(String::from))
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    if field == "message" {
+                        message = messages.into_iter().next();
+                    } else {
+                        fields.push(FieldError { field, messages });
+                    }
+                }
+            }
+            _ => {}
+        }
+        let kind = match status {
+            401 | 403 if fields.iter().any(FieldError::is_credential) => {
+                ApiErrorKind::InvalidCredentials
+            }
+            401 => ApiErrorKind::Unauthorized,
+            403 => ApiErrorKind::Forbidden,
+            404 => ApiErrorKind::NotFound,
+            400 | 422 if !fields.is_empty() => ApiErrorKind::Validation(fields),
+            429 => ApiErrorKind::RateLimited,
+            500..=599 => ApiErrorKind::Server,
+            _ => ApiErrorKind::Other,
+        };
+        ApiError {
+            status,
+            kind,
+            message,
+        }
+    }
+
+    /// The HTTP status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// What kind of error it is.
+    pub fn kind(&self) -> &ApiErrorKind {
+        &self.kind
+    }
+
+    /// The message given for the whole request, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl FieldError {
+    /// The name of the field, as sent in the request.
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// What was wrong with it.
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    fn is_credential(&self) -> bool {
+        matches!(
+            self.field.as_str(),
+            "login" | "email_address" | "password" | "credentials"
+        )
+    }
+}
+
+/// What is wrong with a request, found before sending it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationError {
+    field: &'static str,
+    reason: String,
+}
+
+impl ValidationError {
+    pub(crate) fn new<S: Into<String>>(field: &'static str, reason: S) -> Self {
+        ValidationError {
+            field,
+            reason: reason.into(),
+        }
+    }
+
+    /// The field of the request at fault, as sent to the API.
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+
+    /// What is wrong with it.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.field, self.reason)
+    }
+}
+
+impl StdError for ValidationError {}
+
+impl From<ValidationError> for Error {
+    fn from(error: ValidationError) -> Self {
+        Error::Invalid(error)
+    }
+}
+
+/// The error returned when a name such as a lock mode fails to parse.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseNameError {
+    kind: &'static str,
+    input: String,
+}
+
+impl ParseNameError {
+    pub(crate) fn new(kind: &'static str, input: &str) -> Self {
+        ParseNameError {
+            kind,
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} `{}`", self.kind, self.input)
+    }
+}
+
+impl StdError for ParseNameError {}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+impl From<RangeError> for Error {
+    fn from(error: RangeError) -> Self {
+        Error::Range(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_api_error_kinds() {
+        let login = ApiError::parse(401, br#"{"error":{"login":["Invalid credentials"]}}"#);
+        let expired = ApiError::parse(401, br#"{"error":"Unauthenticated."}"#);
+        let invalid = ApiError::parse(
+            422,
+            br#"{"error":{"message":"Invalid data","name":["Too long"],"tag_id":"Taken"}}"#,
+        );
+
+        assert_eq!(&ApiErrorKind::InvalidCredentials, login.kind());
+        assert_eq!(&ApiErrorKind::Unauthorized, expired.kind());
+        assert_eq!(Some("Unauthenticated."), expired.message());
+        assert_eq!(Some("Invalid data"), invalid.message());
+        match invalid.kind() {
+            ApiErrorKind::Validation(fields) => {
+                let fields: Vec<_> = fields.iter().map(|f| (f.field(), f.messages())).collect();
+                assert_eq!(
+                    vec![
+                        ("name", &["Too long".to_string()][..]),
+                        ("tag_id", &["Taken".to_string()][..])
+                    ],
+                    fields
+                );
+            }
+            kind => panic!("unexpected {:?}", kind),
+        }
+        assert_eq!(
+            &ApiErrorKind::NotFound,
+            ApiError::parse(404, b"<html>").kind()
+        );
+        assert_eq!(&ApiErrorKind::Server, ApiError::parse(503, b"").kind());
+    }
+}
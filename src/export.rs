@@ -0,0 +1,471 @@
+//! Rendering of report data to CSV, JSON and plain text tables.
+//!
+//! The same [`ReportExporter`](struct.ReportExporter.html) backs every output
+//! path, so library users and tools print identical numbers. Raw timeline
+//! events can be written out with the [`csv`](csv/index.html) module.
+use crate::report::{DailyDrinking, DailyFeeding, DailyOutside};
+use crate::time::Date;
+use crate::timeline::{Direction, Event, EventKind, WeightEntry};
+use crate::units::Units;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+pub mod atom;
+pub mod columnar;
+pub mod csv;
+pub mod grafana;
+pub mod ical;
+#[cfg(feature = "influx")]
+pub mod influx;
+pub mod jsonl;
+pub mod prometheus;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+/// The output formats a `ReportExporter` can render.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Format {
+    /// Comma separated values with a header row.
+    Csv,
+    /// A pretty printed JSON array with one object per day.
+    Json,
+    /// One compact JSON object per line and day.
+    JsonLines,
+    /// A plain text table with aligned columns.
+    Table,
+}
+
+/// Renders per day report data, one row per day.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::{Format, ReportExporter};
+/// let output = ReportExporter::new()
+///     .with_format(Format::Csv)
+///     .with_feeding(&[])
+///     .render();
+/// assert_eq!("date,consumed_g,meals,average_meal_s\n", output);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReportExporter<'a> {
+    format: Format,
+    units: Units,
+    feeding: Option<&'a [DailyFeeding]>,
+    drinking: Option<&'a [DailyDrinking]>,
+    outside: Option<&'a [DailyOutside]>,
+}
+
+impl<'a> Default for ReportExporter<'a> {
+    fn default() -> Self {
+        ReportExporter {
+            format: Format::Table,
+            units: Units::Metric,
+            feeding: None,
+            drinking: None,
+            outside: None,
+        }
+    }
+}
+
+impl<'a> ReportExporter<'a> {
+    /// Create a new exporter rendering an empty table.
+    pub fn new() -> Self {
+        ReportExporter::default()
+    }
+
+    /// Sets the output format.
+    pub fn with_format(&mut self, format: Format) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the units of the amount columns, which are named `consumed_oz`
+    /// and `drunk_fl_oz` rather than `consumed_g` and `drunk_ml` in imperial.
+    pub fn with_units(&mut self, units: Units) -> &mut Self {
+        self.units = units;
+        self
+    }
+
+    /// Adds the feeding columns.
+    pub fn with_feeding(&mut self, feeding: &'a [DailyFeeding]) -> &mut Self {
+        self.feeding = Some(feeding);
+        self
+    }
+
+    /// Adds the drinking columns.
+    pub fn with_drinking(&mut self, drinking: &'a [DailyDrinking]) -> &mut Self {
+        self.drinking = Some(drinking);
+        self
+    }
+
+    /// Adds the time outside columns.
+    pub fn with_outside(&mut self, outside: &'a [DailyOutside]) -> &mut Self {
+        self.outside = Some(outside);
+        self
+    }
+
+    /// The column names, in order.
+    pub fn headers(&self) -> Vec<&'static str> {
+        let mut headers = vec!["date"];
+        let imperial = self.units == Units::Imperial;
+        if self.feeding.is_some() {
+            headers.push(if imperial {
+                "consumed_oz"
+            } else {
+                "consumed_g"
+            });
+            headers.extend(&["meals", "average_meal_s"]);
+        }
+        if self.drinking.is_some() {
+            if imperial {
+                headers.extend(&["drunk_fl_oz", "drinks", "rolling_average_fl_oz"]);
+            } else {
+                headers.extend(&["drunk_ml", "drinks", "rolling_average_ml"]);
+            }
+        }
+        if self.outside.is_some() {
+            headers.extend(&["outside_s", "longest_trip_s", "trips"]);
+        }
+        headers
+    }
+
+    /// The rows, one per day that appears in any of the data, in date order.
+    /// Days missing from one of the sections are left blank there.
+    pub fn rows(&self) -> Vec<Vec<Value>> {
+        let mut days: BTreeMap<Date, Vec<Value>> = BTreeMap::new();
+        let mut width = 0;
+        let mut section = |values: Vec<(Date, Vec<Value>)>, columns: usize| {
+            for (date, row) in values {
+                let cells = days.entry(date).or_default();
+                cells.resize(width, Value::Null);
+                cells.extend(row);
+            }
+            width += columns;
+        };
+
+        if let Some(feeding) = self.feeding {
+            section(
+                feeding
+                    .iter()
+                    .map(|d| {
+                        (
+                            d.date(),
+                            vec![
+                                self.units.round(self.units.weight(d.consumed())).into(),
+                                d.visits().into(),
+                                d.average_duration().into(),
+                            ],
+                        )
+                    })
+                    .collect(),
+                3,
+            );
+        }
+        if let Some(drinking) = self.drinking {
+            section(
+                drinking
+                    .iter()
+                    .map(|d| {
+                        (
+                            d.date(),
+                            vec![
+                                self.units.round(self.units.volume(d.consumed())).into(),
+                                d.visits().into(),
+                                self.units
+                                    .round(self.units.volume(d.rolling_average()))
+                                    .into(),
+                            ],
+                        )
+                    })
+                    .collect(),
+                3,
+            );
+        }
+        if let Some(outside) = self.outside {
+            section(
+                outside
+                    .iter()
+                    .map(|d| {
+                        (
+                            d.date(),
+                            vec![d.total().into(), d.longest().into(), d.trips().into()],
+                        )
+                    })
+                    .collect(),
+                3,
+            );
+        }
+
+        days.into_iter()
+            .map(|(date, mut cells)| {
+                cells.resize(width, Value::Null);
+                cells.insert(0, date.to_string().into());
+                cells
+            })
+            .collect()
+    }
+
+    /// Renders the data in the chosen format.
+    pub fn render(&self) -> String {
+        let headers = self.headers();
+        let rows = self.rows();
+        match self.format {
+            Format::Csv => {
+                let mut output = csv::line(headers.iter().map(|h| h.to_string()));
+                for row in &rows {
+                    output.push_str(&csv::line(row.iter().map(cell)));
+                }
+                output
+            }
+            Format::Json => {
+                let objects: Vec<Value> =
+                    rows.into_iter().map(|row| object(&headers, row)).collect();
+                serde_json::to_string_pretty(&objects).unwrap_or_default()
+            }
+            Format::JsonLines => rows
+                .into_iter()
+                .map(|row| format!("{}\n", object(&headers, row)))
+                .collect(),
+            Format::Table => {
+                let cells: Vec<Vec<String>> = rows
+                    .iter()
+                    .map(|row| row.iter().map(cell).collect())
+                    .collect();
+                table(&headers, &cells)
+            }
+        }
+    }
+}
+
+/// Lays out cells as a plain text table with a header separator, numbers
+/// and text both left aligned.
+pub(crate) fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    let mut output = line(headers.to_vec());
+    output.push_str(&line(rule.iter().map(String::as_str).collect()));
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
+
+/// The snake case name used for an event kind in exported data.
+pub fn kind_name(kind: EventKind) -> String {
+    match kind {
+        EventKind::Unknown(code) => format!("unknown_{}", code),
+        kind => kind.to_string(),
+    }
+}
+
+/// The snake case name used for a flap direction in exported data.
+pub(crate) fn direction_name(direction: Direction) -> String {
+    match direction {
+        Direction::Unknown(code) => format!("direction_{}", code),
+        direction => direction.to_string(),
+    }
+}
+
+/// A short kind specific description of an event, such as `in` for a
+/// movement or `duration=120s change=-4.5` for a meal.
+pub(crate) fn event_details(event: &Event<'_>) -> String {
+    let movements = event
+        .movements()
+        .iter()
+        .map(|movement| direction_name(movement.direction()));
+    let weights = event.weights().iter().map(|weight| {
+        format!(
+            "duration={}s change={}",
+            weight.duration(),
+            weight_change(weight)
+        )
+    });
+    movements.chain(weights).collect::<Vec<_>>().join(";")
+}
+
+/// A one line human readable description of an event, such as
+/// `Milo came in through Back door`.
+pub fn event_summary(event: &Event<'_>) -> String {
+    event_summary_in(event, Units::Metric)
+}
+
+/// Like [`event_summary`](fn.event_summary.html), giving amounts eaten and
+/// drunk in `units`.
+pub fn event_summary_in(event: &Event<'_>, units: Units) -> String {
+    let pets: Vec<&str> = event.pets().iter().map(|pet| pet.name()).collect();
+    let pets = if pets.is_empty() {
+        "A pet".to_string()
+    } else {
+        pets.join(" and ")
+    };
+    let device = event.devices().first().map(|device| device.name());
+    let at = |summary: String, word: &str| match device {
+        Some(device) => format!("{} {} {}", summary, word, device),
+        None => summary,
+    };
+    let consumed = -event
+        .weights()
+        .iter()
+        .map(weight_change)
+        .fold(0.0, |a, b| a + b);
+    let device_name = device.unwrap_or("A device");
+    match event.kind() {
+        EventKind::Movement => match event.movements().first().map(|m| m.direction()) {
+            Some(Direction::In) => at(format!("{} came in", pets), "through"),
+            Some(Direction::Out) => at(format!("{} went out", pets), "through"),
+            Some(Direction::LookedThrough) => {
+                format!("{} looked through {}", pets, device.unwrap_or("the flap"))
+            }
+            _ => format!("{} used {}", pets, device.unwrap_or("the flap")),
+        },
+        EventKind::Feeding if consumed > 0.0 => at(
+            format!("{} ate {}", pets, units.format_weight(consumed)),
+            "at",
+        ),
+        EventKind::Feeding => format!("{} was refilled", device_name),
+        EventKind::Drinking if consumed > 0.0 => at(
+            format!("{} drank {}", pets, units.format_volume(consumed)),
+            "at",
+        ),
+        EventKind::Drinking => format!("{} was refilled", device_name),
+        EventKind::LowBattery => format!("{} has low batteries", device_name),
+        EventKind::LockStatus => format!("{} changed lock mode", device_name),
+        EventKind::IntruderMovement => match device {
+            Some(device) => format!("An unknown animal tried {}", device),
+            None => "An unknown animal tried a flap".to_string(),
+        },
+        EventKind::Curfew => format!("{} curfew changed the lock", device_name),
+        EventKind::Unknown(code) => format!("Event of type {}", code),
+    }
+}
+
+/// The total change over all bowls of a visit, rounded to one decimal.
+pub(crate) fn weight_change(weight: &WeightEntry) -> f64 {
+    let change = weight
+        .frames()
+        .iter()
+        .fold(0.0, |total, frame| total + frame.change());
+    (change * 10.0).round() / 10.0
+}
+
+fn object(headers: &[&str], row: Vec<Value>) -> Value {
+    let object: Map<String, Value> = headers.iter().map(|h| h.to_string()).zip(row).collect();
+    Value::Object(object)
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Feeding;
+    use crate::time::DateRange;
+    use pretty_assertions::assert_eq;
+
+    fn feeding() -> Vec<DailyFeeding> {
+        let datapoints: Vec<Feeding> = serde_json::from_str(
+            r#"[{"from":"2019-06-01T07:00:00Z","to":"2019-06-01T07:02:00Z","duration":120,"context":1,
+                 "weights":[{"weight":10,"change":-12.34}]}]"#,
+        )
+        .unwrap();
+        let first = Date::new(2019, 6, 1).unwrap();
+        DailyFeeding::from_datapoints(
+            &datapoints,
+            DateRange::new(first, first.add_days(1)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn should_render_csv() {
+        let feeding = feeding();
+        let output = ReportExporter::new()
+            .with_format(Format::Csv)
+            .with_feeding(&feeding)
+            .with_outside(&[])
+            .render();
+        assert_eq!(
+            "date,consumed_g,meals,average_meal_s,outside_s,longest_trip_s,trips\n\
+             2019-06-01,12.3,1,120,,,\n\
+             2019-06-02,0.0,0,0,,,\n",
+            output
+        );
+    }
+
+    #[test]
+    fn should_render_imperial_units() {
+        let feeding = feeding();
+        let output = ReportExporter::new()
+            .with_format(Format::Csv)
+            .with_units(Units::Imperial)
+            .with_feeding(&feeding)
+            .with_drinking(&[])
+            .render();
+        assert_eq!(
+            "date,consumed_oz,meals,average_meal_s,drunk_fl_oz,drinks,rolling_average_fl_oz\n\
+             2019-06-01,0.44,1,120,,,\n\
+             2019-06-02,0.0,0,0,,,\n",
+            output
+        );
+    }
+
+    #[test]
+    fn should_render_json() {
+        let feeding = feeding();
+        let output = ReportExporter::new()
+            .with_format(Format::Json)
+            .with_feeding(&feeding[..1])
+            .render();
+        let parsed: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            serde_json::json!([{"date":"2019-06-01","consumed_g":12.3,"meals":1,"average_meal_s":120}]),
+            parsed
+        );
+    }
+
+    #[test]
+    fn should_render_json_lines() {
+        let feeding = feeding();
+        let output = ReportExporter::new()
+            .with_format(Format::JsonLines)
+            .with_feeding(&feeding)
+            .render();
+        assert_eq!(
+            "{\"average_meal_s\":120,\"consumed_g\":12.3,\"date\":\"2019-06-01\",\"meals\":1}\n\
+             {\"average_meal_s\":0,\"consumed_g\":0.0,\"date\":\"2019-06-02\",\"meals\":0}\n",
+            output
+        );
+    }
+
+    #[test]
+    fn should_render_table() {
+        let feeding = feeding();
+        let output = ReportExporter::new().with_feeding(&feeding).render();
+        assert_eq!(
+            "date        consumed_g  meals  average_meal_s\n\
+             ----------  ----------  -----  --------------\n\
+             2019-06-01  12.3        1      120\n\
+             2019-06-02  0.0         0      0\n",
+            output
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Atom feeds of timeline events.
+//!
+//! Each event becomes an entry with a one line summary as its title, so the
+//! comings and goings at the flap can be followed from any feed reader.
+use crate::time::Timestamp;
+use crate::timeline::Event;
+
+/// Renders events as an Atom feed with the given title and id, which should
+/// be a stable URI such as `urn:surepet:household:1`. Entries keep the order
+/// given, normally newest first.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::atom;
+/// let xml = atom::feed("Home", "urn:surepet:household:1", &[]);
+/// assert!(xml.contains("<title>Home</title>"));
+/// ```
+pub fn feed(title: &str, id: &str, events: &[Event<'_>]) -> String {
+    let updated = events
+        .iter()
+        .map(Event::created_at)
+        .max()
+        .unwrap_or_else(|| Timestamp::from_unix(0));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    xml.push_str(&format!("  <id>{}</id>\n", escape(id)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    xml.push_str("  <author><name>Sure Petcare</name></author>\n");
+    for event in events {
+        let summary = super::event_summary(event);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&summary)));
+        xml.push_str(&format!("    <id>urn:surepet:event:{}</id>\n", event.id()));
+        xml.push_str(&format!("    <updated>{}</updated>\n", event.created_at()));
+        xml.push_str(&format!(
+            "    <summary>{} at {}</summary>\n",
+            escape(&summary),
+            event.created_at()
+        ));
+        xml.push_str(&format!(
+            "    <category term=\"{}\"/>\n",
+            super::kind_name(event.kind())
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_render_entries() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[{"id":3,"type":22,"created_at":"2019-06-01T06:02:00+00:00",
+                 "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":11,"name":"Feeder"}],
+                 "weights":[{"duration":60,"frames":[{"current_weight":10,"change":-4.5}]}]},
+                {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00",
+                 "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}],
+                 "devices":[{"id":10,"name":"Back <door>"}],"movements":[{"direction":2}]}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+               <title>Home &amp; garden</title>\n  \
+               <id>urn:surepet:household:1</id>\n  \
+               <updated>2019-06-01T06:02:00+00:00</updated>\n  \
+               <author><name>Sure Petcare</name></author>\n  \
+               <entry>\n    \
+                 <title>Milo ate 4.5 g at Feeder</title>\n    \
+                 <id>urn:surepet:event:3</id>\n    \
+                 <updated>2019-06-01T06:02:00+00:00</updated>\n    \
+                 <summary>Milo ate 4.5 g at Feeder at 2019-06-01T06:02:00+00:00</summary>\n    \
+                 <category term=\"feeding\"/>\n  \
+               </entry>\n  \
+               <entry>\n    \
+                 <title>Milo and Tilly went out through Back &lt;door&gt;</title>\n    \
+                 <id>urn:surepet:event:2</id>\n    \
+                 <updated>2019-06-01T05:00:00+00:00</updated>\n    \
+                 <summary>Milo and Tilly went out through Back &lt;door&gt; at 2019-06-01T05:00:00+00:00</summary>\n    \
+                 <category term=\"movement\"/>\n  \
+               </entry>\n\
+             </feed>\n",
+            feed("Home & garden", "urn:surepet:household:1", &events)
+        );
+    }
+}
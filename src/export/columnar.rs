@@ -0,0 +1,93 @@
+//! Column oriented batches of timeline events.
+//!
+//! An [`EventBatch`](struct.EventBatch.html) holds one vector per column with
+//! one entry per event, the layout Arrow record batches and Parquet row
+//! groups use, so converting to either is a matter of wrapping each vector
+//! in the matching array type.
+use crate::timeline::Event;
+
+/// Timeline events split into columns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventBatch {
+    /// The event ids.
+    pub id: Vec<i64>,
+    /// When each event happened, in Unix seconds.
+    pub created_at: Vec<i64>,
+    /// The household of each event.
+    pub household_id: Vec<Option<i64>>,
+    /// The kind codes, see `EventKind::code`.
+    pub kind: Vec<i64>,
+    /// The first pet involved in each event.
+    pub pet_id: Vec<Option<i64>>,
+    /// The first device involved in each event.
+    pub device_id: Vec<Option<i64>>,
+    /// The flap direction codes of movement events.
+    pub direction: Vec<Option<i64>>,
+    /// Seconds at the bowl for feeding and drinking events.
+    pub duration: Vec<Option<i64>>,
+    /// The total bowl change for feeding and drinking events.
+    pub change: Vec<Option<f64>>,
+}
+
+impl EventBatch {
+    /// Splits events into columns, keeping their order.
+    pub fn from_events(events: &[Event<'_>]) -> Self {
+        let mut batch = EventBatch::default();
+        for event in events {
+            let weight = event.weights().first();
+            batch.id.push(event.id());
+            batch.created_at.push(event.created_at().unix());
+            batch.household_id.push(event.household_id().map(i64::from));
+            batch.kind.push(event.kind().code());
+            batch
+                .pet_id
+                .push(event.pets().first().map(|pet| pet.id().get()));
+            batch
+                .device_id
+                .push(event.devices().first().map(|device| device.id().get()));
+            batch.direction.push(
+                event
+                    .movements()
+                    .first()
+                    .map(|movement| movement.direction().code()),
+            );
+            batch.duration.push(weight.map(|weight| weight.duration()));
+            batch.change.push(weight.map(super::weight_change));
+        }
+        batch
+    }
+
+    /// The number of events in the batch.
+    pub fn len(&self) -> usize {
+        self.id.len()
+    }
+
+    /// Whether the batch holds no events.
+    pub fn is_empty(&self) -> bool {
+        self.id.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_split_events_into_columns() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[{"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":2}]},
+                {"id":3,"type":22,"created_at":"2019-06-01T06:00:00+00:00",
+                 "weights":[{"duration":60,"frames":[{"current_weight":10,"change":-4.5}]}]}]"#,
+        )
+        .unwrap();
+        let batch = EventBatch::from_events(&events);
+
+        assert_eq!(2, batch.len());
+        assert_eq!(vec![0, 22], batch.kind);
+        assert_eq!(vec![Some(2), None], batch.pet_id);
+        assert_eq!(vec![Some(2), None], batch.direction);
+        assert_eq!(vec![None, Some(-4.5)], batch.change);
+    }
+}
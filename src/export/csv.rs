@@ -0,0 +1,103 @@
+//! CSV export of timeline events.
+//!
+//! [`write_events`](fn.write_events.html) writes one row per event with a
+//! fixed set of columns, so months of history can be dumped into a
+//! spreadsheet in one call:
+//!
+//! | column      | contents                                                   |
+//! |-------------|------------------------------------------------------------|
+//! | `timestamp` | when the event happened, as RFC 3339 in UTC                |
+//! | `pet`       | the names of the pets involved, separated by `;`           |
+//! | `device`    | the names of the devices involved, separated by `;`        |
+//! | `kind`      | `movement`, `feeding`, `drinking`, ... or `unknown_<code>` |
+//! | `details`   | kind specific, e.g. `in` or `duration=120s change=-4.5`    |
+//!
+//! New columns are only ever added at the end.
+use crate::timeline::Event;
+use std::io::{self, Write};
+
+/// The column names written by `write_events`, in order.
+pub const HEADERS: [&str; 5] = ["timestamp", "pet", "device", "kind", "details"];
+
+/// Writes a header row followed by one row per event, in the order given.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::csv;
+/// let mut output = Vec::new();
+/// csv::write_events(&[], &mut output).unwrap();
+/// assert_eq!(b"timestamp,pet,device,kind,details\n", &output[..]);
+/// ```
+pub fn write_events<W: Write>(events: &[Event<'_>], mut writer: W) -> io::Result<()> {
+    writer.write_all(line(HEADERS.iter().map(|h| h.to_string())).as_bytes())?;
+    for event in events {
+        let pets: Vec<&str> = event.pets().iter().map(|pet| pet.name()).collect();
+        let devices: Vec<&str> = event.devices().iter().map(|device| device.name()).collect();
+        let row = vec![
+            event.created_at().to_string(),
+            pets.join(";"),
+            devices.join(";"),
+            super::kind_name(event.kind()),
+            super::event_details(event),
+        ];
+        writer.write_all(line(row.into_iter()).as_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Quotes a CSV field when it contains separators, quotes or line breaks.
+pub(crate) fn field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Joins fields into a terminated CSV line, quoting where needed.
+pub(crate) fn line<I: Iterator<Item = String>>(fields: I) -> String {
+    let fields: Vec<String> = fields.map(|value| field(&value)).collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_write_events() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":3,"type":22,"created_at":"2019-06-01T06:02:00+00:00",
+             "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":11,"name":"Feeder, kitchen"}],
+             "weights":[{"device_id":11,"duration":120,"context":1,
+                         "frames":[{"index":0,"current_weight":20.5,"change":-4.5},
+                                   {"index":1,"current_weight":10.0,"change":-1.0}]}]},
+            {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00",
+             "pets":[{"id":2,"name":"Milo"},{"id":4,"name":"Tilly"}],
+             "devices":[{"id":10,"name":"Back door"}],
+             "movements":[{"device_id":10,"direction":1}]},
+            {"id":1,"type":99,"created_at":"2019-05-31T23:00:00+00:00"}
+        ]"#,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        write_events(&events, &mut output).unwrap();
+
+        assert_eq!(
+            "timestamp,pet,device,kind,details\n\
+             2019-06-01T06:02:00+00:00,Milo,\"Feeder, kitchen\",feeding,duration=120s change=-5.5\n\
+             2019-06-01T05:00:00+00:00,Milo;Tilly,Back door,movement,in\n\
+             2019-05-31T23:00:00+00:00,,,unknown_99,\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_quote_fields() {
+        assert_eq!("plain", field("plain"));
+        assert_eq!("\"Kitchen, back\"", field("Kitchen, back"));
+        assert_eq!("\"say \"\"hi\"\"\"", field("say \"hi\""));
+    }
+}
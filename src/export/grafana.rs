@@ -0,0 +1,209 @@
+//! Responses in the shape the Grafana JSON datasource expects.
+//!
+//! The datasource plugin POSTs to `/query` and accepts either time series,
+//! a list of `[value, unix milliseconds]` pairs per target, or tables with
+//! typed columns. The types here serialize to exactly those shapes, so a
+//! thin HTTP wrapper only has to pick the data and return them as JSON.
+use crate::report::{DailyDrinking, DailyFeeding, DailyOutside};
+use crate::time::Timestamp;
+use crate::timeline::Event;
+use serde::Serialize;
+use serde_json::Value;
+
+/// One series of a `timeserie` response.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TimeSeries {
+    target: String,
+    datapoints: Vec<(f64, i64)>,
+}
+
+impl TimeSeries {
+    /// Creates a series from values and the times they apply to.
+    pub fn new<S: Into<String>>(target: S, points: &[(Timestamp, f64)]) -> Self {
+        TimeSeries {
+            target: target.into(),
+            datapoints: points
+                .iter()
+                .map(|(at, value)| (*value, at.unix() * 1000))
+                .collect(),
+        }
+    }
+
+    /// The name of the series.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The `[value, unix milliseconds]` pairs.
+    pub fn datapoints(&self) -> &[(f64, i64)] {
+        &self.datapoints
+    }
+}
+
+/// The type of a table column.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Unix milliseconds.
+    Time,
+    /// Any number.
+    Number,
+    /// Any text.
+    String,
+}
+
+/// A column of a `table` response.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Column {
+    text: String,
+    #[serde(rename = "type")]
+    kind: ColumnType,
+}
+
+/// A `table` response.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Table {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    columns: Vec<Column>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    fn new(columns: &[(&str, ColumnType)]) -> Self {
+        Table {
+            kind: "table",
+            columns: columns
+                .iter()
+                .map(|(text, kind)| Column {
+                    text: text.to_string(),
+                    kind: *kind,
+                })
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// The rows, one value per column.
+    pub fn rows(&self) -> &[Vec<Value>] {
+        &self.rows
+    }
+}
+
+/// A table of timeline events with `Time`, `Pet`, `Device`, `Kind` and
+/// `Details` columns, matching the CSV export.
+pub fn events_table(events: &[Event<'_>]) -> Table {
+    let mut table = Table::new(&[
+        ("Time", ColumnType::Time),
+        ("Pet", ColumnType::String),
+        ("Device", ColumnType::String),
+        ("Kind", ColumnType::String),
+        ("Details", ColumnType::String),
+    ]);
+    for event in events {
+        let pets: Vec<&str> = event.pets().iter().map(|pet| pet.name()).collect();
+        let devices: Vec<&str> = event.devices().iter().map(|device| device.name()).collect();
+        table.rows.push(vec![
+            (event.created_at().unix() * 1000).into(),
+            pets.join(";").into(),
+            devices.join(";").into(),
+            super::kind_name(event.kind()).into(),
+            super::event_details(event).into(),
+        ]);
+    }
+    table
+}
+
+/// The `feeding_grams` and `meals` series, one point per day at midnight.
+pub fn feeding_series(daily: &[DailyFeeding]) -> Vec<TimeSeries> {
+    let points = |value: fn(&DailyFeeding) -> f64| {
+        daily
+            .iter()
+            .map(|day| (day.date().midnight(), value(day)))
+            .collect::<Vec<_>>()
+    };
+    vec![
+        TimeSeries::new("feeding_grams", &points(|day| day.consumed())),
+        TimeSeries::new("meals", &points(|day| f64::from(day.visits()))),
+    ]
+}
+
+/// The `drinking_ml` and `drinks` series, one point per day at midnight.
+pub fn drinking_series(daily: &[DailyDrinking]) -> Vec<TimeSeries> {
+    let points = |value: fn(&DailyDrinking) -> f64| {
+        daily
+            .iter()
+            .map(|day| (day.date().midnight(), value(day)))
+            .collect::<Vec<_>>()
+    };
+    vec![
+        TimeSeries::new("drinking_ml", &points(|day| day.consumed())),
+        TimeSeries::new("drinks", &points(|day| f64::from(day.visits()))),
+    ]
+}
+
+/// The `outside_seconds` and `trips` series, one point per day at midnight.
+pub fn outside_series(daily: &[DailyOutside]) -> Vec<TimeSeries> {
+    let points = |value: fn(&DailyOutside) -> f64| {
+        daily
+            .iter()
+            .map(|day| (day.date().midnight(), value(day)))
+            .collect::<Vec<_>>()
+    };
+    vec![
+        TimeSeries::new("outside_seconds", &points(|day| day.total() as f64)),
+        TimeSeries::new("trips", &points(|day| f64::from(day.trips()))),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Feeding;
+    use crate::time::{Date, DateRange};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn should_serialize_time_series() {
+        let datapoints: Vec<Feeding> = serde_json::from_str(
+            r#"[{"from":"2019-06-01T07:00:00Z","to":"2019-06-01T07:02:00Z","duration":120,"context":1,
+                 "weights":[{"weight":10,"change":-12.5}]}]"#,
+        )
+        .unwrap();
+        let day = Date::new(2019, 6, 1).unwrap();
+        let daily = DailyFeeding::from_datapoints(&datapoints, DateRange::day(day));
+
+        assert_eq!(
+            json!([
+                {"target": "feeding_grams", "datapoints": [[12.5, 1_559_347_200_000i64]]},
+                {"target": "meals", "datapoints": [[1.0, 1_559_347_200_000i64]]},
+            ]),
+            serde_json::to_value(feeding_series(&daily)).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_serialize_tables() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[{"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00",
+                 "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":1}]}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            json!({
+                "type": "table",
+                "columns": [
+                    {"text": "Time", "type": "time"},
+                    {"text": "Pet", "type": "string"},
+                    {"text": "Device", "type": "string"},
+                    {"text": "Kind", "type": "string"},
+                    {"text": "Details", "type": "string"},
+                ],
+                "rows": [[1_559_365_200_000i64, "Milo", "", "movement", "in"]],
+            }),
+            serde_json::to_value(events_table(&events)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! iCalendar export of curfew schedules.
+//!
+//! Every enabled curfew becomes a daily recurring event running from its
+//! lock time to its unlock time, so curfews can be laid over a normal
+//! calendar. Curfews follow the household's wall clock, so the times are
+//! written without a time zone and calendar apps show them as local times.
+use crate::device::Device;
+use crate::time::{Date, TimeOfDay};
+
+/// Renders the enabled curfews of the devices as an iCalendar feed, with
+/// the recurrences starting on `from`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::ical;
+/// let feed = ical::curfews(&[], "2019-06-01".parse().unwrap());
+/// assert!(feed.starts_with("BEGIN:VCALENDAR\r\n"));
+/// ```
+pub fn curfews(devices: &[Device<'_>], from: Date) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//sure_petcare//curfews//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    let stamp = local(from, TimeOfDay::default());
+    for device in devices {
+        for (index, curfew) in device.control().curfews().iter().enumerate() {
+            if !curfew.enabled() {
+                continue;
+            }
+            let ends = if curfew.unlock_time() <= curfew.lock_time() {
+                from.add_days(1)
+            } else {
+                from
+            };
+            lines.extend(vec![
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:curfew-{}-{}@sure-petcare", device.id(), index),
+                format!("DTSTAMP:{}Z", stamp),
+                format!("SUMMARY:{}", text(&format!("{} curfew", device.name()))),
+                format!("DTSTART:{}", local(from, curfew.lock_time())),
+                format!("DTEND:{}", local(ends, curfew.unlock_time())),
+                "RRULE:FREQ=DAILY".to_string(),
+                "END:VEVENT".to_string(),
+            ]);
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut output = String::new();
+    for line in lines {
+        output.push_str(&line);
+        output.push_str("\r\n");
+    }
+    output
+}
+
+/// A floating local date and time, such as `20190601T190000`.
+fn local(date: Date, time: TimeOfDay) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}00",
+        date.year(),
+        date.month(),
+        date.day(),
+        time.hour(),
+        time.minute()
+    )
+}
+
+/// Escapes a text value.
+fn text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_render_enabled_curfews() {
+        let devices: Vec<Device> = serde_json::from_str(
+            r#"[{"id":10,"name":"Back door, left","product_id":6,
+                 "control":{"curfew":[{"enabled":true,"lock_time":"19:00","unlock_time":"07:00"},
+                                      {"enabled":false,"lock_time":"12:00","unlock_time":"13:00"},
+                                      {"enabled":true,"lock_time":"12:30","unlock_time":"14:00"}]}}]"#,
+        )
+        .unwrap();
+        let feed = curfews(&devices, "2019-06-01".parse().unwrap());
+
+        assert_eq!(
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//sure_petcare//curfews//EN\r\n\
+             CALSCALE:GREGORIAN\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:curfew-10-0@sure-petcare\r\n\
+             DTSTAMP:20190601T000000Z\r\n\
+             SUMMARY:Back door\\, left curfew\r\n\
+             DTSTART:20190601T190000\r\n\
+             DTEND:20190602T070000\r\n\
+             RRULE:FREQ=DAILY\r\n\
+             END:VEVENT\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:curfew-10-2@sure-petcare\r\n\
+             DTSTAMP:20190601T000000Z\r\n\
+             SUMMARY:Back door\\, left curfew\r\n\
+             DTSTART:20190601T123000\r\n\
+             DTEND:20190601T140000\r\n\
+             RRULE:FREQ=DAILY\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n",
+            feed
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! InfluxDB line protocol export, enabled with the `influx` feature.
+//!
+//! Events are written to the `surepet_event` measurement, one line per pet
+//! involved, and device status to `surepet_device`. Both are tagged with
+//! `household`, `pet` and `device` where known, so they can be grouped in
+//! Grafana without any further processing:
+//!
+//! ```text
+//! surepet_event,household=1,pet=Milo,device=Back\ door,kind=movement direction="in" 1559365200000000000
+//! surepet_device,household=1,device=Water,product=8 battery=5.8,online=true,hub_rssi=-61,water_weight=412.5 1559365200000000000
+//! ```
+use crate::device::{Device, Product};
+use crate::time::Timestamp;
+use crate::timeline::Event;
+use std::io::{self, Write};
+
+/// Writes one `surepet_event` line per event and pet, in the order given.
+/// Events without pets, such as low battery warnings, get a single line
+/// without a `pet` tag.
+pub fn write_events<W: Write>(events: &[Event<'_>], mut writer: W) -> io::Result<()> {
+    for event in events {
+        let tags = |pet: Option<&str>| {
+            let mut tags = Vec::new();
+            if let Some(household_id) = event.household_id() {
+                tags.push(("household", household_id.to_string()));
+            }
+            if let Some(pet) = pet {
+                tags.push(("pet", pet.to_string()));
+            }
+            if let Some(device) = event.devices().first() {
+                tags.push(("device", device.name().to_string()));
+            }
+            tags.push(("kind", super::kind_name(event.kind())));
+            tags
+        };
+
+        let mut fields = vec![("id", format!("{}i", event.id()))];
+        if let Some(movement) = event.movements().first() {
+            let direction = super::direction_name(movement.direction());
+            fields.push(("direction", string(&direction)));
+        }
+        if let Some(weight) = event.weights().first() {
+            fields.push(("duration", format!("{}i", weight.duration())));
+            fields.push(("change", super::weight_change(weight).to_string()));
+        }
+
+        let at = event.created_at();
+        if event.pets().is_empty() {
+            writer.write_all(line("surepet_event", &tags(None), &fields, at).as_bytes())?;
+        }
+        for pet in event.pets() {
+            let tags = tags(Some(pet.name()));
+            writer.write_all(line("surepet_event", &tags, &fields, at).as_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+/// Writes one `surepet_device` line per device with the status it reported,
+/// stamped with `at` since the API does not say when the status was taken.
+/// Water dispensers add a `water_weight` field with the millilitres left.
+pub fn write_devices<W: Write>(
+    devices: &[Device<'_>],
+    at: Timestamp,
+    mut writer: W,
+) -> io::Result<()> {
+    for device in devices {
+        let mut tags = Vec::new();
+        if let Some(household_id) = device.household_id() {
+            tags.push(("household", household_id.to_string()));
+        }
+        tags.push(("device", device.name().to_string()));
+        tags.push(("product", device.product().code().to_string()));
+
+        let status = device.status();
+        let mut fields = Vec::new();
+        if let Some(battery) = status.battery() {
+            fields.push(("battery", battery.to_string()));
+        }
+        if let Some(online) = status.online() {
+            fields.push(("online", online.to_string()));
+        }
+        if let Some(signal) = status.signal() {
+            if let Some(rssi) = signal.device_rssi() {
+                fields.push(("device_rssi", rssi.to_string()));
+            }
+            if let Some(rssi) = signal.hub_rssi() {
+                fields.push(("hub_rssi", rssi.to_string()));
+            }
+        }
+        if device.product() == Product::Felaqua && !status.bowls().is_empty() {
+            let weight = status
+                .bowls()
+                .iter()
+                .fold(0.0, |total, bowl| total + bowl.current_weight());
+            fields.push(("water_weight", weight.to_string()));
+        }
+
+        // A line without fields is rejected, so devices that reported
+        // nothing are skipped.
+        if !fields.is_empty() {
+            writer.write_all(line("surepet_device", &tags, &fields, at).as_bytes())?;
+        }
+    }
+    writer.flush()
+}
+
+fn line(
+    measurement: &str,
+    tags: &[(&str, String)],
+    fields: &[(&str, String)],
+    at: Timestamp,
+) -> String {
+    let mut line = measurement.to_string();
+    for (key, value) in tags {
+        line.push_str(&format!(",{}={}", key, escape(value)));
+    }
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    format!(
+        "{} {} {}\n",
+        line,
+        fields.join(","),
+        at.unix() * 1_000_000_000
+    )
+}
+
+/// Escapes commas, spaces and equals signs in tag values.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if let ',' | ' ' | '=' | '\\' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Quotes a string field value.
+fn string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_write_events() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00","household_id":1,
+             "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}],
+             "devices":[{"id":10,"name":"Back door"}],
+             "movements":[{"device_id":10,"direction":1}]},
+            {"id":3,"type":29,"created_at":"2019-06-01T05:01:00+00:00","household_id":1,
+             "devices":[{"id":11,"name":"Water"}],
+             "weights":[{"device_id":11,"duration":30,"frames":[{"current_weight":400,"change":-12.5}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        write_events(&events, &mut output).unwrap();
+
+        assert_eq!(
+            "surepet_event,household=1,pet=Milo,device=Back\\ door,kind=movement id=2i,direction=\"in\" 1559365200000000000\n\
+             surepet_event,household=1,pet=Tilly,device=Back\\ door,kind=movement id=2i,direction=\"in\" 1559365200000000000\n\
+             surepet_event,household=1,device=Water,kind=drinking id=3i,duration=30i,change=-12.5 1559365260000000000\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_write_device_status() {
+        let devices: Vec<Device> = serde_json::from_str(
+            r#"[
+            {"id":10,"name":"Hub","product_id":1,"household_id":1},
+            {"id":11,"name":"Water","product_id":8,"household_id":1,
+             "status":{"battery":5.8,"online":true,"signal":{"hub_rssi":-61},
+                       "bowl_status":[{"index":0,"current_weight":412.5}]}}
+        ]"#,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        write_devices(&devices, Timestamp::from_unix(1_559_365_200), &mut output).unwrap();
+
+        assert_eq!(
+            "surepet_device,household=1,device=Water,product=8 battery=5.8,online=true,hub_rssi=-61,water_weight=412.5 1559365200000000000\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! JSON Lines export of timeline events.
+//!
+//! Each event is written as one compact object per line, flattened so it can
+//! be piped straight into `jq`, loaded into BigQuery or picked up by a log
+//! shipper. Daily reports can be written the same way with
+//! [`Format::JsonLines`](../enum.Format.html#variant.JsonLines).
+use crate::timeline::{Event, EventKind};
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+/// Writes timeline events as JSON Lines.
+///
+/// Every line has the keys `id`, `timestamp`, `household_id`, `kind`,
+/// `kind_code`, `pet_ids`, `pets`, `device_ids` and `devices`. Movement
+/// events add `direction`, feeding and drinking events add `duration_s`
+/// and `change`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::jsonl::JsonLines;
+/// let mut output = Vec::new();
+/// JsonLines::new()
+///     .with_raw(true)
+///     .write_events(&[], &mut output)
+///     .unwrap();
+/// assert!(output.is_empty());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct JsonLines {
+    raw: bool,
+}
+
+impl JsonLines {
+    /// Create a new writer that leaves out raw payloads.
+    pub fn new() -> Self {
+        JsonLines::default()
+    }
+
+    /// Sets whether events of a kind this crate does not know about carry
+    /// their full API payload under a `raw` key.
+    pub fn with_raw(&mut self, raw: bool) -> &mut Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Flattens a single event into the object written for it.
+    pub fn event(&self, event: &Event<'_>) -> Value {
+        let mut line = json!({
+            "id": event.id(),
+            "timestamp": event.created_at(),
+            "household_id": event.household_id(),
+            "kind": super::kind_name(event.kind()),
+            "kind_code": event.kind().code(),
+            "pet_ids": event.pets().iter().map(|pet| pet.id()).collect::<Vec<_>>(),
+            "pets": event.pets().iter().map(|pet| pet.name()).collect::<Vec<_>>(),
+            "device_ids": event.devices().iter().map(|device| device.id()).collect::<Vec<_>>(),
+            "devices": event.devices().iter().map(|device| device.name()).collect::<Vec<_>>(),
+        });
+        if let Some(movement) = event.movements().first() {
+            line["direction"] = super::direction_name(movement.direction()).into();
+        }
+        if let Some(weight) = event.weights().first() {
+            line["duration_s"] = weight.duration().into();
+            line["change"] = super::weight_change(weight).into();
+        }
+        if let EventKind::Unknown(_) = event.kind() {
+            if self.raw {
+                line["raw"] = serde_json::to_value(event).unwrap_or_default();
+            }
+        }
+        line
+    }
+
+    /// Writes one line per event, in the order given.
+    pub fn write_events<W: Write>(&self, events: &[Event<'_>], mut writer: W) -> io::Result<()> {
+        for event in events {
+            writeln!(writer, "{}", self.event(event))?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const EVENTS: &str = r#"[
+        {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00","household_id":1,
+         "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":10,"name":"Back door"}],
+         "movements":[{"device_id":10,"direction":2}]},
+        {"id":1,"type":99,"created_at":"2019-05-31T23:00:00+00:00","data":{"mode":3}}
+    ]"#;
+
+    #[test]
+    fn should_write_flattened_events() {
+        let events: Vec<Event> = serde_json::from_str(EVENTS).unwrap();
+        let mut output = Vec::new();
+        JsonLines::new().write_events(&events, &mut output).unwrap();
+        let lines: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            json!({"id":2,"timestamp":"2019-06-01T05:00:00+00:00","household_id":1,
+                   "kind":"movement","kind_code":0,"pet_ids":[2],"pets":["Milo"],
+                   "device_ids":[10],"devices":["Back door"],"direction":"out"}),
+            lines[0]
+        );
+        assert_eq!(None, lines[1].get("raw"));
+    }
+
+    #[test]
+    fn should_include_raw_unknown_payloads() {
+        let events: Vec<Event> = serde_json::from_str(EVENTS).unwrap();
+        let line = JsonLines::new().with_raw(true).event(&events[1]);
+
+        assert_eq!(json!({"mode":3}), line["raw"]["data"]);
+        assert_eq!(json!(99), line["raw"]["type"]);
+    }
+}
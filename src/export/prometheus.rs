@@ -0,0 +1,259 @@
+//! Prometheus metrics for the current state of a household.
+//!
+//! [`render`](fn.render.html) turns a start state snapshot into gauges in the
+//! Prometheus text format. With the `prometheus-server` feature,
+//! [`serve`](fn.serve.html) exposes them on a tiny blocking HTTP endpoint.
+use crate::device::Product;
+use crate::id::HouseholdId;
+use crate::pet::Location;
+use crate::ratelimit::RateLimit;
+use crate::state::State;
+
+/// Renders the gauges for every pet and device in the snapshot:
+///
+/// * `surepet_pet_inside`, `1` when a flap last saw the pet come in.
+/// * `surepet_device_battery_percent`, see `Status::battery_percent`.
+/// * `surepet_water_remaining_grams`, what is left in a water dispenser.
+/// * `surepet_feeder_last_meal_timestamp`, the Unix time of the last meal.
+///
+/// Series are labelled with `household` and `pet` or `device` names.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::{export::prometheus, state::State};
+/// let output = prometheus::render(&State::default());
+/// assert!(output.starts_with("# HELP surepet_pet_inside"));
+/// ```
+pub fn render(state: &State<'_>) -> String {
+    let household = |id: Option<HouseholdId>| {
+        state
+            .households()
+            .iter()
+            .find(|household| Some(household.id()) == id)
+            .map(|household| household.name().to_string())
+            .or_else(|| id.map(|id| id.to_string()))
+            .unwrap_or_default()
+    };
+
+    let mut inside = Vec::new();
+    let mut last_meal = Vec::new();
+    for pet in state.pets() {
+        let labels = [
+            ("household", household(pet.household_id())),
+            ("pet", pet.name().to_string()),
+        ];
+        match pet.position().map(|position| position.location()) {
+            Some(Location::Inside) => inside.push((labels.clone(), 1.0)),
+            Some(Location::Outside) => inside.push((labels.clone(), 0.0)),
+            _ => {}
+        }
+        if let Some(feeding) = pet.status().feeding() {
+            last_meal.push((labels, feeding.at().unix() as f64));
+        }
+    }
+
+    let mut battery = Vec::new();
+    let mut water = Vec::new();
+    for device in state.devices() {
+        let labels = [
+            ("household", household(device.household_id())),
+            ("device", device.name().to_string()),
+        ];
+        let status = device.status();
+        if let Some(percent) = status.battery_percent() {
+            battery.push((labels.clone(), percent));
+        }
+        if device.product() == Product::Felaqua && !status.bowls().is_empty() {
+            let grams = status
+                .bowls()
+                .iter()
+                .fold(0.0, |total, bowl| total + bowl.current_weight());
+            water.push((labels, grams));
+        }
+    }
+
+    let mut output = String::new();
+    gauge(
+        &mut output,
+        "surepet_pet_inside",
+        "Whether the pet is inside.",
+        &inside,
+    );
+    gauge(
+        &mut output,
+        "surepet_device_battery_percent",
+        "Estimated battery level of the device.",
+        &battery,
+    );
+    gauge(
+        &mut output,
+        "surepet_water_remaining_grams",
+        "Water left in the dispenser.",
+        &water,
+    );
+    gauge(
+        &mut output,
+        "surepet_feeder_last_meal_timestamp",
+        "Unix time of the pet's last meal.",
+        &last_meal,
+    );
+    output
+}
+
+/// Renders what the API last said about its rate limits, as kept by
+/// [`SurePet::rate_limit`](../../client/struct.SurePet.html#method.rate_limit):
+///
+/// * `surepet_api_rate_limit`, the requests allowed per window.
+/// * `surepet_api_rate_limit_remaining`, the requests left in the window.
+/// * `surepet_api_rate_limit_reset_seconds`, the seconds until it resets.
+/// * `surepet_api_retry_after_seconds`, how long the API last asked to wait.
+///
+/// Gauges the API did not report are left out.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::prometheus;
+/// use sure_petcare::ratelimit::RateLimit;
+/// assert_eq!("", prometheus::render_rate_limit(&RateLimit::default()));
+/// ```
+pub fn render_rate_limit(rate_limit: &RateLimit) -> String {
+    let mut output = String::new();
+    let gauges = [
+        (
+            "surepet_api_rate_limit",
+            "Requests the API allows per window.",
+            rate_limit.limit().map(|limit| limit as f64),
+        ),
+        (
+            "surepet_api_rate_limit_remaining",
+            "Requests left in the API's rate limit window.",
+            rate_limit.remaining().map(|remaining| remaining as f64),
+        ),
+        (
+            "surepet_api_rate_limit_reset_seconds",
+            "Seconds until the API's rate limit window resets.",
+            rate_limit.reset().map(|reset| reset.as_secs_f64()),
+        ),
+        (
+            "surepet_api_retry_after_seconds",
+            "Seconds the API last asked callers to wait.",
+            rate_limit.retry_after().map(|wait| wait.as_secs_f64()),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        if let Some(value) = value {
+            output.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} gauge\n{} {}\n",
+                name, help, name, name, value
+            ));
+        }
+    }
+    output
+}
+
+type Sample = ([(&'static str, String); 2], f64);
+
+fn gauge(output: &mut String, name: &str, help: &str, samples: &[Sample]) {
+    output.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} gauge\n",
+        name, help, name
+    ));
+    for (labels, value) in samples {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+            .collect();
+        output.push_str(&format!("{}{{{}}} {}\n", name, labels.join(","), value));
+    }
+}
+
+/// Escapes backslashes, quotes and line breaks in label values.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Serves the output of `render` on every request to `addr`, blocking the
+/// calling thread. The snapshot is fetched for each scrape; a failure is
+/// answered with `503 Service Unavailable` so Prometheus marks the target
+/// as down.
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> std::io::Result<()> {
+/// use sure_petcare::export::prometheus;
+/// prometheus::serve("0.0.0.0:9680", || client.start())?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "prometheus-server")]
+pub fn serve<A, F>(addr: A, mut snapshot: F) -> std::io::Result<()>
+where
+    A: std::net::ToSocketAddrs,
+    F: FnMut() -> crate::error::Result<State<'static>>,
+{
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind(addr)?;
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        // Only the request line matters; skip the headers up to the blank line.
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            line.clear();
+        }
+        let (status, body) = match snapshot() {
+            Ok(state) => ("200 OK", render(&state)),
+            Err(error) => ("503 Service Unavailable", format!("{}\n", error)),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        // A scraper hanging up early is not a reason to stop serving.
+        let _ = stream.write_all(response.as_bytes());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_render_gauges() {
+        let state: State = serde_json::from_str(
+            r#"{"households":[{"id":1,"name":"Home"}],
+                "pets":[{"id":2,"name":"Milo","household_id":1,
+                         "position":{"where":1,"since":"2019-06-01T05:00:00+00:00"},
+                         "status":{"feeding":{"at":"2019-06-01T04:00:00+00:00","change":[-4.5]}}},
+                        {"id":3,"name":"Tilly \"T\"","household_id":1}],
+                "devices":[{"id":11,"name":"Water","product_id":8,"household_id":1,
+                            "status":{"battery":6.0,"bowl_status":[{"current_weight":412.5}]}}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "# HELP surepet_pet_inside Whether the pet is inside.\n\
+             # TYPE surepet_pet_inside gauge\n\
+             surepet_pet_inside{household=\"Home\",pet=\"Milo\"} 1\n\
+             # HELP surepet_device_battery_percent Estimated battery level of the device.\n\
+             # TYPE surepet_device_battery_percent gauge\n\
+             surepet_device_battery_percent{household=\"Home\",device=\"Water\"} 100\n\
+             # HELP surepet_water_remaining_grams Water left in the dispenser.\n\
+             # TYPE surepet_water_remaining_grams gauge\n\
+             surepet_water_remaining_grams{household=\"Home\",device=\"Water\"} 412.5\n\
+             # HELP surepet_feeder_last_meal_timestamp Unix time of the pet's last meal.\n\
+             # TYPE surepet_feeder_last_meal_timestamp gauge\n\
+             surepet_feeder_last_meal_timestamp{household=\"Home\",pet=\"Milo\"} 1559361600\n",
+            render(&state)
+        );
+        assert_eq!("Tilly \\\"T\\\"", escape("Tilly \"T\""));
+    }
+}
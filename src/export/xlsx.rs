@@ -0,0 +1,457 @@
+//! Excel workbooks of daily reports, enabled with the `xlsx` feature.
+//!
+//! Each sheet holds the rows of a [`ReportExporter`](../struct.ReportExporter.html),
+//! typically one sheet per pet, with a bold, frozen header row and real
+//! dates in the first column, so the file opens ready to sort and chart.
+//! Workbooks are written uncompressed, which keeps this module free of
+//! dependencies at the cost of somewhat larger files.
+use super::ReportExporter;
+use crate::id::PetId;
+use crate::time::Date;
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// The longest sheet name Excel accepts.
+const MAX_NAME: usize = 31;
+
+/// An Excel workbook being put together.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::export::{xlsx::Workbook, ReportExporter};
+/// let mut output = Vec::new();
+/// Workbook::new()
+///     .add_sheet("Milo", ReportExporter::new().with_feeding(&[]))
+///     .write(&mut output)
+///     .unwrap();
+/// assert_eq!(b"PK", &output[..2]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Workbook {
+    sheets: Vec<Sheet>,
+}
+
+#[derive(Clone, Debug)]
+struct Sheet {
+    name: String,
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Workbook {
+    /// Create a new workbook without sheets.
+    pub fn new() -> Self {
+        Workbook::default()
+    }
+
+    /// Adds a sheet with the exporter's columns and rows. Names are cut to
+    /// Excel's 31 character limit and characters it rejects are replaced. A
+    /// name left blank becomes `Sheet <n>`, and one already in the workbook,
+    /// ignoring case as Excel does, gets a ` (2)` style suffix.
+    pub fn add_sheet(&mut self, name: &str, exporter: &ReportExporter<'_>) -> &mut Self {
+        let fallback = format!("Sheet {}", self.sheets.len() + 1);
+        self.push(name, fallback, exporter)
+    }
+
+    /// Adds a sheet for a pet, named like [`add_sheet`](#method.add_sheet)
+    /// but `Pet <id>` when the name is blank.
+    pub fn add_pet_sheet(
+        &mut self,
+        pet_id: PetId,
+        name: &str,
+        exporter: &ReportExporter<'_>,
+    ) -> &mut Self {
+        self.push(name, format!("Pet {}", pet_id), exporter)
+    }
+
+    fn push(&mut self, name: &str, fallback: String, exporter: &ReportExporter<'_>) -> &mut Self {
+        let name = self.sheet_name(name, fallback);
+        self.sheets.push(Sheet {
+            name,
+            headers: exporter.headers(),
+            rows: exporter.rows(),
+        });
+        self
+    }
+
+    /// A name Excel accepts that no sheet has yet.
+    fn sheet_name(&self, name: &str, fallback: String) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                '[' | ']' | ':' | '*' | '?' | '/' | '\\' => '_',
+                c => c,
+            })
+            .collect();
+        let name = name.trim().trim_matches('\'').trim();
+        let name = if name.is_empty() {
+            fallback
+        } else {
+            name.to_string()
+        };
+        let cut = |name: &str, limit: usize| -> String {
+            name.chars()
+                .take(limit)
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+        let taken = |candidate: &str| {
+            self.sheets
+                .iter()
+                .any(|sheet| sheet.name.to_lowercase() == candidate.to_lowercase())
+        };
+        let name = cut(&name, MAX_NAME);
+        if !taken(&name) {
+            return name;
+        }
+        (2..)
+            .map(|n| {
+                let suffix = format!(" ({})", n);
+                format!("{}{}", cut(&name, MAX_NAME - suffix.len()), suffix)
+            })
+            .find(|candidate| !taken(candidate))
+            .expect("a free suffix")
+    }
+
+    /// Writes the workbook as an `.xlsx` file.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut zip = Zip::new(writer);
+        zip.file("[Content_Types].xml", self.content_types().as_bytes())?;
+        zip.file("_rels/.rels", ROOT_RELS.as_bytes())?;
+        zip.file("xl/workbook.xml", self.workbook().as_bytes())?;
+        zip.file(
+            "xl/_rels/workbook.xml.rels",
+            self.workbook_rels().as_bytes(),
+        )?;
+        zip.file("xl/styles.xml", STYLES.as_bytes())?;
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            let path = format!("xl/worksheets/sheet{}.xml", index + 1);
+            zip.file(&path, worksheet(&sheet.headers, &sheet.rows).as_bytes())?;
+        }
+        zip.finish()
+    }
+
+    fn content_types(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+             <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+             <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+             <Override PartName=\"/xl/workbook.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+             <Override PartName=\"/xl/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>",
+        );
+        for index in 1..=self.sheets.len() {
+            xml.push_str(&format!(
+                "<Override PartName=\"/xl/worksheets/sheet{}.xml\" \
+                 ContentType=\"application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+                index
+            ));
+        }
+        xml.push_str("</Types>");
+        xml
+    }
+
+    fn workbook(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <workbook xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\" \
+             xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\"><sheets>",
+        );
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            xml.push_str(&format!(
+                "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>",
+                escape(&sheet.name),
+                index + 1,
+                index + 1
+            ));
+        }
+        xml.push_str("</sheets></workbook>");
+        xml
+    }
+
+    fn workbook_rels(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+        );
+        for index in 1..=self.sheets.len() {
+            xml.push_str(&format!(
+                "<Relationship Id=\"rId{}\" \
+                 Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet\" \
+                 Target=\"worksheets/sheet{}.xml\"/>",
+                index, index
+            ));
+        }
+        xml.push_str(&format!(
+            "<Relationship Id=\"rId{}\" \
+             Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" \
+             Target=\"styles.xml\"/></Relationships>",
+            self.sheets.len() + 1
+        ));
+        xml
+    }
+}
+
+const ROOT_RELS: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">\
+<Relationship Id=\"rId1\" \
+Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" \
+Target=\"xl/workbook.xml\"/></Relationships>";
+
+/// Style `1` is the bold header and style `2` a date.
+const STYLES: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+<styleSheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+<fonts count=\"2\"><font><sz val=\"11\"/><name val=\"Calibri\"/></font>\
+<font><b/><sz val=\"11\"/><name val=\"Calibri\"/></font></fonts>\
+<fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill>\
+<fill><patternFill patternType=\"gray125\"/></fill></fills>\
+<borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+<cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+<cellXfs count=\"3\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\"/>\
+<xf numFmtId=\"0\" fontId=\"1\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyFont=\"1\"/>\
+<xf numFmtId=\"14\" fontId=\"0\" fillId=\"0\" borderId=\"0\" xfId=\"0\" applyNumberFormat=\"1\"/></cellXfs>\
+</styleSheet>";
+
+fn worksheet(headers: &[&str], rows: &[Vec<Value>]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+         <worksheet xmlns=\"http://schemas.openxmlformats.org/spreadsheetml/2006/main\">\
+         <sheetViews><sheetView workbookViewId=\"0\">\
+         <pane ySplit=\"1\" topLeftCell=\"A2\" activePane=\"bottomLeft\" state=\"frozen\"/>\
+         </sheetView></sheetViews>",
+    );
+    xml.push_str(&format!(
+        "<cols><col min=\"1\" max=\"{}\" width=\"16\" customWidth=\"1\"/></cols><sheetData>",
+        headers.len().max(1)
+    ));
+    xml.push_str("<row r=\"1\">");
+    for (column, header) in headers.iter().enumerate() {
+        xml.push_str(&text_cell(&reference(column, 1), header, 1));
+    }
+    xml.push_str("</row>");
+
+    for (index, row) in rows.iter().enumerate() {
+        let line = index + 2;
+        xml.push_str(&format!("<row r=\"{}\">", line));
+        for (column, value) in row.iter().enumerate() {
+            let cell = reference(column, line);
+            match value {
+                Value::Null => {}
+                Value::Number(number) => {
+                    xml.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", cell, number))
+                }
+                Value::String(date) if column == 0 && date.parse::<Date>().is_ok() => {
+                    let date: Date = date.parse().unwrap_or_default();
+                    // Excel counts days from 1899-12-30.
+                    let serial = date.midnight().unix() / 86_400 + 25_569;
+                    xml.push_str(&format!("<c r=\"{}\" s=\"2\"><v>{}</v></c>", cell, serial));
+                }
+                Value::String(value) => xml.push_str(&text_cell(&cell, value, 0)),
+                value => xml.push_str(&text_cell(&cell, &value.to_string(), 0)),
+            }
+        }
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData></worksheet>");
+    xml
+}
+
+fn text_cell(reference: &str, value: &str, style: u8) -> String {
+    format!(
+        "<c r=\"{}\" s=\"{}\" t=\"inlineStr\"><is><t>{}</t></is></c>",
+        reference,
+        style,
+        escape(value)
+    )
+}
+
+/// The `A1` style reference of a zero based column and one based row.
+fn reference(column: usize, row: usize) -> String {
+    let mut letters = Vec::new();
+    let mut column = column + 1;
+    while column > 0 {
+        letters.push(b'A' + ((column - 1) % 26) as u8);
+        column = (column - 1) / 26;
+    }
+    letters.reverse();
+    format!("{}{}", String::from_utf8_lossy(&letters), row)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A minimal zip writer storing files without compression.
+struct Zip<W> {
+    writer: W,
+    offset: u32,
+    entries: Vec<(String, u32, u32, u32)>,
+}
+
+impl<W: Write> Zip<W> {
+    fn new(writer: W) -> Self {
+        Zip {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    fn file(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let crc = crc32(data);
+        let size = data.len() as u32;
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&[20, 0, 0, 0, 0, 0]);
+        // 1980-01-01 00:00, the earliest time a zip file can record.
+        header.extend_from_slice(&[0, 0, 0x21, 0]);
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&[0, 0]);
+        header.extend_from_slice(name.as_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)?;
+
+        self.entries
+            .push((name.to_string(), crc, size, self.offset));
+        self.offset += header.len() as u32 + size;
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let mut directory = Vec::new();
+        for (name, crc, size, offset) in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0]);
+            directory.extend_from_slice(&[0, 0, 0x21, 0]);
+            directory.extend_from_slice(&crc.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes());
+            directory.extend_from_slice(&size.to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let count = (self.entries.len() as u16).to_le_bytes();
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0, 0, 0, 0]);
+        end.extend_from_slice(&count);
+        end.extend_from_slice(&count);
+        end.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        end.extend_from_slice(&self.offset.to_le_bytes());
+        end.extend_from_slice(&[0, 0]);
+
+        self.writer.write_all(&directory)?;
+        self.writer.write_all(&end)?;
+        self.writer.flush()
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{DailyFeeding, Feeding};
+    use crate::time::DateRange;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_write_sheets() {
+        let datapoints: Vec<Feeding> = serde_json::from_str(
+            r#"[{"from":"2019-06-01T07:00:00Z","to":"2019-06-01T07:02:00Z","duration":120,"context":1,
+                 "weights":[{"weight":10,"change":-12.5}]}]"#,
+        )
+        .unwrap();
+        let day = Date::new(2019, 6, 1).unwrap();
+        let feeding = DailyFeeding::from_datapoints(&datapoints, DateRange::day(day));
+        let xml = worksheet(
+            &ReportExporter::new().with_feeding(&feeding).headers(),
+            &ReportExporter::new().with_feeding(&feeding).rows(),
+        );
+
+        assert!(xml.contains("<c r=\"A1\" s=\"1\" t=\"inlineStr\"><is><t>date</t></is></c>"));
+        assert!(xml.contains("<c r=\"A2\" s=\"2\"><v>43617</v></c><c r=\"B2\"><v>12.5</v></c>"));
+
+        let mut output = Vec::new();
+        Workbook::new()
+            .add_sheet(
+                "Milo [indoor]",
+                ReportExporter::new().with_feeding(&feeding),
+            )
+            .write(&mut output)
+            .unwrap();
+        let text = String::from_utf8_lossy(&output);
+        assert!(text.contains("xl/worksheets/sheet1.xml"));
+        assert!(text.contains("<sheet name=\"Milo _indoor_\" sheetId=\"1\" r:id=\"rId1\"/>"));
+        assert_eq!(
+            &[0x50, 0x4b, 0x05, 0x06],
+            &output[output.len() - 22..output.len() - 18]
+        );
+    }
+
+    #[test]
+    fn should_give_every_sheet_a_distinct_name() {
+        let long = "Sir Reginald Fluffington the Third";
+        let mut workbook = Workbook::new();
+        workbook
+            .add_pet_sheet(PetId::new(1), long, &ReportExporter::new())
+            .add_pet_sheet(
+                PetId::new(2),
+                &format!("{} Jr", long),
+                &ReportExporter::new(),
+            )
+            .add_pet_sheet(PetId::new(3), " '' ", &ReportExporter::new())
+            .add_pet_sheet(PetId::new(4), "", &ReportExporter::new())
+            .add_sheet("milo", &ReportExporter::new())
+            .add_sheet("Milo", &ReportExporter::new())
+            .add_sheet("  ", &ReportExporter::new());
+        let names: Vec<&str> = workbook
+            .sheets
+            .iter()
+            .map(|sheet| sheet.name.as_str())
+            .collect();
+
+        assert_eq!(
+            vec![
+                "Sir Reginald Fluffington the Th",
+                "Sir Reginald Fluffington th (2)",
+                "Pet 3",
+                "Pet 4",
+                "milo",
+                "Milo (2)",
+                "Sheet 7",
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn should_name_cells_and_checksum() {
+        assert_eq!("A1", reference(0, 1));
+        assert_eq!("Z3", reference(25, 3));
+        assert_eq!("AA10", reference(26, 10));
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+}
@@ -0,0 +1,442 @@
+//! Random but plausible data, enabled with the `fake` feature.
+//!
+//! A [`Faker`](struct.Faker.html) invents a household of pets and devices
+//! and a timeline of flap, feeder and water events for it, so user
+//! interfaces can be built without an account or hardware. The generator
+//! is seeded, so the same seed always gives the same household.
+//!
+//! ```
+//! use sure_petcare::fake::Faker;
+//! let mut faker = Faker::new(7);
+//! let pets = faker.pets(2);
+//! let devices = faker.devices();
+//! let to = sure_petcare::time::Timestamp::now();
+//! let events = faker.events(&pets, &devices, sure_petcare::time::Timestamp::from_unix(to.unix() - 86_400), to);
+//! assert!(!events.is_empty());
+//! ```
+use crate::device::{
+    BowlStatus, CloseDelay, Control, Curfew, Device, LedMode, Lid, LockMode, Locking, Product,
+    Signal, Status,
+};
+use crate::id::HouseholdId;
+use crate::pet::{Location, Pet, PetStatus, Position, Visit};
+use crate::time::{TimeOfDay, Timestamp};
+use crate::timeline::{
+    DeviceRef, Direction, Event, EventKind, Frame, MovementEntry, PetRef, WeightEntry,
+};
+use std::borrow::Cow;
+
+const NAMES: &[&str] = &[
+    "Milo", "Cleo", "Luna", "Oscar", "Bella", "Simba", "Nala", "Tigger", "Smudge", "Poppy",
+    "Jasper", "Willow", "Felix", "Misty", "Pumpkin", "Shadow",
+];
+
+/// The context of a pet eating or drinking, as opposed to a refill.
+const PET_VISIT: i64 = 1;
+
+/// A seeded generator of made up households.
+#[derive(Clone, Debug)]
+pub struct Faker {
+    state: u64,
+    next_id: i64,
+    household_id: HouseholdId,
+    now: Timestamp,
+}
+
+impl Faker {
+    /// Creates a generator; positions and statuses are relative to the
+    /// current time.
+    pub fn new(seed: u64) -> Self {
+        let mut faker = Faker {
+            state: seed,
+            next_id: 0,
+            household_id: HouseholdId::default(),
+            now: Timestamp::now(),
+        };
+        faker.next_id = faker.between(1_000, 9_000) * 1_000;
+        faker.household_id = faker.id();
+        faker
+    }
+
+    /// Sets the time positions and statuses are relative to.
+    pub fn with_now(mut self, now: Timestamp) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// The household everything generated belongs to.
+    pub fn household_id(&self) -> HouseholdId {
+        self.household_id
+    }
+
+    /// A pet with a tag, a position and recent feeding and drinking.
+    pub fn pet(&mut self) -> Pet<'static> {
+        let id = self.id();
+        let name = NAMES[self.between(0, NAMES.len() as i64 - 1) as usize];
+        let ago = self.between(5 * 60, 6 * 3600);
+        let location = if self.chance(0.5) {
+            Location::Inside
+        } else {
+            Location::Outside
+        };
+        let position = Position {
+            device_id: None,
+            location,
+            since: self.ago(ago),
+        };
+        let fed = self.between(30 * 60, 8 * 3600);
+        let drank = self.between(30 * 60, 8 * 3600);
+        let feeding = Visit {
+            device_id: None,
+            at: self.ago(fed),
+            change: vec![-self.grams(3.0, 15.0), -self.grams(0.0, 8.0)],
+        };
+        let drinking = Visit {
+            device_id: None,
+            at: self.ago(drank),
+            change: vec![-self.grams(5.0, 30.0)],
+        };
+        Pet {
+            id,
+            name: Cow::Owned(name.to_string()),
+            household_id: Some(self.household_id),
+            tag_id: Some(self.id()),
+            position: Some(position),
+            status: PetStatus {
+                feeding: Some(feeding),
+                drinking: Some(drinking),
+            },
+            photo: None,
+            comments: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Several pets.
+    pub fn pets(&mut self, count: usize) -> Vec<Pet<'static>> {
+        (0..count).map(|_| self.pet()).collect()
+    }
+
+    /// A device of the given product with a plausible status.
+    pub fn device(&mut self, product: Product) -> Device<'static> {
+        let id = self.id();
+        let name = match product {
+            Product::Hub => "Hub",
+            Product::Repeater => "Repeater",
+            Product::PetDoor => "Pet door",
+            Product::Feeder | Product::FeederLite => "Feeder",
+            Product::Programmer => "Programmer",
+            Product::CatFlap => "Cat flap",
+            Product::Felaqua => "Water",
+            Product::Unknown(_) => "Device",
+        };
+        let battery = match product {
+            Product::Hub | Product::Repeater => None,
+            _ => Some(self.grams(4.8, 6.0)),
+        };
+        let bowls = match product {
+            Product::Feeder => 2,
+            Product::FeederLite | Product::Felaqua => 1,
+            _ => 0,
+        };
+        let bowl_status = (0..bowls)
+            .map(|index| BowlStatus {
+                index,
+                current_weight: if product == Product::Felaqua {
+                    self.grams(150.0, 450.0)
+                } else {
+                    self.grams(5.0, 60.0)
+                },
+            })
+            .collect();
+        let flap = matches!(product, Product::CatFlap | Product::PetDoor);
+        let curfew = if flap && self.chance(0.5) {
+            vec![Curfew {
+                enabled: true,
+                lock_time: TimeOfDay::new(self.between(20, 23) as u8, 0).unwrap(),
+                unlock_time: TimeOfDay::new(self.between(6, 8) as u8, 30).unwrap(),
+            }]
+        } else {
+            Vec::new()
+        };
+        let online = !self.chance(0.05);
+        Device {
+            id,
+            name: Cow::Borrowed(name),
+            product_id: product,
+            household_id: Some(self.household_id),
+            parent_device_id: None,
+            status: Status {
+                battery,
+                online: Some(online),
+                signal: Some(Signal {
+                    device_rssi: Some(-self.grams(40.0, 90.0).round()),
+                    hub_rssi: Some(-self.grams(40.0, 90.0).round()),
+                }),
+                bowl_status,
+                locking: if flap {
+                    Some(Locking {
+                        mode: LockMode::Unlocked,
+                    })
+                } else {
+                    None
+                },
+                version: None,
+            },
+            control: Control {
+                curfew,
+                lid: if product.is_feeder() {
+                    Some(Lid {
+                        close_delay: CloseDelay::Normal,
+                    })
+                } else {
+                    None
+                },
+                led_mode: if product == Product::Hub {
+                    Some(LedMode::Bright)
+                } else {
+                    None
+                },
+            },
+            last_activity_at: Some(self.ago(if online { 60 } else { 6 * 3600 })),
+        }
+    }
+
+    /// A hub with a cat flap, a feeder and a water dispenser paired to it.
+    pub fn devices(&mut self) -> Vec<Device<'static>> {
+        let hub = self.device(Product::Hub);
+        let mut devices = vec![hub];
+        for product in &[Product::CatFlap, Product::Feeder, Product::Felaqua] {
+            let mut device = self.device(*product);
+            device.parent_device_id = Some(devices[0].id);
+            devices.push(device);
+        }
+        devices
+    }
+
+    /// Events of the pets using the devices between two times, newest first
+    /// as the timeline returns them. Pets go through flaps a few times a
+    /// day, mostly in daylight, eat several small meals from feeders and
+    /// drink from water dispensers now and again.
+    pub fn events(
+        &mut self,
+        pets: &[Pet<'_>],
+        devices: &[Device<'_>],
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Vec<Event<'static>> {
+        let mut events = Vec::new();
+        let mut bowls: Vec<Vec<f64>> = devices
+            .iter()
+            .map(|device| {
+                device
+                    .status
+                    .bowl_status
+                    .iter()
+                    .map(BowlStatus::current_weight)
+                    .collect()
+            })
+            .collect();
+        let days = (to.unix() - from.unix()).max(0) / 86_400 + 1;
+        for pet in pets {
+            let mut inside = pet
+                .position()
+                .is_none_or(|position| position.location() == Location::Inside);
+            for day in 0..days {
+                let start = from.unix() - from.unix() % 86_400 + day * 86_400;
+                for (i, device) in devices.iter().enumerate() {
+                    let visits = match device.product_id {
+                        Product::CatFlap | Product::PetDoor => self.between(2, 6),
+                        Product::Feeder | Product::FeederLite => self.between(3, 6),
+                        Product::Felaqua => self.between(1, 3),
+                        _ => 0,
+                    };
+                    let mut times: Vec<i64> = (0..visits)
+                        .map(|_| start + self.between(6 * 3600, 23 * 3600))
+                        .filter(|at| *at >= from.unix() && *at <= to.unix())
+                        .collect();
+                    times.sort_unstable();
+                    for at in times {
+                        let event = match device.product_id {
+                            Product::CatFlap | Product::PetDoor => {
+                                inside = !inside;
+                                self.movement(pet, device, at, inside)
+                            }
+                            Product::Felaqua => {
+                                self.visit(pet, device, at, EventKind::Drinking, &mut bowls[i])
+                            }
+                            _ => self.visit(pet, device, at, EventKind::Feeding, &mut bowls[i]),
+                        };
+                        events.push(event);
+                    }
+                }
+            }
+        }
+        events.sort_by_key(|event| event.created_at);
+        for event in &mut events {
+            event.id = self.id();
+        }
+        events.reverse();
+        events
+    }
+
+    fn movement(
+        &mut self,
+        pet: &Pet<'_>,
+        device: &Device<'_>,
+        at: i64,
+        inside: bool,
+    ) -> Event<'static> {
+        let mut event = self.event(pet, device, at, EventKind::Movement);
+        event.movements.push(MovementEntry {
+            device_id: Some(device.id),
+            tag_id: pet.tag_id,
+            direction: if inside {
+                Direction::In
+            } else {
+                Direction::Out
+            },
+        });
+        event
+    }
+
+    fn visit(
+        &mut self,
+        pet: &Pet<'_>,
+        device: &Device<'_>,
+        at: i64,
+        kind: EventKind,
+        bowls: &mut [f64],
+    ) -> Event<'static> {
+        let mut event = self.event(pet, device, at, kind);
+        let frames = bowls
+            .iter_mut()
+            .enumerate()
+            .map(|(index, bowl)| {
+                let (full, portion) = match kind {
+                    EventKind::Drinking => (450.0, self.grams(5.0, 30.0)),
+                    _ => (60.0, self.grams(1.0, 12.0)),
+                };
+                if *bowl < portion {
+                    *bowl = full;
+                }
+                *bowl -= portion;
+                Frame {
+                    index: index as u8,
+                    current_weight: round(*bowl),
+                    change: -round(portion),
+                }
+            })
+            .collect();
+        event.weights.push(WeightEntry {
+            device_id: Some(device.id),
+            tag_id: pet.tag_id,
+            duration: self.between(15, 240),
+            context: PET_VISIT,
+            frames,
+        });
+        event
+    }
+
+    fn event(
+        &mut self,
+        pet: &Pet<'_>,
+        device: &Device<'_>,
+        at: i64,
+        kind: EventKind,
+    ) -> Event<'static> {
+        Event {
+            id: 0,
+            kind,
+            created_at: Timestamp::from_unix(at),
+            household_id: Some(self.household_id),
+            pets: vec![PetRef {
+                id: pet.id,
+                name: Cow::Owned(pet.name.to_string()),
+            }],
+            devices: vec![DeviceRef {
+                id: device.id,
+                name: Cow::Owned(device.name.to_string()),
+                product_id: Some(device.product_id.code()),
+            }],
+            movements: Vec::new(),
+            weights: Vec::new(),
+            other: serde_json::Map::new(),
+        }
+    }
+
+    fn id<I: From<i64>>(&mut self) -> I {
+        self.next_id += 1;
+        I::from(self.next_id)
+    }
+
+    fn ago(&self, seconds: i64) -> Timestamp {
+        Timestamp::from_unix(self.now.unix() - seconds)
+    }
+
+    /// A rounded amount between two bounds.
+    fn grams(&mut self, low: f64, high: f64) -> f64 {
+        round(low + self.unit() * (high - low))
+    }
+
+    fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// SplitMix64, which is plenty for made up data.
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Report;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_generate_consistent_households() {
+        let now = Timestamp::from_unix(1_559_390_400);
+        let mut faker = Faker::new(42).with_now(now);
+        let pets = faker.pets(2);
+        let devices = faker.devices();
+        let events = faker.events(
+            &pets,
+            &devices,
+            Timestamp::from_unix(now.unix() - 3 * 86_400),
+            now,
+        );
+
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].created_at() >= pair[1].created_at()));
+        assert!(events.windows(2).all(|pair| pair[0].id() > pair[1].id()));
+        assert!(events
+            .iter()
+            .all(|event| event.household_id() == Some(faker.household_id())));
+        assert_eq!(Some(devices[0].id()), devices[1].parent_device_id());
+        let report = Report::from_events(&events, pets[0].id());
+        assert!(!report.feeding().is_empty());
+        assert!(report.feeding().iter().all(|feeding| feeding.from() <= now));
+
+        let mut again = Faker::new(42).with_now(now);
+        assert_eq!(pets[1].name(), again.pets(2)[1].name());
+    }
+}
@@ -0,0 +1,63 @@
+//! Running independent requests on a few threads at once.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Maps every item with `f` on at most `limit` threads, keeping the order of
+/// `items`. With a limit of one, or a single item, it runs on the calling
+/// thread.
+pub(crate) fn map<I, R, F>(items: &[I], limit: usize, f: F) -> Vec<R>
+where
+    I: Sync,
+    R: Send,
+    F: Fn(&I) -> R + Sync,
+{
+    let workers = limit.max(1).min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let item = match items.get(index) {
+                    Some(item) => item,
+                    None => break,
+                };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is mapped"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn should_keep_the_order_of_items() {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let doubled = map(&[5u64, 1, 3, 2, 4], 2, |n| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(*n));
+            running.fetch_sub(1, Ordering::SeqCst);
+            n * 2
+        });
+
+        assert_eq!(vec![10, 2, 6, 4, 8], doubled);
+        assert!(most.load(Ordering::SeqCst) <= 2);
+    }
+}
@@ -0,0 +1,378 @@
+//! A C interface to the client, enabled with the `ffi` feature.
+//!
+//! Build a C library with `cargo rustc --lib --features ffi --crate-type cdylib`
+//! (or `staticlib`). The client is an opaque `SpClient` handle; HTTP is left
+//! to the caller, who passes an `sp_send` callback that performs a request
+//! and writes the response body with [`sp_response_write`](fn.sp_response_write.html).
+//!
+//! Functions returning a pointer return null on failure and functions
+//! returning an `int32_t` return `0` on success and `-1` on failure; either
+//! way [`sp_last_error`](fn.sp_last_error.html) describes what went wrong.
+//! A panic inside the library is caught at the boundary and reported the
+//! same way rather than unwinding into the caller.
+//! Strings are UTF-8 and NUL terminated, and every string or handle handed
+//! out by the library must be released with the matching `_free` function.
+use crate::client::SurePet;
+use crate::device::LockMode;
+use crate::error::{Error, Result};
+use crate::id::DeviceId;
+use crate::login;
+use crate::transport::{Request, Response, Transport};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// Performs an HTTP request.
+///
+/// `headers` holds one `Name: value` pair per line and `body` may be null
+/// when `body_len` is zero. The callback writes the response body to
+/// `response` and returns the HTTP status code, or a negative number when
+/// the request could not be sent at all.
+pub type SpSend = extern "C" fn(
+    user_data: *mut c_void,
+    method: *const c_char,
+    url: *const c_char,
+    headers: *const c_char,
+    body: *const u8,
+    body_len: usize,
+    response: *mut SpResponse,
+) -> i32;
+
+/// The response body being written by an `SpSend` callback.
+pub struct SpResponse {
+    body: Vec<u8>,
+}
+
+/// An authenticated client.
+pub struct SpClient {
+    client: SurePet<CallbackTransport>,
+}
+
+struct CallbackTransport {
+    send: SpSend,
+    user_data: *mut c_void,
+}
+
+impl Transport for CallbackTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let method = CString::new(request.method.as_str()).map_err(Error::transport)?;
+        let url = CString::new(request.url.as_str()).map_err(Error::transport)?;
+        let headers: String = request
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect();
+        let headers = CString::new(headers).map_err(Error::transport)?;
+        let body = request.body.as_deref().unwrap_or(&[]);
+
+        let mut response = SpResponse { body: Vec::new() };
+        let status = (self.send)(
+            self.user_data,
+            method.as_ptr(),
+            url.as_ptr(),
+            headers.as_ptr(),
+            body.as_ptr(),
+            body.len(),
+            &mut response,
+        );
+        if !(0..=i32::from(u16::MAX)).contains(&status) {
+            return Err(Error::transport(format!(
+                "send callback failed with {}",
+                status
+            )));
+        }
+        Ok(Response {
+            status: status as u16,
+            headers: Vec::new(),
+            body: response.body,
+        })
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error<E: ToString>(error: E) {
+    let message = CString::new(error.to_string().replace('\0', " ")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Runs the body of an entry point, returning `failed` with the panic as
+/// the last error should it panic.
+fn guarded<R>(failed: R, body: impl FnOnce() -> R) -> R {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        set_error(format!("the library panicked: {}", message));
+        failed
+    })
+}
+
+unsafe fn string<'s>(value: *const c_char, name: &str) -> Option<&'s str> {
+    if value.is_null() {
+        set_error(format!("{} is null", name));
+        return None;
+    }
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => Some(value),
+        Err(error) => {
+            set_error(format!("{} is not UTF-8: {}", name, error));
+            None
+        }
+    }
+}
+
+/// The message of the last error on this thread, or null. The pointer stays
+/// valid until the next call into the library on the same thread.
+#[no_mangle]
+pub extern "C" fn sp_last_error() -> *const c_char {
+    guarded(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+    })
+}
+
+/// Appends bytes to the response being written by an `SpSend` callback.
+///
+/// # Safety
+///
+/// `response` must be the pointer passed to the callback and `data` must
+/// point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sp_response_write(response: *mut SpResponse, data: *const u8, len: usize) {
+    guarded((), || {
+        if response.is_null() || (data.is_null() && len > 0) {
+            return;
+        }
+        if len > 0 {
+            (*response)
+                .body
+                .extend_from_slice(std::slice::from_raw_parts(data, len));
+        }
+    })
+}
+
+/// Logs in and returns a client, or null on failure.
+///
+/// # Safety
+///
+/// `email`, `password` and `device_id` must be valid NUL terminated strings.
+/// `send` is called with `user_data` on the calling thread for as long as
+/// the client lives.
+#[no_mangle]
+pub unsafe extern "C" fn sp_login(
+    send: SpSend,
+    user_data: *mut c_void,
+    email: *const c_char,
+    password: *const c_char,
+    device_id: *const c_char,
+) -> *mut SpClient {
+    guarded(ptr::null_mut(), || {
+        let email = match string(email, "email") {
+            Some(email) => email,
+            None => return ptr::null_mut(),
+        };
+        let password = match string(password, "password") {
+            Some(password) => password,
+            None => return ptr::null_mut(),
+        };
+        let device_id = match string(device_id, "device_id") {
+            Some(device_id) => device_id,
+            None => return ptr::null_mut(),
+        };
+        let request = login::RequestBuilder::new()
+            .with_email_address(email)
+            .with_password(password)
+            .with_device_id(device_id)
+            .build();
+        match SurePet::login(CallbackTransport { send, user_data }, &request) {
+            Ok(client) => Box::into_raw(Box::new(SpClient { client })),
+            Err(error) => {
+                set_error(error);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Releases a client. Passing null does nothing.
+///
+/// # Safety
+///
+/// `client` must come from `sp_login` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sp_client_free(client: *mut SpClient) {
+    guarded((), || {
+        if !client.is_null() {
+            drop(Box::from_raw(client));
+        }
+    })
+}
+
+/// Fetches the pets on the account as a JSON array, or null on failure.
+/// Release the string with `sp_string_free`.
+///
+/// # Safety
+///
+/// `client` must be a live handle from `sp_login`.
+#[no_mangle]
+pub unsafe extern "C" fn sp_pets_json(client: *const SpClient) -> *mut c_char {
+    guarded(ptr::null_mut(), || {
+        if client.is_null() {
+            set_error("client is null");
+            return ptr::null_mut();
+        }
+        let json = (*client)
+            .client
+            .pets()
+            .list()
+            .and_then(|pets| serde_json::to_string(&pets).map_err(Error::from));
+        match json.map(|json| CString::new(json).map_err(Error::transport)) {
+            Ok(Ok(json)) => json.into_raw(),
+            Ok(Err(error)) | Err(error) => {
+                set_error(error);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Changes the lock mode of a flap, using the API's mode codes: `0`
+/// unlocked, `1` keep in, `2` keep out, `3` locked.
+///
+/// # Safety
+///
+/// `client` must be a live handle from `sp_login`.
+#[no_mangle]
+pub unsafe extern "C" fn sp_set_lock_mode(
+    client: *const SpClient,
+    device_id: i64,
+    mode: i32,
+) -> i32 {
+    guarded(-1, || {
+        if client.is_null() {
+            set_error("client is null");
+            return -1;
+        }
+        match (*client)
+            .client
+            .devices()
+            .set_lock_mode(DeviceId::new(device_id), LockMode::from(i64::from(mode)))
+        {
+            Ok(()) => 0,
+            Err(error) => {
+                set_error(error);
+                -1
+            }
+        }
+    })
+}
+
+/// Releases a string returned by the library. Passing null does nothing.
+///
+/// # Safety
+///
+/// `value` must come from this library and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sp_string_free(value: *mut c_char) {
+    guarded((), || {
+        if !value.is_null() {
+            drop(CString::from_raw(value));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    extern "C" fn fake_send(
+        user_data: *mut c_void,
+        method: *const c_char,
+        url: *const c_char,
+        _headers: *const c_char,
+        _body: *const u8,
+        _body_len: usize,
+        response: *mut SpResponse,
+    ) -> i32 {
+        let calls = unsafe { &mut *(user_data as *mut Vec<String>) };
+        let (method, url) = unsafe {
+            (
+                CStr::from_ptr(method).to_str().unwrap(),
+                CStr::from_ptr(url).to_str().unwrap(),
+            )
+        };
+        calls.push(format!("{} {}", method, url));
+        let body: &[u8] = if url.ends_with("/api/auth/login") {
+            br#"{"data":{"token":"abc"}}"#
+        } else if url.contains("/api/pet") {
+            br#"{"data":[{"id":2,"name":"Milo"}]}"#
+        } else {
+            br#"{"data":{}}"#
+        };
+        unsafe { sp_response_write(response, body.as_ptr(), body.len()) };
+        200
+    }
+
+    #[test]
+    fn should_report_a_panic_as_an_error() {
+        assert_eq!(-1, guarded(-1, || panic!("oops")));
+        assert_eq!(
+            "the library panicked: oops",
+            unsafe { CStr::from_ptr(sp_last_error()) }.to_str().unwrap()
+        );
+        assert_eq!(0, guarded(-1, || 0));
+    }
+
+    #[test]
+    fn should_drive_client_through_callbacks() {
+        let mut calls: Vec<String> = Vec::new();
+        let user_data = &mut calls as *mut Vec<String> as *mut c_void;
+        let email = CString::new("email@example.com").unwrap();
+        let password = CString::new("qwerty123").unwrap();
+        let device_id = CString::new("xxx").unwrap();
+
+        unsafe {
+            let client = sp_login(
+                fake_send,
+                user_data,
+                email.as_ptr(),
+                password.as_ptr(),
+                device_id.as_ptr(),
+            );
+            assert!(!client.is_null());
+
+            let pets = sp_pets_json(client);
+            let json: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(pets).to_str().unwrap()).unwrap();
+            assert_eq!("Milo", json[0]["name"]);
+            sp_string_free(pets);
+
+            assert_eq!(0, sp_set_lock_mode(client, 13, 3));
+            sp_client_free(client);
+
+            assert!(
+                sp_login(fake_send, user_data, ptr::null(), ptr::null(), ptr::null()).is_null()
+            );
+            assert_eq!(
+                "email is null",
+                CStr::from_ptr(sp_last_error()).to_str().unwrap()
+            );
+        }
+
+        assert_eq!(
+            vec![
+                "POST https://app.api.surehub.io/api/auth/login",
+                "GET https://app.api.surehub.io/api/pet?with%5B%5D=position&with%5B%5D=status&with%5B%5D=photo",
+                "PUT https://app.api.surehub.io/api/device/13/control",
+            ],
+            calls
+        );
+    }
+}
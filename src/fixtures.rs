@@ -0,0 +1,280 @@
+//! Sample API responses, enabled with the `fixtures` feature.
+//!
+//! The bodies describe one made up household, with two cats, a hub, a cat
+//! flap, a feeder, a water dispenser and a pet door, on 1 June 2019. Ids
+//! are consistent between them, so for example the events in
+//! [`TIMELINE`](constant.TIMELINE.html) refer to the pets in [`PETS`](constant.PETS.html).
+//! Downstream tests can serve the raw bodies from a fake transport or use
+//! the parsed models directly.
+//!
+//! ```
+//! use sure_petcare::fixtures;
+//! let state = fixtures::start_state();
+//! assert_eq!("Milo", state.pets()[0].name());
+//! ```
+//!
+//! For tests of code built on the client, [`StubTransport`](struct.StubTransport.html)
+//! answers every endpoint with its fixture:
+//!
+//! ```
+//! use sure_petcare::{client::SurePet, fixtures};
+//! let client = SurePet::new(fixtures::StubTransport::new(), "token");
+//! assert_eq!(2, client.pets().list().unwrap().len());
+//! ```
+//!
+//! The same [`stubs`](fn.stubs.html) can be registered with a mock HTTP
+//! server such as wiremock, by mounting a mock per stub that matches its
+//! method and path and responds with its status and body.
+use crate::client::ResponseEnvelope;
+use crate::device::Device;
+use crate::id::HouseholdId;
+use crate::login;
+use crate::notification::{Notification, Preferences};
+use crate::pet::Pet;
+use crate::report::Report;
+use crate::state::State;
+use crate::timeline::Event;
+use crate::transport::{Method, Request, Response, Transport};
+use serde::Deserialize;
+use std::sync::Mutex;
+
+/// The body of `POST /api/auth/login`.
+pub const LOGIN: &str = include_str!("../fixtures/login.json");
+/// The body of `GET /api/me/start`.
+pub const START: &str = include_str!("../fixtures/start.json");
+/// The body of `GET /api/pet`.
+pub const PETS: &str = include_str!("../fixtures/pets.json");
+/// The body of `GET /api/device`.
+pub const DEVICES: &str = include_str!("../fixtures/devices.json");
+/// The body of `GET /api/timeline/household/1001`, newest first.
+pub const TIMELINE: &str = include_str!("../fixtures/timeline.json");
+/// The body of `GET /api/report/household/1001/pet/3001/aggregate`.
+pub const REPORT: &str = include_str!("../fixtures/report.json");
+/// The body of `GET /api/notification`.
+pub const NOTIFICATIONS: &str = include_str!("../fixtures/notifications.json");
+/// The body of `GET /api/notification/preference`.
+pub const NOTIFICATION_PREFERENCES: &str =
+    include_str!("../fixtures/notification_preferences.json");
+
+/// The household all fixtures belong to.
+pub const HOUSEHOLD_ID: HouseholdId = HouseholdId::new(1001);
+
+/// The login response.
+pub fn login() -> login::Response<'static> {
+    data(LOGIN)
+}
+
+/// The start state.
+pub fn start_state() -> State<'static> {
+    data(START)
+}
+
+/// The pets, Milo inside and Cleo outside.
+pub fn pets() -> Vec<Pet<'static>> {
+    data(PETS)
+}
+
+/// The devices, one of each kind, with the pet door offline.
+pub fn devices() -> Vec<Device<'static>> {
+    data(DEVICES)
+}
+
+/// A day of events, newest first.
+pub fn timeline() -> Vec<Event<'static>> {
+    data(TIMELINE)
+}
+
+/// Milo's report.
+pub fn report() -> Report {
+    data(REPORT)
+}
+
+/// The notifications, newest first.
+pub fn notifications() -> Vec<Notification<'static>> {
+    data(NOTIFICATIONS)
+}
+
+/// The notification preferences, with feeding and drinking switched off.
+pub fn notification_preferences() -> Preferences {
+    data(NOTIFICATION_PREFERENCES)
+}
+
+/// A canned answer to one endpoint.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Stub {
+    /// The request method to match.
+    pub method: Method,
+    /// The path to match, without the host or query string.
+    pub path: String,
+    /// The status code to answer with.
+    pub status: u16,
+    /// The JSON body to answer with.
+    pub body: String,
+}
+
+impl Stub {
+    /// A successful answer with the given body.
+    pub fn new<P: Into<String>, B: Into<String>>(method: Method, path: P, body: B) -> Self {
+        Stub {
+            method,
+            path: path.into(),
+            status: 200,
+            body: body.into(),
+        }
+    }
+
+    /// Changes the status code, e.g. to test error handling.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        let url = request.url.split('?').next().unwrap_or_default();
+        let path = match url.find("://") {
+            Some(scheme) => url[scheme + 3..]
+                .find('/')
+                .map_or("/", |host| &url[scheme + 3 + host..]),
+            None => url,
+        };
+        self.method == request.method && self.path == path
+    }
+}
+
+/// A stub for every read only endpoint, plus login.
+pub fn stubs() -> Vec<Stub> {
+    vec![
+        Stub::new(Method::Post, "/api/auth/login", LOGIN),
+        Stub::new(Method::Get, "/api/me/start", START),
+        Stub::new(Method::Get, "/api/pet", PETS),
+        Stub::new(Method::Get, "/api/device", DEVICES),
+        Stub::new(
+            Method::Get,
+            format!("/api/timeline/household/{}", HOUSEHOLD_ID),
+            TIMELINE,
+        ),
+        Stub::new(
+            Method::Get,
+            format!("/api/report/household/{}/pet/3001/aggregate", HOUSEHOLD_ID),
+            REPORT,
+        ),
+        Stub::new(Method::Get, "/api/notification", NOTIFICATIONS),
+        Stub::new(
+            Method::Get,
+            "/api/notification/preference",
+            NOTIFICATION_PREFERENCES,
+        ),
+    ]
+}
+
+/// A transport answering from stubs, whatever the host, and recording
+/// every request. Requests no stub matches get a `404`.
+#[derive(Debug)]
+pub struct StubTransport {
+    stubs: Vec<Stub>,
+    requests: Mutex<Vec<Request>>,
+}
+
+impl Default for StubTransport {
+    fn default() -> Self {
+        StubTransport {
+            stubs: stubs(),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl StubTransport {
+    /// Create a transport serving every fixture.
+    pub fn new() -> Self {
+        StubTransport::default()
+    }
+
+    /// Adds a stub, which takes precedence over any registered earlier for
+    /// the same method and path.
+    pub fn with_stub(mut self, stub: Stub) -> Self {
+        self.stubs.insert(0, stub);
+        self
+    }
+
+    /// All requests sent so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for StubTransport {
+    fn send(&self, request: &Request) -> crate::error::Result<Response> {
+        self.requests.lock().unwrap().push(request.clone());
+        let response = match self.stubs.iter().find(|stub| stub.matches(request)) {
+            Some(stub) => Response {
+                status: stub.status,
+                headers: vec![("Content-Type".into(), "application/json".into())],
+                body: stub.body.clone().into_bytes(),
+            },
+            None => Response {
+                status: 404,
+                headers: Vec::new(),
+                body: br#"{"error":"no stub"}"#.to_vec(),
+            },
+        };
+        Ok(response)
+    }
+}
+
+fn data<T: Deserialize<'static>>(body: &'static str) -> T {
+    serde_json::from_str::<ResponseEnvelope<T>>(body)
+        .expect("fixtures match the models")
+        .data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::id::{DeviceId, PetId};
+    use crate::notification::Category;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_every_fixture() {
+        assert!(login().access_token().starts_with("eyJ"));
+        assert_eq!(pets().len(), start_state().pets().len());
+        assert_eq!(5, devices().len());
+        assert_eq!(Some(false), devices()[4].status().online());
+        assert_eq!(2, report().feeding().len());
+        assert_eq!(Category::Intruder, notifications()[0].category());
+        assert!(!notification_preferences().enabled(Category::Feeding));
+    }
+
+    #[test]
+    fn should_serve_stubs() {
+        let transport = StubTransport::new().with_stub(
+            Stub::new(Method::Get, "/api/device", r#"{"error":"down"}"#).with_status(503),
+        );
+        let client = SurePet::new(&transport, "token").with_base_url("http://localhost:8080");
+
+        assert_eq!(2, client.start().unwrap().pets().len());
+        assert_eq!(8, client.timeline().household(HOUSEHOLD_ID).unwrap().len());
+        assert!(client.devices().list().is_err());
+        assert!(client.timeline().household(HouseholdId::new(1)).is_err());
+        assert_eq!(
+            "http://localhost:8080/api/timeline/household/1001?page_size=50",
+            transport.requests()[1].url
+        );
+    }
+
+    #[test]
+    fn should_refer_to_known_pets_and_devices() {
+        let pets: Vec<PetId> = pets().iter().map(Pet::id).collect();
+        let devices: Vec<DeviceId> = devices().iter().map(Device::id).collect();
+        for event in timeline() {
+            assert_eq!(Some(HOUSEHOLD_ID), event.household_id());
+            assert!(event.pets().iter().all(|pet| pets.contains(&pet.id())));
+            assert!(event
+                .devices()
+                .iter()
+                .all(|device| devices.contains(&device.id())));
+        }
+    }
+}
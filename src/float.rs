@@ -0,0 +1,36 @@
+//! Hashing for models that hold floats.
+//!
+//! JSON cannot encode NaN, so every float in a deserialized model equals
+//! itself and the models can be `Eq`. They hash floats through
+//! [`HashFloat`](trait.HashFloat.html), which treats both zeros alike so
+//! equal models hash equally.
+use std::hash::{Hash, Hasher};
+
+pub(crate) trait HashFloat {
+    fn hash_float<H: Hasher>(&self, state: &mut H);
+}
+
+impl HashFloat for f64 {
+    fn hash_float<H: Hasher>(&self, state: &mut H) {
+        let bits = if *self == 0.0 { 0 } else { self.to_bits() };
+        bits.hash(state);
+    }
+}
+
+impl HashFloat for Option<f64> {
+    fn hash_float<H: Hasher>(&self, state: &mut H) {
+        self.is_some().hash(state);
+        if let Some(value) = self {
+            value.hash_float(state);
+        }
+    }
+}
+
+impl HashFloat for [f64] {
+    fn hash_float<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for value in self {
+            value.hash_float(state);
+        }
+    }
+}
@@ -0,0 +1,559 @@
+//! Comparing names the way people type them.
+//!
+//! The same name can arrive in different forms: `Zoé` typed on a phone is
+//! usually a single precomposed `é`, while other keyboards send an `e`
+//! followed by a combining accent. A [`NameMatch`](enum.NameMatch.html)
+//! compares names once both are in Unicode normalisation form C (NFC), and
+//! can also ignore case, or case and accents, so `zoe` finds Zoé.
+//!
+//! The tables cover the accented Latin, Greek and Cyrillic letters and the
+//! Hangul syllables names are written with; text outside them is compared
+//! as it is.
+
+/// How two names are compared.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::fold::NameMatch;
+/// let typed = "Zoe\u{301}";
+/// assert!(NameMatch::Exact.matches(typed, "Zoé"));
+/// assert!(!NameMatch::Exact.matches("zoé", "Zoé"));
+/// assert!(NameMatch::IgnoreCase.matches(" ZOÉ", "Zoé"));
+/// assert!(NameMatch::IgnoreAccents.matches("zoe", "Zoë"));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum NameMatch {
+    /// The names are the same once normalised.
+    Exact,
+    /// Case and surrounding whitespace are ignored as well. Lookups such as
+    /// [`State::pet_by_name`](../state/struct.State.html#method.pet_by_name)
+    /// compare this way.
+    #[default]
+    IgnoreCase,
+    /// Accents are ignored too, and letters such as `ø` and `ß` match their
+    /// plain spellings `o` and `ss`.
+    IgnoreAccents,
+}
+
+impl NameMatch {
+    /// The form of a name that is compared, so a list of names can be
+    /// keyed once rather than normalised on every comparison.
+    pub fn key(self, name: &str) -> String {
+        match self {
+            NameMatch::Exact => nfc(name),
+            NameMatch::IgnoreCase => nfc(name.trim()).to_lowercase(),
+            NameMatch::IgnoreAccents => strip_accents(name.trim()).to_lowercase(),
+        }
+    }
+
+    /// Whether two names are the same under this comparison.
+    pub fn matches(self, a: &str, b: &str) -> bool {
+        self.key(a) == self.key(b)
+    }
+}
+
+/// Brings text into normalisation form C, composing letters and the accents
+/// that follow them wherever a precomposed letter exists.
+pub fn nfc(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    compose(&decompose(text))
+}
+
+/// The text with its accents removed and letters without a decomposition,
+/// such as `ø`, spelled out plainly.
+fn strip_accents(text: &str) -> String {
+    let mut letters = decompose(text);
+    letters.retain(|&c| combining_class(c) == 0);
+    let mut plain = String::with_capacity(text.len());
+    for c in compose(&letters).chars() {
+        match FOLDED.iter().find(|(letter, _)| *letter == c) {
+            Some((_, spelled)) => plain.push_str(spelled),
+            None => plain.push(c),
+        }
+    }
+    plain
+}
+
+/// The canonical decomposition of some text, with the accents on each
+/// letter in canonical order.
+fn decompose(text: &str) -> Vec<char> {
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        decompose_into(c, &mut chars);
+    }
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|&c| combining_class(c) == 0)
+            .map_or(chars.len(), |n| start + n);
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+    chars
+}
+
+fn decompose_into(c: char, chars: &mut Vec<char>) {
+    if c.is_ascii() {
+        chars.push(c);
+    } else if let Some(index) = (c as u32)
+        .checked_sub(HANGUL_BASE)
+        .filter(|&i| i < HANGUL_COUNT)
+    {
+        chars.push(char_at(HANGUL_L + index / HANGUL_N));
+        chars.push(char_at(HANGUL_V + index % HANGUL_N / HANGUL_T_COUNT));
+        if index % HANGUL_T_COUNT != 0 {
+            chars.push(char_at(HANGUL_T + index % HANGUL_T_COUNT));
+        }
+    } else if let Some((_, replacement)) = SINGLETONS.iter().find(|(from, _)| *from == c) {
+        replacement.chars().for_each(|c| decompose_into(c, chars));
+    } else if let Some(&(base, mark, _)) =
+        COMPOSITIONS.iter().find(|(_, _, composed)| *composed == c)
+    {
+        decompose_into(base, chars);
+        chars.push(mark);
+    } else {
+        chars.push(c);
+    }
+}
+
+/// Canonical composition of decomposed text: each accent joins the letter
+/// before it unless another accent of the same class comes between.
+fn compose(chars: &[char]) -> String {
+    let mut composed: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter = None;
+    let mut last_class = 0;
+    for &c in chars {
+        let class = combining_class(c);
+        if let Some(index) = starter {
+            let adjacent = index + 1 == composed.len();
+            if adjacent || (last_class != 0 && last_class < class) {
+                if let Some(pair) = compose_pair(composed[index], c) {
+                    composed[index] = pair;
+                    continue;
+                }
+            }
+        }
+        if class == 0 {
+            starter = Some(composed.len());
+        }
+        last_class = class;
+        composed.push(c);
+    }
+    composed.into_iter().collect()
+}
+
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (first, second) = (first as u32, second as u32);
+    if (HANGUL_L..HANGUL_L + 19).contains(&first) && (HANGUL_V..HANGUL_V + 21).contains(&second) {
+        let index = (first - HANGUL_L) * HANGUL_N + (second - HANGUL_V) * HANGUL_T_COUNT;
+        return Some(char_at(HANGUL_BASE + index));
+    }
+    let syllable = first.wrapping_sub(HANGUL_BASE);
+    if syllable < HANGUL_COUNT
+        && syllable % HANGUL_T_COUNT == 0
+        && (HANGUL_T + 1..HANGUL_T + HANGUL_T_COUNT).contains(&second)
+    {
+        return Some(char_at(first + second - HANGUL_T));
+    }
+    let key = (char::from_u32(first)?, char::from_u32(second)?);
+    COMPOSITIONS
+        .binary_search_by(|&(base, mark, _)| (base, mark).cmp(&key))
+        .ok()
+        .map(|index| COMPOSITIONS[index].2)
+}
+
+/// The canonical combining class of an accent, zero for letters. Combining
+/// diacritical marks not listed are in class 230, above the letter.
+fn combining_class(c: char) -> u8 {
+    match c {
+        '\u{300}'..='\u{36F}' => COMBINING_CLASSES
+            .iter()
+            .find(|(mark, _)| *mark == c)
+            .map_or(230, |&(_, class)| class),
+        _ => 0,
+    }
+}
+
+fn char_at(code: u32) -> char {
+    char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+const HANGUL_BASE: u32 = 0xAC00;
+const HANGUL_L: u32 = 0x1100;
+const HANGUL_V: u32 = 0x1161;
+const HANGUL_T: u32 = 0x11A7;
+const HANGUL_T_COUNT: u32 = 28;
+const HANGUL_N: u32 = 21 * HANGUL_T_COUNT;
+const HANGUL_COUNT: u32 = 19 * HANGUL_N;
+
+/// Letters that are not a letter and an accent, with their plain spelling.
+const FOLDED: &[(char, &str)] = &[
+    ('Æ', "AE"),
+    ('æ', "ae"),
+    ('Ð', "D"),
+    ('ð', "d"),
+    ('Đ', "D"),
+    ('đ', "d"),
+    ('Ħ', "H"),
+    ('ħ', "h"),
+    ('ı', "i"),
+    ('Ł', "L"),
+    ('ł', "l"),
+    ('Ø', "O"),
+    ('ø', "o"),
+    ('Œ', "OE"),
+    ('œ', "oe"),
+    ('ß', "ss"),
+    ('Þ', "Th"),
+    ('þ', "th"),
+];
+
+/// Characters that decompose but are never composed again.
+const SINGLETONS: &[(char, &str)] = &[
+    ('\u{340}', "\u{300}"),
+    ('\u{341}', "\u{301}"),
+    ('\u{343}', "\u{313}"),
+    ('\u{344}', "\u{308}\u{301}"),
+    ('\u{374}', "\u{2B9}"),
+    ('\u{37E}', ";"),
+    ('\u{387}', "\u{B7}"),
+];
+
+/// Combining diacritical marks outside class 230.
+#[rustfmt::skip]
+const COMBINING_CLASSES: &[(char, u8)] = &[
+    ('\u{315}', 232), ('\u{316}', 220), ('\u{317}', 220), ('\u{318}', 220), ('\u{319}', 220),
+    ('\u{31A}', 232), ('\u{31B}', 216), ('\u{31C}', 220), ('\u{31D}', 220), ('\u{31E}', 220),
+    ('\u{31F}', 220), ('\u{320}', 220), ('\u{321}', 202), ('\u{322}', 202), ('\u{323}', 220),
+    ('\u{324}', 220), ('\u{325}', 220), ('\u{326}', 220), ('\u{327}', 202), ('\u{328}', 202),
+    ('\u{329}', 220), ('\u{32A}', 220), ('\u{32B}', 220), ('\u{32C}', 220), ('\u{32D}', 220),
+    ('\u{32E}', 220), ('\u{32F}', 220), ('\u{330}', 220), ('\u{331}', 220), ('\u{332}', 220),
+    ('\u{333}', 220), ('\u{334}', 1), ('\u{335}', 1), ('\u{336}', 1), ('\u{337}', 1),
+    ('\u{338}', 1), ('\u{339}', 220), ('\u{33A}', 220), ('\u{33B}', 220), ('\u{33C}', 220),
+    ('\u{345}', 240), ('\u{347}', 220), ('\u{348}', 220), ('\u{349}', 220), ('\u{34D}', 220),
+    ('\u{34E}', 220), ('\u{34F}', 0), ('\u{353}', 220), ('\u{354}', 220), ('\u{355}', 220),
+    ('\u{356}', 220), ('\u{358}', 232), ('\u{359}', 220), ('\u{35A}', 220), ('\u{35C}', 233),
+    ('\u{35D}', 234), ('\u{35E}', 234), ('\u{35F}', 233), ('\u{360}', 234), ('\u{361}', 234),
+    ('\u{362}', 233),
+];
+
+/// Letter, accent and the precomposed letter they make, sorted by letter
+/// and accent. Generated from the Unicode 14 character database for the
+/// Latin-1, Latin Extended, Greek, Cyrillic and Latin Extended Additional
+/// blocks, leaving out compositions NFC excludes.
+#[rustfmt::skip]
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('\u{41}', '\u{300}', '\u{C0}'), ('\u{41}', '\u{301}', '\u{C1}'),
+    ('\u{41}', '\u{302}', '\u{C2}'), ('\u{41}', '\u{303}', '\u{C3}'),
+    ('\u{41}', '\u{304}', '\u{100}'), ('\u{41}', '\u{306}', '\u{102}'),
+    ('\u{41}', '\u{307}', '\u{226}'), ('\u{41}', '\u{308}', '\u{C4}'),
+    ('\u{41}', '\u{309}', '\u{1EA2}'), ('\u{41}', '\u{30A}', '\u{C5}'),
+    ('\u{41}', '\u{30C}', '\u{1CD}'), ('\u{41}', '\u{30F}', '\u{200}'),
+    ('\u{41}', '\u{311}', '\u{202}'), ('\u{41}', '\u{323}', '\u{1EA0}'),
+    ('\u{41}', '\u{325}', '\u{1E00}'), ('\u{41}', '\u{328}', '\u{104}'),
+    ('\u{42}', '\u{307}', '\u{1E02}'), ('\u{42}', '\u{323}', '\u{1E04}'),
+    ('\u{42}', '\u{331}', '\u{1E06}'), ('\u{43}', '\u{301}', '\u{106}'),
+    ('\u{43}', '\u{302}', '\u{108}'), ('\u{43}', '\u{307}', '\u{10A}'),
+    ('\u{43}', '\u{30C}', '\u{10C}'), ('\u{43}', '\u{327}', '\u{C7}'),
+    ('\u{44}', '\u{307}', '\u{1E0A}'), ('\u{44}', '\u{30C}', '\u{10E}'),
+    ('\u{44}', '\u{323}', '\u{1E0C}'), ('\u{44}', '\u{327}', '\u{1E10}'),
+    ('\u{44}', '\u{32D}', '\u{1E12}'), ('\u{44}', '\u{331}', '\u{1E0E}'),
+    ('\u{45}', '\u{300}', '\u{C8}'), ('\u{45}', '\u{301}', '\u{C9}'),
+    ('\u{45}', '\u{302}', '\u{CA}'), ('\u{45}', '\u{303}', '\u{1EBC}'),
+    ('\u{45}', '\u{304}', '\u{112}'), ('\u{45}', '\u{306}', '\u{114}'),
+    ('\u{45}', '\u{307}', '\u{116}'), ('\u{45}', '\u{308}', '\u{CB}'),
+    ('\u{45}', '\u{309}', '\u{1EBA}'), ('\u{45}', '\u{30C}', '\u{11A}'),
+    ('\u{45}', '\u{30F}', '\u{204}'), ('\u{45}', '\u{311}', '\u{206}'),
+    ('\u{45}', '\u{323}', '\u{1EB8}'), ('\u{45}', '\u{327}', '\u{228}'),
+    ('\u{45}', '\u{328}', '\u{118}'), ('\u{45}', '\u{32D}', '\u{1E18}'),
+    ('\u{45}', '\u{330}', '\u{1E1A}'), ('\u{46}', '\u{307}', '\u{1E1E}'),
+    ('\u{47}', '\u{301}', '\u{1F4}'), ('\u{47}', '\u{302}', '\u{11C}'),
+    ('\u{47}', '\u{304}', '\u{1E20}'), ('\u{47}', '\u{306}', '\u{11E}'),
+    ('\u{47}', '\u{307}', '\u{120}'), ('\u{47}', '\u{30C}', '\u{1E6}'),
+    ('\u{47}', '\u{327}', '\u{122}'), ('\u{48}', '\u{302}', '\u{124}'),
+    ('\u{48}', '\u{307}', '\u{1E22}'), ('\u{48}', '\u{308}', '\u{1E26}'),
+    ('\u{48}', '\u{30C}', '\u{21E}'), ('\u{48}', '\u{323}', '\u{1E24}'),
+    ('\u{48}', '\u{327}', '\u{1E28}'), ('\u{48}', '\u{32E}', '\u{1E2A}'),
+    ('\u{49}', '\u{300}', '\u{CC}'), ('\u{49}', '\u{301}', '\u{CD}'),
+    ('\u{49}', '\u{302}', '\u{CE}'), ('\u{49}', '\u{303}', '\u{128}'),
+    ('\u{49}', '\u{304}', '\u{12A}'), ('\u{49}', '\u{306}', '\u{12C}'),
+    ('\u{49}', '\u{307}', '\u{130}'), ('\u{49}', '\u{308}', '\u{CF}'),
+    ('\u{49}', '\u{309}', '\u{1EC8}'), ('\u{49}', '\u{30C}', '\u{1CF}'),
+    ('\u{49}', '\u{30F}', '\u{208}'), ('\u{49}', '\u{311}', '\u{20A}'),
+    ('\u{49}', '\u{323}', '\u{1ECA}'), ('\u{49}', '\u{328}', '\u{12E}'),
+    ('\u{49}', '\u{330}', '\u{1E2C}'), ('\u{4A}', '\u{302}', '\u{134}'),
+    ('\u{4B}', '\u{301}', '\u{1E30}'), ('\u{4B}', '\u{30C}', '\u{1E8}'),
+    ('\u{4B}', '\u{323}', '\u{1E32}'), ('\u{4B}', '\u{327}', '\u{136}'),
+    ('\u{4B}', '\u{331}', '\u{1E34}'), ('\u{4C}', '\u{301}', '\u{139}'),
+    ('\u{4C}', '\u{30C}', '\u{13D}'), ('\u{4C}', '\u{323}', '\u{1E36}'),
+    ('\u{4C}', '\u{327}', '\u{13B}'), ('\u{4C}', '\u{32D}', '\u{1E3C}'),
+    ('\u{4C}', '\u{331}', '\u{1E3A}'), ('\u{4D}', '\u{301}', '\u{1E3E}'),
+    ('\u{4D}', '\u{307}', '\u{1E40}'), ('\u{4D}', '\u{323}', '\u{1E42}'),
+    ('\u{4E}', '\u{300}', '\u{1F8}'), ('\u{4E}', '\u{301}', '\u{143}'),
+    ('\u{4E}', '\u{303}', '\u{D1}'), ('\u{4E}', '\u{307}', '\u{1E44}'),
+    ('\u{4E}', '\u{30C}', '\u{147}'), ('\u{4E}', '\u{323}', '\u{1E46}'),
+    ('\u{4E}', '\u{327}', '\u{145}'), ('\u{4E}', '\u{32D}', '\u{1E4A}'),
+    ('\u{4E}', '\u{331}', '\u{1E48}'), ('\u{4F}', '\u{300}', '\u{D2}'),
+    ('\u{4F}', '\u{301}', '\u{D3}'), ('\u{4F}', '\u{302}', '\u{D4}'),
+    ('\u{4F}', '\u{303}', '\u{D5}'), ('\u{4F}', '\u{304}', '\u{14C}'),
+    ('\u{4F}', '\u{306}', '\u{14E}'), ('\u{4F}', '\u{307}', '\u{22E}'),
+    ('\u{4F}', '\u{308}', '\u{D6}'), ('\u{4F}', '\u{309}', '\u{1ECE}'),
+    ('\u{4F}', '\u{30B}', '\u{150}'), ('\u{4F}', '\u{30C}', '\u{1D1}'),
+    ('\u{4F}', '\u{30F}', '\u{20C}'), ('\u{4F}', '\u{311}', '\u{20E}'),
+    ('\u{4F}', '\u{31B}', '\u{1A0}'), ('\u{4F}', '\u{323}', '\u{1ECC}'),
+    ('\u{4F}', '\u{328}', '\u{1EA}'), ('\u{50}', '\u{301}', '\u{1E54}'),
+    ('\u{50}', '\u{307}', '\u{1E56}'), ('\u{52}', '\u{301}', '\u{154}'),
+    ('\u{52}', '\u{307}', '\u{1E58}'), ('\u{52}', '\u{30C}', '\u{158}'),
+    ('\u{52}', '\u{30F}', '\u{210}'), ('\u{52}', '\u{311}', '\u{212}'),
+    ('\u{52}', '\u{323}', '\u{1E5A}'), ('\u{52}', '\u{327}', '\u{156}'),
+    ('\u{52}', '\u{331}', '\u{1E5E}'), ('\u{53}', '\u{301}', '\u{15A}'),
+    ('\u{53}', '\u{302}', '\u{15C}'), ('\u{53}', '\u{307}', '\u{1E60}'),
+    ('\u{53}', '\u{30C}', '\u{160}'), ('\u{53}', '\u{323}', '\u{1E62}'),
+    ('\u{53}', '\u{326}', '\u{218}'), ('\u{53}', '\u{327}', '\u{15E}'),
+    ('\u{54}', '\u{307}', '\u{1E6A}'), ('\u{54}', '\u{30C}', '\u{164}'),
+    ('\u{54}', '\u{323}', '\u{1E6C}'), ('\u{54}', '\u{326}', '\u{21A}'),
+    ('\u{54}', '\u{327}', '\u{162}'), ('\u{54}', '\u{32D}', '\u{1E70}'),
+    ('\u{54}', '\u{331}', '\u{1E6E}'), ('\u{55}', '\u{300}', '\u{D9}'),
+    ('\u{55}', '\u{301}', '\u{DA}'), ('\u{55}', '\u{302}', '\u{DB}'),
+    ('\u{55}', '\u{303}', '\u{168}'), ('\u{55}', '\u{304}', '\u{16A}'),
+    ('\u{55}', '\u{306}', '\u{16C}'), ('\u{55}', '\u{308}', '\u{DC}'),
+    ('\u{55}', '\u{309}', '\u{1EE6}'), ('\u{55}', '\u{30A}', '\u{16E}'),
+    ('\u{55}', '\u{30B}', '\u{170}'), ('\u{55}', '\u{30C}', '\u{1D3}'),
+    ('\u{55}', '\u{30F}', '\u{214}'), ('\u{55}', '\u{311}', '\u{216}'),
+    ('\u{55}', '\u{31B}', '\u{1AF}'), ('\u{55}', '\u{323}', '\u{1EE4}'),
+    ('\u{55}', '\u{324}', '\u{1E72}'), ('\u{55}', '\u{328}', '\u{172}'),
+    ('\u{55}', '\u{32D}', '\u{1E76}'), ('\u{55}', '\u{330}', '\u{1E74}'),
+    ('\u{56}', '\u{303}', '\u{1E7C}'), ('\u{56}', '\u{323}', '\u{1E7E}'),
+    ('\u{57}', '\u{300}', '\u{1E80}'), ('\u{57}', '\u{301}', '\u{1E82}'),
+    ('\u{57}', '\u{302}', '\u{174}'), ('\u{57}', '\u{307}', '\u{1E86}'),
+    ('\u{57}', '\u{308}', '\u{1E84}'), ('\u{57}', '\u{323}', '\u{1E88}'),
+    ('\u{58}', '\u{307}', '\u{1E8A}'), ('\u{58}', '\u{308}', '\u{1E8C}'),
+    ('\u{59}', '\u{300}', '\u{1EF2}'), ('\u{59}', '\u{301}', '\u{DD}'),
+    ('\u{59}', '\u{302}', '\u{176}'), ('\u{59}', '\u{303}', '\u{1EF8}'),
+    ('\u{59}', '\u{304}', '\u{232}'), ('\u{59}', '\u{307}', '\u{1E8E}'),
+    ('\u{59}', '\u{308}', '\u{178}'), ('\u{59}', '\u{309}', '\u{1EF6}'),
+    ('\u{59}', '\u{323}', '\u{1EF4}'), ('\u{5A}', '\u{301}', '\u{179}'),
+    ('\u{5A}', '\u{302}', '\u{1E90}'), ('\u{5A}', '\u{307}', '\u{17B}'),
+    ('\u{5A}', '\u{30C}', '\u{17D}'), ('\u{5A}', '\u{323}', '\u{1E92}'),
+    ('\u{5A}', '\u{331}', '\u{1E94}'), ('\u{61}', '\u{300}', '\u{E0}'),
+    ('\u{61}', '\u{301}', '\u{E1}'), ('\u{61}', '\u{302}', '\u{E2}'),
+    ('\u{61}', '\u{303}', '\u{E3}'), ('\u{61}', '\u{304}', '\u{101}'),
+    ('\u{61}', '\u{306}', '\u{103}'), ('\u{61}', '\u{307}', '\u{227}'),
+    ('\u{61}', '\u{308}', '\u{E4}'), ('\u{61}', '\u{309}', '\u{1EA3}'),
+    ('\u{61}', '\u{30A}', '\u{E5}'), ('\u{61}', '\u{30C}', '\u{1CE}'),
+    ('\u{61}', '\u{30F}', '\u{201}'), ('\u{61}', '\u{311}', '\u{203}'),
+    ('\u{61}', '\u{323}', '\u{1EA1}'), ('\u{61}', '\u{325}', '\u{1E01}'),
+    ('\u{61}', '\u{328}', '\u{105}'), ('\u{62}', '\u{307}', '\u{1E03}'),
+    ('\u{62}', '\u{323}', '\u{1E05}'), ('\u{62}', '\u{331}', '\u{1E07}'),
+    ('\u{63}', '\u{301}', '\u{107}'), ('\u{63}', '\u{302}', '\u{109}'),
+    ('\u{63}', '\u{307}', '\u{10B}'), ('\u{63}', '\u{30C}', '\u{10D}'),
+    ('\u{63}', '\u{327}', '\u{E7}'), ('\u{64}', '\u{307}', '\u{1E0B}'),
+    ('\u{64}', '\u{30C}', '\u{10F}'), ('\u{64}', '\u{323}', '\u{1E0D}'),
+    ('\u{64}', '\u{327}', '\u{1E11}'), ('\u{64}', '\u{32D}', '\u{1E13}'),
+    ('\u{64}', '\u{331}', '\u{1E0F}'), ('\u{65}', '\u{300}', '\u{E8}'),
+    ('\u{65}', '\u{301}', '\u{E9}'), ('\u{65}', '\u{302}', '\u{EA}'),
+    ('\u{65}', '\u{303}', '\u{1EBD}'), ('\u{65}', '\u{304}', '\u{113}'),
+    ('\u{65}', '\u{306}', '\u{115}'), ('\u{65}', '\u{307}', '\u{117}'),
+    ('\u{65}', '\u{308}', '\u{EB}'), ('\u{65}', '\u{309}', '\u{1EBB}'),
+    ('\u{65}', '\u{30C}', '\u{11B}'), ('\u{65}', '\u{30F}', '\u{205}'),
+    ('\u{65}', '\u{311}', '\u{207}'), ('\u{65}', '\u{323}', '\u{1EB9}'),
+    ('\u{65}', '\u{327}', '\u{229}'), ('\u{65}', '\u{328}', '\u{119}'),
+    ('\u{65}', '\u{32D}', '\u{1E19}'), ('\u{65}', '\u{330}', '\u{1E1B}'),
+    ('\u{66}', '\u{307}', '\u{1E1F}'), ('\u{67}', '\u{301}', '\u{1F5}'),
+    ('\u{67}', '\u{302}', '\u{11D}'), ('\u{67}', '\u{304}', '\u{1E21}'),
+    ('\u{67}', '\u{306}', '\u{11F}'), ('\u{67}', '\u{307}', '\u{121}'),
+    ('\u{67}', '\u{30C}', '\u{1E7}'), ('\u{67}', '\u{327}', '\u{123}'),
+    ('\u{68}', '\u{302}', '\u{125}'), ('\u{68}', '\u{307}', '\u{1E23}'),
+    ('\u{68}', '\u{308}', '\u{1E27}'), ('\u{68}', '\u{30C}', '\u{21F}'),
+    ('\u{68}', '\u{323}', '\u{1E25}'), ('\u{68}', '\u{327}', '\u{1E29}'),
+    ('\u{68}', '\u{32E}', '\u{1E2B}'), ('\u{68}', '\u{331}', '\u{1E96}'),
+    ('\u{69}', '\u{300}', '\u{EC}'), ('\u{69}', '\u{301}', '\u{ED}'),
+    ('\u{69}', '\u{302}', '\u{EE}'), ('\u{69}', '\u{303}', '\u{129}'),
+    ('\u{69}', '\u{304}', '\u{12B}'), ('\u{69}', '\u{306}', '\u{12D}'),
+    ('\u{69}', '\u{308}', '\u{EF}'), ('\u{69}', '\u{309}', '\u{1EC9}'),
+    ('\u{69}', '\u{30C}', '\u{1D0}'), ('\u{69}', '\u{30F}', '\u{209}'),
+    ('\u{69}', '\u{311}', '\u{20B}'), ('\u{69}', '\u{323}', '\u{1ECB}'),
+    ('\u{69}', '\u{328}', '\u{12F}'), ('\u{69}', '\u{330}', '\u{1E2D}'),
+    ('\u{6A}', '\u{302}', '\u{135}'), ('\u{6A}', '\u{30C}', '\u{1F0}'),
+    ('\u{6B}', '\u{301}', '\u{1E31}'), ('\u{6B}', '\u{30C}', '\u{1E9}'),
+    ('\u{6B}', '\u{323}', '\u{1E33}'), ('\u{6B}', '\u{327}', '\u{137}'),
+    ('\u{6B}', '\u{331}', '\u{1E35}'), ('\u{6C}', '\u{301}', '\u{13A}'),
+    ('\u{6C}', '\u{30C}', '\u{13E}'), ('\u{6C}', '\u{323}', '\u{1E37}'),
+    ('\u{6C}', '\u{327}', '\u{13C}'), ('\u{6C}', '\u{32D}', '\u{1E3D}'),
+    ('\u{6C}', '\u{331}', '\u{1E3B}'), ('\u{6D}', '\u{301}', '\u{1E3F}'),
+    ('\u{6D}', '\u{307}', '\u{1E41}'), ('\u{6D}', '\u{323}', '\u{1E43}'),
+    ('\u{6E}', '\u{300}', '\u{1F9}'), ('\u{6E}', '\u{301}', '\u{144}'),
+    ('\u{6E}', '\u{303}', '\u{F1}'), ('\u{6E}', '\u{307}', '\u{1E45}'),
+    ('\u{6E}', '\u{30C}', '\u{148}'), ('\u{6E}', '\u{323}', '\u{1E47}'),
+    ('\u{6E}', '\u{327}', '\u{146}'), ('\u{6E}', '\u{32D}', '\u{1E4B}'),
+    ('\u{6E}', '\u{331}', '\u{1E49}'), ('\u{6F}', '\u{300}', '\u{F2}'),
+    ('\u{6F}', '\u{301}', '\u{F3}'), ('\u{6F}', '\u{302}', '\u{F4}'),
+    ('\u{6F}', '\u{303}', '\u{F5}'), ('\u{6F}', '\u{304}', '\u{14D}'),
+    ('\u{6F}', '\u{306}', '\u{14F}'), ('\u{6F}', '\u{307}', '\u{22F}'),
+    ('\u{6F}', '\u{308}', '\u{F6}'), ('\u{6F}', '\u{309}', '\u{1ECF}'),
+    ('\u{6F}', '\u{30B}', '\u{151}'), ('\u{6F}', '\u{30C}', '\u{1D2}'),
+    ('\u{6F}', '\u{30F}', '\u{20D}'), ('\u{6F}', '\u{311}', '\u{20F}'),
+    ('\u{6F}', '\u{31B}', '\u{1A1}'), ('\u{6F}', '\u{323}', '\u{1ECD}'),
+    ('\u{6F}', '\u{328}', '\u{1EB}'), ('\u{70}', '\u{301}', '\u{1E55}'),
+    ('\u{70}', '\u{307}', '\u{1E57}'), ('\u{72}', '\u{301}', '\u{155}'),
+    ('\u{72}', '\u{307}', '\u{1E59}'), ('\u{72}', '\u{30C}', '\u{159}'),
+    ('\u{72}', '\u{30F}', '\u{211}'), ('\u{72}', '\u{311}', '\u{213}'),
+    ('\u{72}', '\u{323}', '\u{1E5B}'), ('\u{72}', '\u{327}', '\u{157}'),
+    ('\u{72}', '\u{331}', '\u{1E5F}'), ('\u{73}', '\u{301}', '\u{15B}'),
+    ('\u{73}', '\u{302}', '\u{15D}'), ('\u{73}', '\u{307}', '\u{1E61}'),
+    ('\u{73}', '\u{30C}', '\u{161}'), ('\u{73}', '\u{323}', '\u{1E63}'),
+    ('\u{73}', '\u{326}', '\u{219}'), ('\u{73}', '\u{327}', '\u{15F}'),
+    ('\u{74}', '\u{307}', '\u{1E6B}'), ('\u{74}', '\u{308}', '\u{1E97}'),
+    ('\u{74}', '\u{30C}', '\u{165}'), ('\u{74}', '\u{323}', '\u{1E6D}'),
+    ('\u{74}', '\u{326}', '\u{21B}'), ('\u{74}', '\u{327}', '\u{163}'),
+    ('\u{74}', '\u{32D}', '\u{1E71}'), ('\u{74}', '\u{331}', '\u{1E6F}'),
+    ('\u{75}', '\u{300}', '\u{F9}'), ('\u{75}', '\u{301}', '\u{FA}'),
+    ('\u{75}', '\u{302}', '\u{FB}'), ('\u{75}', '\u{303}', '\u{169}'),
+    ('\u{75}', '\u{304}', '\u{16B}'), ('\u{75}', '\u{306}', '\u{16D}'),
+    ('\u{75}', '\u{308}', '\u{FC}'), ('\u{75}', '\u{309}', '\u{1EE7}'),
+    ('\u{75}', '\u{30A}', '\u{16F}'), ('\u{75}', '\u{30B}', '\u{171}'),
+    ('\u{75}', '\u{30C}', '\u{1D4}'), ('\u{75}', '\u{30F}', '\u{215}'),
+    ('\u{75}', '\u{311}', '\u{217}'), ('\u{75}', '\u{31B}', '\u{1B0}'),
+    ('\u{75}', '\u{323}', '\u{1EE5}'), ('\u{75}', '\u{324}', '\u{1E73}'),
+    ('\u{75}', '\u{328}', '\u{173}'), ('\u{75}', '\u{32D}', '\u{1E77}'),
+    ('\u{75}', '\u{330}', '\u{1E75}'), ('\u{76}', '\u{303}', '\u{1E7D}'),
+    ('\u{76}', '\u{323}', '\u{1E7F}'), ('\u{77}', '\u{300}', '\u{1E81}'),
+    ('\u{77}', '\u{301}', '\u{1E83}'), ('\u{77}', '\u{302}', '\u{175}'),
+    ('\u{77}', '\u{307}', '\u{1E87}'), ('\u{77}', '\u{308}', '\u{1E85}'),
+    ('\u{77}', '\u{30A}', '\u{1E98}'), ('\u{77}', '\u{323}', '\u{1E89}'),
+    ('\u{78}', '\u{307}', '\u{1E8B}'), ('\u{78}', '\u{308}', '\u{1E8D}'),
+    ('\u{79}', '\u{300}', '\u{1EF3}'), ('\u{79}', '\u{301}', '\u{FD}'),
+    ('\u{79}', '\u{302}', '\u{177}'), ('\u{79}', '\u{303}', '\u{1EF9}'),
+    ('\u{79}', '\u{304}', '\u{233}'), ('\u{79}', '\u{307}', '\u{1E8F}'),
+    ('\u{79}', '\u{308}', '\u{FF}'), ('\u{79}', '\u{309}', '\u{1EF7}'),
+    ('\u{79}', '\u{30A}', '\u{1E99}'), ('\u{79}', '\u{323}', '\u{1EF5}'),
+    ('\u{7A}', '\u{301}', '\u{17A}'), ('\u{7A}', '\u{302}', '\u{1E91}'),
+    ('\u{7A}', '\u{307}', '\u{17C}'), ('\u{7A}', '\u{30C}', '\u{17E}'),
+    ('\u{7A}', '\u{323}', '\u{1E93}'), ('\u{7A}', '\u{331}', '\u{1E95}'),
+    ('\u{A8}', '\u{301}', '\u{385}'), ('\u{C2}', '\u{300}', '\u{1EA6}'),
+    ('\u{C2}', '\u{301}', '\u{1EA4}'), ('\u{C2}', '\u{303}', '\u{1EAA}'),
+    ('\u{C2}', '\u{309}', '\u{1EA8}'), ('\u{C4}', '\u{304}', '\u{1DE}'),
+    ('\u{C5}', '\u{301}', '\u{1FA}'), ('\u{C6}', '\u{301}', '\u{1FC}'),
+    ('\u{C6}', '\u{304}', '\u{1E2}'), ('\u{C7}', '\u{301}', '\u{1E08}'),
+    ('\u{CA}', '\u{300}', '\u{1EC0}'), ('\u{CA}', '\u{301}', '\u{1EBE}'),
+    ('\u{CA}', '\u{303}', '\u{1EC4}'), ('\u{CA}', '\u{309}', '\u{1EC2}'),
+    ('\u{CF}', '\u{301}', '\u{1E2E}'), ('\u{D4}', '\u{300}', '\u{1ED2}'),
+    ('\u{D4}', '\u{301}', '\u{1ED0}'), ('\u{D4}', '\u{303}', '\u{1ED6}'),
+    ('\u{D4}', '\u{309}', '\u{1ED4}'), ('\u{D5}', '\u{301}', '\u{1E4C}'),
+    ('\u{D5}', '\u{304}', '\u{22C}'), ('\u{D5}', '\u{308}', '\u{1E4E}'),
+    ('\u{D6}', '\u{304}', '\u{22A}'), ('\u{D8}', '\u{301}', '\u{1FE}'),
+    ('\u{DC}', '\u{300}', '\u{1DB}'), ('\u{DC}', '\u{301}', '\u{1D7}'),
+    ('\u{DC}', '\u{304}', '\u{1D5}'), ('\u{DC}', '\u{30C}', '\u{1D9}'),
+    ('\u{E2}', '\u{300}', '\u{1EA7}'), ('\u{E2}', '\u{301}', '\u{1EA5}'),
+    ('\u{E2}', '\u{303}', '\u{1EAB}'), ('\u{E2}', '\u{309}', '\u{1EA9}'),
+    ('\u{E4}', '\u{304}', '\u{1DF}'), ('\u{E5}', '\u{301}', '\u{1FB}'),
+    ('\u{E6}', '\u{301}', '\u{1FD}'), ('\u{E6}', '\u{304}', '\u{1E3}'),
+    ('\u{E7}', '\u{301}', '\u{1E09}'), ('\u{EA}', '\u{300}', '\u{1EC1}'),
+    ('\u{EA}', '\u{301}', '\u{1EBF}'), ('\u{EA}', '\u{303}', '\u{1EC5}'),
+    ('\u{EA}', '\u{309}', '\u{1EC3}'), ('\u{EF}', '\u{301}', '\u{1E2F}'),
+    ('\u{F4}', '\u{300}', '\u{1ED3}'), ('\u{F4}', '\u{301}', '\u{1ED1}'),
+    ('\u{F4}', '\u{303}', '\u{1ED7}'), ('\u{F4}', '\u{309}', '\u{1ED5}'),
+    ('\u{F5}', '\u{301}', '\u{1E4D}'), ('\u{F5}', '\u{304}', '\u{22D}'),
+    ('\u{F5}', '\u{308}', '\u{1E4F}'), ('\u{F6}', '\u{304}', '\u{22B}'),
+    ('\u{F8}', '\u{301}', '\u{1FF}'), ('\u{FC}', '\u{300}', '\u{1DC}'),
+    ('\u{FC}', '\u{301}', '\u{1D8}'), ('\u{FC}', '\u{304}', '\u{1D6}'),
+    ('\u{FC}', '\u{30C}', '\u{1DA}'), ('\u{102}', '\u{300}', '\u{1EB0}'),
+    ('\u{102}', '\u{301}', '\u{1EAE}'), ('\u{102}', '\u{303}', '\u{1EB4}'),
+    ('\u{102}', '\u{309}', '\u{1EB2}'), ('\u{103}', '\u{300}', '\u{1EB1}'),
+    ('\u{103}', '\u{301}', '\u{1EAF}'), ('\u{103}', '\u{303}', '\u{1EB5}'),
+    ('\u{103}', '\u{309}', '\u{1EB3}'), ('\u{112}', '\u{300}', '\u{1E14}'),
+    ('\u{112}', '\u{301}', '\u{1E16}'), ('\u{113}', '\u{300}', '\u{1E15}'),
+    ('\u{113}', '\u{301}', '\u{1E17}'), ('\u{14C}', '\u{300}', '\u{1E50}'),
+    ('\u{14C}', '\u{301}', '\u{1E52}'), ('\u{14D}', '\u{300}', '\u{1E51}'),
+    ('\u{14D}', '\u{301}', '\u{1E53}'), ('\u{15A}', '\u{307}', '\u{1E64}'),
+    ('\u{15B}', '\u{307}', '\u{1E65}'), ('\u{160}', '\u{307}', '\u{1E66}'),
+    ('\u{161}', '\u{307}', '\u{1E67}'), ('\u{168}', '\u{301}', '\u{1E78}'),
+    ('\u{169}', '\u{301}', '\u{1E79}'), ('\u{16A}', '\u{308}', '\u{1E7A}'),
+    ('\u{16B}', '\u{308}', '\u{1E7B}'), ('\u{17F}', '\u{307}', '\u{1E9B}'),
+    ('\u{1A0}', '\u{300}', '\u{1EDC}'), ('\u{1A0}', '\u{301}', '\u{1EDA}'),
+    ('\u{1A0}', '\u{303}', '\u{1EE0}'), ('\u{1A0}', '\u{309}', '\u{1EDE}'),
+    ('\u{1A0}', '\u{323}', '\u{1EE2}'), ('\u{1A1}', '\u{300}', '\u{1EDD}'),
+    ('\u{1A1}', '\u{301}', '\u{1EDB}'), ('\u{1A1}', '\u{303}', '\u{1EE1}'),
+    ('\u{1A1}', '\u{309}', '\u{1EDF}'), ('\u{1A1}', '\u{323}', '\u{1EE3}'),
+    ('\u{1AF}', '\u{300}', '\u{1EEA}'), ('\u{1AF}', '\u{301}', '\u{1EE8}'),
+    ('\u{1AF}', '\u{303}', '\u{1EEE}'), ('\u{1AF}', '\u{309}', '\u{1EEC}'),
+    ('\u{1AF}', '\u{323}', '\u{1EF0}'), ('\u{1B0}', '\u{300}', '\u{1EEB}'),
+    ('\u{1B0}', '\u{301}', '\u{1EE9}'), ('\u{1B0}', '\u{303}', '\u{1EEF}'),
+    ('\u{1B0}', '\u{309}', '\u{1EED}'), ('\u{1B0}', '\u{323}', '\u{1EF1}'),
+    ('\u{1B7}', '\u{30C}', '\u{1EE}'), ('\u{1EA}', '\u{304}', '\u{1EC}'),
+    ('\u{1EB}', '\u{304}', '\u{1ED}'), ('\u{226}', '\u{304}', '\u{1E0}'),
+    ('\u{227}', '\u{304}', '\u{1E1}'), ('\u{228}', '\u{306}', '\u{1E1C}'),
+    ('\u{229}', '\u{306}', '\u{1E1D}'), ('\u{22E}', '\u{304}', '\u{230}'),
+    ('\u{22F}', '\u{304}', '\u{231}'), ('\u{292}', '\u{30C}', '\u{1EF}'),
+    ('\u{391}', '\u{301}', '\u{386}'), ('\u{395}', '\u{301}', '\u{388}'),
+    ('\u{397}', '\u{301}', '\u{389}'), ('\u{399}', '\u{301}', '\u{38A}'),
+    ('\u{399}', '\u{308}', '\u{3AA}'), ('\u{39F}', '\u{301}', '\u{38C}'),
+    ('\u{3A5}', '\u{301}', '\u{38E}'), ('\u{3A5}', '\u{308}', '\u{3AB}'),
+    ('\u{3A9}', '\u{301}', '\u{38F}'), ('\u{3B1}', '\u{301}', '\u{3AC}'),
+    ('\u{3B5}', '\u{301}', '\u{3AD}'), ('\u{3B7}', '\u{301}', '\u{3AE}'),
+    ('\u{3B9}', '\u{301}', '\u{3AF}'), ('\u{3B9}', '\u{308}', '\u{3CA}'),
+    ('\u{3BF}', '\u{301}', '\u{3CC}'), ('\u{3C5}', '\u{301}', '\u{3CD}'),
+    ('\u{3C5}', '\u{308}', '\u{3CB}'), ('\u{3C9}', '\u{301}', '\u{3CE}'),
+    ('\u{3CA}', '\u{301}', '\u{390}'), ('\u{3CB}', '\u{301}', '\u{3B0}'),
+    ('\u{3D2}', '\u{301}', '\u{3D3}'), ('\u{3D2}', '\u{308}', '\u{3D4}'),
+    ('\u{406}', '\u{308}', '\u{407}'), ('\u{410}', '\u{306}', '\u{4D0}'),
+    ('\u{410}', '\u{308}', '\u{4D2}'), ('\u{413}', '\u{301}', '\u{403}'),
+    ('\u{415}', '\u{300}', '\u{400}'), ('\u{415}', '\u{306}', '\u{4D6}'),
+    ('\u{415}', '\u{308}', '\u{401}'), ('\u{416}', '\u{306}', '\u{4C1}'),
+    ('\u{416}', '\u{308}', '\u{4DC}'), ('\u{417}', '\u{308}', '\u{4DE}'),
+    ('\u{418}', '\u{300}', '\u{40D}'), ('\u{418}', '\u{304}', '\u{4E2}'),
+    ('\u{418}', '\u{306}', '\u{419}'), ('\u{418}', '\u{308}', '\u{4E4}'),
+    ('\u{41A}', '\u{301}', '\u{40C}'), ('\u{41E}', '\u{308}', '\u{4E6}'),
+    ('\u{423}', '\u{304}', '\u{4EE}'), ('\u{423}', '\u{306}', '\u{40E}'),
+    ('\u{423}', '\u{308}', '\u{4F0}'), ('\u{423}', '\u{30B}', '\u{4F2}'),
+    ('\u{427}', '\u{308}', '\u{4F4}'), ('\u{42B}', '\u{308}', '\u{4F8}'),
+    ('\u{42D}', '\u{308}', '\u{4EC}'), ('\u{430}', '\u{306}', '\u{4D1}'),
+    ('\u{430}', '\u{308}', '\u{4D3}'), ('\u{433}', '\u{301}', '\u{453}'),
+    ('\u{435}', '\u{300}', '\u{450}'), ('\u{435}', '\u{306}', '\u{4D7}'),
+    ('\u{435}', '\u{308}', '\u{451}'), ('\u{436}', '\u{306}', '\u{4C2}'),
+    ('\u{436}', '\u{308}', '\u{4DD}'), ('\u{437}', '\u{308}', '\u{4DF}'),
+    ('\u{438}', '\u{300}', '\u{45D}'), ('\u{438}', '\u{304}', '\u{4E3}'),
+    ('\u{438}', '\u{306}', '\u{439}'), ('\u{438}', '\u{308}', '\u{4E5}'),
+    ('\u{43A}', '\u{301}', '\u{45C}'), ('\u{43E}', '\u{308}', '\u{4E7}'),
+    ('\u{443}', '\u{304}', '\u{4EF}'), ('\u{443}', '\u{306}', '\u{45E}'),
+    ('\u{443}', '\u{308}', '\u{4F1}'), ('\u{443}', '\u{30B}', '\u{4F3}'),
+    ('\u{447}', '\u{308}', '\u{4F5}'), ('\u{44B}', '\u{308}', '\u{4F9}'),
+    ('\u{44D}', '\u{308}', '\u{4ED}'), ('\u{456}', '\u{308}', '\u{457}'),
+    ('\u{474}', '\u{30F}', '\u{476}'), ('\u{475}', '\u{30F}', '\u{477}'),
+    ('\u{4D8}', '\u{308}', '\u{4DA}'), ('\u{4D9}', '\u{308}', '\u{4DB}'),
+    ('\u{4E8}', '\u{308}', '\u{4EA}'), ('\u{4E9}', '\u{308}', '\u{4EB}'),
+    ('\u{1E36}', '\u{304}', '\u{1E38}'), ('\u{1E37}', '\u{304}', '\u{1E39}'),
+    ('\u{1E5A}', '\u{304}', '\u{1E5C}'), ('\u{1E5B}', '\u{304}', '\u{1E5D}'),
+    ('\u{1E62}', '\u{307}', '\u{1E68}'), ('\u{1E63}', '\u{307}', '\u{1E69}'),
+    ('\u{1EA0}', '\u{302}', '\u{1EAC}'), ('\u{1EA0}', '\u{306}', '\u{1EB6}'),
+    ('\u{1EA1}', '\u{302}', '\u{1EAD}'), ('\u{1EA1}', '\u{306}', '\u{1EB7}'),
+    ('\u{1EB8}', '\u{302}', '\u{1EC6}'), ('\u{1EB9}', '\u{302}', '\u{1EC7}'),
+    ('\u{1ECC}', '\u{302}', '\u{1ED8}'), ('\u{1ECD}', '\u{302}', '\u{1ED9}'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_normalise_and_fold_names() {
+        assert_eq!("Zoé", nfc("Zoe\u{301}"));
+        assert_eq!("Việt", nfc("Vie\u{323}\u{302}t"));
+        assert_eq!("Việt", nfc("Vie\u{302}\u{323}t"));
+        assert_eq!("한", nfc("\u{1112}\u{1161}\u{11AB}"));
+        assert_eq!("Ю\u{301}ля", nfc("Ю\u{301}ля"));
+        assert_eq!("bjorn", NameMatch::IgnoreAccents.key(" Bjørn "));
+        assert_eq!("strasse", NameMatch::IgnoreAccents.key("Straße"));
+        assert_eq!("jiri", NameMatch::IgnoreAccents.key("Jiří"));
+        assert!(!NameMatch::IgnoreCase.matches("Zoe", "Zoé"));
+        assert!(!NameMatch::Exact.matches("Zoé ", "Zoé"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Estimating how much food is left in feeder bowls.
+//!
+//! Feeders weigh their bowls each time a pet eats and each time they are
+//! filled, but the API only reports those readings as timeline events. A
+//! [`FoodTracker`](struct.FoodTracker.html) follows the events to keep the
+//! last known weight of every bowl, works out how fast it has been eaten
+//! since it was filled and when it will be empty at that rate, and raises
+//! [`Alert::LowFood`](../alert/enum.Alert.html#variant.LowFood) once per fill
+//! when a bowl drops below the threshold.
+use crate::alert::Alert;
+use crate::id::DeviceId;
+use crate::time::Timestamp;
+use crate::timeline::{Event, EventKind, PET_VISIT};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// What is known about the food in one bowl.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BowlLevel {
+    device_id: DeviceId,
+    index: u8,
+    remaining: f64,
+    filled: Option<(Timestamp, f64)>,
+    eaten: f64,
+    updated_at: Timestamp,
+}
+
+impl BowlLevel {
+    /// The feeder.
+    pub fn device_id(&self) -> DeviceId {
+        self.device_id
+    }
+
+    /// The bowl index, `0` being the left hand bowl.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Grams in the bowl at the last reading.
+    pub fn remaining(&self) -> f64 {
+        self.remaining
+    }
+
+    /// When the bowl was last filled, if a fill has been seen.
+    pub fn filled_at(&self) -> Option<Timestamp> {
+        self.filled.map(|(at, _)| at)
+    }
+
+    /// Grams in the bowl after the last fill.
+    pub fn filled_to(&self) -> Option<f64> {
+        self.filled.map(|(_, grams)| grams)
+    }
+
+    /// Grams eaten since the last fill.
+    pub fn eaten_since_fill(&self) -> f64 {
+        self.eaten
+    }
+
+    /// When the bowl was last weighed.
+    pub fn updated_at(&self) -> Timestamp {
+        self.updated_at
+    }
+
+    /// Grams eaten per hour since the last fill, `None` before anything has
+    /// been eaten from a fill.
+    pub fn grams_per_hour(&self) -> Option<f64> {
+        let (filled_at, _) = self.filled?;
+        let hours = self.updated_at.seconds_since(filled_at) as f64 / 3600.0;
+        if hours > 0.0 && self.eaten > 0.0 {
+            Some(self.eaten / hours)
+        } else {
+            None
+        }
+    }
+
+    /// When the bowl will be empty if pets keep eating at the rate they have
+    /// since the last fill.
+    pub fn empty_at(&self) -> Option<Timestamp> {
+        let rate = self.grams_per_hour()?;
+        let seconds = (self.remaining / rate * 3600.0).round() as i64;
+        Some(Timestamp::from_unix(self.updated_at.unix() + seconds))
+    }
+}
+
+/// Follows feeder events to keep track of the food in every bowl, see the
+/// module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::food::FoodTracker;
+/// use sure_petcare::timeline::Event;
+/// let events: Vec<Event> = serde_json::from_str(
+///     r#"[{"id":1,"type":22,"created_at":"2019-06-01T06:00:00Z",
+///          "weights":[{"device_id":11,"context":4,"frames":[{"current_weight":60.0,"change":60.0}]}]},
+///         {"id":2,"type":22,"created_at":"2019-06-01T12:00:00Z",
+///          "weights":[{"device_id":11,"context":1,"frames":[{"current_weight":15.0,"change":-45.0}]}]}]"#,
+/// )?;
+/// let mut food = FoodTracker::new(20.0);
+/// let alerts: Vec<_> = events.iter().flat_map(|event| food.apply(event)).collect();
+/// assert_eq!(1, alerts.len());
+/// let bowl = food.bowls().next().unwrap();
+/// assert_eq!(Some(7.5), bowl.grams_per_hour());
+/// assert_eq!("2019-06-01T14:00:00+00:00", bowl.empty_at().unwrap().to_string());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FoodTracker {
+    threshold: f64,
+    bowls: BTreeMap<(DeviceId, u8), BowlLevel>,
+    names: HashMap<DeviceId, String>,
+    alerted: HashSet<(DeviceId, u8)>,
+}
+
+impl FoodTracker {
+    /// A tracker alerting when a bowl holds fewer than `threshold` grams.
+    pub fn new(threshold: f64) -> Self {
+        FoodTracker {
+            threshold,
+            ..FoodTracker::default()
+        }
+    }
+
+    /// Every bowl seen, by feeder and then bowl index.
+    pub fn bowls(&self) -> impl Iterator<Item = &BowlLevel> {
+        self.bowls.values()
+    }
+
+    /// One bowl of a feeder.
+    pub fn bowl(&self, device_id: DeviceId, index: u8) -> Option<&BowlLevel> {
+        self.bowls.get(&(device_id, index))
+    }
+
+    /// Applies a feeding event, returning an alert for each bowl that has
+    /// just dropped below the threshold. Events should be applied oldest
+    /// first; readings older than the last one for a bowl are ignored.
+    pub fn apply(&mut self, event: &Event<'_>) -> Vec<Alert> {
+        if event.kind() != EventKind::Feeding {
+            return Vec::new();
+        }
+        for device in event.devices() {
+            self.names.insert(device.id(), device.name().to_string());
+        }
+        let at = event.created_at();
+        let mut alerts = Vec::new();
+        for entry in event.weights() {
+            let device_id = match entry.device_id() {
+                Some(device_id) => device_id,
+                None => continue,
+            };
+            for frame in entry.frames() {
+                let key = (device_id, frame.index());
+                let bowl = self.bowls.entry(key).or_insert(BowlLevel {
+                    device_id,
+                    index: frame.index(),
+                    remaining: frame.current_weight(),
+                    filled: None,
+                    eaten: 0.0,
+                    updated_at: at,
+                });
+                if at < bowl.updated_at {
+                    continue;
+                }
+                bowl.remaining = frame.current_weight().max(0.0);
+                bowl.updated_at = at;
+                if entry.context() == PET_VISIT {
+                    bowl.eaten -= frame.change().min(0.0);
+                } else if frame.change() > 0.0 {
+                    bowl.filled = Some((at, bowl.remaining));
+                    bowl.eaten = 0.0;
+                }
+                if bowl.remaining >= self.threshold {
+                    self.alerted.remove(&key);
+                } else if self.alerted.insert(key) {
+                    let name = self.names.get(&device_id).cloned();
+                    alerts.push(Alert::LowFood {
+                        device_id,
+                        name: name.unwrap_or_else(|| format!("Feeder {}", device_id)),
+                        bowl: frame.index(),
+                        remaining: bowl.remaining,
+                        empty_at: bowl.empty_at(),
+                    });
+                }
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_follow_bowls_between_fills() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":22,"created_at":"2019-06-01T06:00:00Z",
+             "devices":[{"id":11,"name":"Kitchen feeder"}],
+             "weights":[{"device_id":11,"context":4,"frames":[
+                 {"index":0,"current_weight":50.0,"change":50.0},
+                 {"index":1,"current_weight":40.0,"change":40.0}]}]},
+            {"id":2,"type":22,"created_at":"2019-06-01T08:00:00Z",
+             "weights":[{"device_id":11,"context":1,"frames":[
+                 {"index":0,"current_weight":30.0,"change":-20.0},
+                 {"index":1,"current_weight":40.0,"change":0.0}]}]},
+            {"id":3,"type":22,"created_at":"2019-06-01T07:00:00Z",
+             "weights":[{"device_id":11,"context":1,"frames":[
+                 {"index":0,"current_weight":45.0,"change":-5.0}]}]},
+            {"id":4,"type":22,"created_at":"2019-06-01T10:00:00Z",
+             "weights":[{"device_id":11,"context":1,"frames":[
+                 {"index":0,"current_weight":8.0,"change":-22.0}]}]},
+            {"id":5,"type":22,"created_at":"2019-06-01T11:00:00Z",
+             "weights":[{"device_id":11,"context":1,"frames":[
+                 {"index":0,"current_weight":2.0,"change":-6.0}]}]},
+            {"id":6,"type":22,"created_at":"2019-06-01T12:00:00Z",
+             "weights":[{"device_id":11,"context":4,"frames":[
+                 {"index":0,"current_weight":50.0,"change":48.0}]}]},
+            {"id":7,"type":22,"created_at":"2019-06-01T18:00:00Z",
+             "weights":[{"device_id":11,"context":1,"frames":[
+                 {"index":0,"current_weight":5.0,"change":-45.0}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let mut food = FoodTracker::new(10.0);
+
+        let alerts: Vec<Vec<Alert>> = events.iter().map(|event| food.apply(event)).collect();
+
+        let counts: Vec<usize> = alerts.iter().map(Vec::len).collect();
+        assert_eq!(vec![0, 0, 0, 1, 0, 0, 1], counts);
+        assert_eq!(
+            "Kitchen feeder bowl 1 is down to 8g, refill soon",
+            alerts[3][0].to_string()
+        );
+        let left = food.bowl(DeviceId::new(11), 0).unwrap();
+        assert_eq!(5.0, left.remaining());
+        assert_eq!(45.0, left.eaten_since_fill());
+        assert_eq!(Some(50.0), left.filled_to());
+        assert_eq!(Some(7.5), left.grams_per_hour());
+        let right = food.bowl(DeviceId::new(11), 1).unwrap();
+        assert_eq!(None, right.empty_at());
+        assert_eq!(2, food.bowls().count());
+    }
+}
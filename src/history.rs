@@ -0,0 +1,266 @@
+//! A local archive of timeline events and state snapshots.
+//!
+//! The API only keeps a limited amount of history, so anyone wanting a
+//! long term record has to store it themselves. [`HistoryStore`](trait.HistoryStore.html)
+//! is the interface the rest of the crate writes to; [`MemoryHistory`](struct.MemoryHistory.html)
+//! keeps everything in memory, and [`SQLITE_SCHEMA`](constant.SQLITE_SCHEMA.html)
+//! describes the tables of a database backed store.
+use crate::error::Result;
+use crate::id::PetId;
+use crate::pet::Location;
+use crate::state::State;
+use crate::time::Timestamp;
+use crate::timeline::Event;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// The tables for a SQLite backed `HistoryStore`. Events are keyed by their
+/// id so inserting the same page twice is harmless, and the raw JSON is kept
+/// alongside the columns queries filter on.
+pub const SQLITE_SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    household_id INTEGER,
+    kind INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    json TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS events_created_at ON events (created_at);
+CREATE TABLE IF NOT EXISTS event_pets (
+    event_id INTEGER NOT NULL REFERENCES events (id),
+    pet_id INTEGER NOT NULL,
+    PRIMARY KEY (event_id, pet_id)
+);
+CREATE TABLE IF NOT EXISTS positions (
+    pet_id INTEGER NOT NULL,
+    location INTEGER NOT NULL,
+    since INTEGER NOT NULL,
+    PRIMARY KEY (pet_id, since)
+);
+CREATE TABLE IF NOT EXISTS device_snapshots (
+    device_id INTEGER NOT NULL,
+    taken_at INTEGER NOT NULL,
+    json TEXT NOT NULL,
+    PRIMARY KEY (device_id, taken_at)
+);
+";
+
+/// A recorded pet position.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PositionRecord {
+    /// The pet.
+    pub pet_id: PetId,
+    /// Where the pet went.
+    pub location: Location,
+    /// When it went there.
+    pub since: Timestamp,
+}
+
+/// Persists events and snapshots.
+pub trait HistoryStore {
+    /// Inserts the events not stored yet, returning how many were new.
+    fn upsert_events(&self, events: &[Event<'_>]) -> Result<usize>;
+
+    /// Records pet positions and device status from a start state snapshot
+    /// taken at `at`.
+    fn record_state(&self, at: Timestamp, state: &State<'_>) -> Result<()>;
+
+    /// The stored events created in `[from, to)`, oldest first.
+    fn events_between(&self, from: Timestamp, to: Timestamp) -> Result<Vec<Event<'static>>>;
+
+    /// The stored positions of a pet, oldest first.
+    fn positions(&self, pet_id: PetId) -> Result<Vec<PositionRecord>>;
+
+    /// The id of the newest stored event, to resume fetching from.
+    fn latest_event_id(&self) -> Result<Option<i64>>;
+
+    /// The stored events involving a pet created in `[from, to)`, oldest
+    /// first.
+    fn pet_events_between(
+        &self,
+        pet_id: PetId,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Vec<Event<'static>>> {
+        let mut events = self.events_between(from, to)?;
+        events.retain(|event| event.involves_pet(pet_id));
+        Ok(events)
+    }
+}
+
+/// A history store that lives in memory.
+#[derive(Default)]
+pub struct MemoryHistory {
+    events: Mutex<BTreeMap<i64, Event<'static>>>,
+    positions: Mutex<BTreeMap<(PetId, Timestamp), Location>>,
+    devices: Mutex<Vec<(Timestamp, serde_json::Value)>>,
+}
+
+impl MemoryHistory {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        MemoryHistory::default()
+    }
+
+    /// The number of stored events.
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    /// Whether no events are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for MemoryHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryHistory")
+            .field("events", &self.len())
+            .field("positions", &self.positions.lock().unwrap().len())
+            .field("device_snapshots", &self.devices.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl HistoryStore for MemoryHistory {
+    fn upsert_events(&self, events: &[Event<'_>]) -> Result<usize> {
+        let mut stored = self.events.lock().unwrap();
+        let mut inserted = 0;
+        for event in events {
+            let owned = event.clone().into_owned();
+            if stored.insert(event.id(), owned).is_none() {
+                inserted += 1;
+            }
+        }
+        Ok(inserted)
+    }
+
+    fn record_state(&self, at: Timestamp, state: &State<'_>) -> Result<()> {
+        let mut positions = self.positions.lock().unwrap();
+        for pet in state.pets() {
+            if let Some(position) = pet.position() {
+                positions.insert((pet.id(), position.since()), position.location());
+            }
+        }
+        let mut devices = self.devices.lock().unwrap();
+        for device in state.devices() {
+            devices.push((at, serde_json::to_value(device)?));
+        }
+        Ok(())
+    }
+
+    fn events_between(&self, from: Timestamp, to: Timestamp) -> Result<Vec<Event<'static>>> {
+        let mut events: Vec<Event<'static>> = self
+            .events
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|event| event.created_at() >= from && event.created_at() < to)
+            .cloned()
+            .collect();
+        events.sort_by_key(|event| (event.created_at(), event.id()));
+        Ok(events)
+    }
+
+    fn positions(&self, pet_id: PetId) -> Result<Vec<PositionRecord>> {
+        Ok(self
+            .positions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((id, _), _)| *id == pet_id)
+            .map(|((pet_id, since), location)| PositionRecord {
+                pet_id: *pet_id,
+                location: *location,
+                since: *since,
+            })
+            .collect())
+    }
+
+    fn latest_event_id(&self) -> Result<Option<i64>> {
+        Ok(self.events.lock().unwrap().keys().next_back().copied())
+    }
+}
+
+impl<H: HistoryStore + ?Sized> HistoryStore for &H {
+    fn upsert_events(&self, events: &[Event<'_>]) -> Result<usize> {
+        (**self).upsert_events(events)
+    }
+
+    fn record_state(&self, at: Timestamp, state: &State<'_>) -> Result<()> {
+        (**self).record_state(at, state)
+    }
+
+    fn events_between(&self, from: Timestamp, to: Timestamp) -> Result<Vec<Event<'static>>> {
+        (**self).events_between(from, to)
+    }
+
+    fn positions(&self, pet_id: PetId) -> Result<Vec<PositionRecord>> {
+        (**self).positions(pet_id)
+    }
+
+    fn latest_event_id(&self) -> Result<Option<i64>> {
+        (**self).latest_event_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_upsert_events_by_id() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[{"id":3,"type":0,"created_at":"2019-06-01T06:00:00+00:00","pets":[{"id":2,"name":"Milo"}]},
+                {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00"}]"#,
+        )
+        .unwrap();
+        let history = MemoryHistory::new();
+
+        assert_eq!(2, history.upsert_events(&events).unwrap());
+        assert_eq!(0, history.upsert_events(&events[..1]).unwrap());
+        assert_eq!(Some(3), history.latest_event_id().unwrap());
+
+        let from = "2019-06-01T00:00:00Z".parse().unwrap();
+        let to = "2019-06-02T00:00:00Z".parse().unwrap();
+        let ids: Vec<i64> = history
+            .events_between(from, to)
+            .unwrap()
+            .iter()
+            .map(Event::id)
+            .collect();
+        assert_eq!(vec![2, 3], ids);
+        assert_eq!(
+            1,
+            history
+                .pet_events_between(PetId::new(2), from, to)
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn should_record_positions() {
+        let state: State = serde_json::from_str(
+            r#"{"pets":[{"id":2,"name":"Milo",
+                "position":{"where":2,"since":"2019-06-01T05:00:00+00:00"}}]}"#,
+        )
+        .unwrap();
+        let history = MemoryHistory::new();
+        let at = Timestamp::from_unix(1_559_368_800);
+        history.record_state(at, &state).unwrap();
+        history.record_state(at, &state).unwrap();
+
+        assert_eq!(
+            vec![PositionRecord {
+                pet_id: PetId::new(2),
+                location: Location::Outside,
+                since: "2019-06-01T05:00:00Z".parse().unwrap(),
+            }],
+            history.positions(PetId::new(2)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Setting automations aside for a while.
+//!
+//! An [`Override`](struct.Override.html) holds something for an automation
+//! to do instead of following its schedule, until a given time: curfews for
+//! a [`CurfewScheduler`](../schedule/struct.CurfewScheduler.html) to keep
+//! instead of the profile's, where no curfews at all suppresses them, or an
+//! action for a [`SunLock`](../sun/struct.SunLock.html) to hold, such as
+//! keeping the flap unlocked until Sunday. Handles are shared, so a clone
+//! kept aside can set or clear the override while the automation runs on
+//! another thread. When it expires, the automation goes back to its
+//! schedule.
+use crate::time::Timestamp;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(feature = "devices")]
+use std::time::Duration;
+
+struct Held<T> {
+    value: Option<(T, Timestamp)>,
+    changes: u64,
+}
+
+/// A shared, expiring override, see the module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::holiday::Override;
+/// use sure_petcare::sun::SunAction;
+/// use sure_petcare::time::Timestamp;
+/// let sunday: Timestamp = "2019-06-02T18:00:00Z".parse().unwrap();
+/// let hold = Override::new();
+/// hold.set(SunAction::Unlock, sunday);
+/// let friday = "2019-05-31T21:00:00Z".parse().unwrap();
+/// assert_eq!(Some((SunAction::Unlock, sunday)), hold.active_at(friday));
+/// assert_eq!(None, hold.active_at(sunday));
+/// ```
+pub struct Override<T> {
+    shared: Arc<(Mutex<Held<T>>, Condvar)>,
+}
+
+impl<T> Clone for Override<T> {
+    fn clone(&self) -> Self {
+        Override {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Default for Override<T> {
+    fn default() -> Self {
+        Override {
+            shared: Arc::new((
+                Mutex::new(Held {
+                    value: None,
+                    changes: 0,
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+}
+
+impl<T: Clone> Override<T> {
+    /// An override with nothing set.
+    pub fn new() -> Self {
+        Override::default()
+    }
+
+    /// Holds `value` until `until`, replacing any earlier override.
+    pub fn set(&self, value: T, until: Timestamp) {
+        self.change(Some((value, until)));
+    }
+
+    /// Ends the override now.
+    pub fn clear(&self) {
+        self.change(None);
+    }
+
+    /// The value held at an instant and when it expires, `None` when
+    /// nothing is set or it has expired.
+    pub fn active_at(&self, instant: Timestamp) -> Option<(T, Timestamp)> {
+        self.lock()
+            .value
+            .clone()
+            .filter(|(_, until)| instant < *until)
+    }
+
+    /// Waits for `timeout`, or less if the override is set or cleared in
+    /// the meantime.
+    #[cfg(feature = "devices")]
+    pub(crate) fn wait(&self, timeout: Duration) {
+        let held = self.lock();
+        let changes = held.changes;
+        let _ = self
+            .shared
+            .1
+            .wait_timeout_while(held, timeout, |held| held.changes == changes);
+    }
+
+    fn change(&self, value: Option<(T, Timestamp)>) {
+        let mut held = self.lock();
+        held.value = value;
+        held.changes += 1;
+        self.shared.1.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Held<T>> {
+        self.shared
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Override<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let held = self
+            .shared
+            .0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f.debug_struct("Override")
+            .field("value", &held.value)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn should_expire_and_wake_waiters_when_changed() {
+        let hold: Override<u8> = Override::new();
+        let now: Timestamp = "2019-06-01T12:00:00Z".parse().unwrap();
+        let later = now + Duration::from_secs(3600);
+        assert_eq!(None, hold.active_at(now));
+
+        #[cfg(feature = "devices")]
+        {
+            use std::thread;
+            use std::time::Instant;
+            let shared = hold.clone();
+            let started = Instant::now();
+            let waiter = thread::spawn(move || shared.wait(Duration::from_secs(30)));
+            thread::sleep(Duration::from_millis(20));
+            hold.set(1, later);
+            waiter.join().unwrap();
+            assert!(started.elapsed() < Duration::from_secs(30));
+        }
+        hold.set(1, later);
+
+        assert_eq!(Some((1, later)), hold.active_at(now));
+        assert_eq!(None, hold.active_at(later));
+        hold.clear();
+        assert_eq!(None, hold.active_at(now));
+    }
+}
@@ -0,0 +1,170 @@
+//! Showing times to people.
+//!
+//! The CLI, the dashboard and digests all show times through a
+//! [`Humanizer`](struct.Humanizer.html), so they read the same everywhere:
+//! instants relative to now, such as `18 minutes ago`, or as the wall-clock
+//! time in the household's zone, such as `today 07:42`. Durations read like
+//! `5 h 12 m`, see [`duration`](fn.duration.html).
+use crate::state::State;
+use crate::time::Timestamp;
+use crate::zone::Zone;
+use std::time::Duration;
+
+/// Formats instants as seen at one moment from one time zone.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::humanize::Humanizer;
+/// use sure_petcare::zone::Zone;
+/// let now = "2019-06-01T08:00:00Z".parse().unwrap();
+/// let humanizer = Humanizer::new(now).with_zone("GMT0BST,M3.5.0/1,M10.5.0".parse()?);
+/// let at = "2019-06-01T07:42:00Z".parse().unwrap();
+/// assert_eq!("18 minutes ago", humanizer.relative(at));
+/// assert_eq!("today 08:42", humanizer.local(at));
+/// # Ok::<(), sure_petcare::zone::ZoneError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Humanizer {
+    now: Timestamp,
+    zone: Zone,
+}
+
+impl Humanizer {
+    /// A humanizer for `now`, showing times in UTC.
+    pub fn new(now: Timestamp) -> Self {
+        Humanizer {
+            now,
+            zone: Zone::utc(),
+        }
+    }
+
+    /// Shows times in `zone`.
+    pub fn with_zone(mut self, zone: Zone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// Shows times in the zone of the first household that names one this
+    /// system knows, keeping the current zone otherwise.
+    pub fn with_zone_of(self, state: &State<'_>) -> Self {
+        let zone = state
+            .households()
+            .iter()
+            .filter_map(|household| household.time_zone())
+            .find_map(|name| Zone::named(name).ok());
+        match zone {
+            Some(zone) => self.with_zone(zone),
+            None => self,
+        }
+    }
+
+    /// The moment times are shown from.
+    pub fn now(&self) -> Timestamp {
+        self.now
+    }
+
+    /// How long ago an instant was, like `18 minutes ago`, or how far off
+    /// it is, like `in 2 hours`. Anything within a minute is `just now`.
+    pub fn relative(&self, instant: Timestamp) -> String {
+        let seconds = self.now.seconds_since(instant);
+        let minutes = seconds.abs() / 60;
+        if minutes == 0 {
+            return "just now".to_string();
+        }
+        let (count, unit) = match minutes {
+            1..=59 => (minutes, "minute"),
+            60..=1439 => (minutes / 60, "hour"),
+            _ => (minutes / 1440, "day"),
+        };
+        let plural = if count == 1 { "" } else { "s" };
+        if seconds > 0 {
+            format!("{} {}{} ago", count, unit, plural)
+        } else {
+            format!("in {} {}{}", count, unit, plural)
+        }
+    }
+
+    /// The wall-clock time of an instant, like `07:42`.
+    pub fn time(&self, instant: Timestamp) -> String {
+        self.zone.local(instant).1.to_string()
+    }
+
+    /// The day and wall-clock time of an instant: `today 07:42`,
+    /// `yesterday 23:10` or `tomorrow 06:00`, the weekday within the last
+    /// week, like `Monday 07:42`, and the date otherwise.
+    pub fn local(&self, instant: Timestamp) -> String {
+        let (date, time) = self.zone.local(instant);
+        let today = self.zone.local(self.now).0;
+        let day = match (date.midnight().seconds_since(today.midnight())) / 86_400 {
+            0 => "today".to_string(),
+            -1 => "yesterday".to_string(),
+            1 => "tomorrow".to_string(),
+            -6..=-2 => date.weekday().to_string(),
+            _ => date.to_string(),
+        };
+        format!("{} {}", day, time)
+    }
+}
+
+/// Formats a duration as `5 h 12 m`, or `12 m` under an hour, rounding
+/// down to the minute.
+pub fn duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    if minutes < 60 {
+        format!("{} m", minutes)
+    } else {
+        format!("{} h {} m", minutes / 60, minutes % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_format_times_relative_to_now() {
+        let at = |time: &str| -> Timestamp { time.parse().unwrap() };
+        let humanizer = Humanizer::new(at("2019-06-05T00:30:00Z")).with_zone(Zone::fixed(3600));
+
+        assert_eq!("just now", humanizer.relative(at("2019-06-05T00:29:30Z")));
+        assert_eq!(
+            "1 minute ago",
+            humanizer.relative(at("2019-06-05T00:29:00Z"))
+        );
+        assert_eq!(
+            "3 hours ago",
+            humanizer.relative(at("2019-06-04T21:00:00Z"))
+        );
+        assert_eq!("2 days ago", humanizer.relative(at("2019-06-03T00:00:00Z")));
+        assert_eq!(
+            "in 5 minutes",
+            humanizer.relative(at("2019-06-05T00:35:00Z"))
+        );
+
+        assert_eq!("01:30", humanizer.time(at("2019-06-05T00:30:00Z")));
+        assert_eq!("today 00:10", humanizer.local(at("2019-06-04T23:10:00Z")));
+        assert_eq!(
+            "yesterday 23:10",
+            humanizer.local(at("2019-06-04T22:10:00Z"))
+        );
+        assert_eq!(
+            "tomorrow 06:00",
+            humanizer.local(at("2019-06-06T05:00:00Z"))
+        );
+        assert_eq!(
+            "Saturday 07:42",
+            humanizer.local(at("2019-06-01T06:42:00Z"))
+        );
+        assert_eq!(
+            "2019-05-29 07:42",
+            humanizer.local(at("2019-05-29T06:42:00Z"))
+        );
+
+        assert_eq!("0 m", duration(Duration::from_secs(59)));
+        assert_eq!(
+            "5 h 12 m",
+            duration(Duration::from_secs(5 * 3600 + 12 * 60))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Typed ids, so a pet id cannot be passed where a device id is expected.
+//!
+//! Each id serializes as the bare number the API uses and displays as it,
+//! so they can go straight into paths and logs.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+macro_rules! id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(
+            Clone, Copy, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(i64);
+
+        impl $name {
+            /// Wraps a raw id.
+            pub const fn new(id: i64) -> Self {
+                $name(id)
+            }
+
+            /// The raw id.
+            pub const fn get(self) -> i64 {
+                self.0
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(id: i64) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.trim().parse().map($name)
+            }
+        }
+    };
+}
+
+id! {
+    /// The id of a household.
+    HouseholdId
+}
+
+id! {
+    /// The id of a pet.
+    PetId
+}
+
+id! {
+    /// The id of a hub, flap, feeder or other device.
+    DeviceId
+}
+
+id! {
+    /// The id of a microchip or collar tag, as read by flaps and feeders.
+    TagId
+}
+
+id! {
+    /// The id of an uploaded photo.
+    PhotoId
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_be_transparent() {
+        let id: PetId = serde_json::from_str("3001").unwrap();
+        assert_eq!(PetId::new(3001), id);
+        assert_eq!("3001", serde_json::to_string(&id).unwrap());
+        assert_eq!("3001", id.to_string());
+        assert_eq!(Ok(DeviceId::new(5)), " 5".parse());
+    }
+}
@@ -0,0 +1,331 @@
+//! Working out which pet drank from a water dispenser.
+//!
+//! A Felaqua weighs the water a pet drinks, but it often cannot tell which
+//! pet it was: a drinking event may name several pets, or none when the
+//! chip was not read. A [`DrinkAttributor`](struct.DrinkAttributor.html)
+//! follows the timeline and gives each drink to a pet with a confidence
+//! between `0.0` and `1.0`, on the reasoning recorded in its
+//! [`Basis`](enum.Basis.html):
+//!
+//! - a tag read at the bowl, or a single pet named, is certain,
+//! - otherwise, the pet that most recently came in through a flap and has
+//!   not gone out since, among those named or among all pets when none are,
+//!   within a window of the drink,
+//! - otherwise, the drink is shared equally between the pets named, or left
+//!   unattributed when there are none.
+//!
+//! [`per_pet`](fn.per_pet.html) totals the drinks for intake reports,
+//! leaving out those attributed with too little confidence.
+use crate::id::{DeviceId, PetId, TagId};
+use crate::time::Timestamp;
+use crate::timeline::{Direction, Event, EventKind, PET_VISIT};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// Why a drink was given to a pet.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Basis {
+    /// The pet's tag was read at the bowl, or it was the only pet named.
+    Tagged,
+    /// The pet came in through a flap most recently before the drink.
+    RecentEntry,
+    /// The drink was shared between the pets named.
+    Shared,
+    /// Nothing points to any pet.
+    Unattributed,
+}
+
+/// Water drunk, given to a pet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attribution {
+    event_id: i64,
+    device_id: Option<DeviceId>,
+    at: Timestamp,
+    pet_id: Option<PetId>,
+    millilitres: f64,
+    confidence: f64,
+    basis: Basis,
+}
+
+impl Attribution {
+    /// The drinking event.
+    pub fn event_id(&self) -> i64 {
+        self.event_id
+    }
+
+    /// The water dispenser.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// When the pet drank.
+    pub fn at(&self) -> Timestamp {
+        self.at
+    }
+
+    /// The pet the drink is given to, `None` when unattributed.
+    pub fn pet_id(&self) -> Option<PetId> {
+        self.pet_id
+    }
+
+    /// Millilitres drunk, the pet's share when the drink was shared.
+    pub fn millilitres(&self) -> f64 {
+        self.millilitres
+    }
+
+    /// How likely the pet is to have drunk it, between `0.0` and `1.0`.
+    pub fn confidence(&self) -> f64 {
+        self.confidence
+    }
+
+    /// Why the drink was given to the pet.
+    pub fn basis(&self) -> Basis {
+        self.basis
+    }
+}
+
+/// Gives drinks to pets, see the module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::id::PetId;
+/// use sure_petcare::intake::{Basis, DrinkAttributor};
+/// use sure_petcare::timeline::Event;
+/// let events: Vec<Event> = serde_json::from_str(
+///     r#"[{"id":1,"type":0,"created_at":"2019-06-01T07:50:00Z",
+///          "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":1}]},
+///         {"id":2,"type":29,"created_at":"2019-06-01T08:00:00Z",
+///          "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":300.0,"change":-25.0}]}]}]"#,
+/// )?;
+/// let mut attributor = DrinkAttributor::new();
+/// let drinks: Vec<_> = events.iter().flat_map(|event| attributor.apply(event)).collect();
+/// assert_eq!(Some(PetId::new(2)), drinks[0].pet_id());
+/// assert_eq!(Basis::RecentEntry, drinks[0].basis());
+/// assert_eq!(25.0, drinks[0].millilitres());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DrinkAttributor {
+    window: Duration,
+    among_named: f64,
+    among_all: f64,
+    tags: HashMap<TagId, PetId>,
+    entries: HashMap<PetId, Timestamp>,
+}
+
+impl Default for DrinkAttributor {
+    fn default() -> Self {
+        DrinkAttributor::new()
+    }
+}
+
+impl DrinkAttributor {
+    /// An attributor looking back an hour for flap entries, with a
+    /// confidence of `0.8` for the most recent entry among the pets named
+    /// and `0.5` among all pets.
+    pub fn new() -> Self {
+        DrinkAttributor {
+            window: Duration::from_secs(3600),
+            among_named: 0.8,
+            among_all: 0.5,
+            tags: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Only counts flap entries within `window` before a drink.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the confidence of attributions by recent entry: `among_named`
+    /// when choosing between the pets a drink names, `among_all` when it
+    /// names none.
+    pub fn with_confidence(mut self, among_named: f64, among_all: f64) -> Self {
+        self.among_named = among_named;
+        self.among_all = among_all;
+        self
+    }
+
+    /// Gives drinks with `tag_id` read at the bowl to `pet_id`.
+    pub fn with_tag(mut self, tag_id: TagId, pet_id: PetId) -> Self {
+        self.tags.insert(tag_id, pet_id);
+        self
+    }
+
+    /// Applies an event, oldest first: flap entries and exits are
+    /// remembered, and a drink returns one attribution per pet it is given
+    /// to.
+    pub fn apply(&mut self, event: &Event<'_>) -> Vec<Attribution> {
+        let at = event.created_at();
+        match event.kind() {
+            EventKind::Movement => {
+                for movement in event.movements() {
+                    for pet in event.pets() {
+                        match movement.direction() {
+                            Direction::In => {
+                                let entered = self.entries.entry(pet.id()).or_insert(at);
+                                *entered = (*entered).max(at);
+                            }
+                            Direction::Out => {
+                                self.entries.remove(&pet.id());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Vec::new()
+            }
+            EventKind::Drinking => event
+                .weights()
+                .iter()
+                .filter(|entry| entry.context() == PET_VISIT)
+                .flat_map(|entry| {
+                    let drunk = -entry.frames().iter().map(|f| f.change()).sum::<f64>();
+                    let named: Vec<PetId> = event.pets().iter().map(|pet| pet.id()).collect();
+                    let tagged = entry.tag_id().and_then(|tag| self.tags.get(&tag)).copied();
+                    let attribution = |pet_id, millilitres, confidence, basis| Attribution {
+                        event_id: event.id(),
+                        device_id: entry.device_id(),
+                        at,
+                        pet_id,
+                        millilitres,
+                        confidence,
+                        basis,
+                    };
+                    if drunk <= 0.0 {
+                        return Vec::new();
+                    }
+                    if let Some(pet_id) = tagged.or(match named[..] {
+                        [pet_id] => Some(pet_id),
+                        _ => None,
+                    }) {
+                        return vec![attribution(Some(pet_id), drunk, 1.0, Basis::Tagged)];
+                    }
+                    let (candidates, confidence) = if named.is_empty() {
+                        (self.entries.keys().copied().collect(), self.among_all)
+                    } else {
+                        (named.clone(), self.among_named)
+                    };
+                    if let Some(pet_id) = self.most_recent_entry(&candidates, at) {
+                        return vec![attribution(
+                            Some(pet_id),
+                            drunk,
+                            confidence,
+                            Basis::RecentEntry,
+                        )];
+                    }
+                    if named.is_empty() {
+                        return vec![attribution(None, drunk, 0.0, Basis::Unattributed)];
+                    }
+                    let share = 1.0 / named.len() as f64;
+                    named
+                        .iter()
+                        .map(|&pet_id| {
+                            attribution(Some(pet_id), drunk * share, share, Basis::Shared)
+                        })
+                        .collect()
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn most_recent_entry(&self, candidates: &[PetId], at: Timestamp) -> Option<PetId> {
+        let mut entries: Vec<(Timestamp, PetId)> = candidates
+            .iter()
+            .filter_map(|pet_id| Some((*self.entries.get(pet_id)?, *pet_id)))
+            .filter(|(entered, _)| *entered <= at && at.elapsed_since(*entered) <= self.window)
+            .collect();
+        entries.sort();
+        match entries[..] {
+            // Two pets came in together; neither is more likely.
+            [.., (first, _), (second, _)] if first == second => None,
+            [.., (_, pet_id)] => Some(pet_id),
+            [] => None,
+        }
+    }
+}
+
+/// Millilitres drunk by each pet, counting only drinks attributed with at
+/// least `min_confidence`.
+pub fn per_pet(attributions: &[Attribution], min_confidence: f64) -> BTreeMap<PetId, f64> {
+    let mut totals = BTreeMap::new();
+    for attribution in attributions {
+        if let Some(pet_id) = attribution.pet_id {
+            if attribution.confidence >= min_confidence {
+                *totals.entry(pet_id).or_insert(0.0) += attribution.millilitres;
+            }
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_attribute_drinks_by_tag_name_and_recent_entry() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":0,"created_at":"2019-06-01T07:00:00Z",
+             "pets":[{"id":2,"name":"Milo"}],"movements":[{"direction":1}]},
+            {"id":2,"type":0,"created_at":"2019-06-01T07:30:00Z",
+             "pets":[{"id":3,"name":"Tilly"}],"movements":[{"direction":1}]},
+            {"id":3,"type":0,"created_at":"2019-06-01T07:40:00Z",
+             "pets":[{"id":3,"name":"Tilly"}],"movements":[{"direction":2}]},
+            {"id":4,"type":29,"created_at":"2019-06-01T07:45:00Z",
+             "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}],
+             "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":300.0,"change":-20.0}]}]},
+            {"id":5,"type":29,"created_at":"2019-06-01T07:50:00Z",
+             "weights":[{"device_id":12,"tag_id":8,"context":1,"frames":[{"current_weight":290.0,"change":-10.0}]}]},
+            {"id":6,"type":29,"created_at":"2019-06-01T12:00:00Z",
+             "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}],
+             "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":260.0,"change":-30.0}]}]},
+            {"id":7,"type":29,"created_at":"2019-06-01T12:30:00Z",
+             "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":250.0,"change":-10.0}]}]},
+            {"id":8,"type":29,"created_at":"2019-06-01T13:00:00Z",
+             "weights":[{"device_id":12,"context":4,"frames":[{"current_weight":900.0,"change":650.0}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let mut attributor = DrinkAttributor::new().with_tag(TagId::new(8), PetId::new(2));
+
+        let drinks: Vec<Attribution> = events
+            .iter()
+            .flat_map(|event| attributor.apply(event))
+            .collect();
+
+        let summary: Vec<(i64, Option<PetId>, f64, f64, Basis)> = drinks
+            .iter()
+            .map(|d| {
+                (
+                    d.event_id(),
+                    d.pet_id(),
+                    d.millilitres(),
+                    d.confidence(),
+                    d.basis(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (4, Some(PetId::new(2)), 20.0, 0.8, Basis::RecentEntry),
+                (5, Some(PetId::new(2)), 10.0, 1.0, Basis::Tagged),
+                (6, Some(PetId::new(2)), 15.0, 0.5, Basis::Shared),
+                (6, Some(PetId::new(3)), 15.0, 0.5, Basis::Shared),
+                (7, None, 10.0, 0.0, Basis::Unattributed),
+            ],
+            summary
+        );
+        let totals = per_pet(&drinks, 0.8);
+        assert_eq!(Some(&30.0), totals.get(&PetId::new(2)));
+        assert_eq!(None, totals.get(&PetId::new(3)));
+        let likely = per_pet(&drinks, 0.5);
+        assert_eq!(Some(&45.0), likely.get(&PetId::new(2)));
+        assert_eq!(Some(&15.0), likely.get(&PetId::new(3)));
+    }
+}
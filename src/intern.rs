@@ -0,0 +1,127 @@
+//! Sharing the names that timeline events repeat.
+//!
+//! Every event carries the names of the pets and devices involved, so a
+//! long history holds the same few names thousands of times over. An
+//! [`Interner`](struct.Interner.html) holds one copy of each known name, and
+//! events passed through it borrow that copy instead of keeping their own.
+use crate::state::State;
+use crate::timeline::{DeviceRef, Event, PetRef};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// A set of names events can borrow from.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::intern::Interner;
+/// use sure_petcare::timeline::Event;
+/// let interner = Interner::new(vec!["Milo".to_string()]);
+/// let event: Event<'static> = serde_json::from_str(
+///     r#"{"id":1,"type":0,"created_at":"2019-06-01T05:00:00Z","pets":[{"id":2,"name":"Milo"}]}"#,
+/// )?;
+/// let event = interner.event(event);
+/// assert_eq!("Milo", event.pets()[0].name());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    names: HashSet<Box<str>>,
+}
+
+impl Interner {
+    /// An interner holding the given names.
+    pub fn new<I: IntoIterator<Item = String>>(names: I) -> Self {
+        Interner {
+            names: names.into_iter().map(String::into_boxed_str).collect(),
+        }
+    }
+
+    /// An interner holding the names of every pet and device in the state,
+    /// which covers the names events carry unless something was renamed.
+    pub fn from_state(state: &State<'_>) -> Self {
+        let pets = state.pets().iter().map(|pet| pet.name().to_string());
+        let devices = state.devices().iter().map(|d| d.name().to_string());
+        Interner::new(pets.chain(devices))
+    }
+
+    /// Adds a name.
+    pub fn insert<S: Into<String>>(&mut self, name: S) {
+        self.names.insert(name.into().into_boxed_str());
+    }
+
+    /// The number of names held.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether no names are held.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Borrows the interned copy of a name, keeping names it does not hold
+    /// as they are.
+    pub fn intern<'i>(&'i self, name: Cow<'_, str>) -> Cow<'i, str> {
+        match self.names.get(name.as_ref()) {
+            Some(interned) => Cow::Borrowed(interned),
+            None => Cow::Owned(name.into_owned()),
+        }
+    }
+
+    /// Swaps the pet and device names of an event for the interned copies.
+    pub fn event<'i>(&'i self, event: Event<'_>) -> Event<'i> {
+        Event {
+            id: event.id,
+            kind: event.kind,
+            created_at: event.created_at,
+            household_id: event.household_id,
+            pets: event
+                .pets
+                .into_iter()
+                .map(|pet| PetRef {
+                    id: pet.id,
+                    name: self.intern(pet.name),
+                })
+                .collect(),
+            devices: event
+                .devices
+                .into_iter()
+                .map(|device| DeviceRef {
+                    id: device.id,
+                    name: self.intern(device.name),
+                    product_id: device.product_id,
+                })
+                .collect(),
+            movements: event.movements,
+            weights: event.weights,
+            other: event.other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_share_known_names() {
+        let state: State = serde_json::from_str(
+            r#"{"pets":[{"id":2,"name":"Milo"}],"devices":[{"id":10,"name":"Back door","product_id":6}]}"#,
+        )
+        .unwrap();
+        let interner = Interner::from_state(&state);
+        let event: Event = serde_json::from_str(
+            r#"{"id":1,"type":0,"created_at":"2019-06-01T05:00:00Z",
+                "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}],
+                "devices":[{"id":10,"name":"Back door"}]}"#,
+        )
+        .unwrap();
+        let event = interner.event(event.into_owned());
+
+        assert_eq!(2, interner.len());
+        assert!(matches!(event.pets[0].name, Cow::Borrowed("Milo")));
+        assert!(matches!(event.pets[1].name, Cow::Owned(_)));
+        assert!(matches!(event.devices[0].name, Cow::Borrowed("Back door")));
+    }
+}
@@ -0,0 +1,280 @@
+//! Animals without a registered tag at the flaps.
+//!
+//! Flaps report an animal they do not let in, whether its chip is not
+//! paired or it has none, as an
+//! [`EventKind::IntruderMovement`](../timeline/enum.EventKind.html#variant.IntruderMovement);
+//! movements that name no pet are the same thing seen another way.
+//! [`intruders`](fn.intruders.html) turns a run of events into one
+//! [`Intruder`](struct.Intruder.html) per detection, and an
+//! [`IntrusionMonitor`](struct.IntrusionMonitor.html) raises
+//! [`Alert::Intrusions`](../alert/enum.Alert.html#variant.Intrusions) when a
+//! flap sees more of them than a threshold within a window, such as a
+//! neighbour's cat trying the door all night.
+use crate::alert::Alert;
+use crate::id::{DeviceId, TagId};
+use crate::time::Timestamp;
+use crate::timeline::{Event, EventKind};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// One animal without a registered tag at a flap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Intruder {
+    event_id: i64,
+    device_id: Option<DeviceId>,
+    device_name: Option<String>,
+    at: Timestamp,
+    tag_id: Option<TagId>,
+    raw_tag: Option<serde_json::Value>,
+}
+
+impl Intruder {
+    /// The intruders an event records: one per flap movement of an
+    /// intruder event, or of a movement naming no pet, and none for any
+    /// other event.
+    pub fn from_event(event: &Event<'_>) -> Vec<Intruder> {
+        let intruder = match event.kind() {
+            EventKind::IntruderMovement => true,
+            EventKind::Movement => event.pets().is_empty(),
+            _ => false,
+        };
+        if !intruder {
+            return Vec::new();
+        }
+        let name = |device_id: Option<DeviceId>| {
+            let devices = event.devices();
+            devices
+                .iter()
+                .find(|device| Some(device.id()) == device_id)
+                .or(devices.first().filter(|_| devices.len() == 1))
+                .map(|device| device.name().to_string())
+        };
+        let raw_tag = event.other().get("tag").cloned();
+        let mut movements: Vec<(Option<DeviceId>, Option<TagId>)> = event
+            .movements()
+            .iter()
+            .map(|movement| (movement.device_id(), movement.tag_id()))
+            .collect();
+        if movements.is_empty() {
+            let device_id = event.devices().first().map(|device| device.id());
+            movements.push((device_id, None));
+        }
+        movements
+            .into_iter()
+            .map(|(device_id, tag_id)| Intruder {
+                event_id: event.id(),
+                device_id,
+                device_name: name(device_id),
+                at: event.created_at(),
+                tag_id,
+                raw_tag: raw_tag.clone(),
+            })
+            .collect()
+    }
+
+    /// The event the intruder was seen in.
+    pub fn event_id(&self) -> i64 {
+        self.event_id
+    }
+
+    /// The flap, if the event said.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The flap's name, if the event carried it.
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// When the flap saw the animal.
+    pub fn at(&self) -> Timestamp {
+        self.at
+    }
+
+    /// The id of the chip read, `None` when the animal has none or it
+    /// could not be read.
+    pub fn tag_id(&self) -> Option<TagId> {
+        self.tag_id
+    }
+
+    /// Whether a chip was read, so the animal is someone's pet that is not
+    /// paired with the flap.
+    pub fn is_chipped(&self) -> bool {
+        self.tag_id.is_some() || self.raw_tag.is_some()
+    }
+
+    /// The `tag` the event carried, as received, which for an unknown chip
+    /// may hold the chip number.
+    pub fn raw_tag(&self) -> Option<&serde_json::Value> {
+        self.raw_tag.as_ref()
+    }
+}
+
+/// The intruders in a run of events, in order.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::intruder;
+/// use sure_petcare::timeline::Event;
+/// let events: Vec<Event> = serde_json::from_str(
+///     r#"[{"id":1,"type":0,"created_at":"2019-06-01T07:00:00Z",
+///          "pets":[{"id":2,"name":"Milo"}],"movements":[{"device_id":10,"direction":1}]},
+///         {"id":2,"type":7,"created_at":"2019-06-01T07:05:00Z",
+///          "devices":[{"id":10,"name":"Back door"}],"movements":[{"device_id":10,"direction":0}]}]"#,
+/// )?;
+/// let seen: Vec<_> = intruder::intruders(&events).collect();
+/// assert_eq!(1, seen.len());
+/// assert_eq!(Some("Back door"), seen[0].device_name());
+/// assert!(!seen[0].is_chipped());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+pub fn intruders<'e, 'a: 'e, I>(events: I) -> impl Iterator<Item = Intruder> + 'e
+where
+    I: IntoIterator<Item = &'e Event<'a>>,
+    I::IntoIter: 'e,
+{
+    events.into_iter().flat_map(Intruder::from_event)
+}
+
+/// Raises an alert when a flap sees too many intruders, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::intruder::{self, IntrusionMonitor};
+/// use sure_petcare::timeline::Event;
+/// use std::time::Duration;
+/// let events: Vec<Event> = serde_json::from_str(
+///     r#"[{"id":1,"type":7,"created_at":"2019-06-01T01:00:00Z","movements":[{"device_id":10,"direction":0}]},
+///         {"id":2,"type":7,"created_at":"2019-06-01T01:20:00Z","movements":[{"device_id":10,"direction":0}]},
+///         {"id":3,"type":7,"created_at":"2019-06-01T01:40:00Z","movements":[{"device_id":10,"direction":0}]}]"#,
+/// )?;
+/// let mut monitor = IntrusionMonitor::new(3, Duration::from_secs(3600));
+/// let alerts: Vec<_> = intruder::intruders(&events).filter_map(|i| monitor.apply(&i)).collect();
+/// assert_eq!("Unknown animals tried Flap 10 3 times in 1h 00m", alerts[0].to_string());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct IntrusionMonitor {
+    threshold: usize,
+    window: Duration,
+    seen: HashMap<DeviceId, VecDeque<Timestamp>>,
+    names: HashMap<DeviceId, String>,
+    alerted: HashSet<DeviceId>,
+}
+
+impl IntrusionMonitor {
+    /// A monitor alerting when a flap sees `threshold` intruders within
+    /// `window`.
+    pub fn new(threshold: usize, window: Duration) -> Self {
+        IntrusionMonitor {
+            threshold,
+            window,
+            seen: HashMap::new(),
+            names: HashMap::new(),
+            alerted: HashSet::new(),
+        }
+    }
+
+    /// How many intruders a flap has seen within the window up to its last
+    /// one.
+    pub fn count(&self, device_id: DeviceId) -> usize {
+        self.seen.get(&device_id).map_or(0, VecDeque::len)
+    }
+
+    /// Applies an intruder, oldest first, returning an alert when its flap
+    /// reaches the threshold. A flap alerts once until its count falls
+    /// below the threshold again; intruders at no known flap are ignored.
+    pub fn apply(&mut self, intruder: &Intruder) -> Option<Alert> {
+        let device_id = intruder.device_id?;
+        if let Some(name) = intruder.device_name() {
+            self.names.insert(device_id, name.to_string());
+        }
+        let window = self.window;
+        let seen = self.seen.entry(device_id).or_default();
+        if seen.back().is_some_and(|last| intruder.at < *last) {
+            return None;
+        }
+        seen.push_back(intruder.at);
+        while seen
+            .front()
+            .is_some_and(|first| intruder.at.elapsed_since(*first) >= window)
+        {
+            seen.pop_front();
+        }
+        let count = seen.len();
+        if count < self.threshold {
+            self.alerted.remove(&device_id);
+            return None;
+        }
+        if !self.alerted.insert(device_id) {
+            return None;
+        }
+        Some(Alert::Intrusions {
+            device_id,
+            name: self
+                .names
+                .get(&device_id)
+                .cloned()
+                .unwrap_or_else(|| format!("Flap {}", device_id)),
+            count,
+            window: self.window.as_secs() as i64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_alert_once_per_burst_of_intruders() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":7,"created_at":"2019-06-01T01:00:00Z",
+             "devices":[{"id":10,"name":"Back door"}],
+             "movements":[{"device_id":10,"tag_id":44,"direction":0}],"tag":{"tag":"900.000123456789"}},
+            {"id":2,"type":0,"created_at":"2019-06-01T01:10:00Z",
+             "movements":[{"device_id":10,"direction":1}]},
+            {"id":3,"type":7,"created_at":"2019-06-01T01:20:00Z",
+             "movements":[{"device_id":10,"direction":0},{"device_id":11,"direction":0}]},
+            {"id":4,"type":7,"created_at":"2019-06-01T03:00:00Z",
+             "movements":[{"device_id":10,"direction":0}]},
+            {"id":5,"type":7,"created_at":"2019-06-01T03:05:00Z"},
+            {"id":6,"type":7,"created_at":"2019-06-01T03:10:00Z",
+             "movements":[{"device_id":10,"direction":0}]},
+            {"id":7,"type":7,"created_at":"2019-06-01T03:20:00Z",
+             "movements":[{"device_id":10,"direction":0}]}
+        ]"#,
+        )
+        .unwrap();
+
+        let seen: Vec<Intruder> = intruders(&events).collect();
+        assert_eq!(
+            vec![1, 2, 3, 3, 4, 5, 6, 7],
+            seen.iter().map(Intruder::event_id).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(TagId::new(44)), seen[0].tag_id());
+        assert_eq!(
+            Some(&serde_json::json!({"tag": "900.000123456789"})),
+            seen[0].raw_tag()
+        );
+        assert!(seen[0].is_chipped());
+        assert!(!seen[1].is_chipped());
+        assert_eq!(None, seen[5].device_id());
+
+        let mut monitor = IntrusionMonitor::new(3, Duration::from_secs(3600));
+        let alerts: Vec<Alert> = seen.iter().filter_map(|i| monitor.apply(i)).collect();
+        assert_eq!(
+            vec![
+                "Unknown animals tried Back door 3 times in 1h 00m".to_string(),
+                "Unknown animals tried Back door 3 times in 1h 00m".to_string(),
+            ],
+            alerts.iter().map(Alert::to_string).collect::<Vec<_>>()
+        );
+        assert_eq!(3, monitor.count(DeviceId::new(10)));
+        assert_eq!(1, monitor.count(DeviceId::new(11)));
+    }
+}
@@ -0,0 +1,213 @@
+//! Lists that survive malformed items.
+//!
+//! A single item the models cannot parse normally fails the whole response.
+//! [`Lenient`](struct.Lenient.html) parses each item on its own instead, and
+//! keeps the raw value of those that fail so they can be logged or retried
+//! once the crate catches up with the API.
+use serde::de::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt;
+use std::iter::FromIterator;
+
+/// An item that could not be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skipped {
+    raw: Value,
+    error: String,
+}
+
+impl Skipped {
+    /// The item as sent by the API.
+    pub fn raw(&self) -> &Value {
+        &self.raw
+    }
+
+    /// Why the item was rejected.
+    pub fn error(&self) -> &str {
+        &self.error
+    }
+
+    /// The `id` field of the item, when it has one.
+    pub fn id(&self) -> Option<i64> {
+        self.raw.get("id").and_then(Value::as_i64)
+    }
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.id() {
+            Some(id) => write!(f, "item {}: {}", id, self.error),
+            None => write!(f, "item without id: {}", self.error),
+        }
+    }
+}
+
+/// A list whose items were each parsed on their own, in the order sent.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::lenient::Lenient;
+/// use sure_petcare::state::Household;
+/// let list: Lenient<Household<'static>> =
+///     serde_json::from_str(r#"[{"id":1,"name":"Home"},{"id":"two"}]"#).unwrap();
+/// assert_eq!(1, list.parsed().count());
+/// assert_eq!("1 parsed, 1 skipped", list.summary().to_string());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lenient<T> {
+    items: Vec<Result<T, Skipped>>,
+}
+
+impl<T> Lenient<T> {
+    /// Every item, parsed or skipped.
+    pub fn items(&self) -> &[Result<T, Skipped>] {
+        &self.items
+    }
+
+    /// The items that parsed.
+    pub fn parsed(&self) -> impl Iterator<Item = &T> {
+        self.items.iter().filter_map(|item| item.as_ref().ok())
+    }
+
+    /// The items that did not.
+    pub fn skipped(&self) -> impl Iterator<Item = &Skipped> {
+        self.items.iter().filter_map(|item| item.as_ref().err())
+    }
+
+    /// How many items there were in total.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the list was empty.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Counts the parsed and skipped items.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            parsed: self.parsed().count(),
+            skipped_ids: self.skipped().map(Skipped::id).collect(),
+        }
+    }
+
+    /// Appends the items of another list.
+    pub fn extend(&mut self, other: Lenient<T>) {
+        self.items.extend(other.items);
+    }
+
+    /// Splits the list into the parsed and skipped items.
+    pub fn into_parts(self) -> (Vec<T>, Vec<Skipped>) {
+        let mut parsed = Vec::new();
+        let mut skipped = Vec::new();
+        for item in self.items {
+            match item {
+                Ok(item) => parsed.push(item),
+                Err(item) => skipped.push(item),
+            }
+        }
+        (parsed, skipped)
+    }
+}
+
+impl<T> Default for Lenient<T> {
+    fn default() -> Self {
+        Lenient { items: Vec::new() }
+    }
+}
+
+impl<T> FromIterator<Result<T, Skipped>> for Lenient<T> {
+    fn from_iter<I: IntoIterator<Item = Result<T, Skipped>>>(iter: I) -> Self {
+        Lenient {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T> IntoIterator for Lenient<T> {
+    type Item = Result<T, Skipped>;
+    type IntoIter = std::vec::IntoIter<Result<T, Skipped>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+/// Each item is parsed from its own copy of the raw value, so items may be
+/// models that borrow from the input; their strings are copied either way.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Lenient<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        Ok(values
+            .into_iter()
+            .map(|raw| {
+                T::deserialize(raw.clone()).map_err(|error| Skipped {
+                    error: error.to_string(),
+                    raw,
+                })
+            })
+            .collect())
+    }
+}
+
+/// The outcome of parsing a lenient list.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Summary {
+    parsed: usize,
+    skipped_ids: Vec<Option<i64>>,
+}
+
+impl Summary {
+    /// How many items parsed.
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// How many items were skipped.
+    pub fn skipped(&self) -> usize {
+        self.skipped_ids.len()
+    }
+
+    /// The ids of the skipped items, where they had one.
+    pub fn skipped_ids(&self) -> &[Option<i64>] {
+        &self.skipped_ids
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} parsed, {} skipped", self.parsed, self.skipped())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Household;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_keep_raw_values_of_bad_items() {
+        let list: Lenient<Household<'static>> = serde_json::from_str(
+            r#"[{"id":1,"name":"Home"},{"id":2,"name":7},{"name":"No id"},{"id":4,"name":"Barn"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(4, list.len());
+        assert_eq!("2 parsed, 2 skipped", list.summary().to_string());
+        assert_eq!(&[Some(2), None], list.summary().skipped_ids());
+        let (parsed, skipped) = list.into_parts();
+        assert_eq!(
+            vec![1, 4],
+            parsed
+                .iter()
+                .map(|household| household.id().get())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(&serde_json::json!(7), &skipped[0].raw()["name"]);
+        assert!(skipped[1]
+            .to_string()
+            .starts_with("item without id: missing field `id`"));
+    }
+}
@@ -1,2 +1,118 @@
+pub mod alert;
+#[cfg(feature = "reports")]
+pub mod anomaly;
+pub mod app;
+#[cfg(feature = "backup")]
+pub mod backup;
+pub mod batch;
+pub mod battery;
+pub mod blocking;
+pub mod cache;
+pub mod casing;
+#[cfg(feature = "charts")]
+pub mod chart;
 pub mod client;
+pub mod clock;
+pub mod compat;
+pub mod conformance;
+pub mod debug;
+#[cfg(feature = "timeline")]
+pub mod describe;
+pub mod device;
+pub mod diff;
+#[cfg(feature = "reports")]
+pub mod digest;
+pub mod error;
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "fake")]
+pub mod fake;
+#[cfg(feature = "timeline")]
+mod fanout;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+mod float;
+pub mod fold;
+#[cfg(feature = "timeline")]
+pub mod food;
+#[cfg(feature = "timeline")]
+pub mod history;
+#[cfg(feature = "timeline")]
+mod hmac;
+pub mod holiday;
+pub mod humanize;
+pub mod id;
+#[cfg(feature = "timeline")]
+pub mod intake;
+#[cfg(feature = "timeline")]
+pub mod intern;
+#[cfg(feature = "timeline")]
+pub mod intruder;
+pub mod lenient;
+#[cfg(feature = "live-tests")]
+pub mod live;
+#[cfg(feature = "local")]
+pub mod local;
 pub mod login;
+pub mod missing;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+mod names;
+pub mod notes;
+#[cfg(feature = "notifications")]
+pub mod notification;
+pub mod offline;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod patch;
+pub mod pet;
+pub mod photo;
+pub mod portion;
+pub mod product;
+#[cfg(feature = "timeline")]
+pub mod proto;
+#[cfg(feature = "notifications")]
+pub mod push;
+#[cfg(feature = "timeline")]
+pub mod query;
+pub mod ratelimit;
+#[cfg(feature = "reports")]
+pub mod report;
+#[cfg(feature = "runner")]
+pub mod runner;
+pub mod schedule;
+#[cfg(feature = "schemars")]
+pub mod schema;
+mod secret;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+#[cfg(feature = "timeline")]
+pub mod sink;
+pub mod sort;
+pub mod state;
+#[cfg(feature = "reports")]
+pub mod stats;
+#[cfg(feature = "timeline")]
+pub mod stream;
+pub mod sun;
+#[cfg(feature = "timeline")]
+pub mod sync;
+pub mod tag;
+pub mod time;
+#[cfg(feature = "timeline")]
+pub mod timeline;
+pub mod token;
+pub mod transport;
+#[cfg(feature = "reports")]
+pub mod trend;
+pub mod units;
+pub mod unknown;
+#[cfg(feature = "timeline")]
+pub mod watcher;
+#[cfg(feature = "timeline")]
+pub mod water;
+#[cfg(feature = "timeline")]
+pub mod webhook;
+pub mod zone;
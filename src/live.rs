@@ -0,0 +1,269 @@
+//! Checks against the real API, enabled with the `live-tests` feature.
+//!
+//! [`run`](fn.run.html) logs in, calls every read only endpoint and
+//! compares each response with the models using
+//! [`conformance::check`](../conformance/fn.check.html). Nothing is changed
+//! on the account. The `tests/live.rs` test runs it with credentials from
+//! the environment:
+//!
+//! ```text
+//! SUREPET_EMAIL=me@example.com SUREPET_PASSWORD=... cargo test --features live-tests --test live -- --nocapture
+//! ```
+use crate::client::SurePet;
+use crate::conformance::{self, SchemaDiff};
+use crate::error::Result;
+use crate::login;
+use crate::secret::Secret;
+use crate::transport::{Request, Response, Transport};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::sync::Mutex;
+
+/// How to sign in. The `Debug` output redacts the token, email address and
+/// password.
+#[derive(Clone, PartialEq)]
+pub enum Credentials {
+    /// A bearer token from an earlier login.
+    Token(String),
+    /// An email address and password, with the device id to log in as.
+    Login {
+        /// The account email address.
+        email: String,
+        /// The account password.
+        password: String,
+        /// The device id to log in as.
+        device_id: String,
+    },
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Token(token) => f.debug_tuple("Token").field(&Secret(token)).finish(),
+            Credentials::Login {
+                email,
+                password,
+                device_id,
+            } => f
+                .debug_struct("Login")
+                .field("email", &Secret(email))
+                .field("password", &Secret(password))
+                .field("device_id", device_id)
+                .finish(),
+        }
+    }
+}
+
+impl Credentials {
+    /// Reads `SUREPET_TOKEN`, or else `SUREPET_EMAIL` and `SUREPET_PASSWORD`
+    /// with an optional `SUREPET_DEVICE_ID`. `None` when neither is set, so
+    /// callers can skip the checks.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(token) = env::var("SUREPET_TOKEN") {
+            return Some(Credentials::Token(token));
+        }
+        Some(Credentials::Login {
+            email: env::var("SUREPET_EMAIL").ok()?,
+            password: env::var("SUREPET_PASSWORD").ok()?,
+            device_id: env::var("SUREPET_DEVICE_ID")
+                .unwrap_or_else(|_| "sure-petcare-live-tests".into()),
+        })
+    }
+}
+
+/// The result of calling one endpoint.
+#[derive(Debug)]
+pub struct Outcome {
+    endpoint: String,
+    result: Result<Vec<SchemaDiff>>,
+}
+
+impl Outcome {
+    /// The path called, such as `/api/pet`.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The fields the response and the models disagree on, or the error
+    /// that stopped the response from parsing at all.
+    pub fn result(&self) -> &Result<Vec<SchemaDiff>> {
+        &self.result
+    }
+
+    /// Whether the response parsed into the models.
+    pub fn is_compatible(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(diffs) if diffs.is_empty() => write!(f, "{}: ok", self.endpoint),
+            Ok(diffs) => {
+                write!(f, "{}: {} differences", self.endpoint, diffs.len())?;
+                for diff in diffs {
+                    write!(f, "\n  {}", diff)?;
+                }
+                Ok(())
+            }
+            Err(error) => write!(f, "{}: {}", self.endpoint, error),
+        }
+    }
+}
+
+/// Signs in and checks every read only endpoint. Only a failed login is an
+/// error; endpoints that fail are reported in their outcome.
+pub fn run<T: Transport>(transport: T, credentials: &Credentials) -> Result<Vec<Outcome>> {
+    let capture = Capture {
+        inner: transport,
+        body: Mutex::new(Vec::new()),
+    };
+    let client = match credentials {
+        Credentials::Token(token) => SurePet::new(&capture, token.as_str()),
+        Credentials::Login {
+            email,
+            password,
+            device_id,
+        } => {
+            let request = login::RequestBuilder::new()
+                .with_email_address(email.as_str())
+                .with_password(password.as_str())
+                .with_device_id(device_id.as_str())
+                .build();
+            SurePet::login(&capture, &request)?
+        }
+    };
+
+    let mut outcomes = Vec::new();
+    let start = probe(&capture, "/api/me/start", || client.start());
+    let state = start.model;
+    outcomes.push(start.outcome);
+    outcomes.push(probe(&capture, "/api/pet", || client.pets().list()).outcome);
+    outcomes.push(probe(&capture, "/api/device", || client.devices().list()).outcome);
+    outcomes.push(
+        probe(&capture, "/api/notification", || {
+            client.notifications().list()
+        })
+        .outcome,
+    );
+    outcomes.push(
+        probe(&capture, "/api/notification/preference", || {
+            client.notifications().preferences()
+        })
+        .outcome,
+    );
+    if let Some(state) = state {
+        for household in state.households() {
+            let path = format!("/api/timeline/household/{}", household.id());
+            outcomes.push(
+                probe(&capture, &path, || {
+                    client.timeline().household(household.id())
+                })
+                .outcome,
+            );
+            if let Some(pet) = state.pets().first() {
+                let path = format!(
+                    "/api/report/household/{}/pet/{}/aggregate",
+                    household.id(),
+                    pet.id()
+                );
+                outcomes.push(
+                    probe(&capture, &path, || {
+                        client.reports().pet(household.id(), pet.id())
+                    })
+                    .outcome,
+                );
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+/// An outcome along with the parsed model, for endpoints whose data the
+/// harness needs later on.
+struct Probe<M> {
+    outcome: Outcome,
+    model: Option<M>,
+}
+
+fn probe<T, M, F>(capture: &Capture<T>, endpoint: &str, call: F) -> Probe<M>
+where
+    T: Transport,
+    M: Deserialize<'static> + Serialize,
+    F: FnOnce() -> Result<M>,
+{
+    let result = call();
+    let body = std::mem::take(&mut *capture.body.lock().unwrap());
+    let (result, model) = match result {
+        Ok(model) => (
+            conformance::check::<M>(&String::from_utf8_lossy(&body)),
+            Some(model),
+        ),
+        Err(error) => (Err(error), None),
+    };
+    Probe {
+        outcome: Outcome {
+            endpoint: endpoint.to_string(),
+            result,
+        },
+        model,
+    }
+}
+
+/// Keeps the body of the last response for comparison.
+struct Capture<T> {
+    inner: T,
+    body: Mutex<Vec<u8>>,
+}
+
+impl<T: Transport> Transport for Capture<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let response = self.inner.send(request)?;
+        *self.body.lock().unwrap() = response.body.clone();
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_check_every_endpoint() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"households":[{"id":1,"name":"Home"}],"pets":[{"id":2,"name":"Milo"}]}}"#,
+            )
+            .respond(200, r#"{"data":[{"id":2,"name":"Milo","gender":1}]}"#)
+            .respond(200, r#"{"data":[]}"#)
+            .respond(500, "oops")
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":[]}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let outcomes = run(&transport, &Credentials::Token("token".into())).unwrap();
+
+        assert_eq!(
+            vec![
+                "/api/me/start",
+                "/api/pet",
+                "/api/device",
+                "/api/notification",
+                "/api/notification/preference",
+                "/api/timeline/household/1",
+                "/api/report/household/1/pet/2/aggregate",
+            ],
+            outcomes.iter().map(Outcome::endpoint).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "/api/pet: 1 differences\n  gender is not modelled",
+            outcomes[1].to_string()
+        );
+        assert!(!outcomes[3].is_compatible());
+        assert_eq!(7, transport.requests().len());
+    }
+}
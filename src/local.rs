@@ -0,0 +1,755 @@
+//! Reading the hub's own messages, without the cloud, enabled with the
+//! `local` feature.
+//!
+//! The hub keeps an MQTT connection to the cloud. With its broker hostname
+//! pointed at a broker of your own by DNS, the messages it publishes can be
+//! read on the local network; their framing is documented by the community.
+//! Like the [`mqtt`](../mqtt/index.html) bridge, this module does not ship
+//! an MQTT stack: hand [`LocalHub::receive`](struct.LocalHub.html#method.receive)
+//! the topic and payload of each message the hub publishes, and it returns
+//! the pet movements they describe as they happen, whether or not the cloud
+//! is reachable.
+//!
+//! Each payload is a line of fields separated by spaces:
+//!
+//! ```text
+//! 5cf22ad0 0f20 127 18 15 cd 5b 07 00 e1 01
+//! ```
+//!
+//! a hex timestamp, a hex message counter, the message type and its
+//! arguments. Type `132` reports hub registers as decimal numbers: the
+//! register, how many values follow and the values. Type `127` carries a
+//! device message as hex bytes, the first naming the operation. Movement
+//! messages, operation `18`, hold the microchip in the next six bytes,
+//! least significant first, and then the direction, coded as in the
+//! timeline. Types and operations this module does not know are passed on
+//! undecoded.
+//!
+//! To take the hub's connection directly, a [`LocalServer`](struct.LocalServer.html)
+//! stands in for the broker and turns movements into the same
+//! [`Event`](../timeline/struct.Event.html)s the timeline returns, while
+//! [`LocalHub::update_devices`](struct.LocalHub.html#method.update_devices)
+//! brings [`Device`](../device/struct.Device.html)s up to date with when
+//! each flap was last heard from. Local and cloud data can then go through
+//! the same code.
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::id::{DeviceId, PetId};
+use crate::pet::{Location, Position};
+use crate::time::Timestamp;
+use crate::timeline::{Direction, Event, EventKind, MovementEntry, PetRef};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+
+/// The device message operation for a pet going through a flap.
+pub const MOVEMENT: u8 = 0x18;
+
+/// The error returned when a hub message fails to decode.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FrameError {
+    input: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hub message `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// A microchip number, shown as the country code and national number
+/// like `900.000123456789`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Chip {
+    country: u16,
+    national: u64,
+}
+
+impl Chip {
+    /// A chip number, `None` if either part is out of range.
+    pub fn new(country: u16, national: u64) -> Option<Self> {
+        if country >= 1 << 10 || national >= 1 << 38 {
+            return None;
+        }
+        Some(Chip { country, national })
+    }
+
+    /// The country or manufacturer code.
+    pub fn country(self) -> u16 {
+        self.country
+    }
+
+    /// The national number.
+    pub fn national(self) -> u64 {
+        self.national
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let raw = bytes
+            .iter()
+            .rev()
+            .fold(0u64, |raw, byte| raw << 8 | u64::from(*byte));
+        Chip {
+            country: (raw >> 38 & 0x3ff) as u16,
+            national: raw & ((1 << 38) - 1),
+        }
+    }
+}
+
+impl fmt::Display for Chip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:03}.{:012}", self.country, self.national)
+    }
+}
+
+impl FromStr for Chip {
+    type Err = FrameError;
+
+    /// Parses a chip number written like `900.000123456789`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let error = || FrameError {
+            input: s.into(),
+            reason: "not a chip number",
+        };
+        let (country, national) = s.split_once('.').ok_or_else(error)?;
+        let country = country.parse().map_err(|_| error())?;
+        let national = national.parse().map_err(|_| error())?;
+        Chip::new(country, national).ok_or_else(error)
+    }
+}
+
+/// What a hub message says.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    /// Hub register values, message type `132`.
+    Registers {
+        /// The first register.
+        register: u16,
+        /// The values from that register on.
+        values: Vec<u8>,
+    },
+    /// A message from a device, message type `127`.
+    Device {
+        /// The operation, such as [`MOVEMENT`](constant.MOVEMENT.html).
+        op: u8,
+        /// The bytes after the operation.
+        data: Vec<u8>,
+    },
+    /// A message type this module does not decode.
+    Unknown {
+        /// The message type.
+        code: u16,
+        /// The arguments as sent.
+        arguments: Vec<String>,
+    },
+}
+
+/// A pet going through a flap, decoded from a movement message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Movement {
+    chip: Chip,
+    direction: Direction,
+}
+
+impl Movement {
+    /// The microchip read.
+    pub fn chip(&self) -> Chip {
+        self.chip
+    }
+
+    /// Which way the pet went.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+}
+
+/// One decoded hub message, see the module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::local::Frame;
+/// use sure_petcare::timeline::Direction;
+/// let frame: Frame = "5cf22ad0 0f20 127 18 15 cd 5b 07 00 e1 01".parse()?;
+/// assert_eq!("2019-06-01T07:35:44+00:00", frame.timestamp().to_string());
+/// let movement = frame.movement().unwrap();
+/// assert_eq!("900.000123456789", movement.chip().to_string());
+/// assert_eq!(Direction::In, movement.direction());
+/// # Ok::<(), sure_petcare::local::FrameError>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    timestamp: Timestamp,
+    counter: u32,
+    kind: FrameKind,
+}
+
+impl Frame {
+    /// When the hub sent the message.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// The hub's message counter, repeated when a message is sent again.
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    /// What the message says.
+    pub fn kind(&self) -> &FrameKind {
+        &self.kind
+    }
+
+    /// The movement the message reports, if it is a movement message.
+    pub fn movement(&self) -> Option<Movement> {
+        match &self.kind {
+            FrameKind::Device { op: MOVEMENT, data } if data.len() >= 7 => Some(Movement {
+                chip: Chip::from_bytes(&data[..6]),
+                direction: Direction::from(i64::from(data[6])),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Frame {
+    type Err = FrameError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let error = |reason| FrameError {
+            input: s.into(),
+            reason,
+        };
+        let mut fields = s.split_whitespace();
+        let mut next = |reason| fields.next().ok_or_else(|| error(reason));
+        let timestamp = i64::from_str_radix(next("missing timestamp")?, 16)
+            .map_err(|_| error("bad timestamp"))?;
+        let counter =
+            u32::from_str_radix(next("missing counter")?, 16).map_err(|_| error("bad counter"))?;
+        let code: u16 = next("missing message type")?
+            .parse()
+            .map_err(|_| error("bad message type"))?;
+        let arguments: Vec<&str> = fields.collect();
+        let kind = match code {
+            132 => {
+                let numbers = arguments
+                    .iter()
+                    .map(|argument| argument.parse::<u16>())
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| error("bad register"))?;
+                match numbers.as_slice() {
+                    [register, count, values @ ..] if values.len() == usize::from(*count) => {
+                        FrameKind::Registers {
+                            register: *register,
+                            values: values
+                                .iter()
+                                .map(|value| u8::try_from(*value))
+                                .collect::<std::result::Result<_, _>>()
+                                .map_err(|_| error("bad register value"))?,
+                        }
+                    }
+                    _ => return Err(error("bad register count")),
+                }
+            }
+            127 => {
+                let bytes = arguments
+                    .iter()
+                    .map(|argument| u8::from_str_radix(argument, 16))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|_| error("bad device message"))?;
+                match bytes.split_first() {
+                    Some((op, data)) => FrameKind::Device {
+                        op: *op,
+                        data: data.to_vec(),
+                    },
+                    None => return Err(error("empty device message")),
+                }
+            }
+            code => FrameKind::Unknown {
+                code,
+                arguments: arguments.into_iter().map(String::from).collect(),
+            },
+        };
+        Ok(Frame {
+            timestamp: Timestamp::from_unix(timestamp),
+            counter,
+            kind,
+        })
+    }
+}
+
+/// A pet movement seen by a flap, with the cloud ids it is known by.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LocalEvent {
+    source: String,
+    device_id: Option<DeviceId>,
+    pet_id: Option<PetId>,
+    movement: Movement,
+    at: Timestamp,
+}
+
+impl LocalEvent {
+    /// The last segment of the topic the message came on, which names the
+    /// flap.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The flap, if its topic was registered.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The pet, if its chip was registered. Unregistered chips are
+    /// intruders.
+    pub fn pet_id(&self) -> Option<PetId> {
+        self.pet_id
+    }
+
+    /// The movement.
+    pub fn movement(&self) -> Movement {
+        self.movement
+    }
+
+    /// When the flap saw it.
+    pub fn at(&self) -> Timestamp {
+        self.at
+    }
+
+    /// Where the pet is now, `None` when it only looked through.
+    pub fn position(&self) -> Option<Position> {
+        let location = match self.movement.direction {
+            Direction::In => Location::Inside,
+            Direction::Out => Location::Outside,
+            _ => return None,
+        };
+        Some(Position {
+            device_id: self.device_id,
+            location,
+            since: self.at,
+        })
+    }
+
+    /// The movement as a timeline event, for code written against the
+    /// cloud such as a [`StateSync`](../sync/struct.StateSync.html). Local
+    /// events have no id or household, and a chip that is not registered
+    /// becomes an intruder movement.
+    pub fn to_event(&self) -> Event<'static> {
+        Event {
+            id: 0,
+            kind: match self.pet_id {
+                Some(_) => EventKind::Movement,
+                None => EventKind::IntruderMovement,
+            },
+            created_at: self.at,
+            household_id: None,
+            pets: self
+                .pet_id
+                .map(|id| PetRef {
+                    id,
+                    name: Cow::Borrowed(""),
+                })
+                .into_iter()
+                .collect(),
+            devices: Vec::new(),
+            movements: vec![MovementEntry {
+                device_id: self.device_id,
+                tag_id: None,
+                direction: self.movement.direction,
+            }],
+            weights: Vec::new(),
+            other: serde_json::Map::new(),
+        }
+    }
+}
+
+/// Turns the messages a hub publishes into pet movements, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::id::{DeviceId, PetId};
+/// use sure_petcare::local::LocalHub;
+/// let mut hub = LocalHub::new()
+///     .with_device("0000801f12d3a4b5", DeviceId::new(10))
+///     .with_pet("900.000123456789".parse()?, PetId::new(2));
+/// let topic = "pethublocal/messages/0000801f12d3a4b5";
+/// let payload = b"5cf22ad0 0f20 127 18 15 cd 5b 07 00 e1 02";
+/// let event = hub.receive(topic, payload)?.unwrap();
+/// assert_eq!((Some(DeviceId::new(10)), Some(PetId::new(2))), (event.device_id(), event.pet_id()));
+/// // The hub sends messages again until they are acknowledged.
+/// assert_eq!(None, hub.receive(topic, payload)?);
+/// # Ok::<(), sure_petcare::local::FrameError>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LocalHub {
+    devices: HashMap<String, DeviceId>,
+    pets: HashMap<Chip, PetId>,
+    counters: HashMap<String, u32>,
+    seen: HashMap<String, Timestamp>,
+}
+
+impl LocalHub {
+    /// A hub that knows no flaps or pets yet.
+    pub fn new() -> Self {
+        LocalHub::default()
+    }
+
+    /// Names the flap whose messages come on topics ending in `source`,
+    /// usually its MAC address.
+    pub fn with_device(mut self, source: &str, device_id: DeviceId) -> Self {
+        self.devices.insert(source.to_string(), device_id);
+        self
+    }
+
+    /// Names the pet wearing a chip.
+    pub fn with_pet(mut self, chip: Chip, pet_id: PetId) -> Self {
+        self.pets.insert(chip, pet_id);
+        self
+    }
+
+    /// Decodes a message, returning the movement it reports. Messages that
+    /// are not movements, and movements sent again with the same counter,
+    /// return `None`.
+    pub fn receive(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+    ) -> std::result::Result<Option<LocalEvent>, FrameError> {
+        let text = String::from_utf8_lossy(payload);
+        let frame: Frame = text.trim().parse()?;
+        let source = topic.rsplit('/').next().unwrap_or(topic).to_string();
+        let seen = self.seen.entry(source.clone()).or_insert(frame.timestamp());
+        *seen = (*seen).max(frame.timestamp());
+        let movement = match frame.movement() {
+            Some(movement) => movement,
+            None => return Ok(None),
+        };
+        if self.counters.insert(source.clone(), frame.counter()) == Some(frame.counter()) {
+            return Ok(None);
+        }
+        Ok(Some(LocalEvent {
+            device_id: self.devices.get(&source).copied(),
+            pet_id: self.pets.get(&movement.chip).copied(),
+            source,
+            movement,
+            at: frame.timestamp(),
+        }))
+    }
+
+    /// Marks the registered flaps heard from as online, moving their last
+    /// activity up to the newest message, so devices loaded from the cloud
+    /// reflect what the hub says locally.
+    pub fn update_devices(&self, devices: &mut [Device<'_>]) {
+        for (source, seen) in &self.seen {
+            let device_id = match self.devices.get(source) {
+                Some(device_id) => *device_id,
+                None => continue,
+            };
+            for device in devices.iter_mut().filter(|device| device.id == device_id) {
+                device.status.online = Some(true);
+                if device.last_activity_at.is_none_or(|last| last < *seen) {
+                    device.last_activity_at = Some(*seen);
+                }
+            }
+        }
+    }
+}
+
+/// Accepts the hub's connection in place of the cloud broker, see
+/// [`Session`](struct.Session.html).
+///
+/// The hub connects with TLS on port 8883, which this crate does not speak:
+/// terminate it in front of the server, with stunnel for instance, and
+/// point the hub's broker hostname at that.
+#[derive(Debug)]
+pub struct LocalServer {
+    listener: TcpListener,
+    hub: LocalHub,
+}
+
+impl LocalServer {
+    /// Listens on `address`, decoding messages with `hub`.
+    pub fn bind<A: ToSocketAddrs>(address: A, hub: LocalHub) -> Result<Self> {
+        Ok(LocalServer {
+            listener: TcpListener::bind(address).map_err(Error::transport)?,
+            hub,
+        })
+    }
+
+    /// The address listened on.
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().map_err(Error::transport)
+    }
+
+    /// The hub decoding messages, which knows when each flap was last
+    /// heard from.
+    pub fn hub(&self) -> &LocalHub {
+        &self.hub
+    }
+
+    /// Waits for the hub to connect.
+    pub fn accept(&mut self) -> Result<Session<'_>> {
+        let (stream, _) = self.listener.accept().map_err(Error::transport)?;
+        Ok(Session {
+            stream,
+            hub: &mut self.hub,
+            skipped: 0,
+        })
+    }
+
+    /// Serves connections one after another for as long as the listener
+    /// works, calling `on_event` with each movement.
+    pub fn serve<F: FnMut(Event<'static>)>(&mut self, mut on_event: F) -> Result<()> {
+        loop {
+            let mut session = self.accept()?;
+            // A dropped connection ends the session; the hub reconnects.
+            while let Ok(Some(event)) = session.next_event() {
+                on_event(event);
+            }
+        }
+    }
+}
+
+/// One connection from the hub.
+///
+/// The server answers the hub as a broker would, speaking as much MQTT
+/// 3.1.1 as the hub uses: connecting, subscribing, publishing at any
+/// quality of service and pinging. What the hub publishes is decoded with
+/// the [`LocalHub`](struct.LocalHub.html); messages that do not decode are
+/// counted and skipped.
+#[derive(Debug)]
+pub struct Session<'s> {
+    stream: TcpStream,
+    hub: &'s mut LocalHub,
+    skipped: usize,
+}
+
+impl Session<'_> {
+    /// Reads until the hub reports a movement, returning `None` once it
+    /// disconnects.
+    pub fn next_event(&mut self) -> Result<Option<Event<'static>>> {
+        loop {
+            let (header, body) = match read_packet(&mut self.stream)? {
+                Some(packet) => packet,
+                None => return Ok(None),
+            };
+            let reply = match header >> 4 {
+                CONNECT => vec![0x20, 2, 0, 0],
+                PUBLISH => {
+                    let qos = header >> 1 & 3;
+                    let (topic, rest) = split_string(&body).ok_or_else(malformed)?;
+                    let (id, payload) = match qos {
+                        0 => (None, rest),
+                        _ if rest.len() >= 2 => (Some([rest[0], rest[1]]), &rest[2..]),
+                        _ => return Err(malformed()),
+                    };
+                    if let Some([high, low]) = id {
+                        let kind = if qos == 1 { 0x40 } else { 0x50 };
+                        self.write(&[kind, 2, high, low])?;
+                    }
+                    match self.hub.receive(&topic, payload) {
+                        Ok(Some(event)) => return Ok(Some(event.to_event())),
+                        Ok(None) => {}
+                        Err(_) => self.skipped += 1,
+                    }
+                    continue;
+                }
+                PUBREL if body.len() >= 2 => vec![0x70, 2, body[0], body[1]],
+                SUBSCRIBE if body.len() >= 2 => {
+                    let mut filters = &body[2..];
+                    let mut reply = vec![0x90, 0, body[0], body[1]];
+                    while let Some((_, rest)) = split_string(filters) {
+                        filters = rest.get(1..).ok_or_else(malformed)?;
+                        reply.push(0);
+                    }
+                    reply[1] = u8::try_from(reply.len() - 2).map_err(|_| malformed())?;
+                    reply
+                }
+                PINGREQ => vec![0xd0, 0],
+                DISCONNECT => return Ok(None),
+                _ => continue,
+            };
+            self.write(&reply)?;
+        }
+    }
+
+    /// How many published messages failed to decode.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    fn write(&mut self, packet: &[u8]) -> Result<()> {
+        self.stream.write_all(packet).map_err(Error::transport)
+    }
+}
+
+const CONNECT: u8 = 1;
+const PUBLISH: u8 = 3;
+const PUBREL: u8 = 6;
+const SUBSCRIBE: u8 = 8;
+const PINGREQ: u8 = 12;
+const DISCONNECT: u8 = 14;
+
+fn malformed() -> Error {
+    Error::transport("malformed MQTT packet from the hub")
+}
+
+/// Reads a packet's first byte and body, `None` when the connection closed
+/// between packets.
+fn read_packet<R: Read>(reader: &mut R) -> Result<Option<(u8, Vec<u8>)>> {
+    let mut byte = [0];
+    if reader.read(&mut byte).map_err(Error::transport)? == 0 {
+        return Ok(None);
+    }
+    let header = byte[0];
+    let mut length = 0usize;
+    for shift in (0..4).map(|i| i * 7) {
+        reader.read_exact(&mut byte).map_err(Error::transport)?;
+        length |= usize::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).map_err(Error::transport)?;
+            return Ok(Some((header, body)));
+        }
+    }
+    Err(malformed())
+}
+
+/// Splits a length prefixed string off the front of `bytes`.
+fn split_string(bytes: &[u8]) -> Option<(String, &[u8])> {
+    let length = usize::from(u16::from_be_bytes([*bytes.first()?, *bytes.get(1)?]));
+    let text = bytes.get(2..2 + length)?;
+    Some((
+        String::from_utf8_lossy(text).into_owned(),
+        &bytes[2 + length..],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_decode_hub_messages() {
+        let registers: Frame = "5cf22ad0 0001 132 33 3 1 0 255".parse().unwrap();
+        assert_eq!(
+            &FrameKind::Registers {
+                register: 33,
+                values: vec![1, 0, 255],
+            },
+            registers.kind()
+        );
+        assert_eq!(None, registers.movement());
+
+        let status: Frame = "5cf22ad0 0002 127 0b 01 02".parse().unwrap();
+        assert_eq!(
+            &FrameKind::Device {
+                op: 0x0b,
+                data: vec![1, 2],
+            },
+            status.kind()
+        );
+        let other: Frame = "5cf22ad0 0003 2 hello".parse().unwrap();
+        assert!(matches!(other.kind(), FrameKind::Unknown { code: 2, .. }));
+
+        assert!("5cf22ad0 0004 132 33 2 1".parse::<Frame>().is_err());
+        assert!("5cf22ad0 0004 127".parse::<Frame>().is_err());
+        assert!("later 0004 127 18".parse::<Frame>().is_err());
+        assert_eq!(None, Chip::new(1024, 0));
+    }
+
+    #[test]
+    fn should_report_movements_once() {
+        let mut hub = LocalHub::new().with_pet("900.000123456789".parse().unwrap(), PetId::new(2));
+
+        let out = hub
+            .receive("hub/flap", b"5cf22ad0 0f20 127 18 15 cd 5b 07 00 e1 02\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(PetId::new(2)), out.pet_id());
+        assert_eq!(None, out.device_id());
+        assert_eq!("flap", out.source());
+        assert_eq!(Location::Outside, out.position().unwrap().location());
+
+        let intruder = hub
+            .receive("hub/flap", b"5cf22ad1 0f21 127 18 01 00 00 00 00 00 00")
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, intruder.pet_id());
+        assert_eq!(None, intruder.position());
+        assert_eq!(
+            None,
+            hub.receive("hub/flap", b"5cf22ad1 0f22 132 33 0").unwrap()
+        );
+    }
+
+    #[test]
+    fn should_serve_the_hub_as_a_broker() {
+        use std::net::Shutdown;
+        use std::thread;
+
+        let hub = LocalHub::new()
+            .with_device("flap", DeviceId::new(10))
+            .with_pet("900.000123456789".parse().unwrap(), PetId::new(2));
+        let mut server = LocalServer::bind("127.0.0.1:0", hub).unwrap();
+        let address = server.local_addr().unwrap();
+        let publish = |qos: u8, payload: &str| {
+            let mut body = vec![0, 8];
+            body.extend(b"hub/flap");
+            if qos > 0 {
+                body.extend(&[0, 7]);
+            }
+            body.extend(payload.as_bytes());
+            let mut packet = vec![0x30 | qos << 1, body.len() as u8];
+            packet.extend(body);
+            packet
+        };
+        let hub = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut packets = vec![0x10, 2, 0, 4];
+            packets.extend(&[0x82, 9, 0, 1, 0, 4, b'h', b'u', b'b', b'/', 1]);
+            packets.extend(publish(1, "5cf22ad0 0f20 127 18 15 cd 5b 07 00 e1 02"));
+            packets.extend(publish(0, "not a frame"));
+            packets.extend(publish(0, "5cf22ad9 0f21 132 33 0"));
+            packets.extend(&[0xc0, 0, 0xe0, 0]);
+            stream.write_all(&packets).unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut replies = Vec::new();
+            stream.read_to_end(&mut replies).unwrap();
+            replies
+        });
+
+        let mut session = server.accept().unwrap();
+        let event = session.next_event().unwrap().unwrap();
+        assert_eq!(None, session.next_event().unwrap());
+        assert_eq!(1, session.skipped());
+        drop(session);
+        let replies = hub.join().unwrap();
+        assert_eq!(
+            vec![0x20, 2, 0, 0, 0x90, 3, 0, 1, 0, 0x40, 2, 0, 7, 0xd0, 0],
+            replies
+        );
+
+        assert_eq!(EventKind::Movement, event.kind());
+        assert_eq!(PetId::new(2), event.pets()[0].id());
+        assert_eq!(Some(DeviceId::new(10)), event.movements()[0].device_id());
+        assert_eq!(Direction::Out, event.movements()[0].direction());
+
+        let mut devices: Vec<Device<'static>> =
+            serde_json::from_str(r#"[{"id":10,"name":"Back door","product_id":6}]"#).unwrap();
+        server.hub().update_devices(&mut devices);
+        assert_eq!(Some(true), devices[0].status().online());
+        assert_eq!(
+            Some("2019-06-01T07:35:53+00:00".to_string()),
+            devices[0].last_activity_at().map(|at| at.to_string())
+        );
+    }
+}
@@ -1,9 +1,13 @@
 //! Builder and struct for representing login request and response.
+use crate::secret::Secret;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
 
-/// A struct that represents a login request.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A struct that represents a login request. Its `Debug` output redacts
+/// the email address and password.
+#[derive(Clone, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Request<'a> {
     /// The users email address.
     email_address: Cow<'a, str>,
@@ -17,6 +21,27 @@ pub struct Request<'a> {
     device_id: Cow<'a, str>,
 }
 
+impl<'a> Request<'a> {
+    /// Copies any borrowed fields, so the request can be kept.
+    pub fn into_owned(self) -> Request<'static> {
+        Request {
+            email_address: Cow::Owned(self.email_address.into_owned()),
+            password: Cow::Owned(self.password.into_owned()),
+            device_id: Cow::Owned(self.device_id.into_owned()),
+        }
+    }
+}
+
+impl fmt::Debug for Request<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("email_address", &Secret(&self.email_address))
+            .field("password", &Secret(&self.password))
+            .field("device_id", &self.device_id)
+            .finish()
+    }
+}
+
 /// A builder to help with the creation of a login Request.
 /// # Examples
 ///
@@ -47,11 +72,120 @@ pub struct Request<'a> {
 ///     serialized
 /// );
 /// ```
-#[derive(Clone, Debug, Default)]
-pub struct RequestBuilder<'a> {
+///
+/// [`required`](#method.required) starts a builder that only offers `build`
+/// once every field is set, so a forgotten field fails to compile:
+///
+/// ```compile_fail
+/// use sure_petcare::login;
+/// let item = login::RequestBuilder::required()
+///     .email_address("email@example.com")
+///     .device_id("xxx-xxx-xxx-xxx")
+///     .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct RequestBuilder<'a, S = Unchecked> {
     email_address: Cow<'a, str>,
     password: Cow<'a, str>,
     device_id: Cow<'a, str>,
+    state: PhantomData<S>,
+}
+
+impl<S> fmt::Debug for RequestBuilder<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBuilder")
+            .field("email_address", &Secret(&self.email_address))
+            .field("password", &Secret(&self.password))
+            .field("device_id", &self.device_id)
+            .finish()
+    }
+}
+
+/// The state of a builder whose fields may be set in any order, or not at
+/// all.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unchecked;
+
+/// The state of a required builder that needs an email address next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsEmail;
+
+/// The state of a required builder that needs a password next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsPassword;
+
+/// The state of a required builder that needs a device id next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsDeviceId;
+
+/// The state of a required builder with every field set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ready;
+
+impl<'a> RequestBuilder<'a, NeedsEmail> {
+    /// Create a new builder that takes the email address, password and
+    /// device id in that order before it can build.
+    pub fn required() -> Self {
+        RequestBuilder {
+            email_address: Cow::Borrowed(""),
+            password: Cow::Borrowed(""),
+            device_id: Cow::Borrowed(""),
+            state: PhantomData,
+        }
+    }
+
+    /// Sets the email address.
+    pub fn email_address<T: Into<Cow<'a, str>>>(
+        self,
+        email_address: T,
+    ) -> RequestBuilder<'a, NeedsPassword> {
+        RequestBuilder {
+            email_address: email_address.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, NeedsPassword> {
+    /// Sets the password.
+    pub fn password<T: Into<Cow<'a, str>>>(self, password: T) -> RequestBuilder<'a, NeedsDeviceId> {
+        RequestBuilder {
+            password: password.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, NeedsDeviceId> {
+    /// Sets the device id.
+    pub fn device_id<T: Into<Cow<'a, str>>>(self, device_id: T) -> RequestBuilder<'a, Ready> {
+        RequestBuilder {
+            device_id: device_id.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, Ready> {
+    /// Builds the request.
+    pub fn build(self) -> Request<'a> {
+        Request {
+            email_address: self.email_address,
+            password: self.password,
+            device_id: self.device_id,
+        }
+    }
+}
+
+impl<'a, S> RequestBuilder<'a, S> {
+    fn into_state<N>(self) -> RequestBuilder<'a, N> {
+        RequestBuilder {
+            email_address: self.email_address,
+            password: self.password,
+            device_id: self.device_id,
+            state: PhantomData,
+        }
+    }
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -81,24 +215,39 @@ impl<'a> RequestBuilder<'a> {
     /// Builds the request.
     pub fn build(&self) -> Request<'a> {
         Request {
-            email_address: self.email_address.to_owned(),
-            password: self.password.to_owned(),
-            device_id: self.device_id.to_owned(),
+            email_address: self.email_address.clone(),
+            password: self.password.clone(),
+            device_id: self.device_id.clone(),
         }
     }
 }
 
-/// A struct that represents the response from the login call.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// A struct that represents the response from the login call. Its `Debug`
+/// output redacts the token.
+#[derive(Clone, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Response<'a> {
     /// The bearer token used for authentication.
     token: Cow<'a, str>,
 }
 
 impl<'a> Response<'a> {
+    /// The bearer token, consuming the response.
     pub fn access_token(self) -> Cow<'a, str> {
         self.token
     }
+
+    /// The bearer token.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl fmt::Debug for Response<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("token", &Secret(&self.token))
+            .finish()
+    }
 }
 
 #[cfg(test)]
@@ -120,6 +269,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_build_required_fields_in_order() {
+        let item = RequestBuilder::required()
+            .email_address("email@example.com")
+            .password("qwerty123")
+            .device_id("xxx-xxx-xxx-xxx")
+            .build();
+        assert_eq!(
+            serde_json::to_string(
+                &RequestBuilder::new()
+                    .with_email_address("email@example.com")
+                    .with_password("qwerty123")
+                    .with_device_id("xxx-xxx-xxx-xxx")
+                    .build()
+            )
+            .unwrap(),
+            serde_json::to_string(&item).unwrap()
+        );
+    }
+
     #[test]
     fn should_build_parts() {
         let mut builder = RequestBuilder::new();
@@ -0,0 +1,227 @@
+//! Alerting when a pet has been out for too long.
+//!
+//! A [`MissingPetMonitor`](struct.MissingPetMonitor.html) looks at where the
+//! state says each pet is and raises
+//! [`Alert::PetMissing`](../alert/enum.Alert.html#variant.PetMissing) once
+//! per trip for pets outside longer than their threshold. Quiet periods,
+//! such as overnight for a cat that always stays out, hold alerts back until
+//! they end; a pet still out by then is alerted about as soon as they do.
+use crate::alert::{Alert, AlertSink};
+use crate::error::Result;
+use crate::id::PetId;
+use crate::pet::Location;
+use crate::state::State;
+use crate::time::{TimeOfDay, Timestamp};
+use crate::zone::Zone;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Raises an alert for each pet outside longer than its threshold.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::missing::MissingPetMonitor;
+/// use sure_petcare::state::State;
+/// use std::time::Duration;
+/// let state: State = serde_json::from_str(
+///     r#"{"pets":[{"id":2,"name":"Milo","position":{"where":2,"since":"2019-06-01T06:00:00Z"}}]}"#,
+/// )?;
+/// let mut monitor = MissingPetMonitor::new(Duration::from_secs(6 * 3600));
+/// let alerts = monitor.check(&state, "2019-06-01T13:30:00Z".parse().unwrap());
+/// assert_eq!("Milo has been outside for 7h 30m", alerts[0].to_string());
+/// // Only once per trip.
+/// assert!(monitor.check(&state, "2019-06-01T14:00:00Z".parse().unwrap()).is_empty());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct MissingPetMonitor {
+    threshold: Duration,
+    thresholds: HashMap<PetId, Duration>,
+    quiet: Vec<(TimeOfDay, TimeOfDay)>,
+    zone: Zone,
+    raised: HashMap<PetId, Timestamp>,
+}
+
+impl MissingPetMonitor {
+    /// A monitor alerting about any pet outside for longer than
+    /// `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        MissingPetMonitor {
+            threshold,
+            thresholds: HashMap::new(),
+            quiet: Vec::new(),
+            zone: Zone::utc(),
+            raised: HashMap::new(),
+        }
+    }
+
+    /// Sets a different threshold for one pet.
+    pub fn with_threshold_for(mut self, pet_id: PetId, threshold: Duration) -> Self {
+        self.thresholds.insert(pet_id, threshold);
+        self
+    }
+
+    /// Holds alerts back from `from` until `to`, household time, every day.
+    /// A period ending before it starts runs over midnight.
+    pub fn with_quiet_period(mut self, from: TimeOfDay, to: TimeOfDay) -> Self {
+        self.quiet.push((from, to));
+        self
+    }
+
+    /// Sets the zone quiet periods are in, UTC by default.
+    pub fn with_zone(mut self, zone: Zone) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// The threshold for a pet.
+    pub fn threshold_for(&self, pet_id: PetId) -> Duration {
+        self.thresholds
+            .get(&pet_id)
+            .copied()
+            .unwrap_or(self.threshold)
+    }
+
+    /// Whether alerts are being held back at an instant.
+    pub fn is_quiet_at(&self, instant: Timestamp) -> bool {
+        let (_, now) = self.zone.local(instant);
+        self.quiet.iter().any(|&(from, to)| now.is_within(from, to))
+    }
+
+    /// The alerts due at `now` that have not been raised yet, which are then
+    /// counted as raised.
+    pub fn check(&mut self, state: &State<'_>, now: Timestamp) -> Vec<Alert> {
+        let alerts = self.due(state, now);
+        for alert in &alerts {
+            self.mark_raised(alert);
+        }
+        alerts
+    }
+
+    /// Sends the alerts due at `now` to a sink. Alerts the sink fails to
+    /// deliver are offered again on the next check, and the first failure
+    /// is returned once the rest have been sent.
+    pub fn raise_into<S: AlertSink>(
+        &mut self,
+        state: &State<'_>,
+        now: Timestamp,
+        sink: &S,
+    ) -> Result<()> {
+        let mut failure = None;
+        for alert in self.due(state, now) {
+            match sink.raise(&alert) {
+                Ok(()) => self.mark_raised(&alert),
+                Err(error) => failure = failure.or(Some(error)),
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn due(&mut self, state: &State<'_>, now: Timestamp) -> Vec<Alert> {
+        let trips: HashMap<PetId, Timestamp> = state
+            .pets()
+            .iter()
+            .filter_map(|pet| {
+                let position = pet.position()?;
+                match position.location() {
+                    Location::Outside => Some((pet.id(), position.since())),
+                    _ => None,
+                }
+            })
+            .collect();
+        // Forget pets that came home, so their next trip is alerted again.
+        self.raised
+            .retain(|pet_id, out_since| trips.get(pet_id) == Some(out_since));
+        if self.is_quiet_at(now) {
+            return Vec::new();
+        }
+        state
+            .pets()
+            .iter()
+            .filter_map(|pet| {
+                let out_since = *trips.get(&pet.id())?;
+                let outside = now.elapsed_since(out_since);
+                if outside <= self.threshold_for(pet.id()) || self.raised.contains_key(&pet.id()) {
+                    return None;
+                }
+                Some(Alert::PetMissing {
+                    pet_id: pet.id(),
+                    name: pet.name().to_string(),
+                    out_since,
+                    outside: outside.as_secs() as i64,
+                })
+            })
+            .collect()
+    }
+
+    fn mark_raised(&mut self, alert: &Alert) {
+        if let Alert::PetMissing {
+            pet_id, out_since, ..
+        } = alert
+        {
+            self.raised.insert(*pet_id, *out_since);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use pretty_assertions::assert_eq;
+    use std::cell::RefCell;
+
+    struct Outbox {
+        sent: RefCell<Vec<Alert>>,
+        down: bool,
+    }
+
+    impl AlertSink for Outbox {
+        fn raise(&self, alert: &Alert) -> Result<()> {
+            if self.down {
+                return Err(Error::transport("push service down"));
+            }
+            self.sent.borrow_mut().push(alert.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_alert_once_per_trip_outside_quiet_periods() {
+        let state: State = serde_json::from_str(
+            r#"{"pets":[
+                {"id":2,"name":"Milo","position":{"where":2,"since":"2019-06-01T20:00:00Z"}},
+                {"id":3,"name":"Tilly","position":{"where":2,"since":"2019-06-01T20:00:00Z"}},
+                {"id":4,"name":"Rex","position":{"where":1,"since":"2019-06-01T08:00:00Z"}}
+            ]}"#,
+        )
+        .unwrap();
+        let mut monitor = MissingPetMonitor::new(Duration::from_secs(2 * 3600))
+            .with_threshold_for(PetId::new(3), Duration::from_secs(12 * 3600))
+            .with_quiet_period("23:00".parse().unwrap(), "07:00".parse().unwrap());
+        let mut outbox = Outbox {
+            sent: RefCell::new(Vec::new()),
+            down: true,
+        };
+
+        // Two and a half hours out, but the night is quiet.
+        let night = "2019-06-01T23:30:00Z".parse().unwrap();
+        monitor.raise_into(&state, night, &outbox).unwrap();
+        let morning = "2019-06-02T07:00:00Z".parse().unwrap();
+        assert!(monitor.raise_into(&state, morning, &outbox).is_err());
+        outbox.down = false;
+        monitor.raise_into(&state, morning, &outbox).unwrap();
+        monitor.raise_into(&state, morning, &outbox).unwrap();
+        let later = "2019-06-02T08:30:00Z".parse().unwrap();
+        monitor.raise_into(&state, later, &outbox).unwrap();
+
+        let sent: Vec<String> = outbox.sent.borrow().iter().map(Alert::to_string).collect();
+        assert_eq!(
+            vec![
+                "Milo has been outside for 11h 00m".to_string(),
+                "Tilly has been outside for 12h 30m".to_string(),
+            ],
+            sent
+        );
+    }
+}
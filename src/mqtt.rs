@@ -0,0 +1,547 @@
+//! A bridge publishing events and state to MQTT, enabled with the `mqtt`
+//! feature.
+//!
+//! Like the HTTP client, the bridge does not ship an MQTT stack of its own;
+//! it hands messages to a [`Publisher`](trait.Publisher.html), so any MQTT
+//! library can be plugged in.
+//!
+//! Topics are templates in which `{household}`, `{pet}` and `{device}` are
+//! replaced by ids. State topics are published retained and only when their
+//! payload changes, so subscribers joining late still see the current state.
+//!
+//! [`Bridge::publish_discovery`](struct.Bridge.html#method.publish_discovery)
+//! announces those topics to Home Assistant, so pets show up as device
+//! trackers, flaps as locks and batteries and meals as sensors.
+//!
+//! A bridge is also an [`AlertSink`](../alert/trait.AlertSink.html),
+//! publishing alerts as JSON to the alert topic.
+use crate::alert::{self, Alert, AlertSink};
+use crate::device::{LockMode, Product};
+use crate::error::Result;
+use crate::export::jsonl::JsonLines;
+use crate::id::{DeviceId, HouseholdId, PetId};
+use crate::pet::Location;
+use crate::state::State;
+use crate::timeline::Event;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// A message ready to be published.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Message {
+    /// The topic to publish to.
+    pub topic: String,
+
+    /// The message payload.
+    pub payload: Vec<u8>,
+
+    /// Whether the broker should keep the message for new subscribers.
+    pub retain: bool,
+}
+
+/// Publishes messages to a broker.
+pub trait Publisher {
+    /// Publishes the message.
+    fn publish(&self, message: &Message) -> Result<()>;
+}
+
+impl<P: Publisher + ?Sized> Publisher for &P {
+    fn publish(&self, message: &Message) -> Result<()> {
+        (**self).publish(message)
+    }
+}
+
+impl<P: Publisher + ?Sized> Publisher for Box<P> {
+    fn publish(&self, message: &Message) -> Result<()> {
+        (**self).publish(message)
+    }
+}
+
+/// A builder for a `Bridge`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::mqtt::BridgeBuilder;
+/// # struct Broker;
+/// # impl sure_petcare::mqtt::Publisher for Broker {
+/// #     fn publish(&self, _: &sure_petcare::mqtt::Message) -> sure_petcare::error::Result<()> { Ok(()) }
+/// # }
+/// let bridge = BridgeBuilder::new()
+///     .with_event_topic("home/{household}/timeline")
+///     .with_location_topic("home/pets/{pet}")
+///     .build(Broker);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BridgeBuilder {
+    event_topic: String,
+    location_topic: String,
+    battery_topic: String,
+    online_topic: String,
+    lock_topic: String,
+    meal_topic: String,
+    alert_topic: String,
+    discovery_prefix: String,
+}
+
+impl Default for BridgeBuilder {
+    fn default() -> Self {
+        BridgeBuilder {
+            event_topic: "surepet/{household}/events".into(),
+            location_topic: "surepet/{household}/{pet}/location".into(),
+            battery_topic: "surepet/{household}/{device}/battery".into(),
+            online_topic: "surepet/{household}/{device}/online".into(),
+            lock_topic: "surepet/{household}/{device}/lock".into(),
+            meal_topic: "surepet/{household}/{pet}/last_meal".into(),
+            alert_topic: "surepet/alerts".into(),
+            discovery_prefix: "homeassistant".into(),
+        }
+    }
+}
+
+impl BridgeBuilder {
+    /// Create a new builder with topics under `surepet/`.
+    pub fn new() -> Self {
+        BridgeBuilder::default()
+    }
+
+    /// Sets the topic timeline events are published to as JSON.
+    pub fn with_event_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.event_topic = topic.into();
+        self
+    }
+
+    /// Sets the retained topic for a pet's location, `inside` or `outside`.
+    pub fn with_location_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.location_topic = topic.into();
+        self
+    }
+
+    /// Sets the retained topic for a device's battery percentage.
+    pub fn with_battery_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.battery_topic = topic.into();
+        self
+    }
+
+    /// Sets the retained topic for whether a device is online.
+    pub fn with_online_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.online_topic = topic.into();
+        self
+    }
+
+    /// Sets the retained topic for a flap's lock mode, such as `keep_in`.
+    /// Commands are not handled by the bridge; Home Assistant sends them to
+    /// this topic with `/set` appended.
+    pub fn with_lock_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.lock_topic = topic.into();
+        self
+    }
+
+    /// Sets the retained topic for the grams a pet ate at its last meal.
+    pub fn with_meal_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.meal_topic = topic.into();
+        self
+    }
+
+    /// Sets the topic alerts are published to. `{pet}` and `{device}` are
+    /// replaced by what the alert is about, and `{household}` is not known.
+    pub fn with_alert_topic<S: Into<String>>(&mut self, topic: S) -> &mut Self {
+        self.alert_topic = topic.into();
+        self
+    }
+
+    /// Sets the Home Assistant discovery prefix, `homeassistant` by default.
+    pub fn with_discovery_prefix<S: Into<String>>(&mut self, prefix: S) -> &mut Self {
+        self.discovery_prefix = prefix.into();
+        self
+    }
+
+    /// Builds a bridge publishing through `publisher`.
+    pub fn build<P: Publisher>(&self, publisher: P) -> Bridge<P> {
+        Bridge {
+            publisher,
+            topics: self.clone(),
+            retained: HashMap::new(),
+        }
+    }
+}
+
+/// Publishes timeline events and state changes, see the module documentation.
+#[derive(Debug)]
+pub struct Bridge<P> {
+    publisher: P,
+    topics: BridgeBuilder,
+    retained: HashMap<String, Vec<u8>>,
+}
+
+impl<P: Publisher> Bridge<P> {
+    /// Publishes an event as a flattened JSON object, see
+    /// [`JsonLines::event`](../export/jsonl/struct.JsonLines.html#method.event).
+    /// When the topic mentions `{pet}`, the event is published once per pet.
+    pub fn publish_event(&self, event: &Event<'_>) -> Result<()> {
+        let payload = JsonLines::new().event(event).to_string().into_bytes();
+        let template = &self.topics.event_topic;
+        let mut pets: Vec<Option<PetId>> = event.pets().iter().map(|pet| Some(pet.id())).collect();
+        if pets.is_empty() || !template.contains("{pet}") {
+            pets = vec![None];
+        }
+        let device = event.devices().first().map(|device| device.id());
+        for pet in pets {
+            self.publisher.publish(&Message {
+                topic: topic(template, event.household_id(), pet, device),
+                payload: payload.clone(),
+                retain: false,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Publishes the state topics whose payload changed since the last call,
+    /// returning how many messages were sent.
+    pub fn publish_state(&mut self, state: &State<'_>) -> Result<usize> {
+        let mut messages = Vec::new();
+        for pet in state.pets() {
+            let location = match pet.position().map(|position| position.location()) {
+                Some(Location::Inside) => "inside",
+                Some(Location::Outside) => "outside",
+                _ => continue,
+            };
+            let template = &self.topics.location_topic;
+            messages.push((
+                topic(template, pet.household_id(), Some(pet.id()), None),
+                location.to_string(),
+            ));
+        }
+        for pet in state.pets() {
+            if let Some(feeding) = pet.status().feeding() {
+                let eaten = feeding
+                    .change()
+                    .iter()
+                    .fold(0.0, |total, change| total - change);
+                let template = &self.topics.meal_topic;
+                messages.push((
+                    topic(template, pet.household_id(), Some(pet.id()), None),
+                    format!("{:.1}", eaten),
+                ));
+            }
+        }
+        for device in state.devices() {
+            let household = device.household_id();
+            let status = device.status();
+            if let Some(percent) = status.battery_percent() {
+                let template = &self.topics.battery_topic;
+                messages.push((
+                    topic(template, household, None, Some(device.id())),
+                    format!("{:.0}", percent),
+                ));
+            }
+            if let Some(online) = status.online() {
+                let template = &self.topics.online_topic;
+                messages.push((
+                    topic(template, household, None, Some(device.id())),
+                    online.to_string(),
+                ));
+            }
+            if let Some(mode) = status.lock_mode() {
+                let template = &self.topics.lock_topic;
+                messages.push((
+                    topic(template, household, None, Some(device.id())),
+                    lock_mode(mode),
+                ));
+            }
+        }
+
+        let mut sent = 0;
+        for (topic, payload) in messages {
+            let payload = payload.into_bytes();
+            if self.retained.get(&topic) == Some(&payload) {
+                continue;
+            }
+            self.publisher.publish(&Message {
+                topic: topic.clone(),
+                payload: payload.clone(),
+                retain: true,
+            })?;
+            self.retained.insert(topic, payload);
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// The Home Assistant discovery messages for the pets and devices in
+    /// the snapshot, pointing at the state topics of this bridge.
+    pub fn discovery(&self, state: &State<'_>) -> Vec<Message> {
+        let mut messages = Vec::new();
+        let mut config = |component: &str, object_id: String, config: Value| {
+            messages.push(Message {
+                topic: format!(
+                    "{}/{}/surepet/{}/config",
+                    self.topics.discovery_prefix, component, object_id
+                ),
+                payload: config.to_string().into_bytes(),
+                retain: true,
+            });
+        };
+
+        for pet in state.pets() {
+            let household = pet.household_id();
+            let node = json!({
+                "identifiers": [format!("surepet_pet_{}", pet.id())],
+                "name": pet.name(),
+                "manufacturer": "Sure Petcare",
+            });
+            if pet.position().is_some() {
+                let object_id = format!("pet_{}", pet.id());
+                config(
+                    "device_tracker",
+                    object_id.clone(),
+                    json!({
+                        "name": pet.name(),
+                        "unique_id": format!("surepet_{}", object_id),
+                        "state_topic": topic(&self.topics.location_topic, household, Some(pet.id()), None),
+                        "payload_home": "inside",
+                        "payload_not_home": "outside",
+                        "source_type": "router",
+                        "device": node,
+                    }),
+                );
+            }
+            if pet.status().feeding().is_some() {
+                let object_id = format!("pet_{}_last_meal", pet.id());
+                config(
+                    "sensor",
+                    object_id.clone(),
+                    json!({
+                        "name": format!("{} last meal", pet.name()),
+                        "unique_id": format!("surepet_{}", object_id),
+                        "state_topic": topic(&self.topics.meal_topic, household, Some(pet.id()), None),
+                        "unit_of_measurement": "g",
+                        "device": node,
+                    }),
+                );
+            }
+        }
+
+        for device in state.devices() {
+            let household = device.household_id();
+            let status = device.status();
+            let node = json!({
+                "identifiers": [format!("surepet_device_{}", device.id())],
+                "name": device.name(),
+                "manufacturer": "Sure Petcare",
+                "model": model(device.product()),
+            });
+            if status.battery().is_some() {
+                let object_id = format!("device_{}_battery", device.id());
+                config(
+                    "sensor",
+                    object_id.clone(),
+                    json!({
+                        "name": format!("{} battery", device.name()),
+                        "unique_id": format!("surepet_{}", object_id),
+                        "state_topic": topic(&self.topics.battery_topic, household, None, Some(device.id())),
+                        "device_class": "battery",
+                        "unit_of_measurement": "%",
+                        "device": node,
+                    }),
+                );
+            }
+            if status.lock_mode().is_some() {
+                let object_id = format!("device_{}_lock", device.id());
+                let state_topic =
+                    topic(&self.topics.lock_topic, household, None, Some(device.id()));
+                config(
+                    "lock",
+                    object_id.clone(),
+                    json!({
+                        "name": device.name(),
+                        "unique_id": format!("surepet_{}", object_id),
+                        "command_topic": format!("{}/set", state_topic),
+                        "state_topic": state_topic,
+                        "value_template": "{{ 'UNLOCKED' if value == 'unlocked' else 'LOCKED' }}",
+                        "payload_lock": "locked",
+                        "payload_unlock": "unlocked",
+                        "device": node,
+                    }),
+                );
+            }
+        }
+        messages
+    }
+
+    /// Publishes the discovery messages, returning how many were sent.
+    pub fn publish_discovery(&self, state: &State<'_>) -> Result<usize> {
+        let messages = self.discovery(state);
+        for message in &messages {
+            self.publisher.publish(message)?;
+        }
+        Ok(messages.len())
+    }
+}
+
+fn lock_mode(mode: LockMode) -> String {
+    match mode {
+        LockMode::Unknown(code) => format!("mode_{}", code),
+        mode => mode.to_string(),
+    }
+}
+
+fn model(product: Product) -> String {
+    match product {
+        Product::Hub => "Hub".to_string(),
+        Product::Repeater => "Repeater".to_string(),
+        Product::PetDoor => "Pet Door Connect".to_string(),
+        Product::Feeder => "Microchip Pet Feeder Connect".to_string(),
+        Product::Programmer => "Programmer".to_string(),
+        Product::CatFlap => "Microchip Cat Flap Connect".to_string(),
+        Product::FeederLite => "Feeder Lite".to_string(),
+        Product::Felaqua => "Felaqua Connect".to_string(),
+        Product::Unknown(code) => format!("Product {}", code),
+    }
+}
+
+/// Fills in a topic template, using `unknown` for missing ids.
+impl<P: Publisher> AlertSink for Bridge<P> {
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        self.publisher.publish(&Message {
+            topic: topic(
+                &self.topics.alert_topic,
+                None,
+                alert.pet_id(),
+                alert.device_id(),
+            ),
+            payload: alert::payload(alert)?,
+            retain: false,
+        })
+    }
+}
+
+fn topic(
+    template: &str,
+    household: Option<HouseholdId>,
+    pet: Option<PetId>,
+    device: Option<DeviceId>,
+) -> String {
+    fn id<I: ToString>(id: Option<I>) -> String {
+        id.map_or_else(|| "unknown".to_string(), |id| id.to_string())
+    }
+    template
+        .replace("{household}", &id(household))
+        .replace("{pet}", &id(pet))
+        .replace("{device}", &id(device))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockPublisher {
+        messages: Mutex<Vec<Message>>,
+    }
+
+    impl Publisher for MockPublisher {
+        fn publish(&self, message: &Message) -> Result<()> {
+            self.messages.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    fn state(location: i64) -> State<'static> {
+        serde_json::from_value(serde_json::json!({
+            "pets": [{"id": 2, "name": "Milo", "household_id": 1,
+                      "position": {"where": location, "since": "2019-06-01T05:00:00+00:00"}}],
+            "devices": [{"id": 10, "name": "Back door", "product_id": 6, "household_id": 1,
+                         "status": {"battery": 6.0, "online": true, "locking": {"mode": 1}}}],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn should_publish_changed_state_retained() {
+        let publisher = MockPublisher::default();
+        let mut bridge = BridgeBuilder::new().build(&publisher);
+
+        assert_eq!(4, bridge.publish_state(&state(1)).unwrap());
+        assert_eq!(0, bridge.publish_state(&state(1)).unwrap());
+        assert_eq!(1, bridge.publish_state(&state(2)).unwrap());
+
+        let messages = publisher.messages.lock().unwrap();
+        let summary: Vec<(&str, &[u8], bool)> = messages
+            .iter()
+            .map(|m| (m.topic.as_str(), &m.payload[..], m.retain))
+            .collect();
+        assert_eq!(
+            vec![
+                ("surepet/1/2/location", &b"inside"[..], true),
+                ("surepet/1/10/battery", &b"100"[..], true),
+                ("surepet/1/10/online", &b"true"[..], true),
+                ("surepet/1/10/lock", &b"keep_in"[..], true),
+                ("surepet/1/2/location", &b"outside"[..], true),
+            ],
+            summary
+        );
+    }
+
+    #[test]
+    fn should_describe_entities_for_home_assistant() {
+        let bridge = BridgeBuilder::new().build(MockPublisher::default());
+        let messages = bridge.discovery(&state(1));
+        let topics: Vec<&str> = messages.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(
+            vec![
+                "homeassistant/device_tracker/surepet/pet_2/config",
+                "homeassistant/sensor/surepet/device_10_battery/config",
+                "homeassistant/lock/surepet/device_10_lock/config",
+            ],
+            topics
+        );
+
+        let lock: Value = serde_json::from_slice(&messages[2].payload).unwrap();
+        assert_eq!("surepet/1/10/lock", lock["state_topic"]);
+        assert_eq!("surepet/1/10/lock/set", lock["command_topic"]);
+        assert_eq!("Microchip Cat Flap Connect", lock["device"]["model"]);
+        assert!(messages.iter().all(|m| m.retain));
+    }
+
+    #[test]
+    fn should_publish_events_per_pet() {
+        let publisher = MockPublisher::default();
+        let bridge = BridgeBuilder::new()
+            .with_event_topic("home/{household}/{pet}/events")
+            .build(&publisher);
+        let event: Event = serde_json::from_str(
+            r#"{"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00","household_id":1,
+                "pets":[{"id":2,"name":"Milo"},{"id":3,"name":"Tilly"}]}"#,
+        )
+        .unwrap();
+        bridge.publish_event(&event).unwrap();
+
+        let messages = publisher.messages.lock().unwrap();
+        assert_eq!("home/1/2/events", messages[0].topic);
+        assert_eq!("home/1/3/events", messages[1].topic);
+        assert!(!messages[1].retain);
+    }
+
+    #[test]
+    fn should_publish_alerts() {
+        let publisher = MockPublisher::default();
+        let bridge = BridgeBuilder::new()
+            .with_alert_topic("home/alerts/{device}")
+            .build(&publisher);
+        bridge
+            .raise(&Alert::LowWater {
+                device_id: DeviceId::new(12),
+                name: "Fountain".into(),
+                remaining: 150.0,
+            })
+            .unwrap();
+
+        let messages = publisher.messages.lock().unwrap();
+        assert_eq!("home/alerts/12", messages[0].topic);
+        let payload: serde_json::Value = serde_json::from_slice(&messages[0].payload).unwrap();
+        assert_eq!("low_water", payload["kind"]);
+        assert_eq!("Fountain is down to 150ml, top it up", payload["message"]);
+    }
+}
@@ -0,0 +1,47 @@
+//! Names for the code enums, used by their `Display` and `FromStr` impls.
+
+/// Implements `Display` and `FromStr` for an enum with an `Unknown(i64)`
+/// variant and a `From<i64>` impl. Known variants display as their snake
+/// case name and unknown ones as their code. Parsing ignores case, accepts
+/// dashes and spaces for underscores, and takes codes too. Deserializing
+/// through `unknown::deserialize` reports unknown codes to the hook.
+macro_rules! names {
+    ($kind:literal, $name:ident { $($variant:ident => $text:literal,)* }) => {
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $($name::$variant => f.write_str($text),)*
+                    $name::Unknown(code) => write!(f, "{}", code),
+                }
+            }
+        }
+
+        impl crate::unknown::Coded for $name {
+            const KIND: &'static str = $kind;
+
+            fn unknown_code(&self) -> Option<i64> {
+                match self {
+                    $name::Unknown(code) => Some(*code),
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = crate::error::ParseNameError;
+
+            fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+                let name = s.trim().to_lowercase().replace(['-', ' '], "_");
+                match name.as_str() {
+                    $($text => Ok($name::$variant),)*
+                    _ => name
+                        .parse::<i64>()
+                        .map($name::from)
+                        .map_err(|_| crate::error::ParseNameError::new($kind, s)),
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use names;
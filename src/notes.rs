@@ -0,0 +1,173 @@
+//! Caretaker notes kept in a pet's comments.
+//!
+//! The app gives each pet one free text comments field. Households with
+//! several caretakers use it for things like "on medication, feed at 8am",
+//! and overwriting it loses whatever someone else wrote. [`Notes`](struct.Notes.html)
+//! reads the field as one note per line, each stamped with when and by
+//! whom it was written:
+//!
+//! ```text
+//! [2019-06-01T07:00:00+00:00 Sam] on medication, feed at 8am
+//! ```
+//!
+//! [`Pets::add_note`](../pet/struct.Pets.html#method.add_note) reads the
+//! pet afresh, appends a note and writes the field back, so earlier notes
+//! are kept. Lines written in the app without a stamp are kept as they are.
+use crate::time::Timestamp;
+use std::fmt;
+use std::str::FromStr;
+
+/// One line of a pet's comments.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Note {
+    at: Option<Timestamp>,
+    author: Option<String>,
+    text: String,
+}
+
+impl Note {
+    /// A note written at `at` by `author`. Line breaks in the text are
+    /// replaced by spaces, since each note is one line.
+    pub fn new(at: Timestamp, author: &str, text: &str) -> Self {
+        let author = one_line(author).replace(']', ")");
+        Note {
+            at: Some(at),
+            author: if author.is_empty() {
+                None
+            } else {
+                Some(author)
+            },
+            text: one_line(text),
+        }
+    }
+
+    /// When the note was written, `None` for a line without a stamp.
+    pub fn at(&self) -> Option<Timestamp> {
+        self.at
+    }
+
+    /// Who wrote the note, if they said.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    /// What the note says.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    fn parse(line: &str) -> Self {
+        let stamped = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once("] "))
+            .and_then(|(stamp, text)| {
+                let (at, author) = stamp.split_once(' ').unwrap_or((stamp, ""));
+                let at = at.parse().ok()?;
+                Some(Note {
+                    at: Some(at),
+                    author: if author.is_empty() {
+                        None
+                    } else {
+                        Some(author.to_string())
+                    },
+                    text: text.to_string(),
+                })
+            });
+        stamped.unwrap_or_else(|| Note {
+            at: None,
+            author: None,
+            text: line.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.at, &self.author) {
+            (Some(at), Some(author)) => write!(f, "[{} {}] {}", at, author, self.text),
+            (Some(at), None) => write!(f, "[{}] {}", at, self.text),
+            (None, _) => f.write_str(&self.text),
+        }
+    }
+}
+
+/// A pet's comments as notes, oldest first.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::notes::{Note, Notes};
+/// let mut notes: Notes = "Likes chin scratches".parse().unwrap();
+/// notes.push(Note::new(
+///     "2019-06-01T07:00:00Z".parse().unwrap(),
+///     "Sam",
+///     "on medication, feed at 8am",
+/// ));
+/// assert_eq!(
+///     "Likes chin scratches\n[2019-06-01T07:00:00+00:00 Sam] on medication, feed at 8am",
+///     notes.to_string()
+/// );
+/// assert_eq!(Some("Sam"), notes.latest().unwrap().author());
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Notes {
+    notes: Vec<Note>,
+}
+
+impl Notes {
+    /// Reads a pet's comments, `None` or empty giving no notes.
+    pub fn from_comments(comments: Option<&str>) -> Self {
+        Notes {
+            notes: comments
+                .unwrap_or_default()
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty())
+                .map(Note::parse)
+                .collect(),
+        }
+    }
+
+    /// Every note, oldest first.
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// The last note written.
+    pub fn latest(&self) -> Option<&Note> {
+        self.notes.last()
+    }
+
+    /// Appends a note.
+    pub fn push(&mut self, note: Note) {
+        self.notes.push(note);
+    }
+
+    /// Whether there are no notes.
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+}
+
+impl FromStr for Notes {
+    type Err = std::convert::Infallible;
+
+    fn from_str(comments: &str) -> Result<Self, Self::Err> {
+        Ok(Notes::from_comments(Some(comments)))
+    }
+}
+
+impl fmt::Display for Notes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, note) in self.notes.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", note)?;
+        }
+        Ok(())
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
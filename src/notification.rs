@@ -0,0 +1,581 @@
+//! Notifications, as shown behind the bell icon in the official app.
+use crate::client::SurePet;
+use crate::error::{Error, Result};
+use crate::id::HouseholdId;
+use crate::names::names;
+use crate::push;
+use crate::sort::{Field, Key, Listing, Sortable};
+use crate::time::Timestamp;
+use crate::transport::{Method, Transport};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+/// The longest a [`Watch`](struct.Watch.html) waits after failed polls,
+/// unless its interval or the service asks for longer.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// What a notification is about.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Category {
+    /// General announcements.
+    General,
+    /// A device is running low on batteries.
+    LowBattery,
+    /// An unknown animal tried to use a flap.
+    Intruder,
+    /// A curfew locked or unlocked a flap.
+    Curfew,
+    /// A device went offline.
+    DeviceOffline,
+    /// A pet ate.
+    Feeding,
+    /// A pet drank.
+    Drinking,
+    /// A pet came in or went out.
+    Movement,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl Category {
+    /// The code the API uses for this category.
+    pub fn code(self) -> i64 {
+        match self {
+            Category::General => 0,
+            Category::LowBattery => 1,
+            Category::Intruder => 2,
+            Category::Curfew => 3,
+            Category::DeviceOffline => 4,
+            Category::Feeding => 5,
+            Category::Drinking => 6,
+            Category::Movement => 7,
+            Category::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for Category {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => Category::General,
+            1 => Category::LowBattery,
+            2 => Category::Intruder,
+            3 => Category::Curfew,
+            4 => Category::DeviceOffline,
+            5 => Category::Feeding,
+            6 => Category::Drinking,
+            7 => Category::Movement,
+            code => Category::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for Category {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Category {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("category", Category {
+    General => "general",
+    LowBattery => "low_battery",
+    Intruder => "intruder",
+    Curfew => "curfew",
+    DeviceOffline => "device_offline",
+    Feeding => "feeding",
+    Drinking => "drinking",
+    Movement => "movement",
+});
+
+/// A single notification.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Notification<'a> {
+    pub(crate) id: i64,
+    #[serde(rename = "type")]
+    pub(crate) category: Category,
+    #[serde(alias = "text", default)]
+    pub(crate) message: Cow<'a, str>,
+    pub(crate) created_at: Timestamp,
+    #[serde(default)]
+    pub(crate) read: bool,
+    #[serde(default)]
+    pub(crate) household_id: Option<HouseholdId>,
+}
+
+impl<'a> Sortable for Notification<'a> {
+    fn sort_key(&self, field: Field) -> Key<'_> {
+        match field {
+            Field::Id => Key::Number(self.id),
+            Field::CreatedAt => Key::Time(self.created_at),
+            Field::Name | Field::UpdatedAt => Key::None,
+        }
+    }
+}
+
+impl<'a> Notification<'a> {
+    /// The notification id; ids increase over time.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// What the notification is about.
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// The text shown to the user.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// When the notification was raised.
+    pub fn created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
+    /// Whether the notification has been read.
+    pub fn read(&self) -> bool {
+        self.read
+    }
+
+    /// The household the notification belongs to, if any.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.household_id
+    }
+}
+
+/// Which categories of notification are delivered to the account.
+///
+/// Settings this crate does not model are kept and sent back unchanged on
+/// update.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::notification::{Category, Preferences};
+/// let mut preferences = Preferences::default();
+/// preferences.set(Category::Movement, false).set(Category::Intruder, true);
+/// assert!(!preferences.enabled(Category::Movement));
+/// ```
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Preferences {
+    #[serde(default = "enabled")]
+    intruder: bool,
+    #[serde(default = "enabled")]
+    curfew: bool,
+    #[serde(default = "enabled")]
+    low_battery: bool,
+    #[serde(default = "enabled")]
+    device_offline: bool,
+    #[serde(default = "enabled")]
+    feeding: bool,
+    #[serde(default = "enabled")]
+    drinking: bool,
+    #[serde(default = "enabled")]
+    movement: bool,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for Preferences {
+    /// Every category enabled, as for a new account.
+    fn default() -> Self {
+        Preferences {
+            intruder: true,
+            curfew: true,
+            low_battery: true,
+            device_offline: true,
+            feeding: true,
+            drinking: true,
+            movement: true,
+            other: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Preferences {
+    /// Whether notifications of the category are delivered. General and
+    /// unknown categories cannot be switched off.
+    pub fn enabled(&self, category: Category) -> bool {
+        match category {
+            Category::Intruder => self.intruder,
+            Category::Curfew => self.curfew,
+            Category::LowBattery => self.low_battery,
+            Category::DeviceOffline => self.device_offline,
+            Category::Feeding => self.feeding,
+            Category::Drinking => self.drinking,
+            Category::Movement => self.movement,
+            Category::General | Category::Unknown(_) => true,
+        }
+    }
+
+    /// Switches a category on or off; general and unknown categories are
+    /// left alone.
+    pub fn set(&mut self, category: Category, enabled: bool) -> &mut Self {
+        let setting = match category {
+            Category::Intruder => &mut self.intruder,
+            Category::Curfew => &mut self.curfew,
+            Category::LowBattery => &mut self.low_battery,
+            Category::DeviceOffline => &mut self.device_offline,
+            Category::Feeding => &mut self.feeding,
+            Category::Drinking => &mut self.drinking,
+            Category::Movement => &mut self.movement,
+            Category::General | Category::Unknown(_) => return self,
+        };
+        *setting = enabled;
+        self
+    }
+}
+
+/// The body sent when marking notifications read.
+#[derive(Serialize)]
+struct ReadUpdate {
+    read: bool,
+}
+
+const PREFERENCES: &str = "/api/notification/preference";
+
+/// The notification endpoints, obtained from [`SurePet::notifications`](../client/struct.SurePet.html#method.notifications).
+#[derive(Debug)]
+pub struct Notifications<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+impl<'c, T: Transport> Notifications<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Notifications { client }
+    }
+
+    /// Fetches the notifications for the account, newest first.
+    pub fn list(&self) -> Result<Vec<Notification<'static>>> {
+        self.client.get("/api/notification", &[])
+    }
+
+    /// Like [`list`](#method.list), sorted, see the
+    /// [`sort`](../sort/index.html) module.
+    pub fn query(&self) -> Listing<'c, T, Notification<'static>> {
+        Listing::new(self.client, "/api/notification", Vec::new())
+    }
+
+    /// Fetches the notification preferences of the account.
+    pub fn preferences(&self) -> Result<Preferences> {
+        self.client.get(PREFERENCES, &[])
+    }
+
+    /// Replaces the notification preferences, returning them as stored, or
+    /// as sent when the API answers without a body.
+    pub fn set_preferences(&self, preferences: &Preferences) -> Result<Preferences> {
+        let stored = self
+            .client
+            .send_optional(Method::Put, PREFERENCES, &[], Some(preferences))?;
+        Ok(stored.unwrap_or_else(|| preferences.clone()))
+    }
+
+    /// Polls the notification list every `interval`, yielding each new
+    /// notification once, oldest first.
+    ///
+    /// Notifications that already exist when watching starts are skipped.
+    /// A failed poll yields the error and the next call polls again, so the
+    /// caller decides whether to keep going. The next poll waits for the
+    /// interval, doubling with each failure in a row up to
+    /// [`MAX_BACKOFF`](constant.MAX_BACKOFF.html); when the service reports
+    /// itself unavailable it waits at least for the `Retry-After` it asked
+    /// for.
+    pub fn watch(&self, interval: Duration) -> Watch<'c, T> {
+        Watch {
+            notifications: Notifications::new(self.client),
+            interval,
+            seen: None,
+            pending: VecDeque::new(),
+            backoff: None,
+            failing: None,
+            sleep: Sleep(Box::new(std::thread::sleep)),
+        }
+    }
+
+    /// Registers a push token so the account's notifications are delivered
+    /// to it.
+    pub fn register_push(&self, request: &push::Request) -> Result<()> {
+        self.client
+            .send_unit(Method::Post, "/api/me/client", Some(request))
+    }
+
+    /// Marks a single notification as read.
+    pub fn mark_read(&self, notification_id: i64) -> Result<()> {
+        let path = format!("/api/notification/{}", notification_id);
+        self.client
+            .send_unit(Method::Put, &path, Some(&ReadUpdate { read: true }))
+    }
+
+    /// Marks every notification on the account as read.
+    pub fn mark_all_read(&self) -> Result<()> {
+        self.client.send_unit(
+            Method::Put,
+            "/api/notification",
+            Some(&ReadUpdate { read: true }),
+        )
+    }
+}
+
+/// An endless iterator of new notifications, see [`Notifications::watch`](struct.Notifications.html#method.watch).
+#[derive(Debug)]
+pub struct Watch<'c, T> {
+    notifications: Notifications<'c, T>,
+    interval: Duration,
+    seen: Option<HashSet<i64>>,
+    pending: VecDeque<Notification<'static>>,
+    backoff: Option<Duration>,
+    /// The wait after the last failure in a row, doubled by the next one.
+    failing: Option<Duration>,
+    sleep: Sleep,
+}
+
+struct Sleep(Box<dyn FnMut(Duration) + Send>);
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sleep")
+    }
+}
+
+impl<'c, T> Watch<'c, T> {
+    /// Waits between polls with `sleep` rather than by blocking the thread,
+    /// as a test or an event loop driving the watch may want.
+    pub fn with_sleep<F: FnMut(Duration) + Send + 'static>(mut self, sleep: F) -> Self {
+        self.sleep = Sleep(Box::new(sleep));
+        self
+    }
+}
+
+impl<'c, T: Transport> Iterator for Watch<'c, T> {
+    type Item = Result<Notification<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(notification) = self.pending.pop_front() {
+                return Some(Ok(notification));
+            }
+            if let Some(backoff) = self.backoff.take() {
+                (self.sleep.0)(backoff);
+            } else if self.seen.is_some() {
+                (self.sleep.0)(self.interval);
+            }
+            let mut list = match self.notifications.list() {
+                Ok(list) => list,
+                Err(error) => {
+                    let wait = self
+                        .failing
+                        .map_or(self.interval, |last| last * 2)
+                        .min(MAX_BACKOFF.max(self.interval));
+                    self.failing = Some(wait);
+                    self.backoff = Some(match error {
+                        Error::ServiceUnavailable { retry_after } => {
+                            retry_after.unwrap_or_default().max(wait)
+                        }
+                        _ => wait,
+                    });
+                    return Some(Err(error));
+                }
+            };
+            self.failing = None;
+            list.sort_by_key(|notification| notification.id);
+            let ids = list.iter().map(|notification| notification.id).collect();
+            if let Some(seen) = self.seen.replace(ids) {
+                self.pending
+                    .extend(list.into_iter().filter(|n| !seen.contains(&n.id)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn should_list_notifications() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[
+                {"id":7,"type":2,"text":"An unknown animal tried the Kitchen flap","created_at":"2019-06-01T03:12:00+00:00","household_id":1},
+                {"id":6,"type":42,"message":"Something new","created_at":"2019-05-31T10:00:00+00:00","read":true}
+            ]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let notifications = client.notifications().list().unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/notification",
+            transport.requests()[0].url
+        );
+        assert_eq!(Category::Intruder, notifications[0].category());
+        assert_eq!(
+            "An unknown animal tried the Kitchen flap",
+            notifications[0].message()
+        );
+        assert!(!notifications[0].read());
+        assert_eq!(Category::Unknown(42), notifications[1].category());
+        assert!(notifications[1].read());
+    }
+
+    #[test]
+    fn should_update_preferences_keeping_unknown_settings() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"intruder":true,"movement":true,"quiet":"22:00"}}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":{"intruder":true,"movement":false,"quiet":"22:00"}}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+        let mut preferences = client.notifications().preferences().unwrap();
+        assert!(preferences.enabled(Category::Curfew));
+        preferences.set(Category::Movement, false);
+        let stored = client
+            .notifications()
+            .set_preferences(&preferences)
+            .unwrap();
+
+        assert_eq!(preferences, stored);
+        let body: serde_json::Value =
+            serde_json::from_slice(transport.requests()[1].body.as_ref().unwrap()).unwrap();
+        assert_eq!(Some(&serde_json::json!(false)), body.get("movement"));
+        assert_eq!(Some(&serde_json::json!("22:00")), body.get("quiet"));
+    }
+
+    #[test]
+    fn should_register_push_token() {
+        let transport = MockTransport::new().respond(201, r#"{"data":{"id":3}}"#);
+        let client = SurePet::new(&transport, "token");
+        let request = push::RequestBuilder::new()
+            .with_token("fcm-token")
+            .with_platform("android")
+            .with_device_id("xxx")
+            .build();
+        client.notifications().register_push(&request).unwrap();
+
+        let sent = &transport.requests()[0];
+        assert_eq!(Method::Post, sent.method);
+        assert_eq!("https://app.api.surehub.io/api/me/client", sent.url);
+        assert_eq!(Some(serde_json::to_vec(&request).unwrap()), sent.body);
+    }
+
+    #[test]
+    fn should_watch_for_new_notifications() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[{"id":1,"type":0,"created_at":"2019-06-01T00:00:00Z"}]}"#,
+            )
+            .respond(500, "oops")
+            .respond(
+                200,
+                r#"{"data":[
+                    {"id":3,"type":0,"created_at":"2019-06-01T00:02:00Z"},
+                    {"id":2,"type":0,"created_at":"2019-06-01T00:01:00Z"},
+                    {"id":1,"type":0,"created_at":"2019-06-01T00:00:00Z"}
+                ]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":3,"type":0,"created_at":"2019-06-01T00:02:00Z"}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":4,"type":0,"created_at":"2019-06-01T00:03:00Z"}]}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+        let mut watch = client.notifications().watch(Duration::from_millis(0));
+
+        assert!(watch.next().unwrap().is_err());
+        let ids: Vec<i64> = watch.take(3).map(|n| n.unwrap().id()).collect();
+        assert_eq!(vec![2, 3, 4], ids);
+        assert_eq!(5, transport.requests().len());
+    }
+
+    #[test]
+    fn should_back_off_after_failed_polls() {
+        let transport = MockTransport::new()
+            .respond(500, "oops")
+            .respond(401, r#"{"error":"unauthorised"}"#)
+            .respond(200, r#"{"data":[]}"#)
+            .respond(
+                200,
+                r#"{"data":[{"id":1,"type":0,"created_at":"2019-06-01T00:00:00Z"}]}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+        let slept = Arc::new(Mutex::new(Vec::new()));
+        let record = Arc::clone(&slept);
+        let mut watch = client
+            .notifications()
+            .watch(Duration::from_secs(10))
+            .with_sleep(move |wait| record.lock().unwrap().push(wait));
+
+        assert!(watch.next().unwrap().is_err());
+        assert!(watch.next().unwrap().is_err());
+        assert_eq!(1, watch.next().unwrap().unwrap().id());
+        assert_eq!(
+            vec![
+                Duration::from_secs(10),
+                Duration::from_secs(20),
+                Duration::from_secs(10)
+            ],
+            *slept.lock().unwrap()
+        );
+        assert_eq!(4, transport.requests().len());
+    }
+
+    #[test]
+    fn should_mark_notifications_read() {
+        let transport = MockTransport::new().respond(200, "").respond(204, "");
+        let client = SurePet::new(&transport, "token");
+        client.notifications().mark_read(7).unwrap();
+        client.notifications().mark_all_read().unwrap();
+
+        let sent = transport.requests();
+        assert_eq!(Method::Put, sent[0].method);
+        assert_eq!("https://app.api.surehub.io/api/notification/7", sent[0].url);
+        assert_eq!(Some(br#"{"read":true}"#.to_vec()), sent[0].body);
+        assert_eq!("https://app.api.surehub.io/api/notification", sent[1].url);
+    }
+
+    #[test]
+    fn should_accept_empty_responses_where_nothing_is_returned() {
+        let transport = MockTransport::new().respond(204, "").respond(200, " \n");
+        let client = SurePet::new(&transport, "token");
+        let mut preferences = Preferences::default();
+        preferences.set(Category::Movement, false);
+
+        assert_eq!(
+            preferences,
+            client
+                .notifications()
+                .set_preferences(&preferences)
+                .unwrap()
+        );
+        match client.notifications().list() {
+            Err(Error::Json(error)) => assert_eq!(
+                "expected a body in the response to GET /api/notification",
+                error.to_string()
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Noticing devices that have dropped off the network.
+//!
+//! A device counts as down while it reports itself offline or while the
+//! cloud has not heard from it for longer than the grace period. Hubs
+//! reboot for firmware updates and flaps drop out when the signal fades for
+//! a moment, so an [`OfflineMonitor`](struct.OfflineMonitor.html) only
+//! raises [`Alert::DeviceOffline`](../alert/enum.Alert.html#variant.DeviceOffline)
+//! once a device has been down for the whole grace period, and
+//! [`Alert::DeviceOnline`](../alert/enum.Alert.html#variant.DeviceOnline)
+//! when one it alerted about comes back. A device that is down for less
+//! than the grace period raises neither.
+use crate::alert::Alert;
+use crate::device::Device;
+use crate::id::DeviceId;
+use crate::time::Timestamp;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Down {
+    since: Timestamp,
+    alerted: bool,
+}
+
+/// Raises alerts as devices go offline and come back, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::Device;
+/// use sure_petcare::offline::OfflineMonitor;
+/// use std::time::Duration;
+/// let hub = |online: bool| -> Vec<Device<'static>> {
+///     let json = format!(r#"[{{"id":1,"name":"Hub","product_id":1,"status":{{"online":{}}}}}]"#, online);
+///     serde_json::from_str(&json).unwrap()
+/// };
+/// let at = |time: &str| format!("2019-06-01T{}:00Z", time).parse().unwrap();
+/// let mut monitor = OfflineMonitor::new(Duration::from_secs(600));
+/// // A reboot is over before the grace period is.
+/// assert!(monitor.check(&hub(false), at("10:00")).is_empty());
+/// assert!(monitor.check(&hub(true), at("10:05")).is_empty());
+/// // An outage is not.
+/// assert!(monitor.check(&hub(false), at("11:00")).is_empty());
+/// assert_eq!("Hub is offline", monitor.check(&hub(false), at("11:10"))[0].to_string());
+/// assert_eq!("Hub is back online after 0h 30m", monitor.check(&hub(true), at("11:30"))[0].to_string());
+/// ```
+#[derive(Clone, Debug)]
+pub struct OfflineMonitor {
+    grace: Duration,
+    down: HashMap<DeviceId, Down>,
+}
+
+impl Default for OfflineMonitor {
+    fn default() -> Self {
+        OfflineMonitor::new(Duration::from_secs(15 * 60))
+    }
+}
+
+impl OfflineMonitor {
+    /// A monitor alerting about devices down for longer than `grace`.
+    pub fn new(grace: Duration) -> Self {
+        OfflineMonitor {
+            grace,
+            down: HashMap::new(),
+        }
+    }
+
+    /// How long a device has to be down before it is alerted about.
+    pub fn grace(&self) -> Duration {
+        self.grace
+    }
+
+    /// Whether a device has been alerted about as offline and has not come
+    /// back since.
+    pub fn is_offline(&self, device_id: DeviceId) -> bool {
+        self.down.get(&device_id).is_some_and(|down| down.alerted)
+    }
+
+    /// The devices alerted about as offline that have not come back.
+    pub fn offline(&self) -> impl Iterator<Item = DeviceId> + '_ {
+        self.down
+            .iter()
+            .filter(|(_, down)| down.alerted)
+            .map(|(device_id, _)| *device_id)
+    }
+
+    /// Looks at each device at `now`, returning an alert for each that has
+    /// just been down for the grace period or has just come back. Devices
+    /// missing from `devices` keep their state.
+    pub fn check(&mut self, devices: &[Device<'_>], now: Timestamp) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for device in devices {
+            let id = device.id();
+            let silent_since = device
+                .last_activity_at()
+                .filter(|seen| now.elapsed_since(*seen) > self.grace);
+            let reported_down = device.status().online() == Some(false);
+            if !reported_down && silent_since.is_none() {
+                if let Some(down) = self.down.remove(&id) {
+                    if down.alerted {
+                        alerts.push(Alert::DeviceOnline {
+                            device_id: id,
+                            name: device.name().to_string(),
+                            offline: now.seconds_since(down.since).max(0),
+                        });
+                    }
+                }
+                continue;
+            }
+            let since = silent_since.or(device.last_activity_at()).unwrap_or(now);
+            let down = self.down.entry(id).or_insert(Down {
+                since,
+                alerted: false,
+            });
+            down.since = down.since.min(since);
+            if !down.alerted && now.elapsed_since(down.since) >= self.grace {
+                down.alerted = true;
+                alerts.push(Alert::DeviceOffline {
+                    device_id: id,
+                    name: device.name().to_string(),
+                    last_seen: device.last_activity_at(),
+                });
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_debounce_devices_dropping_out() {
+        let devices = |flap_seen: &str, hub_online: bool| -> Vec<Device<'static>> {
+            let json = format!(
+                r#"[{{"id":1,"name":"Hub","product_id":1,"status":{{"online":{}}}}},
+                    {{"id":10,"name":"Back door","product_id":6,"status":{{"online":true}},
+                      "last_activity_at":"2019-06-01T{}:00Z"}}]"#,
+                hub_online, flap_seen
+            );
+            serde_json::from_str(&json).unwrap()
+        };
+        let at = |time: &str| -> Timestamp { format!("2019-06-01T{}:00Z", time).parse().unwrap() };
+        let mut monitor = OfflineMonitor::new(Duration::from_secs(30 * 60));
+        let names =
+            |alerts: Vec<Alert>| -> Vec<String> { alerts.iter().map(Alert::to_string).collect() };
+
+        assert!(monitor
+            .check(&devices("10:00", true), at("10:20"))
+            .is_empty());
+        // Not heard from for 40 minutes, longer than the grace period.
+        assert_eq!(
+            vec!["Back door is offline".to_string()],
+            names(monitor.check(&devices("10:00", false), at("10:40")))
+        );
+        assert!(monitor.is_offline(DeviceId::new(10)));
+        assert!(!monitor.is_offline(DeviceId::new(1)));
+        assert_eq!(
+            vec![
+                "Hub is offline".to_string(),
+                "Back door is back online after 1h 10m".to_string()
+            ],
+            names(monitor.check(&devices("11:10", false), at("11:10")))
+        );
+        assert!(monitor
+            .check(&devices("11:10", false), at("11:20"))
+            .is_empty());
+        assert_eq!(
+            vec![DeviceId::new(1)],
+            monitor.offline().collect::<Vec<_>>()
+        );
+    }
+}
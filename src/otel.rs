@@ -0,0 +1,193 @@
+//! OpenTelemetry style client spans, enabled with the `otel` feature.
+//!
+//! [`Traced`](struct.Traced.html) wraps a transport and opens a client span
+//! for every request through a [`Tracer`](trait.Tracer.html), recording the
+//! semantic HTTP attributes and injecting the span's W3C `traceparent`
+//! header, so requests join the caller's distributed trace. Implement
+//! `Tracer` on top of the `opentelemetry` crate's tracer to export them.
+//!
+//! Every attempt made by a retrying caller goes through the transport, so
+//! each one gets a span of its own under the same parent context, with
+//! `http.request.resend_count` counting repeats of the same request.
+use crate::error::Result;
+use crate::transport::{Request, Response, Transport};
+use std::sync::Mutex;
+
+/// The value of a span attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A string attribute.
+    String(String),
+    /// An integer attribute.
+    Int(i64),
+}
+
+/// A span that has been started.
+pub trait Span {
+    /// Sets an attribute.
+    fn set_attribute(&mut self, key: &'static str, value: Value);
+
+    /// Marks the span as failed.
+    fn set_error(&mut self, description: &str);
+
+    /// The W3C `traceparent` header value identifying the span.
+    fn traceparent(&self) -> Option<String>;
+
+    /// Ends the span.
+    fn end(self);
+}
+
+/// Starts client spans as children of the current context.
+pub trait Tracer {
+    /// The span type.
+    type Span: Span;
+
+    /// Starts a client span.
+    fn start_client_span(&self, name: &str) -> Self::Span;
+}
+
+/// A transport that traces every request of the wrapped transport.
+#[derive(Debug)]
+pub struct Traced<T, R> {
+    transport: T,
+    tracer: R,
+    last: Mutex<Option<(String, i64)>>,
+}
+
+impl<T: Transport, R: Tracer> Traced<T, R> {
+    /// Wraps `transport`, starting spans through `tracer`.
+    pub fn new(transport: T, tracer: R) -> Self {
+        Traced {
+            transport,
+            tracer,
+            last: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: Transport, R: Tracer> Transport for Traced<T, R> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let method = request.method.as_str();
+        let mut span = self.tracer.start_client_span(method);
+        span.set_attribute("http.request.method", Value::String(method.into()));
+        span.set_attribute("url.full", Value::String(request.url.clone()));
+        if let Some(host) = host(&request.url) {
+            span.set_attribute("server.address", Value::String(host.into()));
+        }
+
+        let key = format!("{} {}", method, request.url);
+        let resends = {
+            let mut last = self.last.lock().unwrap();
+            let resends = match &*last {
+                Some((previous, count)) if *previous == key => count + 1,
+                _ => 0,
+            };
+            *last = Some((key, resends));
+            resends
+        };
+        if resends > 0 {
+            span.set_attribute("http.request.resend_count", Value::Int(resends));
+        }
+
+        let mut traced = request.clone();
+        if let Some(traceparent) = span.traceparent() {
+            traced.headers.push(("traceparent".into(), traceparent));
+        }
+        let response = self.transport.send(&traced);
+        match &response {
+            Ok(response) => {
+                let status = i64::from(response.status);
+                span.set_attribute("http.response.status_code", Value::Int(status));
+                if response.status >= 400 {
+                    span.set_attribute("error.type", Value::String(status.to_string()));
+                    span.set_error(&format!("status {}", status));
+                } else {
+                    // A success ends the run of repeats.
+                    *self.last.lock().unwrap() = None;
+                }
+            }
+            Err(error) => {
+                span.set_attribute("error.type", Value::String("transport".into()));
+                span.set_error(&error.to_string());
+            }
+        }
+        span.end();
+        response
+    }
+}
+
+fn host(url: &str) -> Option<&str> {
+    let rest = url.split("://").nth(1)?;
+    rest.split(['/', '?', ':']).next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    type Recorded = Arc<Mutex<Vec<Vec<(&'static str, Value)>>>>;
+
+    struct RecordingTracer(Recorded);
+
+    struct RecordingSpan(Recorded, Vec<(&'static str, Value)>);
+
+    impl Tracer for RecordingTracer {
+        type Span = RecordingSpan;
+
+        fn start_client_span(&self, _name: &str) -> RecordingSpan {
+            RecordingSpan(self.0.clone(), Vec::new())
+        }
+    }
+
+    impl Span for RecordingSpan {
+        fn set_attribute(&mut self, key: &'static str, value: Value) {
+            self.1.push((key, value));
+        }
+
+        fn set_error(&mut self, _description: &str) {}
+
+        fn traceparent(&self) -> Option<String> {
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".into())
+        }
+
+        fn end(self) {
+            self.0.lock().unwrap().push(self.1);
+        }
+    }
+
+    #[test]
+    fn should_record_spans_and_propagate_context() {
+        let spans = Recorded::default();
+        let mock = MockTransport::new()
+            .respond(503, "{}")
+            .respond(200, r#"{"data":{}}"#);
+        let transport = Traced::new(&mock, RecordingTracer(spans.clone()));
+        let client = SurePet::new(&transport, "token");
+        assert!(client.start().is_err());
+        client.start().unwrap();
+
+        assert_eq!(
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            mock.requests()[0].header("traceparent")
+        );
+        let spans = spans.lock().unwrap();
+        assert_eq!(
+            vec![
+                ("http.request.method", Value::String("GET".into())),
+                (
+                    "url.full",
+                    Value::String("https://app.api.surehub.io/api/me/start".into())
+                ),
+                ("server.address", Value::String("app.api.surehub.io".into())),
+                ("http.response.status_code", Value::Int(503)),
+                ("error.type", Value::String("503".into())),
+            ],
+            spans[0]
+        );
+        assert!(spans[1].contains(&("http.request.resend_count", Value::Int(1))));
+    }
+}
@@ -0,0 +1,112 @@
+//! A field of a partial update, telling a field left alone apart from one
+//! that is cleared.
+//!
+//! The control endpoint only changes the fields a request names, and for
+//! some of them an explicit `null` resets the setting. `Option` cannot say
+//! both, so update builders hold their fields as a [`Patch`](enum.Patch.html)
+//! and skip the unset ones when serializing:
+//!
+//! ```
+//! use serde::Serialize;
+//! use sure_petcare::patch::Patch;
+//!
+//! #[derive(Serialize)]
+//! struct Update {
+//!     #[serde(skip_serializing_if = "Patch::is_unset")]
+//!     name: Patch<&'static str>,
+//!     #[serde(skip_serializing_if = "Patch::is_unset")]
+//!     note: Patch<&'static str>,
+//! }
+//!
+//! let update = Update { name: Patch::Value("Milo"), note: Patch::Null };
+//! assert_eq!(r#"{"name":"Milo","note":null}"#, serde_json::to_string(&update).unwrap());
+//! ```
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// One field of a partial update.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Patch<T> {
+    /// Leave the field as it is; the field is not sent.
+    #[default]
+    Unset,
+    /// Clear the field; sent as `null`.
+    Null,
+    /// Set the field to the value.
+    Value(T),
+}
+
+impl<T> Patch<T> {
+    /// Whether the field is left as it is, for `skip_serializing_if`.
+    pub fn is_unset(&self) -> bool {
+        matches!(self, Patch::Unset)
+    }
+
+    /// The value being set, if any.
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Patch::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Converts the value, keeping `Unset` and `Null` as they are.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Patch<U> {
+        match self {
+            Patch::Unset => Patch::Unset,
+            Patch::Null => Patch::Null,
+            Patch::Value(value) => Patch::Value(f(value)),
+        }
+    }
+}
+
+impl<T> From<T> for Patch<T> {
+    fn from(value: T) -> Self {
+        Patch::Value(value)
+    }
+}
+
+/// `None` clears the field rather than leaving it alone.
+impl<T> From<Option<T>> for Patch<T> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Patch::Null, Patch::Value)
+    }
+}
+
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Patch::Value(value) => value.serialize(serializer),
+            Patch::Unset | Patch::Null => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Reads `null` as `Null`; a missing field needs `#[serde(default)]` to read
+/// as `Unset`.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::<T>::deserialize(deserializer).map(Patch::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_tell_missing_from_null() {
+        #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+        struct Update {
+            #[serde(default, skip_serializing_if = "Patch::is_unset")]
+            mode: Patch<i64>,
+        }
+
+        assert_eq!("{}", serde_json::to_string(&Update::default()).unwrap());
+        for json in &[r#"{}"#, r#"{"mode":null}"#, r#"{"mode":2}"#] {
+            let update: Update = serde_json::from_str(json).unwrap();
+            assert_eq!(*json, serde_json::to_string(&update).unwrap());
+        }
+        assert_eq!(Patch::Null, Patch::<i64>::from(None));
+    }
+}
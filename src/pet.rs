@@ -0,0 +1,745 @@
+//! The pets registered to the account.
+#[cfg(feature = "pets")]
+use crate::batch::BatchResult;
+#[cfg(feature = "pets")]
+use crate::client::SurePet;
+#[cfg(feature = "pets")]
+use crate::device::Device;
+#[cfg(feature = "pets")]
+use crate::error::{Error, Result, ValidationError};
+use crate::float::HashFloat;
+use crate::id::{DeviceId, HouseholdId, PetId, PhotoId, TagId};
+use crate::names::names;
+#[cfg(feature = "pets")]
+use crate::notes::Note;
+use crate::notes::Notes;
+#[cfg(feature = "pets")]
+use crate::sort::Listing;
+use crate::sort::{Field, Key, Sortable};
+use crate::time::Timestamp;
+#[cfg(feature = "pets")]
+use crate::transport::{Method, Transport};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+
+/// Where a pet is, as sent in a position's `where` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Location {
+    /// The pet is indoors.
+    Inside,
+    /// The pet is outdoors.
+    Outside,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl Location {
+    /// The code the API uses for this location.
+    pub fn code(self) -> i64 {
+        match self {
+            Location::Inside => 1,
+            Location::Outside => 2,
+            Location::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for Location {
+    fn from(code: i64) -> Self {
+        match code {
+            1 => Location::Inside,
+            2 => Location::Outside,
+            code => Location::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for Location {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Location {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("location", Location {
+    Inside => "inside",
+    Outside => "outside",
+});
+
+/// What a flap lets a pet's tag do, as sent in a tag's `profile` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TagProfile {
+    /// The pet may come and go.
+    Outdoor,
+    /// The pet may come in but not go out.
+    IndoorOnly,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl TagProfile {
+    /// The code the API uses for this profile.
+    pub fn code(self) -> i64 {
+        match self {
+            TagProfile::Outdoor => 2,
+            TagProfile::IndoorOnly => 3,
+            TagProfile::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for TagProfile {
+    fn from(code: i64) -> Self {
+        match code {
+            2 => TagProfile::Outdoor,
+            3 => TagProfile::IndoorOnly,
+            code => TagProfile::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for TagProfile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for TagProfile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("tag profile", TagProfile {
+    Outdoor => "outdoor",
+    IndoorOnly => "indoor_only",
+});
+
+/// A pet and what its devices last saw of it.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Pet<'a> {
+    pub(crate) id: PetId,
+    pub(crate) name: Cow<'a, str>,
+    #[serde(default)]
+    pub(crate) household_id: Option<HouseholdId>,
+    #[serde(default)]
+    pub(crate) tag_id: Option<TagId>,
+    #[serde(default)]
+    pub(crate) position: Option<Position>,
+    #[serde(default)]
+    pub(crate) status: PetStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) photo: Option<Photo<'a>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) comments: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) conditions: Vec<Condition>,
+}
+
+impl<'a> Pet<'a> {
+    /// The pet id.
+    pub fn id(&self) -> PetId {
+        self.id
+    }
+
+    /// The pet's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The household the pet belongs to.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.household_id
+    }
+
+    /// The id of the microchip or collar tag assigned to the pet.
+    pub fn tag_id(&self) -> Option<TagId> {
+        self.tag_id
+    }
+
+    /// Where a flap last saw the pet, if it uses one.
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+
+    /// The pet's last meal and drink.
+    pub fn status(&self) -> &PetStatus {
+        &self.status
+    }
+
+    /// The pet's photo, if one was uploaded.
+    pub fn photo(&self) -> Option<&Photo<'a>> {
+        self.photo.as_ref()
+    }
+
+    /// The free text comments set in the app.
+    pub fn comments(&self) -> Option<&str> {
+        self.comments.as_deref()
+    }
+
+    /// The comments read as caretaker notes, see the
+    /// [`notes`](../notes/index.html) module.
+    pub fn notes(&self) -> Notes {
+        Notes::from_comments(self.comments())
+    }
+
+    /// The medical conditions recorded for the pet.
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+}
+
+/// A medical condition recorded for a pet, by the id the app lists it
+/// under. The API sends either the bare id or an object holding it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(transparent)]
+pub struct Condition(i64);
+
+impl Condition {
+    /// The condition with the given id.
+    pub const fn new(id: i64) -> Self {
+        Condition(id)
+    }
+
+    /// The condition's id.
+    pub fn id(self) -> i64 {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Id(i64),
+            Object { id: i64 },
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Id(id) | Raw::Object { id } => Condition(id),
+        })
+    }
+}
+
+impl<'a> Sortable for Pet<'a> {
+    fn sort_key(&self, field: Field) -> Key<'_> {
+        match field {
+            Field::Id => Key::Number(self.id.get()),
+            Field::Name => Key::Text(&self.name),
+            Field::CreatedAt | Field::UpdatedAt => Key::None,
+        }
+    }
+}
+
+/// A pet's photo. Its location is a signed url that expires after a while;
+/// [`PhotoCache`](../photo/struct.PhotoCache.html) keeps a copy on disk.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Photo<'a> {
+    pub(crate) id: PhotoId,
+    pub(crate) location: Cow<'a, str>,
+    #[serde(default)]
+    pub(crate) updated_at: Option<Timestamp>,
+}
+
+impl<'a> Photo<'a> {
+    /// The photo id, which stays the same when the url is signed again.
+    pub fn id(&self) -> PhotoId {
+        self.id
+    }
+
+    /// The url to download the photo from while the signature lasts.
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// When the photo was last replaced.
+    pub fn updated_at(&self) -> Option<Timestamp> {
+        self.updated_at
+    }
+}
+
+impl<'a> Sortable for Photo<'a> {
+    fn sort_key(&self, field: Field) -> Key<'_> {
+        match field {
+            Field::Id => Key::Number(self.id.get()),
+            Field::UpdatedAt => self.updated_at.into(),
+            Field::Name | Field::CreatedAt => Key::None,
+        }
+    }
+}
+
+/// Where a pet is and since when.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Position {
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    #[serde(rename = "where")]
+    pub(crate) location: Location,
+    pub(crate) since: Timestamp,
+}
+
+impl Position {
+    /// The flap that last saw the pet, `None` when set by hand.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// Whether the pet is inside or outside.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    /// When the pet went there.
+    pub fn since(&self) -> Timestamp {
+        self.since
+    }
+}
+
+/// The most recent feeder and water dispenser visits of a pet.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PetStatus {
+    #[serde(default)]
+    pub(crate) feeding: Option<Visit>,
+    #[serde(default)]
+    pub(crate) drinking: Option<Visit>,
+}
+
+impl PetStatus {
+    /// The last meal.
+    pub fn feeding(&self) -> Option<&Visit> {
+        self.feeding.as_ref()
+    }
+
+    /// The last drink.
+    pub fn drinking(&self) -> Option<&Visit> {
+        self.drinking.as_ref()
+    }
+}
+
+/// A single visit to a feeder or water dispenser.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Visit {
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    pub(crate) at: Timestamp,
+    #[serde(default)]
+    pub(crate) change: Vec<f64>,
+}
+
+impl Eq for Visit {}
+
+impl Hash for Visit {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.device_id.hash(state);
+        self.at.hash(state);
+        self.change.hash_float(state);
+    }
+}
+
+impl Visit {
+    /// The device visited.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// When the visit ended.
+    pub fn at(&self) -> Timestamp {
+        self.at
+    }
+
+    /// The change per bowl, negative when food or water was taken.
+    pub fn change(&self) -> &[f64] {
+        &self.change
+    }
+}
+
+/// The pet endpoints, obtained from [`SurePet::pets`](../client/struct.SurePet.html#method.pets).
+#[cfg(feature = "pets")]
+#[derive(Debug)]
+pub struct Pets<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+#[cfg(feature = "pets")]
+impl<'c, T: Transport> Pets<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Pets { client }
+    }
+
+    /// Fetches every pet on the account with its position, last visits and
+    /// photo.
+    pub fn list(&self) -> Result<Vec<Pet<'static>>> {
+        self.client.get(
+            "/api/pet",
+            &[
+                ("with[]", "position".to_string()),
+                ("with[]", "status".to_string()),
+                ("with[]", "photo".to_string()),
+            ],
+        )
+    }
+
+    /// Like [`list`](#method.list), sorted, see the
+    /// [`sort`](../sort/index.html) module.
+    pub fn query(&self) -> Listing<'c, T, Pet<'static>> {
+        Listing::new(
+            self.client,
+            "/api/pet",
+            vec![
+                ("with[]", "position".to_string()),
+                ("with[]", "status".to_string()),
+                ("with[]", "photo".to_string()),
+            ],
+        )
+    }
+
+    /// Fetches a single pet with its position, last visits and photo, or
+    /// takes it from the client's [state cache](../cache/struct.StateCache.html).
+    pub fn get(&self, pet_id: PetId) -> Result<Pet<'static>> {
+        let cache = self.client.state_cache();
+        if let Some(pet) = cache.and_then(|cache| cache.pet(pet_id)) {
+            return Ok(pet);
+        }
+        let pet: Pet<'static> = self.client.get(
+            &format!("/api/pet/{}", pet_id),
+            &[
+                ("with[]", "position".to_string()),
+                ("with[]", "status".to_string()),
+                ("with[]", "photo".to_string()),
+            ],
+        )?;
+        if let Some(cache) = cache {
+            cache.put_pet(pet.clone());
+        }
+        Ok(pet)
+    }
+
+    /// Records where a pet is, as when correcting it in the app after the
+    /// pet left through an open window.
+    pub fn set_location(&self, pet_id: PetId, location: Location, since: Timestamp) -> Result<()> {
+        let path = format!("/api/pet/{}/position", pet_id);
+        self.client.send_unit(
+            Method::Post,
+            &path,
+            Some(&PositionUpdate { location, since }),
+        )?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_pet(pet_id);
+        }
+        Ok(())
+    }
+
+    /// Adds a caretaker note to a pet's comments, keeping the notes already
+    /// there, and returns them all. The pet is fetched afresh rather than
+    /// from the state cache, so a note someone else just added is not
+    /// overwritten.
+    pub fn add_note(&self, pet_id: PetId, author: &str, text: &str) -> Result<Notes> {
+        if text.trim().is_empty() {
+            return Err(Error::Invalid(ValidationError::new(
+                "comments",
+                "the note is empty",
+            )));
+        }
+        let path = format!("/api/pet/{}", pet_id);
+        let pet: Pet<'static> = self.client.get(&path, &[])?;
+        let mut notes = pet.notes();
+        notes.push(Note::new(self.client.clock().now(), author, text));
+        let comments = notes.to_string();
+        self.client.send_unit(
+            Method::Put,
+            &path,
+            Some(&CommentsUpdate {
+                comments: &comments,
+            }),
+        )?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_pet(pet_id);
+        }
+        Ok(notes)
+    }
+
+    /// Replaces the medical conditions recorded for a pet.
+    pub fn set_conditions(&self, pet_id: PetId, conditions: &[Condition]) -> Result<()> {
+        let path = format!("/api/pet/{}", pet_id);
+        self.client
+            .send_unit(Method::Put, &path, Some(&ConditionsUpdate { conditions }))?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_pet(pet_id);
+        }
+        Ok(())
+    }
+
+    /// Records where several pets are, as when marking every pet inside for
+    /// the night. Pets that fail do not stop the rest from being updated.
+    pub fn set_all_locations(
+        &self,
+        pet_ids: &[PetId],
+        location: Location,
+        since: Timestamp,
+    ) -> BatchResult<PetId> {
+        pet_ids
+            .iter()
+            .map(|&pet_id| (pet_id, self.set_location(pet_id, location, since)))
+            .collect()
+    }
+
+    /// Keeps a pet in, or lets it out again, by changing its tag's profile
+    /// on every flap in its household. Flaps that fail do not stop the rest
+    /// from being updated; a pet without a tag fails with `Error::Invalid`
+    /// without a request.
+    pub fn set_indoor_only(
+        &self,
+        pet: &Pet<'_>,
+        indoor_only: bool,
+    ) -> Result<BatchResult<DeviceId>> {
+        let profile = if indoor_only {
+            TagProfile::IndoorOnly
+        } else {
+            TagProfile::Outdoor
+        };
+        self.set_tag_profile(pet, profile)
+    }
+
+    /// Lets a pet come and go through every flap in its household, like
+    /// [`set_indoor_only`](#method.set_indoor_only) with `false`.
+    pub fn allow_outside(&self, pet: &Pet<'_>) -> Result<BatchResult<DeviceId>> {
+        self.set_indoor_only(pet, false)
+    }
+
+    /// Sets a pet's tag profile on every flap in its household.
+    pub fn set_tag_profile(
+        &self,
+        pet: &Pet<'_>,
+        profile: TagProfile,
+    ) -> Result<BatchResult<DeviceId>> {
+        let tag_id = pet
+            .tag_id()
+            .ok_or_else(|| Error::Invalid(ValidationError::new("tag_id", "the pet has no tag")))?;
+        let devices: Vec<Device<'static>> = self
+            .client
+            .get("/api/device", &[("with[]", "status".to_string())])?;
+        Ok(devices
+            .iter()
+            .filter(|device| device.product().is_flap())
+            .filter(|device| {
+                pet.household_id().is_none() || device.household_id() == pet.household_id()
+            })
+            .map(|device| {
+                let path = format!("/api/device/{}/tag/{}", device.id(), tag_id);
+                let result =
+                    self.client
+                        .send_unit(Method::Put, &path, Some(&TagProfileUpdate { profile }));
+                (device.id(), result)
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "pets")]
+#[derive(Serialize)]
+struct TagProfileUpdate {
+    profile: TagProfile,
+}
+
+#[cfg(feature = "pets")]
+#[derive(Serialize)]
+struct CommentsUpdate<'a> {
+    comments: &'a str,
+}
+
+#[cfg(feature = "pets")]
+#[derive(Serialize)]
+struct ConditionsUpdate<'a> {
+    conditions: &'a [Condition],
+}
+
+#[cfg(feature = "pets")]
+#[derive(Serialize)]
+struct PositionUpdate {
+    #[serde(rename = "where")]
+    location: Location,
+    since: Timestamp,
+}
+
+#[cfg(all(test, feature = "pets"))]
+mod tests {
+    use super::*;
+    use crate::cache::StateCache;
+    use crate::clock::ManualClock;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn should_list_pets() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[{"id":2,"name":"Milo","tag_id":5,
+                "position":{"where":1,"since":"2019-06-01T05:00:00+00:00"}},
+                {"id":3,"name":"Tilly","position":{"where":9,"since":"2019-06-01T05:00:00+00:00"}}]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let pets = client.pets().list().unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/pet?with%5B%5D=position&with%5B%5D=status&with%5B%5D=photo",
+            transport.requests()[0].url
+        );
+        assert_eq!(Some(TagId::new(5)), pets[0].tag_id());
+        assert_eq!(Location::Inside, pets[0].position().unwrap().location());
+        assert_eq!(Location::Unknown(9), pets[1].position().unwrap().location());
+    }
+
+    #[test]
+    fn should_cache_pets_until_changed() {
+        let pet = r#"{"data":{"id":2,"name":"Milo"}}"#;
+        let transport = MockTransport::new()
+            .respond(200, pet)
+            .respond(200, r#"{"data":{"where":2}}"#)
+            .respond(200, pet);
+        let cache = StateCache::new(Duration::from_secs(60));
+        let client = SurePet::new(&transport, "token").with_state_cache(cache);
+        let pets = client.pets();
+
+        assert_eq!("Milo", pets.get(PetId::new(2)).unwrap().name());
+        assert_eq!("Milo", pets.get(PetId::new(2)).unwrap().name());
+        assert_eq!(1, transport.requests().len());
+        pets.set_location(PetId::new(2), Location::Outside, Timestamp::from_unix(0))
+            .unwrap();
+        pets.get(PetId::new(2)).unwrap();
+        assert_eq!(3, transport.requests().len());
+        assert_eq!(
+            "https://app.api.surehub.io/api/pet/2?with%5B%5D=position&with%5B%5D=status&with%5B%5D=photo",
+            transport.requests()[2].url
+        );
+    }
+
+    #[test]
+    fn should_set_location() {
+        let transport = MockTransport::new().respond(200, r#"{"data":{"where":2}}"#);
+        let client = SurePet::new(&transport, "token");
+        client
+            .pets()
+            .set_location(
+                PetId::new(2),
+                Location::Outside,
+                Timestamp::from_unix(1_559_372_400),
+            )
+            .unwrap();
+
+        let request = &transport.requests()[0];
+        assert_eq!(Method::Post, request.method);
+        assert_eq!("https://app.api.surehub.io/api/pet/2/position", request.url);
+        assert_eq!(
+            r#"{"where":2,"since":"2019-06-01T07:00:00+00:00"}"#,
+            String::from_utf8_lossy(request.body.as_ref().unwrap())
+        );
+    }
+
+    #[test]
+    fn should_add_notes_keeping_earlier_ones() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"id":2,"name":"Milo","conditions":[7,{"id":9}],
+                    "comments":"Likes chin scratches\n[2019-05-31T18:00:00+00:00 Alex] wormed"}}"#,
+            )
+            .respond(200, r#"{"data":{}}"#)
+            .respond(200, r#"{"data":{}}"#);
+        let client = SurePet::new(&transport, "token")
+            .with_clock(ManualClock::new(Timestamp::from_unix(1_559_372_400)));
+        let notes = client
+            .pets()
+            .add_note(PetId::new(2), "Sam", "on medication,\nfeed at 8am")
+            .unwrap();
+        client
+            .pets()
+            .set_conditions(PetId::new(2), &[Condition::new(7)])
+            .unwrap();
+
+        assert_eq!(3, notes.notes().len());
+        assert_eq!(None, notes.notes()[0].at());
+        assert_eq!(Some("Alex"), notes.notes()[1].author());
+        assert_eq!("on medication, feed at 8am", notes.latest().unwrap().text());
+        let requests = transport.requests();
+        assert_eq!("https://app.api.surehub.io/api/pet/2", requests[0].url);
+        assert_eq!(Method::Put, requests[1].method);
+        assert_eq!(
+            r#"{"comments":"Likes chin scratches\n[2019-05-31T18:00:00+00:00 Alex] wormed\n[2019-06-01T07:00:00+00:00 Sam] on medication, feed at 8am"}"#,
+            String::from_utf8_lossy(requests[1].body.as_ref().unwrap())
+        );
+        assert_eq!(
+            r#"{"conditions":[7]}"#,
+            String::from_utf8_lossy(requests[2].body.as_ref().unwrap())
+        );
+        let pet: Pet =
+            serde_json::from_str(r#"{"id":2,"name":"Milo","conditions":[7,{"id":9}]}"#).unwrap();
+        assert_eq!(&[Condition::new(7), Condition::new(9)], pet.conditions());
+    }
+
+    #[test]
+    fn should_set_every_location_it_can() {
+        let transport = MockTransport::new()
+            .respond(200, r#"{"data":{"where":1}}"#)
+            .respond(404, r#"{"error":"not found"}"#)
+            .respond(200, r#"{"data":{"where":1}}"#);
+        let client = SurePet::new(&transport, "token");
+        let pets = [PetId::new(2), PetId::new(3), PetId::new(4)];
+        let batch = client.pets().set_all_locations(
+            &pets,
+            Location::Inside,
+            Timestamp::from_unix(1_559_372_400),
+        );
+
+        assert_eq!(3, batch.len());
+        assert_eq!(vec![PetId::new(2), PetId::new(4)], batch.succeeded_keys());
+        assert_eq!(vec![PetId::new(3)], batch.failed_keys());
+        assert!(batch.into_result().is_err());
+    }
+
+    #[test]
+    fn should_keep_a_pet_in_through_every_flap_in_its_household() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[{"id":10,"name":"Back door","product_id":6,"household_id":1},
+                    {"id":11,"name":"Hub","product_id":1,"household_id":1},
+                    {"id":12,"name":"Front door","product_id":3,"household_id":1},
+                    {"id":13,"name":"Cabin","product_id":6,"household_id":2}]}"#,
+            )
+            .respond(200, r#"{"data":{"profile":3}}"#)
+            .respond(504, r#"{"error":"timeout"}"#);
+        let client = SurePet::new(&transport, "token");
+        let milo: Pet<'_> =
+            serde_json::from_str(r#"{"id":2,"name":"Milo","household_id":1,"tag_id":5}"#).unwrap();
+
+        let batch = client.pets().set_indoor_only(&milo, true).unwrap();
+
+        assert_eq!(vec![DeviceId::new(10)], batch.succeeded_keys());
+        assert_eq!(vec![DeviceId::new(12)], batch.failed_keys());
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(Method::Put, requests[1].method);
+        assert_eq!(
+            "https://app.api.surehub.io/api/device/10/tag/5",
+            requests[1].url
+        );
+        assert_eq!(
+            r#"{"profile":3}"#,
+            String::from_utf8_lossy(requests[1].body.as_ref().unwrap())
+        );
+
+        let untagged: Pet<'_> = serde_json::from_str(r#"{"id":3,"name":"Tilly"}"#).unwrap();
+        assert!(matches!(
+            client.pets().allow_outside(&untagged),
+            Err(Error::Invalid(_))
+        ));
+        assert_eq!(3, transport.requests().len());
+    }
+}
@@ -0,0 +1,511 @@
+//! Photos of pets, households and devices.
+//!
+//! [`Photos`](struct.Photos.html), from
+//! [`SurePet::photos`](../client/struct.SurePet.html#method.photos), lists,
+//! uploads and deletes photos, each attached to a pet, a household or a
+//! device, as when an installer records where each flap was fitted.
+//!
+//! A [`Photo`](../pet/struct.Photo.html) points at a signed url that
+//! expires, and downloading it on every redraw of a dashboard is slow and
+//! wasteful. A [`PhotoCache`](struct.PhotoCache.html) downloads each photo
+//! once into a directory, keyed by photo id, and hands out the local file.
+//! A copy is downloaded again once the pet's photo has been replaced, or
+//! when it is older than the maximum age; if that download fails, say
+//! because the url in hand has expired, the old copy is used until a
+//! fresh url comes along.
+use crate::client::SurePet;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{Error, Result};
+use crate::id::{DeviceId, HouseholdId, PetId, PhotoId};
+use crate::pet::Photo;
+use crate::sort::Listing;
+use crate::time::Timestamp;
+use crate::transport::{Method, Request, Transport};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The largest photo downloaded, in bytes.
+pub const MAX_PHOTO_SIZE: usize = 16 * 1024 * 1024;
+
+/// What a photo is of.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Subject {
+    /// A pet; its photo is the one shown for it.
+    Pet(PetId),
+    /// A household.
+    Household(HouseholdId),
+    /// A device, as when recording where it is fitted.
+    Device(DeviceId),
+}
+
+impl Subject {
+    /// The form field and query parameter naming the subject.
+    fn field(self) -> (&'static str, String) {
+        match self {
+            Subject::Pet(id) => ("pet_id", id.to_string()),
+            Subject::Household(id) => ("household_id", id.to_string()),
+            Subject::Device(id) => ("device_id", id.to_string()),
+        }
+    }
+}
+
+/// An image to upload with [`Photos::upload`](struct.Photos.html#method.upload).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PhotoUpload<'a> {
+    bytes: Cow<'a, [u8]>,
+    file_name: String,
+    content_type: String,
+    subject: Option<Subject>,
+}
+
+impl<'a> PhotoUpload<'a> {
+    /// An image read from a file called `file_name`, its type taken from
+    /// the extension: JPEG, PNG, GIF or WebP.
+    pub fn new<B: Into<Cow<'a, [u8]>>>(bytes: B, file_name: &str) -> Self {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+        let content_type = match extension.as_deref() {
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => "application/octet-stream",
+        };
+        PhotoUpload {
+            bytes: bytes.into(),
+            file_name: file_name.to_string(),
+            content_type: content_type.to_string(),
+            subject: None,
+        }
+    }
+
+    /// Sets the media type, for names without a known extension.
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = content_type.to_string();
+        self
+    }
+
+    /// Attaches the photo to a pet, household or device.
+    pub fn attached_to(mut self, subject: Subject) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    /// The body and content type of the `multipart/form-data` request,
+    /// with a boundary that does not occur in the image.
+    fn encode(&self) -> (String, Vec<u8>) {
+        let mut hasher = DefaultHasher::new();
+        self.bytes.hash(&mut hasher);
+        let mut seed = hasher.finish();
+        let boundary = loop {
+            let boundary = format!("surepet-{:016x}", seed);
+            let found = self
+                .bytes
+                .windows(boundary.len())
+                .any(|window| window == boundary.as_bytes());
+            if !found {
+                break boundary;
+            }
+            seed = seed.wrapping_add(1);
+        };
+        let mut body = Vec::with_capacity(self.bytes.len() + 512);
+        if let Some((name, value)) = self.subject.map(Subject::field) {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    boundary, name, value
+                )
+                .as_bytes(),
+            );
+        }
+        let file_name: String = self
+            .file_name
+            .chars()
+            .map(|c| if c == '"' || c.is_control() { '_' } else { c })
+            .collect();
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: {}\r\n\r\n",
+                boundary, file_name, self.content_type
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&self.bytes);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        (format!("multipart/form-data; boundary={}", boundary), body)
+    }
+}
+
+/// The photo endpoints, obtained from [`SurePet::photos`](../client/struct.SurePet.html#method.photos).
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::id::DeviceId;
+/// use sure_petcare::photo::{PhotoUpload, Subject};
+/// let image = std::fs::read("back-door.jpg").map_err(sure_petcare::error::Error::transport)?;
+/// let upload = PhotoUpload::new(image, "back-door.jpg").attached_to(Subject::Device(DeviceId::new(10)));
+/// let photo = client.photos().upload(&upload)?;
+/// println!("uploaded {}", photo.location());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Photos<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+impl<'c, T: Transport> Photos<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Photos { client }
+    }
+
+    /// Fetches every photo on the account.
+    pub fn list(&self) -> Result<Vec<Photo<'static>>> {
+        self.client.get("/api/photo", &[])
+    }
+
+    /// Like [`list`](#method.list), sorted, see the
+    /// [`sort`](../sort/index.html) module.
+    pub fn query(&self) -> Listing<'c, T, Photo<'static>> {
+        Listing::new(self.client, "/api/photo", Vec::new())
+    }
+
+    /// Fetches the photos attached to a pet, household or device.
+    pub fn list_for(&self, subject: Subject) -> Result<Vec<Photo<'static>>> {
+        let (name, value) = subject.field();
+        self.client.get("/api/photo", &[(name, value)])
+    }
+
+    /// Uploads a photo, returning it with the location it is served from.
+    pub fn upload(&self, upload: &PhotoUpload<'_>) -> Result<Photo<'static>> {
+        let (content_type, body) = upload.encode();
+        let photo = self
+            .client
+            .send_encoded(Method::Post, "/api/photo", &content_type, body)?;
+        self.invalidate(upload.subject);
+        Ok(photo)
+    }
+
+    /// Deletes a photo. Pass what it was attached to, if known, so a cached
+    /// pet or device does not keep showing it.
+    pub fn delete(&self, photo_id: PhotoId, subject: Option<Subject>) -> Result<()> {
+        let path = format!("/api/photo/{}", photo_id);
+        self.client.send_unit::<()>(Method::Delete, &path, None)?;
+        self.invalidate(subject);
+        Ok(())
+    }
+
+    fn invalidate(&self, subject: Option<Subject>) {
+        let cache = match self.client.state_cache() {
+            Some(cache) => cache,
+            None => return,
+        };
+        match subject {
+            Some(Subject::Pet(pet_id)) => cache.invalidate_pet(pet_id),
+            Some(Subject::Device(device_id)) => cache.invalidate_device(device_id),
+            _ => {}
+        }
+    }
+}
+
+/// What the cache knows about a stored copy, kept next to it.
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    file: String,
+    updated_at: Option<Timestamp>,
+    fetched_at: Timestamp,
+}
+
+/// Photos downloaded into a directory, see the module documentation.
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "pets")]
+/// # fn run<T: sure_petcare::transport::Transport>(client: &sure_petcare::client::SurePet<T>, transport: T) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::photo::PhotoCache;
+/// let photos = PhotoCache::new(transport, "/var/cache/surepet/photos");
+/// for pet in client.pets().list()? {
+///     if let Some(photo) = pet.photo() {
+///         println!("{}: {}", pet.name(), photos.path(photo)?.display());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct PhotoCache<T> {
+    transport: T,
+    dir: PathBuf,
+    max_age: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for PhotoCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PhotoCache")
+            .field("transport", &self.transport)
+            .field("dir", &self.dir)
+            .field("max_age", &self.max_age)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Transport> PhotoCache<T> {
+    /// A cache downloading with `transport` into `dir`, which is created
+    /// when the first photo is stored. Copies are kept for a week.
+    pub fn new<P: Into<PathBuf>>(transport: T, dir: P) -> Self {
+        PhotoCache {
+            transport,
+            dir: dir.into(),
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Downloads copies again once they are older than `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Replaces the system clock copies are timed with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The directory photos are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The file holding a photo, downloading it first unless a current
+    /// copy is stored.
+    pub fn path(&self, photo: &Photo<'_>) -> Result<PathBuf> {
+        let now = self.clock.now();
+        let stored = self
+            .entry(photo.id())
+            .filter(|entry| entry.updated_at == photo.updated_at());
+        if let Some(entry) = &stored {
+            if now.elapsed_since(entry.fetched_at) < self.max_age {
+                return Ok(self.dir.join(&entry.file));
+            }
+        }
+        match (self.download(photo, now), stored) {
+            (Err(_), Some(entry)) => Ok(self.dir.join(entry.file)),
+            (result, _) => result,
+        }
+    }
+
+    /// The bytes of a photo, downloading it first unless a current copy is
+    /// stored.
+    pub fn bytes(&self, photo: &Photo<'_>) -> Result<Vec<u8>> {
+        fs::read(self.path(photo)?).map_err(Error::transport)
+    }
+
+    /// Forgets a photo, so it is downloaded again when next asked for.
+    pub fn invalidate(&self, photo_id: PhotoId) -> Result<()> {
+        if let Some(entry) = self.entry(photo_id) {
+            remove(&self.dir.join(entry.file))?;
+        }
+        remove(&self.entry_path(photo_id))
+    }
+
+    /// Forgets every photo in the directory.
+    pub fn clear(&self) -> Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(Error::transport(error)),
+        };
+        for entry in entries {
+            let path = entry.map_err(Error::transport)?.path();
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse().ok());
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                if let Some(id) = id {
+                    self.invalidate(PhotoId::new(id))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn download(&self, photo: &Photo<'_>, now: Timestamp) -> Result<PathBuf> {
+        let response = self.transport.send(&Request {
+            method: Method::Get,
+            url: photo.location().to_string(),
+            headers: Vec::new(),
+            body: None,
+            max_body_size: Some(MAX_PHOTO_SIZE),
+        })?;
+        if !response.is_success() {
+            return Err(Error::Status {
+                status: response.status,
+                body: String::from_utf8_lossy(&response.body).into_owned(),
+            });
+        }
+        if response.body.len() > MAX_PHOTO_SIZE {
+            return Err(Error::TooLarge {
+                limit: MAX_PHOTO_SIZE,
+            });
+        }
+        let extension = match response.content_type() {
+            Some("image/png") => "png",
+            Some("image/gif") => "gif",
+            Some("image/webp") => "webp",
+            _ => "jpg",
+        };
+        let file = format!("{}.{}", photo.id(), extension);
+        fs::create_dir_all(&self.dir).map_err(Error::transport)?;
+        if let Some(old) = self.entry(photo.id()).filter(|old| old.file != file) {
+            remove(&self.dir.join(old.file))?;
+        }
+        let path = self.dir.join(&file);
+        write(&path, &response.body)?;
+        let entry = Entry {
+            file,
+            updated_at: photo.updated_at(),
+            fetched_at: now,
+        };
+        write(&self.entry_path(photo.id()), &serde_json::to_vec(&entry)?)?;
+        Ok(path)
+    }
+
+    fn entry(&self, photo_id: PhotoId) -> Option<Entry> {
+        let bytes = fs::read(self.entry_path(photo_id)).ok()?;
+        let entry: Entry = serde_json::from_slice(&bytes).ok()?;
+        Some(entry).filter(|entry| self.dir.join(&entry.file).is_file())
+    }
+
+    fn entry_path(&self, photo_id: PhotoId) -> PathBuf {
+        self.dir.join(format!("{}.json", photo_id))
+    }
+}
+
+/// Writes through a temporary file, so readers never see half a photo.
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, bytes).map_err(Error::transport)?;
+    fs::rename(&partial, path).map_err(Error::transport)
+}
+
+fn remove(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(Error::transport(error)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_upload_list_and_delete_photos() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"id":7,"location":"https://photos.example.com/7?sig=x"}}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":7,"location":"https://photos.example.com/7?sig=y"}]}"#,
+            )
+            .respond(204, "");
+        let client = SurePet::new(&transport, "token");
+        let door = Subject::Device(DeviceId::new(10));
+        let upload = PhotoUpload::new(&b"\xff\xd8jpeg"[..], "Back \"door\".JPG").attached_to(door);
+
+        let photo = client.photos().upload(&upload).unwrap();
+        let listed = client.photos().list_for(door).unwrap();
+        client.photos().delete(photo.id(), Some(door)).unwrap();
+
+        assert_eq!(PhotoId::new(7), photo.id());
+        assert_eq!("https://photos.example.com/7?sig=y", listed[0].location());
+        let requests = transport.requests();
+        let content_type = requests[0].header("Content-Type").unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        let mut expected = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"device_id\"\r\n\r\n10\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"Back _door_.JPG\"\r\n\
+             Content-Type: image/jpeg\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        expected.extend_from_slice(b"\xff\xd8jpeg");
+        expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        assert_eq!(Some(expected), requests[0].body);
+        assert_eq!("https://app.api.surehub.io/api/photo", requests[0].url);
+        assert_eq!(
+            "https://app.api.surehub.io/api/photo?device_id=10",
+            requests[1].url
+        );
+        assert_eq!(Method::Delete, requests[2].method);
+        assert_eq!("https://app.api.surehub.io/api/photo/7", requests[2].url);
+    }
+
+    #[test]
+    fn should_download_once_and_again_when_replaced_or_stale() {
+        let dir = std::env::temp_dir().join(format!("surepet-photos-{}", std::process::id()));
+        let transport = MockTransport::new()
+            .respond(200, "first")
+            .with_header("Content-Type", "image/png")
+            .respond(200, "second")
+            .with_header("Content-Type", "image/jpeg")
+            .respond(403, "expired")
+            .respond(200, "third")
+            .with_header("Content-Type", "image/jpeg");
+        let clock = Arc::new(ManualClock::new("2019-06-01T12:00:00Z".parse().unwrap()));
+        let photos = PhotoCache::new(&transport, &dir)
+            .with_max_age(Duration::from_secs(3600))
+            .with_clock(Arc::clone(&clock));
+        let photo = |updated: &str| -> Photo<'static> {
+            let json = format!(
+                r#"{{"id":7,"location":"https://photos.example.com/7?sig=x","updated_at":"{}"}}"#,
+                updated
+            );
+            serde_json::from_str(&json).unwrap()
+        };
+        let old = photo("2019-05-01T00:00:00Z");
+
+        assert_eq!(dir.join("7.png"), photos.path(&old).unwrap());
+        assert_eq!(b"first".to_vec(), photos.bytes(&old).unwrap());
+        assert_eq!(1, transport.requests().len());
+        assert!(transport.requests()[0].headers.is_empty());
+
+        let new = photo("2019-06-01T11:00:00Z");
+        assert_eq!(b"second".to_vec(), photos.bytes(&new).unwrap());
+        assert!(!dir.join("7.png").exists());
+
+        // Stale, and the url has expired: the old copy will do.
+        clock.advance(Duration::from_secs(2 * 3600));
+        assert_eq!(b"second".to_vec(), photos.bytes(&new).unwrap());
+        assert_eq!(b"third".to_vec(), photos.bytes(&new).unwrap());
+        assert_eq!(4, transport.requests().len());
+
+        photos.clear().unwrap();
+        assert!(!dir.join("7.jpg").exists());
+        assert!(photos.bytes(&new).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
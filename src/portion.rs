@@ -0,0 +1,243 @@
+//! Working out feeder bowl targets from a daily food budget.
+//!
+//! Feeders take a target weight per bowl, but feeding guides give a daily
+//! amount, often in kilocalories, and differ between foods. A
+//! [`Portioning`](struct.Portioning.html) turns a daily
+//! [`Budget`](enum.Budget.html) into the grams for each bowl at each meal,
+//! given the energy density of the [`Food`](struct.Food.html) in it. A
+//! feeder with its bowl split in halves can serve wet food in one half and
+//! dry in the other, the budget shared between them in a given ratio.
+use crate::device::{ControlUpdate, ControlUpdateBuilder, MAX_BOWL_TARGET};
+use crate::error::{Error, Result, ValidationError};
+
+/// How much a pet should eat in a day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Budget {
+    /// A weight of food in grams.
+    Grams(f64),
+    /// An amount of energy in kilocalories.
+    Kilocalories(f64),
+}
+
+/// A food, known by how much energy it holds.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::portion::Food;
+/// let dry = Food::new(3800.0);
+/// assert_eq!(50.0, dry.grams(190.0));
+/// assert_eq!(190.0, dry.kilocalories(50.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Food {
+    kcal_per_kg: f64,
+}
+
+impl Food {
+    /// A food holding `kcal_per_kg` kilocalories per kilogram, as printed on
+    /// the packet; typically around 3,500 to 4,000 for dry food and 700 to
+    /// 1,100 for wet.
+    pub fn new(kcal_per_kg: f64) -> Self {
+        Food { kcal_per_kg }
+    }
+
+    /// The energy density in kilocalories per kilogram.
+    pub fn kcal_per_kg(&self) -> f64 {
+        self.kcal_per_kg
+    }
+
+    /// The grams of the food holding `kilocalories`.
+    pub fn grams(&self, kilocalories: f64) -> f64 {
+        kilocalories * 1000.0 / self.kcal_per_kg
+    }
+
+    /// The kilocalories in `grams` of the food.
+    pub fn kilocalories(&self, grams: f64) -> f64 {
+        grams * self.kcal_per_kg / 1000.0
+    }
+}
+
+/// Bowl targets worked out from a daily budget, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::portion::{Budget, Food, Portioning};
+/// let dry = Food::new(3800.0);
+/// let wet = Food::new(800.0);
+/// let portions = Portioning::new(Budget::Kilocalories(240.0), dry).with_meals(2);
+/// assert_eq!(vec![32], portions.targets()?);
+/// // Two thirds of the energy from wet food, in bowl 0.
+/// let split = Portioning::split(Budget::Kilocalories(240.0), wet, dry, 2.0 / 3.0).with_meals(2);
+/// assert_eq!(vec![100, 11], split.targets()?);
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Portioning {
+    budget: Budget,
+    meals: u32,
+    bowls: Vec<(Food, f64)>,
+}
+
+impl Portioning {
+    /// Serves the whole budget of one food from a single bowl, in one meal
+    /// a day.
+    pub fn new(budget: Budget, food: Food) -> Self {
+        Portioning {
+            budget,
+            meals: 1,
+            bowls: vec![(food, 1.0)],
+        }
+    }
+
+    /// Serves a budget from a split bowl: `first` in bowl 0 taking
+    /// `first_share` of it, between `0.0` and `1.0`, and `second` in bowl 1
+    /// the rest. The shares are of the energy for a budget in kilocalories
+    /// and of the weight for one in grams.
+    pub fn split(budget: Budget, first: Food, second: Food, first_share: f64) -> Self {
+        Portioning {
+            budget,
+            meals: 1,
+            bowls: vec![(first, first_share), (second, 1.0 - first_share)],
+        }
+    }
+
+    /// Spreads the budget over `meals` fills a day.
+    pub fn with_meals(mut self, meals: u32) -> Self {
+        self.meals = meals;
+        self
+    }
+
+    /// The daily budget.
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    /// The number of fills a day.
+    pub fn meals(&self) -> u32 {
+        self.meals
+    }
+
+    /// The grams to serve in each bowl at each meal, to the nearest gram.
+    /// Budgets, densities and shares that are negative or not finite, no
+    /// meals, and targets over the feeder's limit fail with
+    /// `Error::Invalid`.
+    pub fn targets(&self) -> Result<Vec<u32>> {
+        let invalid = |field, reason: String| Error::from(ValidationError::new(field, reason));
+        let daily = match self.budget {
+            Budget::Grams(grams) | Budget::Kilocalories(grams) => grams,
+        };
+        if !daily.is_finite() || daily < 0.0 {
+            return Err(invalid("budget", format!("of {} is not an amount", daily)));
+        }
+        if self.meals == 0 {
+            return Err(invalid("meals", "must be at least one".into()));
+        }
+        self.bowls
+            .iter()
+            .map(|&(food, share)| {
+                if !(0.0..=1.0).contains(&share) {
+                    return Err(invalid(
+                        "share",
+                        format!("of {} is not between 0 and 1", share),
+                    ));
+                }
+                let grams = match self.budget {
+                    Budget::Grams(grams) => grams * share,
+                    Budget::Kilocalories(kcal) => {
+                        if !food.kcal_per_kg.is_finite() || food.kcal_per_kg <= 0.0 {
+                            return Err(invalid(
+                                "food",
+                                format!("density of {} kcal/kg is not positive", food.kcal_per_kg),
+                            ));
+                        }
+                        food.grams(kcal * share)
+                    }
+                };
+                let target = (grams / f64::from(self.meals)).round();
+                if target > f64::from(MAX_BOWL_TARGET) {
+                    return Err(invalid(
+                        "bowls",
+                        format!(
+                            "target of {} g is over the {} g limit",
+                            target, MAX_BOWL_TARGET
+                        ),
+                    ));
+                }
+                Ok(target as u32)
+            })
+            .collect()
+    }
+
+    /// The feeder update setting the targets, to send with
+    /// [`DeviceHandle::set_portions`](../device/struct.DeviceHandle.html#method.set_portions)
+    /// or [`Devices::update`](../device/struct.Devices.html#method.update).
+    pub fn update(&self) -> Result<ControlUpdate> {
+        Ok(ControlUpdateBuilder::new()
+            .with_bowl_targets(&self.targets()?)
+            .build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Product;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_portion_budgets_across_meals_and_bowls() {
+        let dry = Food::new(3800.0);
+        let wet = Food::new(850.0);
+
+        assert_eq!(
+            vec![20],
+            Portioning::new(Budget::Grams(60.0), dry)
+                .with_meals(3)
+                .targets()
+                .unwrap()
+        );
+        assert_eq!(
+            vec![188, 11],
+            Portioning::split(Budget::Kilocalories(400.0), wet, dry, 0.8)
+                .with_meals(2)
+                .targets()
+                .unwrap()
+        );
+        assert_eq!(
+            vec![30, 10],
+            Portioning::split(Budget::Grams(40.0), wet, dry, 0.75)
+                .targets()
+                .unwrap()
+        );
+        let update = Portioning::new(Budget::Kilocalories(190.0), dry)
+            .update()
+            .unwrap();
+        assert_eq!(
+            r#"{"bowls":{"type":1,"settings":[{"target":50}]}}"#,
+            serde_json::to_string(&update).unwrap()
+        );
+        assert!(update.validate(Product::Feeder).is_ok());
+
+        let reason = |portions: Portioning| match portions.targets() {
+            Err(Error::Invalid(error)) => error.field(),
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            "meals",
+            reason(Portioning::new(Budget::Grams(60.0), dry).with_meals(0))
+        );
+        assert_eq!(
+            "share",
+            reason(Portioning::split(Budget::Grams(60.0), wet, dry, 1.5))
+        );
+        assert_eq!(
+            "food",
+            reason(Portioning::new(Budget::Kilocalories(200.0), Food::new(0.0)))
+        );
+        assert_eq!(
+            "bowls",
+            reason(Portioning::new(Budget::Kilocalories(2000.0), wet))
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! What the API says about each kind of hardware.
+//!
+//! The [`Product`](../device/enum.Product.html) enum knows the products
+//! there were when this crate was written; newer hardware comes through as
+//! `Product::Unknown` with its raw id. The API's product list,
+//! fetched with [`SurePet::products`](../client/struct.SurePet.html#method.products),
+//! names every product and what it can do. Registering it with
+//! [`ProductCatalogue::register`](struct.ProductCatalogue.html#method.register)
+//! lets [`Product::display_name`](../device/enum.Product.html#method.display_name)
+//! name unknown products for the rest of the process, so logs and menus
+//! show "Pet Door Connect" rather than `9`.
+use crate::device::Product;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+use std::sync::RwLock;
+
+static NAMES: RwLock<BTreeMap<i64, String>> = RwLock::new(BTreeMap::new());
+
+/// One entry of the product list.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ProductInfo {
+    pub(crate) id: i64,
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) capabilities: Vec<String>,
+    #[serde(flatten)]
+    pub(crate) other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ProductInfo {
+    /// The product, `Product::Unknown` for hardware newer than the crate.
+    pub fn product(&self) -> Product {
+        Product::from(self.id)
+    }
+
+    /// The product's name, such as `Microchip Pet Feeder Connect`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What the product can do, such as `curfew` or `bowls`, as the API
+    /// names it.
+    pub fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    /// Whether the product lists a capability, ignoring case.
+    pub fn has(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(capability))
+    }
+
+    /// Fields of the entry the crate does not model.
+    pub fn other(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.other
+    }
+}
+
+/// The products the API knows, by id.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::Product;
+/// use sure_petcare::product::ProductCatalogue;
+/// let catalogue: ProductCatalogue = serde_json::from_str(
+///     r#"[{"id":6,"name":"DualScan Cat Flap Connect","capabilities":["curfew"]},
+///         {"id":9,"name":"Pet Door Connect","capabilities":["curfew"]}]"#,
+/// )?;
+/// assert_eq!("Pet Door Connect", catalogue.name(Product::Unknown(9)));
+/// assert!(catalogue.get(Product::CatFlap).unwrap().has("curfew"));
+/// catalogue.register();
+/// assert_eq!("Pet Door Connect", Product::Unknown(9).display_name());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ProductCatalogue {
+    products: BTreeMap<i64, ProductInfo>,
+}
+
+impl<'de> Deserialize<'de> for ProductCatalogue {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let products = Vec::<ProductInfo>::deserialize(deserializer)?;
+        Ok(products.into_iter().collect())
+    }
+}
+
+impl FromIterator<ProductInfo> for ProductCatalogue {
+    fn from_iter<I: IntoIterator<Item = ProductInfo>>(products: I) -> Self {
+        ProductCatalogue {
+            products: products.into_iter().map(|info| (info.id, info)).collect(),
+        }
+    }
+}
+
+impl ProductCatalogue {
+    /// The entry for a product.
+    pub fn get(&self, product: Product) -> Option<&ProductInfo> {
+        self.products.get(&product.code())
+    }
+
+    /// Every entry, by id.
+    pub fn products(&self) -> impl Iterator<Item = &ProductInfo> {
+        self.products.values()
+    }
+
+    /// The catalogue's name for a product, or its
+    /// [`display_name`](../device/enum.Product.html#method.display_name)
+    /// when the catalogue lacks it.
+    pub fn name(&self, product: Product) -> String {
+        match self.get(product) {
+            Some(info) => info.name.clone(),
+            None => product.display_name(),
+        }
+    }
+
+    /// Remembers the names of the products the crate does not know, for
+    /// [`Product::display_name`](../device/enum.Product.html#method.display_name)
+    /// to use from now on. Known products keep their own names.
+    pub fn register(&self) {
+        let mut names = match NAMES.write() {
+            Ok(names) => names,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        for info in self.products.values() {
+            if let Product::Unknown(code) = info.product() {
+                names.insert(code, info.name.clone());
+            }
+        }
+    }
+}
+
+/// The name registered for an unknown product code.
+pub(crate) fn registered_name(code: i64) -> Option<String> {
+    match NAMES.read() {
+        Ok(names) => names.get(&code).cloned(),
+        Err(poisoned) => poisoned.into_inner().get(&code).cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_name_unknown_products_from_the_catalogue() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[{"id":4,"name":"Microchip Pet Feeder Connect","capabilities":["Bowls"]},
+                {"id":9921,"name":"Future Flap","capabilities":["curfew","locking"],"released":2030}]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let future = Product::Unknown(9921);
+        assert_eq!("product 9921", future.display_name());
+
+        let catalogue = client.products().unwrap();
+        catalogue.register();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/product",
+            transport.requests()[0].url
+        );
+        assert_eq!("Future Flap", future.display_name());
+        assert_eq!("Future Flap", catalogue.name(future));
+        assert_eq!("feeder lite", catalogue.name(Product::FeederLite));
+        assert_eq!(
+            "Microchip Pet Feeder Connect",
+            catalogue.name(Product::Feeder)
+        );
+        let feeder = catalogue.get(Product::Feeder).unwrap();
+        assert!(feeder.has("bowls"));
+        assert!(!feeder.has("curfew"));
+        assert_eq!(
+            Some(&serde_json::json!(2030)),
+            catalogue.get(future).unwrap().other().get("released")
+        );
+        assert_eq!("cat flap", Product::CatFlap.display_name());
+    }
+}
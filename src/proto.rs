@@ -0,0 +1,183 @@
+//! Protocol buffer encoding of the core models.
+//!
+//! [`SCHEMA`](constant.SCHEMA.html) holds the `.proto` definitions, which
+//! other services can generate code from, and the `encode_*` functions write
+//! the models in the matching wire format, so Sure Petcare data can be
+//! forwarded over existing protobuf channels without a schema of their own.
+use crate::device::Device;
+use crate::pet::Pet;
+use crate::timeline::Event;
+
+/// The `.proto` definitions of the encoded messages.
+pub const SCHEMA: &str = include_str!("../proto/sure_petcare.proto");
+
+/// Encodes a pet as a `sure_petcare.Pet` message.
+pub fn encode_pet(pet: &Pet<'_>) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.int64(1, pet.id());
+    writer.string(2, pet.name());
+    writer.optional_int64(3, pet.household_id());
+    writer.optional_int64(4, pet.tag_id());
+    if let Some(position) = pet.position() {
+        let mut message = Writer::default();
+        message.optional_int64(1, position.device_id());
+        message.int64(2, position.location().code());
+        message.int64(3, position.since().unix());
+        writer.message(5, message);
+    }
+    writer.bytes
+}
+
+/// Encodes a device as a `sure_petcare.Device` message.
+pub fn encode_device(device: &Device<'_>) -> Vec<u8> {
+    let status = device.status();
+    let mut writer = Writer::default();
+    writer.int64(1, device.id());
+    writer.string(2, device.name());
+    writer.int64(3, device.product().code());
+    writer.optional_int64(4, device.household_id());
+    writer.optional_int64(5, device.parent_device_id());
+    if let Some(battery) = status.battery() {
+        writer.key(6, 1);
+        writer.bytes.extend_from_slice(&battery.to_le_bytes());
+    }
+    if let Some(online) = status.online() {
+        writer.key(7, 0);
+        writer.varint(online as u64);
+    }
+    writer.optional_int64(8, status.lock_mode().map(|mode| mode.code()));
+    writer.bytes
+}
+
+/// Encodes an event as a `sure_petcare.Event` message.
+pub fn encode_event(event: &Event<'_>) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.int64(1, event.id());
+    writer.int64(2, event.kind().code());
+    writer.int64(3, event.created_at().unix());
+    writer.optional_int64(4, event.household_id());
+    for pet in event.pets() {
+        let mut message = Writer::default();
+        message.int64(1, pet.id());
+        message.string(2, pet.name());
+        writer.message(5, message);
+    }
+    for device in event.devices() {
+        let mut message = Writer::default();
+        message.int64(1, device.id());
+        message.string(2, device.name());
+        writer.message(6, message);
+    }
+    for movement in event.movements() {
+        let mut message = Writer::default();
+        message.optional_int64(1, movement.device_id());
+        message.optional_int64(2, movement.tag_id());
+        message.int64(3, movement.direction().code());
+        writer.message(7, message);
+    }
+    for weight in event.weights() {
+        let mut message = Writer::default();
+        message.optional_int64(1, weight.device_id());
+        message.optional_int64(2, weight.tag_id());
+        message.int64(3, weight.duration());
+        message.int64(4, weight.context());
+        for frame in weight.frames() {
+            let mut inner = Writer::default();
+            inner.int64(1, i64::from(frame.index()));
+            inner.double(2, frame.current_weight());
+            inner.double(3, frame.change());
+            message.message(5, inner);
+        }
+        writer.message(8, message);
+    }
+    writer.bytes
+}
+
+/// Writes proto3 fields, leaving out scalars at their default value.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    fn int64<V: Into<i64>>(&mut self, field: u32, value: V) {
+        let value = value.into();
+        if value != 0 {
+            self.optional_int64(field, Some(value));
+        }
+    }
+
+    fn optional_int64<V: Into<i64>>(&mut self, field: u32, value: Option<V>) {
+        if let Some(value) = value {
+            self.key(field, 0);
+            self.varint(value.into() as u64);
+        }
+    }
+
+    fn double(&mut self, field: u32, value: f64) {
+        if value != 0.0 {
+            self.key(field, 1);
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn string(&mut self, field: u32, value: &str) {
+        if !value.is_empty() {
+            self.key(field, 2);
+            self.varint(value.len() as u64);
+            self.bytes.extend_from_slice(value.as_bytes());
+        }
+    }
+
+    fn message(&mut self, field: u32, message: Writer) {
+        self.key(field, 2);
+        self.varint(message.bytes.len() as u64);
+        self.bytes.extend(message.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_encode_pets() {
+        let pet: Pet = serde_json::from_str(
+            r#"{"id":2,"name":"Milo","household_id":1,
+                "position":{"where":2,"since":"1970-01-01T00:05:00+00:00"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                0x08, 0x02, 0x12, 0x04, b'M', b'i', b'l', b'o', 0x18, 0x01, 0x2a, 0x05, 0x10, 0x02,
+                0x18, 0xac, 0x02,
+            ],
+            encode_pet(&pet)
+        );
+    }
+
+    #[test]
+    fn should_encode_events() {
+        let event: Event = serde_json::from_str(
+            r#"{"id":300,"type":99,"created_at":"1970-01-01T00:00:00+00:00",
+                "weights":[{"duration":0,"frames":[{"current_weight":0,"change":-1.5}]}]}"#,
+        )
+        .unwrap();
+        let mut expected = vec![0x08, 0xac, 0x02, 0x10, 0x63, 0x42, 0x0b, 0x2a, 0x09, 0x19];
+        expected.extend_from_slice(&(-1.5f64).to_le_bytes());
+        assert_eq!(expected, encode_event(&event));
+    }
+}
@@ -0,0 +1,215 @@
+//! Builder and struct for registering a device for push notifications.
+use crate::secret::Secret;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A struct that represents a push token registration request. Its `Debug`
+/// output redacts the token.
+#[derive(Clone, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Request<'a> {
+    /// The token issued by the push service (FCM or APNs).
+    token: Cow<'a, str>,
+
+    /// The platform the token belongs to, `android` or `ios`.
+    platform: Cow<'a, str>,
+
+    /// The same device id that was used to log in.
+    device_id: Cow<'a, str>,
+}
+
+impl fmt::Debug for Request<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("token", &Secret(&self.token))
+            .field("platform", &self.platform)
+            .field("device_id", &self.device_id)
+            .finish()
+    }
+}
+
+/// A builder to help with the creation of a push token registration Request.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::push;
+/// let item = push::RequestBuilder::new()
+///     .with_token("fcm-token")
+///     .with_platform("android")
+///     .with_device_id("xxx-xxx-xxx-xxx")
+///     .build();
+/// let serialized = serde_json::to_string(&item).unwrap();
+/// assert_eq!(
+///     "{\"token\":\"fcm-token\",\"platform\":\"android\",\"device_id\":\"xxx-xxx-xxx-xxx\"}".to_string(),
+///     serialized
+/// );
+/// ```
+///
+/// Like the login builder, [`required`](#method.required) only offers
+/// `build` once the token, platform and device id have been set in turn:
+///
+/// ```
+/// use sure_petcare::push;
+/// let item = push::RequestBuilder::required()
+///     .token("fcm-token")
+///     .platform("android")
+///     .device_id("xxx-xxx-xxx-xxx")
+///     .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct RequestBuilder<'a, S = Unchecked> {
+    token: Cow<'a, str>,
+    platform: Cow<'a, str>,
+    device_id: Cow<'a, str>,
+    state: PhantomData<S>,
+}
+
+impl<S> fmt::Debug for RequestBuilder<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBuilder")
+            .field("token", &Secret(&self.token))
+            .field("platform", &self.platform)
+            .field("device_id", &self.device_id)
+            .finish()
+    }
+}
+
+/// The state of a builder whose fields may be set in any order, or not at
+/// all.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unchecked;
+
+/// The state of a required builder that needs a push token next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsToken;
+
+/// The state of a required builder that needs a platform next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsPlatform;
+
+/// The state of a required builder that needs a device id next.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NeedsDeviceId;
+
+/// The state of a required builder with every field set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ready;
+
+impl<'a> RequestBuilder<'a, NeedsToken> {
+    /// Create a new builder that takes the push token, platform and device
+    /// id in that order before it can build.
+    pub fn required() -> Self {
+        RequestBuilder {
+            token: Cow::Borrowed(""),
+            platform: Cow::Borrowed(""),
+            device_id: Cow::Borrowed(""),
+            state: PhantomData,
+        }
+    }
+
+    /// Sets the push token.
+    pub fn token<T: Into<Cow<'a, str>>>(self, token: T) -> RequestBuilder<'a, NeedsPlatform> {
+        RequestBuilder {
+            token: token.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, NeedsPlatform> {
+    /// Sets the platform.
+    pub fn platform<T: Into<Cow<'a, str>>>(self, platform: T) -> RequestBuilder<'a, NeedsDeviceId> {
+        RequestBuilder {
+            platform: platform.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, NeedsDeviceId> {
+    /// Sets the device id.
+    pub fn device_id<T: Into<Cow<'a, str>>>(self, device_id: T) -> RequestBuilder<'a, Ready> {
+        RequestBuilder {
+            device_id: device_id.into(),
+            ..self.into_state()
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a, Ready> {
+    /// Builds the request.
+    pub fn build(self) -> Request<'a> {
+        Request {
+            token: self.token,
+            platform: self.platform,
+            device_id: self.device_id,
+        }
+    }
+}
+
+impl<'a, S> RequestBuilder<'a, S> {
+    fn into_state<N>(self) -> RequestBuilder<'a, N> {
+        RequestBuilder {
+            token: self.token,
+            platform: self.platform,
+            device_id: self.device_id,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Create a new empty builder.
+    pub fn new() -> Self {
+        RequestBuilder::default()
+    }
+
+    /// Sets the push token.
+    pub fn with_token<T: Into<Cow<'a, str>>>(&mut self, token: T) -> &mut Self {
+        self.token = token.into();
+        self
+    }
+
+    /// Sets the platform.
+    pub fn with_platform<T: Into<Cow<'a, str>>>(&mut self, platform: T) -> &mut Self {
+        self.platform = platform.into();
+        self
+    }
+
+    /// Sets the device id.
+    pub fn with_device_id<T: Into<Cow<'a, str>>>(&mut self, device_id: T) -> &mut Self {
+        self.device_id = device_id.into();
+        self
+    }
+
+    /// Builds the request.
+    pub fn build(&self) -> Request<'a> {
+        Request {
+            token: self.token.clone(),
+            platform: self.platform.clone(),
+            device_id: self.device_id.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_build_parts() {
+        let mut builder = RequestBuilder::new();
+        builder.with_token("apns-token");
+        builder.with_platform("ios");
+        builder.with_device_id("xxx-xxx-xxx-xxx");
+        let item = builder.build();
+        let serialized = serde_json::to_string(&item).unwrap();
+        assert_eq!(
+            "{\"token\":\"apns-token\",\"platform\":\"ios\",\"device_id\":\"xxx-xxx-xxx-xxx\"}"
+                .to_string(),
+            serialized
+        );
+    }
+}
@@ -0,0 +1,543 @@
+//! Aggregating timeline events into tables.
+//!
+//! Most questions asked of a pet's history are the same shape: take the
+//! events of one pet or device, of one kind, between two times, group them
+//! by day, hour or pet, and add something up. A [`Query`](struct.Query.html)
+//! says which events and how to group them, one call at a time; ending it
+//! with [`count`](struct.Query.html#method.count),
+//! [`sum`](struct.Query.html#method.sum) or another aggregate gives an
+//! [`Aggregation`](struct.Aggregation.html) that runs over fetched or
+//! archived events and returns a [`Table`](struct.Table.html) with a row
+//! per group.
+use crate::id::{DeviceId, PetId};
+use crate::time::{Date, Timestamp, Weekday};
+use crate::timeline::{Event, EventKind, PET_VISIT};
+use crate::zone::Zone;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// What to group events by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Group {
+    /// The local date of the event.
+    Day,
+    /// The local hour of the day, `0` to `23`.
+    Hour,
+    /// The local day of the week.
+    Weekday,
+    /// Each pet the event names, so an event naming two counts for both.
+    Pet,
+    /// Each device the event names.
+    Device,
+    /// The kind of event.
+    Kind,
+}
+
+impl Group {
+    fn name(self) -> &'static str {
+        match self {
+            Group::Day => "day",
+            Group::Hour => "hour",
+            Group::Weekday => "weekday",
+            Group::Pet => "pet",
+            Group::Device => "device",
+            Group::Kind => "kind",
+        }
+    }
+}
+
+/// What to add up for each event.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Measure {
+    /// One for every event.
+    Events,
+    /// The grams of food, or millilitres of water, taken during pet visits.
+    ConsumedGrams,
+    /// The seconds pets spent at the bowl.
+    VisitSeconds,
+}
+
+impl Measure {
+    fn name(self) -> &'static str {
+        match self {
+            Measure::Events => "events",
+            Measure::ConsumedGrams => "consumed_grams",
+            Measure::VisitSeconds => "visit_seconds",
+        }
+    }
+
+    /// The measure for an event, `None` when the event has no pet visit to
+    /// measure.
+    fn of(self, event: &Event<'_>) -> Option<f64> {
+        let mut visits = event
+            .weights()
+            .iter()
+            .filter(|entry| entry.context() == PET_VISIT)
+            .peekable();
+        match self {
+            Measure::Events => Some(1.0),
+            _ if visits.peek().is_none() => None,
+            Measure::ConsumedGrams => Some(
+                visits
+                    .flat_map(|entry| entry.frames())
+                    .filter(|frame| frame.change() < 0.0)
+                    .fold(0.0, |total, frame| total - frame.change()),
+            ),
+            Measure::VisitSeconds => Some(visits.map(|entry| entry.duration() as f64).sum()),
+        }
+    }
+}
+
+/// How the values of a group are combined.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Aggregate {
+    /// Their total.
+    Sum,
+    /// Their mean.
+    Mean,
+    /// The smallest.
+    Min,
+    /// The largest.
+    Max,
+}
+
+impl Aggregate {
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Mean => "mean",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+}
+
+/// The value of a group for one row.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Key {
+    /// A local date.
+    Day(Date),
+    /// A local hour of the day.
+    Hour(u8),
+    /// A day of the week.
+    Weekday(Weekday),
+    /// A pet.
+    Pet(PetId),
+    /// A device.
+    Device(DeviceId),
+    /// A kind of event.
+    Kind(EventKind),
+}
+
+impl Key {
+    fn rank(&self) -> (u8, i64) {
+        match self {
+            Key::Day(date) => (0, date.midnight().unix()),
+            Key::Hour(hour) => (1, i64::from(*hour)),
+            Key::Weekday(day) => (2, *day as i64),
+            Key::Pet(pet_id) => (3, pet_id.get()),
+            Key::Device(device_id) => (4, device_id.get()),
+            Key::Kind(kind) => (5, kind.code()),
+        }
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Day(date) => date.fmt(f),
+            Key::Hour(hour) => write!(f, "{:02}", hour),
+            Key::Weekday(day) => day.fmt(f),
+            Key::Pet(pet_id) => pet_id.fmt(f),
+            Key::Device(device_id) => device_id.fmt(f),
+            Key::Kind(kind) => kind.fmt(f),
+        }
+    }
+}
+
+/// Which events to take and how to group them, see the module
+/// documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::id::PetId;
+/// use sure_petcare::query::{Group, Measure, Query};
+/// use sure_petcare::timeline::{Event, EventKind};
+/// let events: Vec<Event> = serde_json::from_str(
+///     r#"[{"id":1,"type":22,"created_at":"2019-06-01T07:00:00Z","pets":[{"id":2,"name":"Milo"}],
+///          "weights":[{"duration":60,"context":1,"frames":[{"current_weight":30,"change":-10}]}]},
+///         {"id":2,"type":22,"created_at":"2019-06-01T19:00:00Z","pets":[{"id":2,"name":"Milo"}],
+///          "weights":[{"duration":90,"context":1,"frames":[{"current_weight":15,"change":-15}]}]}]"#,
+/// )?;
+/// let table = Query::new()
+///     .pet(PetId::new(2))
+///     .kind(EventKind::Feeding)
+///     .group_by(Group::Day)
+///     .sum(Measure::ConsumedGrams)
+///     .run(&events);
+/// assert_eq!("day\tconsumed_grams_sum\n2019-06-01\t25\n", table.to_string());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pets: Vec<PetId>,
+    devices: Vec<DeviceId>,
+    kinds: Vec<EventKind>,
+    from: Option<Timestamp>,
+    to: Option<Timestamp>,
+    groups: Vec<Group>,
+    zone: Option<Zone>,
+}
+
+impl Query {
+    /// A query taking every event, in one group.
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Takes only events naming the pet; called again, events naming
+    /// either pet.
+    pub fn pet(mut self, pet_id: PetId) -> Self {
+        self.pets.push(pet_id);
+        self
+    }
+
+    /// Takes only events naming the device; called again, either device.
+    pub fn device(mut self, device_id: DeviceId) -> Self {
+        self.devices.push(device_id);
+        self
+    }
+
+    /// Takes only events of the kind; called again, either kind.
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Takes only events from `from` up to but not including `to`.
+    pub fn between(mut self, from: Timestamp, to: Timestamp) -> Self {
+        self.from = Some(from);
+        self.to = Some(to);
+        self
+    }
+
+    /// Groups the events; called again, groups within each group, so
+    /// grouping by pet then day gives a row per pet per day.
+    pub fn group_by(mut self, group: Group) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    /// Works out days, hours and weekdays in a time zone rather than UTC.
+    pub fn in_zone(mut self, zone: Zone) -> Self {
+        self.zone = Some(zone);
+        self
+    }
+
+    /// Counts the events in each group.
+    pub fn count(self) -> Aggregation {
+        self.sum(Measure::Events)
+    }
+
+    /// Totals a measure in each group.
+    pub fn sum(self, measure: Measure) -> Aggregation {
+        self.aggregate(Aggregate::Sum, measure)
+    }
+
+    /// Averages a measure over the events in each group.
+    pub fn mean(self, measure: Measure) -> Aggregation {
+        self.aggregate(Aggregate::Mean, measure)
+    }
+
+    /// The smallest value of a measure in each group.
+    pub fn min(self, measure: Measure) -> Aggregation {
+        self.aggregate(Aggregate::Min, measure)
+    }
+
+    /// The largest value of a measure in each group.
+    pub fn max(self, measure: Measure) -> Aggregation {
+        self.aggregate(Aggregate::Max, measure)
+    }
+
+    /// Combines a measure in each group.
+    pub fn aggregate(self, aggregate: Aggregate, measure: Measure) -> Aggregation {
+        Aggregation {
+            query: self,
+            aggregate,
+            measure,
+        }
+    }
+
+    /// Whether the query takes an event.
+    pub fn matches(&self, event: &Event<'_>) -> bool {
+        let at = event.created_at();
+        (self.pets.is_empty() || self.pets.iter().any(|&pet_id| event.involves_pet(pet_id)))
+            && (self.devices.is_empty()
+                || event
+                    .devices()
+                    .iter()
+                    .any(|device| self.devices.contains(&device.id())))
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+            && self.from.is_none_or(|from| at >= from)
+            && self.to.is_none_or(|to| at < to)
+    }
+
+    /// The rows an event falls in: one for each combination of the pets
+    /// and devices it names, none when it names none a grouping needs.
+    fn keys(&self, event: &Event<'_>) -> Vec<Vec<Key>> {
+        let (date, time) = match &self.zone {
+            Some(zone) => zone.local(event.created_at()),
+            None => Zone::utc().local(event.created_at()),
+        };
+        let mut rows: Vec<Vec<Key>> = vec![Vec::new()];
+        for group in &self.groups {
+            let values: Vec<Key> = match group {
+                Group::Day => vec![Key::Day(date)],
+                Group::Hour => vec![Key::Hour(time.hour())],
+                Group::Weekday => vec![Key::Weekday(date.weekday())],
+                Group::Pet => event
+                    .pets()
+                    .iter()
+                    .map(|pet| pet.id())
+                    .filter(|pet_id| self.pets.is_empty() || self.pets.contains(pet_id))
+                    .map(Key::Pet)
+                    .collect(),
+                Group::Device => event
+                    .devices()
+                    .iter()
+                    .map(|device| device.id())
+                    .filter(|id| self.devices.is_empty() || self.devices.contains(id))
+                    .map(Key::Device)
+                    .collect(),
+                Group::Kind => vec![Key::Kind(event.kind())],
+            };
+            rows = rows
+                .into_iter()
+                .flat_map(|row| {
+                    values.iter().map(move |value| {
+                        let mut row = row.clone();
+                        row.push(*value);
+                        row
+                    })
+                })
+                .collect();
+        }
+        rows
+    }
+}
+
+/// A query with its aggregate, ready to run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregation {
+    query: Query,
+    aggregate: Aggregate,
+    measure: Measure,
+}
+
+impl Aggregation {
+    /// Runs over events, in any order. Events without the measure, such as
+    /// a movement when summing grams, are left out of every group.
+    pub fn run<'e, 'a: 'e, I>(&self, events: I) -> Table
+    where
+        I: IntoIterator<Item = &'e Event<'a>>,
+    {
+        let mut groups: HashMap<Vec<Key>, Vec<f64>> = HashMap::new();
+        for event in events {
+            if !self.query.matches(event) {
+                continue;
+            }
+            let value = match self.measure.of(event) {
+                Some(value) => value,
+                None => continue,
+            };
+            for keys in self.query.keys(event) {
+                groups.entry(keys).or_default().push(value);
+            }
+        }
+        let mut rows: Vec<Row> = groups
+            .into_iter()
+            .map(|(keys, values)| Row {
+                value: self.combine(&values),
+                count: values.len(),
+                keys,
+            })
+            .collect();
+        rows.sort_by(|a, b| a.keys.cmp(&b.keys));
+        let mut columns: Vec<String> = self
+            .query
+            .groups
+            .iter()
+            .map(|group| group.name().to_string())
+            .collect();
+        columns.push(format!("{}_{}", self.measure.name(), self.aggregate.name()));
+        Table { columns, rows }
+    }
+
+    fn combine(&self, values: &[f64]) -> f64 {
+        match self.aggregate {
+            Aggregate::Sum => values.iter().sum(),
+            Aggregate::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// One group of a [`Table`](struct.Table.html).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    keys: Vec<Key>,
+    value: f64,
+    count: usize,
+}
+
+impl Row {
+    /// The group's value for each grouping, in the order they were given.
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// The aggregated value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// How many events went into it.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// The result of an [`Aggregation`](struct.Aggregation.html): a row per
+/// group, ordered by group. It displays as tab separated values with a
+/// header line, ready to paste into a spreadsheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    columns: Vec<String>,
+    rows: Vec<Row>,
+}
+
+impl Table {
+    /// The column names: one per grouping, then the value's, such as
+    /// `consumed_grams_sum`.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// The rows, ordered by group.
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// The value of a group.
+    pub fn get(&self, keys: &[Key]) -> Option<f64> {
+        self.rows
+            .iter()
+            .find(|row| row.keys == keys)
+            .map(|row| row.value)
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.columns.join("\t"))?;
+        for row in &self.rows {
+            for key in &row.keys {
+                write!(f, "{}\t", key)?;
+            }
+            writeln!(f, "{}", row.value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_group_and_aggregate_events() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":22,"created_at":"2019-06-01T07:00:00Z",
+             "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":10,"name":"Feeder"}],
+             "weights":[{"duration":60,"context":1,"frames":[{"current_weight":30,"change":-10},{"index":1,"current_weight":5,"change":-4}]}]},
+            {"id":2,"type":22,"created_at":"2019-06-01T23:30:00Z",
+             "pets":[{"id":3,"name":"Luna"}],"devices":[{"id":10,"name":"Feeder"}],
+             "weights":[{"duration":30,"context":1,"frames":[{"current_weight":20,"change":-6}]}]},
+            {"id":3,"type":22,"created_at":"2019-06-02T08:00:00Z",
+             "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":10,"name":"Feeder"}],
+             "weights":[{"duration":90,"context":1,"frames":[{"current_weight":10,"change":-20}]}]},
+            {"id":4,"type":22,"created_at":"2019-06-02T09:00:00Z",
+             "devices":[{"id":10,"name":"Feeder"}],
+             "weights":[{"duration":0,"context":2,"frames":[{"current_weight":80,"change":70}]}]},
+            {"id":5,"type":0,"created_at":"2019-06-02T10:00:00Z",
+             "pets":[{"id":2,"name":"Milo"}],"movements":[{"device_id":11,"direction":1}]}
+        ]"#,
+        )
+        .unwrap();
+        let day = |d| Key::Day(Date::new(2019, 6, d).unwrap());
+
+        let eaten = Query::new()
+            .kind(EventKind::Feeding)
+            .group_by(Group::Pet)
+            .group_by(Group::Day)
+            .sum(Measure::ConsumedGrams)
+            .run(&events);
+        assert_eq!(
+            "pet\tday\tconsumed_grams_sum\n2\t2019-06-01\t14\n2\t2019-06-02\t20\n3\t2019-06-01\t6\n",
+            eaten.to_string()
+        );
+        assert_eq!(Some(20.0), eaten.get(&[Key::Pet(PetId::new(2)), day(2)]));
+
+        let in_sydney = Query::new()
+            .pet(PetId::new(3))
+            .group_by(Group::Day)
+            .in_zone(Zone::fixed(10 * 3600))
+            .count()
+            .run(&events);
+        assert_eq!(vec![day(2)], in_sydney.rows()[0].keys().to_vec());
+
+        let by_kind = Query::new()
+            .between(
+                "2019-06-02T00:00:00Z".parse().unwrap(),
+                "2019-06-03T00:00:00Z".parse().unwrap(),
+            )
+            .group_by(Group::Kind)
+            .count()
+            .run(&events);
+        assert_eq!(
+            vec![(0, 1), (22, 2)],
+            by_kind
+                .rows()
+                .iter()
+                .map(|row| match row.keys()[0] {
+                    Key::Kind(kind) => (kind.code(), row.count()),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        );
+
+        let visits = Query::new()
+            .device(DeviceId::new(10))
+            .mean(Measure::VisitSeconds)
+            .run(&events);
+        assert_eq!(vec!["visit_seconds_mean".to_string()], visits.columns());
+        assert_eq!(Some(60.0), visits.get(&[]));
+        assert_eq!(3, visits.rows()[0].count());
+    }
+}
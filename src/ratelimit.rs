@@ -0,0 +1,246 @@
+//! What the API says about how fast it may be called.
+//!
+//! Responses can carry the conventional `X-RateLimit-Limit`,
+//! `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers, or their
+//! unprefixed `RateLimit-*` forms, and a `Retry-After` when a request was
+//! refused. [`RateLimit`](struct.RateLimit.html) is what they said;
+//! the client keeps the last one in
+//! [`SurePet::rate_limit`](../client/struct.SurePet.html#method.rate_limit),
+//! and a `429 Too Many Requests` fails with
+//! [`Error::RateLimited`](../error/enum.Error.html#variant.RateLimited)
+//! carrying it.
+//!
+//! A [`Pacer`](struct.Pacer.html), attached with
+//! [`SurePet::with_pacing`](../client/struct.SurePet.html#method.with_pacing),
+//! spaces requests at least its minimum interval apart, and once the API
+//! reports its limits, spreads the requests remaining evenly over the time
+//! until they reset, and holds every request back for as long as a
+//! `Retry-After` asks.
+use crate::time::Timestamp;
+use crate::transport::Response;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// A reset larger than this is a Unix time rather than a number of seconds.
+const RESET_EPOCH: u64 = 1_000_000_000;
+
+/// The rate limit headers of a response.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize)]
+pub struct RateLimit {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<Duration>,
+    retry_after: Option<Duration>,
+}
+
+impl RateLimit {
+    /// Reads the headers of a response, `None` when it has none of them.
+    /// `now` is the time a `Retry-After` or reset given as a time is
+    /// measured from when the response has no `Date` of its own.
+    pub fn from_response(response: &Response, now: Timestamp) -> Option<RateLimit> {
+        let number = |name: &str| {
+            response
+                .header(&format!("X-RateLimit-{}", name))
+                .or_else(|| response.header(&format!("RateLimit-{}", name)))
+                .and_then(|value| value.trim().parse::<u64>().ok())
+        };
+        let now = response
+            .header("Date")
+            .and_then(Timestamp::from_http_date)
+            .unwrap_or(now);
+        let reset = number("Reset").map(|reset| {
+            if reset > RESET_EPOCH {
+                Timestamp::from_unix(reset as i64).elapsed_since(now)
+            } else {
+                Duration::from_secs(reset)
+            }
+        });
+        let rate_limit = RateLimit {
+            limit: number("Limit"),
+            remaining: number("Remaining"),
+            reset,
+            retry_after: retry_after(response, now),
+        };
+        if rate_limit == RateLimit::default() {
+            None
+        } else {
+            Some(rate_limit)
+        }
+    }
+
+    /// How many requests the window allows.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// How many requests are left in the window.
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    /// How long until the window resets, from when the response was sent.
+    pub fn reset(&self) -> Option<Duration> {
+        self.reset
+    }
+
+    /// How long the API asked callers to wait before trying again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Whether no requests are left in the window.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
+
+/// The wait a `Retry-After` header asks for, given as seconds or as a date
+/// measured against the response's `Date`, or `now` without one.
+pub(crate) fn retry_after(response: &Response, now: Timestamp) -> Option<Duration> {
+    let value = response.header("Retry-After")?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let until = Timestamp::from_http_date(value)?;
+    let now = response
+        .header("Date")
+        .and_then(Timestamp::from_http_date)
+        .unwrap_or(now);
+    Some(until.elapsed_since(now))
+}
+
+/// Spaces out requests, adapting to the limits the API reports, see the
+/// module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::ratelimit::Pacer;
+/// use std::time::{Duration, Instant};
+/// let mut pacer = Pacer::new(Duration::from_millis(200));
+/// let now = Instant::now();
+/// assert_eq!(Duration::ZERO, pacer.delay(now));
+/// pacer.sent(now);
+/// assert_eq!(Duration::from_millis(200), pacer.delay(now));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Pacer {
+    min_interval: Duration,
+    spacing: Duration,
+    next: Option<Instant>,
+}
+
+impl Pacer {
+    /// A pacer sending requests at least `min_interval` apart, and further
+    /// apart when the API asks.
+    pub fn new(min_interval: Duration) -> Self {
+        Pacer {
+            min_interval,
+            spacing: min_interval,
+            next: None,
+        }
+    }
+
+    /// The configured minimum interval between requests.
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// The interval requests are currently spaced by: the minimum, or
+    /// longer to make the remaining requests last until the limits reset.
+    pub fn spacing(&self) -> Duration {
+        self.spacing
+    }
+
+    /// How long to wait at `now` before sending the next request.
+    pub fn delay(&self, now: Instant) -> Duration {
+        self.next
+            .map_or(Duration::ZERO, |next| next.saturating_duration_since(now))
+    }
+
+    /// Records that a request was sent at `now`.
+    pub fn sent(&mut self, now: Instant) {
+        self.hold(now + self.spacing);
+    }
+
+    /// Adapts to the limits a response reported at `now`: a `Retry-After`
+    /// holds every request back that long, an exhausted window until it
+    /// resets, and otherwise requests are spread over what is left of it.
+    pub fn observe(&mut self, rate_limit: &RateLimit, now: Instant) {
+        if let Some(retry_after) = rate_limit.retry_after {
+            self.hold(now + retry_after);
+        }
+        match (rate_limit.remaining, rate_limit.reset) {
+            (Some(0), Some(reset)) => {
+                self.spacing = self.min_interval;
+                self.hold(now + reset);
+            }
+            (Some(remaining), Some(reset)) => {
+                self.spacing =
+                    (reset / remaining.min(u64::from(u32::MAX)) as u32).max(self.min_interval);
+                self.hold(now + self.spacing);
+            }
+            _ => self.spacing = self.min_interval,
+        }
+    }
+
+    fn hold(&mut self, until: Instant) {
+        self.next = Some(self.next.map_or(until, |next| next.max(until)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_read_headers_and_adapt_the_pacing() {
+        let response = |headers: &[(&str, &str)]| Response {
+            status: 200,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: Vec::new(),
+        };
+        let now: Timestamp = "2019-06-01T12:00:00Z".parse().unwrap();
+
+        assert_eq!(None, RateLimit::from_response(&response(&[]), now));
+        let spread = RateLimit::from_response(
+            &response(&[
+                ("X-RateLimit-Limit", "60"),
+                ("X-RateLimit-Remaining", "10"),
+                ("X-RateLimit-Reset", "30"),
+            ]),
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            (Some(60), Some(10), Some(Duration::from_secs(30))),
+            (spread.limit(), spread.remaining(), spread.reset())
+        );
+        let exhausted = RateLimit::from_response(
+            &response(&[
+                ("RateLimit-Remaining", "0"),
+                ("RateLimit-Reset", "1559390460"),
+                ("Retry-After", "Sat, 01 Jun 2019 12:00:20 GMT"),
+            ]),
+            now,
+        )
+        .unwrap();
+        assert!(exhausted.is_exhausted());
+        assert_eq!(Some(Duration::from_secs(60)), exhausted.reset());
+        assert_eq!(Some(Duration::from_secs(20)), exhausted.retry_after());
+
+        let start = Instant::now();
+        let mut pacer = Pacer::new(Duration::from_secs(1));
+        pacer.observe(&spread, start);
+        assert_eq!(Duration::from_secs(3), pacer.spacing());
+        assert_eq!(Duration::from_secs(3), pacer.delay(start));
+        pacer.observe(&exhausted, start);
+        assert_eq!(Duration::from_secs(60), pacer.delay(start));
+        assert_eq!(Duration::from_secs(1), pacer.spacing());
+        pacer.observe(&RateLimit::default(), start + Duration::from_secs(61));
+        assert_eq!(Duration::ZERO, pacer.delay(start + Duration::from_secs(61)));
+    }
+}
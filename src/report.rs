@@ -0,0 +1,1030 @@
+//! Aggregated per pet reports of movement, feeding and drinking.
+//!
+//! The report endpoint returns pre-aggregated datapoints, which is far cheaper
+//! than paging through the household timeline to work the same numbers out.
+use crate::cache::ReportCache;
+use crate::client::SurePet;
+use crate::error::{Error, Result};
+use crate::float::HashFloat;
+use crate::id::{DeviceId, HouseholdId, PetId};
+use crate::time::{self, Date, DateRange, RangeError, Timestamp};
+use crate::timeline::{Direction, Event, EventKind, PET_VISIT};
+use crate::transport::Transport;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// The aggregated report for a single pet.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Report {
+    #[serde(default)]
+    pub(crate) movement: Section<Movement>,
+    #[serde(default)]
+    pub(crate) feeding: Section<Feeding>,
+    #[serde(default)]
+    pub(crate) drinking: Section<Drinking>,
+}
+
+impl Report {
+    /// The datapoints that started on `date`.
+    pub(crate) fn for_date(&self, date: Date) -> Report {
+        Report {
+            movement: Section {
+                datapoints: self
+                    .movement
+                    .datapoints
+                    .iter()
+                    .filter(|d| d.from.date() == date)
+                    .cloned()
+                    .collect(),
+            },
+            feeding: Section {
+                datapoints: self
+                    .feeding
+                    .datapoints
+                    .iter()
+                    .filter(|d| d.from.date() == date)
+                    .cloned()
+                    .collect(),
+            },
+            drinking: Section {
+                datapoints: self
+                    .drinking
+                    .datapoints
+                    .iter()
+                    .filter(|d| d.from.date() == date)
+                    .cloned()
+                    .collect(),
+            },
+        }
+    }
+
+    /// Adds the datapoints of a later report to this one.
+    pub(crate) fn append(&mut self, other: Report) {
+        self.movement.datapoints.extend(other.movement.datapoints);
+        self.feeding.datapoints.extend(other.feeding.datapoints);
+        self.drinking.datapoints.extend(other.drinking.datapoints);
+    }
+
+    /// Rebuilds the feeding and drinking parts of a report for a pet from
+    /// timeline events, oldest first.
+    pub fn from_events(events: &[Event<'_>], pet_id: PetId) -> Report {
+        let mut events: Vec<&Event<'_>> =
+            events.iter().filter(|e| e.involves_pet(pet_id)).collect();
+        events.sort_by_key(|event| event.created_at());
+
+        let mut report = Report::default();
+        for event in events {
+            match event.kind() {
+                EventKind::Feeding => report.feeding.datapoints.extend(visits_from_event(event)),
+                EventKind::Drinking => {
+                    report
+                        .drinking
+                        .datapoints
+                        .extend(visits_from_event(event).map(|visit| Drinking {
+                            from: visit.from,
+                            to: visit.to,
+                            duration: visit.duration,
+                            device_id: visit.device_id,
+                            context: visit.context,
+                            weights: visit.weights,
+                        }))
+                }
+                _ => {}
+            }
+        }
+        report
+    }
+
+    /// Trips outside, oldest first.
+    pub fn movement(&self) -> &[Movement] {
+        &self.movement.datapoints
+    }
+
+    /// Visits to a feeder, oldest first.
+    pub fn feeding(&self) -> &[Feeding] {
+        &self.feeding.datapoints
+    }
+
+    /// Visits to a water dispenser, oldest first.
+    pub fn drinking(&self) -> &[Drinking] {
+        &self.drinking.datapoints
+    }
+}
+
+/// Each part of the report is a list of datapoints.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Section<T> {
+    #[serde(default = "Vec::new")]
+    pub(crate) datapoints: Vec<T>,
+}
+
+impl<T> Default for Section<T> {
+    fn default() -> Self {
+        Section {
+            datapoints: Vec::new(),
+        }
+    }
+}
+
+/// A single trip outside.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Movement {
+    pub(crate) from: Timestamp,
+    #[serde(default)]
+    pub(crate) to: Option<Timestamp>,
+    #[serde(default)]
+    pub(crate) duration: i64,
+    #[serde(default)]
+    pub(crate) exit_device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) entry_device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) active: bool,
+}
+
+impl Movement {
+    /// When the pet went out.
+    pub fn from(&self) -> Timestamp {
+        self.from
+    }
+
+    /// When the pet came back in, `None` while it is still outside.
+    pub fn to(&self) -> Option<Timestamp> {
+        self.to
+    }
+
+    /// Seconds spent outside.
+    pub fn duration(&self) -> i64 {
+        self.duration
+    }
+
+    /// Time spent outside.
+    pub fn elapsed(&self) -> Duration {
+        time::seconds(self.duration)
+    }
+
+    /// The flap the pet left through.
+    pub fn exit_device_id(&self) -> Option<DeviceId> {
+        self.exit_device_id
+    }
+
+    /// The flap the pet came back in through.
+    pub fn entry_device_id(&self) -> Option<DeviceId> {
+        self.entry_device_id
+    }
+
+    /// Whether the trip is still in progress.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+}
+
+/// A single visit to a feeder.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Feeding {
+    pub(crate) from: Timestamp,
+    pub(crate) to: Timestamp,
+    #[serde(default)]
+    pub(crate) duration: i64,
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) context: i64,
+    #[serde(default)]
+    pub(crate) bowl_count: u8,
+    #[serde(default)]
+    pub(crate) weights: Vec<Weight>,
+}
+
+impl Feeding {
+    /// When the visit started.
+    pub fn from(&self) -> Timestamp {
+        self.from
+    }
+
+    /// When the visit ended.
+    pub fn to(&self) -> Timestamp {
+        self.to
+    }
+
+    /// Seconds spent at the bowl.
+    pub fn duration(&self) -> i64 {
+        self.duration
+    }
+
+    /// Time spent at the bowl.
+    pub fn elapsed(&self) -> Duration {
+        time::seconds(self.duration)
+    }
+
+    /// The feeder that was visited.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The raw context code; `1` is a pet eating, other values are refills
+    /// and manual resets.
+    pub fn context(&self) -> i64 {
+        self.context
+    }
+
+    /// The number of bowls on the feeder.
+    pub fn bowl_count(&self) -> u8 {
+        self.bowl_count
+    }
+
+    /// Per bowl weights at the end of the visit.
+    pub fn weights(&self) -> &[Weight] {
+        &self.weights
+    }
+
+    /// Grams eaten during the visit across all bowls.
+    pub fn consumed(&self) -> f64 {
+        self.weights
+            .iter()
+            .filter(|w| w.change < 0.0)
+            .fold(0.0, |total, w| total - w.change)
+    }
+}
+
+/// A single visit to a water dispenser.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Drinking {
+    pub(crate) from: Timestamp,
+    pub(crate) to: Timestamp,
+    #[serde(default)]
+    pub(crate) duration: i64,
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) context: i64,
+    #[serde(default)]
+    pub(crate) weights: Vec<Weight>,
+}
+
+impl Drinking {
+    /// When the visit started.
+    pub fn from(&self) -> Timestamp {
+        self.from
+    }
+
+    /// When the visit ended.
+    pub fn to(&self) -> Timestamp {
+        self.to
+    }
+
+    /// Seconds spent at the dispenser.
+    pub fn duration(&self) -> i64 {
+        self.duration
+    }
+
+    /// Time spent at the dispenser.
+    pub fn elapsed(&self) -> Duration {
+        time::seconds(self.duration)
+    }
+
+    /// The dispenser that was visited.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The raw context code, as for feeding.
+    pub fn context(&self) -> i64 {
+        self.context
+    }
+
+    /// The remaining water after the visit.
+    pub fn weights(&self) -> &[Weight] {
+        &self.weights
+    }
+
+    /// Millilitres drunk during the visit.
+    pub fn consumed(&self) -> f64 {
+        self.weights
+            .iter()
+            .filter(|w| w.change < 0.0)
+            .fold(0.0, |total, w| total - w.change)
+    }
+}
+
+/// The weight of one bowl, in grams (or millilitres of water).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Weight {
+    #[serde(default)]
+    pub(crate) index: u8,
+    pub(crate) weight: f64,
+    pub(crate) change: f64,
+}
+
+impl Eq for Weight {}
+
+impl Hash for Weight {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.weight.hash_float(state);
+        self.change.hash_float(state);
+    }
+}
+
+impl Weight {
+    /// The bowl index, `0` being the left hand bowl.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// The weight after the visit.
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// The change in weight during the visit, negative when food was eaten.
+    pub fn change(&self) -> f64 {
+        self.change
+    }
+}
+
+/// The longest range, in days, accepted by the report calls.
+pub const MAX_RANGE_DAYS: i64 = 366;
+
+fn check_range(range: DateRange) -> Result<()> {
+    if range.len() > MAX_RANGE_DAYS {
+        return Err(Error::Range(RangeError::TooLong {
+            days: range.len(),
+            max: MAX_RANGE_DAYS,
+        }));
+    }
+    Ok(())
+}
+
+/// Feeding totals for a single day.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DailyFeeding {
+    date: Date,
+    consumed: f64,
+    visits: u32,
+    average_duration: i64,
+}
+
+impl DailyFeeding {
+    /// Totals the pet visits in `datapoints` per day, with one entry for every
+    /// day in `range` even when nothing was eaten. Days are UTC days.
+    pub fn from_datapoints(datapoints: &[Feeding], range: DateRange) -> Vec<DailyFeeding> {
+        range
+            .days()
+            .map(|date| {
+                let visits: Vec<&Feeding> = datapoints
+                    .iter()
+                    .filter(|d| d.context == PET_VISIT && d.from.date() == date)
+                    .collect();
+                let total_duration: i64 = visits.iter().map(|d| d.duration).sum();
+                DailyFeeding {
+                    date,
+                    consumed: visits.iter().fold(0.0, |total, d| total + d.consumed()),
+                    visits: visits.len() as u32,
+                    average_duration: if visits.is_empty() {
+                        0
+                    } else {
+                        total_duration / visits.len() as i64
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// The day.
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Grams eaten.
+    pub fn consumed(&self) -> f64 {
+        self.consumed
+    }
+
+    /// The number of visits to a feeder.
+    pub fn visits(&self) -> u32 {
+        self.visits
+    }
+
+    /// The average seconds per visit, `0` on days without visits.
+    pub fn average_duration(&self) -> i64 {
+        self.average_duration
+    }
+
+    /// The average time per visit, zero on days without visits.
+    pub fn average_elapsed(&self) -> Duration {
+        time::seconds(self.average_duration)
+    }
+}
+
+/// Drinking totals for a single day.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DailyDrinking {
+    date: Date,
+    consumed: f64,
+    visits: u32,
+    rolling_average: f64,
+    trend: f64,
+}
+
+impl DailyDrinking {
+    /// Totals the pet visits in `datapoints` per day for every day in `range`.
+    ///
+    /// The rolling average covers the last `window` days up to and including
+    /// each day, limited to days inside the range; a window of `0` is treated
+    /// as `1`.
+    pub fn from_datapoints(
+        datapoints: &[Drinking],
+        range: DateRange,
+        window: usize,
+    ) -> Vec<DailyDrinking> {
+        let window = window.max(1);
+        let totals: Vec<(Date, f64, u32)> = range
+            .days()
+            .map(|date| {
+                let visits = datapoints
+                    .iter()
+                    .filter(|d| d.context == PET_VISIT && d.from.date() == date);
+                let (consumed, count) = visits.fold((0.0, 0), |(consumed, count), d| {
+                    (consumed + d.consumed(), count + 1)
+                });
+                (date, consumed, count)
+            })
+            .collect();
+
+        let mut previous = None;
+        totals
+            .iter()
+            .enumerate()
+            .map(|(i, (date, consumed, visits))| {
+                let days = &totals[(i + 1).saturating_sub(window)..=i];
+                let rolling_average =
+                    days.iter().map(|(_, consumed, _)| consumed).sum::<f64>() / days.len() as f64;
+                let trend = previous.map_or(0.0, |previous| rolling_average - previous);
+                previous = Some(rolling_average);
+                DailyDrinking {
+                    date: *date,
+                    consumed: *consumed,
+                    visits: *visits,
+                    rolling_average,
+                    trend,
+                }
+            })
+            .collect()
+    }
+
+    /// The day.
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Millilitres drunk.
+    pub fn consumed(&self) -> f64 {
+        self.consumed
+    }
+
+    /// The number of visits to a water dispenser.
+    pub fn visits(&self) -> u32 {
+        self.visits
+    }
+
+    /// The average millilitres per day over the rolling window.
+    pub fn rolling_average(&self) -> f64 {
+        self.rolling_average
+    }
+
+    /// The change in rolling average since the previous day, `0` on the
+    /// first day of the range.
+    pub fn trend(&self) -> f64 {
+        self.trend
+    }
+}
+
+/// Time spent outside on a single day.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DailyOutside {
+    date: Date,
+    total: i64,
+    longest: i64,
+    trips: u32,
+}
+
+impl DailyOutside {
+    /// Works out the time a pet spent outside on every day in `range` from
+    /// its movement events.
+    ///
+    /// Exits are paired with the next entry, and trips spanning midnight are
+    /// split between the days they cover. An entry without a preceding exit
+    /// is taken to end a trip that began at the start of the range if it is
+    /// the first movement seen, and is otherwise ignored; two exits in a row
+    /// keep only the later one. A trip still in progress runs until `until`.
+    pub fn from_events(
+        events: &[Event<'_>],
+        pet_id: PetId,
+        range: DateRange,
+        until: Timestamp,
+    ) -> Vec<DailyOutside> {
+        let mut movements: Vec<(Timestamp, Direction)> = events
+            .iter()
+            .filter(|e| e.kind() == EventKind::Movement && e.involves_pet(pet_id))
+            .flat_map(|e| {
+                e.movements()
+                    .iter()
+                    .map(move |m| (e.created_at(), m.direction()))
+            })
+            .filter(|(_, direction)| matches!(direction, Direction::In | Direction::Out))
+            .collect();
+        movements.sort_by_key(|(at, _)| *at);
+
+        let mut trips = Vec::new();
+        let mut out_since = None;
+        let mut seen_any = false;
+        for (at, direction) in movements {
+            match (direction, out_since) {
+                (Direction::Out, _) => out_since = Some(at),
+                (Direction::In, Some(since)) => {
+                    trips.push((since, at));
+                    out_since = None;
+                }
+                (Direction::In, None) if !seen_any => trips.push((range.start(), at)),
+                _ => {}
+            }
+            seen_any = true;
+        }
+        if let Some(since) = out_since {
+            trips.push((since, until));
+        }
+
+        range
+            .days()
+            .map(|date| {
+                let day_start = date.midnight();
+                let day_end = date.add_days(1).midnight();
+                let portions: Vec<i64> = trips
+                    .iter()
+                    .map(|(from, to)| to.min(&day_end).seconds_since(*from.max(&day_start)))
+                    .filter(|seconds| *seconds > 0)
+                    .collect();
+                DailyOutside {
+                    date,
+                    total: portions.iter().sum(),
+                    longest: portions.iter().copied().max().unwrap_or(0),
+                    trips: portions.len() as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// The day.
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Seconds spent outside.
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+
+    /// Seconds spent on the longest trip outside, counting only the part of
+    /// the trip that fell on this day.
+    pub fn longest(&self) -> i64 {
+        self.longest
+    }
+
+    /// Time spent outside, as a `Duration`.
+    pub fn total_elapsed(&self) -> Duration {
+        time::seconds(self.total)
+    }
+
+    /// Time spent on the longest trip outside, as a `Duration`.
+    pub fn longest_elapsed(&self) -> Duration {
+        time::seconds(self.longest)
+    }
+
+    /// The number of trips that were at least partly on this day.
+    pub fn trips(&self) -> u32 {
+        self.trips
+    }
+}
+
+/// Feeding and drinking share the same shape of datapoint.
+fn visits_from_event<'e>(event: &'e Event<'_>) -> impl Iterator<Item = Feeding> + 'e {
+    event.weights().iter().map(move |entry| Feeding {
+        from: Timestamp::from_unix(event.created_at().unix() - entry.duration()),
+        to: event.created_at(),
+        duration: entry.duration(),
+        device_id: entry.device_id(),
+        context: entry.context(),
+        bowl_count: entry.frames().len() as u8,
+        weights: entry
+            .frames()
+            .iter()
+            .map(|frame| Weight {
+                index: frame.index(),
+                weight: frame.current_weight(),
+                change: frame.change(),
+            })
+            .collect(),
+    })
+}
+
+/// The report endpoints, obtained from [`SurePet::reports`](../client/struct.SurePet.html#method.reports).
+pub struct Reports<'c, T> {
+    client: &'c SurePet<T>,
+    cache: Option<&'c dyn ReportCache>,
+}
+
+impl<'c, T> fmt::Debug for Reports<'c, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reports")
+            .field("cached", &self.cache.is_some())
+            .finish()
+    }
+}
+
+impl<'c, T: Transport> Reports<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Reports {
+            client,
+            cache: None,
+        }
+    }
+
+    /// Serves days that are over from `cache`, so only the days that are
+    /// missing or still in progress are fetched. Applies to every call that
+    /// takes a range and is answered by the report endpoint.
+    pub fn with_cache(mut self, cache: &'c dyn ReportCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Fetches the aggregated report for a pet in a household.
+    pub fn pet(&self, household_id: HouseholdId, pet_id: PetId) -> Result<Report> {
+        let path = format!(
+            "/api/report/household/{}/pet/{}/aggregate",
+            household_id, pet_id
+        );
+        self.client.get(&path, &[])
+    }
+
+    /// Per day feeding totals for a pet over `range`.
+    ///
+    /// Households without access to the report endpoint get a `404`; for those
+    /// the totals are worked out from the timeline instead.
+    pub fn feeding_daily(
+        &self,
+        household_id: HouseholdId,
+        pet_id: PetId,
+        range: DateRange,
+    ) -> Result<Vec<DailyFeeding>> {
+        let report = self.between_or_timeline(household_id, pet_id, range)?;
+        Ok(DailyFeeding::from_datapoints(report.feeding(), range))
+    }
+
+    /// Per day drinking totals for a pet over `range`, with rolling averages
+    /// over the last `window` days. Falls back to the timeline like
+    /// [`feeding_daily`](#method.feeding_daily).
+    pub fn drinking_daily(
+        &self,
+        household_id: HouseholdId,
+        pet_id: PetId,
+        range: DateRange,
+        window: usize,
+    ) -> Result<Vec<DailyDrinking>> {
+        let report = self.between_or_timeline(household_id, pet_id, range)?;
+        Ok(DailyDrinking::from_datapoints(
+            report.drinking(),
+            range,
+            window,
+        ))
+    }
+
+    /// Per day time outside for a pet over `range`, from the movement events
+    /// on the timeline. The day before the range is fetched as well, to catch
+    /// trips that started before it.
+    pub fn outside_time(
+        &self,
+        household_id: HouseholdId,
+        pet_id: PetId,
+        range: DateRange,
+    ) -> Result<Vec<DailyOutside>> {
+        check_range(range)?;
+        let since = range.from().add_days(-1).midnight();
+        let events = self.client.timeline().since(household_id, since)?;
+        let until = self.client.clock().now().min(range.end());
+        Ok(DailyOutside::from_events(&events, pet_id, range, until))
+    }
+
+    /// Fetches the report for `range`, rebuilding it from the timeline when
+    /// the household has no access to the report endpoint.
+    fn between_or_timeline(
+        &self,
+        household_id: HouseholdId,
+        pet_id: PetId,
+        range: DateRange,
+    ) -> Result<Report> {
+        match self.pet_between(household_id, pet_id, range) {
+            Err(Error::Status { status: 404, .. }) => {
+                let events = self.client.timeline().since(household_id, range.start())?;
+                Ok(Report::from_events(&events, pet_id))
+            }
+            result => result,
+        }
+    }
+
+    /// Fetches the aggregated report for a pet, limited to `range`.
+    ///
+    /// Ranges longer than [`MAX_RANGE_DAYS`](constant.MAX_RANGE_DAYS.html)
+    /// are rejected before anything is sent.
+    pub fn pet_between(
+        &self,
+        household_id: HouseholdId,
+        pet_id: PetId,
+        range: DateRange,
+    ) -> Result<Report> {
+        check_range(range)?;
+        let cache = match self.cache {
+            Some(cache) => cache,
+            None => return self.fetch(household_id, pet_id, range),
+        };
+
+        let today = self.client.clock().now().date();
+        let days: Vec<(Date, Option<Report>)> = range
+            .days()
+            .map(|date| {
+                (
+                    date,
+                    Some(date)
+                        .filter(|d| *d < today)
+                        .and_then(|d| cache.get(pet_id, d)),
+                )
+            })
+            .collect();
+        let missing: Vec<Date> = days
+            .iter()
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(date, _)| *date)
+            .collect();
+        let fetched = match (missing.first(), missing.last()) {
+            (Some(first), Some(last)) => {
+                let span = DateRange::new(*first, *last)?;
+                Some(self.fetch(household_id, pet_id, span)?)
+            }
+            _ => None,
+        };
+
+        let mut report = Report::default();
+        for (date, cached) in days {
+            let day = match (cached, &fetched) {
+                (Some(cached), _) => cached,
+                (None, Some(fetched)) => {
+                    let day = fetched.for_date(date);
+                    if date < today {
+                        cache.put(pet_id, date, day.clone());
+                    }
+                    day
+                }
+                (None, None) => continue,
+            };
+            report.append(day);
+        }
+        Ok(report)
+    }
+
+    fn fetch(&self, household_id: HouseholdId, pet_id: PetId, range: DateRange) -> Result<Report> {
+        let path = format!(
+            "/api/report/household/{}/pet/{}/aggregate",
+            household_id, pet_id
+        );
+        let query = [
+            ("from", range.start().to_string()),
+            ("to", range.end().to_string()),
+        ];
+        self.client.get(&path, &query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+    use std::time::Duration;
+
+    const REPORT: &str = r#"{"data":{
+        "movement":{"datapoints":[
+            {"from":"2019-06-01T07:00:00+00:00","to":"2019-06-01T09:30:00+00:00","duration":9000,"exit_device_id":10,"entry_device_id":10},
+            {"from":"2019-06-01T18:00:00+00:00","active":true,"exit_device_id":10}
+        ]},
+        "feeding":{"datapoints":[
+            {"from":"2019-06-01T06:00:00+00:00","to":"2019-06-01T06:02:00+00:00","duration":120,"device_id":11,"context":1,"bowl_count":2,
+             "weights":[{"index":0,"weight":20.5,"change":-4.5},{"index":1,"weight":40.0,"change":-1.5}]}
+        ]}
+    }}"#;
+
+    #[test]
+    fn should_fetch_pet_report() {
+        let transport = MockTransport::new().respond(200, REPORT);
+        let client = SurePet::new(&transport, "token");
+        let report = client
+            .reports()
+            .pet(HouseholdId::new(1), PetId::new(2))
+            .unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/report/household/1/pet/2/aggregate",
+            transport.requests()[0].url
+        );
+        assert_eq!(2, report.movement().len());
+        assert_eq!(
+            Some(DeviceId::new(10)),
+            report.movement()[0].entry_device_id()
+        );
+        assert!(report.movement()[1].active());
+        assert_eq!(None, report.movement()[1].to());
+        assert_eq!(6.0, report.feeding()[0].consumed());
+        assert!(report.drinking().is_empty());
+    }
+
+    #[test]
+    fn should_total_feeding_per_day() {
+        let transport = MockTransport::new().respond(200, REPORT);
+        let client = SurePet::new(&transport, "token");
+        let day = Date::new(2019, 6, 1).unwrap();
+        let range = DateRange::new(day, day.add_days(1)).unwrap();
+        let daily = client
+            .reports()
+            .feeding_daily(HouseholdId::new(1), PetId::new(2), range)
+            .unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/report/household/1/pet/2/aggregate\
+             ?from=2019-06-01T00%3A00%3A00%2B00%3A00&to=2019-06-02T23%3A59%3A59%2B00%3A00",
+            transport.requests()[0].url
+        );
+        assert_eq!(2, daily.len());
+        assert_eq!(6.0, daily[0].consumed());
+        assert_eq!(1, daily[0].visits());
+        assert_eq!(120, daily[0].average_duration());
+        assert_eq!(Duration::from_secs(120), daily[0].average_elapsed());
+        assert_eq!(0, daily[1].visits());
+    }
+
+    #[test]
+    fn should_fall_back_to_timeline() {
+        let transport = MockTransport::new()
+            .respond(404, r#"{"error":"not found"}"#)
+            .respond(
+                200,
+                r#"{"data":[
+                {"id":3,"type":22,"created_at":"2019-06-01T06:02:00+00:00","pets":[{"id":2}],
+                 "weights":[{"duration":60,"context":1,"frames":[{"current_weight":10,"change":-3}]}]},
+                {"id":2,"type":22,"created_at":"2019-06-01T05:00:00+00:00","pets":[{"id":9}],
+                 "weights":[{"duration":60,"context":1,"frames":[{"current_weight":10,"change":-8}]}]}
+            ]}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+        let day = Date::new(2019, 6, 1).unwrap();
+        let daily = client
+            .reports()
+            .feeding_daily(
+                HouseholdId::new(1),
+                PetId::new(2),
+                DateRange::new(day, day).unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(3.0, daily[0].consumed());
+        assert_eq!(1, daily[0].visits());
+        assert_eq!(60, daily[0].average_duration());
+    }
+
+    #[test]
+    fn should_only_fetch_uncached_days() {
+        let cache = MemoryCache::new(Duration::from_secs(3600));
+        let first = Date::new(2019, 5, 31).unwrap();
+        cache.put(PetId::new(2), first, Report::default());
+        let transport = MockTransport::new()
+            .respond(200, REPORT)
+            .respond(200, REPORT);
+        let client = SurePet::new(&transport, "token");
+        let range = DateRange::new(first, first.add_days(1)).unwrap();
+
+        let report = client
+            .reports()
+            .with_cache(&cache)
+            .pet_between(HouseholdId::new(1), PetId::new(2), range)
+            .unwrap();
+        assert_eq!(1, report.feeding().len());
+        assert!(transport.requests()[0].url.contains("from=2019-06-01T00"));
+        assert_eq!(2, cache.len());
+
+        let again = client
+            .reports()
+            .with_cache(&cache)
+            .pet_between(HouseholdId::new(1), PetId::new(2), range)
+            .unwrap();
+        assert_eq!(1, again.feeding().len());
+        assert_eq!(1, transport.requests().len());
+    }
+
+    #[test]
+    fn should_reject_long_ranges() {
+        let transport = MockTransport::new();
+        let client = SurePet::new(&transport, "token");
+        let range = DateRange::last_days(MAX_RANGE_DAYS + 1, client.clock().now());
+        match client
+            .reports()
+            .pet_between(HouseholdId::new(1), PetId::new(2), range)
+        {
+            Err(Error::Range(RangeError::TooLong { days, max })) => {
+                assert_eq!((MAX_RANGE_DAYS + 1, MAX_RANGE_DAYS), (days, max));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(transport.requests().is_empty());
+    }
+
+    #[test]
+    fn should_split_time_outside_across_midnight() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":0,"created_at":"2019-06-01T08:00:00Z","pets":[{"id":2}],"movements":[{"direction":1}]},
+            {"id":2,"type":0,"created_at":"2019-06-01T12:00:00Z","pets":[{"id":2}],"movements":[{"direction":2}]},
+            {"id":3,"type":0,"created_at":"2019-06-01T13:00:00Z","pets":[{"id":2}],"movements":[{"direction":0}]},
+            {"id":4,"type":0,"created_at":"2019-06-01T14:00:00Z","pets":[{"id":2}],"movements":[{"direction":1}]},
+            {"id":5,"type":0,"created_at":"2019-06-01T22:00:00Z","pets":[{"id":2}],"movements":[{"direction":2}]},
+            {"id":6,"type":0,"created_at":"2019-06-02T01:00:00Z","pets":[{"id":9}],"movements":[{"direction":1}]},
+            {"id":7,"type":0,"created_at":"2019-06-02T06:30:00Z","pets":[{"id":2}],"movements":[{"direction":1}]},
+            {"id":8,"type":0,"created_at":"2019-06-02T07:00:00Z","pets":[{"id":2}],"movements":[{"direction":1}]},
+            {"id":9,"type":0,"created_at":"2019-06-02T20:00:00Z","pets":[{"id":2}],"movements":[{"direction":2}]}
+        ]"#,
+        )
+        .unwrap();
+        let range = DateRange::new(
+            Date::new(2019, 6, 1).unwrap(),
+            Date::new(2019, 6, 2).unwrap(),
+        )
+        .unwrap();
+        let until = "2019-06-02T21:00:00Z".parse().unwrap();
+        let daily = DailyOutside::from_events(&events, PetId::new(2), range, until);
+
+        let hours = |seconds: i64| seconds as f64 / 3600.0;
+        assert_eq!(8.0 + 2.0 + 2.0, hours(daily[0].total()));
+        assert_eq!(8.0, hours(daily[0].longest()));
+        assert_eq!(3, daily[0].trips());
+        assert_eq!(6.5 + 1.0, hours(daily[1].total()));
+        assert_eq!(6.5, hours(daily[1].longest()));
+    }
+
+    #[test]
+    fn should_roll_drinking_averages() {
+        let visit = |day: &str, change: f64| Drinking {
+            from: format!("2019-06-{}T10:00:00Z", day).parse().unwrap(),
+            to: format!("2019-06-{}T10:01:00Z", day).parse().unwrap(),
+            duration: 60,
+            device_id: None,
+            context: 1,
+            weights: vec![Weight {
+                index: 0,
+                weight: 500.0,
+                change,
+            }],
+        };
+        let datapoints = [
+            visit("01", -30.0),
+            visit("01", -10.0),
+            visit("02", -20.0),
+            visit("03", -60.0),
+        ];
+        let range = DateRange::new(
+            Date::new(2019, 6, 1).unwrap(),
+            Date::new(2019, 6, 3).unwrap(),
+        )
+        .unwrap();
+        let daily = DailyDrinking::from_datapoints(&datapoints, range, 2);
+
+        assert_eq!(
+            vec![40.0, 20.0, 60.0],
+            daily
+                .iter()
+                .map(DailyDrinking::consumed)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, daily[0].visits());
+        assert_eq!(
+            vec![40.0, 30.0, 40.0],
+            daily
+                .iter()
+                .map(DailyDrinking::rolling_average)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![0.0, -10.0, 10.0],
+            daily.iter().map(DailyDrinking::trend).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Running the long lived parts of the crate in the background, enabled
+//! with the `runner` feature.
+//!
+//! A daemon usually runs a [`Watcher`](../watcher/struct.Watcher.html), a
+//! [`CurfewScheduler`](../schedule/struct.CurfewScheduler.html) and a
+//! [`SunLock`](../sun/struct.SunLock.html) side by side. A
+//! [`Runner`](struct.Runner.html) gives each its own thread as a managed
+//! [`Task`](trait.Task.html): a task does whatever is due and says how long
+//! to wait before it is due again, which is what their `step` methods do.
+//! The runner keeps a [`Health`](struct.Health.html) record for every task,
+//! retries a step that fails after a delay, rebuilds a task that panics and
+//! stops them all when asked or dropped.
+//!
+//! The client is blocking, so tasks run on plain threads rather than an
+//! async executor. Each task is built on its own thread by a factory, which
+//! means tasks, and the handlers they hold, do not have to be `Send`.
+use crate::clock::{Clock, SystemClock};
+use crate::error::{Error, Result};
+use crate::time::Timestamp;
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Work a [`Runner`](struct.Runner.html) repeats.
+pub trait Task {
+    /// Does whatever is due and returns how long to wait before the next
+    /// step, `None` when there is nothing more to do.
+    fn step(&mut self) -> Result<Option<Duration>>;
+}
+
+impl<F: FnMut() -> Result<Option<Duration>>> Task for F {
+    fn step(&mut self) -> Result<Option<Duration>> {
+        self()
+    }
+}
+
+/// Where a task is up to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TaskState {
+    /// In the middle of a step.
+    Running,
+    /// Waiting for its next step.
+    Waiting {
+        /// When the next step is due.
+        until: Timestamp,
+    },
+    /// Nothing more to do.
+    Finished,
+    /// Stopped by the runner.
+    Stopped,
+}
+
+/// How a task has been doing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Health {
+    name: String,
+    state: TaskState,
+    steps: u64,
+    failures: u64,
+    panics: u64,
+    last_success: Option<Timestamp>,
+    last_error: Option<String>,
+}
+
+impl Health {
+    /// The name the task was started with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where the task is up to.
+    pub fn state(&self) -> TaskState {
+        self.state
+    }
+
+    /// Steps taken, whether they succeeded or not.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Steps that returned an error.
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Steps that panicked, each followed by a restart.
+    pub fn panics(&self) -> u64 {
+        self.panics
+    }
+
+    /// When a step last succeeded.
+    pub fn last_success(&self) -> Option<Timestamp> {
+        self.last_success
+    }
+
+    /// Why the last step failed, `None` once a step succeeds again.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Whether the last step succeeded, or no step has been taken yet.
+    pub fn is_healthy(&self) -> bool {
+        self.last_error.is_none()
+    }
+}
+
+#[derive(Debug)]
+struct Control {
+    health: Health,
+    stopping: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    control: Mutex<Control>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn update(&self, f: impl FnOnce(&mut Health)) {
+        f(&mut self.control.lock().unwrap().health);
+    }
+
+    /// Waits for `wait`, returning `false` if the task was stopped first.
+    fn sleep(&self, wait: Duration) -> bool {
+        let control = self.control.lock().unwrap();
+        let (control, _) = self
+            .wake
+            .wait_timeout_while(control, wait, |control| !control.stopping)
+            .unwrap();
+        !control.stopping
+    }
+
+    fn stopping(&self) -> bool {
+        self.control.lock().unwrap().stopping
+    }
+
+    fn stop(&self) {
+        self.control.lock().unwrap().stopping = true;
+        self.wake.notify_all();
+    }
+}
+
+struct Managed {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Managed {
+    fn stop(&mut self) {
+        self.shared.stop();
+        if let Some(thread) = self.thread.take() {
+            // The task catches its own panics, so the thread always returns.
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs tasks on their own threads, see the module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::runner::{Runner, TaskState};
+/// use std::time::Duration;
+/// let mut runner = Runner::new();
+/// runner.start("count to three", || {
+///     let mut count = 0;
+///     move || {
+///         count += 1;
+///         Ok(if count < 3 { Some(Duration::from_millis(1)) } else { None })
+///     }
+/// });
+/// while runner.health()[0].state() != TaskState::Finished {
+///     std::thread::sleep(Duration::from_millis(1));
+/// }
+/// assert_eq!(3, runner.health()[0].steps());
+/// ```
+pub struct Runner {
+    retry_delay: Duration,
+    restart_delay: Duration,
+    clock: Arc<dyn Clock>,
+    tasks: Vec<Managed>,
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner {
+            retry_delay: Duration::from_secs(60),
+            restart_delay: Duration::from_secs(10),
+            clock: Arc::new(SystemClock),
+            tasks: Vec::new(),
+        }
+    }
+}
+
+impl Runner {
+    /// A runner retrying failed steps after a minute and restarting tasks
+    /// ten seconds after they panic.
+    pub fn new() -> Self {
+        Runner::default()
+    }
+
+    /// Sets how long a task waits after a step fails. While the service is
+    /// unavailable it waits as long as the API asks, if that is longer.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Sets how long a task that panicked waits before it is rebuilt.
+    pub fn with_restart_delay(mut self, delay: Duration) -> Self {
+        self.restart_delay = delay;
+        self
+    }
+
+    /// Sets the clock used for the times in the health records.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Starts a task on a new thread. `make` builds the task on that
+    /// thread, and builds it again after it panics.
+    pub fn start<F, K>(&mut self, name: &str, make: F)
+    where
+        F: FnMut() -> K + Send + 'static,
+        K: Task,
+    {
+        let shared = Arc::new(Shared {
+            control: Mutex::new(Control {
+                health: Health {
+                    name: name.to_string(),
+                    state: TaskState::Running,
+                    steps: 0,
+                    failures: 0,
+                    panics: 0,
+                    last_success: None,
+                    last_error: None,
+                },
+                stopping: false,
+            }),
+            wake: Condvar::new(),
+        });
+        let worker = Worker {
+            shared: Arc::clone(&shared),
+            clock: Arc::clone(&self.clock),
+            retry_delay: self.retry_delay,
+            restart_delay: self.restart_delay,
+        };
+        let thread = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || worker.run(make))
+            .expect("failed to spawn a task thread");
+        self.tasks.push(Managed {
+            shared,
+            thread: Some(thread),
+        });
+    }
+
+    /// The health of every task, in the order they were started.
+    pub fn health(&self) -> Vec<Health> {
+        self.tasks
+            .iter()
+            .map(|task| task.shared.control.lock().unwrap().health.clone())
+            .collect()
+    }
+
+    /// Stops the tasks with the given name, waiting for a step in progress
+    /// to finish. Returns whether there were any.
+    pub fn stop(&mut self, name: &str) -> bool {
+        let mut found = false;
+        for task in &mut self.tasks {
+            if task.shared.control.lock().unwrap().health.name == name {
+                task.stop();
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// Stops every task, waiting for steps in progress to finish. Dropping
+    /// the runner does the same.
+    pub fn stop_all(&mut self) {
+        self.tasks.iter().for_each(|task| task.shared.stop());
+        self.tasks.iter_mut().for_each(Managed::stop);
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+impl fmt::Debug for Runner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Runner")
+            .field("retry_delay", &self.retry_delay)
+            .field("restart_delay", &self.restart_delay)
+            .field("tasks", &self.health())
+            .finish_non_exhaustive()
+    }
+}
+
+struct Worker {
+    shared: Arc<Shared>,
+    clock: Arc<dyn Clock>,
+    retry_delay: Duration,
+    restart_delay: Duration,
+}
+
+impl Worker {
+    fn run<F, K>(self, mut make: F)
+    where
+        F: FnMut() -> K,
+        K: Task,
+    {
+        let mut task = None;
+        while !self.shared.stopping() {
+            self.shared
+                .update(|health| health.state = TaskState::Running);
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                task.get_or_insert_with(&mut make).step()
+            }));
+            let now = self.clock.now();
+            let wait = match outcome {
+                Ok(Ok(wait)) => {
+                    self.shared.update(|health| {
+                        health.steps += 1;
+                        health.last_success = Some(now);
+                        health.last_error = None;
+                    });
+                    match wait {
+                        Some(wait) => wait,
+                        None => {
+                            self.shared
+                                .update(|health| health.state = TaskState::Finished);
+                            return;
+                        }
+                    }
+                }
+                Ok(Err(error)) => {
+                    let wait = match &error {
+                        Error::ServiceUnavailable { retry_after } => {
+                            retry_after.unwrap_or_default().max(self.retry_delay)
+                        }
+                        _ => self.retry_delay,
+                    };
+                    self.shared.update(|health| {
+                        health.steps += 1;
+                        health.failures += 1;
+                        health.last_error = Some(error.to_string());
+                    });
+                    wait
+                }
+                Err(payload) => {
+                    task = None;
+                    self.shared.update(|health| {
+                        health.steps += 1;
+                        health.panics += 1;
+                        health.last_error = Some(format!("panicked: {}", message(&*payload)));
+                    });
+                    self.restart_delay
+                }
+            };
+            self.shared.update(|health| {
+                health.state = TaskState::Waiting { until: now + wait };
+            });
+            if !self.shared.sleep(wait) {
+                break;
+            }
+        }
+        self.shared
+            .update(|health| health.state = TaskState::Stopped);
+    }
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    fn wait_for(runner: &Runner, done: impl Fn(TaskState) -> bool) -> Health {
+        let started = Instant::now();
+        loop {
+            let health = runner.health().remove(0);
+            if done(health.state()) || started.elapsed() > Duration::from_secs(5) {
+                return health;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn should_retry_failures_and_restart_after_panics() {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let mut runner = Runner::new()
+            .with_retry_delay(Duration::ZERO)
+            .with_restart_delay(Duration::ZERO);
+        let counted = Arc::clone(&builds);
+        runner.start("flaky", move || {
+            let build = counted.fetch_add(1, Ordering::SeqCst);
+            let mut steps = 0;
+            move || {
+                steps += 1;
+                match (build, steps) {
+                    (0, 1) => Ok(Some(Duration::ZERO)),
+                    (0, 2) => Err(Error::transport("connection reset")),
+                    (0, _) => panic!("bad frame"),
+                    _ => Ok(None),
+                }
+            }
+        });
+
+        let health = wait_for(&runner, |state| state == TaskState::Finished);
+        assert_eq!(TaskState::Finished, health.state());
+        assert_eq!(4, health.steps());
+        assert_eq!(1, health.failures());
+        assert_eq!(1, health.panics());
+        assert!(health.is_healthy());
+        assert_eq!(2, builds.load(Ordering::SeqCst));
+        assert_eq!("flaky", health.name());
+    }
+
+    #[test]
+    fn should_stop_waiting_tasks() {
+        let mut runner = Runner::new();
+        runner.start("slow", || {
+            || -> Result<Option<Duration>> { Err(Error::transport("down")) }
+        });
+        let health = wait_for(&runner, |state| matches!(state, TaskState::Waiting { .. }));
+        assert_eq!(1, health.failures());
+
+        assert!(runner.stop("slow"));
+        assert!(!runner.stop("missing"));
+        let health = runner.health().remove(0);
+        assert_eq!(TaskState::Stopped, health.state());
+        assert_eq!(Some("transport error: down"), health.last_error());
+    }
+}
@@ -0,0 +1,346 @@
+//! Curfews that change with the day of the week or the time of year.
+//!
+//! A flap stores one set of curfews and keeps them every day. A
+//! [`CurfewProfile`](struct.CurfewProfile.html) says which curfews apply on
+//! which days, such as a later lock at weekends or an earlier one in
+//! winter, and a [`CurfewScheduler`](struct.CurfewScheduler.html) pushes
+//! each set to the flaps when its days begin. Days change over at midday
+//! household time by default, so an overnight curfew is never swapped while
+//! it is keeping pets in.
+use crate::clock::{Clock, SystemClock};
+use crate::device::Curfew;
+#[cfg(feature = "devices")]
+use crate::device::{Device, Devices};
+#[cfg(feature = "devices")]
+use crate::error::Result;
+use crate::holiday::Override;
+use crate::time::{Date, TimeOfDay, Timestamp, Weekday};
+#[cfg(feature = "devices")]
+use crate::transport::Transport;
+use crate::zone::Zone;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "devices")]
+use std::thread;
+#[cfg(feature = "devices")]
+use std::time::Duration;
+
+/// The curfews for some of the days of a profile.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurfewRule {
+    curfews: Vec<Curfew>,
+    days: Option<Vec<Weekday>>,
+    season: Option<((u8, u8), (u8, u8))>,
+}
+
+impl CurfewRule {
+    /// A rule applying the curfews every day, until narrowed with
+    /// [`on`](#method.on) or [`between`](#method.between).
+    pub fn new<C: Into<Vec<Curfew>>>(curfews: C) -> Self {
+        CurfewRule {
+            curfews: curfews.into(),
+            days: None,
+            season: None,
+        }
+    }
+
+    /// Applies the rule only on these days of the week.
+    pub fn on(mut self, days: &[Weekday]) -> Self {
+        self.days = Some(days.to_vec());
+        self
+    }
+
+    /// Applies the rule only from one `(month, day)` to another, both
+    /// included, every year. A season ending before it starts runs over
+    /// the new year.
+    pub fn between(mut self, from: (u8, u8), to: (u8, u8)) -> Self {
+        self.season = Some((from, to));
+        self
+    }
+
+    /// The curfews the rule applies.
+    pub fn curfews(&self) -> &[Curfew] {
+        &self.curfews
+    }
+
+    /// Whether the rule applies on a date.
+    pub fn applies_on(&self, date: Date) -> bool {
+        let on_day = self
+            .days
+            .as_ref()
+            .is_none_or(|days| days.contains(&date.weekday()));
+        let in_season = self.season.is_none_or(|(from, to)| {
+            let day = (date.month(), date.day());
+            if from <= to {
+                from <= day && day <= to
+            } else {
+                from <= day || day <= to
+            }
+        });
+        on_day && in_season
+    }
+}
+
+/// Which curfews apply on which days.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::Curfew;
+/// use sure_petcare::schedule::{CurfewProfile, CurfewRule};
+/// use sure_petcare::time::{Date, Weekday};
+/// let night = |lock: &str, unlock: &str| Curfew::new(lock.parse().unwrap(), unlock.parse().unwrap());
+/// let profile = CurfewProfile::new(vec![night("21:00", "06:30")])
+///     .with_rule(CurfewRule::new(vec![night("19:00", "07:00")]).between((11, 1), (2, 28)))
+///     .with_rule(CurfewRule::new(vec![night("23:00", "08:00")]).on(&Weekday::WEEKEND));
+/// // A Saturday in winter: the winter rule comes first.
+/// let saturday = Date::new(2019, 12, 7).unwrap();
+/// assert_eq!("19:00", profile.curfews_on(saturday)[0].lock_time().to_string());
+/// // A Saturday in summer.
+/// let saturday = Date::new(2019, 6, 1).unwrap();
+/// assert_eq!("23:00", profile.curfews_on(saturday)[0].lock_time().to_string());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurfewProfile {
+    default: Vec<Curfew>,
+    rules: Vec<CurfewRule>,
+}
+
+impl CurfewProfile {
+    /// A profile applying `default` on days no rule applies to.
+    pub fn new<C: Into<Vec<Curfew>>>(default: C) -> Self {
+        CurfewProfile {
+            default: default.into(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// One set of curfews from Monday to Friday and another at weekends.
+    pub fn weekly<C: Into<Vec<Curfew>>, W: Into<Vec<Curfew>>>(working: C, weekend: W) -> Self {
+        CurfewProfile::new(working).with_rule(CurfewRule::new(weekend).on(&Weekday::WEEKEND))
+    }
+
+    /// Adds a rule. The first rule applying on a day decides its curfews.
+    pub fn with_rule(mut self, rule: CurfewRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The rules, in the order they are tried.
+    pub fn rules(&self) -> &[CurfewRule] {
+        &self.rules
+    }
+
+    /// The curfews that apply on a date.
+    pub fn curfews_on(&self, date: Date) -> &[Curfew] {
+        self.rules
+            .iter()
+            .find(|rule| rule.applies_on(date))
+            .map_or(&self.default, |rule| &rule.curfews)
+    }
+}
+
+/// Pushes the curfews of a profile to flaps as the days change, see the
+/// module documentation.
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "devices")]
+/// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::device::Curfew;
+/// use sure_petcare::schedule::{CurfewProfile, CurfewScheduler};
+/// use sure_petcare::zone::Zone;
+/// let night = |lock: &str, unlock: &str| Curfew::new(lock.parse().unwrap(), unlock.parse().unwrap());
+/// let profile = CurfewProfile::weekly(vec![night("21:00", "06:30")], vec![night("23:00", "08:00")]);
+/// let flaps = client.devices().list()?;
+/// CurfewScheduler::new(profile, Zone::named("Europe/London").unwrap()).run(&client.devices(), &flaps)
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CurfewScheduler {
+    profile: CurfewProfile,
+    zone: Zone,
+    changeover: TimeOfDay,
+    hold: Option<Override<Vec<Curfew>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl CurfewScheduler {
+    /// Schedules a profile by the wall clock of the household's zone.
+    pub fn new(profile: CurfewProfile, zone: Zone) -> Self {
+        CurfewScheduler {
+            profile,
+            zone,
+            changeover: TimeOfDay::new(12, 0).expect("midday"),
+            hold: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the local time each day's curfews take over from the day
+    /// before, midday by default.
+    pub fn with_changeover(mut self, changeover: TimeOfDay) -> Self {
+        self.changeover = changeover;
+        self
+    }
+
+    /// Sets the clock [`run`](#method.run) schedules from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Keeps the curfews set on `hold`, while they are set, instead of the
+    /// profile's; an empty list suspends curfews. [`run`](#method.run)
+    /// pushes a change to the override as soon as it is made, and the
+    /// profile again once it expires.
+    pub fn with_override(mut self, hold: Override<Vec<Curfew>>) -> Self {
+        self.hold = Some(hold);
+        self
+    }
+
+    /// The profile being scheduled.
+    pub fn profile(&self) -> &CurfewProfile {
+        &self.profile
+    }
+
+    /// The curfews the flaps should hold at an instant.
+    pub fn curfews_at(&self, instant: Timestamp) -> &[Curfew] {
+        let (date, time) = self.zone.local(instant);
+        let day = if time < self.changeover {
+            date.add_days(-1)
+        } else {
+            date
+        };
+        self.profile.curfews_on(day)
+    }
+
+    /// The next time the curfews change after an instant, with the new
+    /// curfews, `None` if they stay the same for the coming year.
+    pub fn next_change_after(&self, instant: Timestamp) -> Option<(Timestamp, &[Curfew])> {
+        let current = self.curfews_at(instant);
+        let (today, _) = self.zone.local(instant);
+        (0..=366)
+            .map(|days| today.add_days(days))
+            .map(|date| self.zone.resolve(date, self.changeover))
+            .filter(|at| *at > instant)
+            .map(|at| (at, self.curfews_at(at)))
+            .find(|(_, curfews)| *curfews != current)
+    }
+
+    /// Pushes the current curfews to the flaps, then sleeps until each
+    /// change and pushes the new ones, for as long as the process runs.
+    /// Returns the first failure to update a flap, after the others have
+    /// been updated.
+    #[cfg(feature = "devices")]
+    pub fn run<T: Transport>(&self, devices: &Devices<'_, T>, flaps: &[Device<'_>]) -> Result<()> {
+        while let Some(wait) = self.step(devices, flaps)? {
+            match &self.hold {
+                Some(hold) => hold.wait(wait),
+                None => thread::sleep(wait),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes the current curfews to the flaps, or those the override
+    /// holds, and returns how long until they next change or the override
+    /// expires, `None` if they stay the same for the coming year.
+    #[cfg(feature = "devices")]
+    pub fn step<T: Transport>(
+        &self,
+        devices: &Devices<'_, T>,
+        flaps: &[Device<'_>],
+    ) -> Result<Option<Duration>> {
+        let now = self.clock.now();
+        if let Some((curfews, until)) = self.hold.as_ref().and_then(|hold| hold.active_at(now)) {
+            devices.set_all_curfews(flaps, &curfews).into_result()?;
+            return Ok(Some(until.elapsed_since(now)));
+        }
+        devices
+            .set_all_curfews(flaps, self.curfews_at(now))
+            .into_result()?;
+        Ok(self
+            .next_change_after(now)
+            .map(|(at, _)| at.elapsed_since(now)))
+    }
+}
+
+impl fmt::Debug for CurfewScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CurfewScheduler")
+            .field("profile", &self.profile)
+            .field("zone", &self.zone)
+            .field("changeover", &self.changeover)
+            .field("hold", &self.hold)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_change_curfews_at_midday_household_time() {
+        let night =
+            |lock: &str, unlock: &str| Curfew::new(lock.parse().unwrap(), unlock.parse().unwrap());
+        let working = vec![night("21:00", "06:30")];
+        let weekend = vec![night("23:00", "08:00")];
+        let scheduler = CurfewScheduler::new(
+            CurfewProfile::weekly(working.clone(), weekend.clone()),
+            "GMT0BST,M3.5.0/1,M10.5.0".parse().unwrap(),
+        );
+
+        assert_eq!(Weekday::Saturday, Date::new(2019, 6, 1).unwrap().weekday());
+        // Friday night into Saturday morning keeps Friday's curfew.
+        let saturday_morning: Timestamp = "2019-06-01T07:00:00Z".parse().unwrap();
+        assert_eq!(&working[..], scheduler.curfews_at(saturday_morning));
+        let (at, curfews) = scheduler.next_change_after(saturday_morning).unwrap();
+        assert_eq!("2019-06-01T11:00:00+00:00", at.to_string());
+        assert_eq!(&weekend[..], curfews);
+        let (at, curfews) = scheduler.next_change_after(at).unwrap();
+        assert_eq!("2019-06-03T11:00:00+00:00", at.to_string());
+        assert_eq!(&working[..], curfews);
+
+        let same = CurfewScheduler::new(CurfewProfile::new(working), Zone::utc());
+        assert_eq!(None, same.next_change_after(saturday_morning));
+    }
+
+    #[cfg(feature = "devices")]
+    #[test]
+    fn should_keep_overridden_curfews_until_they_expire() {
+        use crate::client::SurePet;
+        use crate::clock::ManualClock;
+        use crate::transport::mock::MockTransport;
+
+        let night =
+            |lock: &str, unlock: &str| Curfew::new(lock.parse().unwrap(), unlock.parse().unwrap());
+        let clock = Arc::new(ManualClock::new("2019-06-01T07:00:00Z".parse().unwrap()));
+        let hold = Override::new();
+        let scheduler = CurfewScheduler::new(
+            CurfewProfile::new(vec![night("21:00", "06:30")]),
+            Zone::utc(),
+        )
+        .with_clock(Arc::clone(&clock))
+        .with_override(hold.clone());
+        let transport = MockTransport::new().respond(200, "{}").respond(200, "{}");
+        let client = SurePet::new(&transport, "token");
+        let flaps: Vec<Device> =
+            serde_json::from_str(r#"[{"id":10,"name":"Back door","product_id":6}]"#).unwrap();
+
+        hold.set(Vec::new(), "2019-06-08T12:00:00Z".parse().unwrap());
+        let wait = scheduler.step(&client.devices(), &flaps).unwrap();
+        assert_eq!(Some(Duration::from_secs(7 * 86_400 + 5 * 3600)), wait);
+        clock.advance(wait.unwrap());
+        assert_eq!(None, scheduler.step(&client.devices(), &flaps).unwrap());
+
+        let bodies: Vec<serde_json::Value> = transport
+            .requests()
+            .iter()
+            .map(|request| serde_json::from_slice(request.body.as_ref().unwrap()).unwrap())
+            .collect();
+        assert_eq!(serde_json::json!([]), bodies[0]["curfew"]);
+        assert_eq!(1, bodies[1]["curfew"].as_array().unwrap().len());
+    }
+}
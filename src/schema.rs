@@ -0,0 +1,520 @@
+//! JSON Schemas of the models, enabled with the `schemars` feature.
+//!
+//! The schemas describe the JSON this crate serializes, for example in
+//! exports or the history store, so consumers in other languages can
+//! validate it or generate types from it.
+//!
+//! ```
+//! use sure_petcare::pet::Pet;
+//! use sure_petcare::schema;
+//! let schema = schema::schema_for::<Pet>();
+//! assert_eq!("Pet", schema["title"]);
+//! assert!(schema["definitions"]["Position"].is_object());
+//! ```
+use crate::device::{BowlStatus, Control, Curfew, Device, LockMode, Product, Signal, Status};
+use crate::pet::{Location, Pet, PetStatus, Photo, Position, Visit};
+use crate::state::{Household, State};
+use crate::time::{TimeOfDay, Timestamp};
+use crate::timeline::{
+    DeviceRef, Direction, Event, EventKind, Frame, MovementEntry, PetRef, WeightEntry,
+};
+use serde_json::{json, Map, Value};
+
+/// A type with a JSON Schema.
+pub trait JsonSchema {
+    /// The name of the type under `definitions`.
+    const NAME: &'static str;
+
+    /// The schema of the type, referring to other types through
+    /// `#/definitions/<name>`.
+    fn definition() -> Value;
+}
+
+/// A draft-07 schema document for `T`, including the definitions of every
+/// model so all references resolve.
+pub fn schema_for<T: JsonSchema>() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": T::NAME,
+        "allOf": [reference::<T>()],
+        "definitions": definitions(),
+    })
+}
+
+/// The definitions of every model, keyed by name.
+pub fn definitions() -> Map<String, Value> {
+    fn insert<T: JsonSchema>(map: &mut Map<String, Value>) {
+        map.insert(T::NAME.to_string(), T::definition());
+    }
+
+    let mut map = Map::new();
+    insert::<Timestamp>(&mut map);
+    insert::<TimeOfDay>(&mut map);
+    insert::<Household>(&mut map);
+    insert::<State>(&mut map);
+    insert::<Location>(&mut map);
+    insert::<Pet>(&mut map);
+    insert::<Photo>(&mut map);
+    insert::<Position>(&mut map);
+    insert::<PetStatus>(&mut map);
+    insert::<Visit>(&mut map);
+    insert::<Product>(&mut map);
+    insert::<LockMode>(&mut map);
+    insert::<Device>(&mut map);
+    insert::<Status>(&mut map);
+    insert::<Signal>(&mut map);
+    insert::<BowlStatus>(&mut map);
+    insert::<Control>(&mut map);
+    insert::<Curfew>(&mut map);
+    insert::<EventKind>(&mut map);
+    insert::<Direction>(&mut map);
+    insert::<Event>(&mut map);
+    insert::<PetRef>(&mut map);
+    insert::<DeviceRef>(&mut map);
+    insert::<MovementEntry>(&mut map);
+    insert::<WeightEntry>(&mut map);
+    insert::<Frame>(&mut map);
+    map
+}
+
+fn reference<T: JsonSchema>() -> Value {
+    json!({ "$ref": format!("#/definitions/{}", T::NAME) })
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+/// The schema of a code enum, listing the known codes.
+fn code(description: &str, known: &[i64]) -> Value {
+    json!({ "type": "integer", "description": description, "examples": known })
+}
+
+impl JsonSchema for Timestamp {
+    const NAME: &'static str = "Timestamp";
+
+    fn definition() -> Value {
+        json!({ "type": "string", "format": "date-time" })
+    }
+}
+
+impl JsonSchema for TimeOfDay {
+    const NAME: &'static str = "TimeOfDay";
+
+    fn definition() -> Value {
+        json!({ "type": "string", "pattern": "^[0-2][0-9]:[0-5][0-9]$" })
+    }
+}
+
+impl JsonSchema for Household<'_> {
+    const NAME: &'static str = "Household";
+
+    fn definition() -> Value {
+        object(
+            &["id", "name"],
+            json!({ "id": { "type": "integer" }, "name": { "type": "string" } }),
+        )
+    }
+}
+
+impl JsonSchema for State<'_> {
+    const NAME: &'static str = "State";
+
+    fn definition() -> Value {
+        object(
+            &["households", "pets", "devices"],
+            json!({
+                "households": array(reference::<Household>()),
+                "pets": array(reference::<Pet>()),
+                "devices": array(reference::<Device>()),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Location {
+    const NAME: &'static str = "Location";
+
+    fn definition() -> Value {
+        code("1 inside, 2 outside", &[1, 2])
+    }
+}
+
+impl JsonSchema for Pet<'_> {
+    const NAME: &'static str = "Pet";
+
+    fn definition() -> Value {
+        object(
+            &["id", "name", "status"],
+            json!({
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "household_id": nullable(json!({ "type": "integer" })),
+                "tag_id": nullable(json!({ "type": "integer" })),
+                "position": nullable(reference::<Position>()),
+                "status": reference::<PetStatus>(),
+                "photo": reference::<Photo>(),
+                "comments": nullable(json!({ "type": "string" })),
+                "conditions": array(json!({ "anyOf": [
+                    { "type": "integer" },
+                    object(&["id"], json!({ "id": { "type": "integer" } })),
+                ] })),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Photo<'_> {
+    const NAME: &'static str = "Photo";
+
+    fn definition() -> Value {
+        object(
+            &["id", "location"],
+            json!({
+                "id": { "type": "integer" },
+                "location": { "type": "string", "format": "uri" },
+                "updated_at": nullable(reference::<Timestamp>()),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Position {
+    const NAME: &'static str = "Position";
+
+    fn definition() -> Value {
+        object(
+            &["where", "since"],
+            json!({
+                "device_id": nullable(json!({ "type": "integer" })),
+                "where": reference::<Location>(),
+                "since": reference::<Timestamp>(),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for PetStatus {
+    const NAME: &'static str = "PetStatus";
+
+    fn definition() -> Value {
+        object(
+            &[],
+            json!({
+                "feeding": nullable(reference::<Visit>()),
+                "drinking": nullable(reference::<Visit>()),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Visit {
+    const NAME: &'static str = "Visit";
+
+    fn definition() -> Value {
+        object(
+            &["at"],
+            json!({
+                "device_id": nullable(json!({ "type": "integer" })),
+                "at": reference::<Timestamp>(),
+                "change": array(json!({ "type": "number" })),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Product {
+    const NAME: &'static str = "Product";
+
+    fn definition() -> Value {
+        code(
+            "1 hub, 2 repeater, 3 pet door, 4 feeder, 5 programmer, 6 cat flap, \
+             7 feeder lite, 8 Felaqua",
+            &[1, 2, 3, 4, 5, 6, 7, 8],
+        )
+    }
+}
+
+impl JsonSchema for LockMode {
+    const NAME: &'static str = "LockMode";
+
+    fn definition() -> Value {
+        code(
+            "0 unlocked, 1 keep in, 2 keep out, 3 locked, 4 curfew",
+            &[0, 1, 2, 3, 4],
+        )
+    }
+}
+
+impl JsonSchema for Device<'_> {
+    const NAME: &'static str = "Device";
+
+    fn definition() -> Value {
+        object(
+            &["id", "name", "product_id"],
+            json!({
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "product_id": reference::<Product>(),
+                "household_id": nullable(json!({ "type": "integer" })),
+                "parent_device_id": nullable(json!({ "type": "integer" })),
+                "status": reference::<Status>(),
+                "control": reference::<Control>(),
+                "last_activity_at": nullable(reference::<Timestamp>()),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Status {
+    const NAME: &'static str = "Status";
+
+    fn definition() -> Value {
+        object(
+            &[],
+            json!({
+                "battery": nullable(json!({ "type": "number", "description": "volts" })),
+                "online": nullable(json!({ "type": "boolean" })),
+                "signal": nullable(reference::<Signal>()),
+                "bowl_status": array(reference::<BowlStatus>()),
+                "locking": nullable(object(&["mode"], json!({ "mode": reference::<LockMode>() }))),
+                "version": nullable(object(
+                    &[],
+                    json!({ "device": nullable(object(
+                        &[],
+                        json!({
+                            "hardware": { "type": ["number", "string", "null"] },
+                            "firmware": { "type": ["number", "string", "null"] },
+                        }),
+                    )) }),
+                )),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Signal {
+    const NAME: &'static str = "Signal";
+
+    fn definition() -> Value {
+        object(
+            &[],
+            json!({
+                "device_rssi": nullable(json!({ "type": "number" })),
+                "hub_rssi": nullable(json!({ "type": "number" })),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for BowlStatus {
+    const NAME: &'static str = "BowlStatus";
+
+    fn definition() -> Value {
+        object(
+            &["current_weight"],
+            json!({
+                "index": { "type": "integer", "minimum": 0 },
+                "current_weight": { "type": "number" },
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Control {
+    const NAME: &'static str = "Control";
+
+    fn definition() -> Value {
+        object(&[], json!({ "curfew": array(reference::<Curfew>()) }))
+    }
+}
+
+impl JsonSchema for Curfew {
+    const NAME: &'static str = "Curfew";
+
+    fn definition() -> Value {
+        object(
+            &["lock_time", "unlock_time"],
+            json!({
+                "enabled": { "type": "boolean" },
+                "lock_time": reference::<TimeOfDay>(),
+                "unlock_time": reference::<TimeOfDay>(),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for EventKind {
+    const NAME: &'static str = "EventKind";
+
+    fn definition() -> Value {
+        code(
+            "0 movement, 1 low battery, 6 lock status, 7 intruder movement, \
+             20 curfew, 22 feeding, 29 drinking",
+            &[0, 1, 6, 7, 20, 22, 29],
+        )
+    }
+}
+
+impl JsonSchema for Direction {
+    const NAME: &'static str = "Direction";
+
+    fn definition() -> Value {
+        code("0 looked through, 1 in, 2 out", &[0, 1, 2])
+    }
+}
+
+impl JsonSchema for Event<'_> {
+    const NAME: &'static str = "Event";
+
+    fn definition() -> Value {
+        let mut schema = object(
+            &["id", "type", "created_at"],
+            json!({
+                "id": { "type": "integer" },
+                "type": reference::<EventKind>(),
+                "created_at": reference::<Timestamp>(),
+                "household_id": nullable(json!({ "type": "integer" })),
+                "pets": array(reference::<PetRef>()),
+                "devices": array(reference::<DeviceRef>()),
+                "movements": array(reference::<MovementEntry>()),
+                "weights": array(reference::<WeightEntry>()),
+            }),
+        );
+        // Fields the crate does not model are kept as they were received.
+        schema["additionalProperties"] = json!(true);
+        schema
+    }
+}
+
+impl JsonSchema for PetRef<'_> {
+    const NAME: &'static str = "PetRef";
+
+    fn definition() -> Value {
+        object(
+            &["id"],
+            json!({ "id": { "type": "integer" }, "name": { "type": "string" } }),
+        )
+    }
+}
+
+impl JsonSchema for DeviceRef<'_> {
+    const NAME: &'static str = "DeviceRef";
+
+    fn definition() -> Value {
+        object(
+            &["id"],
+            json!({
+                "id": { "type": "integer" },
+                "name": { "type": "string" },
+                "product_id": nullable(json!({ "type": "integer" })),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for MovementEntry {
+    const NAME: &'static str = "MovementEntry";
+
+    fn definition() -> Value {
+        object(
+            &["direction"],
+            json!({
+                "device_id": nullable(json!({ "type": "integer" })),
+                "tag_id": nullable(json!({ "type": "integer" })),
+                "direction": reference::<Direction>(),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for WeightEntry {
+    const NAME: &'static str = "WeightEntry";
+
+    fn definition() -> Value {
+        object(
+            &[],
+            json!({
+                "device_id": nullable(json!({ "type": "integer" })),
+                "tag_id": nullable(json!({ "type": "integer" })),
+                "duration": { "type": "integer", "description": "seconds" },
+                "context": { "type": "integer" },
+                "frames": array(reference::<Frame>()),
+            }),
+        )
+    }
+}
+
+impl JsonSchema for Frame {
+    const NAME: &'static str = "Frame";
+
+    fn definition() -> Value {
+        object(
+            &["current_weight", "change"],
+            json!({
+                "index": { "type": "integer", "minimum": 0 },
+                "current_weight": { "type": "number" },
+                "change": { "type": "number" },
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn references(value: &Value, found: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(target)) = map.get("$ref") {
+                    found.push(target.trim_start_matches("#/definitions/").to_string());
+                }
+                map.values().for_each(|value| references(value, found));
+            }
+            Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn should_resolve_every_reference() {
+        let document = schema_for::<State>();
+        let mut found = Vec::new();
+        references(&document, &mut found);
+
+        let missing: Vec<&String> = found
+            .iter()
+            .filter(|name| document["definitions"].get(name.as_str()).is_none())
+            .collect();
+        assert_eq!(Vec::<&String>::new(), missing);
+    }
+
+    #[test]
+    fn should_describe_serialized_fields() {
+        let pet: Pet = serde_json::from_str(
+            r#"{"id":1,"name":"Milo","tag_id":3,"position":{"where":1,"since":"2019-06-01T07:00:00Z"},
+                "photo":{"id":7,"location":"https://photos.example.com/7"}}"#,
+        )
+        .unwrap();
+        let serialized = serde_json::to_value(&pet).unwrap();
+        let schema = Pet::definition();
+
+        for key in serialized.as_object().unwrap().keys() {
+            assert!(
+                schema["properties"].get(key).is_some(),
+                "{} is undocumented",
+                key
+            );
+        }
+    }
+}
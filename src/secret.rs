@@ -0,0 +1,188 @@
+//! Keeping secrets out of recordings and `Debug` output.
+//!
+//! Tokens, passwords, email addresses and share codes travel in headers and
+//! bodies, and `{:?}` of a client, request, response or error would print
+//! them into logs. The formatters here print `REDACTED` in place of those
+//! values. Building with the `debug-secrets` feature prints them as they
+//! are, for troubleshooting on one's own machine; cassettes are redacted
+//! either way.
+use serde_json::Value;
+use std::fmt;
+
+/// What replaces redacted values.
+pub(crate) const REDACTED: &str = "REDACTED";
+
+/// JSON keys whose values are always redacted.
+pub(crate) const SECRET_KEYS: &[&str] = &[
+    "token",
+    "access_token",
+    "refresh_token",
+    "password",
+    "email_address",
+    "email",
+    "share_code",
+    "secret",
+];
+
+/// Headers whose values are always redacted, in lower case.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// Whether `Debug` output keeps secrets, with the `debug-secrets` feature.
+const SHOW_SECRETS: bool = cfg!(feature = "debug-secrets");
+
+/// Formats a secret value as `REDACTED`.
+pub(crate) struct Secret<'a, T: ?Sized>(pub(crate) &'a T);
+
+impl<T: fmt::Debug + ?Sized> fmt::Debug for Secret<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if SHOW_SECRETS {
+            self.0.fmt(f)
+        } else {
+            f.write_str(REDACTED)
+        }
+    }
+}
+
+/// Formats headers with the values of credential headers redacted.
+pub(crate) struct Headers<'a>(pub(crate) &'a [(String, String)]);
+
+impl fmt::Debug for Headers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        for (name, value) in self.0 {
+            if SECRET_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                list.entry(&(name, Secret(value)));
+            } else {
+                list.entry(&(name, value));
+            }
+        }
+        list.finish()
+    }
+}
+
+/// Formats query parameters with every value redacted, since callers put
+/// keys and tokens in them.
+pub(crate) struct Query<'a>(pub(crate) &'a [(String, String)]);
+
+impl fmt::Debug for Query<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(name, value)| (name, Secret(value))))
+            .finish()
+    }
+}
+
+/// Formats a body as text with its secrets redacted.
+pub(crate) struct Body<'a>(pub(crate) &'a [u8]);
+
+impl fmt::Debug for Body<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if SHOW_SECRETS {
+            String::from_utf8_lossy(self.0).fmt(f)
+        } else {
+            redact_body(self.0).fmt(f)
+        }
+    }
+}
+
+/// Redacts a JSON body, or any email addresses in a body that is not JSON.
+pub(crate) fn redact_body(body: &[u8]) -> String {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body)
+            .split(' ')
+            .map(|word| if is_email(word) { REDACTED } else { word })
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = Value::String(REDACTED.into());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::String(text) if is_email(text) => *text = REDACTED.into(),
+        _ => {}
+    }
+}
+
+pub(crate) fn is_email(text: &str) -> bool {
+    match text.split_once('@') {
+        Some((user, domain)) => {
+            !user.is_empty() && domain.contains('.') && !text.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+#[cfg(all(test, not(feature = "debug-secrets")))]
+mod tests {
+    use crate::client::SurePet;
+    use crate::error::Error;
+    use crate::login;
+    use crate::transport::mock::MockTransport;
+    use crate::transport::{Method, Request, Response};
+
+    #[test]
+    fn should_leave_secrets_out_of_debug_output() {
+        let login = login::RequestBuilder::new()
+            .with_email_address("me@example.com")
+            .with_password("qwerty123")
+            .with_device_id("device")
+            .build();
+        let request = Request {
+            method: Method::Post,
+            url: "https://app.api.surehub.io/api/auth/login".into(),
+            headers: vec![
+                ("Authorization".into(), "Bearer abc.def".into()),
+                ("Accept".into(), "application/json".into()),
+            ],
+            body: Some(serde_json::to_vec(&login).unwrap()),
+            max_body_size: None,
+        };
+        let response = Response {
+            status: 200,
+            headers: vec![("Set-Cookie".into(), "session=s3cret".into())],
+            body: br#"{"data":{"token":"abc.def","user":{"share_code":"XYZ123"}}}"#.to_vec(),
+        };
+        let error = Error::Status {
+            status: 401,
+            body: "unknown user me@example.com".into(),
+        };
+        let client = SurePet::new(MockTransport::new(), "abc.def").with_query("api_key", "k3y");
+
+        let printed = format!(
+            "{:?} {:?} {:?} {:?} {:?}",
+            login, request, response, error, client
+        );
+        for secret in &[
+            "me@example.com",
+            "qwerty123",
+            "abc.def",
+            "s3cret",
+            "XYZ123",
+            "k3y",
+        ] {
+            assert!(!printed.contains(secret), "{} in {}", secret, printed);
+        }
+        assert!(printed.contains("application/json"));
+        assert!(printed.contains("device"));
+        assert!(printed.contains("api_key"));
+    }
+}
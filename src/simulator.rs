@@ -0,0 +1,463 @@
+//! A made up household behind a transport, enabled with the `simulator`
+//! feature.
+//!
+//! A [`Simulator`](struct.Simulator.html) answers the client's requests
+//! from a household invented by a [`Faker`](../fake/struct.Faker.html)
+//! that lives on between them. Each request first catches the household up
+//! to the time on its clock: pets go in and out through the flap, unless
+//! its lock mode or curfew keeps them in or out, eat from the feeder and
+//! drink from the water dispenser, and batteries drain a little every day
+//! and with every visit. Pets, devices, the start state and the timeline
+//! read back what happened; lock mode changes and pet positions set by
+//! hand are applied, and other device settings are accepted and ignored.
+//!
+//! ```
+//! use sure_petcare::client::SurePet;
+//! use sure_petcare::simulator::Simulator;
+//! let client = SurePet::new(Simulator::new(7), "token");
+//! let state = client.start()?;
+//! assert_eq!(2, state.pets().len());
+//! let events = client.timeline().household(state.households()[0].id())?;
+//! assert!(!events.is_empty());
+//! # Ok::<(), sure_petcare::error::Error>(())
+//! ```
+//!
+//! With a [`ManualClock`](../clock/struct.ManualClock.html) in
+//! [`with_clock`](struct.Simulator.html#method.with_clock), days pass as
+//! fast as a demo or a load test needs.
+use crate::clock::{Clock, SystemClock};
+use crate::device::{Device, LockMode, Product};
+use crate::fake::Faker;
+use crate::id::{DeviceId, HouseholdId, PetId};
+use crate::pet::{Location, Pet, Position, Visit};
+use crate::state::{Household, State};
+use crate::time::Timestamp;
+use crate::timeline::{Direction, Event, EventKind, PAGE_SIZE};
+use crate::transport::{Method, Request, Response, Transport};
+use crate::zone::Zone;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+/// Battery volts lost per day, idle.
+const DRAIN_PER_DAY: f64 = 0.02;
+
+/// Battery volts lost per visit; motors and radios cost more than waiting.
+const DRAIN_PER_VISIT: f64 = 0.002;
+
+/// Below this a battery is flat and the device goes offline.
+const FLAT: f64 = 4.0;
+
+/// A transport serving a simulated household, see the module
+/// documentation.
+#[derive(Debug)]
+pub struct Simulator {
+    seed: u64,
+    pets: usize,
+    history: Duration,
+    clock: Arc<dyn Clock>,
+    world: Mutex<Option<World>>,
+}
+
+#[derive(Debug)]
+struct World {
+    faker: Faker,
+    household: Household<'static>,
+    pets: Vec<Pet<'static>>,
+    devices: Vec<Device<'static>>,
+    /// Oldest first.
+    events: Vec<Event<'static>>,
+    until: i64,
+}
+
+#[derive(Deserialize)]
+struct ControlChange {
+    #[serde(default)]
+    locking: Option<LockMode>,
+}
+
+#[derive(Serialize)]
+struct Envelope<T> {
+    data: T,
+}
+
+impl Simulator {
+    /// A household of two pets, a hub, a cat flap, a feeder and a water
+    /// dispenser, made up from `seed` when the first request arrives.
+    pub fn new(seed: u64) -> Self {
+        Simulator {
+            seed,
+            pets: 2,
+            history: Duration::from_secs(86_400),
+            clock: Arc::new(SystemClock),
+            world: Mutex::new(None),
+        }
+    }
+
+    /// Simulates this many pets rather than two.
+    pub fn with_pets(mut self, pets: usize) -> Self {
+        self.pets = pets;
+        self
+    }
+
+    /// Gives the household this much past activity when it is made up, a
+    /// day unless given, so the timeline is not empty from the start.
+    pub fn with_history(mut self, history: Duration) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Runs the household by `clock` rather than the system time.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The household, once the first request has made it up.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.lock().as_ref().map(|world| world.household.id)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<World>> {
+        self.world.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Transport for Simulator {
+    fn send(&self, request: &Request) -> crate::error::Result<Response> {
+        let now = self.clock.now().unix();
+        let mut world = self.lock();
+        let world = world.get_or_insert_with(|| {
+            World::new(
+                self.seed,
+                self.pets,
+                now - self.history.as_secs() as i64,
+                now,
+            )
+        });
+        world.advance(now);
+        let (status, body) = world.answer(request, now);
+        Ok(Response {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: body.into_bytes(),
+        })
+    }
+}
+
+impl World {
+    fn new(seed: u64, pets: usize, from: i64, now: i64) -> Self {
+        let mut faker = Faker::new(seed).with_now(Timestamp::from_unix(from));
+        let household = Household {
+            id: faker.household_id(),
+            name: Cow::Borrowed("Simulated home"),
+            timezone: None,
+        };
+        let pets = faker.pets(pets);
+        let devices = faker.devices();
+        let mut world = World {
+            faker,
+            household,
+            pets,
+            devices,
+            events: Vec::new(),
+            until: from,
+        };
+        world.advance(now);
+        world
+    }
+
+    /// Plays the household forward to `now`.
+    fn advance(&mut self, now: i64) {
+        if now <= self.until {
+            return;
+        }
+        let from = Timestamp::from_unix(self.until + 1);
+        let to = Timestamp::from_unix(now);
+        let mut events = self.faker.events(&self.pets, &self.devices, from, to);
+        events.reverse();
+        let days = (now - self.until) as f64 / 86_400.0;
+        for device in &mut self.devices {
+            drain(device, DRAIN_PER_DAY * days);
+        }
+        for event in events {
+            if self.apply(&event) {
+                self.events.push(event);
+            }
+        }
+        for device in &mut self.devices {
+            if device.status.online != Some(false) && device.product_id == Product::Hub {
+                device.last_activity_at = Some(to);
+            }
+        }
+        self.until = now;
+    }
+
+    /// Applies an event to the pets and devices, returning whether it
+    /// happened: a flap locked the way a pet wants to go turns it back.
+    fn apply(&mut self, event: &Event<'static>) -> bool {
+        let (pet_id, device_id) = match (event.pets.first(), event.devices.first()) {
+            (Some(pet), Some(device)) => (pet.id, device.id),
+            _ => return false,
+        };
+        let device = match self.devices.iter_mut().find(|d| d.id == device_id) {
+            Some(device) => device,
+            None => return false,
+        };
+        if device.status.online == Some(false) {
+            return false;
+        }
+        let pet = match self.pets.iter_mut().find(|p| p.id == pet_id) {
+            Some(pet) => pet,
+            None => return false,
+        };
+        match event.kind {
+            EventKind::Movement => {
+                let direction = match event.movements.first() {
+                    Some(movement) => movement.direction,
+                    None => return false,
+                };
+                if !lets_through(device, direction, event.created_at) {
+                    return false;
+                }
+                pet.position = Some(Position {
+                    device_id: Some(device_id),
+                    location: if direction == Direction::In {
+                        Location::Inside
+                    } else {
+                        Location::Outside
+                    },
+                    since: event.created_at,
+                });
+            }
+            EventKind::Feeding | EventKind::Drinking => {
+                let weights = match event.weights.first() {
+                    Some(weights) => weights,
+                    None => return false,
+                };
+                for frame in &weights.frames {
+                    if let Some(bowl) = device.status.bowl_status.get_mut(frame.index as usize) {
+                        bowl.current_weight = frame.current_weight;
+                    }
+                }
+                let visit = Visit {
+                    device_id: Some(device_id),
+                    at: event.created_at,
+                    change: weights.frames.iter().map(|frame| frame.change).collect(),
+                };
+                if event.kind == EventKind::Feeding {
+                    pet.status.feeding = Some(visit);
+                } else {
+                    pet.status.drinking = Some(visit);
+                }
+            }
+            _ => {}
+        }
+        drain(device, DRAIN_PER_VISIT);
+        device.last_activity_at = Some(event.created_at);
+        true
+    }
+
+    /// The status and body answering a request.
+    fn answer(&mut self, request: &Request, now: i64) -> (u16, String) {
+        let (path, query) = split_url(&request.url);
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let id = |index: usize| segments.get(index).and_then(|s| s.parse::<i64>().ok());
+        match (request.method, segments.as_slice()) {
+            (Method::Post, ["api", "auth", "login"]) => ok(json!({
+                "user": {"id": 1, "email_address": "simulated@example.invalid"},
+                "token": "simulated",
+            })),
+            (Method::Get, ["api", "me", "start"]) => ok(State {
+                households: vec![self.household.clone()],
+                pets: self.pets.clone(),
+                devices: self.devices.clone(),
+            }),
+            (Method::Get, ["api", "household"]) => ok(vec![self.household.clone()]),
+            (Method::Get, ["api", "pet"]) => ok(&self.pets),
+            (Method::Get, ["api", "device"]) => ok(&self.devices),
+            (Method::Get, ["api", "pet", _]) => match self.pet(id(2)) {
+                Some(pet) => ok(pet),
+                None => not_found(),
+            },
+            (Method::Get, ["api", "device", _]) => match self.device(id(2)) {
+                Some(device) => ok(device),
+                None => not_found(),
+            },
+            (Method::Get, ["api", "timeline", "household", _])
+                if id(3) == Some(self.household.id.get()) =>
+            {
+                let number = |name: &str| {
+                    query
+                        .iter()
+                        .find(|(key, _)| *key == name)
+                        .and_then(|(_, value)| value.parse::<i64>().ok())
+                };
+                let size = number("page_size").map_or(PAGE_SIZE, |size| size.max(1) as usize);
+                let page: Vec<&Event<'static>> = self
+                    .events
+                    .iter()
+                    .rev()
+                    .filter(|event| number("before_id").is_none_or(|before| event.id < before))
+                    .filter(|event| number("after_id").is_none_or(|after| event.id > after))
+                    .take(size)
+                    .collect();
+                ok(page)
+            }
+            (Method::Put, ["api", "device", _, "control"]) => {
+                let change: ControlChange = match body(request) {
+                    Some(change) => change,
+                    None => return bad_request(),
+                };
+                let device = match self.device(id(2)) {
+                    Some(device) => device,
+                    None => return not_found(),
+                };
+                if let (Some(mode), Some(locking)) = (change.locking, &mut device.status.locking) {
+                    locking.mode = mode;
+                }
+                ok(&device.control)
+            }
+            (Method::Post, ["api", "pet", _, "position"]) => {
+                let position: Position = match body(request) {
+                    Some(position) => position,
+                    None => return bad_request(),
+                };
+                let pet = match self.pet(id(2)) {
+                    Some(pet) => pet,
+                    None => return not_found(),
+                };
+                pet.position = Some(Position {
+                    device_id: None,
+                    since: position.since.min(Timestamp::from_unix(now)),
+                    ..position
+                });
+                ok(pet.position)
+            }
+            _ => not_found(),
+        }
+    }
+
+    fn pet(&mut self, id: Option<i64>) -> Option<&mut Pet<'static>> {
+        let id = PetId::new(id?);
+        self.pets.iter_mut().find(|pet| pet.id == id)
+    }
+
+    fn device(&mut self, id: Option<i64>) -> Option<&mut Device<'static>> {
+        let id = DeviceId::new(id?);
+        self.devices.iter_mut().find(|device| device.id == id)
+    }
+}
+
+/// Whether a flap lets a pet through in a direction at a time.
+fn lets_through(device: &Device<'_>, direction: Direction, at: Timestamp) -> bool {
+    let mode = device
+        .status
+        .locking
+        .as_ref()
+        .map_or(LockMode::Unlocked, |locking| locking.mode);
+    let curfew = device.control.is_locked_at(at, &Zone::utc());
+    match (mode, direction) {
+        (LockMode::Locked, _) => false,
+        (LockMode::KeepIn, Direction::Out) | (LockMode::KeepOut, Direction::In) => false,
+        (LockMode::Curfew, Direction::Out) => !curfew,
+        _ => true,
+    }
+}
+
+fn drain(device: &mut Device<'_>, volts: f64) {
+    if let Some(battery) = &mut device.status.battery {
+        *battery = (*battery - volts).max(0.0);
+        if *battery < FLAT {
+            device.status.online = Some(false);
+        }
+    }
+}
+
+fn split_url(url: &str) -> (&str, Vec<(&str, &str)>) {
+    let url = match url.find("://") {
+        Some(scheme) => url[scheme + 3..]
+            .find('/')
+            .map_or("/", |host| &url[scheme + 3 + host..]),
+        None => url,
+    };
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .collect();
+    (path, query)
+}
+
+fn body<T: serde::de::DeserializeOwned>(request: &Request) -> Option<T> {
+    serde_json::from_slice(request.body.as_deref()?).ok()
+}
+
+fn ok<T: Serialize>(data: T) -> (u16, String) {
+    match serde_json::to_string(&Envelope { data }) {
+        Ok(body) => (200, body),
+        Err(error) => (500, json!({ "error": error.to_string() }).to_string()),
+    }
+}
+
+fn not_found() -> (u16, String) {
+    (404, r#"{"error":"not simulated"}"#.to_string())
+}
+
+fn bad_request() -> (u16, String) {
+    (400, r#"{"error":"unreadable body"}"#.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::clock::ManualClock;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_play_the_household_forward() {
+        let clock = Arc::new(ManualClock::new(Timestamp::from_unix(1_559_390_400)));
+        let simulator = Simulator::new(42).with_clock(Arc::clone(&clock));
+        let client = SurePet::new(&simulator, "token");
+
+        let state = client.start().unwrap();
+        let household = simulator.household_id().unwrap();
+        assert_eq!(household, state.households()[0].id());
+        let flap = state
+            .devices()
+            .iter()
+            .find(|device| device.product() == Product::CatFlap)
+            .unwrap();
+        let battery = flap.status().battery().unwrap();
+        let first = client.timeline().household(household).unwrap();
+        assert!(!first.is_empty());
+
+        client
+            .devices()
+            .set_lock_mode(flap.id(), LockMode::Locked)
+            .unwrap();
+        clock.advance(Duration::from_secs(3 * 86_400));
+        let later = client.timeline().after(household, first[0].id()).unwrap();
+        assert!(!later.is_empty());
+        assert!(later
+            .windows(2)
+            .all(|pair| pair[0].created_at() >= pair[1].created_at()));
+        assert!(later.iter().all(
+            |event| event.kind() != EventKind::Movement || event.devices()[0].id() != flap.id()
+        ));
+
+        let flap = client.devices().get(flap.id()).unwrap();
+        assert_eq!(Some(LockMode::Locked), flap.status().lock_mode());
+        assert!(flap.status().battery().unwrap() < battery);
+        assert_eq!(
+            404,
+            match client.get::<()>("/api/photo", &[]) {
+                Err(crate::error::Error::Status { status, .. }) => status,
+                other => panic!("unexpected {:?}", other),
+            }
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Streaming timeline events into external storage.
+//!
+//! An [`EventSink`](trait.EventSink.html) is anything events can be written
+//! to, such as a database table or a message queue. [`BatchingSink`](struct.BatchingSink.html)
+//! sits in front of one, collecting events into batches and keeping a batch
+//! buffered until the sink accepts it, so nothing is lost when the sink is
+//! briefly unavailable. Events may be delivered more than once, so sinks
+//! should ignore ids they have already stored.
+use crate::error::Result;
+use crate::timeline::Event;
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+/// Persists batches of events.
+pub trait EventSink {
+    /// Stores the events. Either all of them are stored or an error is
+    /// returned, in which case the same events will be offered again.
+    fn persist(&self, events: &[Event<'_>]) -> Result<()>;
+}
+
+impl<S: EventSink + ?Sized> EventSink for &S {
+    fn persist(&self, events: &[Event<'_>]) -> Result<()> {
+        (**self).persist(events)
+    }
+}
+
+impl<S: EventSink + ?Sized> EventSink for Box<S> {
+    fn persist(&self, events: &[Event<'_>]) -> Result<()> {
+        (**self).persist(events)
+    }
+}
+
+/// A builder for a `BatchingSink`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::sink::{BatchingSinkBuilder, EventSink};
+/// # struct Table;
+/// # impl EventSink for Table {
+/// #     fn persist(&self, _: &[sure_petcare::timeline::Event<'_>]) -> sure_petcare::error::Result<()> { Ok(()) }
+/// # }
+/// let mut sink = BatchingSinkBuilder::new()
+///     .with_batch_size(500)
+///     .build(Table);
+/// sink.push(&[]).unwrap();
+/// assert_eq!(0, sink.pending());
+/// ```
+#[derive(Clone, Debug)]
+pub struct BatchingSinkBuilder {
+    batch_size: usize,
+}
+
+impl Default for BatchingSinkBuilder {
+    fn default() -> Self {
+        BatchingSinkBuilder { batch_size: 100 }
+    }
+}
+
+impl BatchingSinkBuilder {
+    /// Create a new builder with batches of 100 events.
+    pub fn new() -> Self {
+        BatchingSinkBuilder::default()
+    }
+
+    /// Sets how many events are collected before they are persisted.
+    pub fn with_batch_size(&mut self, events: usize) -> &mut Self {
+        self.batch_size = events.max(1);
+        self
+    }
+
+    /// Builds the batching sink in front of `sink`.
+    pub fn build<S: EventSink>(&self, sink: S) -> BatchingSink<S> {
+        BatchingSink {
+            sink,
+            batch_size: self.batch_size,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Collects events into batches for an `EventSink`, see
+/// [`BatchingSinkBuilder`](struct.BatchingSinkBuilder.html).
+///
+/// Buffered events are not written when the sink is dropped, so call
+/// [`flush`](#method.flush) before shutting down.
+#[derive(Debug)]
+pub struct BatchingSink<S> {
+    sink: S,
+    batch_size: usize,
+    pending: Vec<Event<'static>>,
+}
+
+impl<S: EventSink> BatchingSink<S> {
+    /// Buffers the events, persisting full batches. On error the failed
+    /// batch stays buffered and is retried by the next `push` or `flush`.
+    /// Returns how many events were persisted.
+    pub fn push(&mut self, events: &[Event<'_>]) -> Result<usize> {
+        for event in events {
+            self.pending.push(event.clone().into_owned());
+        }
+        let mut persisted = 0;
+        while self.pending.len() >= self.batch_size {
+            persisted += self.persist(self.batch_size)?;
+        }
+        Ok(persisted)
+    }
+
+    /// Persists everything buffered, returning how many events were written.
+    pub fn flush(&mut self) -> Result<usize> {
+        let mut persisted = 0;
+        while !self.pending.is_empty() {
+            persisted += self.persist(self.batch_size.min(self.pending.len()))?;
+        }
+        Ok(persisted)
+    }
+
+    /// The number of buffered events.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The wrapped sink.
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    fn persist(&mut self, count: usize) -> Result<usize> {
+        self.sink.persist(&self.pending[..count])?;
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default)]
+    struct Table {
+        fail: Cell<bool>,
+        batches: RefCell<Vec<Vec<i64>>>,
+    }
+
+    impl EventSink for Table {
+        fn persist(&self, events: &[Event<'_>]) -> Result<()> {
+            if self.fail.get() {
+                return Err(Error::transport("connection refused"));
+            }
+            self.batches
+                .borrow_mut()
+                .push(events.iter().map(Event::id).collect());
+            Ok(())
+        }
+    }
+
+    fn events(ids: &[i64]) -> Vec<Event<'static>> {
+        ids.iter()
+            .map(|id| {
+                Event::deserialize(serde_json::json!({
+                    "id": id,
+                    "type": 0,
+                    "created_at": "2019-06-01T07:00:00+00:00",
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_keep_failed_batches() {
+        let table = Table::default();
+        let mut sink = BatchingSinkBuilder::new().with_batch_size(2).build(&table);
+
+        assert_eq!(2, sink.push(&events(&[1, 2, 3])).unwrap());
+        table.fail.set(true);
+        assert!(sink.push(&events(&[4])).is_err());
+        assert_eq!(2, sink.pending());
+
+        table.fail.set(false);
+        assert_eq!(3, sink.push(&events(&[5])).unwrap() + sink.flush().unwrap());
+        assert_eq!(
+            vec![vec![1, 2], vec![3, 4], vec![5]],
+            *table.batches.borrow()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! A PostgreSQL event sink, enabled with the `postgres` feature.
+//!
+//! The sink renders batches into a single idempotent `INSERT` statement
+//! and hands it to a caller supplied executor, so it works with whichever
+//! Postgres client the application already uses. The table suits
+//! TimescaleDB as well, where `created_at` can become the hypertable time
+//! column.
+use super::EventSink;
+use crate::error::Result;
+use crate::timeline::Event;
+use std::fmt;
+
+/// The table the sink writes to.
+pub const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS surepet_events (
+    id BIGINT NOT NULL,
+    household_id BIGINT,
+    kind INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    pet_ids BIGINT[] NOT NULL,
+    json JSONB NOT NULL,
+    PRIMARY KEY (id, created_at)
+);
+";
+
+/// An event sink executing SQL through `execute`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::sink::{postgres::PostgresSink, EventSink};
+/// let sink = PostgresSink::new(|sql: &str| {
+///     // e.g. client.lock().unwrap().batch_execute(sql).map_err(Error::transport)
+///     assert!(sql.is_empty());
+///     Ok(())
+/// });
+/// sink.persist(&[]).unwrap();
+/// ```
+pub struct PostgresSink<E> {
+    execute: E,
+}
+
+impl<E: Fn(&str) -> Result<()>> PostgresSink<E> {
+    /// Creates a sink running statements with `execute`, typically a
+    /// closure around a client's `batch_execute`.
+    pub fn new(execute: E) -> Self {
+        PostgresSink { execute }
+    }
+}
+
+impl<E> fmt::Debug for PostgresSink<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PostgresSink").finish()
+    }
+}
+
+impl<E: Fn(&str) -> Result<()>> EventSink for PostgresSink<E> {
+    fn persist(&self, events: &[Event<'_>]) -> Result<()> {
+        let sql = insert(events)?;
+        if sql.is_empty() {
+            return Ok(());
+        }
+        (self.execute)(&sql)
+    }
+}
+
+/// The statement inserting `events`, skipping ids already stored. Empty
+/// when there are no events.
+pub fn insert(events: &[Event<'_>]) -> Result<String> {
+    if events.is_empty() {
+        return Ok(String::new());
+    }
+    let mut rows = Vec::with_capacity(events.len());
+    for event in events {
+        let pet_ids: Vec<String> = event
+            .pets()
+            .iter()
+            .map(|pet| pet.id().to_string())
+            .collect();
+        rows.push(format!(
+            "({}, {}, {}, to_timestamp({}), ARRAY[{}]::BIGINT[], {}::jsonb)",
+            event.id(),
+            event
+                .household_id()
+                .map_or_else(|| "NULL".to_string(), |id| id.to_string()),
+            event.kind().code(),
+            event.created_at().unix(),
+            pet_ids.join(", "),
+            literal(&serde_json::to_string(event)?)
+        ));
+    }
+    Ok(format!(
+        "INSERT INTO surepet_events (id, household_id, kind, created_at, pet_ids, json) VALUES\n{}\n\
+         ON CONFLICT DO NOTHING;",
+        rows.join(",\n")
+    ))
+}
+
+/// A string literal, doubling quotes so any JSON is safe to embed.
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_render_inserts() {
+        let events: Vec<Event<'static>> = serde_json::from_str(
+            r#"[{"id":7,"type":0,"created_at":"2019-06-01T07:00:00Z","household_id":3,
+                 "pets":[{"id":2,"name":"O'Malley"}]}]"#,
+        )
+        .unwrap();
+        let sql = insert(&events).unwrap();
+
+        assert!(sql.starts_with(
+            "INSERT INTO surepet_events (id, household_id, kind, created_at, pet_ids, json) VALUES\n\
+             (7, 3, 0, to_timestamp(1559372400), ARRAY[2]::BIGINT[], '{"
+        ));
+        assert!(sql.contains("O''Malley"));
+        assert!(sql.ends_with("\nON CONFLICT DO NOTHING;"));
+        assert_eq!("", insert(&[]).unwrap());
+    }
+}
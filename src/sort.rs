@@ -0,0 +1,336 @@
+//! Sorting the lists the API returns.
+//!
+//! The list endpoints take `sort` and `order` query parameters, but not all
+//! of them honour them, and some only for some fields. A
+//! [`Listing`](struct.Listing.html), from the `query` method of pets,
+//! devices, notifications, photos and tags, sends them and then sorts what
+//! comes back as well, so the order asked for is the order returned
+//! whether or not the server obliged.
+use crate::client::SurePet;
+use crate::error::Result;
+use crate::time::Timestamp;
+use crate::transport::Transport;
+use serde::de::DeserializeOwned;
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// What to sort by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Field {
+    /// The id, which for most lists follows creation.
+    Id,
+    /// The name.
+    Name,
+    /// When the item was created.
+    CreatedAt,
+    /// When the item last changed; for a device, its last activity.
+    UpdatedAt,
+}
+
+impl Field {
+    /// The field as the `sort` parameter names it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Name => "name",
+            Field::CreatedAt => "created_at",
+            Field::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which way to sort.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Order {
+    /// Smallest, earliest or alphabetically first first.
+    Ascending,
+    /// Largest, latest or alphabetically last first.
+    Descending,
+}
+
+impl Order {
+    /// The order as the `order` parameter names it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Order::Ascending => "asc",
+            Order::Descending => "desc",
+        }
+    }
+}
+
+/// A field and an order to sort by.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::sort::{Field, Order, Sort};
+/// let sort = Sort::by(Field::UpdatedAt).descending();
+/// assert_eq!(Order::Descending, sort.order());
+/// assert_eq!(
+///     vec![("sort", "updated_at".to_string()), ("order", "desc".to_string())],
+///     sort.query()
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Sort {
+    field: Field,
+    order: Order,
+}
+
+impl Sort {
+    /// Sorts by a field, ascending.
+    pub fn by(field: Field) -> Self {
+        Sort {
+            field,
+            order: Order::Ascending,
+        }
+    }
+
+    /// Sorts ascending.
+    pub fn ascending(mut self) -> Self {
+        self.order = Order::Ascending;
+        self
+    }
+
+    /// Sorts descending.
+    pub fn descending(mut self) -> Self {
+        self.order = Order::Descending;
+        self
+    }
+
+    /// The field sorted by.
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    /// The order sorted in.
+    pub fn order(&self) -> Order {
+        self.order
+    }
+
+    /// The query parameters asking the server for the order.
+    pub fn query(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("sort", self.field.as_str().to_string()),
+            ("order", self.order.as_str().to_string()),
+        ]
+    }
+
+    /// Sorts items in place. The sort is stable, so items the field does
+    /// not tell apart keep the order the server gave them; items without
+    /// the field come first ascending and last descending.
+    pub fn apply<S: Sortable>(&self, items: &mut [S]) {
+        items.sort_by(|a, b| {
+            let ordering = a.sort_key(self.field).cmp(&b.sort_key(self.field));
+            match self.order {
+                Order::Ascending => ordering,
+                Order::Descending => ordering.reverse(),
+            }
+        });
+    }
+}
+
+/// The value of a field, as compared when sorting.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Key<'a> {
+    /// The item lacks the field.
+    None,
+    /// An id or other number.
+    Number(i64),
+    /// A name, compared ignoring case.
+    Text(&'a str),
+    /// A time.
+    Time(Timestamp),
+}
+
+impl<'a> Key<'a> {
+    fn rank(&self) -> u8 {
+        match self {
+            Key::None => 0,
+            Key::Number(_) => 1,
+            Key::Text(_) => 2,
+            Key::Time(_) => 3,
+        }
+    }
+}
+
+impl<'a> From<Option<Timestamp>> for Key<'a> {
+    fn from(time: Option<Timestamp>) -> Self {
+        time.map_or(Key::None, Key::Time)
+    }
+}
+
+impl<'a> Ord for Key<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Number(a), Key::Number(b)) => a.cmp(b),
+            (Key::Text(a), Key::Text(b)) => a
+                .chars()
+                .flat_map(char::to_lowercase)
+                .cmp(b.chars().flat_map(char::to_lowercase))
+                .then_with(|| a.cmp(b)),
+            (Key::Time(a), Key::Time(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl<'a> PartialOrd for Key<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Something a list can be sorted by.
+pub trait Sortable {
+    /// The item's value for a field, `Key::None` when it has none.
+    fn sort_key(&self, field: Field) -> Key<'_>;
+}
+
+/// A list call with sorting, see the module documentation.
+/// # Examples
+///
+/// ```
+/// # use sure_petcare::client::SurePet;
+/// # use sure_petcare::transport::Transport;
+/// use sure_petcare::sort::Field;
+/// # #[cfg(feature = "pets")]
+/// # fn run<T: Transport>(client: &SurePet<T>) -> sure_petcare::error::Result<()> {
+/// let pets = client.pets().query().sort_by(Field::Name).fetch()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Listing<'c, T, R> {
+    client: &'c SurePet<T>,
+    path: &'static str,
+    query: Vec<(&'static str, String)>,
+    sort: Option<Sort>,
+    item: PhantomData<fn() -> R>,
+}
+
+impl<'c, T: Transport, R: DeserializeOwned + Sortable> Listing<'c, T, R> {
+    pub(crate) fn new(
+        client: &'c SurePet<T>,
+        path: &'static str,
+        query: Vec<(&'static str, String)>,
+    ) -> Self {
+        Listing {
+            client,
+            path,
+            query,
+            sort: None,
+            item: PhantomData,
+        }
+    }
+
+    /// Sorts by a field, ascending unless
+    /// [`descending`](#method.descending) follows.
+    pub fn sort_by(mut self, field: Field) -> Self {
+        self.sort = Some(Sort::by(field));
+        self
+    }
+
+    /// Sorts ascending, by id unless a field was given.
+    pub fn ascending(mut self) -> Self {
+        self.sort = Some(self.sort.unwrap_or(Sort::by(Field::Id)).ascending());
+        self
+    }
+
+    /// Sorts descending, by id unless a field was given.
+    pub fn descending(mut self) -> Self {
+        self.sort = Some(self.sort.unwrap_or(Sort::by(Field::Id)).descending());
+        self
+    }
+
+    /// Sorts as given.
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Fetches the list, in the order asked for.
+    pub fn fetch(&self) -> Result<Vec<R>> {
+        let mut query = self.query.clone();
+        if let Some(sort) = self.sort {
+            query.extend(sort.query());
+        }
+        let mut items: Vec<R> = self.client.get(self.path, &query)?;
+        if let Some(sort) = self.sort {
+            sort.apply(&mut items);
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::Tag;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_sort_lists_the_server_left_unsorted() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[
+                    {"id":5,"tag":"b","updated_at":"2019-06-01T07:00:00Z"},
+                    {"id":6,"tag":"a"},
+                    {"id":7,"tag":"c","updated_at":"2019-06-02T07:00:00Z"}]}"#,
+            )
+            .respond(200, r#"{"data":[{"id":9,"tag":"x"},{"id":8,"tag":"y"}]}"#);
+        let client = SurePet::new(&transport, "token");
+        let ids = |tags: Vec<Tag>| tags.iter().map(|tag| tag.id().get()).collect::<Vec<_>>();
+
+        let tags = client
+            .tags()
+            .query()
+            .sort_by(Field::UpdatedAt)
+            .descending()
+            .fetch()
+            .unwrap();
+        assert_eq!(vec![7, 5, 6], ids(tags));
+        assert_eq!(
+            vec![8, 9],
+            ids(client.tags().query().ascending().fetch().unwrap())
+        );
+
+        let requests = transport.requests();
+        assert_eq!(
+            "https://app.api.surehub.io/api/tag?sort=updated_at&order=desc",
+            requests[0].url
+        );
+        assert_eq!(
+            "https://app.api.surehub.io/api/tag?sort=id&order=asc",
+            requests[1].url
+        );
+    }
+
+    #[test]
+    fn should_compare_names_ignoring_case() {
+        let mut names = vec![
+            Key::Text("milo"),
+            Key::None,
+            Key::Text("Luna"),
+            Key::Text("luna"),
+        ];
+        names.sort();
+        assert_eq!(
+            vec![
+                Key::None,
+                Key::Text("Luna"),
+                Key::Text("luna"),
+                Key::Text("milo")
+            ],
+            names
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! The start state: everything on the account in one call.
+//!
+//! `/api/me/start` is what the app loads when it opens. It carries the
+//! households, pets and devices together with their current positions and
+//! status, see [`SurePet::start`](../client/struct.SurePet.html#method.start).
+//! It is the cheapest way to get a snapshot of a household.
+use crate::device::{Device, Product};
+use crate::fold::NameMatch;
+use crate::id::{DeviceId, HouseholdId, PetId};
+use crate::pet::{Location, Pet};
+use crate::time::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// A household sharing pets and devices.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Household<'a> {
+    pub(crate) id: HouseholdId,
+    pub(crate) name: Cow<'a, str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) timezone: Option<HouseholdTimeZone<'a>>,
+}
+
+/// The time zone set on a household in the app.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct HouseholdTimeZone<'a> {
+    pub(crate) timezone: Cow<'a, str>,
+}
+
+impl<'a> Household<'a> {
+    /// The household id.
+    pub fn id(&self) -> HouseholdId {
+        self.id
+    }
+
+    /// The name given to the household in the app.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The IANA name of the household's time zone, such as
+    /// `Europe/London`, which curfew times are local to. Load its rules
+    /// with [`Zone::named`](../zone/struct.Zone.html#method.named).
+    pub fn time_zone(&self) -> Option<&str> {
+        self.timezone.as_ref().map(|zone| zone.timezone.as_ref())
+    }
+}
+
+/// A snapshot of the whole account.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct State<'a> {
+    #[serde(default)]
+    pub(crate) households: Vec<Household<'a>>,
+    #[serde(default)]
+    pub(crate) pets: Vec<Pet<'a>>,
+    #[serde(default)]
+    pub(crate) devices: Vec<Device<'a>>,
+}
+
+impl<'a> State<'a> {
+    /// The households on the account.
+    pub fn households(&self) -> &[Household<'a>] {
+        &self.households
+    }
+
+    /// Every pet across the households.
+    pub fn pets(&self) -> &[Pet<'a>] {
+        &self.pets
+    }
+
+    /// Every device across the households.
+    pub fn devices(&self) -> &[Device<'a>] {
+        &self.devices
+    }
+
+    /// A household together with its pets and devices.
+    pub fn household(&self, household_id: HouseholdId) -> Option<HouseholdView<'_, 'a>> {
+        let household = self.households.iter().find(|h| h.id() == household_id)?;
+        Some(HouseholdView {
+            household,
+            state: self,
+        })
+    }
+
+    /// Looks a pet up by name in any household, ignoring case and
+    /// surrounding whitespace and however its accents were typed.
+    pub fn pet_by_name(&self, name: &str) -> Option<PetId> {
+        self.pet_by_name_with(name, NameMatch::IgnoreCase)
+    }
+
+    /// Looks a pet up by name in any household, compared as `matching`
+    /// says, so `NameMatch::IgnoreAccents` finds Zoé from `zoe`.
+    pub fn pet_by_name_with(&self, name: &str, matching: NameMatch) -> Option<PetId> {
+        by_name(&self.pets, name, matching, Pet::name).map(Pet::id)
+    }
+
+    /// Looks a device up by name in any household, like
+    /// [`pet_by_name`](#method.pet_by_name).
+    pub fn device_by_name(&self, name: &str) -> Option<DeviceId> {
+        self.device_by_name_with(name, NameMatch::IgnoreCase)
+    }
+
+    /// Looks a device up by name in any household, like
+    /// [`pet_by_name_with`](#method.pet_by_name_with).
+    pub fn device_by_name_with(&self, name: &str, matching: NameMatch) -> Option<DeviceId> {
+        by_name(&self.devices, name, matching, Device::name).map(Device::id)
+    }
+
+    /// The pet with the given id.
+    pub fn pet(&self, pet_id: PetId) -> Option<&Pet<'a>> {
+        self.pets.iter().find(|pet| pet.id() == pet_id)
+    }
+
+    /// The device with the given id.
+    pub fn device(&self, device_id: DeviceId) -> Option<&Device<'a>> {
+        self.devices.iter().find(|device| device.id() == device_id)
+    }
+
+    /// The pets last seen going in.
+    pub fn pets_inside(&self) -> impl Iterator<Item = &Pet<'a>> {
+        self.pets_at(Location::Inside)
+    }
+
+    /// The pets last seen going out.
+    pub fn pets_outside(&self) -> impl Iterator<Item = &Pet<'a>> {
+        self.pets_at(Location::Outside)
+    }
+
+    /// The pets of one household.
+    pub fn pets_in(&self, household_id: HouseholdId) -> impl Iterator<Item = &Pet<'a>> {
+        self.pets
+            .iter()
+            .filter(move |pet| pet.household_id() == Some(household_id))
+    }
+
+    /// The devices of one kind, such as every feeder.
+    pub fn devices_of(&self, product: Product) -> impl Iterator<Item = &Device<'a>> {
+        self.devices
+            .iter()
+            .filter(move |device| device.product() == product)
+    }
+
+    /// The cat flaps and pet doors.
+    pub fn flaps(&self) -> impl Iterator<Item = &Device<'a>> {
+        self.devices
+            .iter()
+            .filter(|device| matches!(device.product(), Product::CatFlap | Product::PetDoor))
+    }
+
+    /// The devices the hub reports as offline. Devices that do not report
+    /// whether they are online are left out.
+    pub fn offline_devices(&self) -> impl Iterator<Item = &Device<'a>> {
+        self.devices
+            .iter()
+            .filter(|device| device.status().online() == Some(false))
+    }
+
+    fn pets_at(&self, location: Location) -> impl Iterator<Item = &Pet<'a>> {
+        self.pets.iter().filter(move |pet| {
+            pet.position()
+                .is_some_and(|position| position.location() == location)
+        })
+    }
+}
+
+/// A household and the parts of the start state that belong to it,
+/// obtained from [`State::household`](struct.State.html#method.household).
+#[derive(Clone, Copy, Debug)]
+pub struct HouseholdView<'s, 'a> {
+    household: &'s Household<'a>,
+    state: &'s State<'a>,
+}
+
+impl<'s, 'a> HouseholdView<'s, 'a> {
+    /// The household id.
+    pub fn id(&self) -> HouseholdId {
+        self.household.id()
+    }
+
+    /// The name given to the household in the app.
+    pub fn name(&self) -> &'s str {
+        &self.household.name
+    }
+
+    /// The pets of the household.
+    pub fn pets(&self) -> impl Iterator<Item = &'s Pet<'a>> {
+        let id = self.id();
+        self.state
+            .pets
+            .iter()
+            .filter(move |pet| pet.household_id() == Some(id))
+    }
+
+    /// The devices of the household.
+    pub fn devices(&self) -> impl Iterator<Item = &'s Device<'a>> {
+        let id = self.id();
+        self.state
+            .devices
+            .iter()
+            .filter(move |device| device.household_id() == Some(id))
+    }
+
+    /// Whether no pet of the household was last seen going out. Pets whose
+    /// position is not known, such as those that never use a flap, are not
+    /// counted.
+    pub fn all_pets_inside(&self) -> bool {
+        !self.pets().any(|pet| {
+            pet.position()
+                .is_some_and(|position| position.location() == Location::Outside)
+        })
+    }
+
+    /// The pets of the household last seen going out, with how long each
+    /// has been out at `now`.
+    pub fn pets_outside(&self, now: Timestamp) -> impl Iterator<Item = (&'s Pet<'a>, Duration)> {
+        self.pets().filter_map(move |pet| {
+            let position = pet.position()?;
+            if position.location() != Location::Outside {
+                return None;
+            }
+            Some((pet, now.elapsed_since(position.since())))
+        })
+    }
+
+    /// Looks a pet of the household up by name, ignoring case and
+    /// surrounding whitespace and however its accents were typed.
+    pub fn pet_by_name(&self, name: &str) -> Option<PetId> {
+        self.pet_by_name_with(name, NameMatch::IgnoreCase)
+    }
+
+    /// Looks a pet of the household up by name, compared as `matching`
+    /// says.
+    pub fn pet_by_name_with(&self, name: &str, matching: NameMatch) -> Option<PetId> {
+        by_name(self.pets(), name, matching, Pet::name).map(Pet::id)
+    }
+
+    /// Looks a device of the household up by name, like
+    /// [`pet_by_name`](#method.pet_by_name).
+    pub fn device_by_name(&self, name: &str) -> Option<DeviceId> {
+        self.device_by_name_with(name, NameMatch::IgnoreCase)
+    }
+
+    /// Looks a device of the household up by name, like
+    /// [`pet_by_name_with`](#method.pet_by_name_with).
+    pub fn device_by_name_with(&self, name: &str, matching: NameMatch) -> Option<DeviceId> {
+        by_name(self.devices(), name, matching, Device::name).map(Device::id)
+    }
+}
+
+fn by_name<'s, T, I, F>(items: I, name: &str, matching: NameMatch, name_of: F) -> Option<&'s T>
+where
+    I: IntoIterator<Item = &'s T>,
+    F: Fn(&'s T) -> &'s str,
+{
+    let name = matching.key(name);
+    items
+        .into_iter()
+        .find(|item| matching.key(name_of(item)) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_fetch_start_state() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":{"user":{"id":7},
+                "households":[{"id":1,"name":"Home","timezone":{"id":374,"timezone":"Europe/London"}}],
+                "pets":[{"id":2,"name":"Milo","household_id":1,
+                         "position":{"device_id":10,"where":2,"since":"2019-06-01T05:00:00+00:00"},
+                         "status":{"feeding":{"at":"2019-06-01T04:00:00+00:00","change":[-4.5]}}}],
+                "devices":[{"id":10,"name":"Back door","product_id":6,"household_id":1}]}}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let state = client.start().unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/me/start",
+            transport.requests()[0].url
+        );
+        assert_eq!("Home", state.households()[0].name());
+        assert_eq!(Some("Europe/London"), state.households()[0].time_zone());
+        let milo = &state.pets()[0];
+        assert_eq!(Location::Outside, milo.position().unwrap().location());
+        assert_eq!(&[-4.5], milo.status().feeding().unwrap().change());
+        assert_eq!("Back door", state.devices()[0].name());
+    }
+
+    #[test]
+    fn should_filter_pets_and_devices() {
+        let state: State<'static> = serde_json::from_str(
+            r#"{"pets":[{"id":1,"name":"Milo","household_id":1,"position":{"where":1,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":2,"name":"Cleo","household_id":2,"position":{"where":2,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":3,"name":"Tom"}],
+                "devices":[{"id":10,"name":"Hub","product_id":1,"status":{"online":true}},
+                           {"id":11,"name":"Flap","product_id":6,"status":{"online":false}},
+                           {"id":12,"name":"Door","product_id":3}]}"#,
+        )
+        .unwrap();
+        let names = |pets: Vec<&Pet<'_>>| {
+            pets.iter()
+                .map(|pet| pet.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["Milo"], names(state.pets_inside().collect()));
+        assert_eq!(vec!["Cleo"], names(state.pets_outside().collect()));
+        assert_eq!(
+            vec!["Cleo"],
+            names(state.pets_in(HouseholdId::new(2)).collect())
+        );
+        assert_eq!(Some("Tom"), state.pet(PetId::new(3)).map(Pet::name));
+        assert_eq!(1, state.devices_of(Product::Hub).count());
+        assert_eq!(2, state.flaps().count());
+        assert_eq!(
+            vec![DeviceId::new(11)],
+            state.offline_devices().map(Device::id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_look_up_by_name() {
+        let state: State<'static> = serde_json::from_str(
+            r#"{"households":[{"id":1,"name":"Home"},{"id":2,"name":"Cottage"}],
+                "pets":[{"id":1,"name":"Milo","household_id":1},{"id":2,"name":"Cleo ","household_id":2},
+                         {"id":3,"name":"Zoé","household_id":2}],
+                "devices":[{"id":10,"name":"Kitchen Flap","product_id":6,"household_id":1}]}"#,
+        )
+        .unwrap();
+        let home = state.household(HouseholdId::new(1)).unwrap();
+
+        assert_eq!("Home", home.name());
+        assert_eq!(Some(PetId::new(1)), home.pet_by_name(" MILO"));
+        assert_eq!(None, home.pet_by_name("cleo"));
+        assert_eq!(Some(DeviceId::new(10)), home.device_by_name("kitchen flap"));
+        assert_eq!(Some(PetId::new(2)), state.pet_by_name("cleo"));
+        assert_eq!(Some(PetId::new(3)), state.pet_by_name("ZOE\u{301}"));
+        assert_eq!(None, state.pet_by_name("zoe"));
+        assert_eq!(
+            Some(PetId::new(3)),
+            state.pet_by_name_with("zoe", NameMatch::IgnoreAccents)
+        );
+        assert_eq!(None, state.pet_by_name_with("milo", NameMatch::Exact));
+        assert!(state.household(HouseholdId::new(3)).is_none());
+    }
+
+    #[test]
+    fn should_say_who_is_outside() {
+        let state: State<'static> = serde_json::from_str(
+            r#"{"households":[{"id":1,"name":"Home"},{"id":2,"name":"Cottage"}],
+                "pets":[{"id":1,"name":"Milo","household_id":1,"position":{"where":2,"since":"2019-06-01T05:00:00Z"}},
+                        {"id":2,"name":"Cleo","household_id":1,"position":{"where":1,"since":"2019-06-01T06:00:00Z"}},
+                        {"id":3,"name":"Tom","household_id":2},
+                        {"id":4,"name":"Rex","household_id":2,"position":{"where":1,"since":"2019-06-01T06:00:00Z"}}]}"#,
+        )
+        .unwrap();
+        let now = "2019-06-01T07:30:00Z".parse().unwrap();
+        let home = state.household(HouseholdId::new(1)).unwrap();
+
+        assert!(!home.all_pets_inside());
+        let outside: Vec<(&str, Duration)> = home
+            .pets_outside(now)
+            .map(|(pet, out)| (pet.name(), out))
+            .collect();
+        assert_eq!(vec![("Milo", Duration::from_secs(9000))], outside);
+        assert!(state
+            .household(HouseholdId::new(2))
+            .unwrap()
+            .all_pets_inside());
+    }
+}
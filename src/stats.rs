@@ -0,0 +1,235 @@
+//! Descriptive statistics over timeline events.
+//!
+//! Gives every consumer the same numbers for questions like "how long does a
+//! meal take" instead of each one doing its own arithmetic.
+use crate::report::DailyFeeding;
+use crate::time::Timestamp;
+use crate::timeline::{Event, EventKind, PET_VISIT};
+use serde::Serialize;
+
+/// Minimum, maximum, mean and median of a set of values.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    count: usize,
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+}
+
+impl Summary {
+    /// Summarises `values`, returning `None` when there are none.
+    pub fn of(values: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2.0
+        } else {
+            sorted[middle]
+        };
+        Some(Summary {
+            count: sorted.len(),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median,
+        })
+    }
+
+    /// The number of values.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The smallest value.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// The largest value.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// The arithmetic mean.
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The median, averaging the middle pair for an even count.
+    pub fn median(&self) -> f64 {
+        self.median
+    }
+}
+
+/// Statistics about the feeding and drinking visits in a set of events.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    meal_duration: Option<Summary>,
+    meal_size: Option<Summary>,
+    meal_gap: Option<Summary>,
+    meals_per_day: f64,
+    drink_duration: Option<Summary>,
+    drink_size: Option<Summary>,
+    drinks_per_day: f64,
+}
+
+impl Stats {
+    /// Works out the statistics for every pet visit in `events`; filter the
+    /// events first to restrict them to one pet.
+    ///
+    /// Per day rates are over the UTC days from the first to the last event.
+    pub fn from_events(events: &[Event<'_>]) -> Stats {
+        let meals = visits(events, EventKind::Feeding);
+        let drinks = visits(events, EventKind::Drinking);
+
+        let days = match (
+            events.iter().map(Event::created_at).min(),
+            events.iter().map(Event::created_at).max(),
+        ) {
+            (Some(first), Some(last)) => {
+                (last.date().midnight().unix() - first.date().midnight().unix()) / 86_400 + 1
+            }
+            _ => 0,
+        };
+        let per_day = |count: usize| {
+            if days == 0 {
+                0.0
+            } else {
+                count as f64 / days as f64
+            }
+        };
+
+        let mut meal_times: Vec<Timestamp> = meals.iter().map(|visit| visit.at).collect();
+        meal_times.sort();
+        let gaps: Vec<f64> = meal_times
+            .windows(2)
+            .map(|pair| pair[1].seconds_since(pair[0]) as f64)
+            .collect();
+
+        Stats {
+            meal_duration: Summary::of(&meals.iter().map(|v| v.duration).collect::<Vec<_>>()),
+            meal_size: Summary::of(&meals.iter().map(|v| v.consumed).collect::<Vec<_>>()),
+            meal_gap: Summary::of(&gaps),
+            meals_per_day: per_day(meals.len()),
+            drink_duration: Summary::of(&drinks.iter().map(|v| v.duration).collect::<Vec<_>>()),
+            drink_size: Summary::of(&drinks.iter().map(|v| v.consumed).collect::<Vec<_>>()),
+            drinks_per_day: per_day(drinks.len()),
+        }
+    }
+
+    /// Seconds spent per meal.
+    pub fn meal_duration(&self) -> Option<Summary> {
+        self.meal_duration
+    }
+
+    /// Grams eaten per meal.
+    pub fn meal_size(&self) -> Option<Summary> {
+        self.meal_size
+    }
+
+    /// Seconds between the start of one meal and the next.
+    pub fn meal_gap(&self) -> Option<Summary> {
+        self.meal_gap
+    }
+
+    /// The average number of meals per day.
+    pub fn meals_per_day(&self) -> f64 {
+        self.meals_per_day
+    }
+
+    /// Seconds spent per drink.
+    pub fn drink_duration(&self) -> Option<Summary> {
+        self.drink_duration
+    }
+
+    /// Millilitres drunk per visit.
+    pub fn drink_size(&self) -> Option<Summary> {
+        self.drink_size
+    }
+
+    /// The average number of drinks per day.
+    pub fn drinks_per_day(&self) -> f64 {
+        self.drinks_per_day
+    }
+}
+
+/// Summarises grams eaten per day from daily feeding totals.
+pub fn daily_consumption(daily: &[DailyFeeding]) -> Option<Summary> {
+    Summary::of(&daily.iter().map(DailyFeeding::consumed).collect::<Vec<_>>())
+}
+
+struct Visit {
+    at: Timestamp,
+    duration: f64,
+    consumed: f64,
+}
+
+fn visits(events: &[Event<'_>], kind: EventKind) -> Vec<Visit> {
+    events
+        .iter()
+        .filter(|event| event.kind() == kind)
+        .flat_map(|event| {
+            event
+                .weights()
+                .iter()
+                .filter(|entry| entry.context() == PET_VISIT)
+                .map(move |entry| Visit {
+                    at: Timestamp::from_unix(event.created_at().unix() - entry.duration()),
+                    duration: entry.duration() as f64,
+                    consumed: entry
+                        .frames()
+                        .iter()
+                        .filter(|frame| frame.change() < 0.0)
+                        .fold(0.0, |total, frame| total - frame.change()),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_summarise_values() {
+        let summary = Summary::of(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(
+            (4, 1.0, 4.0),
+            (summary.count(), summary.min(), summary.max())
+        );
+        assert_eq!((2.5, 2.5), (summary.mean(), summary.median()));
+        assert_eq!(3.0, Summary::of(&[3.0, 1.0, 9.0]).unwrap().median());
+        assert_eq!(None, Summary::of(&[]));
+    }
+
+    #[test]
+    fn should_work_out_meal_stats() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":22,"created_at":"2019-06-01T07:01:00Z",
+             "weights":[{"duration":60,"context":1,"frames":[{"current_weight":30,"change":-10}]}]},
+            {"id":2,"type":22,"created_at":"2019-06-01T09:00:00Z",
+             "weights":[{"duration":0,"context":2,"frames":[{"current_weight":80,"change":50}]}]},
+            {"id":3,"type":22,"created_at":"2019-06-01T19:03:00Z",
+             "weights":[{"duration":180,"context":1,"frames":[{"current_weight":10,"change":-20}]}]},
+            {"id":4,"type":29,"created_at":"2019-06-02T12:00:30Z",
+             "weights":[{"duration":30,"context":1,"frames":[{"current_weight":300,"change":-15}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let stats = Stats::from_events(&events);
+
+        assert_eq!(Some(120.0), stats.meal_duration().map(|s| s.mean()));
+        assert_eq!(Some(20.0), stats.meal_size().map(|s| s.max()));
+        assert_eq!(Some(12.0 * 3600.0), stats.meal_gap().map(|s| s.median()));
+        assert_eq!(1.0, stats.meals_per_day());
+        assert_eq!(Some(15.0), stats.drink_size().map(|s| s.min()));
+        assert_eq!(0.5, stats.drinks_per_day());
+    }
+}
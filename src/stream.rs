@@ -0,0 +1,116 @@
+//! Visiting the items of a list response one at a time.
+//!
+//! Parsing a page into a `Vec` keeps every event in memory until the page
+//! has been handled. [`for_each`](fn.for_each.html) hands each item to a
+//! closure as soon as it is decoded and drops it afterwards, so only the
+//! response body and a single item are held at once.
+use crate::error::Result;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, IgnoredAny, SeqAccess, Visitor};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Parses a list response, calling `f` with each item in order, and returns
+/// how many there were. The `data` envelope of API responses is looked
+/// through, so whole bodies can be passed as well as bare arrays. Items may
+/// borrow from `body`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::timeline::Event;
+/// let body = br#"{"data":[{"id":2,"type":0,"created_at":"2019-06-01T05:00:00Z"},
+///                         {"id":1,"type":0,"created_at":"2019-06-01T04:00:00Z"}]}"#;
+/// let mut ids = Vec::new();
+/// let count = sure_petcare::stream::for_each(body, |event: Event<'_>| ids.push(event.id()))?;
+/// assert_eq!((2, vec![2, 1]), (count, ids));
+/// # Ok::<(), sure_petcare::error::Error>(())
+/// ```
+pub fn for_each<'de, T, F>(body: &'de [u8], f: F) -> Result<usize>
+where
+    T: Deserialize<'de>,
+    F: FnMut(T),
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let count = deserializer.deserialize_any(ListVisitor {
+        f,
+        item: PhantomData,
+    })?;
+    deserializer.end()?;
+    Ok(count)
+}
+
+/// Visits either a bare array or an object with the array in `data`.
+struct ListVisitor<T, F> {
+    f: F,
+    item: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: Deserialize<'de>, F: FnMut(T)> Visitor<'de> for ListVisitor<T, F> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a list or an object with a data list")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> std::result::Result<usize, A::Error> {
+        let mut count = 0;
+        while let Some(item) = seq.next_element::<T>()? {
+            (self.f)(item);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<usize, A::Error> {
+        let mut visitor = Some(self);
+        let mut count = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match (key.as_str(), visitor.take()) {
+                ("data", Some(visitor)) => count = Some(map.next_value_seed(Items(visitor))?),
+                (_, rest) => {
+                    visitor = rest;
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        count.ok_or_else(|| de::Error::missing_field("data"))
+    }
+}
+
+/// Parses the `data` array of an envelope with the visitor.
+struct Items<T, F>(ListVisitor<T, F>);
+
+impl<'de, T: Deserialize<'de>, F: FnMut(T)> DeserializeSeed<'de> for Items<T, F> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<usize, D::Error> {
+        deserializer.deserialize_seq(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::Event;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_visit_items_without_collecting_them() {
+        let body = br#"{"meta":{"page":1},"data":[
+            {"id":3,"type":0,"created_at":"2019-06-01T06:00:00Z","pets":[{"id":2,"name":"Milo"}]},
+            {"id":2,"type":0,"created_at":"2019-06-01T05:00:00Z"}
+        ]}"#;
+        let mut names = Vec::new();
+        let count = for_each(body, |event: Event<'_>| {
+            names.extend(event.pets().iter().map(|pet| pet.name().to_string()))
+        })
+        .unwrap();
+
+        assert_eq!(2, count);
+        assert_eq!(vec!["Milo".to_string()], names);
+        assert_eq!(1, for_each(b"[7]", |_: i64| ()).unwrap());
+        assert!(for_each(br#"{"error":"nope"}"#, |_: i64| ()).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Locking flaps at dusk and unlocking them at dawn.
+//!
+//! Curfews are fixed times, but dusk moves by hours over a year.
+//! [`Coordinates`](struct.Coordinates.html) works out sunrise and sunset for
+//! a household's location without any network lookup, using the NOAA
+//! approximation, which is within a minute or two away from the poles. A
+//! [`SunLock`](struct.SunLock.html) turns those into a schedule of
+//! [`lock_in`](../device/struct.DeviceHandle.html#method.lock_in) at dusk and
+//! [`unlock`](../device/struct.DeviceHandle.html#method.unlock) at dawn, each
+//! moved by an offset, and can run it against a flap.
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "devices")]
+use crate::device::DeviceHandle;
+#[cfg(feature = "devices")]
+use crate::error::Result;
+use crate::holiday::Override;
+use crate::time::{Date, Timestamp};
+#[cfg(feature = "devices")]
+use crate::transport::Transport;
+use std::fmt;
+use std::sync::Arc;
+#[cfg(feature = "devices")]
+use std::thread;
+#[cfg(feature = "devices")]
+use std::time::Duration;
+
+/// The Julian day of 1970-01-01 00:00 UTC.
+const UNIX_EPOCH_JULIAN: f64 = 2_440_587.5;
+/// The Julian day of 2000-01-01 12:00 UTC.
+const J2000: f64 = 2_451_545.0;
+/// How far below the horizon the sun's centre is at sunrise and sunset,
+/// allowing for refraction and the size of the disc.
+const HORIZON: f64 = -0.833;
+/// The tilt of the Earth's axis.
+const OBLIQUITY: f64 = 23.4397;
+
+/// A place on the Earth, in degrees north and east.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coordinates {
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Coordinates {
+    /// The coordinates of a place, `None` if the latitude is outside
+    /// -90..=90 or the longitude outside -180..=180. West and south are
+    /// negative.
+    pub fn new(latitude: f64, longitude: f64) -> Option<Self> {
+        if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+            Some(Coordinates {
+                latitude,
+                longitude,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Degrees north of the equator.
+    pub fn latitude(self) -> f64 {
+        self.latitude
+    }
+
+    /// Degrees east of Greenwich.
+    pub fn longitude(self) -> f64 {
+        self.longitude
+    }
+
+    /// When the sun rises on a date, `None` when it stays up or down all
+    /// day.
+    /// # Examples
+    ///
+    /// ```
+    /// use sure_petcare::sun::Coordinates;
+    /// use sure_petcare::time::Date;
+    /// let london = Coordinates::new(51.5, -0.13).unwrap();
+    /// let sunrise = london.sunrise(Date::new(2019, 6, 21).unwrap()).unwrap();
+    /// assert!(sunrise.to_string().starts_with("2019-06-21T03:4"));
+    /// ```
+    pub fn sunrise(self, date: Date) -> Option<Timestamp> {
+        self.sun_times(date).map(|(rise, _)| rise)
+    }
+
+    /// When the sun sets on a date, `None` when it stays up or down all
+    /// day.
+    pub fn sunset(self, date: Date) -> Option<Timestamp> {
+        self.sun_times(date).map(|(_, set)| set)
+    }
+
+    fn sun_times(self, date: Date) -> Option<(Timestamp, Timestamp)> {
+        let noon = (date.midnight().unix() + 43_200) as f64 / 86_400.0 + UNIX_EPOCH_JULIAN;
+        let mean_noon = (noon - J2000).round() - self.longitude / 360.0;
+        let anomaly = (357.5291 + 0.985_600_28 * mean_noon).rem_euclid(360.0);
+        let m = anomaly.to_radians();
+        let centre = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+        let ecliptic = (anomaly + centre + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+        let transit = J2000 + mean_noon + 0.0053 * m.sin() - 0.0069 * (2.0 * ecliptic).sin();
+        let declination = (ecliptic.sin() * OBLIQUITY.to_radians().sin()).asin();
+        let latitude = self.latitude.to_radians();
+        let cos_hour_angle = (HORIZON.to_radians().sin() - latitude.sin() * declination.sin())
+            / (latitude.cos() * declination.cos());
+        if !(-1.0..=1.0).contains(&cos_hour_angle) {
+            return None;
+        }
+        let half_day = cos_hour_angle.acos().to_degrees() / 360.0;
+        let at = |julian: f64| {
+            Timestamp::from_unix(((julian - UNIX_EPOCH_JULIAN) * 86_400.0).round() as i64)
+        };
+        Some((at(transit - half_day), at(transit + half_day)))
+    }
+}
+
+impl fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.latitude, self.longitude)
+    }
+}
+
+/// What a [`SunLock`](struct.SunLock.html) does to the flap.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SunAction {
+    /// Lets pets come in but not go out, at dusk.
+    LockIn,
+    /// Lets pets through both ways, at dawn.
+    Unlock,
+}
+
+impl fmt::Display for SunAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SunAction::LockIn => "lock in",
+            SunAction::Unlock => "unlock",
+        })
+    }
+}
+
+/// Keeps pets in from dusk until dawn, see the module documentation.
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "devices")]
+/// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::sun::{Coordinates, SunLock};
+/// let flap = client.devices().list()?.remove(0);
+/// SunLock::new(Coordinates::new(51.5, -0.13).unwrap())
+///     .with_dusk_offset(-30)
+///     .with_dawn_offset(60)
+///     .run(&client.devices().handle(&flap))
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SunLock {
+    coordinates: Coordinates,
+    dusk_offset: i64,
+    dawn_offset: i64,
+    hold: Option<Override<SunAction>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SunLock {
+    /// Locks in at sunset and unlocks at sunrise at the given place.
+    pub fn new(coordinates: Coordinates) -> Self {
+        SunLock {
+            coordinates,
+            dusk_offset: 0,
+            dawn_offset: 0,
+            hold: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Moves locking by this many minutes after sunset, or before it when
+    /// negative.
+    pub fn with_dusk_offset(mut self, minutes: i64) -> Self {
+        self.dusk_offset = minutes;
+        self
+    }
+
+    /// Moves unlocking by this many minutes after sunrise, or before it
+    /// when negative.
+    pub fn with_dawn_offset(mut self, minutes: i64) -> Self {
+        self.dawn_offset = minutes;
+        self
+    }
+
+    /// Sets the clock [`run`](#method.run) schedules from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Holds the action set on `hold`, while it is set, instead of
+    /// following the sun. [`run`](#method.run) applies a change to the
+    /// override as soon as it is made, and the schedule again once it
+    /// expires.
+    pub fn with_override(mut self, hold: Override<SunAction>) -> Self {
+        self.hold = Some(hold);
+        self
+    }
+
+    /// The actions due on a date, in the order they happen. Days without a
+    /// sunrise or sunset have fewer.
+    pub fn actions_on(&self, date: Date) -> Vec<(Timestamp, SunAction)> {
+        let mut actions = Vec::new();
+        if let Some(sunrise) = self.coordinates.sunrise(date) {
+            let at = Timestamp::from_unix(sunrise.unix() + self.dawn_offset * 60);
+            actions.push((at, SunAction::Unlock));
+        }
+        if let Some(sunset) = self.coordinates.sunset(date) {
+            let at = Timestamp::from_unix(sunset.unix() + self.dusk_offset * 60);
+            actions.push((at, SunAction::LockIn));
+        }
+        actions.sort_by_key(|(at, _)| *at);
+        actions
+    }
+
+    /// The first action due after an instant, looking up to a year ahead
+    /// to get through polar days and nights.
+    pub fn next_after(&self, after: Timestamp) -> Option<(Timestamp, SunAction)> {
+        let today = after.date();
+        let mut next: Option<(Timestamp, SunAction)> = None;
+        for days in -1..=366 {
+            let due = self
+                .actions_on(today.add_days(days))
+                .into_iter()
+                .filter(|(at, _)| *at > after);
+            for action in due {
+                if next.is_none_or(|(at, _)| action.0 < at) {
+                    next = Some(action);
+                }
+            }
+            // Offsets can move an action onto the day either side.
+            if next.is_some() && days >= 1 {
+                break;
+            }
+        }
+        next
+    }
+
+    /// The action that should be in force at an instant, the last one
+    /// due before it.
+    pub fn current_at(&self, at: Timestamp) -> Option<SunAction> {
+        let today = at.date();
+        (-366..=1)
+            .rev()
+            .flat_map(|days| self.actions_on(today.add_days(days)).into_iter().rev())
+            .find(|(due, _)| *due <= at)
+            .map(|(_, action)| action)
+    }
+
+    /// Sets the flap to the current action, then sleeps until each next
+    /// one and applies it, for as long as the process runs. Returns when a
+    /// change fails, or at once if the sun neither rises nor sets in the
+    /// coming year.
+    #[cfg(feature = "devices")]
+    pub fn run<T: Transport>(&self, flap: &DeviceHandle<'_, T>) -> Result<()> {
+        while let Some(wait) = self.step(flap)? {
+            match &self.hold {
+                Some(hold) => hold.wait(wait),
+                None => thread::sleep(wait),
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the flap to the current action, or the one the override holds,
+    /// and returns how long until the next one is due or the override
+    /// expires, `None` if the sun neither rises nor sets in the coming year.
+    #[cfg(feature = "devices")]
+    pub fn step<T: Transport>(&self, flap: &DeviceHandle<'_, T>) -> Result<Option<Duration>> {
+        let now = self.clock.now();
+        if let Some((action, until)) = self.hold.as_ref().and_then(|hold| hold.active_at(now)) {
+            apply(flap, action)?;
+            return Ok(Some(until.elapsed_since(now)));
+        }
+        if let Some(action) = self.current_at(now) {
+            apply(flap, action)?;
+        }
+        Ok(self.next_after(now).map(|(at, _)| at.elapsed_since(now)))
+    }
+}
+
+#[cfg(feature = "devices")]
+fn apply<T: Transport>(flap: &DeviceHandle<'_, T>, action: SunAction) -> Result<()> {
+    match action {
+        SunAction::LockIn => flap.lock_in(),
+        SunAction::Unlock => flap.unlock(),
+    }
+}
+
+impl fmt::Debug for SunLock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SunLock")
+            .field("coordinates", &self.coordinates)
+            .field("dusk_offset", &self.dusk_offset)
+            .field("dawn_offset", &self.dawn_offset)
+            .field("hold", &self.hold)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_schedule_around_sunrise_and_sunset() {
+        let london = Coordinates::new(51.5074, -0.1278).unwrap();
+        let midsummer = Date::new(2019, 6, 21).unwrap();
+        let near = |at: Timestamp, expected: &str| {
+            let expected: Timestamp = expected.parse().unwrap();
+            assert!(
+                at.seconds_since(expected).abs() <= 120,
+                "{} is not {}",
+                at,
+                expected
+            );
+        };
+        near(london.sunrise(midsummer).unwrap(), "2019-06-21T03:43:00Z");
+        near(london.sunset(midsummer).unwrap(), "2019-06-21T20:21:00Z");
+        let sydney = Coordinates::new(-33.87, 151.21).unwrap();
+        near(sydney.sunrise(midsummer).unwrap(), "2019-06-20T20:59:00Z");
+        let tromso = Coordinates::new(69.65, 18.96).unwrap();
+        assert_eq!(None, tromso.sunset(midsummer));
+        assert_eq!(None, Coordinates::new(91.0, 0.0));
+
+        let lock = SunLock::new(london)
+            .with_dusk_offset(-30)
+            .with_dawn_offset(60);
+        let evening: Timestamp = "2019-06-21T19:00:00Z".parse().unwrap();
+        let (at, action) = lock.next_after(evening).unwrap();
+        assert_eq!(SunAction::LockIn, action);
+        near(at, "2019-06-21T19:51:00Z");
+        let (at, action) = lock.next_after(at).unwrap();
+        assert_eq!(SunAction::Unlock, action);
+        near(at, "2019-06-22T04:43:00Z");
+        assert_eq!(Some(SunAction::Unlock), lock.current_at(evening));
+        assert_eq!(
+            Some(SunAction::LockIn),
+            lock.current_at("2019-06-22T01:00:00Z".parse().unwrap())
+        );
+
+        let polar = SunLock::new(tromso);
+        let (at, _) = polar.next_after(evening).unwrap();
+        assert_eq!(7, at.date().month());
+    }
+}
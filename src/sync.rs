@@ -0,0 +1,366 @@
+//! Keeping a start state up to date from the timeline.
+//!
+//! Polling `/api/me/start` fetches every household, pet and device each
+//! time. [`StateSync`](struct.StateSync.html) fetches the start state once
+//! and then only the timeline events newer than the last one it saw, moving
+//! pets and recording their meals and drinks as the events come in. Device
+//! status such as battery and signal is only refreshed by fetching the start
+//! state again.
+use crate::client::SurePet;
+use crate::error::Result;
+use crate::fanout;
+use crate::id::{HouseholdId, PetId, TagId};
+use crate::pet::{Location, Position, Visit};
+use crate::state::State;
+use crate::timeline::{Direction, Event, EventKind, PET_VISIT};
+use crate::transport::Transport;
+use std::collections::BTreeMap;
+
+/// A change made to the state by a timeline event.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Change {
+    /// A pet went through a flap.
+    Moved {
+        /// The pet.
+        pet_id: PetId,
+        /// Where it is now.
+        position: Position,
+    },
+    /// A pet ate from a feeder.
+    Ate {
+        /// The pet.
+        pet_id: PetId,
+        /// The meal.
+        visit: Visit,
+    },
+    /// A pet drank from a water dispenser.
+    Drank {
+        /// The pet.
+        pet_id: PetId,
+        /// The drink.
+        visit: Visit,
+    },
+}
+
+impl Change {
+    /// The pet the change is about.
+    pub fn pet_id(&self) -> PetId {
+        match self {
+            Change::Moved { pet_id, .. }
+            | Change::Ate { pet_id, .. }
+            | Change::Drank { pet_id, .. } => *pet_id,
+        }
+    }
+}
+
+/// A start state kept current with timeline events, see the module
+/// documentation.
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport + Send + Sync>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::sync::StateSync;
+/// let mut sync = StateSync::start(&client)?;
+/// loop {
+///     for change in sync.sync(&client)? {
+///         println!("{:?}", change);
+///     }
+///     println!("{} pets inside", sync.state().pets_inside().count());
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct StateSync {
+    state: State<'static>,
+    cursors: BTreeMap<HouseholdId, i64>,
+    concurrency: usize,
+}
+
+impl StateSync {
+    /// Fetches the start state and the newest event of every household.
+    pub fn start<T: Transport + Send + Sync>(client: &SurePet<T>) -> Result<Self> {
+        let mut sync = StateSync::new(client.start()?);
+        sync.sync(client)?;
+        Ok(sync)
+    }
+
+    /// Keeps a state fetched earlier up to date. The first
+    /// [`sync`](#method.sync) of a household without a cursor only records
+    /// its newest event, as the state already reflects it.
+    pub fn new(state: State<'static>) -> Self {
+        StateSync {
+            state,
+            cursors: BTreeMap::new(),
+            concurrency: 4,
+        }
+    }
+
+    /// Sets how many household timelines are fetched at once, four by
+    /// default.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Resumes a household from the id of the last event applied, for
+    /// instance one saved before a restart.
+    pub fn with_cursor(mut self, household_id: HouseholdId, event_id: i64) -> Self {
+        self.cursors.insert(household_id, event_id);
+        self
+    }
+
+    /// The current snapshot.
+    pub fn state(&self) -> &State<'static> {
+        &self.state
+    }
+
+    /// Takes the snapshot out of the sync.
+    pub fn into_state(self) -> State<'static> {
+        self.state
+    }
+
+    /// The id of the last event seen in a household.
+    pub fn cursor(&self, household_id: HouseholdId) -> Option<i64> {
+        self.cursors.get(&household_id).copied()
+    }
+
+    /// Fetches the events since the last sync of every household and applies
+    /// them oldest first, returning the changes made. Households whose events
+    /// were fetched are applied even when another fails; the first failure is
+    /// returned after that.
+    pub fn sync<T: Transport + Send + Sync>(&mut self, client: &SurePet<T>) -> Result<Vec<Change>> {
+        self.sync_with(client, |_| {})
+    }
+
+    /// Like [`sync`](#method.sync), also passing each event applied to
+    /// `observe`, as [`sync_observing`](#method.sync_observing) does.
+    pub(crate) fn sync_with<T, F>(&mut self, client: &SurePet<T>, observe: F) -> Result<Vec<Change>>
+    where
+        T: Transport + Send + Sync,
+        F: FnMut(&Event<'static>),
+    {
+        let mut changes = Vec::new();
+        let mut failure = None;
+        for (_, result) in self.sync_observing(client, observe) {
+            match result {
+                Ok(household) => changes.extend(household),
+                Err(error) => failure = failure.or(Some(error)),
+            }
+        }
+        match failure {
+            Some(error) => Err(error),
+            None => Ok(changes),
+        }
+    }
+
+    /// Like [`sync`](#method.sync), but reports the outcome of each
+    /// household on its own. Timelines are fetched on up to
+    /// [`concurrency`](#method.with_concurrency) threads at once.
+    pub fn sync_households<T: Transport + Send + Sync>(
+        &mut self,
+        client: &SurePet<T>,
+    ) -> Vec<(HouseholdId, Result<Vec<Change>>)> {
+        self.sync_observing(client, |_| {})
+    }
+
+    /// Like [`sync_households`](#method.sync_households), also passing each
+    /// event applied to `observe`, oldest first, for trackers that need more
+    /// of an event than the changes it makes.
+    pub(crate) fn sync_observing<T, F>(
+        &mut self,
+        client: &SurePet<T>,
+        mut observe: F,
+    ) -> Vec<(HouseholdId, Result<Vec<Change>>)>
+    where
+        T: Transport + Send + Sync,
+        F: FnMut(&Event<'static>),
+    {
+        let households: Vec<(HouseholdId, Option<i64>)> = self
+            .state
+            .households()
+            .iter()
+            .map(|household| (household.id(), self.cursor(household.id())))
+            .collect();
+        let fetched =
+            fanout::map(
+                &households,
+                self.concurrency,
+                |(household_id, cursor)| match cursor {
+                    Some(cursor) => client.timeline().after(*household_id, *cursor),
+                    None => client.timeline().household(*household_id),
+                },
+            );
+
+        let mut outcomes = Vec::new();
+        for ((household_id, cursor), events) in households.into_iter().zip(fetched) {
+            let outcome = events.map(|events| match cursor {
+                Some(_) => events
+                    .iter()
+                    .rev()
+                    .flat_map(|event| {
+                        observe(event);
+                        self.apply(event)
+                    })
+                    .collect(),
+                None => {
+                    let newest = events.iter().map(Event::id).max().unwrap_or(0);
+                    self.cursors.insert(household_id, newest);
+                    Vec::new()
+                }
+            });
+            outcomes.push((household_id, outcome));
+        }
+        outcomes
+    }
+
+    /// Applies a single event, such as one pushed over MQTT, and returns the
+    /// changes made. Positions and visits older than the ones in the state
+    /// are left alone.
+    pub fn apply(&mut self, event: &Event<'_>) -> Vec<Change> {
+        if let Some(household_id) = event.household_id() {
+            let cursor = self.cursors.entry(household_id).or_insert(event.id());
+            *cursor = (*cursor).max(event.id());
+        }
+        match event.kind() {
+            EventKind::Movement => self.apply_movements(event),
+            EventKind::Feeding | EventKind::Drinking => self.apply_visits(event),
+            _ => Vec::new(),
+        }
+    }
+
+    fn apply_movements(&mut self, event: &Event<'_>) -> Vec<Change> {
+        let mut changes = Vec::new();
+        for entry in event.movements() {
+            let location = match entry.direction() {
+                Direction::In => Location::Inside,
+                Direction::Out => Location::Outside,
+                _ => continue,
+            };
+            let position = Position {
+                device_id: entry.device_id(),
+                location,
+                since: event.created_at(),
+            };
+            for pet_id in self.pets_of(event, entry.tag_id()) {
+                let pet = match self.state.pets.iter_mut().find(|p| p.id == pet_id) {
+                    Some(pet) => pet,
+                    None => continue,
+                };
+                if pet.position.is_some_and(|p| p.since > position.since) {
+                    continue;
+                }
+                pet.position = Some(position);
+                changes.push(Change::Moved { pet_id, position });
+            }
+        }
+        changes
+    }
+
+    fn apply_visits(&mut self, event: &Event<'_>) -> Vec<Change> {
+        let drinking = event.kind() == EventKind::Drinking;
+        let mut changes = Vec::new();
+        for entry in event.weights() {
+            if entry.context() != PET_VISIT {
+                continue;
+            }
+            let visit = Visit {
+                device_id: entry.device_id(),
+                at: event.created_at(),
+                change: entry.frames().iter().map(|frame| frame.change()).collect(),
+            };
+            for pet_id in self.pets_of(event, entry.tag_id()) {
+                let pet = match self.state.pets.iter_mut().find(|p| p.id == pet_id) {
+                    Some(pet) => pet,
+                    None => continue,
+                };
+                let last = if drinking {
+                    &mut pet.status.drinking
+                } else {
+                    &mut pet.status.feeding
+                };
+                if last.as_ref().is_some_and(|last| last.at > visit.at) {
+                    continue;
+                }
+                *last = Some(visit.clone());
+                changes.push(if drinking {
+                    Change::Drank {
+                        pet_id,
+                        visit: visit.clone(),
+                    }
+                } else {
+                    Change::Ate {
+                        pet_id,
+                        visit: visit.clone(),
+                    }
+                });
+            }
+        }
+        changes
+    }
+
+    /// The pets an event entry is about: those named by the event, or else
+    /// the pet wearing the tag the device read.
+    fn pets_of(&self, event: &Event<'_>, tag_id: Option<TagId>) -> Vec<PetId> {
+        if !event.pets().is_empty() {
+            return event.pets().iter().map(|pet| pet.id()).collect();
+        }
+        self.state
+            .pets()
+            .iter()
+            .filter(|pet| tag_id.is_some() && pet.tag_id() == tag_id)
+            .map(|pet| pet.id())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    const STATE: &str = r#"{
+        "households":[{"id":1,"name":"Home"}],
+        "pets":[{"id":2,"name":"Milo","household_id":1,"tag_id":5,
+                 "position":{"where":1,"since":"2019-06-01T04:00:00+00:00"}}]
+    }"#;
+
+    #[test]
+    fn should_apply_new_events_to_the_state() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[{"id":7,"type":0,"created_at":"2019-06-01T05:00:00+00:00"}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[
+                {"id":9,"type":22,"created_at":"2019-06-01T06:02:00+00:00","household_id":1,
+                 "weights":[{"device_id":11,"tag_id":5,"context":1,
+                             "frames":[{"current_weight":20.5,"change":-4.5}]}]},
+                {"id":8,"type":0,"created_at":"2019-06-01T06:00:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],
+                 "movements":[{"device_id":10,"direction":2}]},
+                {"id":7,"type":0,"created_at":"2019-06-01T05:00:00+00:00"}
+            ]}"#,
+            );
+        let client = SurePet::new(&transport, "token");
+        let mut sync = StateSync::new(serde_json::from_str(STATE).unwrap());
+
+        assert!(sync.sync(&client).unwrap().is_empty());
+        assert_eq!(Some(7), sync.cursor(HouseholdId::new(1)));
+        let changes = sync.sync(&client).unwrap();
+
+        assert!(transport.requests()[1].url.ends_with("page_size=50"));
+        assert_eq!(2, changes.len());
+        assert!(matches!(changes[0], Change::Moved { .. }));
+        assert!(matches!(changes[1], Change::Ate { .. }));
+        assert_eq!(Some(9), sync.cursor(HouseholdId::new(1)));
+        let outcomes = sync.sync_households(&client);
+        assert!(outcomes[0].1.is_err());
+        let milo = &sync.state().pets()[0];
+        assert_eq!(Location::Outside, milo.position().unwrap().location());
+        assert_eq!(&[-4.5], milo.status().feeding().unwrap().change());
+    }
+}
@@ -0,0 +1,178 @@
+//! The microchips and collar tags registered to the account.
+//!
+//! A chip is registered when a flap or feeder first learns it, before
+//! anyone says which pet wears it. [`Tags`](struct.Tags.html), from
+//! [`SurePet::tags`](../client/struct.SurePet.html#method.tags), lists them,
+//! finds the ones no pet has yet, assigns them and removes the stale ones
+//! left over from a pet that was rehomed.
+use crate::client::SurePet;
+use crate::error::Result;
+use crate::id::{PetId, TagId};
+use crate::sort::{Field, Key, Listing, Sortable};
+use crate::time::Timestamp;
+use crate::transport::{Method, Transport};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A microchip or collar tag known to the account.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Tag {
+    pub(crate) id: TagId,
+    pub(crate) tag: String,
+    #[serde(default)]
+    pub(crate) created_at: Option<Timestamp>,
+    #[serde(default)]
+    pub(crate) updated_at: Option<Timestamp>,
+}
+
+impl Tag {
+    /// The tag id, as pets and devices refer to it.
+    pub fn id(&self) -> TagId {
+        self.id
+    }
+
+    /// The number read from the chip, such as `900.000123456789`.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// When the tag was first registered.
+    pub fn created_at(&self) -> Option<Timestamp> {
+        self.created_at
+    }
+
+    /// When the tag was last changed.
+    pub fn updated_at(&self) -> Option<Timestamp> {
+        self.updated_at
+    }
+}
+
+impl Sortable for Tag {
+    fn sort_key(&self, field: Field) -> Key<'_> {
+        match field {
+            Field::Id => Key::Number(self.id.get()),
+            Field::Name => Key::Text(&self.tag),
+            Field::CreatedAt => self.created_at.into(),
+            Field::UpdatedAt => self.updated_at.into(),
+        }
+    }
+}
+
+/// The pet's side of a tag, as read back to find the tags in use.
+#[derive(Deserialize)]
+struct Wearer {
+    #[serde(default)]
+    tag_id: Option<TagId>,
+}
+
+#[derive(Serialize)]
+struct Assignment {
+    tag_id: TagId,
+}
+
+/// The tag endpoints, obtained from [`SurePet::tags`](../client/struct.SurePet.html#method.tags).
+#[derive(Debug)]
+pub struct Tags<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+impl<'c, T: Transport> Tags<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Tags { client }
+    }
+
+    /// Fetches every tag on the account, whether or not a pet wears it.
+    pub fn list(&self) -> Result<Vec<Tag>> {
+        self.client.get("/api/tag", &[])
+    }
+
+    /// Like [`list`](#method.list), sorted, see the
+    /// [`sort`](../sort/index.html) module.
+    pub fn query(&self) -> Listing<'c, T, Tag> {
+        Listing::new(self.client, "/api/tag", Vec::new())
+    }
+
+    /// Fetches a single tag.
+    pub fn get(&self, tag_id: TagId) -> Result<Tag> {
+        self.client.get(&format!("/api/tag/{}", tag_id), &[])
+    }
+
+    /// Fetches the tags no pet on the account wears, such as a chip a flap
+    /// learned before its pet was added.
+    pub fn unassigned(&self) -> Result<Vec<Tag>> {
+        let worn: HashSet<TagId> = self
+            .client
+            .get::<Vec<Wearer>>("/api/pet", &[])?
+            .into_iter()
+            .filter_map(|pet| pet.tag_id)
+            .collect();
+        let mut tags = self.list()?;
+        tags.retain(|tag| !worn.contains(&tag.id));
+        Ok(tags)
+    }
+
+    /// Assigns a tag to a pet, replacing the one it wore.
+    pub fn assign(&self, tag_id: TagId, pet_id: PetId) -> Result<()> {
+        let path = format!("/api/pet/{}", pet_id);
+        self.client
+            .send_unit(Method::Put, &path, Some(&Assignment { tag_id }))?;
+        if let Some(cache) = self.client.state_cache() {
+            cache.invalidate_pet(pet_id);
+        }
+        Ok(())
+    }
+
+    /// Removes a tag from the account. Devices forget it, so a pet still
+    /// wearing it is no longer let through.
+    pub fn delete(&self, tag_id: TagId) -> Result<()> {
+        let path = format!("/api/tag/{}", tag_id);
+        self.client.send_unit::<()>(Method::Delete, &path, None)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_find_and_assign_unassigned_tags() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":[{"id":2,"name":"Milo","tag_id":5},{"id":3,"name":"Luna"}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":5,"tag":"900.000123456789"},
+                    {"id":6,"tag":"900.000987654321","created_at":"2019-06-01T07:00:00Z"}]}"#,
+            )
+            .respond(200, r#"{"data":{}}"#)
+            .respond(204, "");
+        let client = SurePet::new(&transport, "token");
+        let tags = client.tags();
+
+        let unassigned = tags.unassigned().unwrap();
+        assert_eq!(
+            vec![TagId::new(6)],
+            unassigned.iter().map(Tag::id).collect::<Vec<_>>()
+        );
+        assert_eq!("900.000987654321", unassigned[0].tag());
+        tags.assign(TagId::new(6), PetId::new(3)).unwrap();
+        tags.delete(TagId::new(5)).unwrap();
+
+        let requests = transport.requests();
+        assert_eq!("https://app.api.surehub.io/api/pet", requests[0].url);
+        assert_eq!("https://app.api.surehub.io/api/tag", requests[1].url);
+        assert_eq!(Method::Put, requests[2].method);
+        assert_eq!("https://app.api.surehub.io/api/pet/3", requests[2].url);
+        assert_eq!(
+            Some(r#"{"tag_id":6}"#.as_bytes()),
+            requests[2].body.as_deref()
+        );
+        assert_eq!(Method::Delete, requests[3].method);
+        assert_eq!("https://app.api.surehub.io/api/tag/5", requests[3].url);
+    }
+}
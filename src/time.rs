@@ -0,0 +1,771 @@
+//! Minimal date and time types used by the API models.
+//!
+//! The API sends timestamps as RFC 3339 strings such as
+//! `2019-06-01T07:42:00+00:00`; they are normalised to UTC on parsing.
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// An instant in time, stored as whole seconds since the Unix epoch.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    /// The current time according to the system clock.
+    pub fn now() -> Self {
+        Timestamp::from(std::time::SystemTime::now())
+    }
+
+    /// Creates a timestamp from seconds since the Unix epoch.
+    pub fn from_unix(seconds: i64) -> Self {
+        Timestamp(seconds)
+    }
+
+    /// Seconds since the Unix epoch.
+    pub fn unix(self) -> i64 {
+        self.0
+    }
+
+    /// The number of seconds from `earlier` to this timestamp, negative if
+    /// `earlier` is actually later.
+    pub fn seconds_since(self, earlier: Timestamp) -> i64 {
+        self.0 - earlier.0
+    }
+
+    /// The time from `earlier` to this timestamp, zero if `earlier` is
+    /// actually later.
+    pub fn elapsed_since(self, earlier: Timestamp) -> Duration {
+        seconds(self.seconds_since(earlier))
+    }
+
+    /// Parses an HTTP date such as the `Date` response header,
+    /// `Sun, 06 Nov 1994 08:49:37 GMT`.
+    pub fn from_http_date(s: &str) -> Option<Self> {
+        parse_http_date(s)
+    }
+
+    /// The UTC calendar date this timestamp falls on.
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(86_400))
+    }
+}
+
+/// A calendar date, stored as days since 1970-01-01.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date(i64);
+
+impl Date {
+    /// Creates a date, returning `None` if the day does not exist.
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        let (year, month, day) = (i64::from(year), i64::from(month), i64::from(day));
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date(days_from_civil(year, month, day)))
+    }
+
+    /// The year.
+    pub fn year(self) -> i32 {
+        civil_from_days(self.0).0 as i32
+    }
+
+    /// The month, from `1` to `12`.
+    pub fn month(self) -> u8 {
+        civil_from_days(self.0).1 as u8
+    }
+
+    /// The day of the month, from `1`.
+    pub fn day(self) -> u8 {
+        civil_from_days(self.0).2 as u8
+    }
+
+    /// The date a number of days later, or earlier when negative.
+    pub fn add_days(self, days: i64) -> Self {
+        Date(self.0 + days)
+    }
+
+    /// Midnight UTC at the start of the date.
+    pub fn midnight(self) -> Timestamp {
+        Timestamp(self.0 * 86_400)
+    }
+
+    /// The day of the week.
+    pub fn weekday(self) -> Weekday {
+        // 1970-01-01 was a Thursday.
+        Weekday::ALL[(self.0 + 3).rem_euclid(7) as usize]
+    }
+}
+
+/// A day of the week.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Weekday {
+    /// Monday.
+    Monday,
+    /// Tuesday.
+    Tuesday,
+    /// Wednesday.
+    Wednesday,
+    /// Thursday.
+    Thursday,
+    /// Friday.
+    Friday,
+    /// Saturday.
+    Saturday,
+    /// Sunday.
+    Sunday,
+}
+
+impl Weekday {
+    /// Every day of the week, from Monday.
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    /// Monday to Friday.
+    pub const WORKING: [Weekday; 5] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+    ];
+
+    /// Saturday and Sunday.
+    pub const WEEKEND: [Weekday; 2] = [Weekday::Saturday, Weekday::Sunday];
+
+    /// Whether the day is a Saturday or Sunday.
+    pub fn is_weekend(self) -> bool {
+        Weekday::WEEKEND.contains(&self)
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FromStr for Date {
+    type Err = ParseError;
+
+    /// Parses a `YYYY-MM-DD` date.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseError { input: s.into() };
+        let mut parts = s.splitn(3, '-');
+        let mut next = |len: usize| {
+            parts
+                .next()
+                .filter(|part| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|part| part.parse::<i32>().ok())
+                .ok_or_else(error)
+        };
+        let (year, month, day) = (next(4)?, next(2)?, next(2)?);
+        Date::new(year, month as u8, day as u8).ok_or_else(error)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = civil_from_days(self.0);
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// A wall clock time with minute precision, such as a curfew's lock time.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TimeOfDay(u16);
+
+impl TimeOfDay {
+    /// Creates a time, returning `None` if it does not exist.
+    pub fn new(hour: u8, minute: u8) -> Option<Self> {
+        if hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(TimeOfDay(u16::from(hour) * 60 + u16::from(minute)))
+    }
+
+    /// The hour, from `0` to `23`.
+    pub fn hour(self) -> u8 {
+        (self.0 / 60) as u8
+    }
+
+    /// The minute, from `0` to `59`.
+    pub fn minute(self) -> u8 {
+        (self.0 % 60) as u8
+    }
+
+    /// Minutes since midnight.
+    pub fn minutes(self) -> u16 {
+        self.0
+    }
+
+    /// Whether the time is at or after `from` and before `to`. A period
+    /// ending before it starts runs over midnight.
+    pub fn is_within(self, from: TimeOfDay, to: TimeOfDay) -> bool {
+        if from <= to {
+            from <= self && self < to
+        } else {
+            from <= self || self < to
+        }
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = ParseError;
+
+    /// Parses an `HH:MM` time; trailing seconds are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseError { input: s.into() };
+        let mut parts = s.splitn(3, ':');
+        let mut next = || {
+            parts
+                .next()
+                .filter(|part| part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|part| part.parse::<u8>().ok())
+                .ok_or_else(error)
+        };
+        let (hour, minute) = (next()?, next()?);
+        TimeOfDay::new(hour, minute).ok_or_else(error)
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour(), self.minute())
+    }
+}
+
+impl Serialize for TimeOfDay {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeOfDay {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
+
+/// An inclusive range of calendar dates.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DateRange {
+    from: Date,
+    to: Date,
+}
+
+impl DateRange {
+    /// Creates a range covering `from` to `to`, both inclusive.
+    pub fn new(from: Date, to: Date) -> Result<Self, RangeError> {
+        if from > to {
+            return Err(RangeError::Reversed { from, to });
+        }
+        Ok(DateRange { from, to })
+    }
+
+    /// A range covering a single day.
+    pub fn day(date: Date) -> Self {
+        DateRange {
+            from: date,
+            to: date,
+        }
+    }
+
+    /// The last `days` days, ending with the day of `now` (UTC); pass the
+    /// client's [`clock`](../client/struct.SurePet.html#method.clock) so the
+    /// range agrees with it. Asking for fewer than one day gives just that
+    /// day.
+    pub fn last_days(days: i64, now: Timestamp) -> Self {
+        let today = now.date();
+        DateRange {
+            from: today.add_days(1 - days.max(1)),
+            to: today,
+        }
+    }
+
+    /// The first day of the range.
+    pub fn from(&self) -> Date {
+        self.from
+    }
+
+    /// The last day of the range.
+    pub fn to(&self) -> Date {
+        self.to
+    }
+
+    /// The number of days in the range.
+    pub fn len(&self) -> i64 {
+        self.to.0 - self.from.0 + 1
+    }
+
+    /// Always `false`, a range covers at least one day.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Whether the date falls inside the range.
+    pub fn contains(&self, date: Date) -> bool {
+        self.from <= date && date <= self.to
+    }
+
+    /// Every date in the range, in order.
+    pub fn days(&self) -> impl Iterator<Item = Date> {
+        (self.from.0..=self.to.0).map(Date)
+    }
+
+    /// The first instant of the range.
+    pub fn start(&self) -> Timestamp {
+        self.from.midnight()
+    }
+
+    /// The last instant of the range.
+    pub fn end(&self) -> Timestamp {
+        Timestamp(self.to.add_days(1).midnight().0 - 1)
+    }
+}
+
+/// The error returned for an unusable date range.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RangeError {
+    /// The start of the range is after its end.
+    Reversed { from: Date, to: Date },
+    /// The range covers more days than the call allows.
+    TooLong { days: i64, max: i64 },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::Reversed { from, to } => {
+                write!(f, "range starts on {} which is after {}", from, to)
+            }
+            RangeError::TooLong { days, max } => {
+                write!(f, "range covers {} days, at most {} are allowed", days, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+/// The error returned when a date or time fails to parse.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    input: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid date or time `{}`", self.input)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    /// Parses an RFC 3339 timestamp; fractional seconds are truncated and a
+    /// missing offset is taken to mean UTC.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_rfc3339(s).ok_or_else(|| ParseError { input: s.into() })
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, duration: Duration) -> Timestamp {
+        Timestamp(self.0 + duration.as_secs() as i64)
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, duration: Duration) -> Timestamp {
+        Timestamp(self.0 - duration.as_secs() as i64)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.0.div_euclid(86_400);
+        let seconds = self.0.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+00:00",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Timestamps arrive as RFC 3339 strings from most endpoints and as Unix
+/// seconds from a few, so both are accepted wherever one is expected.
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+struct TimestampVisitor;
+
+impl<'de> de::Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an RFC 3339 timestamp or Unix seconds")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, seconds: i64) -> Result<Timestamp, E> {
+        Ok(Timestamp(seconds))
+    }
+
+    fn visit_u64<E: de::Error>(self, seconds: u64) -> Result<Timestamp, E> {
+        i64::try_from(seconds)
+            .map(Timestamp)
+            .map_err(|_| E::custom("timestamp out of range"))
+    }
+
+    fn visit_f64<E: de::Error>(self, seconds: f64) -> Result<Timestamp, E> {
+        Ok(Timestamp(seconds.floor() as i64))
+    }
+}
+
+/// Serializes a timestamp as Unix seconds rather than RFC 3339, for fields
+/// and formats that want a number: `#[serde(with = "time::unix")]`.
+pub mod unix {
+    use super::Timestamp;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Writes the timestamp as Unix seconds.
+    pub fn serialize<S: Serializer>(
+        timestamp: &Timestamp,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(timestamp.unix())
+    }
+
+    /// Reads a timestamp in either of the forms the API uses.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+        Timestamp::deserialize(deserializer)
+    }
+}
+
+impl From<Timestamp> for std::time::SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        let magnitude = Duration::from_secs(timestamp.0.unsigned_abs());
+        if timestamp.0 < 0 {
+            std::time::UNIX_EPOCH - magnitude
+        } else {
+            std::time::UNIX_EPOCH + magnitude
+        }
+    }
+}
+
+impl From<std::time::SystemTime> for Timestamp {
+    /// Truncates to whole seconds.
+    fn from(time: std::time::SystemTime) -> Self {
+        match time.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => Timestamp(after.as_secs() as i64),
+            Err(before) => {
+                let before = before.duration();
+                let seconds = before.as_secs() as i64;
+                Timestamp(if before.subsec_nanos() > 0 {
+                    -seconds - 1
+                } else {
+                    -seconds
+                })
+            }
+        }
+    }
+}
+
+fn parse_rfc3339(s: &str) -> Option<Timestamp> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 19 || !matches!(bytes[10], b'T' | b't' | b' ') {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = s.get(range)?;
+        if part.bytes().all(|b| b.is_ascii_digit()) {
+            part.parse().ok()
+        } else {
+            None
+        }
+    };
+    if bytes[4] != b'-' || bytes[7] != b'-' || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &s[19..];
+    if rest.starts_with('.') {
+        let digits = rest[1..].bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return None;
+        }
+        rest = &rest[1 + digits..];
+    }
+    let offset = match rest.as_bytes() {
+        [] | [b'Z'] | [b'z'] => 0,
+        [sign @ b'+', ..] | [sign @ b'-', ..] => {
+            let digits: String = rest[1..].chars().filter(|c| *c != ':').collect();
+            if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let hours: i64 = digits[..2].parse().ok()?;
+            let minutes: i64 = digits[2..].parse().ok()?;
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    Some(Timestamp(
+        days * 86_400 + hour * 3600 + minute * 60 + second - offset,
+    ))
+}
+
+fn parse_http_date(s: &str) -> Option<Timestamp> {
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    let (day, month, year, time) = match parts.as_slice() {
+        [_, day, month, year, time, "GMT"] => (*day, *month, *year, *time),
+        _ => return None,
+    };
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let (day, year): (i64, i64) = (day.parse().ok()?, year.parse().ok()?);
+    let mut clock = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    if clock.next().is_some() || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    Some(Timestamp(
+        days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second,
+    ))
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The proleptic Gregorian date for a number of days since 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a count of seconds from the API into a `Duration`, treating
+/// negative counts as zero.
+pub(crate) fn seconds(seconds: i64) -> Duration {
+    Duration::from_secs(seconds.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_with_offsets() {
+        let utc: Timestamp = "2019-06-01T07:42:00+00:00".parse().unwrap();
+        assert_eq!(1_559_374_920, utc.unix());
+        assert_eq!(
+            Duration::from_secs(60),
+            (utc + Duration::from_secs(60)).elapsed_since(utc)
+        );
+        assert_eq!(
+            Duration::from_secs(0),
+            utc.elapsed_since(utc + Duration::from_secs(1))
+        );
+        assert_eq!(utc, utc + Duration::from_secs(90) - Duration::from_secs(90));
+        assert_eq!(utc, "2019-06-01T08:42:00+01:00".parse().unwrap());
+        assert_eq!(utc, "2019-06-01T07:42:00.123Z".parse().unwrap());
+        assert_eq!(utc, "2019-06-01 07:42:00".parse().unwrap());
+    }
+
+    #[test]
+    fn should_reject_garbage() {
+        assert!("2019-02-29T00:00:00Z".parse::<Timestamp>().is_err());
+        assert!("2019-06-01".parse::<Timestamp>().is_err());
+        assert!("2019-06-01T07:42:00+1".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn should_parse_http_dates() {
+        assert_eq!(
+            Some("1994-11-06T08:49:37Z".parse().unwrap()),
+            Timestamp::from_http_date("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+        assert_eq!(
+            None,
+            Timestamp::from_http_date("Sun, 06 Nov 1994 08:49 GMT")
+        );
+        assert_eq!(
+            None,
+            Timestamp::from_http_date("Sun, 31 Nov 1994 08:49:37 GMT")
+        );
+    }
+
+    #[test]
+    fn should_convert_dates() {
+        let date: Date = "2020-02-29".parse().unwrap();
+        assert_eq!((2020, 2, 29), (date.year(), date.month(), date.day()));
+        assert_eq!("2020-03-01", date.add_days(1).to_string());
+        assert_eq!(
+            date,
+            "2020-02-29T23:59:59Z".parse::<Timestamp>().unwrap().date()
+        );
+        assert!("2019-02-29".parse::<Date>().is_err());
+        assert!("2019-2-28".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn should_cover_whole_days_in_range() {
+        let range = DateRange::new(
+            Date::new(2019, 12, 31).unwrap(),
+            Date::new(2020, 1, 2).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(3, range.days().count());
+        assert_eq!("2019-12-31T00:00:00+00:00", range.start().to_string());
+        assert_eq!("2020-01-02T23:59:59+00:00", range.end().to_string());
+    }
+
+    #[test]
+    fn should_validate_ranges() {
+        let day = Date::new(2019, 6, 1).unwrap();
+        assert_eq!(
+            Err(RangeError::Reversed {
+                from: day,
+                to: day.add_days(-1)
+            }),
+            DateRange::new(day, day.add_days(-1))
+        );
+        assert_eq!(DateRange::day(day), DateRange::new(day, day).unwrap());
+        let now = Timestamp::from_unix(1_559_374_920);
+        let week = DateRange::last_days(7, now);
+        assert_eq!(7, week.len());
+        assert_eq!(now.date(), week.to());
+        assert_eq!(Date::new(2019, 5, 26).unwrap(), week.from());
+        assert_eq!(DateRange::day(now.date()), DateRange::last_days(0, now));
+    }
+
+    #[test]
+    fn should_read_unix_seconds() {
+        let item: Timestamp = serde_json::from_str("1559374920").unwrap();
+        assert_eq!("2019-06-01T07:42:00+00:00", item.to_string());
+        assert_eq!(item, serde_json::from_str("1559374920.5").unwrap());
+
+        #[derive(Deserialize, Serialize)]
+        struct Seen {
+            #[serde(with = "unix")]
+            at: Timestamp,
+        }
+        let seen: Seen = serde_json::from_str(r#"{"at":"2019-06-01T07:42:00Z"}"#).unwrap();
+        assert_eq!(
+            r#"{"at":1559374920}"#,
+            serde_json::to_string(&seen).unwrap()
+        );
+        assert_eq!(
+            Timestamp::from_unix(-1),
+            Timestamp::from(std::time::SystemTime::from(Timestamp::from_unix(-1)))
+        );
+    }
+
+    #[test]
+    fn should_round_trip_through_serde() {
+        let item: Timestamp = serde_json::from_str("\"1969-12-31T23:59:59-00:00\"").unwrap();
+        assert_eq!(-1, item.unix());
+        assert_eq!(
+            "\"1969-12-31T23:59:59+00:00\"",
+            serde_json::to_string(&item).unwrap()
+        );
+    }
+}
@@ -0,0 +1,1051 @@
+//! The household timeline of flap, feeder and water events.
+use crate::client::ResponseEnvelope;
+use crate::client::SurePet;
+use crate::describe::Catalogue;
+use crate::error::{Error, Result};
+use crate::fanout;
+use crate::float::HashFloat;
+use crate::id::{DeviceId, HouseholdId, PetId, TagId};
+use crate::intern::Interner;
+use crate::lenient::Lenient;
+use crate::names::names;
+use crate::stream;
+use crate::time::{self, Timestamp};
+use crate::transport::{self, Transport};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// The number of events the API returns per timeline page.
+pub const PAGE_SIZE: usize = 50;
+
+/// How long a long-poll asks the server to hold the request open, see
+/// [`Subscription::with_long_poll`](struct.Subscription.html#method.with_long_poll).
+pub const LONG_POLL_WAIT: Duration = Duration::from_secs(30);
+
+/// The context code of a visit by a pet, as opposed to a refill.
+pub(crate) const PET_VISIT: i64 = 1;
+
+/// The kind of a timeline event, as sent in its `type` field.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EventKind {
+    /// A pet went through a flap.
+    Movement,
+    /// A device reported low batteries.
+    LowBattery,
+    /// A flap changed lock mode.
+    LockStatus,
+    /// An unknown animal tried the flap.
+    IntruderMovement,
+    /// A curfew locked or unlocked a flap.
+    Curfew,
+    /// A pet ate from a feeder, or the feeder was filled.
+    Feeding,
+    /// A pet drank from a water dispenser, or it was refilled.
+    Drinking,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl EventKind {
+    /// The code the API uses for this kind.
+    pub fn code(self) -> i64 {
+        match self {
+            EventKind::Movement => 0,
+            EventKind::LowBattery => 1,
+            EventKind::LockStatus => 6,
+            EventKind::IntruderMovement => 7,
+            EventKind::Curfew => 20,
+            EventKind::Feeding => 22,
+            EventKind::Drinking => 29,
+            EventKind::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for EventKind {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => EventKind::Movement,
+            1 => EventKind::LowBattery,
+            6 => EventKind::LockStatus,
+            7 => EventKind::IntruderMovement,
+            20 => EventKind::Curfew,
+            22 => EventKind::Feeding,
+            29 => EventKind::Drinking,
+            code => EventKind::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for EventKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("event kind", EventKind {
+    Movement => "movement",
+    LowBattery => "low_battery",
+    LockStatus => "lock_status",
+    IntruderMovement => "intruder_movement",
+    Curfew => "curfew",
+    Feeding => "feeding",
+    Drinking => "drinking",
+});
+
+/// Which way a pet went through a flap.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// The pet looked through the flap without using it.
+    LookedThrough,
+    /// The pet came in.
+    In,
+    /// The pet went out.
+    Out,
+    /// A code this crate does not know about.
+    Unknown(i64),
+}
+
+impl Direction {
+    /// The code the API uses for this direction.
+    pub fn code(self) -> i64 {
+        match self {
+            Direction::LookedThrough => 0,
+            Direction::In => 1,
+            Direction::Out => 2,
+            Direction::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<i64> for Direction {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => Direction::LookedThrough,
+            1 => Direction::In,
+            2 => Direction::Out,
+            code => Direction::Unknown(code),
+        }
+    }
+}
+
+impl Serialize for Direction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Direction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        crate::unknown::deserialize(deserializer)
+    }
+}
+
+names!("direction", Direction {
+    LookedThrough => "looked_through",
+    In => "in",
+    Out => "out",
+});
+
+/// A single timeline event.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Event<'a> {
+    pub(crate) id: i64,
+    #[serde(rename = "type")]
+    pub(crate) kind: EventKind,
+    pub(crate) created_at: Timestamp,
+    #[serde(default)]
+    pub(crate) household_id: Option<HouseholdId>,
+    #[serde(borrow, default)]
+    pub(crate) pets: Vec<PetRef<'a>>,
+    #[serde(borrow, default)]
+    pub(crate) devices: Vec<DeviceRef<'a>>,
+    #[serde(default)]
+    pub(crate) movements: Vec<MovementEntry>,
+    #[serde(default)]
+    pub(crate) weights: Vec<WeightEntry>,
+    #[serde(flatten)]
+    pub(crate) other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<'a> Event<'a> {
+    /// The event id; ids increase over time.
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    /// What happened.
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// When it happened.
+    pub fn created_at(&self) -> Timestamp {
+        self.created_at
+    }
+
+    /// The household the event belongs to.
+    pub fn household_id(&self) -> Option<HouseholdId> {
+        self.household_id
+    }
+
+    /// The pets involved.
+    pub fn pets(&self) -> &[PetRef<'a>] {
+        &self.pets
+    }
+
+    /// The devices involved.
+    pub fn devices(&self) -> &[DeviceRef<'a>] {
+        &self.devices
+    }
+
+    /// Flap movements, present on movement events.
+    pub fn movements(&self) -> &[MovementEntry] {
+        &self.movements
+    }
+
+    /// Bowl weights, present on feeding and drinking events.
+    pub fn weights(&self) -> &[WeightEntry] {
+        &self.weights
+    }
+
+    /// Fields this crate does not model, kept as the API sent them.
+    pub fn other(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.other
+    }
+
+    /// Whether the given pet is involved in the event.
+    pub fn involves_pet(&self, pet_id: PetId) -> bool {
+        self.pets.iter().any(|pet| pet.id == pet_id)
+    }
+
+    /// Describes the event in a sentence in the language of `locale`, such
+    /// as `de-DE`, using the built in
+    /// [`Catalogue`](../describe/struct.Catalogue.html) for it.
+    pub fn describe(&self, locale: &str) -> String {
+        Catalogue::for_locale(locale).describe(self)
+    }
+
+    /// Copies any borrowed names so the event outlives the response body it
+    /// was parsed from.
+    pub fn into_owned(self) -> Event<'static> {
+        Event {
+            id: self.id,
+            kind: self.kind,
+            created_at: self.created_at,
+            household_id: self.household_id,
+            pets: self.pets.into_iter().map(PetRef::into_owned).collect(),
+            devices: self
+                .devices
+                .into_iter()
+                .map(DeviceRef::into_owned)
+                .collect(),
+            movements: self.movements,
+            weights: self.weights,
+            other: self.other,
+        }
+    }
+}
+
+/// A pet referenced by an event.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct PetRef<'a> {
+    pub(crate) id: PetId,
+    #[serde(borrow, default)]
+    pub(crate) name: Cow<'a, str>,
+}
+
+impl<'a> PetRef<'a> {
+    /// The pet id.
+    pub fn id(&self) -> PetId {
+        self.id
+    }
+
+    /// The pet's name at the time of the event.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Copies the name if it is borrowed.
+    pub fn into_owned(self) -> PetRef<'static> {
+        PetRef {
+            id: self.id,
+            name: Cow::Owned(self.name.into_owned()),
+        }
+    }
+}
+
+/// A device referenced by an event.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct DeviceRef<'a> {
+    pub(crate) id: DeviceId,
+    #[serde(borrow, default)]
+    pub(crate) name: Cow<'a, str>,
+    #[serde(default)]
+    pub(crate) product_id: Option<i64>,
+}
+
+impl<'a> DeviceRef<'a> {
+    /// The device id.
+    pub fn id(&self) -> DeviceId {
+        self.id
+    }
+
+    /// The device's name at the time of the event.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The raw product id.
+    pub fn product_id(&self) -> Option<i64> {
+        self.product_id
+    }
+
+    /// Copies the name if it is borrowed.
+    pub fn into_owned(self) -> DeviceRef<'static> {
+        DeviceRef {
+            id: self.id,
+            name: Cow::Owned(self.name.into_owned()),
+            product_id: self.product_id,
+        }
+    }
+}
+
+/// A pass through a flap.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct MovementEntry {
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) tag_id: Option<TagId>,
+    pub(crate) direction: Direction,
+}
+
+impl MovementEntry {
+    /// The flap used.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The microchip tag read, if any.
+    pub fn tag_id(&self) -> Option<TagId> {
+        self.tag_id
+    }
+
+    /// Which way the pet went.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+}
+
+/// Bowl readings taken during a feeder or water dispenser visit.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct WeightEntry {
+    #[serde(default)]
+    pub(crate) device_id: Option<DeviceId>,
+    #[serde(default)]
+    pub(crate) tag_id: Option<TagId>,
+    #[serde(default)]
+    pub(crate) duration: i64,
+    #[serde(default)]
+    pub(crate) context: i64,
+    #[serde(default)]
+    pub(crate) frames: Vec<Frame>,
+}
+
+impl WeightEntry {
+    /// The device visited.
+    pub fn device_id(&self) -> Option<DeviceId> {
+        self.device_id
+    }
+
+    /// The microchip tag read, if any.
+    pub fn tag_id(&self) -> Option<TagId> {
+        self.tag_id
+    }
+
+    /// Seconds spent at the bowl.
+    pub fn duration(&self) -> i64 {
+        self.duration
+    }
+
+    /// Time spent at the bowl.
+    pub fn elapsed(&self) -> Duration {
+        time::seconds(self.duration)
+    }
+
+    /// The raw context code; `1` is a pet visit, other values are refills
+    /// and manual resets.
+    pub fn context(&self) -> i64 {
+        self.context
+    }
+
+    /// One reading per bowl.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+}
+
+/// The reading for one bowl.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Frame {
+    #[serde(default)]
+    pub(crate) index: u8,
+    pub(crate) current_weight: f64,
+    pub(crate) change: f64,
+}
+
+impl Eq for Frame {}
+
+impl Hash for Frame {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.current_weight.hash_float(state);
+        self.change.hash_float(state);
+    }
+}
+
+impl Frame {
+    /// The bowl index, `0` being the left hand bowl.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// The weight left in the bowl.
+    pub fn current_weight(&self) -> f64 {
+        self.current_weight
+    }
+
+    /// The change during the visit, negative when food or water was taken.
+    pub fn change(&self) -> f64 {
+        self.change
+    }
+}
+
+/// The timeline endpoints, obtained from [`SurePet::timeline`](../client/struct.SurePet.html#method.timeline).
+#[derive(Debug)]
+pub struct Timeline<'c, T> {
+    client: &'c SurePet<T>,
+}
+
+impl<'c, T: Transport> Timeline<'c, T> {
+    pub(crate) fn new(client: &'c SurePet<T>) -> Self {
+        Timeline { client }
+    }
+
+    /// Fetches the newest page of events for a household, newest first.
+    pub fn household(&self, household_id: HouseholdId) -> Result<Vec<Event<'static>>> {
+        self.page(household_id, None)
+    }
+
+    /// Fetches the page of events older than `before_id`, newest first.
+    pub fn before(&self, household_id: HouseholdId, before_id: i64) -> Result<Vec<Event<'static>>> {
+        self.page(household_id, Some(before_id))
+    }
+
+    /// Pages backwards through the timeline until every event created at or
+    /// after `since` has been fetched, returning them newest first.
+    pub fn since(
+        &self,
+        household_id: HouseholdId,
+        since: Timestamp,
+    ) -> Result<Vec<Event<'static>>> {
+        let mut events = Vec::new();
+        let mut before_id = None;
+        loop {
+            let page = self.page(household_id, before_id)?;
+            let done = page.len() < PAGE_SIZE || page.iter().any(|e| e.created_at < since);
+            before_id = page.last().map(|event| event.id);
+            events.extend(page.into_iter().filter(|e| e.created_at >= since));
+            if done || before_id.is_none() {
+                return Ok(events);
+            }
+        }
+    }
+
+    /// Like [`since`](#method.since), but hands each event to `f` as it is
+    /// parsed instead of collecting them, so long backfills only hold one
+    /// page body and one event in memory. Returns how many events were
+    /// passed to `f`.
+    pub fn each_since<F: FnMut(Event<'_>)>(
+        &self,
+        household_id: HouseholdId,
+        since: Timestamp,
+        mut f: F,
+    ) -> Result<usize> {
+        let mut passed = 0;
+        let mut before_id = None;
+        loop {
+            let response = self.page_response(household_id, before_id)?;
+            let mut oldest = None;
+            let mut done = false;
+            let len = stream::for_each(&response.body, |event: Event<'_>| {
+                oldest = Some(event.id);
+                if event.created_at < since {
+                    done = true;
+                } else {
+                    passed += 1;
+                    f(event);
+                }
+            })?;
+            if done || len < PAGE_SIZE || oldest.is_none() {
+                return Ok(passed);
+            }
+            before_id = oldest;
+        }
+    }
+
+    /// Like [`since`](#method.since), but the pet and device names of each
+    /// event are swapped for the copies held by `interner` as the page is
+    /// parsed, so a long history shares one allocation per name instead of
+    /// holding one per event.
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>, since: sure_petcare::time::Timestamp) -> sure_petcare::error::Result<()> {
+    /// use sure_petcare::id::HouseholdId;
+    /// use sure_petcare::intern::Interner;
+    /// let interner = Interner::from_state(&client.start()?);
+    /// let events = client.timeline().since_interned(HouseholdId::new(1234), since, &interner)?;
+    /// println!("{} events", events.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn since_interned<'i>(
+        &self,
+        household_id: HouseholdId,
+        since: Timestamp,
+        interner: &'i Interner,
+    ) -> Result<Vec<Event<'i>>> {
+        let mut events = Vec::new();
+        self.each_since(household_id, since, |event| {
+            events.push(interner.event(event))
+        })?;
+        Ok(events)
+    }
+
+    /// Pages backwards through the timeline until every event newer than
+    /// `after_id` has been fetched, returning them newest first.
+    pub fn after(&self, household_id: HouseholdId, after_id: i64) -> Result<Vec<Event<'static>>> {
+        let mut events = Vec::new();
+        let mut before_id = None;
+        loop {
+            let page = self.page(household_id, before_id)?;
+            let done = page.len() < PAGE_SIZE || page.iter().any(|e| e.id <= after_id);
+            before_id = page.last().map(|event| event.id);
+            events.extend(page.into_iter().filter(|e| e.id > after_id));
+            if done || before_id.is_none() {
+                return Ok(events);
+            }
+        }
+    }
+
+    /// Yields each new event of a household as it happens, oldest first;
+    /// events already on the timeline when subscribing are skipped.
+    ///
+    /// The subscription fetches the newest events every `interval`, the
+    /// ones [`after`](#method.after) returns. A failed request yields the
+    /// error and the next call tries again. See
+    /// [`Subscription::with_long_poll`](struct.Subscription.html#method.with_long_poll)
+    /// for trying to hear of events sooner.
+    pub fn subscribe(&self, household_id: HouseholdId, interval: Duration) -> Subscription<'c, T> {
+        Subscription {
+            timeline: Timeline::new(self.client),
+            household_id,
+            interval,
+            channel: Channel::Polling,
+            last_id: None,
+            pending: VecDeque::new(),
+            backoff: None,
+        }
+    }
+
+    /// Like [`since`](#method.since), but an event the models cannot parse is
+    /// kept as a skipped item instead of failing the whole backfill.
+    pub fn since_lenient(
+        &self,
+        household_id: HouseholdId,
+        since: Timestamp,
+    ) -> Result<Lenient<Event<'static>>> {
+        let mut events = Lenient::default();
+        let mut before_id = None;
+        loop {
+            let page = self.lenient_page(household_id, before_id)?;
+            let done = page.len() < PAGE_SIZE || page.parsed().any(|e| e.created_at < since);
+            let oldest = page
+                .items()
+                .iter()
+                .filter_map(|item| match item {
+                    Ok(event) => Some(event.id),
+                    Err(skipped) => skipped.id(),
+                })
+                .min();
+            events.extend(
+                page.into_iter()
+                    .filter(|item| item.as_ref().map_or(true, |e| e.created_at >= since))
+                    .collect(),
+            );
+            if done || oldest.is_none() || oldest == before_id {
+                return Ok(events);
+            }
+            before_id = oldest;
+        }
+    }
+
+    /// Fetches a page of events and keeps the response, so the events can be
+    /// parsed borrowing their names from the body rather than copying them.
+    /// This saves an allocation per name when working through a long
+    /// timeline one page at a time.
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn run<T: sure_petcare::transport::Transport>(client: sure_petcare::client::SurePet<T>) -> sure_petcare::error::Result<()> {
+    /// use sure_petcare::client::ResponseEnvelope;
+    /// use sure_petcare::id::HouseholdId;
+    /// use sure_petcare::timeline::Event;
+    /// let response = client.timeline().page_response(HouseholdId::new(1234), None)?;
+    /// let page: ResponseEnvelope<Vec<Event<'_>>> = ResponseEnvelope::parse(&response.body)?;
+    /// for event in page.data() {
+    ///     println!("{} {:?}", event.id(), event.pets().first().map(|pet| pet.name()));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn page_response(
+        &self,
+        household_id: HouseholdId,
+        before_id: Option<i64>,
+    ) -> Result<transport::Response> {
+        let (path, query) = page_call(household_id, before_id);
+        self.client.fetch(&path, &query)
+    }
+
+    fn page(
+        &self,
+        household_id: HouseholdId,
+        before_id: Option<i64>,
+    ) -> Result<Vec<Event<'static>>> {
+        Self::parse_page(&self.page_response(household_id, before_id)?)
+    }
+
+    fn parse_page(response: &transport::Response) -> Result<Vec<Event<'static>>> {
+        let page: ResponseEnvelope<Vec<Event<'_>>> = ResponseEnvelope::parse(&response.body)?;
+        Ok(page.data.into_iter().map(Event::into_owned).collect())
+    }
+
+    fn lenient_page(
+        &self,
+        household_id: HouseholdId,
+        before_id: Option<i64>,
+    ) -> Result<Lenient<Event<'static>>> {
+        let response = self.page_response(household_id, before_id)?;
+        let page: ResponseEnvelope<Lenient<Event<'_>>> = ResponseEnvelope::parse(&response.body)?;
+        Ok(page
+            .data
+            .into_iter()
+            .map(|item| item.map(Event::into_owned))
+            .collect())
+    }
+}
+
+impl<'c, T: Transport + Send + Sync> Timeline<'c, T> {
+    /// Fetches the newest page of several households, at most `concurrency`
+    /// at a time. Each household gets its own result, so one failing does
+    /// not lose the others. When the transport
+    /// [multiplexes](../transport/trait.Transport.html#method.multiplexes)
+    /// the pages are sent as one batch, and the transport's own stream limit
+    /// applies instead.
+    pub fn households(
+        &self,
+        household_ids: &[HouseholdId],
+        concurrency: usize,
+    ) -> Vec<(HouseholdId, Result<Vec<Event<'static>>>)> {
+        if !self.client.multiplexes() {
+            return fanout::map(household_ids, concurrency, |household_id| {
+                (*household_id, self.household(*household_id))
+            });
+        }
+        let calls: Vec<_> = household_ids
+            .iter()
+            .map(|household_id| page_call(*household_id, None))
+            .collect();
+        let responses = self.client.fetch_all(&calls);
+        household_ids
+            .iter()
+            .zip(responses)
+            .map(|(household_id, response)| {
+                (*household_id, response.and_then(|r| Self::parse_page(&r)))
+            })
+            .collect()
+    }
+}
+
+/// How a [`Subscription`](struct.Subscription.html) hears of new events.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Channel {
+    /// Requests the server holds open until there are new events, see
+    /// [`Subscription::with_long_poll`](struct.Subscription.html#method.with_long_poll).
+    LongPoll,
+    /// Fetching the newest events on an interval.
+    Polling,
+}
+
+/// An endless iterator of new events, see [`Timeline::subscribe`](struct.Timeline.html#method.subscribe).
+#[derive(Debug)]
+pub struct Subscription<'c, T> {
+    timeline: Timeline<'c, T>,
+    household_id: HouseholdId,
+    interval: Duration,
+    channel: Channel,
+    last_id: Option<i64>,
+    pending: VecDeque<Event<'static>>,
+    backoff: Option<Duration>,
+}
+
+impl<'c, T: Transport> Subscription<'c, T> {
+    /// Asks for events newer than the last one seen with `after_id` and
+    /// `wait` parameters, for a server that holds the request open until
+    /// there are some, so they arrive as soon as they happen.
+    ///
+    /// This is speculative: the API is not documented to long-poll, and
+    /// nothing says it reads either parameter. A server that rejects the
+    /// request, or answers in under half of
+    /// [`LONG_POLL_WAIT`](constant.LONG_POLL_WAIT.html) with nothing new, is
+    /// taken not to long-poll, and the subscription falls back to polling
+    /// every `interval` for good; see [`channel`](#method.channel).
+    pub fn with_long_poll(mut self) -> Self {
+        self.channel = Channel::LongPoll;
+        self
+    }
+
+    /// How the subscription is hearing of events: by polling, or by
+    /// long-poll when asked for until the server shows it does not support
+    /// it.
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Waits for events newer than `last_id` with a long-poll, or returns
+    /// `None` once the server shows it does not hold requests open.
+    fn long_poll(&mut self, last_id: i64) -> Option<Result<Vec<Event<'static>>>> {
+        let (path, mut query) = page_call(self.household_id, None);
+        query.push(("after_id", last_id.to_string()));
+        query.push(("wait", LONG_POLL_WAIT.as_secs().to_string()));
+        let started = Instant::now();
+        let page = match self.timeline.client.fetch(&path, &query) {
+            Ok(response) => Timeline::<T>::parse_page(&response),
+            Err(Error::Status {
+                status: 400 | 404 | 405 | 501,
+                ..
+            }) => return None,
+            Err(error) => return Some(Err(error)),
+        };
+        let page = match page {
+            Ok(page) => page,
+            Err(error) => return Some(Err(error)),
+        };
+        let fresh = page.iter().filter(|event| event.id > last_id).count();
+        if fresh == 0 && started.elapsed() < LONG_POLL_WAIT / 2 {
+            return None;
+        }
+        if fresh == PAGE_SIZE {
+            return Some(self.timeline.after(self.household_id, last_id));
+        }
+        Some(Ok(page))
+    }
+}
+
+impl<'c, T: Transport> Iterator for Subscription<'c, T> {
+    type Item = Result<Event<'static>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            let last_id = match self.last_id {
+                Some(last_id) => last_id,
+                None => match self.timeline.household(self.household_id) {
+                    Ok(page) => {
+                        self.last_id = Some(page.iter().map(|event| event.id).max().unwrap_or(0));
+                        continue;
+                    }
+                    Err(error) => return Some(Err(error)),
+                },
+            };
+            if let Some(backoff) = self.backoff.take() {
+                std::thread::sleep(backoff);
+            }
+            let events = match self.channel {
+                Channel::LongPoll => match self.long_poll(last_id) {
+                    Some(events) => events,
+                    None => {
+                        self.channel = Channel::Polling;
+                        continue;
+                    }
+                },
+                Channel::Polling => {
+                    std::thread::sleep(self.interval);
+                    self.timeline.after(self.household_id, last_id)
+                }
+            };
+            let mut events = match events {
+                Ok(events) => events,
+                Err(error) => {
+                    if let Error::ServiceUnavailable { retry_after } = error {
+                        self.backoff = Some(retry_after.unwrap_or_default().max(self.interval));
+                    }
+                    return Some(Err(error));
+                }
+            };
+            events.retain(|event| event.id > last_id);
+            events.sort_by_key(|event| event.id);
+            if let Some(newest) = events.last() {
+                self.last_id = Some(newest.id);
+            }
+            self.pending.extend(events);
+        }
+    }
+}
+
+/// The path and query of a timeline page.
+fn page_call(
+    household_id: HouseholdId,
+    before_id: Option<i64>,
+) -> (String, Vec<(&'static str, String)>) {
+    let path = format!("/api/timeline/household/{}", household_id);
+    let mut query = vec![("page_size", PAGE_SIZE.to_string())];
+    if let Some(before_id) = before_id {
+        query.push(("before_id", before_id.to_string()));
+    }
+    (path, query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn should_fall_back_to_polling_when_the_server_does_not_long_poll() {
+        let event = |id| {
+            format!(
+                r#"{{"id":{},"type":0,"created_at":"2019-06-01T07:00:00Z"}}"#,
+                id
+            )
+        };
+        let transport = MockTransport::new()
+            .respond(200, &format!(r#"{{"data":[{}]}}"#, event(5)))
+            .respond(200, &format!(r#"{{"data":[{}]}}"#, event(6)))
+            .respond(200, r#"{"data":[]}"#)
+            .respond(
+                200,
+                &format!(r#"{{"data":[{},{},{}]}}"#, event(8), event(7), event(6)),
+            )
+            .respond(200, &format!(r#"{{"data":[{}]}}"#, event(1)))
+            .respond(404, r#"{"error":"not found"}"#)
+            .respond(200, &format!(r#"{{"data":[{},{}]}}"#, event(2), event(1)))
+            .respond(200, &format!(r#"{{"data":[{}]}}"#, event(3)))
+            .respond(200, &format!(r#"{{"data":[{},{}]}}"#, event(4), event(3)));
+        let client = SurePet::new(&transport, "token");
+        let mut subscription = client
+            .timeline()
+            .subscribe(HouseholdId::new(1), Duration::from_millis(0))
+            .with_long_poll();
+
+        assert_eq!(6, subscription.next().unwrap().unwrap().id());
+        assert_eq!(Channel::LongPoll, subscription.channel());
+        assert_eq!(7, subscription.next().unwrap().unwrap().id());
+        assert_eq!(Channel::Polling, subscription.channel());
+        assert_eq!(8, subscription.next().unwrap().unwrap().id());
+
+        let mut unsupported = client
+            .timeline()
+            .subscribe(HouseholdId::new(2), Duration::from_millis(0))
+            .with_long_poll();
+        assert_eq!(2, unsupported.next().unwrap().unwrap().id());
+        assert_eq!(Channel::Polling, unsupported.channel());
+
+        let mut polling = client
+            .timeline()
+            .subscribe(HouseholdId::new(3), Duration::from_millis(0));
+        assert_eq!(Channel::Polling, polling.channel());
+        assert_eq!(4, polling.next().unwrap().unwrap().id());
+
+        let requests = transport.requests();
+        assert_eq!(
+            "https://app.api.surehub.io/api/timeline/household/1?page_size=50&after_id=5&wait=30",
+            requests[1].url
+        );
+        assert_eq!(
+            "https://app.api.surehub.io/api/timeline/household/1?page_size=50",
+            requests[3].url
+        );
+        assert_eq!(
+            "https://app.api.surehub.io/api/timeline/household/3?page_size=50",
+            requests[8].url
+        );
+        assert_eq!(9, requests.len());
+    }
+
+    const PAGE: &str = r#"{"data":[
+        {"id":3,"type":22,"created_at":"2019-06-01T06:02:00+00:00","household_id":1,
+         "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":11,"name":"Feeder","product_id":4}],
+         "weights":[{"device_id":11,"tag_id":5,"duration":120,"context":1,
+                     "frames":[{"index":0,"current_weight":20.5,"change":-4.5}]}]},
+        {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00","household_id":1,
+         "pets":[{"id":2,"name":"Milo"}],
+         "movements":[{"device_id":10,"tag_id":5,"direction":1}]},
+        {"id":1,"type":99,"created_at":"2019-05-31T23:00:00+00:00"}
+    ]}"#;
+
+    #[test]
+    fn should_parse_events() {
+        let transport = MockTransport::new().respond(200, PAGE);
+        let client = SurePet::new(&transport, "token");
+        let events = client.timeline().household(HouseholdId::new(1)).unwrap();
+
+        assert_eq!(
+            "https://app.api.surehub.io/api/timeline/household/1?page_size=50",
+            transport.requests()[0].url
+        );
+        assert_eq!(EventKind::Feeding, events[0].kind());
+        assert_eq!("Milo", events[0].pets()[0].name());
+        assert_eq!(-4.5, events[0].weights()[0].frames()[0].change());
+        assert_eq!(Direction::In, events[1].movements()[0].direction());
+        assert_eq!(EventKind::Unknown(99), events[2].kind());
+    }
+
+    #[test]
+    fn should_borrow_names_from_the_body() {
+        let transport = MockTransport::new().respond(200, PAGE);
+        let client = SurePet::new(&transport, "token");
+        let response = client
+            .timeline()
+            .page_response(HouseholdId::new(1), Some(4))
+            .unwrap();
+        let page: ResponseEnvelope<Vec<Event<'_>>> =
+            ResponseEnvelope::parse(&response.body).unwrap();
+
+        assert!(transport.requests()[0].url.ends_with("before_id=4"));
+        assert!(matches!(page.data()[0].pets[0].name, Cow::Borrowed("Milo")));
+        let owned: Event<'static> = page.into_data().remove(0).into_owned();
+        assert!(matches!(owned.devices[0].name, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn should_stream_events_since() {
+        let transport = MockTransport::new().respond(200, PAGE);
+        let client = SurePet::new(&transport, "token");
+        let since = "2019-06-01T00:00:00Z".parse().unwrap();
+        let mut ids = Vec::new();
+        let passed = client
+            .timeline()
+            .each_since(HouseholdId::new(1), since, |event| ids.push(event.id()))
+            .unwrap();
+
+        assert_eq!(2, passed);
+        assert_eq!(vec![3, 2], ids);
+    }
+
+    #[test]
+    fn should_batch_household_pages_on_multiplexing_transports() {
+        struct Batched(MockTransport, AtomicUsize);
+        impl Transport for Batched {
+            fn send(&self, request: &transport::Request) -> Result<transport::Response> {
+                self.0.send(request)
+            }
+            fn send_all(
+                &self,
+                requests: &[transport::Request],
+            ) -> Vec<Result<transport::Response>> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                requests
+                    .iter()
+                    .map(|request| self.0.send(request))
+                    .collect()
+            }
+            fn multiplexes(&self) -> bool {
+                true
+            }
+        }
+
+        let transport = Batched(
+            MockTransport::new().respond(200, PAGE).respond(500, "{}"),
+            Default::default(),
+        );
+        let client = SurePet::new(&transport, "token");
+        let pages = client
+            .timeline()
+            .households(&[HouseholdId::new(1), HouseholdId::new(2)], 1);
+
+        assert_eq!(1, transport.1.load(Ordering::SeqCst));
+        assert_eq!(3, pages[0].1.as_ref().unwrap().len());
+        assert_eq!(HouseholdId::new(2), pages[1].0);
+        assert!(pages[1].1.is_err());
+    }
+
+    #[test]
+    fn should_compare_and_hash_events() {
+        let events: ResponseEnvelope<Vec<Event>> = serde_json::from_str(PAGE).unwrap();
+        let mut again: ResponseEnvelope<Vec<Event>> = serde_json::from_str(PAGE).unwrap();
+        assert_eq!(events.data, again.data);
+
+        again.data[0].weights[0].frames[0].change = -5.0;
+        let mut seen: HashSet<&Event> = events.data.iter().collect();
+        seen.extend(&again.data);
+        assert_eq!(4, seen.len());
+    }
+
+    #[test]
+    fn should_stop_paging_once_past_since() {
+        let transport = MockTransport::new().respond(200, PAGE);
+        let client = SurePet::new(&transport, "token");
+        let since = "2019-06-01T00:00:00Z".parse().unwrap();
+        let events = client.timeline().since(HouseholdId::new(1), since).unwrap();
+
+        assert_eq!(1, transport.requests().len());
+        assert_eq!(vec![3, 2], events.iter().map(Event::id).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_skip_malformed_events_when_lenient() {
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[
+                {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00"},
+                {"id":1,"type":"nonsense","created_at":"2019-06-01T04:00:00+00:00"}
+            ]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let since = "2019-06-01T00:00:00Z".parse().unwrap();
+        assert!(client.timeline().since(HouseholdId::new(1), since).is_err());
+
+        let transport = MockTransport::new().respond(
+            200,
+            r#"{"data":[
+                {"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00"},
+                {"id":1,"type":"nonsense","created_at":"2019-06-01T04:00:00+00:00"}
+            ]}"#,
+        );
+        let client = SurePet::new(&transport, "token");
+        let events = client
+            .timeline()
+            .since_lenient(HouseholdId::new(1), since)
+            .unwrap();
+        assert_eq!(vec![2], events.parsed().map(Event::id).collect::<Vec<_>>());
+        assert_eq!(&[Some(1)], events.summary().skipped_ids());
+    }
+}
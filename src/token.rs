@@ -0,0 +1,182 @@
+//! Reading when a bearer token expires.
+//!
+//! Login tokens are JWTs whose `exp` claim says when the API stops
+//! accepting them. The claim is read without checking the signature, which
+//! only the API can do, so it is good for deciding when to log in again and
+//! nothing more.
+//!
+//! A client can also tell other code when its token changes, so a token
+//! store, another process sharing the account or a metrics gauge can follow
+//! it: see [`SurePet::on_token_acquired`](../client/struct.SurePet.html#method.on_token_acquired)
+//! and the hooks next to it, which receive a [`TokenEvent`](struct.TokenEvent.html).
+use crate::secret::Secret;
+use crate::time::Timestamp;
+use serde::Deserialize;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// When a JWT expires, or `None` for tokens that are not JWTs or carry no
+/// `exp` claim.
+/// # Examples
+///
+/// ```
+/// // {"alg":"none"} . {"exp":1559390400} . no signature
+/// let token = "eyJhbGciOiJub25lIn0.eyJleHAiOjE1NTkzOTA0MDB9.";
+/// let expires = sure_petcare::token::expires_at(token).unwrap();
+/// assert_eq!("2019-06-01T12:00:00+00:00", expires.to_string());
+/// assert_eq!(None, sure_petcare::token::expires_at("opaque"));
+/// ```
+pub fn expires_at(token: &str) -> Option<Timestamp> {
+    #[derive(Deserialize)]
+    struct Claims {
+        exp: Option<Timestamp>,
+    }
+
+    let mut parts = token.split('.');
+    let payload = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(payload), Some(_), None) => payload,
+        _ => return None,
+    };
+    let claims: Claims = serde_json::from_slice(&decode_base64url(payload)?).ok()?;
+    claims.exp
+}
+
+/// What happened to a client's token.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TokenChange {
+    /// A login gave the client its token.
+    Acquired,
+    /// The client logged in again after the API rejected the old token.
+    Refreshed,
+    /// The API rejected the token with `401`.
+    Invalidated,
+}
+
+/// A change to a client's token, as handed to its token hooks. The `Debug`
+/// output redacts the token.
+#[derive(Clone, Copy, PartialEq)]
+pub struct TokenEvent<'a> {
+    change: TokenChange,
+    token: &'a str,
+}
+
+impl<'a> TokenEvent<'a> {
+    /// What happened.
+    pub fn change(&self) -> TokenChange {
+        self.change
+    }
+
+    /// The token acquired or refreshed, or the one the API rejected.
+    pub fn token(&self) -> &'a str {
+        self.token
+    }
+
+    /// When the token expires, from its `exp` claim.
+    pub fn expires_at(&self) -> Option<Timestamp> {
+        expires_at(self.token)
+    }
+}
+
+impl fmt::Debug for TokenEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenEvent")
+            .field("change", &self.change)
+            .field("token", &Secret(self.token))
+            .field("expires_at", &self.expires_at())
+            .finish()
+    }
+}
+
+type Hook = Arc<dyn Fn(&TokenEvent<'_>) + Send + Sync>;
+
+/// The token hooks of a client, shared by its clones.
+#[derive(Default)]
+pub(crate) struct TokenHooks {
+    hooks: Vec<(TokenChange, Hook)>,
+    /// The last token reported invalidated, so a burst of rejected requests
+    /// reports it once.
+    invalidated: Mutex<Option<String>>,
+}
+
+impl TokenHooks {
+    pub(crate) fn add(&mut self, change: TokenChange, hook: Hook) {
+        self.hooks.push((change, hook));
+    }
+
+    /// Calls the hooks for a change to `token`.
+    pub(crate) fn notify(&self, change: TokenChange, token: &str) {
+        if self.hooks.iter().all(|(wanted, _)| *wanted != change) {
+            return;
+        }
+        if change == TokenChange::Invalidated {
+            let mut last = self
+                .invalidated
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if last.as_deref() == Some(token) {
+                return;
+            }
+            *last = Some(token.to_string());
+        }
+        let event = TokenEvent { change, token };
+        for (wanted, hook) in &self.hooks {
+            if *wanted == change {
+                hook(&event);
+            }
+        }
+    }
+}
+
+impl Clone for TokenHooks {
+    fn clone(&self) -> Self {
+        TokenHooks {
+            hooks: self.hooks.clone(),
+            invalidated: Mutex::default(),
+        }
+    }
+}
+
+impl fmt::Debug for TokenHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenHooks")
+            .field("hooks", &self.hooks.len())
+            .finish()
+    }
+}
+
+/// Decodes unpadded base64url, as used by the parts of a JWT.
+fn decode_base64url(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in input.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return None,
+        };
+        buffer = buffer << 6 | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_decode_base64url() {
+        assert_eq!(Some(b"hello?>".to_vec()), decode_base64url("aGVsbG8_Pg"));
+        assert_eq!(Some(b"ab".to_vec()), decode_base64url("YWI="));
+        assert_eq!(None, decode_base64url("a.b"));
+    }
+}
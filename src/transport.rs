@@ -0,0 +1,357 @@
+//! The HTTP abstraction the client is built on.
+//!
+//! The crate does not ship an HTTP stack of its own; instead the client hands
+//! fully-formed requests to a [`Transport`](trait.Transport.html), so any HTTP
+//! library (or a fake, in tests) can be plugged in.
+use crate::error::Result;
+use crate::secret;
+use std::fmt;
+
+#[cfg(feature = "curl")]
+pub mod curl;
+pub mod vcr;
+
+/// The HTTP methods used by the API.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Method {
+    /// The method name as sent on the wire.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+        }
+    }
+}
+
+/// A request ready to be sent by a transport. Its `Debug` output redacts
+/// credential headers and the secrets in the body.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Request {
+    /// The HTTP method.
+    pub method: Method,
+
+    /// The absolute url, including any query string.
+    pub url: String,
+
+    /// Headers to send, in order.
+    pub headers: Vec<(String, String)>,
+
+    /// The encoded body, if any: JSON, unless `Content-Type` says
+    /// otherwise.
+    pub body: Option<Vec<u8>>,
+
+    /// The largest response body the caller accepts, in bytes. Transports
+    /// that can should stop reading once a body grows past it and fail with
+    /// `Error::TooLarge`; the client checks the body either way.
+    pub max_body_size: Option<usize>,
+}
+
+impl Request {
+    /// Looks up the first header with the given name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+impl fmt::Debug for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("method", &self.method)
+            .field("url", &self.url)
+            .field("headers", &secret::Headers(&self.headers))
+            .field("body", &self.body.as_deref().map(secret::Body))
+            .field("max_body_size", &self.max_body_size)
+            .finish()
+    }
+}
+
+/// A response as read by a transport. Its `Debug` output redacts cookies
+/// and the secrets in the body.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct Response {
+    /// The HTTP status code.
+    pub status: u16,
+
+    /// Response headers, in order.
+    pub headers: Vec<(String, String)>,
+
+    /// The raw response body.
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Looks up the first header with the given name, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Whether the status code is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Whether the response carries no payload: a `204 No Content`, or a
+    /// body of nothing but whitespace.
+    pub fn is_empty(&self) -> bool {
+        self.status == 204 || self.body.iter().all(u8::is_ascii_whitespace)
+    }
+
+    /// The media type the response declared, such as `application/json`,
+    /// without parameters like `charset`.
+    pub fn content_type(&self) -> Option<&str> {
+        let value = self.header("Content-Type")?;
+        Some(value.split(';').next().unwrap_or(value).trim())
+    }
+
+    /// Whether the response declared a JSON media type, including `+json`
+    /// types such as `application/problem+json`.
+    pub fn is_json(&self) -> bool {
+        self.content_type().is_some_and(|kind| {
+            let kind = kind.to_ascii_lowercase();
+            kind.ends_with("/json") || kind.ends_with("+json")
+        })
+    }
+
+    /// Whether the response says the service is unavailable rather than
+    /// answering: a `503`, or, with a success or server error status, an
+    /// HTML page or a JSON maintenance notice where the API sends its data.
+    /// Proxies and the maintenance page answer like this during outages.
+    pub fn is_unavailable(&self) -> bool {
+        if self.status == 503 {
+            return true;
+        }
+        if !self.is_success() && self.status < 500 {
+            return false;
+        }
+        let html = self
+            .content_type()
+            .is_some_and(|kind| kind.eq_ignore_ascii_case("text/html"));
+        html || self.body.trim_ascii_start().starts_with(b"<") || is_maintenance_notice(&self.body)
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("headers", &secret::Headers(&self.headers))
+            .field("body", &secret::Body(&self.body))
+            .finish()
+    }
+}
+
+/// Whether a body is a JSON object announcing maintenance, such as
+/// `{"maintenance":true}` or `{"message":"Down for maintenance"}`. Bodies
+/// that never mention maintenance are not parsed.
+fn is_maintenance_notice(body: &[u8]) -> bool {
+    const WORD: &[u8] = b"aintenance";
+    if !body.windows(WORD.len()).any(|window| window == WORD) {
+        return false;
+    }
+    let notice = match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Object(notice)) => notice,
+        _ => return false,
+    };
+    notice.get("maintenance") == Some(&serde_json::Value::Bool(true))
+        || ["status", "message", "error"].iter().any(|key| {
+            notice
+                .get(*key)
+                .and_then(serde_json::Value::as_str)
+                .is_some_and(|text| text.to_lowercase().contains("maintenance"))
+        })
+}
+
+/// Sends requests to the API.
+///
+/// Implementations should only report failures to deliver the request here;
+/// non-success status codes are returned as a normal `Response` and handled by
+/// the client.
+pub trait Transport {
+    /// Sends the request and returns the response.
+    fn send(&self, request: &Request) -> Result<Response>;
+
+    /// Sends several requests at once and returns their responses in the
+    /// same order. Transports that can multiplex them over a single
+    /// connection override this together with
+    /// [`multiplexes`](#method.multiplexes); the default sends them one
+    /// after another.
+    fn send_all(&self, requests: &[Request]) -> Vec<Result<Response>> {
+        requests.iter().map(|request| self.send(request)).collect()
+    }
+
+    /// Whether [`send_all`](#method.send_all) sends its requests
+    /// concurrently, so callers need not spread them over threads.
+    fn multiplexes(&self) -> bool {
+        false
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn send(&self, request: &Request) -> Result<Response> {
+        (**self).send(request)
+    }
+
+    fn send_all(&self, requests: &[Request]) -> Vec<Result<Response>> {
+        (**self).send_all(requests)
+    }
+
+    fn multiplexes(&self) -> bool {
+        (**self).multiplexes()
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        (**self).send(request)
+    }
+
+    fn send_all(&self, requests: &[Request]) -> Vec<Result<Response>> {
+        (**self).send_all(requests)
+    }
+
+    fn multiplexes(&self) -> bool {
+        (**self).multiplexes()
+    }
+}
+
+fn find_header<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Percent-encodes a query string component.
+pub(crate) fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Joins query parameters into a query string, without the leading `?`.
+pub(crate) fn query_string(query: &[(&str, String)]) -> String {
+    query
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// A transport that replays canned responses and records every request.
+    #[derive(Debug, Default)]
+    pub(crate) struct MockTransport {
+        responses: Mutex<VecDeque<Response>>,
+        requests: Mutex<Vec<Request>>,
+    }
+
+    impl MockTransport {
+        pub(crate) fn new() -> Self {
+            MockTransport::default()
+        }
+
+        /// Queues a response with the given status and JSON body.
+        pub(crate) fn respond(self, status: u16, body: &str) -> Self {
+            self.responses.lock().unwrap().push_back(Response {
+                status,
+                headers: vec![("Content-Type".into(), "application/json".into())],
+                body: body.as_bytes().to_vec(),
+            });
+            self
+        }
+
+        /// Sets a header on the response queued last, replacing any of the
+        /// same name.
+        pub(crate) fn with_header(self, name: &str, value: &str) -> Self {
+            if let Some(response) = self.responses.lock().unwrap().back_mut() {
+                response
+                    .headers
+                    .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+                response.headers.push((name.into(), value.into()));
+            }
+            self
+        }
+
+        /// All requests sent so far.
+        pub(crate) fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn send(&self, request: &Request) -> Result<Response> {
+            self.requests.lock().unwrap().push(request.clone());
+            self.responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| crate::error::Error::transport("no response queued"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_encode_query() {
+        let query = [
+            ("from", "2019-06-01T00:00:00+01:00".to_string()),
+            ("with[]", "photo".to_string()),
+        ];
+        assert_eq!(
+            "from=2019-06-01T00%3A00%3A00%2B01%3A00&with%5B%5D=photo",
+            query_string(&query)
+        );
+    }
+
+    #[test]
+    fn should_find_header_ignoring_case() {
+        let response = Response {
+            status: 200,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: vec![],
+        };
+        assert_eq!(Some("application/json"), response.header("content-type"));
+        assert_eq!(None, response.header("retry-after"));
+    }
+
+    #[test]
+    fn should_recognise_outage_pages() {
+        let response = |status, body: &str| Response {
+            status,
+            headers: vec![],
+            body: body.into(),
+        };
+        assert!(response(503, "{}").is_unavailable());
+        assert!(response(200, "\n<!DOCTYPE html><title>Down</title>").is_unavailable());
+        assert!(response(502, r#"{"status":"Scheduled Maintenance"}"#).is_unavailable());
+        assert!(response(200, r#"{"maintenance":true}"#).is_unavailable());
+        assert!(!response(200, r#"{"data":{"name":"Maintenance shed"}}"#).is_unavailable());
+        assert!(!response(404, "<html>Not found</html>").is_unavailable());
+        assert!(!response(500, "oops").is_unavailable());
+    }
+}
@@ -0,0 +1,552 @@
+//! A transport running the `curl` command, enabled with the `curl` feature.
+//!
+//! This gives the crate a working HTTPS stack without linking one, which
+//! suits scripts and the `spc` command line tool. Applications that send
+//! many requests should plug in a proper HTTP library instead.
+use super::{Request, Response, Transport};
+use crate::error::{Error, Result};
+use std::collections::hash_map::RandomState;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Room left for the status line and headers when reading a response with a
+/// body size limit.
+const HEADER_ALLOWANCE: usize = 16 * 1024;
+
+/// The exit code of curl when `--max-filesize` stops a download.
+const FILESIZE_EXCEEDED: i32 = 63;
+
+/// The exit code of curl when the server's key matches none of the pins.
+const PINNED_KEY_MISMATCH: i32 = 90;
+
+/// Numbers the scratch directories made at the same time.
+static SCRATCH_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sends requests by running `curl`.
+/// # Examples
+///
+/// ```no_run
+/// use sure_petcare::{client::SurePet, transport::curl::CurlTransport};
+/// let client = SurePet::new(CurlTransport::new(), "token");
+/// ```
+#[derive(Clone, Debug)]
+pub struct CurlTransport {
+    program: String,
+    timeout: u32,
+    max_streams: Option<u32>,
+    pinned_keys: Vec<String>,
+    ca_certificate: Option<PathBuf>,
+}
+
+impl Default for CurlTransport {
+    fn default() -> Self {
+        CurlTransport {
+            program: "curl".into(),
+            timeout: 30,
+            max_streams: None,
+            pinned_keys: Vec::new(),
+            ca_certificate: None,
+        }
+    }
+}
+
+impl CurlTransport {
+    /// Create a transport running `curl` from the `PATH` with a thirty
+    /// second timeout.
+    pub fn new() -> Self {
+        CurlTransport::default()
+    }
+
+    /// Sets the program to run, e.g. a full path to `curl`.
+    pub fn with_program<S: Into<String>>(mut self, program: S) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Sets the maximum time a request may take, in seconds.
+    pub fn with_timeout(mut self, seconds: u32) -> Self {
+        self.timeout = seconds;
+        self
+    }
+
+    /// Sends batches of requests from a single `curl --parallel` process,
+    /// multiplexed over one HTTP/2 connection with at most `max_streams` in
+    /// flight, instead of one process and TLS handshake per request. Needs
+    /// curl 7.66 or later.
+    pub fn with_multiplexing(mut self, max_streams: u32) -> Self {
+        self.max_streams = Some(max_streams.max(1));
+        self
+    }
+
+    /// Only talks to servers whose public key matches one of `hashes`, the
+    /// base64 SHA-256 digests of the SubjectPublicKeyInfo, as printed by
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der |
+    /// openssl dgst -sha256 -binary | base64`. A `sha256//` prefix is
+    /// accepted. Requests to any other server fail, as do requests when the
+    /// installed curl cannot check pins. Include a backup key so a
+    /// certificate rotation does not lock the client out.
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use sure_petcare::transport::curl::CurlTransport;
+    /// let transport = CurlTransport::new().with_pinned_keys(&[
+    ///     "primary+key+hash+in+base64+xxxxxxxxxxxxxx=",
+    ///     "sha256//backup+key+hash+in+base64+xxxxxxxxxxxxxxx=",
+    /// ]);
+    /// ```
+    pub fn with_pinned_keys<S: AsRef<str>>(mut self, hashes: &[S]) -> Self {
+        self.pinned_keys = hashes
+            .iter()
+            .map(|hash| {
+                let hash = hash.as_ref().trim();
+                hash.strip_prefix("sha256//").unwrap_or(hash).to_string()
+            })
+            .collect();
+        self
+    }
+
+    /// Trusts only the certificate authorities in the PEM file at `path`
+    /// instead of the system store, pinning the CA that issues the API's
+    /// certificate.
+    pub fn with_ca_certificate<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.ca_certificate = Some(path.into());
+        self
+    }
+
+    /// The arguments that check the server's certificate against the pins.
+    fn tls_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if !self.pinned_keys.is_empty() {
+            let pins: Vec<String> = self
+                .pinned_keys
+                .iter()
+                .map(|hash| format!("sha256//{}", hash))
+                .collect();
+            args.push("--pinnedpubkey".into());
+            args.push(pins.join(";").into());
+        }
+        if let Some(path) = &self.ca_certificate {
+            args.push("--cacert".into());
+            args.push(path.into());
+        }
+        args
+    }
+
+    /// Sends a batch through one curl process, returning an error only when
+    /// the batch could not be run at all.
+    fn send_batch(&self, requests: &[Request], max_streams: u32) -> Result<Vec<Result<Response>>> {
+        let dir = scratch_dir()?;
+        let responses = self.run_batch(&dir, requests, max_streams);
+        let _ = fs::remove_dir_all(&dir);
+        responses
+    }
+
+    fn run_batch(
+        &self,
+        dir: &Path,
+        requests: &[Request],
+        max_streams: u32,
+    ) -> Result<Vec<Result<Response>>> {
+        for (index, request) in requests.iter().enumerate() {
+            if let Some(body) = &request.body {
+                fs::write(file(dir, "request", index), body).map_err(Error::transport)?;
+            }
+        }
+        // The whole batch is read from stdin, keeping the headers out of the
+        // argument list other users can see.
+        let mut child = Command::new(&self.program)
+            .args(["--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(Error::transport)?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin
+            .write_all(self.batch_config(dir, requests, max_streams).as_bytes())
+            .map_err(Error::transport)?;
+        drop(stdin);
+        let output = child.wait_with_output().map_err(Error::transport)?;
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+        Ok(requests
+            .iter()
+            .enumerate()
+            .map(|(index, request)| {
+                let raw = fs::read(file(dir, "response", index)).map_err(|_| {
+                    Error::transport(
+                        match stderr.as_str() {
+                            "" => "no response from curl",
+                            stderr => stderr,
+                        }
+                        .to_string(),
+                    )
+                })?;
+                let response = parse(&raw)?;
+                match request.max_body_size {
+                    Some(limit) if response.body.len() > limit => Err(Error::TooLarge { limit }),
+                    _ => Ok(response),
+                }
+            })
+            .collect())
+    }
+
+    /// The curl config sending every request of a batch, each written to
+    /// its own file in `dir`.
+    fn batch_config(&self, dir: &Path, requests: &[Request], max_streams: u32) -> String {
+        let mut config = String::from("silent\nshow-error\nparallel\n");
+        config.push_str(&config_line("parallel-max", &max_streams.to_string()));
+        for (index, request) in requests.iter().enumerate() {
+            if index > 0 {
+                config.push_str("next\n");
+            }
+            config.push_str("http2\ninclude\nsuppress-connect-headers\n");
+            config.push_str(&config_line("max-time", &self.timeout.to_string()));
+            let tls = self.tls_args();
+            for pair in tls.chunks(2) {
+                config.push_str(&config_line(
+                    pair[0].to_string_lossy().trim_start_matches('-'),
+                    &pair[1].to_string_lossy(),
+                ));
+            }
+            config.push_str(&config_line("request", request.method.as_str()));
+            config.push_str(&header_config(request));
+            if request.body.is_some() {
+                let data = format!("@{}", file(dir, "request", index).display());
+                config.push_str(&config_line("data-binary", &data));
+            }
+            if let Some(limit) = request.max_body_size {
+                config.push_str(&config_line("max-filesize", &limit.to_string()));
+            }
+            let output = file(dir, "response", index);
+            config.push_str(&config_line("output", &output.display().to_string()));
+            config.push_str(&config_line("url", &request.url));
+        }
+        config
+    }
+}
+
+fn file(dir: &Path, kind: &str, index: usize) -> PathBuf {
+    dir.join(format!("{}-{}", kind, index))
+}
+
+/// Runs a single request's curl, writing its headers to stdin as a config.
+fn run(mut command: Command, request: &Request) -> Result<Response> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::transport)?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin
+        .write_all(header_config(request).as_bytes())
+        .map_err(Error::transport)?;
+    drop(stdin);
+
+    // Read at most one byte past the limit, so a server that does not
+    // announce the length is cut off instead of buffered.
+    let mut stdout = Vec::new();
+    let mut pipe = child.stdout.take().expect("stdout is piped");
+    let read = match request.max_body_size {
+        Some(limit) => (&mut pipe)
+            .take(limit.saturating_add(HEADER_ALLOWANCE) as u64 + 1)
+            .read_to_end(&mut stdout),
+        None => pipe.read_to_end(&mut stdout),
+    };
+    read.map_err(Error::transport)?;
+    if let Some(limit) = request.max_body_size {
+        if stdout.len() > limit.saturating_add(HEADER_ALLOWANCE) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Error::TooLarge { limit });
+        }
+    }
+
+    let output = child.wait_with_output().map_err(Error::transport)?;
+    if let (Some(limit), Some(FILESIZE_EXCEEDED)) = (request.max_body_size, output.status.code()) {
+        return Err(Error::TooLarge { limit });
+    }
+    if output.status.code() == Some(PINNED_KEY_MISMATCH) {
+        return Err(Error::transport(format!(
+            "the server's public key matches no pin: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    if !output.status.success() {
+        return Err(Error::transport(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let response = parse(&stdout)?;
+    match request.max_body_size {
+        Some(limit) if response.body.len() > limit => Err(Error::TooLarge { limit }),
+        _ => Ok(response),
+    }
+}
+
+/// The headers of a request as curl config lines.
+fn header_config(request: &Request) -> String {
+    request
+        .headers
+        .iter()
+        .map(|(name, value)| config_line("header", &format!("{}: {}", name, value)))
+        .collect()
+}
+
+/// A curl config line setting `option` to `value`, quoted so any character
+/// in the value is read back as it is.
+fn config_line(option: &str, value: &str) -> String {
+    let mut line = format!("{} = \"", option);
+    for c in value.chars() {
+        match c {
+            '\\' => line.push_str("\\\\"),
+            '"' => line.push_str("\\\""),
+            '\t' => line.push_str("\\t"),
+            '\n' => line.push_str("\\n"),
+            '\r' => line.push_str("\\r"),
+            '\x0b' => line.push_str("\\v"),
+            c => line.push(c),
+        }
+    }
+    line.push_str("\"\n");
+    line
+}
+
+/// Makes a directory only the current user can read, for the files of a
+/// request. The name is unpredictable and creating it fails if anything is
+/// already there, so another user cannot plant it beforehand.
+fn scratch_dir() -> Result<PathBuf> {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_usize(SCRATCH_DIRS.fetch_add(1, Ordering::Relaxed));
+    let dir = std::env::temp_dir().join(format!("sure-petcare-{:016x}", hasher.finish()));
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(&dir).map_err(Error::transport)?;
+    Ok(dir)
+}
+
+impl Transport for CurlTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(["--silent", "--show-error", "--include"])
+            .arg("--suppress-connect-headers")
+            .args(["--max-time", &self.timeout.to_string()])
+            .args(self.tls_args())
+            .args(["--request", request.method.as_str()]);
+        // The headers, the token among them, are read from stdin so they
+        // stay out of the argument list other users can see.
+        command.args(["--config", "-"]);
+        let dir = match &request.body {
+            Some(body) => {
+                let dir = scratch_dir()?;
+                let path = dir.join("request");
+                if let Err(error) = fs::write(&path, body) {
+                    let _ = fs::remove_dir_all(&dir);
+                    return Err(Error::transport(error));
+                }
+                let mut data = OsString::from("@");
+                data.push(path);
+                command.arg("--data-binary").arg(data);
+                Some(dir)
+            }
+            None => None,
+        };
+        if let Some(limit) = request.max_body_size {
+            command.args(["--max-filesize", &limit.to_string()]);
+        }
+        // Keep the url out of option parsing in case it starts with a dash.
+        command.arg("--").arg(&request.url);
+
+        let response = run(command, request);
+        if let Some(dir) = dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+        response
+    }
+
+    fn send_all(&self, requests: &[Request]) -> Vec<Result<Response>> {
+        let max_streams = match self.max_streams {
+            Some(max_streams) if requests.len() > 1 => max_streams,
+            _ => return requests.iter().map(|request| self.send(request)).collect(),
+        };
+        match self.send_batch(requests, max_streams) {
+            Ok(responses) => responses,
+            Err(error) => {
+                let message = error.to_string();
+                requests
+                    .iter()
+                    .map(|_| Err(Error::transport(message.clone())))
+                    .collect()
+            }
+        }
+    }
+
+    fn multiplexes(&self) -> bool {
+        self.max_streams.is_some()
+    }
+}
+
+/// Splits the output of `curl --include` into a response, skipping interim
+/// `1xx` responses and the answer of a proxy to `CONNECT`, should curl
+/// print it despite `--suppress-connect-headers`.
+fn parse(mut output: &[u8]) -> Result<Response> {
+    loop {
+        let end = output
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| Error::transport("incomplete response from curl"))?;
+        let head = String::from_utf8_lossy(&output[..end]);
+        output = &output[end + 4..];
+
+        let mut lines = head.split("\r\n");
+        let status = lines
+            .next()
+            .and_then(|line| line.split(' ').nth(1))
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| Error::transport(format!("unexpected status line in `{}`", head)))?;
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        if (100..200).contains(&status) {
+            continue;
+        }
+        // A tunnel's answer has no body, and the server's head follows it.
+        let sized = headers.iter().any(|(name, _)| {
+            name.eq_ignore_ascii_case("content-length")
+                || name.eq_ignore_ascii_case("transfer-encoding")
+        });
+        if (200..300).contains(&status) && !sized && output.starts_with(b"HTTP/") {
+            continue;
+        }
+        return Ok(Response {
+            status,
+            headers,
+            body: output.to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Method;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_parse_included_headers() {
+        let response = parse(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/2 201 \r\ncontent-type: application/json\r\nx-id: a:b\r\n\r\n{\"data\":{}}",
+        )
+        .unwrap();
+
+        assert_eq!(201, response.status);
+        assert_eq!(Some("a:b"), response.header("X-Id"));
+        assert_eq!(b"{\"data\":{}}".to_vec(), response.body);
+        assert!(parse(b"HTTP/1.1 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn should_skip_the_answer_of_a_proxy_to_connect() {
+        let response = parse(
+            b"HTTP/1.1 200 Connection established\r\n\r\n\
+              HTTP/2 401 \r\ncontent-type: application/json\r\ncontent-length: 2\r\n\r\n{}",
+        )
+        .unwrap();
+
+        assert_eq!(401, response.status);
+        assert_eq!(Some("application/json"), response.header("Content-Type"));
+        assert_eq!(b"{}".to_vec(), response.body);
+
+        let echoed = parse(b"HTTP/1.1 200 OK\r\ncontent-length: 9\r\n\r\nHTTP/1.1 ").unwrap();
+        assert_eq!(b"HTTP/1.1 ".to_vec(), echoed.body);
+    }
+
+    #[test]
+    fn should_pass_headers_as_quoted_config() {
+        let request = Request {
+            method: Method::Get,
+            url: "https://a.test/".into(),
+            headers: vec![
+                ("Authorization".into(), "Bearer se\"cr\\et".into()),
+                ("X-Note".into(), "a\tb".into()),
+            ],
+            body: None,
+            max_body_size: None,
+        };
+
+        assert_eq!(
+            "header = \"Authorization: Bearer se\\\"cr\\\\et\"\n\
+             header = \"X-Note: a\\tb\"\n",
+            header_config(&request)
+        );
+    }
+
+    #[test]
+    fn should_send_a_batch_as_parallel_transfers() {
+        let request = |url: &str| Request {
+            method: Method::Get,
+            url: url.into(),
+            headers: vec![("Accept".into(), "application/json".into())],
+            body: None,
+            max_body_size: None,
+        };
+        let transport = CurlTransport::new().with_multiplexing(4);
+        let config = transport.batch_config(
+            Path::new("/tmp/batch"),
+            &[request("https://a.test/1"), request("https://a.test/2")],
+            4,
+        );
+        let lines: Vec<&str> = config.lines().collect();
+
+        assert!(transport.multiplexes());
+        assert!(!config.contains("pinnedpubkey"));
+        assert_eq!(&["parallel", "parallel-max = \"4\""], &lines[2..4]);
+        assert_eq!(1, lines.iter().filter(|line| **line == "next").count());
+        assert_eq!(
+            2,
+            config
+                .matches("header = \"Accept: application/json\"")
+                .count()
+        );
+        assert_eq!(
+            &[
+                "output = \"/tmp/batch/response-1\"",
+                "url = \"https://a.test/2\""
+            ],
+            &lines[lines.len() - 2..]
+        );
+    }
+
+    #[test]
+    fn should_pass_pins_to_curl() {
+        let transport = CurlTransport::new()
+            .with_pinned_keys(&["sha256//AAAA", " BBBB "])
+            .with_ca_certificate("/etc/surepet/ca.pem");
+        let args = transport.tls_args();
+        let args: Vec<&str> = args.iter().map(|arg| arg.to_str().unwrap()).collect();
+
+        assert_eq!(
+            vec![
+                "--pinnedpubkey",
+                "sha256//AAAA;sha256//BBBB",
+                "--cacert",
+                "/etc/surepet/ca.pem"
+            ],
+            args
+        );
+    }
+}
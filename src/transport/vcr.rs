@@ -0,0 +1,223 @@
+//! Recording live traffic and replaying it in tests.
+//!
+//! Wrap a real transport in a [`Recorder`](struct.Recorder.html) once to
+//! capture a session into a cassette file, then serve the file with a
+//! [`Replayer`](struct.Replayer.html) so tests run offline and give the
+//! same answers every time. Tokens, passwords and email addresses are
+//! redacted before anything is written, so cassettes can be committed.
+use super::{Request, Response, Transport};
+use crate::error::{Error, Result};
+use crate::secret::{self, redact_body};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// What replaces redacted values.
+pub const REDACTED: &str = secret::REDACTED;
+
+/// Response headers worth keeping in a cassette.
+const KEPT_HEADERS: &[&str] = &["content-type", "retry-after", "etag", "last-modified"];
+
+/// One recorded request and its response.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Interaction {
+    /// The request method, e.g. `GET`.
+    pub method: String,
+    /// The request url.
+    pub url: String,
+    /// The redacted request body.
+    #[serde(default)]
+    pub request_body: Option<String>,
+    /// The response status code.
+    pub status: u16,
+    /// The response headers kept.
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// The redacted response body.
+    pub body: String,
+}
+
+/// The contents of a cassette file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Cassette {
+    /// The interactions in the order they happened.
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Reads a cassette file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        serde_json::from_slice(&contents)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// Writes the cassette file, replacing any previous contents.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let contents = serde_json::to_vec_pretty(self)?;
+        fs::write(path, contents)
+    }
+}
+
+/// A transport recording everything another transport does to a cassette
+/// file, which is rewritten after each request.
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport>(live: T) -> sure_petcare::error::Result<()> {
+/// use sure_petcare::{client::SurePet, transport::vcr::Recorder};
+/// let client = SurePet::new(Recorder::new(live, "tests/cassettes/start.json"), "token");
+/// client.start()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder<T> {
+    inner: T,
+    path: PathBuf,
+    cassette: Mutex<Cassette>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Records the requests sent through `inner` to `path`.
+    pub fn new<P: Into<PathBuf>>(inner: T, path: P) -> Self {
+        Recorder {
+            inner,
+            path: path.into(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Recorder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("path", &self.path)
+            .field(
+                "interactions",
+                &self.cassette.lock().unwrap().interactions.len(),
+            )
+            .finish()
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let response = self.inner.send(request)?;
+        let mut cassette = self.cassette.lock().unwrap();
+        cassette.interactions.push(Interaction {
+            method: request.method.as_str().to_string(),
+            url: request.url.clone(),
+            request_body: request.body.as_deref().map(redact_body),
+            status: response.status,
+            headers: response
+                .headers
+                .iter()
+                .filter(|(name, _)| KEPT_HEADERS.contains(&name.to_ascii_lowercase().as_str()))
+                .cloned()
+                .collect(),
+            body: redact_body(&response.body),
+        });
+        cassette.save(&self.path).map_err(Error::transport)?;
+        Ok(response)
+    }
+}
+
+/// A transport answering from a cassette. Each request gets the first
+/// unused interaction with the same method and url.
+#[derive(Debug)]
+pub struct Replayer {
+    interactions: Mutex<Vec<Option<Interaction>>>,
+}
+
+impl Replayer {
+    /// Replays the cassette file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Replayer::new(Cassette::load(path)?))
+    }
+
+    /// Replays a cassette already in memory.
+    pub fn new(cassette: Cassette) -> Self {
+        Replayer {
+            interactions: Mutex::new(cassette.interactions.into_iter().map(Some).collect()),
+        }
+    }
+
+    /// How many interactions have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.interactions.lock().unwrap().iter().flatten().count()
+    }
+}
+
+impl Transport for Replayer {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let method = request.method.as_str();
+        let mut interactions = self.interactions.lock().unwrap();
+        let interaction = interactions
+            .iter_mut()
+            .find(|slot| {
+                slot.as_ref().is_some_and(|interaction| {
+                    interaction.method == method && interaction.url == request.url
+                })
+            })
+            .and_then(Option::take)
+            .ok_or_else(|| {
+                Error::transport(format!(
+                    "no recorded response for {} {}",
+                    method, request.url
+                ))
+            })?;
+        Ok(Response {
+            status: interaction.status,
+            headers: interaction.headers,
+            body: interaction.body.into_bytes(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::SurePet;
+    use crate::login;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_record_redacted_and_replay() {
+        let path = std::env::temp_dir().join(format!("surepet-vcr-{}.json", std::process::id()));
+        let live = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"user":{"email_address":"me@example.com"},"token":"secret"}}"#,
+            )
+            .respond(200, r#"{"data":{"households":[{"id":1,"name":"Home"}]}}"#);
+        let request = login::RequestBuilder::new()
+            .with_email_address("me@example.com")
+            .with_password("qwerty123")
+            .with_device_id("device")
+            .build();
+        let client = SurePet::login(Recorder::new(&live, &path), &request).unwrap();
+        client.start().unwrap();
+
+        let cassette = Cassette::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            Some(r#"{"device_id":"device","email_address":"REDACTED","password":"REDACTED"}"#),
+            cassette.interactions[0].request_body.as_deref()
+        );
+        assert_eq!(
+            r#"{"data":{"token":"REDACTED","user":{"email_address":"REDACTED"}}}"#,
+            cassette.interactions[0].body
+        );
+
+        let replayer = Replayer::new(cassette);
+        let client = SurePet::login(&replayer, &request).unwrap();
+        assert_eq!(REDACTED, client.token());
+        assert_eq!("Home", client.start().unwrap().households()[0].name());
+        assert_eq!(0, replayer.remaining());
+        assert!(client.start().is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Weight and food intake trends derived from feeder data.
+//!
+//! Feeders weigh what is left in their bowls, so daily consumption is a good
+//! proxy for appetite. Pet weights are only recorded now and again, so they
+//! are carried forward to fill the days between readings.
+use crate::report::DailyFeeding;
+use crate::time::{Date, Timestamp};
+use serde::Serialize;
+
+/// One day of a pet's trend.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TrendPoint {
+    date: Date,
+    consumed: f64,
+    baseline: Option<f64>,
+    weight: Option<f64>,
+    intake_drop: bool,
+}
+
+impl TrendPoint {
+    /// The day.
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Grams eaten on the day.
+    pub fn consumed(&self) -> f64 {
+        self.consumed
+    }
+
+    /// The average grams eaten per day over the preceding window, `None`
+    /// until at least one earlier day is available.
+    pub fn baseline(&self) -> Option<f64> {
+        self.baseline
+    }
+
+    /// The most recent known weight of the pet in kilograms.
+    pub fn weight(&self) -> Option<f64> {
+        self.weight
+    }
+
+    /// Whether intake fell significantly below the baseline.
+    pub fn intake_drop(&self) -> bool {
+        self.intake_drop
+    }
+}
+
+/// A builder for a pet's weight and intake trend.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::trend::TrendBuilder;
+/// let trend = TrendBuilder::new()
+///     .with_window(3)
+///     .with_drop_threshold(0.5)
+///     .with_weight("2019-06-01T08:00:00Z".parse().unwrap(), 4.2)
+///     .build(&[]);
+/// assert!(trend.is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrendBuilder {
+    window: usize,
+    drop_threshold: f64,
+    weights: Vec<(Timestamp, f64)>,
+}
+
+impl Default for TrendBuilder {
+    fn default() -> Self {
+        TrendBuilder {
+            window: 7,
+            drop_threshold: 0.25,
+            weights: Vec::new(),
+        }
+    }
+}
+
+impl TrendBuilder {
+    /// Create a new builder with a seven day window and a 25% drop threshold.
+    pub fn new() -> Self {
+        TrendBuilder::default()
+    }
+
+    /// Sets how many preceding days make up the baseline.
+    pub fn with_window(&mut self, days: usize) -> &mut Self {
+        self.window = days.max(1);
+        self
+    }
+
+    /// Sets the fraction below the baseline at which a day is flagged, e.g.
+    /// `0.25` flags days where the pet ate less than 75% of its usual amount.
+    pub fn with_drop_threshold(&mut self, fraction: f64) -> &mut Self {
+        self.drop_threshold = fraction;
+        self
+    }
+
+    /// Adds a weight reading in kilograms, such as a pet's `weight` field.
+    pub fn with_weight(&mut self, at: Timestamp, kilograms: f64) -> &mut Self {
+        self.weights.push((at, kilograms));
+        self
+    }
+
+    /// Builds the trend from per day feeding totals, which should be in date
+    /// order without gaps, as returned by `Reports::feeding_daily`.
+    pub fn build(&self, daily: &[DailyFeeding]) -> Vec<TrendPoint> {
+        let mut weights = self.weights.clone();
+        weights.sort_by_key(|(at, _)| *at);
+
+        daily
+            .iter()
+            .enumerate()
+            .map(|(i, day)| {
+                let previous = &daily[i.saturating_sub(self.window)..i];
+                let baseline = if previous.is_empty() {
+                    None
+                } else {
+                    Some(
+                        previous.iter().map(DailyFeeding::consumed).sum::<f64>()
+                            / previous.len() as f64,
+                    )
+                };
+                let day_end = day.date().add_days(1).midnight();
+                let weight = weights
+                    .iter()
+                    .take_while(|(at, _)| *at < day_end)
+                    .last()
+                    .map(|(_, kilograms)| *kilograms);
+                TrendPoint {
+                    date: day.date(),
+                    consumed: day.consumed(),
+                    baseline,
+                    weight,
+                    intake_drop: baseline.is_some_and(|baseline| {
+                        baseline > 0.0 && day.consumed() < baseline * (1.0 - self.drop_threshold)
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Feeding;
+    use crate::time::DateRange;
+    use pretty_assertions::assert_eq;
+
+    fn daily(grams: &[f64]) -> Vec<DailyFeeding> {
+        let first = Date::new(2019, 6, 1).unwrap();
+        let datapoints: Vec<Feeding> = grams
+            .iter()
+            .enumerate()
+            .map(|(i, grams)| {
+                let from = first.add_days(i as i64).midnight().unix() + 3600;
+                serde_json::from_value(serde_json::json!({
+                    "from": Timestamp::from_unix(from),
+                    "to": Timestamp::from_unix(from + 60),
+                    "duration": 60,
+                    "context": 1,
+                    "weights": [{"weight": 10.0, "change": -grams}],
+                }))
+                .unwrap()
+            })
+            .collect();
+        let range = DateRange::new(first, first.add_days(grams.len() as i64 - 1)).unwrap();
+        DailyFeeding::from_datapoints(&datapoints, range)
+    }
+
+    #[test]
+    fn should_flag_drops_against_baseline() {
+        let trend = TrendBuilder::new()
+            .with_window(2)
+            .build(&daily(&[40.0, 40.0, 35.0, 20.0]));
+
+        assert_eq!(None, trend[0].baseline());
+        assert_eq!(Some(40.0), trend[2].baseline());
+        assert!(!trend[2].intake_drop());
+        assert_eq!(Some(37.5), trend[3].baseline());
+        assert!(trend[3].intake_drop());
+    }
+
+    #[test]
+    fn should_carry_weights_forward() {
+        let trend = TrendBuilder::new()
+            .with_weight("2019-06-02T18:00:00Z".parse().unwrap(), 4.5)
+            .with_weight("2019-06-01T07:00:00Z".parse().unwrap(), 4.4)
+            .build(&daily(&[40.0, 40.0, 40.0]));
+
+        assert_eq!(
+            vec![Some(4.4), Some(4.5), Some(4.5)],
+            trend.iter().map(TrendPoint::weight).collect::<Vec<_>>()
+        );
+    }
+}
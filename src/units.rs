@@ -0,0 +1,157 @@
+//! Metric and imperial units for food and water amounts.
+//!
+//! The API reports food in grams and water in millilitres. The helpers here
+//! convert those to US customary ounces and fluid ounces, and
+//! [`Units`](enum.Units.html) picks which of the two the formatting
+//! functions in [`digest`](../digest/index.html) and
+//! [`export`](../export/index.html) use.
+use crate::error::ParseNameError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Grams in an avoirdupois ounce.
+pub const GRAMS_PER_OUNCE: f64 = 28.349_523_125;
+
+/// Millilitres in a US fluid ounce.
+pub const MILLILITRES_PER_FLUID_OUNCE: f64 = 29.573_529_562_5;
+
+/// Converts grams to ounces.
+pub fn grams_to_ounces(grams: f64) -> f64 {
+    grams / GRAMS_PER_OUNCE
+}
+
+/// Converts ounces to grams.
+pub fn ounces_to_grams(ounces: f64) -> f64 {
+    ounces * GRAMS_PER_OUNCE
+}
+
+/// Converts millilitres to US fluid ounces.
+pub fn millilitres_to_fluid_ounces(millilitres: f64) -> f64 {
+    millilitres / MILLILITRES_PER_FLUID_OUNCE
+}
+
+/// Converts US fluid ounces to millilitres.
+pub fn fluid_ounces_to_millilitres(fluid_ounces: f64) -> f64 {
+    fluid_ounces * MILLILITRES_PER_FLUID_OUNCE
+}
+
+/// The units amounts are shown in.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::units::Units;
+/// assert_eq!("12.3 g", Units::Metric.format_weight(12.34));
+/// assert_eq!("0.44 oz", Units::Imperial.format_weight(12.34));
+/// assert_eq!("1.01 fl oz", Units::Imperial.format_volume(30.0));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Units {
+    /// Grams and millilitres, as the API reports them.
+    #[default]
+    Metric,
+    /// Ounces and US fluid ounces.
+    Imperial,
+}
+
+impl Units {
+    /// A food weight in grams, converted to these units.
+    pub fn weight(self, grams: f64) -> f64 {
+        match self {
+            Units::Metric => grams,
+            Units::Imperial => grams_to_ounces(grams),
+        }
+    }
+
+    /// A water volume in millilitres, converted to these units.
+    pub fn volume(self, millilitres: f64) -> f64 {
+        match self {
+            Units::Metric => millilitres,
+            Units::Imperial => millilitres_to_fluid_ounces(millilitres),
+        }
+    }
+
+    /// The symbol of the weight unit, `g` or `oz`.
+    pub fn weight_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "g",
+            Units::Imperial => "oz",
+        }
+    }
+
+    /// The symbol of the volume unit, `ml` or `fl oz`.
+    pub fn volume_symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "ml",
+            Units::Imperial => "fl oz",
+        }
+    }
+
+    /// A weight in grams as text, such as `12.3 g` or `0.44 oz`.
+    pub fn format_weight(self, grams: f64) -> String {
+        format!(
+            "{} {}",
+            self.round(self.weight(grams)),
+            self.weight_symbol()
+        )
+    }
+
+    /// A volume in millilitres as text, such as `15 ml` or `0.51 fl oz`.
+    pub fn format_volume(self, millilitres: f64) -> String {
+        format!(
+            "{} {}",
+            self.round(self.volume(millilitres)),
+            self.volume_symbol()
+        )
+    }
+
+    /// Rounds an amount in these units to the precision worth showing: a
+    /// tenth of a gram or millilitre, or a hundredth of an ounce.
+    pub(crate) fn round(self, amount: f64) -> f64 {
+        let scale = match self {
+            Units::Metric => 10.0,
+            Units::Imperial => 100.0,
+        };
+        (amount * scale).round() / scale
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Units::Metric => "metric",
+            Units::Imperial => "imperial",
+        })
+    }
+}
+
+impl FromStr for Units {
+    type Err = ParseNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "metric" => Ok(Units::Metric),
+            "imperial" | "us" => Ok(Units::Imperial),
+            _ => Err(ParseNameError::new("units", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_convert_both_ways() {
+        assert_eq!(1.0, grams_to_ounces(GRAMS_PER_OUNCE));
+        assert_eq!(100.0, ounces_to_grams(grams_to_ounces(100.0)).round());
+        assert_eq!(
+            250.0,
+            fluid_ounces_to_millilitres(millilitres_to_fluid_ounces(250.0)).round()
+        );
+        assert_eq!("15 ml", Units::Metric.format_volume(15.0));
+        assert_eq!("0.51 fl oz", Units::Imperial.format_volume(15.0));
+        assert_eq!(Ok(Units::Imperial), " Imperial".parse());
+        assert!("furlongs".parse::<Units>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Reporting enum codes the crate does not recognise.
+//!
+//! Codes the models have no variant for, such as the product id of new
+//! hardware, are kept as the `Unknown` variant with the raw code. A hook set
+//! with [`set_hook`](fn.set_hook.html) is told about each such code the first
+//! time one is deserialized, so it can be logged and looked into rather than
+//! passing unnoticed.
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeSet;
+use std::sync::{Mutex, RwLock};
+
+/// A code deserialized into an `Unknown` variant.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct UnknownCode {
+    kind: &'static str,
+    code: i64,
+}
+
+impl UnknownCode {
+    /// What the code is, such as `product` or `event kind`.
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// The raw code.
+    pub fn code(&self) -> i64 {
+        self.code
+    }
+}
+
+type Hook = Box<dyn Fn(&UnknownCode) + Send + Sync>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+static REPORTED: Mutex<BTreeSet<UnknownCode>> = Mutex::new(BTreeSet::new());
+
+/// Calls `hook` once for every distinct unknown code deserialized from now
+/// on, replacing any hook set before. Codes are only counted as reported
+/// while a hook is set. The hook must not set or clear the hook itself.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::device::Product;
+/// sure_petcare::unknown::set_hook(|unknown| {
+///     eprintln!("unrecognised {} {}, please report it", unknown.kind(), unknown.code());
+/// });
+/// let product: Product = serde_json::from_str("42").unwrap();
+/// assert_eq!(Product::Unknown(42), product);
+/// sure_petcare::unknown::clear_hook();
+/// ```
+pub fn set_hook<F: Fn(&UnknownCode) + Send + Sync + 'static>(hook: F) {
+    let hook: Hook = Box::new(hook);
+    match HOOK.write() {
+        Ok(mut current) => *current = Some(hook),
+        Err(poisoned) => *poisoned.into_inner() = Some(hook),
+    }
+}
+
+/// Removes the hook.
+pub fn clear_hook() {
+    match HOOK.write() {
+        Ok(mut current) => *current = None,
+        Err(poisoned) => *poisoned.into_inner() = None,
+    }
+}
+
+/// A code enum with an `Unknown(i64)` variant, implemented by `names!`.
+pub(crate) trait Coded: From<i64> {
+    /// The name of the enum in messages.
+    const KIND: &'static str;
+
+    /// The code of an `Unknown` value.
+    fn unknown_code(&self) -> Option<i64>;
+}
+
+/// Deserializes a code enum from its number, reporting unknown codes.
+pub(crate) fn deserialize<'de, T: Coded, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    let value = T::from(i64::deserialize(deserializer)?);
+    if let Some(code) = value.unknown_code() {
+        report(UnknownCode {
+            kind: T::KIND,
+            code,
+        });
+    }
+    Ok(value)
+}
+
+fn report(unknown: UnknownCode) {
+    let hook = match HOOK.read() {
+        Ok(hook) => hook,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let hook = match hook.as_ref() {
+        Some(hook) => hook,
+        None => return,
+    };
+    let first = match REPORTED.lock() {
+        Ok(mut reported) => reported.insert(unknown),
+        Err(poisoned) => poisoned.into_inner().insert(unknown),
+    };
+    if first {
+        hook(&unknown);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{LockMode, Product};
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+
+    #[test]
+    fn should_report_each_unknown_code_once() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        set_hook(move |unknown| sink.lock().unwrap().push(*unknown));
+        let products: Vec<Product> = serde_json::from_str("[6, 9917, 9917]").unwrap();
+        let mode: LockMode = serde_json::from_str("9917").unwrap();
+        clear_hook();
+        let _: Product = serde_json::from_str("9918").unwrap();
+
+        assert_eq!(Product::Unknown(9917), products[1]);
+        assert_eq!(LockMode::Unknown(9917), mode);
+        let seen: Vec<(&str, i64)> = seen
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|unknown| unknown.code() > 9900)
+            .map(|unknown| (unknown.kind(), unknown.code()))
+            .collect();
+        assert_eq!(vec![("product", 9917), ("lock mode", 9917)], seen);
+    }
+}
@@ -0,0 +1,667 @@
+//! Calling handlers as things happen in the household.
+//!
+//! A [`Watcher`](struct.Watcher.html) keeps a
+//! [`StateSync`](../sync/struct.StateSync.html) up to date on a schedule and
+//! calls the handlers registered for each kind of change: pets coming and
+//! going, meals and drinks, and devices dropping offline or running low on
+//! battery. Pet events come from the timeline on every poll; device status
+//! is only reported in the start state, which is fetched again every few
+//! polls. Water dispensers are followed by a
+//! [`WaterTracker`](../water/struct.WaterTracker.html), queryable through
+//! [`Watcher::water`](struct.Watcher.html#method.water). With the `pets`
+//! feature, the watcher can also write each pet's position back to the API
+//! as flaps see it move, for accounts where the app's position goes stale.
+//! Every alert the watcher's monitors raise, and one for each animal without
+//! a known tag trying a flap, can also be sent to
+//! [`AlertSink`](../alert/trait.AlertSink.html)s.
+use crate::alert::{Alert, AlertSink};
+use crate::battery::{BatteryMonitor, Calibration};
+use crate::client::SurePet;
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::id::DeviceId;
+use crate::offline::OfflineMonitor;
+use crate::pet::{Location, Pet, Position, Visit};
+use crate::sync::{Change, StateSync};
+use crate::time::Timestamp;
+use crate::timeline::EventKind;
+use crate::transport::Transport;
+use crate::water::{WaterLevel, WaterTracker};
+use std::fmt;
+use std::mem;
+use std::thread;
+use std::time::Duration;
+
+type PetHandler<'h, T> = Box<dyn FnMut(&Pet<'static>, &T) + 'h>;
+type DeviceHandler<'h> = Box<dyn FnMut(&Device<'static>) + 'h>;
+type BatteryHandler<'h> = Box<dyn FnMut(&Device<'static>, f64) + 'h>;
+type WaterHandler<'h> = Box<dyn FnMut(&Device<'static>, &WaterLevel) + 'h>;
+type ErrorHandler<'h> = Box<dyn FnMut(&Error) + 'h>;
+
+/// Polls the API and calls the handlers registered for what changed, see
+/// the module documentation.
+/// # Examples
+///
+/// ```no_run
+/// # fn run<T: sure_petcare::transport::Transport + Send + Sync>(client: sure_petcare::client::SurePet<T>) {
+/// use std::time::Duration;
+/// use sure_petcare::watcher::Watcher;
+/// Watcher::new()
+///     .with_interval(Duration::from_secs(30))
+///     .on_pet_entered(|pet, _| println!("{} came in", pet.name()))
+///     .on_pet_exited(|pet, _| println!("{} went out", pet.name()))
+///     .on_battery_low(|device, percent| println!("{} is at {:.0}%", device.name(), percent))
+///     .on_error(|error| eprintln!("{}", error))
+///     .run(&client);
+/// # }
+/// ```
+pub struct Watcher<'h> {
+    interval: Duration,
+    status_every: u32,
+    battery: BatteryMonitor,
+    sync: Option<StateSync>,
+    polls: u32,
+    #[cfg(feature = "pets")]
+    reconcile: bool,
+    offline: OfflineMonitor,
+    water: WaterTracker,
+    entered: Vec<PetHandler<'h, Position>>,
+    exited: Vec<PetHandler<'h, Position>>,
+    feeding: Vec<PetHandler<'h, Visit>>,
+    drinking: Vec<PetHandler<'h, Visit>>,
+    device_offline: Vec<DeviceHandler<'h>>,
+    device_online: Vec<DeviceHandler<'h>>,
+    intruder: Vec<DeviceHandler<'h>>,
+    battery_low: Vec<BatteryHandler<'h>>,
+    low_water: Vec<WaterHandler<'h>>,
+    stale_water: Vec<WaterHandler<'h>>,
+    errors: Vec<ErrorHandler<'h>>,
+    sinks: Vec<Box<dyn AlertSink + 'h>>,
+}
+
+impl<'h> Default for Watcher<'h> {
+    fn default() -> Self {
+        Watcher {
+            interval: Duration::from_secs(60),
+            status_every: 5,
+            battery: BatteryMonitor::new(),
+            sync: None,
+            polls: 0,
+            #[cfg(feature = "pets")]
+            reconcile: false,
+            offline: OfflineMonitor::default(),
+            water: WaterTracker::default(),
+            entered: Vec::new(),
+            exited: Vec::new(),
+            feeding: Vec::new(),
+            drinking: Vec::new(),
+            device_offline: Vec::new(),
+            device_online: Vec::new(),
+            intruder: Vec::new(),
+            battery_low: Vec::new(),
+            low_water: Vec::new(),
+            stale_water: Vec::new(),
+            errors: Vec::new(),
+            sinks: Vec::new(),
+        }
+    }
+}
+
+impl<'h> Watcher<'h> {
+    /// Creates a new watcher polling every minute, checking device status
+    /// every fifth poll and warning below 25% battery until back
+    /// above 35%.
+    pub fn new() -> Self {
+        Watcher::default()
+    }
+
+    /// Sets how long [`run`](#method.run) waits between polls.
+    pub fn with_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets how many polls pass between fetches of the start state, which
+    /// is where device status comes from.
+    pub fn with_status_every(&mut self, polls: u32) -> &mut Self {
+        self.status_every = polls.max(1);
+        self
+    }
+
+    /// Sets the battery percentage below which a device counts as low,
+    /// clearing ten points above it.
+    pub fn with_battery_threshold(&mut self, percent: f64) -> &mut Self {
+        self.battery = mem::take(&mut self.battery).with_threshold(percent, percent + 10.0);
+        self
+    }
+
+    /// Sets the discharge curve battery percentages are worked out on.
+    pub fn with_calibration(&mut self, calibration: Calibration) -> &mut Self {
+        self.battery = mem::take(&mut self.battery).with_calibration(calibration);
+        self
+    }
+
+    /// Sets the monitor batteries are checked with, for per-device
+    /// thresholds and calibrations.
+    pub fn with_battery_monitor(&mut self, battery: BatteryMonitor) -> &mut Self {
+        self.battery = battery;
+        self
+    }
+
+    /// Sets the position of a pet through the API whenever a flap sees it
+    /// go in or out, correcting the app when it still shows the pet where
+    /// it was days ago. Movements without a clear direction, such as a pet
+    /// that looked through the flap, are left alone. Off by default.
+    #[cfg(feature = "pets")]
+    pub fn with_location_reconciliation(&mut self, reconcile: bool) -> &mut Self {
+        self.reconcile = reconcile;
+        self
+    }
+
+    /// Sends every alert the watcher raises to `sink` as well as to the
+    /// handlers. Alerts the sink fails to deliver go to the error handlers
+    /// and are not sent again.
+    pub fn with_alert_sink<S: AlertSink + 'h>(&mut self, sink: S) -> &mut Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Sets the monitor devices dropping offline are noticed with, for
+    /// another grace period than the default of fifteen minutes.
+    pub fn with_offline_monitor(&mut self, offline: OfflineMonitor) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Sets the tracker water dispensers are followed with, for other
+    /// thresholds than the defaults of 200ml and two days.
+    pub fn with_water_tracker(&mut self, water: WaterTracker) -> &mut Self {
+        self.water = water;
+        self
+    }
+
+    /// Calls `handler` when a pet comes in, with its new position.
+    pub fn on_pet_entered<F: FnMut(&Pet<'static>, &Position) + 'h>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.entered.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a pet goes out, with its new position.
+    pub fn on_pet_exited<F: FnMut(&Pet<'static>, &Position) + 'h>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.exited.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a pet eats from a feeder.
+    pub fn on_feeding<F: FnMut(&Pet<'static>, &Visit) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.feeding.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a pet drinks from a water dispenser.
+    pub fn on_drinking<F: FnMut(&Pet<'static>, &Visit) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.drinking.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a device has been offline for the grace period
+    /// of the [`OfflineMonitor`](../offline/struct.OfflineMonitor.html), and
+    /// again only after it has come back. Devices already offline that long
+    /// when watching starts are reported on the first poll.
+    pub fn on_device_offline<F: FnMut(&Device<'static>) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.device_offline.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a device reported offline comes back.
+    pub fn on_device_online<F: FnMut(&Device<'static>) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.device_online.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` with the battery percentage when a device drops
+    /// below its threshold, and again only once it has been back above the
+    /// clearing level, as after fitting new cells.
+    pub fn on_battery_low<F: FnMut(&Device<'static>, f64) + 'h>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.battery_low.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when an animal without a registered tag tries a
+    /// flap.
+    pub fn on_intruder<F: FnMut(&Device<'static>) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.intruder.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a water dispenser runs low, and again only
+    /// after it has been topped up above the threshold.
+    pub fn on_low_water<F: FnMut(&Device<'static>, &WaterLevel) + 'h>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.low_water.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when the water in a dispenser gets too old, and
+    /// again only after it has been changed.
+    pub fn on_stale_water<F: FnMut(&Device<'static>, &WaterLevel) + 'h>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.stale_water.push(Box::new(handler));
+        self
+    }
+
+    /// Calls `handler` when a poll made by [`run`](#method.run) fails.
+    pub fn on_error<F: FnMut(&Error) + 'h>(&mut self, handler: F) -> &mut Self {
+        self.errors.push(Box::new(handler));
+        self
+    }
+
+    /// The state as of the last poll, `None` before the first.
+    pub fn sync(&self) -> Option<&StateSync> {
+        self.sync.as_ref()
+    }
+
+    /// The water dispensers as of the last poll.
+    pub fn water(&self) -> &WaterTracker {
+        &self.water
+    }
+
+    /// Polls once and calls the handlers for what changed. The first poll
+    /// fetches the start state, so it only reports devices that are
+    /// already offline or low.
+    pub fn poll<T: Transport + Send + Sync>(&mut self, client: &SurePet<T>) -> Result<()> {
+        let water = &mut self.water;
+        let mut alerts = Vec::new();
+        let mut intruders = Vec::new();
+        let changes = match &mut self.sync {
+            Some(sync) => sync.sync_with(client, |event| {
+                alerts.extend(water.apply(event));
+                if event.kind() == EventKind::IntruderMovement {
+                    let flaps = event.movements().iter().filter_map(|m| m.device_id());
+                    intruders.extend(flaps.map(|device_id| (device_id, event.created_at())));
+                }
+            })?,
+            None => {
+                let sync = StateSync::start(client)?;
+                let devices = sync.state().devices().to_vec();
+                self.sync = Some(sync);
+                self.check_devices(&devices, client.clock().now());
+                return Ok(());
+            }
+        };
+        self.dispatch(&changes);
+        #[cfg(feature = "pets")]
+        let reconciled = self.reconcile(client, &changes);
+        alerts.extend(self.water.check(client.clock().now()));
+        alerts.extend(self.intruder_alerts(&intruders));
+        self.dispatch_alerts(&alerts);
+        self.polls += 1;
+        if self.polls.is_multiple_of(self.status_every) {
+            let state = client.start()?;
+            self.check_devices(state.devices(), client.clock().now());
+        }
+        self.flush();
+        #[cfg(feature = "pets")]
+        reconciled?;
+        Ok(())
+    }
+
+    /// Polls on the interval for as long as the process runs. Failed polls
+    /// go to the [`on_error`](#method.on_error) handlers, and while the
+    /// service is unavailable the watcher waits as long as it asks.
+    pub fn run<T: Transport + Send + Sync>(&mut self, client: &SurePet<T>) -> ! {
+        loop {
+            thread::sleep(self.step(client));
+        }
+    }
+
+    /// Polls once like [`run`](#method.run) does, sending a failure to the
+    /// error handlers, and returns how long to wait before the next poll.
+    pub fn step<T: Transport + Send + Sync>(&mut self, client: &SurePet<T>) -> Duration {
+        match self.poll(client) {
+            Ok(()) => self.interval,
+            Err(error) => {
+                self.errors.iter_mut().for_each(|handler| handler(&error));
+                match error {
+                    Error::ServiceUnavailable { retry_after } => {
+                        retry_after.unwrap_or_default().max(self.interval)
+                    }
+                    _ => self.interval,
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, changes: &[Change]) {
+        let state = match &self.sync {
+            Some(sync) => sync.state(),
+            None => return,
+        };
+        for change in changes {
+            let pet = match state.pet(change.pet_id()) {
+                Some(pet) => pet,
+                None => continue,
+            };
+            match change {
+                Change::Moved { position, .. } => {
+                    let handlers = match position.location() {
+                        Location::Inside => &mut self.entered,
+                        Location::Outside => &mut self.exited,
+                        Location::Unknown(_) => continue,
+                    };
+                    handlers
+                        .iter_mut()
+                        .for_each(|handler| handler(pet, position));
+                }
+                Change::Ate { visit, .. } => {
+                    self.feeding
+                        .iter_mut()
+                        .for_each(|handler| handler(pet, visit));
+                }
+                Change::Drank { visit, .. } => {
+                    self.drinking
+                        .iter_mut()
+                        .for_each(|handler| handler(pet, visit));
+                }
+            }
+        }
+    }
+
+    /// Sends the positions of pets that moved, carrying on past failures and
+    /// returning the first.
+    #[cfg(feature = "pets")]
+    fn reconcile<T: Transport + Send + Sync>(
+        &self,
+        client: &SurePet<T>,
+        changes: &[Change],
+    ) -> Result<()> {
+        if !self.reconcile {
+            return Ok(());
+        }
+        let mut failure = None;
+        for change in changes {
+            if let Change::Moved { pet_id, position } = change {
+                if let Err(error) =
+                    client
+                        .pets()
+                        .set_location(*pet_id, position.location(), position.since())
+                {
+                    failure = failure.or(Some(error));
+                }
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn raise(&mut self, alerts: &[Alert]) {
+        for alert in alerts {
+            for sink in &self.sinks {
+                if let Err(error) = sink.raise(alert) {
+                    self.errors.iter_mut().for_each(|handler| handler(&error));
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        for sink in &self.sinks {
+            if let Err(error) = sink.flush() {
+                self.errors.iter_mut().for_each(|handler| handler(&error));
+            }
+        }
+    }
+
+    fn intruder_alerts(&self, intruders: &[(DeviceId, Timestamp)]) -> Vec<Alert> {
+        let state = match &self.sync {
+            Some(sync) => sync.state(),
+            None => return Vec::new(),
+        };
+        intruders
+            .iter()
+            .map(|&(device_id, at)| Alert::Intruder {
+                device_id,
+                name: state
+                    .device(device_id)
+                    .map_or_else(|| format!("Flap {}", device_id), |d| d.name().to_string()),
+                at,
+            })
+            .collect()
+    }
+
+    fn dispatch_alerts(&mut self, alerts: &[Alert]) {
+        self.raise(alerts);
+        let state = match &self.sync {
+            Some(sync) => sync.state(),
+            None => return,
+        };
+        for alert in alerts {
+            let device = match alert.device_id().and_then(|id| state.device(id)) {
+                Some(device) => device,
+                None => continue,
+            };
+            let handlers = match alert {
+                Alert::LowWater { .. } => &mut self.low_water,
+                Alert::StaleWater { .. } => &mut self.stale_water,
+                Alert::Intruder { .. } => {
+                    self.intruder.iter_mut().for_each(|handler| handler(device));
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(level) = self.water.level(device.id()) {
+                handlers
+                    .iter_mut()
+                    .for_each(|handler| handler(device, level));
+            }
+        }
+    }
+
+    fn check_devices(&mut self, devices: &[Device<'static>], now: Timestamp) {
+        let mut alerts = self.offline.check(devices, now);
+        alerts.extend(self.battery.check(devices));
+        self.raise(&alerts);
+        for alert in alerts {
+            let device_id = match alert {
+                Alert::DeviceOffline { device_id, .. }
+                | Alert::DeviceOnline { device_id, .. }
+                | Alert::LowBattery { device_id, .. } => device_id,
+                _ => continue,
+            };
+            let device = match devices.iter().find(|d| d.id() == device_id) {
+                Some(device) => device,
+                None => continue,
+            };
+            match alert {
+                Alert::DeviceOffline { .. } => self
+                    .device_offline
+                    .iter_mut()
+                    .for_each(|handler| handler(device)),
+                Alert::DeviceOnline { .. } => self
+                    .device_online
+                    .iter_mut()
+                    .for_each(|handler| handler(device)),
+                Alert::LowBattery { percent, .. } => self
+                    .battery_low
+                    .iter_mut()
+                    .for_each(|handler| handler(device, percent)),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Watcher<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("interval", &self.interval)
+            .field("status_every", &self.status_every)
+            .field("battery", &self.battery)
+            .field("sync", &self.sync)
+            .field("polls", &self.polls)
+            .field("offline", &self.offline)
+            .field("water", &self.water)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::LogSink;
+    use crate::transport::mock::MockTransport;
+    #[cfg(feature = "pets")]
+    use crate::transport::Method;
+    use pretty_assertions::assert_eq;
+    use std::cell::RefCell;
+
+    #[test]
+    fn should_call_the_handlers_for_each_change() {
+        let start = |battery: f64| {
+            format!(
+                r#"{{"data":{{"households":[{{"id":1,"name":"Home"}}],
+                "pets":[{{"id":2,"name":"Milo","household_id":1}}],
+                "devices":[{{"id":10,"name":"Back door","product_id":6,"household_id":1,
+                             "status":{{"online":true,"battery":{}}}}},
+                           {{"id":11,"name":"Bowl","product_id":4,"household_id":1,
+                             "status":{{"online":false}},"last_activity_at":"2019-06-01T04:00:00Z"}},
+                           {{"id":12,"name":"Fountain","product_id":8,"household_id":1,
+                             "status":{{"online":true}}}}]}}}}"#,
+                battery
+            )
+        };
+        let transport = MockTransport::new()
+            .respond(200, &start(6.0))
+            .respond(
+                200,
+                r#"{"data":[{"id":7,"type":0,"created_at":"2019-06-01T05:00:00+00:00"}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[
+                {"id":11,"type":7,"created_at":"2019-06-01T06:06:00+00:00","household_id":1,
+                 "movements":[{"device_id":10,"direction":1}]},
+                {"id":10,"type":29,"created_at":"2019-06-01T06:05:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],"devices":[{"id":12,"name":"Fountain"}],
+                 "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":150.0,"change":-20.0}]}]},
+                {"id":9,"type":22,"created_at":"2019-06-01T06:02:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],
+                 "weights":[{"device_id":11,"context":1,"frames":[{"current_weight":20.5,"change":-4.5}]}]},
+                {"id":8,"type":0,"created_at":"2019-06-01T06:00:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],
+                 "movements":[{"device_id":10,"direction":1}]}
+            ]}"#,
+            )
+            .respond(200, &start(4.9))
+            .respond(200, r#"{"data":[]}"#)
+            .respond(200, &start(4.8));
+        let client = SurePet::new(&transport, "token");
+        let seen = RefCell::new(Vec::new());
+        let log = LogSink::new(Vec::new());
+        let mut watcher = Watcher::new();
+        watcher
+            .with_status_every(1)
+            .with_alert_sink(&log)
+            .on_pet_entered(|pet, _| seen.borrow_mut().push(format!("{} in", pet.name())))
+            .on_pet_exited(|pet, _| seen.borrow_mut().push(format!("{} out", pet.name())))
+            .on_feeding(|pet, visit| {
+                seen.borrow_mut()
+                    .push(format!("{} ate {}", pet.name(), -visit.change()[0]))
+            })
+            .on_device_offline(|device| {
+                seen.borrow_mut().push(format!("{} offline", device.name()))
+            })
+            .on_battery_low(|device, percent| {
+                seen.borrow_mut()
+                    .push(format!("{} at {:.0}%", device.name(), percent))
+            })
+            .on_low_water(|device, level| {
+                seen.borrow_mut()
+                    .push(format!("{} has {}ml", device.name(), level.remaining()))
+            })
+            .on_intruder(|device| {
+                seen.borrow_mut()
+                    .push(format!("Intruder at {}", device.name()))
+            });
+
+        for _ in 0..3 {
+            watcher.poll(&client).unwrap();
+        }
+
+        assert_eq!(
+            vec![
+                "Bowl offline".to_string(),
+                "Milo in".to_string(),
+                "Milo ate 4.5".to_string(),
+                "Fountain has 150ml".to_string(),
+                "Intruder at Back door".to_string(),
+                "Back door at 8%".to_string(),
+            ],
+            *seen.borrow()
+        );
+        assert_eq!(6, transport.requests().len());
+        let fountain = watcher.water().level(DeviceId::new(12)).unwrap();
+        assert!(watcher.water().is_low(fountain));
+        drop(watcher);
+        assert_eq!(
+            "Bowl is offline\nFountain is down to 150ml, top it up\n\
+             An unknown animal tried Back door\n\
+             Back door battery is at 8%, replace it soon\n",
+            String::from_utf8(log.into_inner()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "pets")]
+    #[test]
+    fn should_write_movements_back_as_positions() {
+        let transport = MockTransport::new()
+            .respond(
+                200,
+                r#"{"data":{"households":[{"id":1,"name":"Home"}],
+                "pets":[{"id":2,"name":"Milo","household_id":1,
+                         "position":{"where":1,"since":"2019-05-29T08:00:00+00:00"}}]}}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[{"id":7,"type":0,"created_at":"2019-06-01T05:00:00+00:00"}]}"#,
+            )
+            .respond(
+                200,
+                r#"{"data":[
+                {"id":9,"type":0,"created_at":"2019-06-01T06:05:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],
+                 "movements":[{"device_id":10,"direction":0}]},
+                {"id":8,"type":0,"created_at":"2019-06-01T06:00:00+00:00","household_id":1,
+                 "pets":[{"id":2,"name":"Milo"}],
+                 "movements":[{"device_id":10,"direction":2}]}
+            ]}"#,
+            )
+            .respond(200, "{}");
+        let client = SurePet::new(&transport, "token");
+        let mut watcher = Watcher::new();
+        watcher.with_location_reconciliation(true);
+
+        watcher.poll(&client).unwrap();
+        watcher.poll(&client).unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(4, requests.len());
+        assert_eq!(Method::Post, requests[3].method);
+        assert!(requests[3].url.ends_with("/api/pet/2/position"));
+        let body: serde_json::Value =
+            serde_json::from_slice(requests[3].body.as_ref().unwrap()).unwrap();
+        assert_eq!(2, body["where"]);
+    }
+}
@@ -0,0 +1,253 @@
+//! Keeping track of the water in Felaqua dispensers.
+//!
+//! Water dispensers weigh their bowl when a pet drinks and when it is
+//! refilled. A [`WaterTracker`](struct.WaterTracker.html) follows those
+//! events to know how much water is left and when it was last changed, and
+//! raises [`Alert::LowWater`](../alert/enum.Alert.html#variant.LowWater)
+//! when it runs low and
+//! [`Alert::StaleWater`](../alert/enum.Alert.html#variant.StaleWater) when it
+//! has not been changed for too long, each once until the next refill. A
+//! [`Watcher`](../watcher/struct.Watcher.html) keeps one and calls handlers
+//! for its alerts.
+use crate::alert::Alert;
+use crate::id::DeviceId;
+use crate::time::Timestamp;
+use crate::timeline::{Event, EventKind, PET_VISIT};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+/// What is known about the water in one dispenser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaterLevel {
+    device_id: DeviceId,
+    remaining: f64,
+    refilled: Option<(Timestamp, f64)>,
+    updated_at: Timestamp,
+}
+
+impl WaterLevel {
+    /// The water dispenser.
+    pub fn device_id(&self) -> DeviceId {
+        self.device_id
+    }
+
+    /// Millilitres in the bowl at the last reading.
+    pub fn remaining(&self) -> f64 {
+        self.remaining
+    }
+
+    /// When the water was last changed, if a refill has been seen.
+    pub fn refilled_at(&self) -> Option<Timestamp> {
+        self.refilled.map(|(at, _)| at)
+    }
+
+    /// Millilitres in the bowl after the last refill.
+    pub fn filled_to(&self) -> Option<f64> {
+        self.refilled.map(|(_, ml)| ml)
+    }
+
+    /// When the dispenser was last weighed.
+    pub fn updated_at(&self) -> Timestamp {
+        self.updated_at
+    }
+
+    /// How long the water has been in the bowl at an instant, `None` if no
+    /// refill has been seen.
+    pub fn age_at(&self, now: Timestamp) -> Option<Duration> {
+        self.refilled_at().map(|at| now.elapsed_since(at))
+    }
+}
+
+/// Follows water dispenser events, see the module documentation.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::water::WaterTracker;
+/// use sure_petcare::timeline::Event;
+/// use std::time::Duration;
+/// let refill: Event = serde_json::from_str(
+///     r#"{"id":1,"type":29,"created_at":"2019-06-01T08:00:00Z",
+///         "weights":[{"device_id":12,"context":4,"frames":[{"current_weight":950.0,"change":700.0}]}]}"#,
+/// )?;
+/// let mut water = WaterTracker::new(200.0).with_stale_after(Duration::from_secs(3 * 86_400));
+/// assert!(water.apply(&refill).is_empty());
+/// assert!(water.check("2019-06-03T08:00:00Z".parse().unwrap()).is_empty());
+/// let alerts = water.check("2019-06-04T09:00:00Z".parse().unwrap());
+/// assert_eq!("Water dispenser 12 has not been changed for 3 days", alerts[0].to_string());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct WaterTracker {
+    low: f64,
+    stale_after: Duration,
+    levels: BTreeMap<DeviceId, WaterLevel>,
+    names: HashMap<DeviceId, String>,
+    low_alerted: HashSet<DeviceId>,
+    stale_alerted: HashSet<DeviceId>,
+}
+
+impl Default for WaterTracker {
+    fn default() -> Self {
+        WaterTracker::new(200.0)
+    }
+}
+
+impl WaterTracker {
+    /// A tracker alerting when a dispenser holds less than `low`
+    /// millilitres, or water older than two days.
+    pub fn new(low: f64) -> Self {
+        WaterTracker {
+            low,
+            stale_after: Duration::from_secs(2 * 86_400),
+            levels: BTreeMap::new(),
+            names: HashMap::new(),
+            low_alerted: HashSet::new(),
+            stale_alerted: HashSet::new(),
+        }
+    }
+
+    /// Sets how old water can get before it counts as stale.
+    pub fn with_stale_after(mut self, age: Duration) -> Self {
+        self.stale_after = age;
+        self
+    }
+
+    /// Every dispenser seen, by device id.
+    pub fn levels(&self) -> impl Iterator<Item = &WaterLevel> {
+        self.levels.values()
+    }
+
+    /// One dispenser.
+    pub fn level(&self, device_id: DeviceId) -> Option<&WaterLevel> {
+        self.levels.get(&device_id)
+    }
+
+    /// Whether a dispenser holds less than the low threshold.
+    pub fn is_low(&self, level: &WaterLevel) -> bool {
+        level.remaining < self.low
+    }
+
+    /// Whether a dispenser's water is older than the stale age at `now`.
+    pub fn is_stale_at(&self, level: &WaterLevel, now: Timestamp) -> bool {
+        level.age_at(now).is_some_and(|age| age > self.stale_after)
+    }
+
+    /// Applies a drinking event, returning an alert for each dispenser that
+    /// has just run low. Events should be applied oldest first; readings
+    /// older than the last one for a dispenser are ignored.
+    pub fn apply(&mut self, event: &Event<'_>) -> Vec<Alert> {
+        if event.kind() != EventKind::Drinking {
+            return Vec::new();
+        }
+        for device in event.devices() {
+            self.names.insert(device.id(), device.name().to_string());
+        }
+        let at = event.created_at();
+        let mut alerts = Vec::new();
+        for entry in event.weights() {
+            let device_id = match entry.device_id() {
+                Some(device_id) => device_id,
+                None => continue,
+            };
+            let frames = entry.frames();
+            let remaining: f64 = frames.iter().map(|f| f.current_weight()).sum();
+            let change: f64 = frames.iter().map(|f| f.change()).sum();
+            let level = self.levels.entry(device_id).or_insert(WaterLevel {
+                device_id,
+                remaining,
+                refilled: None,
+                updated_at: at,
+            });
+            if at < level.updated_at {
+                continue;
+            }
+            level.remaining = remaining.max(0.0);
+            level.updated_at = at;
+            if entry.context() != PET_VISIT && change > 0.0 {
+                level.refilled = Some((at, level.remaining));
+                self.stale_alerted.remove(&device_id);
+            }
+            let level = *level;
+            if !self.is_low(&level) {
+                self.low_alerted.remove(&device_id);
+            } else if self.low_alerted.insert(device_id) {
+                alerts.push(Alert::LowWater {
+                    device_id,
+                    name: self.name(device_id),
+                    remaining: level.remaining,
+                });
+            }
+        }
+        alerts
+    }
+
+    /// Returns an alert for each dispenser whose water has just gone stale
+    /// at `now`.
+    pub fn check(&mut self, now: Timestamp) -> Vec<Alert> {
+        let stale: Vec<WaterLevel> = self
+            .levels
+            .values()
+            .filter(|level| self.is_stale_at(level, now))
+            .filter(|level| !self.stale_alerted.contains(&level.device_id))
+            .copied()
+            .collect();
+        stale
+            .into_iter()
+            .filter_map(|level| {
+                self.stale_alerted.insert(level.device_id);
+                Some(Alert::StaleWater {
+                    device_id: level.device_id,
+                    name: self.name(level.device_id),
+                    refilled_at: level.refilled_at()?,
+                    age: level.age_at(now)?.as_secs() as i64,
+                })
+            })
+            .collect()
+    }
+
+    fn name(&self, device_id: DeviceId) -> String {
+        self.names
+            .get(&device_id)
+            .cloned()
+            .unwrap_or_else(|| format!("Water dispenser {}", device_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn should_alert_on_low_and_stale_water_once_per_refill() {
+        let events: Vec<Event> = serde_json::from_str(
+            r#"[
+            {"id":1,"type":29,"created_at":"2019-06-01T08:00:00Z",
+             "devices":[{"id":12,"name":"Fountain"}],
+             "weights":[{"device_id":12,"context":4,"frames":[{"current_weight":400.0,"change":300.0}]}]},
+            {"id":2,"type":29,"created_at":"2019-06-01T12:00:00Z",
+             "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":190.0,"change":-210.0}]}]},
+            {"id":3,"type":29,"created_at":"2019-06-01T13:00:00Z",
+             "weights":[{"device_id":12,"context":1,"frames":[{"current_weight":150.0,"change":-40.0}]}]},
+            {"id":4,"type":22,"created_at":"2019-06-01T14:00:00Z",
+             "weights":[{"device_id":11,"context":4,"frames":[{"current_weight":50.0,"change":50.0}]}]}
+        ]"#,
+        )
+        .unwrap();
+        let mut water = WaterTracker::new(200.0);
+
+        let low: Vec<Alert> = events.iter().flat_map(|event| water.apply(event)).collect();
+        assert_eq!(vec!["Fountain is down to 190ml, top it up".to_string()], {
+            low.iter().map(Alert::to_string).collect::<Vec<_>>()
+        });
+        let level = *water.level(DeviceId::new(12)).unwrap();
+        assert_eq!(150.0, level.remaining());
+        assert!(water.is_low(&level));
+        assert_eq!(1, water.levels().count());
+
+        let later: Timestamp = "2019-06-03T09:00:00Z".parse().unwrap();
+        assert!(water.is_stale_at(&level, later));
+        assert_eq!(1, water.check(later).len());
+        assert!(water.check(later).is_empty());
+    }
+}
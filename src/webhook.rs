@@ -0,0 +1,247 @@
+//! Forwarding of new timeline events to a webhook.
+//!
+//! Each event is POSTed as JSON, exactly as the timeline returned it. The
+//! sink is also an [`AlertSink`](../alert/trait.AlertSink.html), posting
+//! alerts as JSON with their text in `message`. When a
+//! secret is set, the body is signed with HMAC-SHA256 and the hex digest is
+//! sent in the `X-SurePet-Signature` header as `sha256=<digest>`, so the
+//! receiver can check the request came from its own forwarder.
+use crate::alert::{self, Alert, AlertSink};
+use crate::error::{Error, Result};
+use crate::hmac;
+use crate::secret::Secret;
+use crate::timeline::Event;
+use crate::transport::{self, Method, Transport};
+use std::fmt;
+use std::time::Duration;
+
+/// The header carrying the payload signature.
+pub const SIGNATURE_HEADER: &str = "X-SurePet-Signature";
+
+/// A builder for a `WebhookSink`.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::webhook::WebhookSinkBuilder;
+/// use std::time::Duration;
+/// # struct Http;
+/// # impl sure_petcare::transport::Transport for Http {
+/// #     fn send(&self, _: &sure_petcare::transport::Request) -> sure_petcare::error::Result<sure_petcare::transport::Response> { unimplemented!() }
+/// # }
+/// let sink = WebhookSinkBuilder::new()
+///     .with_url("https://example.com/hooks/surepet")
+///     .with_secret("s3cret")
+///     .with_retries(5)
+///     .with_backoff(Duration::from_secs(2))
+///     .build(Http);
+/// ```
+#[derive(Clone)]
+pub struct WebhookSinkBuilder {
+    url: String,
+    secret: Option<String>,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl fmt::Debug for WebhookSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebhookSinkBuilder")
+            .field("url", &self.url)
+            .field("secret", &self.secret.as_ref().map(Secret))
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .finish()
+    }
+}
+
+impl Default for WebhookSinkBuilder {
+    fn default() -> Self {
+        WebhookSinkBuilder {
+            url: String::new(),
+            secret: None,
+            retries: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl WebhookSinkBuilder {
+    /// Create a new builder retrying three times, one second apart at first.
+    pub fn new() -> Self {
+        WebhookSinkBuilder::default()
+    }
+
+    /// Sets the url events are posted to.
+    pub fn with_url<S: Into<String>>(&mut self, url: S) -> &mut Self {
+        self.url = url.into();
+        self
+    }
+
+    /// Sets the secret payloads are signed with.
+    pub fn with_secret<S: Into<String>>(&mut self, secret: S) -> &mut Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    /// Sets how many times a failed delivery is retried.
+    pub fn with_retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the wait before the first retry; it doubles with every attempt.
+    pub fn with_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Builds a sink posting through `transport`.
+    pub fn build<T: Transport>(&self, transport: T) -> WebhookSink<T> {
+        WebhookSink {
+            transport,
+            config: self.clone(),
+            last_id: None,
+        }
+    }
+}
+
+/// Posts timeline events to a webhook, see the module documentation.
+#[derive(Debug)]
+pub struct WebhookSink<T> {
+    transport: T,
+    config: WebhookSinkBuilder,
+    last_id: Option<i64>,
+}
+
+impl<T: Transport> WebhookSink<T> {
+    /// Posts the events not forwarded before, oldest first, and returns how
+    /// many were sent. Stops at the first event that could not be delivered,
+    /// so it is sent again on the next call.
+    pub fn forward(&mut self, events: &[Event<'_>]) -> Result<usize> {
+        let mut new: Vec<&Event> = events
+            .iter()
+            .filter(|event| self.last_id.is_none_or(|last_id| event.id() > last_id))
+            .collect();
+        new.sort_by_key(|event| event.id());
+
+        for event in &new {
+            self.send(event)?;
+            self.last_id = Some(event.id());
+        }
+        Ok(new.len())
+    }
+
+    /// Posts a single event, retrying on transport failures and server
+    /// errors. Client errors are not retried.
+    pub fn send(&self, event: &Event<'_>) -> Result<()> {
+        self.post(serde_json::to_vec(event)?)
+    }
+
+    fn post(&self, body: Vec<u8>) -> Result<()> {
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        if let Some(secret) = &self.config.secret {
+            let signature = hmac::hex(&hmac::hmac_sha256(secret.as_bytes(), &body));
+            headers.push((SIGNATURE_HEADER.into(), format!("sha256={}", signature)));
+        }
+        let request = transport::Request {
+            method: Method::Post,
+            url: self.config.url.clone(),
+            headers,
+            body: Some(body),
+            max_body_size: None,
+        };
+
+        let mut backoff = self.config.backoff;
+        let mut attempt = 0;
+        loop {
+            let error = match self.transport.send(&request) {
+                Ok(response) if response.is_success() => return Ok(()),
+                Ok(response) => {
+                    let error = Error::Status {
+                        status: response.status,
+                        body: String::from_utf8_lossy(&response.body).into_owned(),
+                    };
+                    if response.status < 500 {
+                        return Err(error);
+                    }
+                    error
+                }
+                Err(error) => error,
+            };
+            if attempt >= self.config.retries {
+                return Err(error);
+            }
+            attempt += 1;
+            std::thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+}
+
+impl<T: Transport> AlertSink for WebhookSink<T> {
+    /// Posts the alert, retrying like [`send`](#method.send).
+    fn raise(&self, alert: &Alert) -> Result<()> {
+        self.post(alert::payload(alert)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock::MockTransport;
+    use pretty_assertions::assert_eq;
+
+    fn events() -> Vec<Event<'static>> {
+        serde_json::from_str(
+            r#"[{"id":2,"type":0,"created_at":"2019-06-01T05:00:00+00:00"},
+                {"id":1,"type":0,"created_at":"2019-06-01T04:00:00+00:00"}]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn should_forward_new_events_signed() {
+        let transport = MockTransport::new()
+            .respond(200, "")
+            .respond(200, "")
+            .respond(200, "");
+        let mut sink = WebhookSinkBuilder::new()
+            .with_url("https://example.com/hook")
+            .with_secret("s3cret")
+            .build(&transport);
+
+        assert_eq!(2, sink.forward(&events()).unwrap());
+        assert_eq!(0, sink.forward(&events()).unwrap());
+
+        let requests = transport.requests();
+        assert_eq!(2, requests.len());
+        assert_eq!("https://example.com/hook", requests[0].url);
+        let body = requests[0].body.as_ref().unwrap();
+        let event: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(1, event["id"]);
+        assert_eq!(
+            Some(format!("sha256={}", hmac::hex(&hmac::hmac_sha256(b"s3cret", body))).as_str()),
+            requests[0].header(SIGNATURE_HEADER)
+        );
+    }
+
+    #[test]
+    fn should_retry_server_errors_only() {
+        let transport = MockTransport::new()
+            .respond(503, "busy")
+            .respond(200, "")
+            .respond(400, "bad");
+        let sink = WebhookSinkBuilder::new()
+            .with_backoff(Duration::from_millis(0))
+            .build(&transport);
+        let events = events();
+
+        sink.send(&events[0]).unwrap();
+        match sink.send(&events[0]) {
+            Err(Error::Status { status: 400, .. }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(3, transport.requests().len());
+        assert_eq!(None, transport.requests()[2].header(SIGNATURE_HEADER));
+    }
+}
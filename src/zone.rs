@@ -0,0 +1,358 @@
+//! Household time zones, for working with curfew times.
+//!
+//! Curfews are wall-clock times in the household's zone, so the instant a
+//! flap locks moves with daylight saving time. A [`Zone`](struct.Zone.html)
+//! holds the offset rules of one zone, written as a POSIX `TZ` string such
+//! as `GMT0BST,M3.5.0/1,M10.5.0`, and converts between instants and local
+//! times. [`Zone::named`](struct.Zone.html#method.named) reads the rules of
+//! an IANA zone such as `Europe/London` from the system's zoneinfo files.
+//! Only the current rules are known, which is what scheduling needs.
+use crate::time::{self, Date, TimeOfDay, Timestamp};
+use std::fmt;
+use std::path::PathBuf;
+
+/// The offset rules of a time zone.
+/// # Examples
+///
+/// ```
+/// use sure_petcare::zone::Zone;
+/// let london: Zone = "GMT0BST,M3.5.0/1,M10.5.0".parse()?;
+/// assert_eq!(0, london.offset_at("2019-01-15T12:00:00Z".parse().unwrap()));
+/// assert_eq!(3600, london.offset_at("2019-07-15T12:00:00Z".parse().unwrap()));
+/// # Ok::<(), sure_petcare::zone::ZoneError>(())
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Zone {
+    offset: i64,
+    dst: Option<Dst>,
+}
+
+/// Daylight saving time: its offset and when it starts and ends.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Dst {
+    offset: i64,
+    start: Transition,
+    end: Transition,
+}
+
+/// A yearly change of offset, at a local time on a day of the year.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Transition {
+    day: Day,
+    seconds: i64,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Day {
+    /// `Jn`: day 1 to 365, never counting February 29.
+    Julian(i64),
+    /// `n`: day 0 to 365, counting February 29.
+    Ordinal(i64),
+    /// `Mm.w.d`: weekday `d` (0 is Sunday) of week `w` of month `m`, week 5
+    /// being the last.
+    Weekday { month: i64, week: i64, weekday: i64 },
+}
+
+impl Zone {
+    /// Coordinated universal time.
+    pub fn utc() -> Self {
+        Zone::fixed(0)
+    }
+
+    /// A zone that is always `seconds` east of UTC.
+    pub fn fixed(seconds: i64) -> Self {
+        Zone {
+            offset: seconds,
+            dst: None,
+        }
+    }
+
+    /// Reads the current rules of an IANA zone, such as `Europe/London`,
+    /// from the zoneinfo files under `$TZDIR` or `/usr/share/zoneinfo`.
+    pub fn named(name: &str) -> Result<Self, ZoneError> {
+        let error = || ZoneError { input: name.into() };
+        if name.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(error());
+        }
+        let dir = std::env::var_os("TZDIR")
+            .map_or_else(|| PathBuf::from("/usr/share/zoneinfo"), PathBuf::from);
+        let data = std::fs::read(dir.join(name)).map_err(|_| error())?;
+        // Version 2 and later files end with the rules as a TZ string on a
+        // line of its own.
+        if !data.starts_with(b"TZif") || data.get(4).is_none_or(|v| *v < b'2') {
+            return Err(error());
+        }
+        let footer = data
+            .strip_suffix(b"\n")
+            .and_then(|data| data.rsplit(|b| *b == b'\n').next())
+            .and_then(|line| std::str::from_utf8(line).ok())
+            .ok_or_else(error)?;
+        footer.parse().map_err(|_| error())
+    }
+
+    /// The seconds the zone is ahead of UTC at `instant`.
+    pub fn offset_at(&self, instant: Timestamp) -> i64 {
+        match &self.dst {
+            Some(dst) if dst.contains(self.offset, instant.unix()) => dst.offset,
+            _ => self.offset,
+        }
+    }
+
+    /// The local date and time of day at `instant`, to the minute.
+    pub fn local(&self, instant: Timestamp) -> (Date, TimeOfDay) {
+        let local = instant.unix() + self.offset_at(instant);
+        let date = Timestamp::from_unix(local).date();
+        let minutes = local.rem_euclid(86_400) / 60;
+        let time = TimeOfDay::new((minutes / 60) as u8, (minutes % 60) as u8)
+            .expect("minutes of a day make a valid time");
+        (date, time)
+    }
+
+    /// The instant a local date and time happens. A time repeated when the
+    /// clocks go back is taken the first time round; a time skipped when
+    /// they go forward is taken as the instant the clocks changed plus the
+    /// time past it, the way a clock left on the old offset would read it.
+    pub fn resolve(&self, date: Date, time: TimeOfDay) -> Timestamp {
+        let local = date.midnight().unix() + i64::from(time.minutes()) * 60;
+        let offsets = match &self.dst {
+            Some(dst) => vec![self.offset, dst.offset],
+            None => vec![self.offset],
+        };
+        offsets
+            .iter()
+            .map(|offset| local - offset)
+            .filter(|utc| local - self.offset_at(Timestamp::from_unix(*utc)) == *utc)
+            .min()
+            .map_or_else(
+                || Timestamp::from_unix(local - offsets.iter().min().copied().unwrap_or(0)),
+                Timestamp::from_unix,
+            )
+    }
+}
+
+impl Dst {
+    /// Whether daylight saving time is in effect at `utc`.
+    fn contains(&self, standard: i64, utc: i64) -> bool {
+        let year = Timestamp::from_unix(utc + standard).date().year() as i64;
+        // The start is given in standard time and the end in daylight time.
+        let start = self.start.local(year) - standard;
+        let end = self.end.local(year) - self.offset;
+        if start <= end {
+            start <= utc && utc < end
+        } else {
+            utc < end || start <= utc
+        }
+    }
+}
+
+impl Transition {
+    /// Local seconds since the epoch at which the transition happens in
+    /// `year`.
+    fn local(&self, year: i64) -> i64 {
+        let jan1 = time::days_from_civil(year, 1, 1);
+        let leap = time::days_from_civil(year, 3, 1) - time::days_from_civil(year, 2, 1) == 29;
+        let days = match self.day {
+            Day::Julian(n) => jan1 + n - 1 + i64::from(leap && n >= 60),
+            Day::Ordinal(n) => jan1 + n,
+            Day::Weekday {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = time::days_from_civil(year, month, 1);
+                let next_month = if month == 12 {
+                    time::days_from_civil(year + 1, 1, 1)
+                } else {
+                    time::days_from_civil(year, month + 1, 1)
+                };
+                // 1970-01-01 was a Thursday.
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first + (weekday - first_weekday).rem_euclid(7) + 7 * (week - 1);
+                while day >= next_month {
+                    day -= 7;
+                }
+                day
+            }
+        };
+        days * 86_400 + self.seconds
+    }
+}
+
+impl std::str::FromStr for Zone {
+    type Err = ZoneError;
+
+    /// Parses a POSIX `TZ` string. Daylight saving time without rules
+    /// follows the United States rules, as POSIX specifies.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s).ok_or_else(|| ZoneError { input: s.into() })
+    }
+}
+
+fn parse(s: &str) -> Option<Zone> {
+    let mut rest = s.trim();
+    name(&mut rest)?;
+    // TZ strings give the offset west of UTC.
+    let offset = -clock(&mut rest, 24)?;
+    if rest.is_empty() {
+        return Some(Zone::fixed(offset));
+    }
+    name(&mut rest)?;
+    let dst_offset = if rest.starts_with(',') || rest.is_empty() {
+        offset + 3600
+    } else {
+        -clock(&mut rest, 24)?
+    };
+    let rules = if rest.is_empty() {
+        "M3.2.0,M11.1.0"
+    } else {
+        rest.strip_prefix(',')?
+    };
+    let (start, end) = rules.split_once(',')?;
+    Some(Zone {
+        offset,
+        dst: Some(Dst {
+            offset: dst_offset,
+            start: transition(start)?,
+            end: transition(end)?,
+        }),
+    })
+}
+
+/// Skips an abbreviation: three or more letters, or anything in angle
+/// brackets such as `<+0530>`.
+fn name(rest: &mut &str) -> Option<()> {
+    let len = match rest.strip_prefix('<') {
+        Some(inner) => inner.find('>')? + 2,
+        None => rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len()),
+    };
+    if len < 3 {
+        return None;
+    }
+    *rest = &rest[len..];
+    Some(())
+}
+
+/// Reads `[+-]hh[:mm[:ss]]` as seconds, hours being at most `max_hours`.
+fn clock(rest: &mut &str, max_hours: i64) -> Option<i64> {
+    let len = rest
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, ':' | '+' | '-')))
+        .unwrap_or(rest.len());
+    let (text, tail) = rest.split_at(len);
+    *rest = tail;
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => (-1, text),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let mut parts = text.split(':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+    let seconds: i64 = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+    if parts.next().is_some() || hours > max_hours || minutes > 59 || seconds > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+/// Reads `date[/time]`, the time defaulting to 02:00.
+fn transition(s: &str) -> Option<Transition> {
+    let (date, time) = match s.split_once('/') {
+        Some((date, time)) => {
+            let mut time = time;
+            let seconds = clock(&mut time, 167)?;
+            if !time.is_empty() {
+                return None;
+            }
+            (date, seconds)
+        }
+        None => (s, 7200),
+    };
+    let number = |s: &str| s.parse::<i64>().ok();
+    let day = if let Some(weekday) = date.strip_prefix('M') {
+        let mut parts = weekday.split('.').map(number);
+        let (month, week, weekday) = (parts.next()??, parts.next()??, parts.next()??);
+        if parts.next().is_some()
+            || !(1..=12).contains(&month)
+            || !(1..=5).contains(&week)
+            || !(0..=6).contains(&weekday)
+        {
+            return None;
+        }
+        Day::Weekday {
+            month,
+            week,
+            weekday,
+        }
+    } else if let Some(day) = date.strip_prefix('J') {
+        Day::Julian(number(day).filter(|day| (1..=365).contains(day))?)
+    } else {
+        Day::Ordinal(number(date).filter(|day| (0..=365).contains(day))?)
+    };
+    Some(Transition { day, seconds: time })
+}
+
+/// The error returned when a time zone cannot be parsed or found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ZoneError {
+    input: String,
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown or invalid time zone `{}`", self.input)
+    }
+}
+
+impl std::error::Error for ZoneError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn at(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn should_follow_daylight_saving_rules() {
+        let london: Zone = "GMT0BST,M3.5.0/1,M10.5.0".parse().unwrap();
+        let sydney: Zone = "AEST-10AEDT,M10.1.0,M4.1.0/3".parse().unwrap();
+
+        // The clocks went forward at 01:00 UTC on 2019-03-31.
+        assert_eq!(0, london.offset_at(at("2019-03-31T00:59:59Z")));
+        assert_eq!(3600, london.offset_at(at("2019-03-31T01:00:00Z")));
+        assert_eq!(0, london.offset_at(at("2019-10-27T01:00:00Z")));
+        assert_eq!(39_600, sydney.offset_at(at("2019-01-15T00:00:00Z")));
+        assert_eq!(36_000, sydney.offset_at(at("2019-07-15T00:00:00Z")));
+        assert_eq!(Zone::fixed(19_800), "<+0530>-5:30".parse::<Zone>().unwrap());
+        assert!("GMT".parse::<Zone>().is_err());
+        assert!("GMT0BST,M13.5.0,M10.5.0".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn should_resolve_skipped_and_repeated_times() {
+        let london: Zone = "GMT0BST,M3.5.0/1,M10.5.0".parse().unwrap();
+        let time = |s: &str| s.parse::<TimeOfDay>().unwrap();
+
+        let spring = Date::new(2019, 3, 31).unwrap();
+        assert_eq!(
+            at("2019-03-31T01:30:00Z"),
+            london.resolve(spring, time("01:30"))
+        );
+        assert_eq!(
+            at("2019-03-31T02:30:00Z"),
+            london.resolve(spring, time("03:30"))
+        );
+        let autumn = Date::new(2019, 10, 27).unwrap();
+        assert_eq!(
+            at("2019-10-27T00:30:00Z"),
+            london.resolve(autumn, time("01:30"))
+        );
+        assert_eq!(
+            (autumn, time("01:30")),
+            london.local(at("2019-10-27T01:30:00Z"))
+        );
+    }
+}
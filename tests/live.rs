@@ -0,0 +1,40 @@
+//! Runs the read only endpoints against the real API. Enabled with the
+//! `live-tests` feature and skipped unless credentials are set, see
+//! `sure_petcare::live`.
+#![cfg(feature = "live-tests")]
+use sure_petcare::live::{self, Credentials};
+use sure_petcare::transport::curl::CurlTransport;
+
+#[test]
+fn should_match_the_live_api() {
+    let credentials = match Credentials::from_env() {
+        Some(credentials) => credentials,
+        None => {
+            eprintln!("skipping: set SUREPET_TOKEN or SUREPET_EMAIL and SUREPET_PASSWORD");
+            return;
+        }
+    };
+    let outcomes = live::run(CurlTransport::new(), &credentials).expect("login failed");
+    for outcome in &outcomes {
+        println!("{}", outcome);
+    }
+    let failed: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| !outcome.is_compatible())
+        .map(|outcome| outcome.endpoint())
+        .collect();
+    assert!(
+        failed.is_empty(),
+        "incompatible responses from {:?}",
+        failed
+    );
+    if std::env::var_os("SUREPET_STRICT").is_some() {
+        let drifted = outcomes.iter().any(|outcome| {
+            outcome
+                .result()
+                .as_ref()
+                .is_ok_and(|diffs| !diffs.is_empty())
+        });
+        assert!(!drifted, "the models differ from the live API");
+    }
+}